@@ -1,4 +1,10 @@
 fn main() {
+    // stb is only used for reading and writing images, which allows building without a C
+    // toolchain for the target, for example for wasm32-unknown-unknown
+    if std::env::var_os("CARGO_FEATURE_IO").is_none() {
+        return;
+    }
+
     cc::Build::new()
         .file("stb/stb.c")
         .flag_if_supported("-Wno-unused-parameter")
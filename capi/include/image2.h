@@ -0,0 +1,122 @@
+#ifndef IMAGE2_H
+#define IMAGE2_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// An image handle
+typedef struct image2_t image2_t;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// The message of the last error that occurred on the current thread, or `NULL`. The string is
+// valid until the next failing call.
+const char *image2_last_error(void);
+
+// Create a new, transparent image
+struct image2_t *image2_new(size_t width, size_t height);
+
+// Create an image by copying `width * height * 4` bytes of RGBA data
+//
+// # Safety
+// `data` must point to at least `width * height * 4` bytes
+struct image2_t *image2_from_rgba(size_t width, size_t height, const uint8_t *data);
+
+// Read an image from disk
+//
+// # Safety
+// `filename` must be NULL or point to a NUL-terminated string
+struct image2_t *image2_open(const char *filename);
+
+// Write an image to disk, the format is determined by the file extension
+//
+// # Safety
+// `image` must be NULL or a handle returned by this library that hasn't been freed, and
+// `filename` must be NULL or point to a NUL-terminated string
+int image2_save(const struct image2_t *image, const char *filename);
+
+// Copy an image
+//
+// # Safety
+// `image` must be NULL or a handle returned by this library that hasn't been freed
+struct image2_t *image2_clone(const struct image2_t *image);
+
+// Free an image, passing `NULL` is allowed
+//
+// # Safety
+// `image` must be NULL or a handle returned by this library that hasn't been freed yet, it
+// can't be used after this call
+void image2_free(struct image2_t *image);
+
+// Image width in pixels
+//
+// # Safety
+// `image` must be NULL or a handle returned by this library that hasn't been freed
+size_t image2_width(const struct image2_t *image);
+
+// Image height in pixels
+//
+// # Safety
+// `image` must be NULL or a handle returned by this library that hasn't been freed
+size_t image2_height(const struct image2_t *image);
+
+// Pointer to the RGBA pixel data, which is `width * height * 4` bytes long. The pointer is
+// invalidated by any function that changes the size of the image.
+//
+// # Safety
+// `image` must be NULL or a handle returned by this library that hasn't been freed
+uint8_t *image2_data(struct image2_t *image);
+
+// Resize the image, if one of the dimensions is 0 it is calculated to preserve the aspect ratio
+//
+// # Safety
+// `image` must be NULL or a handle returned by this library that hasn't been freed
+int image2_resize(struct image2_t *image, size_t width, size_t height);
+
+// Crop the image to the given region, which must lie inside of the image
+//
+// # Safety
+// `image` must be NULL or a handle returned by this library that hasn't been freed
+int image2_crop(struct image2_t *image, size_t x, size_t y, size_t width, size_t height);
+
+// Rotate the image clockwise by 90, 180 or 270 degrees
+//
+// # Safety
+// `image` must be NULL or a handle returned by this library that hasn't been freed
+int image2_rotate(struct image2_t *image, int degrees);
+
+// Convert color channels to grayscale, the alpha channel is preserved
+//
+// # Safety
+// `image` must be NULL or a handle returned by this library that hasn't been freed
+int image2_grayscale(struct image2_t *image);
+
+// Invert color channels, the alpha channel is preserved
+//
+// # Safety
+// `image` must be NULL or a handle returned by this library that hasn't been freed
+int image2_invert(struct image2_t *image);
+
+// Apply a 5x5 gaussian blur
+//
+// # Safety
+// `image` must be NULL or a handle returned by this library that hasn't been freed
+int image2_blur(struct image2_t *image);
+
+// Convolve the image with a kernel of `rows * cols` values stored in row-major order
+//
+// # Safety
+// `image` must be NULL or a handle returned by this library that hasn't been freed, and `kernel`
+// must point to at least `rows * cols` values
+int image2_convolve(struct image2_t *image, const double *kernel, size_t rows, size_t cols);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* IMAGE2_H */
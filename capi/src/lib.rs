@@ -0,0 +1,379 @@
+//! C API for `image2`
+//!
+//! Images are exposed as opaque `image2_t` handles wrapping an 8-bit RGBA `Image2`. Functions
+//! that can fail return `NULL` or `-1` and store a message that can be retrieved using
+//! `image2_last_error`. The header in `include/image2.h` is generated by `cbindgen` when the
+//! crate is built.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+use image2::{kernel::Kernel, Error, Image, Image2};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// An image handle
+pub struct Handle(Image2);
+
+fn set_error(err: Error) {
+    let message = CString::new(err.to_string().replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+/// Convert a C string to a path
+///
+/// # Safety
+/// `path` must be NULL or point to a NUL-terminated string
+unsafe fn path<'a>(path: *const c_char) -> Result<&'a str, Error> {
+    if path.is_null() {
+        return Err(Error::Message(String::from("Path is NULL")));
+    }
+
+    CStr::from_ptr(path)
+        .to_str()
+        .map_err(|_| Error::Message(String::from("Path is not valid UTF-8")))
+}
+
+fn handle(image: Result<Image2, Error>) -> *mut Handle {
+    match image {
+        Ok(image) => Box::into_raw(Box::new(Handle(image))),
+        Err(err) => {
+            set_error(err);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Number of bytes of RGBA data in an image of the given size
+fn rgba_len(width: usize, height: usize) -> Result<usize, Error> {
+    width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(4))
+        .ok_or_else(|| Error::Message(format!("Image size {}x{} is too large", width, height)))
+}
+
+/// Replace the image stored in `image` with the result of `f`, returning an error without
+/// modifying the image when `check` fails
+///
+/// # Safety
+/// `image` must be NULL or a handle that hasn't been freed
+unsafe fn update<F: FnOnce(Image2) -> Image2>(
+    image: *mut Handle,
+    check: impl FnOnce(&Image2) -> Result<(), Error>,
+    f: F,
+) -> c_int {
+    let image = match image.as_mut() {
+        Some(image) => image,
+        None => {
+            set_error(Error::Message(String::from("Image is NULL")));
+            return -1;
+        }
+    };
+
+    if let Err(err) = check(&image.0) {
+        set_error(err);
+        return -1;
+    }
+
+    let current = std::mem::replace(&mut image.0, Image2::new(0, 0));
+    image.0 = f(current);
+    0
+}
+
+fn ok(_: &Image2) -> Result<(), Error> {
+    Ok(())
+}
+
+/// The message of the last error that occurred on the current thread, or `NULL`. The string is
+/// valid until the next failing call.
+#[no_mangle]
+pub extern "C" fn image2_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| match &*e.borrow() {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+/// Create a new, transparent image
+#[no_mangle]
+pub extern "C" fn image2_new(width: usize, height: usize) -> *mut Handle {
+    handle(rgba_len(width, height).map(|_| Image2::new(width, height)))
+}
+
+/// Create an image by copying `width * height * 4` bytes of RGBA data
+///
+/// # Safety
+/// `data` must point to at least `width * height * 4` bytes
+#[no_mangle]
+pub unsafe extern "C" fn image2_from_rgba(
+    width: usize,
+    height: usize,
+    data: *const u8,
+) -> *mut Handle {
+    if data.is_null() {
+        return handle(Err(Error::Message(String::from("Data is NULL"))));
+    }
+
+    let len = match rgba_len(width, height) {
+        Ok(len) => len,
+        Err(err) => return handle(Err(err)),
+    };
+    let mut image = Image2::new(width, height);
+    image
+        .as_image_buf_mut()
+        .data_mut()
+        .copy_from_slice(std::slice::from_raw_parts(data, len));
+    handle(Ok(image))
+}
+
+/// Read an image from disk
+///
+/// # Safety
+/// `filename` must be NULL or point to a NUL-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn image2_open(filename: *const c_char) -> *mut Handle {
+    handle(path(filename).and_then(Image2::open))
+}
+
+/// Write an image to disk, the format is determined by the file extension
+///
+/// # Safety
+/// `image` must be NULL or a handle returned by this library that hasn't been freed, and
+/// `filename` must be NULL or point to a NUL-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn image2_save(image: *const Handle, filename: *const c_char) -> c_int {
+    let image = match image.as_ref() {
+        Some(image) => image,
+        None => {
+            set_error(Error::Message(String::from("Image is NULL")));
+            return -1;
+        }
+    };
+
+    match path(filename).and_then(|p| image.0.save(p)) {
+        Ok(()) => 0,
+        Err(err) => {
+            set_error(err);
+            -1
+        }
+    }
+}
+
+/// Copy an image
+///
+/// # Safety
+/// `image` must be NULL or a handle returned by this library that hasn't been freed
+#[no_mangle]
+pub unsafe extern "C" fn image2_clone(image: *const Handle) -> *mut Handle {
+    match image.as_ref() {
+        Some(image) => handle(Ok(image.0.clone())),
+        None => handle(Err(Error::Message(String::from("Image is NULL")))),
+    }
+}
+
+/// Free an image, passing `NULL` is allowed
+///
+/// # Safety
+/// `image` must be NULL or a handle returned by this library that hasn't been freed yet, it
+/// can't be used after this call
+#[no_mangle]
+pub unsafe extern "C" fn image2_free(image: *mut Handle) {
+    if !image.is_null() {
+        drop(Box::from_raw(image));
+    }
+}
+
+/// Image width in pixels
+///
+/// # Safety
+/// `image` must be NULL or a handle returned by this library that hasn't been freed
+#[no_mangle]
+pub unsafe extern "C" fn image2_width(image: *const Handle) -> usize {
+    image.as_ref().map_or(0, |image| image.0.width())
+}
+
+/// Image height in pixels
+///
+/// # Safety
+/// `image` must be NULL or a handle returned by this library that hasn't been freed
+#[no_mangle]
+pub unsafe extern "C" fn image2_height(image: *const Handle) -> usize {
+    image.as_ref().map_or(0, |image| image.0.height())
+}
+
+/// Pointer to the RGBA pixel data, which is `width * height * 4` bytes long. The pointer is
+/// invalidated by any function that changes the size of the image.
+///
+/// # Safety
+/// `image` must be NULL or a handle returned by this library that hasn't been freed
+#[no_mangle]
+pub unsafe extern "C" fn image2_data(image: *mut Handle) -> *mut u8 {
+    match image.as_mut() {
+        Some(image) => image.0.as_image_buf_mut().data_mut().as_mut_ptr(),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Resize the image, if one of the dimensions is 0 it is calculated to preserve the aspect ratio
+///
+/// # Safety
+/// `image` must be NULL or a handle returned by this library that hasn't been freed
+#[no_mangle]
+pub unsafe extern "C" fn image2_resize(image: *mut Handle, width: usize, height: usize) -> c_int {
+    update(image, ok, |image| image.resize(width, height))
+}
+
+/// Crop the image to the given region, which must lie inside of the image
+///
+/// # Safety
+/// `image` must be NULL or a handle returned by this library that hasn't been freed
+#[no_mangle]
+pub unsafe extern "C" fn image2_crop(
+    image: *mut Handle,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+) -> c_int {
+    update(
+        image,
+        |image| {
+            let right = x.checked_add(width).filter(|&r| r <= image.width());
+            let bottom = y.checked_add(height).filter(|&b| b <= image.height());
+            if right.is_none() || bottom.is_none() {
+                return Err(Error::Message(format!(
+                    "Crop region {}x{}+{}+{} is outside of the {}x{} image",
+                    width,
+                    height,
+                    x,
+                    y,
+                    image.width(),
+                    image.height()
+                )));
+            }
+            Ok(())
+        },
+        |image| image.crop(x, y, width, height),
+    )
+}
+
+/// Rotate the image clockwise by 90, 180 or 270 degrees
+///
+/// # Safety
+/// `image` must be NULL or a handle returned by this library that hasn't been freed
+#[no_mangle]
+pub unsafe extern "C" fn image2_rotate(image: *mut Handle, degrees: c_int) -> c_int {
+    let degrees = degrees.rem_euclid(360);
+    update(
+        image,
+        |_| match degrees {
+            0 | 90 | 180 | 270 => Ok(()),
+            _ => Err(Error::Message(format!(
+                "Invalid rotation: {} degrees",
+                degrees
+            ))),
+        },
+        |image| match degrees {
+            90 => image.rotate90(),
+            180 => image.rotate180(),
+            270 => image.rotate270(),
+            _ => image,
+        },
+    )
+}
+
+/// Convert color channels to grayscale, the alpha channel is preserved
+///
+/// # Safety
+/// `image` must be NULL or a handle returned by this library that hasn't been freed
+#[no_mangle]
+pub unsafe extern "C" fn image2_grayscale(image: *mut Handle) -> c_int {
+    update(image, ok, |image| image.grayscale())
+}
+
+/// Invert color channels, the alpha channel is preserved
+///
+/// # Safety
+/// `image` must be NULL or a handle returned by this library that hasn't been freed
+#[no_mangle]
+pub unsafe extern "C" fn image2_invert(image: *mut Handle) -> c_int {
+    update(image, ok, |image| image.invert())
+}
+
+/// Apply a 5x5 gaussian blur
+///
+/// # Safety
+/// `image` must be NULL or a handle returned by this library that hasn't been freed
+#[no_mangle]
+pub unsafe extern "C" fn image2_blur(image: *mut Handle) -> c_int {
+    update(image, ok, |image| image.blur())
+}
+
+/// Convolve the image with a kernel of `rows * cols` values stored in row-major order
+///
+/// # Safety
+/// `image` must be NULL or a handle returned by this library that hasn't been freed, and `kernel`
+/// must point to at least `rows * cols` values
+#[no_mangle]
+pub unsafe extern "C" fn image2_convolve(
+    image: *mut Handle,
+    kernel: *const f64,
+    rows: usize,
+    cols: usize,
+) -> c_int {
+    let len = match rows.checked_mul(cols) {
+        Some(len) if !kernel.is_null() && len > 0 => len,
+        _ => {
+            set_error(Error::Message(String::from("Invalid kernel")));
+            return -1;
+        }
+    };
+
+    let data = std::slice::from_raw_parts(kernel, len);
+    let kernel = Kernel::create(rows, cols, |i, j| data[j * cols + i]);
+    update(image, ok, |image| image.filter(&kernel))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_capi() {
+        unsafe { test_handles() }
+    }
+
+    unsafe fn test_handles() {
+        let image = image2_new(4, 2);
+        assert_eq!(image2_width(image), 4);
+
+        assert_eq!(image2_rotate(image, 90), 0);
+        assert_eq!(image2_width(image), 2);
+        assert_eq!(image2_height(image), 4);
+
+        assert_eq!(image2_crop(image, 1, 1, 4, 4), -1);
+        let message = CStr::from_ptr(image2_last_error());
+        assert!(message.to_str().unwrap().starts_with("Crop region"));
+        assert_eq!(image2_width(image), 2);
+
+        let kernel = [0.0, 1.0, 0.0];
+        assert_eq!(image2_convolve(image, kernel.as_ptr(), 1, 3), 0);
+        assert_eq!(image2_resize(image, 4, 0), 0);
+        assert_eq!(image2_height(image), 8);
+
+        assert!(image2_open(ptr::null()).is_null());
+
+        // Sizes that overflow are errors instead of panics
+        assert_eq!(image2_crop(image, usize::MAX, 0, 2, 1), -1);
+        assert_eq!(image2_crop(image, 0, 1, 1, usize::MAX), -1);
+        assert!(image2_new(usize::MAX, 2).is_null());
+        let data = [0u8; 4];
+        assert!(image2_from_rgba(usize::MAX, usize::MAX, data.as_ptr()).is_null());
+        assert_eq!(image2_convolve(image, kernel.as_ptr(), usize::MAX, 2), -1);
+        image2_free(image);
+    }
+}
@@ -11,5 +11,7 @@ bindgen  \
   --whitelist-function stbi_write_bmp \
   --whitelist-function stbi_write_hdr \
   --whitelist-function stbi_write_png_to_mem \
+  --whitelist-function stbi_load_gif_from_memory \
+  --whitelist-function stbi_image_free \
   --raw-line  "#![allow(non_camel_case_types)]" \
   stb/stb.c > src/io/stb.rs
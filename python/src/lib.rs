@@ -0,0 +1,306 @@
+//! Python bindings for `image2`
+//!
+//! Image data is stored in a C-contiguous numpy array with the shape `(height, width,
+//! channels)`, using `uint8`, `uint16` or `float32` values and 1 (gray), 3 (RGB) or 4 (RGBA)
+//! channels. The array is shared with Python instead of being copied: `Image.to_numpy` returns
+//! it directly and `Image` implements the buffer protocol by exporting it.
+//!
+//! Operations only borrow the array for reading, so read-only arrays and arrays that are borrowed
+//! elsewhere can be used. `image2` images need mutable data, so the operations work on a copy of
+//! the array and return a new image.
+
+use std::os::raw::c_int;
+
+use image2::color::{Color, Gray, Rgb, Rgba};
+use image2::{io, kernel, transform, Filter, Image as _, ImageBuf, Kernel, Type};
+use numpy::ndarray::Array3;
+use numpy::PyUntypedArrayMethods;
+use numpy::{dtype, Element, PyArray3, PyArrayDescrMethods, PyArrayMethods, PyUntypedArray};
+use pyo3::exceptions::{PyBufferError, PyIOError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::{ffi, IntoPyObjectExt};
+
+fn error(err: image2::Error) -> PyErr {
+    match err.inner() {
+        image2::Error::IO(_) | image2::Error::Command { .. } => PyIOError::new_err(err.to_string()),
+        _ => PyValueError::new_err(err.to_string()),
+    }
+}
+
+/// Move an image into a new numpy array without copying
+fn into_numpy<T: Type + Element, C: Color>(
+    py: Python<'_>,
+    image: ImageBuf<T, C>,
+) -> Py<PyUntypedArray> {
+    let (width, height, channels) = image.shape();
+    let array = Array3::from_shape_vec((height, width, channels), image.inner())
+        .expect("Image data does not match its shape");
+    PyArray3::from_owned_array(py, array)
+        .into_any()
+        .cast_into::<PyUntypedArray>()
+        .expect("Array is not a numpy array")
+        .unbind()
+}
+
+/// Evaluate `$body` with `$image` bound to an `ImageBuf` containing a copy of the data of `$array`,
+/// using the element type and color matching its dtype and number of channels
+macro_rules! with_image {
+    ($array:expr, |$image:ident| $body:expr) => {{
+        let array: &Bound<'_, PyUntypedArray> = $array;
+        let py = array.py();
+        let shape = array.shape().to_vec();
+        let descr = array.dtype();
+        if descr.is_equiv_to(&dtype::<u8>(py)) {
+            with_image!(@color array, u8, shape, |$image| $body)
+        } else if descr.is_equiv_to(&dtype::<u16>(py)) {
+            with_image!(@color array, u16, shape, |$image| $body)
+        } else if descr.is_equiv_to(&dtype::<f32>(py)) {
+            with_image!(@color array, f32, shape, |$image| $body)
+        } else {
+            Err(PyValueError::new_err("Unsupported dtype"))
+        }
+    }};
+    (@color $array:expr, $t:ty, $shape:expr, |$image:ident| $body:expr) => {
+        match $shape[2] {
+            1 => with_image!(@image $array, $t, Gray, $shape, |$image| $body),
+            3 => with_image!(@image $array, $t, Rgb, $shape, |$image| $body),
+            4 => with_image!(@image $array, $t, Rgba, $shape, |$image| $body),
+            n => Err(PyValueError::new_err(format!("Unsupported number of channels: {}", n))),
+        }
+    };
+    (@image $array:expr, $t:ty, $c:ty, $shape:expr, |$image:ident| $body:expr) => {{
+        let array = $array.cast::<PyArray3<$t>>()?;
+        let data = array.try_readonly()?;
+        let $image: ImageBuf<$t, $c> =
+            ImageBuf::new_from($shape[1], $shape[0], data.as_slice()?.to_vec());
+        $body
+    }};
+}
+
+/// Check that the dtype and number of channels of an array are supported
+fn check_array(array: &Bound<'_, PyUntypedArray>) -> PyResult<()> {
+    let py = array.py();
+    let descr = array.dtype();
+    if ![dtype::<u8>(py), dtype::<u16>(py), dtype::<f32>(py)]
+        .iter()
+        .any(|d| descr.is_equiv_to(d))
+    {
+        return Err(PyValueError::new_err("Unsupported dtype"));
+    }
+
+    match array.shape()[2] {
+        1 | 3 | 4 => Ok(()),
+        n => Err(PyValueError::new_err(format!(
+            "Unsupported number of channels: {}",
+            n
+        ))),
+    }
+}
+
+/// Create an image with the given element type and color by calling `$f::<T, C>`
+macro_rules! with_type {
+    ($dtype:expr, $channels:expr, $f:ident($($arg:expr),*)) => {
+        match ($dtype, $channels) {
+            ("uint8", 1) => $f::<u8, Gray>($($arg),*),
+            ("uint8", 3) => $f::<u8, Rgb>($($arg),*),
+            ("uint8", 4) => $f::<u8, Rgba>($($arg),*),
+            ("uint16", 1) => $f::<u16, Gray>($($arg),*),
+            ("uint16", 3) => $f::<u16, Rgb>($($arg),*),
+            ("uint16", 4) => $f::<u16, Rgba>($($arg),*),
+            ("float32", 1) => $f::<f32, Gray>($($arg),*),
+            ("float32", 3) => $f::<f32, Rgb>($($arg),*),
+            ("float32", 4) => $f::<f32, Rgba>($($arg),*),
+            (dtype, channels) => Err(PyValueError::new_err(format!(
+                "Unsupported image type: {} with {} channels",
+                dtype, channels
+            ))),
+        }
+    };
+}
+
+fn new_image<T: Type + Element, C: Color>(
+    py: Python<'_>,
+    width: usize,
+    height: usize,
+) -> PyResult<Py<PyUntypedArray>> {
+    Ok(into_numpy(py, ImageBuf::<T, C>::new(width, height)))
+}
+
+fn read_image<T: Type + Element, C: Color>(
+    py: Python<'_>,
+    path: &str,
+) -> PyResult<Py<PyUntypedArray>> {
+    let image: ImageBuf<T, C> = io::read(path).map_err(error)?;
+    Ok(into_numpy(py, image))
+}
+
+/// An image backed by a numpy array
+#[pyclass(name = "Image", module = "image2")]
+struct PyImage {
+    array: Py<PyUntypedArray>,
+}
+
+impl PyImage {
+    /// Apply a filter that keeps the size, type and color of the image
+    fn filter<F: Filter>(&self, py: Python<'_>, filter: &F) -> PyResult<PyImage> {
+        let array = with_image!(self.array.bind(py), |image| {
+            let mut dest = image.new_like();
+            filter.eval(&mut dest, &[&image]);
+            Ok(into_numpy(py, dest))
+        })?;
+        Ok(PyImage { array })
+    }
+}
+
+#[pymethods]
+impl PyImage {
+    /// Create a new, zeroed image
+    #[new]
+    #[pyo3(signature = (width, height, channels = 3, dtype = "uint8"))]
+    fn new(
+        py: Python<'_>,
+        width: usize,
+        height: usize,
+        channels: usize,
+        dtype: &str,
+    ) -> PyResult<PyImage> {
+        let array = with_type!(dtype, channels, new_image(py, width, height))?;
+        Ok(PyImage { array })
+    }
+
+    /// Wrap a numpy array with the shape `(height, width)` or `(height, width, channels)`, the
+    /// data is only copied when the array is not C-contiguous
+    #[staticmethod]
+    fn from_numpy(py: Python<'_>, array: &Bound<'_, PyAny>) -> PyResult<PyImage> {
+        let array = array.cast::<PyUntypedArray>()?;
+        let array = match array.ndim() {
+            2 => array.call_method1("reshape", ((array.shape()[0], array.shape()[1], 1),))?,
+            3 => array.clone().into_any(),
+            n => {
+                return Err(PyValueError::new_err(format!(
+                    "Expected an array with 2 or 3 dimensions, got {}",
+                    n
+                )))
+            }
+        };
+        let array = array.cast_into::<PyUntypedArray>()?;
+        let array = if array.is_c_contiguous() {
+            array
+        } else {
+            py.import("numpy")?
+                .call_method1("ascontiguousarray", (array,))?
+                .cast_into::<PyUntypedArray>()?
+        };
+
+        check_array(&array)?;
+        Ok(PyImage {
+            array: array.unbind(),
+        })
+    }
+
+    /// The underlying numpy array, modifying it modifies the image
+    fn to_numpy(&self, py: Python<'_>) -> Py<PyUntypedArray> {
+        self.array.clone_ref(py)
+    }
+
+    /// Read an image from disk
+    #[staticmethod]
+    #[pyo3(signature = (path, channels = 3, dtype = "uint8"))]
+    fn read(py: Python<'_>, path: &str, channels: usize, dtype: &str) -> PyResult<PyImage> {
+        let array = with_type!(dtype, channels, read_image(py, path))?;
+        Ok(PyImage { array })
+    }
+
+    /// Write an image to disk, the format is determined by the file extension
+    fn write(&self, py: Python<'_>, path: &str) -> PyResult<()> {
+        with_image!(self.array.bind(py), |image| io::write(path, &image)
+            .map_err(error))
+    }
+
+    #[getter]
+    fn width(&self, py: Python<'_>) -> usize {
+        self.array.bind(py).shape()[1]
+    }
+
+    #[getter]
+    fn height(&self, py: Python<'_>) -> usize {
+        self.array.bind(py).shape()[0]
+    }
+
+    #[getter]
+    fn channels(&self, py: Python<'_>) -> usize {
+        self.array.bind(py).shape()[2]
+    }
+
+    #[getter]
+    fn dtype(&self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        self.array.bind(py).dtype().into_py_any(py)
+    }
+
+    /// Resize to the given width and height
+    fn resize(&self, py: Python<'_>, width: usize, height: usize) -> PyResult<PyImage> {
+        let array = with_image!(self.array.bind(py), |image| {
+            let mut dest = ImageBuf::new(width, height);
+            transform::resize(&mut dest, &image, width, height);
+            Ok(into_numpy(py, dest))
+        })?;
+        Ok(PyImage { array })
+    }
+
+    /// Convolve with a kernel given as a list of rows
+    fn convolve(&self, py: Python<'_>, kernel: Vec<Vec<f64>>) -> PyResult<PyImage> {
+        let rows = kernel.len();
+        let cols = kernel.first().map_or(0, |row| row.len());
+        if rows == 0 || cols == 0 || kernel.iter().any(|row| row.len() != cols) {
+            return Err(PyValueError::new_err(
+                "Kernel rows must have the same, non-zero length",
+            ));
+        }
+
+        let kernel = Kernel::create(rows, cols, |i, j| kernel[j][i]);
+        self.filter(py, &kernel)
+    }
+
+    /// Apply a 5x5 gaussian blur
+    fn blur(&self, py: Python<'_>) -> PyResult<PyImage> {
+        self.filter(py, &kernel::gaussian_5x5())
+    }
+
+    /// Invert all channels
+    fn invert(&self, py: Python<'_>) -> PyResult<PyImage> {
+        self.filter(py, &image2::filter::Invert)
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> String {
+        let array = self.array.bind(py);
+        format!(
+            "Image(width={}, height={}, channels={}, dtype={})",
+            array.shape()[1],
+            array.shape()[0],
+            array.shape()[2],
+            array.dtype()
+        )
+    }
+
+    /// Export the underlying array, so `memoryview(image)` and `numpy.asarray(image)` share
+    /// its data
+    unsafe fn __getbuffer__(
+        slf: Bound<'_, Self>,
+        view: *mut ffi::Py_buffer,
+        flags: c_int,
+    ) -> PyResult<()> {
+        let py = slf.py();
+        let array = slf.borrow().array.clone_ref(py);
+        if unsafe { ffi::PyObject_GetBuffer(array.as_ptr(), view, flags) } != 0 {
+            return Err(PyErr::take(py)
+                .unwrap_or_else(|| PyBufferError::new_err("Unable to export buffer")));
+        }
+        Ok(())
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "image2")]
+fn init(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyImage>()
+}
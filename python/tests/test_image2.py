@@ -0,0 +1,56 @@
+import numpy as np
+import pytest
+
+from image2 import Image
+
+
+def test_numpy_round_trip():
+    array = np.arange(4 * 3 * 3, dtype=np.uint8).reshape((4, 3, 3))
+    image = Image.from_numpy(array)
+    assert (image.width, image.height, image.channels) == (3, 4, 3)
+    assert image.dtype == np.uint8
+
+    # The array is shared, not copied
+    out = image.to_numpy()
+    assert out is array
+    out[0, 0, 0] = 200
+    assert image.to_numpy()[0, 0, 0] == 200
+
+    # 2D arrays are gray images and non-contiguous arrays are copied
+    gray = Image.from_numpy(np.zeros((5, 6), dtype=np.float32))
+    assert (gray.width, gray.height, gray.channels) == (6, 5, 1)
+    strided = Image.from_numpy(array[:, ::2])
+    assert strided.width == 2
+    assert np.array_equal(strided.to_numpy(), array[:, ::2])
+
+    with pytest.raises(ValueError):
+        Image.from_numpy(np.zeros((2, 2, 2), dtype=np.uint8))
+    with pytest.raises(ValueError):
+        Image.from_numpy(np.zeros((2, 2, 3), dtype=np.int64))
+
+
+def test_read_only_array():
+    array = np.full((8, 8, 3), 10, dtype=np.uint8)
+    array.flags.writeable = False
+    image = Image.from_numpy(array)
+
+    inverted = image.invert()
+    assert np.all(inverted.to_numpy() == 245)
+    assert image.blur().to_numpy().shape == (8, 8, 3)
+    assert image.convolve([[0, 0, 0], [0, 1, 0], [0, 0, 0]]).to_numpy()[4, 4, 0] == 10
+    assert image.resize(4, 2).to_numpy().shape == (2, 4, 3)
+    assert np.all(array == 10)
+
+
+def test_buffer_protocol():
+    image = Image(4, 2, channels=4, dtype="uint16")
+    view = memoryview(image)
+    assert view.shape == (2, 4, 4)
+    assert view.format == "H"
+    assert view.nbytes == 2 * 4 * 4 * 2
+
+    # Views share the data of the image
+    shared = np.asarray(image)
+    shared[1, 3, 2] = 1000
+    assert image.to_numpy()[1, 3, 2] == 1000
+    assert memoryview(image).tolist()[1][3][2] == 1000
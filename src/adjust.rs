@@ -0,0 +1,167 @@
+//! Automatic tonal adjustments
+
+use alloc::vec::Vec;
+
+use crate::color::Color;
+use crate::feature::luminance;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::stats::Histogram;
+use crate::ty::Type;
+
+/// Default percentage of the darkest and brightest values clipped by `auto_contrast`
+pub const DEFAULT_CLIP: f64 = 0.5;
+
+/// Number of histogram bins used to find the clipping points
+const BINS: usize = 4096;
+
+/// Number of channels that hold color information, the alpha channel is never adjusted
+fn color_channels<C: Color>() -> usize {
+    if C::has_alpha() {
+        C::channels() - 1
+    } else {
+        C::channels()
+    }
+}
+
+/// The normalized range that remains after clipping `clip_percent` percent of the values of
+/// channel `c` from each end, returns `None` for empty or flat channels. The bin edges are
+/// narrowed to `extent`, the smallest and largest value of the channel, so nothing is lost to
+/// the bin width when nothing is clipped
+fn clip_range(
+    hist: &Histogram,
+    c: usize,
+    extent: (f64, f64),
+    clip_percent: f64,
+) -> Option<(f64, f64)> {
+    let total = hist.total(c) as f64;
+    let threshold = total * clip_percent.clamp(0.0, 50.0) / 100.0;
+    let cumulative = hist.cumulative(c);
+    let low = cumulative.iter().position(|&x| x as f64 > threshold)?;
+    let high = cumulative
+        .iter()
+        .position(|&x| x as f64 >= total - threshold)?;
+    let low = (low as f64 / BINS as f64).max(extent.0);
+    let high = ((high + 1) as f64 / BINS as f64).min(extent.1);
+    if high - low < 1e-9 {
+        return None;
+    }
+
+    Some((low, high))
+}
+
+/// Stretch the color channels of `image` using one normalized `(low, high)` range per channel,
+/// channels without a range are copied unchanged
+fn stretch<T: Type, C: Color, I: Image<T, C>>(
+    image: &I,
+    ranges: &[Option<(f64, f64)>],
+) -> ImageBuf<T, C> {
+    let mut dest = ImageBuf::new(image.width(), image.height());
+    dest.copy_metadata(image);
+    for (d, px) in dest
+        .data_mut()
+        .chunks_exact_mut(C::channels())
+        .zip(image.data().chunks_exact(C::channels()))
+    {
+        for (c, (d, x)) in d.iter_mut().zip(px).enumerate() {
+            *d = match ranges.get(c) {
+                Some(Some((low, high))) => T::from_f((x.to_f() - low) / (high - low)),
+                _ => *x,
+            };
+        }
+    }
+    dest
+}
+
+/// Stretch each color channel independently so the darkest `clip_percent` percent of its values
+/// become black and the brightest `clip_percent` percent become the maximum value. Since every
+/// channel is stretched separately this also removes color casts. Flat channels and the alpha
+/// channel are left unchanged.
+pub fn auto_levels<T: Type, C: Color, I: Image<T, C>>(
+    image: &I,
+    clip_percent: f64,
+) -> ImageBuf<T, C> {
+    let channels = color_channels::<C>();
+    let mut hist = Histogram::new(channels, BINS, 0.0, 1.0);
+    let mut extents = vec![(1.0f64, 0.0f64); channels];
+    for px in image.data().chunks_exact(C::channels()) {
+        for (c, x) in px.iter().take(channels).enumerate() {
+            let x = x.to_f().clamp(0.0, 1.0);
+            hist.add(c, x);
+            extents[c] = (extents[c].0.min(x), extents[c].1.max(x));
+        }
+    }
+
+    let ranges: Vec<_> = (0..channels)
+        .map(|c| clip_range(&hist, c, extents[c], clip_percent))
+        .collect();
+    stretch(image, &ranges)
+}
+
+/// Stretch the luminance of `image` to the full range, clipping `DEFAULT_CLIP` percent of the
+/// darkest and brightest pixels. Unlike `auto_levels` every channel is scaled by the same amount
+/// so hues are preserved.
+pub fn auto_contrast<T: Type, C: Color, I: Image<T, C>>(image: &I) -> ImageBuf<T, C> {
+    auto_contrast_with(image, DEFAULT_CLIP)
+}
+
+/// Like `auto_contrast`, clipping `clip_percent` percent of the pixels at each end
+pub fn auto_contrast_with<T: Type, C: Color, I: Image<T, C>>(
+    image: &I,
+    clip_percent: f64,
+) -> ImageBuf<T, C> {
+    let mut hist = Histogram::new(1, BINS, 0.0, 1.0);
+    let mut extent = (1.0f64, 0.0f64);
+    for l in luminance(image) {
+        let l = l.clamp(0.0, 1.0);
+        hist.add(0, l);
+        extent = (extent.0.min(l), extent.1.max(l));
+    }
+
+    let range = clip_range(&hist, 0, extent, clip_percent);
+    stretch(image, &vec![range; color_channels::<C>()])
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{adjust, Gray, Image, ImageBuf, Rgb, Rgba};
+
+    #[test]
+    fn test_auto_levels() {
+        // A flat scan using only the middle of the range, with a blue cast
+        let image: ImageBuf<u8, Rgb> =
+            ImageBuf::from_fn(100, 1, |x, _| [64 + x as u8, 64 + x as u8, 128 + x as u8]);
+        let output = adjust::auto_levels(&image, 0.0);
+        assert_eq!(output.at(0, 0), &[0, 0, 0]);
+        assert_eq!(output.at(99, 0), &[255, 255, 255]);
+        assert!((output.get(50, 0, 0) as i32 - 128).abs() <= 2);
+        assert!((output.get(50, 0, 0) as i32 - output.get(50, 0, 2) as i32).abs() <= 1);
+
+        // Clipping ignores outliers
+        let mut image: ImageBuf<f32, Gray> =
+            ImageBuf::from_fn(100, 1, |x, _| [0.4 + x as f32 * 0.002]);
+        image.set(0, 0, 0, 0.0);
+        image.set(99, 0, 0, 1.0);
+        let output = adjust::auto_levels(&image, 1.0);
+        assert!(output.get(1, 0, 0) < 0.01);
+        assert!(output.get(98, 0, 0) > 0.99);
+
+        // Flat images and alpha are left alone
+        let image: ImageBuf<u8, Rgba> = ImageBuf::new_with(4, 4, [10, 20, 30, 40]);
+        assert_eq!(adjust::auto_levels(&image, 0.5), image);
+    }
+
+    #[test]
+    fn test_auto_contrast() {
+        let image: ImageBuf<f32, Rgb> =
+            ImageBuf::from_fn(100, 1, |x, _| [0.25 + x as f32 * 0.0025, 0.25, 0.25]);
+        let output = adjust::auto_contrast_with(&image, 0.0);
+        let (min, max) = (output.at(0, 0), output.at(99, 0));
+
+        // The same scale is applied to every channel, so green and blue stay equal to each other
+        assert_eq!(min[1], min[2]);
+        assert!(max[0] > image.get(99, 0, 0));
+        assert!(min[0] < image.get(0, 0, 0));
+        assert_eq!(adjust::auto_contrast(&image).shape(), image.shape());
+    }
+}
@@ -0,0 +1,166 @@
+use alloc::vec::Vec;
+
+use crate::analysis::Connectivity;
+use crate::color::{Color, Gray};
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::rect::Rect;
+use crate::ty::Type;
+
+/// Statistics about a single connected component
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Component {
+    /// Label used for the component in the label image
+    pub label: u32,
+    /// Number of pixels
+    pub area: usize,
+    /// Bounding box
+    pub bbox: Rect,
+    /// Center of mass
+    pub centroid: (f64, f64),
+}
+
+fn find(parent: &mut [u32], mut x: u32) -> u32 {
+    while parent[x as usize] != x {
+        parent[x as usize] = parent[parent[x as usize] as usize];
+        x = parent[x as usize];
+    }
+    x
+}
+
+fn union(parent: &mut [u32], a: u32, b: u32) -> u32 {
+    let a = find(parent, a);
+    let b = find(parent, b);
+    let (lo, hi) = if a < b { (a, b) } else { (b, a) };
+    parent[hi as usize] = lo;
+    lo
+}
+
+/// Label the connected regions of a binary image. Pixels with a non-zero first channel are
+/// treated as foreground. Returns an image containing the label of each pixel, where 0 is
+/// background and components are numbered from 1 in raster order, along with statistics for
+/// each component.
+pub fn connected_components<T: Type, C: Color, I: Image<T, C>>(
+    image: &I,
+    connectivity: Connectivity,
+) -> (ImageBuf<u32, Gray>, Vec<Component>) {
+    let (width, height) = (image.width(), image.height());
+    let mut labels: ImageBuf<u32, Gray> = ImageBuf::new(width, height);
+    let mut parent = vec![0u32];
+
+    // First pass: assign provisional labels and record equivalences
+    {
+        let data = labels.data_mut();
+        for y in 0..height {
+            for x in 0..width {
+                if image.at(x, y)[0] == T::zero() {
+                    continue;
+                }
+
+                let mut neighbors = [0u32; 4];
+                if x > 0 {
+                    neighbors[0] = data[y * width + x - 1];
+                }
+                if y > 0 {
+                    neighbors[1] = data[(y - 1) * width + x];
+                    if connectivity == Connectivity::Eight {
+                        if x > 0 {
+                            neighbors[2] = data[(y - 1) * width + x - 1];
+                        }
+                        if x + 1 < width {
+                            neighbors[3] = data[(y - 1) * width + x + 1];
+                        }
+                    }
+                }
+
+                let mut label = 0;
+                for &n in neighbors.iter().filter(|n| **n != 0) {
+                    label = if label == 0 {
+                        find(&mut parent, n)
+                    } else {
+                        union(&mut parent, label, n)
+                    };
+                }
+
+                if label == 0 {
+                    label = parent.len() as u32;
+                    parent.push(label);
+                }
+
+                data[y * width + x] = label;
+            }
+        }
+    }
+
+    // Second pass: resolve equivalences, renumber labels and collect statistics
+    let mut index = vec![0u32; parent.len()];
+    let mut components: Vec<Component> = Vec::new();
+    let mut sums: Vec<(f64, f64)> = Vec::new();
+    let data = labels.data_mut();
+    for y in 0..height {
+        for x in 0..width {
+            let label = data[y * width + x];
+            if label == 0 {
+                continue;
+            }
+
+            let root = find(&mut parent, label) as usize;
+            if index[root] == 0 {
+                components.push(Component {
+                    label: components.len() as u32 + 1,
+                    area: 0,
+                    bbox: Rect::point(x, y),
+                    centroid: (0.0, 0.0),
+                });
+                sums.push((0.0, 0.0));
+                index[root] = components.len() as u32;
+            }
+
+            let label = index[root];
+            let c = &mut components[label as usize - 1];
+            c.area += 1;
+            c.bbox = c.bbox.union(&Rect::point(x, y));
+            let s = &mut sums[label as usize - 1];
+            s.0 += x as f64;
+            s.1 += y as f64;
+            data[y * width + x] = label;
+        }
+    }
+
+    for (c, (sx, sy)) in components.iter_mut().zip(sums) {
+        c.centroid = (sx / c.area as f64, sy / c.area as f64);
+    }
+
+    (labels, components)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::analysis::{connected_components, Connectivity};
+    use crate::{Gray, Image, ImageBuf, Rect};
+
+    #[test]
+    fn test_connected_components() {
+        #[rustfmt::skip]
+        let image: ImageBuf<u8, Gray> = ImageBuf::new_from(5, 4, vec![
+            1, 1, 0, 0, 1,
+            0, 1, 0, 1, 1,
+            0, 0, 1, 0, 0,
+            1, 0, 0, 0, 1,
+        ]);
+
+        let (labels, components) = connected_components(&image, Connectivity::Four);
+        assert_eq!(components.len(), 5);
+        assert_eq!(components[0].area, 3);
+        assert_eq!(components[0].bbox, Rect::new(0, 0, 2, 2));
+        assert_eq!(labels.at(4, 1)[0], 2);
+
+        let (labels, components) = connected_components(&image, Connectivity::Eight);
+        assert_eq!(components.len(), 3);
+        assert_eq!(components[0].area, 7);
+        assert_eq!(components[0].bbox, Rect::new(0, 0, 5, 3));
+        assert_eq!(labels.at(2, 2)[0], 1);
+        assert_eq!(components[1].centroid, (0.0, 3.0));
+    }
+}
@@ -0,0 +1,298 @@
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::color::Color;
+use crate::image::Image;
+use crate::rect::Rect;
+use crate::ty::Type;
+
+/// Offsets (dx, dy) of the 8 neighbors of a pixel in clockwise order, starting from the right
+const NEIGHBORS: [(isize, isize); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+
+/// A border of a connected region
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contour {
+    /// Points along the border as (x, y) coordinates
+    pub points: Vec<(usize, usize)>,
+    /// Index of the enclosing contour
+    pub parent: Option<usize>,
+    /// True if the contour is the border of a hole rather than the outer border of a region
+    pub is_hole: bool,
+}
+
+impl Contour {
+    /// The bounding box of the contour
+    pub fn bbox(&self) -> Rect {
+        self.points
+            .iter()
+            .fold(Rect::default(), |r, (x, y)| r.union(&Rect::point(*x, *y)))
+    }
+
+    /// The area enclosed by the contour, computed using the shoelace formula
+    pub fn area(&self) -> f64 {
+        let n = self.points.len();
+        let mut sum = 0.0;
+        for i in 0..n {
+            let (x0, y0) = self.points[i];
+            let (x1, y1) = self.points[(i + 1) % n];
+            sum += x0 as f64 * y1 as f64 - x1 as f64 * y0 as f64;
+        }
+        sum.abs() / 2.0
+    }
+
+    /// The length of the closed contour
+    pub fn perimeter(&self) -> f64 {
+        let n = self.points.len();
+        (0..n)
+            .map(|i| distance(self.points[i], self.points[(i + 1) % n]))
+            .sum()
+    }
+
+    /// Simplify the contour using the Douglas-Peucker algorithm
+    pub fn simplify(&self, epsilon: f64) -> Vec<(usize, usize)> {
+        simplify_closed(&self.points, epsilon)
+    }
+}
+
+fn distance(a: (usize, usize), b: (usize, usize)) -> f64 {
+    let dx = a.0 as f64 - b.0 as f64;
+    let dy = a.1 as f64 - b.1 as f64;
+    (dx * dx + dy * dy).sqrt()
+}
+
+/// Distance from `p` to the line through `a` and `b`
+fn line_distance(p: (usize, usize), a: (usize, usize), b: (usize, usize)) -> f64 {
+    let len = distance(a, b);
+    if len == 0.0 {
+        return distance(p, a);
+    }
+
+    let (px, py) = (p.0 as f64, p.1 as f64);
+    let (ax, ay) = (a.0 as f64, a.1 as f64);
+    let (bx, by) = (b.0 as f64, b.1 as f64);
+    ((bx - ax) * (ay - py) - (ax - px) * (by - ay)).abs() / len
+}
+
+fn douglas_peucker(points: &[(usize, usize)], epsilon: f64, dest: &mut Vec<(usize, usize)>) {
+    let last = points.len() - 1;
+    let (index, max) = points[1..last]
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (i + 1, line_distance(*p, points[0], points[last])))
+        .fold((0, 0.0), |a, b| if b.1 > a.1 { b } else { a });
+
+    if max > epsilon {
+        douglas_peucker(&points[..=index], epsilon, dest);
+        douglas_peucker(&points[index..], epsilon, dest);
+    } else {
+        dest.push(points[last]);
+    }
+}
+
+/// Simplify an open polyline using the Douglas-Peucker algorithm, points further than `epsilon`
+/// from the simplified line are kept
+pub fn simplify(points: &[(usize, usize)], epsilon: f64) -> Vec<(usize, usize)> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+
+    let mut dest = vec![points[0]];
+    douglas_peucker(points, epsilon, &mut dest);
+    dest
+}
+
+/// Simplify a closed polygon using the Douglas-Peucker algorithm
+pub fn simplify_closed(points: &[(usize, usize)], epsilon: f64) -> Vec<(usize, usize)> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+
+    // Split the polygon at the point furthest from the first point
+    let (split, _) = points
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (i, distance(*p, points[0])))
+        .fold((0, 0.0), |a, b| if b.1 > a.1 { b } else { a });
+    if split == 0 {
+        return vec![points[0]];
+    }
+
+    let mut ring = points.to_vec();
+    ring.push(points[0]);
+    let mut dest = simplify(&ring[..=split], epsilon);
+    dest.extend(simplify(&ring[split..], epsilon).into_iter().skip(1));
+    dest.pop();
+    dest
+}
+
+/// Find the borders of all regions in a binary image using the Suzuki-Abe border following
+/// algorithm. Pixels with a non-zero first channel are treated as foreground. Each contour
+/// stores the index of the contour that encloses it: the parent of an outer border is the hole
+/// it is inside of, and the parent of a hole is the outer border of the region containing it.
+pub fn find_contours<T: Type, C: Color, I: Image<T, C>>(image: &I) -> Vec<Contour> {
+    // Add a one pixel frame of background around the image
+    let width = image.width() + 2;
+    let height = image.height() + 2;
+    let mut f = vec![0i64; width * height];
+    for y in 0..image.height() {
+        for x in 0..image.width() {
+            if image.at(x, y)[0] != T::zero() {
+                f[(y + 1) * width + x + 1] = 1;
+            }
+        }
+    }
+
+    let index = |x: usize, y: usize, d: usize| -> usize {
+        let (dx, dy) = NEIGHBORS[d];
+        (y as isize + dy) as usize * width + (x as isize + dx) as usize
+    };
+
+    let mut contours: Vec<Contour> = Vec::new();
+
+    // Border numbers start at 2, the frame has number 1
+    let mut nbd = 1i64;
+    for y in 1..height - 1 {
+        let mut lnbd = 1i64;
+        for x in 1..width - 1 {
+            let i = y * width + x;
+            let start_dir = if f[i] == 1 && f[i - 1] == 0 {
+                Some((4, false))
+            } else if f[i] >= 1 && f[i + 1] == 0 {
+                if f[i] > 1 {
+                    lnbd = f[i];
+                }
+                Some((0, true))
+            } else {
+                None
+            };
+
+            if let Some((dir, is_hole)) = start_dir {
+                nbd += 1;
+
+                let parent = if lnbd <= 1 {
+                    None
+                } else {
+                    let prev = &contours[lnbd as usize - 2];
+                    if prev.is_hole == is_hole {
+                        prev.parent
+                    } else {
+                        Some(lnbd as usize - 2)
+                    }
+                };
+
+                let mut points = Vec::new();
+
+                // Search clockwise for the first non-zero neighbor
+                let first = (0..8)
+                    .map(|k| (dir + k) % 8)
+                    .find(|d| f[index(x, y, *d)] != 0);
+                match first {
+                    None => {
+                        f[i] = -nbd;
+                        points.push((x - 1, y - 1));
+                    }
+                    Some(d1) => {
+                        let p1 = index(x, y, d1);
+                        let (mut x3, mut y3) = (x, y);
+                        let mut prev_dir = d1;
+                        loop {
+                            points.push((x3 - 1, y3 - 1));
+
+                            // Search counterclockwise starting after the previous point
+                            let mut right_is_zero = false;
+                            let mut d4 = prev_dir;
+                            for k in 1..=8 {
+                                let d = (prev_dir + 8 - k) % 8;
+                                if f[index(x3, y3, d)] != 0 {
+                                    d4 = d;
+                                    break;
+                                }
+                                if d == 0 {
+                                    right_is_zero = true;
+                                }
+                            }
+
+                            let i3 = y3 * width + x3;
+                            if right_is_zero {
+                                f[i3] = -nbd;
+                            } else if f[i3] == 1 {
+                                f[i3] = nbd;
+                            }
+
+                            let i4 = index(x3, y3, d4);
+                            if i4 == i && i3 == p1 {
+                                break;
+                            }
+
+                            // Direction from the next point back to the current one
+                            prev_dir = (d4 + 4) % 8;
+                            let (dx, dy) = NEIGHBORS[d4];
+                            x3 = (x3 as isize + dx) as usize;
+                            y3 = (y3 as isize + dy) as usize;
+                        }
+                    }
+                }
+
+                contours.push(Contour {
+                    points,
+                    parent,
+                    is_hole,
+                });
+            }
+
+            if f[i] != 0 && f[i] != 1 {
+                lnbd = f[i].abs();
+            }
+        }
+    }
+
+    contours
+}
+
+#[cfg(test)]
+mod test {
+    use crate::analysis::{find_contours, simplify_closed};
+    use crate::{Gray, ImageBuf, Rect};
+
+    #[test]
+    fn test_find_contours() {
+        #[rustfmt::skip]
+        let image: ImageBuf<u8, Gray> = ImageBuf::new_from(7, 6, vec![
+            1, 1, 1, 1, 1, 0, 0,
+            1, 0, 0, 0, 1, 0, 0,
+            1, 0, 1, 0, 1, 0, 1,
+            1, 0, 0, 0, 1, 0, 0,
+            1, 1, 1, 1, 1, 0, 0,
+            0, 0, 0, 0, 0, 0, 0,
+        ]);
+
+        let contours = find_contours(&image);
+        assert_eq!(contours.len(), 4);
+        assert!(!contours[0].is_hole);
+        assert_eq!(contours[0].parent, None);
+        assert_eq!(contours[0].bbox(), Rect::new(0, 0, 5, 5));
+        assert_eq!(contours[0].points.len(), 16);
+        assert!(contours[1].is_hole);
+        assert_eq!(contours[1].parent, Some(0));
+        assert_eq!(contours[2].points, vec![(2, 2)]);
+        assert_eq!(contours[2].parent, Some(1));
+        assert_eq!(contours[3].points, vec![(6, 2)]);
+        assert_eq!(contours[3].parent, None);
+
+        assert_eq!(contours[0].area(), 16.0);
+        let square = contours[0].simplify(0.5);
+        assert_eq!(square.len(), 4);
+        assert_eq!(simplify_closed(&square, 0.5), square);
+    }
+}
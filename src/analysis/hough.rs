@@ -0,0 +1,473 @@
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::color::Color;
+use crate::feature::luminance;
+use crate::image::Image;
+use crate::ty::Type;
+
+/// A line in polar form: the set of points where `x * cos(theta) + y * sin(theta) == rho`
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Line {
+    /// Distance from the origin in pixels
+    pub rho: f64,
+    /// Angle of the line normal in degrees, between 0 and 180
+    pub theta: f64,
+    /// Number of edge pixels on the line
+    pub votes: usize,
+}
+
+/// A line segment between two points
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+}
+
+impl Segment {
+    /// Length of the segment in pixels
+    pub fn length(&self) -> f64 {
+        let dx = self.end.0 as f64 - self.start.0 as f64;
+        let dy = self.end.1 as f64 - self.start.1 as f64;
+        (dx * dx + dy * dy).sqrt()
+    }
+}
+
+/// The accumulator used by the Hough line transforms
+struct Accumulator {
+    rho_res: f64,
+    theta_res: f64,
+    offset: f64,
+    num_rho: usize,
+    trig: Vec<(f64, f64)>,
+    votes: Vec<usize>,
+}
+
+impl Accumulator {
+    fn new(width: usize, height: usize, rho_res: f64, theta_res: f64) -> Accumulator {
+        // Keep the offset a multiple of the resolution so bins are centered on whole values
+        let diagonal = ((width * width + height * height) as f64).sqrt();
+        let offset = (diagonal / rho_res).ceil() * rho_res;
+        let num_rho = (2.0 * offset / rho_res).round() as usize + 1;
+        let num_theta = (180.0 / theta_res).round().max(1.0) as usize;
+        let trig = (0..num_theta)
+            .map(|t| {
+                let a = (t as f64 * theta_res).to_radians();
+                (a.cos(), a.sin())
+            })
+            .collect();
+        Accumulator {
+            rho_res,
+            theta_res,
+            offset,
+            num_rho,
+            trig,
+            votes: vec![0; num_rho * num_theta],
+        }
+    }
+
+    fn num_theta(&self) -> usize {
+        self.trig.len()
+    }
+
+    #[inline]
+    fn rho_index(&self, x: usize, y: usize, t: usize) -> usize {
+        let (cos, sin) = self.trig[t];
+        ((x as f64 * cos + y as f64 * sin + self.offset) / self.rho_res).round() as usize
+    }
+
+    /// Add or remove the votes of a single point, returns the index of the strongest bin along
+    /// with its vote count
+    fn vote(&mut self, x: usize, y: usize, add: bool) -> (usize, usize) {
+        let mut best = (0, 0);
+        for t in 0..self.num_theta() {
+            let i = t * self.num_rho + self.rho_index(x, y, t);
+            if add {
+                self.votes[i] += 1;
+                if self.votes[i] > best.1 {
+                    best = (t, self.votes[i]);
+                }
+            } else {
+                self.votes[i] = self.votes[i].saturating_sub(1);
+            }
+        }
+        best
+    }
+}
+
+fn edge_points<T: Type, C: Color, I: Image<T, C>>(edges: &I) -> Vec<(usize, usize)> {
+    let mut points = Vec::new();
+    for y in 0..edges.height() {
+        for x in 0..edges.width() {
+            if edges.at(x, y)[0] != T::zero() {
+                points.push((x, y));
+            }
+        }
+    }
+    points
+}
+
+/// Detect lines in a binary edge image using the standard Hough transform. Pixels with a
+/// non-zero first channel are treated as edges. `rho_res` is the distance resolution in pixels
+/// and `theta_res` is the angle resolution in degrees. Lines with at least `threshold` votes
+/// that are a local maximum in the accumulator are returned, strongest first.
+pub fn hough_lines<T: Type, C: Color, I: Image<T, C>>(
+    edges: &I,
+    rho_res: f64,
+    theta_res: f64,
+    threshold: usize,
+) -> Vec<Line> {
+    let mut acc = Accumulator::new(edges.width(), edges.height(), rho_res, theta_res);
+    for (x, y) in edge_points(edges) {
+        acc.vote(x, y, true);
+    }
+
+    let (num_rho, num_theta) = (acc.num_rho, acc.num_theta());
+    let votes = &acc.votes;
+    let at = |t: isize, r: isize| -> usize {
+        if r < 0 || r >= num_rho as isize {
+            return 0;
+        }
+
+        // theta wraps around to 180 degrees with the sign of rho reversed
+        if t < 0 {
+            return votes[(num_theta - 1) * num_rho + (num_rho - 1 - r as usize)];
+        }
+        if t >= num_theta as isize {
+            return votes[num_rho - 1 - r as usize];
+        }
+        votes[t as usize * num_rho + r as usize]
+    };
+
+    let mut lines = Vec::new();
+    for t in 0..num_theta as isize {
+        for r in 0..num_rho as isize {
+            let v = at(t, r);
+            if v < threshold.max(1) {
+                continue;
+            }
+
+            if v > at(t, r - 1) && v >= at(t, r + 1) && v > at(t - 1, r) && v >= at(t + 1, r) {
+                lines.push(Line {
+                    rho: r as f64 * acc.rho_res - acc.offset,
+                    theta: t as f64 * acc.theta_res,
+                    votes: v,
+                });
+            }
+        }
+    }
+
+    lines.sort_by_key(|l| core::cmp::Reverse(l.votes));
+    lines
+}
+
+/// Small deterministic generator used to randomize the order in which points are processed
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// Detect line segments using the progressive probabilistic Hough transform. Edge points are
+/// processed in random order; once a bin reaches `threshold` votes the corresponding line is
+/// followed through the edge image, allowing gaps of up to `max_gap` pixels, and the points on
+/// it are removed from the accumulator. Segments shorter than `min_length` are discarded.
+pub fn hough_line_segments<T: Type, C: Color, I: Image<T, C>>(
+    edges: &I,
+    rho_res: f64,
+    theta_res: f64,
+    threshold: usize,
+    min_length: f64,
+    max_gap: usize,
+) -> Vec<Segment> {
+    let (width, height) = (edges.width(), edges.height());
+    let mut acc = Accumulator::new(width, height, rho_res, theta_res);
+    let mut points = edge_points(edges);
+
+    // 0 = not an edge, 1 = unprocessed edge, 2 = edge that has voted
+    let mut mask = vec![0u8; width * height];
+    for (x, y) in &points {
+        mask[y * width + x] = 1;
+    }
+
+    let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+    for i in (1..points.len()).rev() {
+        let j = (rng.next() % (i as u64 + 1)) as usize;
+        points.swap(i, j);
+    }
+
+    let mut segments = Vec::new();
+    for (x, y) in points {
+        if mask[y * width + x] != 1 {
+            continue;
+        }
+
+        mask[y * width + x] = 2;
+        let (t, votes) = acc.vote(x, y, true);
+        if votes < threshold.max(1) {
+            continue;
+        }
+
+        // Walk along the line in both directions from (x, y)
+        let (cos, sin) = acc.trig[t];
+        let (dx, dy) = (-sin, cos);
+        let scale = dx.abs().max(dy.abs());
+        let (dx, dy) = (dx / scale, dy / scale);
+
+        let mut ends = [(x, y); 2];
+        for (k, end) in ends.iter_mut().enumerate() {
+            let sign = if k == 0 { 1.0 } else { -1.0 };
+            let (mut fx, mut fy) = (x as f64, y as f64);
+            let mut gap = 0;
+            loop {
+                fx += sign * dx;
+                fy += sign * dy;
+                let (px, py) = (fx.round(), fy.round());
+                if px < 0.0 || py < 0.0 || px >= width as f64 || py >= height as f64 {
+                    break;
+                }
+
+                let (px, py) = (px as usize, py as usize);
+                if mask[py * width + px] != 0 {
+                    gap = 0;
+                    *end = (px, py);
+                } else {
+                    gap += 1;
+                    if gap > max_gap {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let segment = Segment {
+            start: ends[1],
+            end: ends[0],
+        };
+        let keep = segment.length() >= min_length;
+
+        // Remove the points of the segment from the image and their votes from the accumulator
+        let steps = (segment.end.0 as isize - segment.start.0 as isize)
+            .abs()
+            .max((segment.end.1 as isize - segment.start.1 as isize).abs());
+        for s in 0..=steps {
+            let f = if steps == 0 {
+                0.0
+            } else {
+                s as f64 / steps as f64
+            };
+            let px = (segment.start.0 as f64 + f * (segment.end.0 as f64 - segment.start.0 as f64))
+                .round() as usize;
+            let py = (segment.start.1 as f64 + f * (segment.end.1 as f64 - segment.start.1 as f64))
+                .round() as usize;
+            let m = &mut mask[py * width + px];
+            if *m == 2 && keep {
+                acc.vote(px, py, false);
+            }
+            if *m != 0 {
+                *m = 0;
+            }
+        }
+
+        if keep {
+            segments.push(segment);
+        }
+    }
+
+    segments
+}
+
+/// A circle detected using `hough_circles`
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Circle {
+    pub x: f64,
+    pub y: f64,
+    pub radius: f64,
+    /// Number of edge pixels on the circle
+    pub votes: usize,
+}
+
+/// Detect circles with a radius between `min_radius` and `max_radius` using the Hough gradient
+/// method. Edge pixels vote for possible centers along their gradient direction, then the
+/// radius of each center is chosen by counting the edge pixels at each distance. Circles are
+/// returned strongest first.
+pub fn hough_circles<T: Type, C: Color, I: Image<T, C>>(
+    image: &I,
+    min_radius: usize,
+    max_radius: usize,
+) -> Vec<Circle> {
+    let (width, height) = (image.width(), image.height());
+    let min_radius = min_radius.max(1);
+    if width < 3 || height < 3 || max_radius < min_radius {
+        return Vec::new();
+    }
+
+    let gray = luminance(image);
+    let at = |x: usize, y: usize| gray[y * width + x];
+
+    // Sobel gradients of edge pixels
+    let mut gradients = Vec::new();
+    let mut max_magnitude = 0.0f64;
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let gx = (at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1))
+                - (at(x - 1, y - 1) + 2.0 * at(x - 1, y) + at(x - 1, y + 1));
+            let gy = (at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1))
+                - (at(x - 1, y - 1) + 2.0 * at(x, y - 1) + at(x + 1, y - 1));
+            let magnitude = (gx * gx + gy * gy).sqrt();
+            if magnitude > 0.0 {
+                max_magnitude = max_magnitude.max(magnitude);
+                gradients.push((x, y, gx / magnitude, gy / magnitude, magnitude));
+            }
+        }
+    }
+
+    let threshold = max_magnitude * 0.25;
+    let edges: Vec<(usize, usize, f64, f64)> = gradients
+        .into_iter()
+        .filter(|g| g.4 >= threshold)
+        .map(|(x, y, dx, dy, _)| (x, y, dx, dy))
+        .collect();
+
+    // Vote for centers in both directions along the gradient
+    let mut acc = vec![0usize; width * height];
+    for &(x, y, dx, dy) in &edges {
+        for sign in &[1.0, -1.0] {
+            for r in min_radius..=max_radius {
+                let cx = (x as f64 + sign * dx * r as f64).round();
+                let cy = (y as f64 + sign * dy * r as f64).round();
+                if cx >= 0.0 && cy >= 0.0 && cx < width as f64 && cy < height as f64 {
+                    acc[cy as usize * width + cx as usize] += 1;
+                }
+            }
+        }
+    }
+
+    // Candidate centers are local maxima of the accumulator
+    let min_votes = (core::f64::consts::PI * min_radius as f64) as usize;
+    let mut centers = Vec::new();
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let v = acc[y * width + x];
+            if v < min_votes.max(1) {
+                continue;
+            }
+
+            let is_max = (y - 1..=y + 1).all(|j| {
+                (x - 1..=x + 1).all(|i| {
+                    let o = acc[j * width + i];
+                    o < v || (o == v && (j, i) >= (y, x))
+                })
+            });
+            if is_max {
+                centers.push((x, y, v));
+            }
+        }
+    }
+    centers.sort_by_key(|c| core::cmp::Reverse(c.2));
+
+    // Find the best radius for each center, ignoring centers close to stronger circles
+    let mut circles: Vec<Circle> = Vec::new();
+    let mut counts = vec![0usize; max_radius + 2];
+    for (cx, cy, _) in centers {
+        let too_close = circles.iter().any(|c| {
+            let dx = c.x - cx as f64;
+            let dy = c.y - cy as f64;
+            (dx * dx + dy * dy).sqrt() < min_radius as f64
+        });
+        if too_close {
+            continue;
+        }
+
+        counts.iter_mut().for_each(|c| *c = 0);
+        for &(x, y, _, _) in &edges {
+            let dx = x as f64 - cx as f64;
+            let dy = y as f64 - cy as f64;
+            let d = (dx * dx + dy * dy).sqrt().round() as usize;
+            if d >= min_radius && d <= max_radius {
+                counts[d] += 1;
+            }
+        }
+
+        let best = (min_radius..=max_radius)
+            .max_by(|a, b| {
+                // Normalize by circumference so larger radii aren't favored
+                let fa = counts[*a] as f64 / *a as f64;
+                let fb = counts[*b] as f64 / *b as f64;
+                fa.partial_cmp(&fb).unwrap_or(core::cmp::Ordering::Equal)
+            })
+            .unwrap_or(min_radius);
+
+        let votes = counts[best];
+        if (votes as f64) < core::f64::consts::PI * best as f64 {
+            continue;
+        }
+
+        circles.push(Circle {
+            x: cx as f64,
+            y: cy as f64,
+            radius: best as f64,
+            votes,
+        });
+    }
+
+    circles.sort_by_key(|c| core::cmp::Reverse(c.votes));
+    circles
+}
+
+#[cfg(test)]
+mod test {
+    use crate::analysis::{hough_circles, hough_line_segments, hough_lines};
+    use crate::{Gray, Image, ImageBuf};
+
+    #[test]
+    fn test_hough_lines() {
+        let mut image: ImageBuf<u8, Gray> = ImageBuf::new(40, 40);
+        image.for_each(|(x, y), px| {
+            if y == 10 && (5..35).contains(&x) {
+                px[0] = 255;
+            }
+            if x == 30 && (12..40).contains(&y) {
+                px[0] = 255;
+            }
+        });
+
+        let lines = hough_lines(&image, 1.0, 1.0, 28);
+        assert_eq!(lines.len(), 2);
+        assert_eq!((lines[0].rho, lines[0].theta), (10.0, 90.0));
+        assert_eq!((lines[1].rho, lines[1].theta), (30.0, 0.0));
+
+        let mut segments: Vec<_> = hough_line_segments(&image, 1.0, 1.0, 10, 10.0, 2)
+            .into_iter()
+            .map(|s| (s.start.min(s.end), s.start.max(s.end)))
+            .collect();
+        segments.sort();
+        assert_eq!(segments, vec![((5, 10), (34, 10)), ((30, 10), (30, 39))]);
+    }
+
+    #[test]
+    fn test_hough_circles() {
+        let mut image: ImageBuf<u8, Gray> = ImageBuf::new(64, 48);
+        image.for_each(|(x, y), px| {
+            let dx = x as f64 - 20.0;
+            let dy = y as f64 - 24.0;
+            if dx * dx + dy * dy <= 100.0 {
+                px[0] = 255;
+            }
+        });
+
+        let circles = hough_circles(&image, 5, 15);
+        assert_eq!(circles.len(), 1);
+        assert_eq!((circles[0].x, circles[0].y), (20.0, 24.0));
+        assert!((circles[0].radius - 10.0).abs() <= 1.0);
+    }
+}
@@ -0,0 +1,160 @@
+use alloc::vec::Vec;
+
+use crate::color::Color;
+use crate::image::Image;
+use crate::rect::Rect;
+use crate::ty::Type;
+
+use core::ops::{Add, Sub};
+
+/// Values that can be used to accumulate an integral image
+pub trait IntegralValue: Copy + Default + Add<Output = Self> + Sub<Output = Self> {
+    /// Convert a raw pixel value
+    fn from_value(f: f64) -> Self;
+
+    /// Convert to `f64`
+    fn to_f64(self) -> f64;
+}
+
+impl IntegralValue for u64 {
+    #[inline]
+    fn from_value(f: f64) -> u64 {
+        f.max(0.0) as u64
+    }
+
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+impl IntegralValue for f64 {
+    #[inline]
+    fn from_value(f: f64) -> f64 {
+        f
+    }
+
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+/// Summed-area table, each entry holds the sum of all raw pixel values above and to the left
+/// of it, per channel. This allows the sum of any rectangle to be computed in constant time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Integral<S: IntegralValue> {
+    width: usize,
+    height: usize,
+    channels: usize,
+    data: Vec<S>,
+}
+
+impl<S: IntegralValue> Integral<S> {
+    /// Width of the source image
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Height of the source image
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Number of channels
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Get the table entry at (x, y), the sum of all pixels in `[0, x) x [0, y)`. `x` and `y`
+    /// can be equal to the width and height of the image.
+    #[inline]
+    pub fn at(&self, x: usize, y: usize, c: usize) -> S {
+        self.data[(y * (self.width + 1) + x) * self.channels + c]
+    }
+
+    /// Sum of channel `c` in the given rectangle
+    #[inline]
+    pub fn sum(&self, x: usize, y: usize, width: usize, height: usize, c: usize) -> S {
+        let (x1, y1) = (x + width, y + height);
+        self.at(x1, y1, c) + self.at(x, y, c) - self.at(x1, y, c) - self.at(x, y1, c)
+    }
+
+    /// Sum of channel `c` in `rect`
+    pub fn sum_rect(&self, rect: &Rect, c: usize) -> S {
+        self.sum(rect.x, rect.y, rect.width, rect.height, c)
+    }
+
+    /// Mean of channel `c` in `rect`
+    pub fn mean_rect(&self, rect: &Rect, c: usize) -> f64 {
+        if rect.is_empty() {
+            return 0.0;
+        }
+
+        self.sum_rect(rect, c).to_f64() / rect.area() as f64
+    }
+}
+
+fn build<S: IntegralValue, T: Type, C: Color, I: Image<T, C>, F: Fn(f64) -> f64>(
+    image: &I,
+    f: F,
+) -> Integral<S> {
+    let (width, height, channels) = image.shape();
+    let stride = (width + 1) * channels;
+    let mut data = vec![S::default(); stride * (height + 1)];
+    let mut row = vec![S::default(); channels];
+    for y in 0..height {
+        row.iter_mut().for_each(|r| *r = S::default());
+        for x in 0..width {
+            let px = image.at(x, y);
+            for c in 0..channels {
+                row[c] = row[c] + S::from_value(f(T::to_float(&px[c])));
+                let i = (y + 1) * stride + (x + 1) * channels + c;
+                data[i] = data[i - stride] + row[c];
+            }
+        }
+    }
+
+    Integral {
+        width,
+        height,
+        channels,
+        data,
+    }
+}
+
+/// Compute the integral image of `image` using raw pixel values. The accumulator type is
+/// usually `u64` for integer images and `f64` for floating point images.
+pub fn integral<S: IntegralValue, T: Type, C: Color, I: Image<T, C>>(image: &I) -> Integral<S> {
+    build(image, |v| v)
+}
+
+/// Compute the integral image of the squared raw pixel values, used along with `integral` to
+/// compute the variance of a region
+pub fn integral_squared<S: IntegralValue, T: Type, C: Color, I: Image<T, C>>(
+    image: &I,
+) -> Integral<S> {
+    build(image, |v| v * v)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::analysis::{integral, Integral};
+    use crate::{Gray, ImageBuf, Rect, Rgb};
+
+    #[test]
+    fn test_integral() {
+        let image: ImageBuf<u8, Gray> = ImageBuf::new_from(3, 3, vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        let ii: Integral<u64> = integral(&image);
+        assert_eq!(ii.sum(0, 0, 3, 3, 0), 45);
+        assert_eq!(ii.sum(1, 1, 2, 2, 0), 28);
+        assert_eq!(ii.sum_rect(&Rect::new(0, 2, 3, 1), 0), 24);
+        assert_eq!(ii.mean_rect(&Rect::new(1, 0, 1, 3), 0), 5.0);
+
+        let image: ImageBuf<f32, Rgb> =
+            ImageBuf::new_from(2, 1, vec![0.5, 0.0, 1.0, 0.25, 0.0, 1.0]);
+        let ii: Integral<f64> = integral(&image);
+        assert_eq!(ii.sum(0, 0, 2, 1, 0), 0.75);
+        assert_eq!(ii.sum(1, 0, 1, 1, 2), 1.0);
+    }
+}
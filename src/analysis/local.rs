@@ -0,0 +1,133 @@
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::analysis::{integral, integral_squared, Integral};
+use crate::color::Color;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::ty::Type;
+
+/// Number of bins used by `local_entropy`
+const ENTROPY_BINS: usize = 256;
+
+/// The window of the given radius around (x, y), clipped to the image
+#[inline]
+fn window(
+    x: usize,
+    y: usize,
+    radius: usize,
+    width: usize,
+    height: usize,
+) -> (usize, usize, usize, usize) {
+    let x0 = x.saturating_sub(radius);
+    let y0 = y.saturating_sub(radius);
+    let x1 = (x + radius + 1).min(width);
+    let y1 = (y + radius + 1).min(height);
+    (x0, y0, x1 - x0, y1 - y0)
+}
+
+/// Compute the variance of each channel in a `2 * radius + 1` square window around every
+/// pixel, using normalized values. Windows are clipped at the image edges.
+pub fn local_variance<T: Type, C: Color, I: Image<T, C>>(
+    image: &I,
+    radius: usize,
+) -> ImageBuf<f64, C> {
+    let (width, height) = (image.width(), image.height());
+    let sum: Integral<f64> = integral(image);
+    let sq: Integral<f64> = integral_squared(image);
+    let range = T::max_f() - T::min_f();
+
+    let mut dest = ImageBuf::new(width, height);
+    dest.for_each(|(x, y), px| {
+        let (x0, y0, w, h) = window(x, y, radius, width, height);
+        let n = (w * h) as f64;
+        for (c, item) in px.iter_mut().enumerate() {
+            let mean = sum.sum(x0, y0, w, h, c) / n;
+            let variance = (sq.sum(x0, y0, w, h, c) / n - mean * mean).max(0.0);
+            *item = variance / (range * range);
+        }
+    });
+    dest
+}
+
+/// Compute the standard deviation of each channel in a `2 * radius + 1` square window around
+/// every pixel, using normalized values
+pub fn local_std<T: Type, C: Color, I: Image<T, C>>(image: &I, radius: usize) -> ImageBuf<f64, C> {
+    let mut dest = local_variance(image, radius);
+    dest.data_mut().iter_mut().for_each(|x| *x = x.sqrt());
+    dest
+}
+
+/// Compute the Shannon entropy in bits of each channel in a `2 * radius + 1` square window
+/// around every pixel. Values are quantized into 256 levels, so the result is between 0 and 8.
+pub fn local_entropy<T: Type, C: Color, I: Image<T, C>>(
+    image: &I,
+    radius: usize,
+) -> ImageBuf<f64, C> {
+    let (width, height, channels) = image.shape();
+    let bin = |x: usize, y: usize, c: usize| {
+        ((image.get_f(x, y, c) * ENTROPY_BINS as f64) as usize).min(ENTROPY_BINS - 1)
+    };
+
+    let mut dest = ImageBuf::new(width, height);
+    let mut hist = vec![0usize; ENTROPY_BINS];
+    for c in 0..channels {
+        for y in 0..height {
+            let (_, y0, _, h) = window(0, y, radius, width, height);
+
+            // Slide the window along the row, adding and removing columns
+            hist.iter_mut().for_each(|h| *h = 0);
+            for i in 0..radius.min(width) {
+                for j in y0..y0 + h {
+                    hist[bin(i, j, c)] += 1;
+                }
+            }
+
+            for x in 0..width {
+                if x + radius < width {
+                    for j in y0..y0 + h {
+                        hist[bin(x + radius, j, c)] += 1;
+                    }
+                }
+                if x > radius {
+                    for j in y0..y0 + h {
+                        hist[bin(x - radius - 1, j, c)] -= 1;
+                    }
+                }
+
+                let (_, _, w, _) = window(x, y, radius, width, height);
+                let n = (w * h) as f64;
+                let entropy: f64 = hist
+                    .iter()
+                    .filter(|count| **count > 0)
+                    .map(|count| {
+                        let p = *count as f64 / n;
+                        -p * p.log2()
+                    })
+                    .sum();
+                dest.at_mut(x, y)[c] = entropy;
+            }
+        }
+    }
+    dest
+}
+
+#[cfg(test)]
+mod test {
+    use crate::analysis::{local_entropy, local_std, local_variance};
+    use crate::{Gray, Image, ImageBuf};
+
+    #[test]
+    fn test_local() {
+        let image: ImageBuf<u8, Gray> = ImageBuf::new_from(4, 1, vec![0, 255, 0, 255]);
+        let v = local_variance(&image, 1);
+        assert!((v.at(1, 0)[0] - 2.0 / 9.0).abs() < 1e-9);
+        assert!((v.at(0, 0)[0] - 0.25).abs() < 1e-9);
+        assert!((local_std(&image, 1).at(0, 0)[0] - 0.5).abs() < 1e-9);
+
+        let e = local_entropy(&image, 1);
+        assert!((e.at(0, 0)[0] - 1.0).abs() < 1e-9);
+        let flat: ImageBuf<u8, Gray> = ImageBuf::new(5, 5);
+        assert_eq!(local_entropy(&flat, 2).at(2, 2)[0], 0.0);
+    }
+}
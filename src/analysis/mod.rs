@@ -0,0 +1,30 @@
+//! Image analysis
+
+mod components;
+mod contours;
+mod hough;
+mod integral;
+mod local;
+mod moments;
+mod saliency;
+mod template;
+
+pub use self::components::*;
+pub use self::contours::*;
+pub use self::hough::*;
+pub use self::integral::*;
+pub use self::local::*;
+pub use self::moments::*;
+pub use self::saliency::*;
+pub use self::template::*;
+
+/// Determines which neighboring pixels are considered connected
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Connectivity {
+    /// Horizontal and vertical neighbors
+    Four,
+    /// Horizontal, vertical and diagonal neighbors
+    #[default]
+    Eight,
+}
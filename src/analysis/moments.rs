@@ -0,0 +1,174 @@
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::color::Color;
+use crate::image::Image;
+use crate::ty::Type;
+
+/// Image moments up to the third order
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Moments {
+    raw: [[f64; 4]; 4],
+    central: [[f64; 4]; 4],
+}
+
+impl Moments {
+    /// Raw moment `m_pq`, `p + q` must be at most 3
+    pub fn m(&self, p: usize, q: usize) -> f64 {
+        self.raw[p][q]
+    }
+
+    /// Central moment `mu_pq`, `p + q` must be at most 3
+    pub fn mu(&self, p: usize, q: usize) -> f64 {
+        self.central[p][q]
+    }
+
+    /// Scale invariant normalized central moment `nu_pq`, `p + q` must be between 2 and 3
+    pub fn nu(&self, p: usize, q: usize) -> f64 {
+        let m00 = self.raw[0][0];
+        if m00 == 0.0 {
+            return 0.0;
+        }
+
+        self.central[p][q] / m00.powf(1.0 + (p + q) as f64 / 2.0)
+    }
+
+    /// Total mass, the area of a binary image
+    pub fn area(&self) -> f64 {
+        self.raw[0][0]
+    }
+
+    /// Center of mass
+    pub fn centroid(&self) -> (f64, f64) {
+        let m00 = self.raw[0][0];
+        if m00 == 0.0 {
+            return (0.0, 0.0);
+        }
+
+        (self.raw[1][0] / m00, self.raw[0][1] / m00)
+    }
+
+    /// Angle of the major axis in degrees, between -90 and 90
+    pub fn orientation(&self) -> f64 {
+        let (mu20, mu02, mu11) = (self.central[2][0], self.central[0][2], self.central[1][1]);
+        (0.5 * (2.0 * mu11).atan2(mu20 - mu02)).to_degrees()
+    }
+
+    /// Eccentricity of the ellipse with the same second order moments, 0 for a circle and
+    /// approaching 1 for elongated shapes
+    pub fn eccentricity(&self) -> f64 {
+        let (mu20, mu02, mu11) = (self.central[2][0], self.central[0][2], self.central[1][1]);
+        let d = ((mu20 - mu02) * (mu20 - mu02) + 4.0 * mu11 * mu11).sqrt();
+        let major = mu20 + mu02 + d;
+        let minor = mu20 + mu02 - d;
+        if major <= 0.0 {
+            return 0.0;
+        }
+
+        (1.0 - minor.max(0.0) / major).sqrt()
+    }
+
+    /// The seven Hu moments, which are invariant to translation, scale and rotation
+    pub fn hu(&self) -> [f64; 7] {
+        let n20 = self.nu(2, 0);
+        let n02 = self.nu(0, 2);
+        let n11 = self.nu(1, 1);
+        let n30 = self.nu(3, 0);
+        let n03 = self.nu(0, 3);
+        let n21 = self.nu(2, 1);
+        let n12 = self.nu(1, 2);
+
+        let a = n30 + n12;
+        let b = n21 + n03;
+        let c = n30 - 3.0 * n12;
+        let d = 3.0 * n21 - n03;
+        [
+            n20 + n02,
+            (n20 - n02) * (n20 - n02) + 4.0 * n11 * n11,
+            c * c + d * d,
+            a * a + b * b,
+            c * a * (a * a - 3.0 * b * b) + d * b * (3.0 * a * a - b * b),
+            (n20 - n02) * (a * a - b * b) + 4.0 * n11 * a * b,
+            d * a * (a * a - 3.0 * b * b) - c * b * (3.0 * a * a - b * b),
+        ]
+    }
+}
+
+/// Compute the moments of the first channel of an image, using normalized values as weights.
+/// For binary images this describes the shape of the foreground.
+pub fn moments<T: Type, C: Color, I: Image<T, C>>(image: &I) -> Moments {
+    let mut raw = [[0.0; 4]; 4];
+    for y in 0..image.height() {
+        for x in 0..image.width() {
+            let v = image.get_f(x, y, 0);
+            if v == 0.0 {
+                continue;
+            }
+
+            let (fx, fy) = (x as f64, y as f64);
+            let xs = [1.0, fx, fx * fx, fx * fx * fx];
+            let ys = [1.0, fy, fy * fy, fy * fy * fy];
+            for p in 0..4 {
+                for q in 0..4 - p {
+                    raw[p][q] += v * xs[p] * ys[q];
+                }
+            }
+        }
+    }
+
+    let mut central = [[0.0; 4]; 4];
+    let m00 = raw[0][0];
+    if m00 != 0.0 {
+        let (cx, cy) = (raw[1][0] / m00, raw[0][1] / m00);
+        central[0][0] = m00;
+        central[2][0] = raw[2][0] - cx * raw[1][0];
+        central[0][2] = raw[0][2] - cy * raw[0][1];
+        central[1][1] = raw[1][1] - cx * raw[0][1];
+        central[3][0] = raw[3][0] - 3.0 * cx * raw[2][0] + 2.0 * cx * cx * raw[1][0];
+        central[0][3] = raw[0][3] - 3.0 * cy * raw[0][2] + 2.0 * cy * cy * raw[0][1];
+        central[2][1] =
+            raw[2][1] - 2.0 * cx * raw[1][1] - cy * raw[2][0] + 2.0 * cx * cx * raw[0][1];
+        central[1][2] =
+            raw[1][2] - 2.0 * cy * raw[1][1] - cx * raw[0][2] + 2.0 * cy * cy * raw[1][0];
+    }
+
+    Moments { raw, central }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::analysis::moments;
+    use crate::{Gray, Image, ImageBuf};
+
+    fn ellipse(cx: f64, cy: f64, a: f64, b: f64, angle: f64) -> ImageBuf<u8, Gray> {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let mut image = ImageBuf::new(80, 80);
+        image.for_each(|(x, y), px| {
+            let (dx, dy) = (x as f64 - cx, y as f64 - cy);
+            let u = dx * cos + dy * sin;
+            let v = -dx * sin + dy * cos;
+            if (u / a).powi(2) + (v / b).powi(2) <= 1.0 {
+                px[0] = 255;
+            }
+        });
+        image
+    }
+
+    #[test]
+    fn test_moments() {
+        let m = moments(&ellipse(30.0, 40.0, 20.0, 10.0, 30.0));
+        let (x, y) = m.centroid();
+        assert!((x - 30.0).abs() < 0.1 && (y - 40.0).abs() < 0.1);
+        assert!((m.orientation() - 30.0).abs() < 1.0);
+        assert!((m.eccentricity() - 0.866).abs() < 0.02);
+        assert!((m.mu(2, 0) - (m.m(2, 0) - x * m.m(1, 0))).abs() < 1e-6);
+
+        // Hu moments are invariant to translation, rotation and scale
+        let a = m.hu();
+        let b = moments(&ellipse(45.0, 35.0, 30.0, 15.0, -50.0)).hu();
+        for i in 0..2 {
+            assert!((a[i] - b[i]).abs() / a[i].abs() < 0.02);
+        }
+    }
+}
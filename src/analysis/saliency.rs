@@ -0,0 +1,104 @@
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::color::{Color, Gray};
+use crate::feature::luminance;
+use crate::fft;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::pyramid;
+use crate::ty::Type;
+
+/// Size of the image the spectral residual is computed at
+const SIZE: usize = 64;
+
+/// Compute a saliency map using the spectral residual method (Hou and Zhang, 2007). The
+/// result has the same size as `image`, with values normalized between 0 and 1 where higher
+/// values mark regions that stand out from their surroundings.
+pub fn saliency<T: Type, C: Color, I: Image<T, C>>(image: &I) -> ImageBuf<f64, Gray> {
+    let (width, height) = (image.width(), image.height());
+    if width == 0 || height == 0 {
+        return ImageBuf::new(width, height);
+    }
+
+    let mut gray: ImageBuf<f64, Gray> = ImageBuf::new_from(width, height, luminance(image));
+    while gray.width() / 2 >= SIZE && gray.height() / 2 >= SIZE {
+        gray = pyramid::down(&gray);
+    }
+    let small: ImageBuf<f64, Gray> = gray.lazy().resize(SIZE, SIZE).eval();
+
+    let mut spectrum = fft::pad(small.data(), SIZE, SIZE, SIZE);
+    fft::fft_2d(&mut spectrum, SIZE, SIZE);
+
+    // Log amplitude minus its local average, the part of the spectrum that is unexpected
+    let amplitude: Vec<f64> = spectrum
+        .iter()
+        .map(|c| (fft::norm(c) + 1e-12).ln())
+        .collect();
+    let at = |x: isize, y: isize| {
+        let x = (x + SIZE as isize) as usize % SIZE;
+        let y = (y + SIZE as isize) as usize % SIZE;
+        amplitude[y * SIZE + x]
+    };
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let mut mean = 0.0;
+            for j in -1..=1 {
+                for i in -1..=1 {
+                    mean += at(x as isize + i, y as isize + j);
+                }
+            }
+            let residual = amplitude[y * SIZE + x] - mean / 9.0;
+            let c = &mut spectrum[y * SIZE + x];
+            *c = fft::from_polar(residual.exp(), c.im.atan2(c.re));
+        }
+    }
+    fft::ifft_2d(&mut spectrum, SIZE, SIZE);
+
+    let map: ImageBuf<f64, Gray> =
+        ImageBuf::new_from(SIZE, SIZE, spectrum.iter().map(|c| c.norm_sqr()).collect());
+    let max = map.data().iter().cloned().fold(0.0, f64::max);
+    let mut dest: ImageBuf<f64, Gray> = if max > 0.0 {
+        map.lazy()
+            .map(move |f| f / max)
+            .blur(2.5)
+            .resize(width, height)
+            .eval()
+    } else {
+        ImageBuf::new(width, height)
+    };
+
+    let (min, max) = dest
+        .data()
+        .iter()
+        .fold((f64::INFINITY, 0.0f64), |(a, b), x| (a.min(*x), b.max(*x)));
+    if max > min {
+        dest.data_mut()
+            .iter_mut()
+            .for_each(|x| *x = (*x - min) / (max - min));
+    }
+    dest
+}
+
+#[cfg(test)]
+mod test {
+    use crate::analysis::saliency;
+    use crate::{Gray, Image, ImageBuf};
+
+    #[test]
+    fn test_saliency() {
+        let mut image: ImageBuf<u8, Gray> = ImageBuf::new(128, 96);
+        image.for_each(|(x, y), px| {
+            px[0] = 100 + ((x * 7 + y * 13) % 5) as u8;
+            if (80..92).contains(&x) && (30..42).contains(&y) {
+                px[0] = 255;
+            }
+        });
+
+        let map = saliency(&image);
+        assert_eq!((map.width(), map.height()), (128, 96));
+        assert!(map.at(86, 36)[0] > 0.5);
+        assert!(map.at(20, 70)[0] < 0.1);
+    }
+}
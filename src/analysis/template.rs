@@ -0,0 +1,306 @@
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::color::{Color, Gray};
+use crate::error::Error;
+use crate::fft;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::ty::Type;
+
+/// Templates with more pixels than this are matched using FFT-based correlation
+const FFT_THRESHOLD: usize = 256;
+
+/// Template matching score
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// Sum of squared differences, lower is better
+    Ssd,
+    /// Normalized cross-correlation, higher is better
+    Ncc,
+    /// Zero-mean normalized cross-correlation, between -1 and 1, higher is better. This is
+    /// insensitive to changes in brightness and contrast.
+    ZeroMeanNcc,
+}
+
+impl Method {
+    /// Returns true if `a` is a better score than `b`
+    pub fn is_better(self, a: f64, b: f64) -> bool {
+        match self {
+            Method::Ssd => a < b,
+            Method::Ncc | Method::ZeroMeanNcc => a > b,
+        }
+    }
+}
+
+/// The result of `match_template`
+#[derive(Debug, Clone)]
+pub struct TemplateMatch {
+    method: Method,
+    scores: ImageBuf<f64, Gray>,
+}
+
+impl TemplateMatch {
+    /// The method used to compute the scores
+    pub fn method(&self) -> Method {
+        self.method
+    }
+
+    /// The score of the template placed with its top-left corner at each position
+    pub fn scores(&self) -> &ImageBuf<f64, Gray> {
+        &self.scores
+    }
+
+    /// Consume the result, returning the score map
+    pub fn into_scores(self) -> ImageBuf<f64, Gray> {
+        self.scores
+    }
+
+    /// Get the score at the given position
+    pub fn score(&self, x: usize, y: usize) -> f64 {
+        self.scores.at(x, y)[0]
+    }
+
+    /// Returns the position and score of the best match
+    pub fn best_match(&self) -> Option<(usize, usize, f64)> {
+        let width = self.scores.width();
+        let mut best: Option<(usize, usize, f64)> = None;
+        for (i, s) in self.scores.data().iter().enumerate() {
+            if best.map(|b| self.method.is_better(*s, b.2)).unwrap_or(true) {
+                best = Some((i % width, i / width, *s));
+            }
+        }
+        best
+    }
+
+    /// Returns the positions and scores of all matches better than `threshold`
+    pub fn matches(&self, threshold: f64) -> Vec<(usize, usize, f64)> {
+        let width = self.scores.width();
+        self.scores
+            .data()
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| self.method.is_better(**s, threshold))
+            .map(|(i, s)| (i % width, i / width, *s))
+            .collect()
+    }
+}
+
+fn planes<T: Type, C: Color, I: Image<T, C>>(image: &I) -> Vec<Vec<f64>> {
+    let channels = C::channels();
+    let n = if C::has_alpha() {
+        channels - 1
+    } else {
+        channels
+    };
+    (0..n)
+        .map(|c| {
+            image
+                .data()
+                .iter()
+                .skip(c)
+                .step_by(channels)
+                .map(|x| T::to_f(x))
+                .collect()
+        })
+        .collect()
+}
+
+/// Summed-area table with an extra leading row and column of zeros
+fn summed_area(data: &[f64], width: usize, height: usize, f: impl Fn(f64) -> f64) -> Vec<f64> {
+    let w = width + 1;
+    let mut table = vec![0.0; w * (height + 1)];
+    for y in 0..height {
+        let mut row = 0.0;
+        for x in 0..width {
+            row += f(data[y * width + x]);
+            table[(y + 1) * w + x + 1] = table[y * w + x + 1] + row;
+        }
+    }
+    table
+}
+
+fn window_sum(table: &[f64], width: usize, x: usize, y: usize, w: usize, h: usize) -> f64 {
+    let stride = width + 1;
+    table[(y + h) * stride + x + w] - table[y * stride + x + w] - table[(y + h) * stride + x]
+        + table[y * stride + x]
+}
+
+/// Correlate `image` with `template`, adding the result to `dest`
+fn correlate_direct(
+    image: &[f64],
+    (width, _height): (usize, usize),
+    template: &[f64],
+    (tw, th): (usize, usize),
+    dest: &mut [f64],
+    (ow, oh): (usize, usize),
+) {
+    for y in 0..oh {
+        for x in 0..ow {
+            let mut sum = 0.0;
+            for j in 0..th {
+                let row = &image[(y + j) * width + x..(y + j) * width + x + tw];
+                for (a, b) in row.iter().zip(&template[j * tw..(j + 1) * tw]) {
+                    sum += a * b;
+                }
+            }
+            dest[y * ow + x] += sum;
+        }
+    }
+}
+
+/// Correlate `image` with `template` using the FFT, adding the result to `dest`
+fn correlate_fft(
+    image: &[f64],
+    (width, height): (usize, usize),
+    template: &[f64],
+    (tw, _th): (usize, usize),
+    dest: &mut [f64],
+    (ow, oh): (usize, usize),
+) {
+    let pw = width.next_power_of_two();
+    let ph = height.next_power_of_two();
+    let mut a = fft::pad(image, width, pw, ph);
+    let mut b = fft::pad(template, tw, pw, ph);
+    fft::fft_2d(&mut a, pw, ph);
+    fft::fft_2d(&mut b, pw, ph);
+    for (x, y) in a.iter_mut().zip(&b) {
+        *x *= y.conj();
+    }
+    fft::ifft_2d(&mut a, pw, ph);
+
+    for y in 0..oh {
+        for x in 0..ow {
+            dest[y * ow + x] += a[y * pw + x].re;
+        }
+    }
+}
+
+fn match_template_impl<T: Type, C: Color, I: Image<T, C>, J: Image<T, C>>(
+    image: &I,
+    template: &J,
+    method: Method,
+    use_fft: bool,
+) -> Result<TemplateMatch, Error> {
+    let (width, height) = (image.width(), image.height());
+    let (tw, th) = (template.width(), template.height());
+    if tw == 0 || th == 0 || tw > width || th > height {
+        return Err(Error::Message(format!(
+            "Invalid template size: {}x{} for image of size {}x{}",
+            tw, th, width, height
+        )));
+    }
+
+    let (ow, oh) = (width - tw + 1, height - th + 1);
+    let n = (tw * th) as f64;
+    let image_planes = planes(image);
+    let template_planes = planes(template);
+
+    let mut cross = vec![0.0; ow * oh];
+    let mut sum_i = vec![0.0; ow * oh];
+    let mut sum_i2 = vec![0.0; ow * oh];
+    let (mut sum_t, mut sum_t2) = (0.0, 0.0);
+
+    for (ip, tp) in image_planes.iter().zip(&template_planes) {
+        if use_fft {
+            correlate_fft(ip, (width, height), tp, (tw, th), &mut cross, (ow, oh));
+        } else {
+            correlate_direct(ip, (width, height), tp, (tw, th), &mut cross, (ow, oh));
+        }
+
+        let s1 = summed_area(ip, width, height, |v| v);
+        let s2 = summed_area(ip, width, height, |v| v * v);
+        for y in 0..oh {
+            for x in 0..ow {
+                sum_i[y * ow + x] += window_sum(&s1, width, x, y, tw, th);
+                sum_i2[y * ow + x] += window_sum(&s2, width, x, y, tw, th);
+            }
+        }
+
+        sum_t += tp.iter().sum::<f64>();
+        sum_t2 += tp.iter().map(|v| v * v).sum::<f64>();
+    }
+
+    // Each channel contributes `n` values to the sums
+    let n = n * image_planes.len() as f64;
+    let mut scores: ImageBuf<f64, Gray> = ImageBuf::new(ow, oh);
+    for (i, s) in scores.data_mut().iter_mut().enumerate() {
+        *s = match method {
+            Method::Ssd => (sum_i2[i] - 2.0 * cross[i] + sum_t2).max(0.0),
+            Method::Ncc => {
+                let d = (sum_i2[i] * sum_t2).sqrt();
+                if d > 1e-12 {
+                    cross[i] / d
+                } else {
+                    0.0
+                }
+            }
+            Method::ZeroMeanNcc => {
+                let var_i = sum_i2[i] - sum_i[i] * sum_i[i] / n;
+                let var_t = sum_t2 - sum_t * sum_t / n;
+                let d = (var_i * var_t).max(0.0).sqrt();
+                if d > 1e-12 {
+                    ((cross[i] - sum_i[i] * sum_t / n) / d).clamp(-1.0, 1.0)
+                } else {
+                    0.0
+                }
+            }
+        };
+    }
+
+    Ok(TemplateMatch { method, scores })
+}
+
+/// Slide `template` over `image` and compute a matching score at each position, using
+/// normalized values of all color channels. The resulting score map has a size of
+/// `(image.width() - template.width() + 1, image.height() - template.height() + 1)`. Large
+/// templates are correlated in the frequency domain.
+pub fn match_template<T: Type, C: Color, I: Image<T, C>, J: Image<T, C>>(
+    image: &I,
+    template: &J,
+    method: Method,
+) -> Result<TemplateMatch, Error> {
+    let use_fft = template.width() * template.height() > FFT_THRESHOLD;
+    match_template_impl(image, template, method, use_fft)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::analysis::template::match_template_impl;
+    use crate::analysis::{match_template, Method};
+    use crate::{Image, ImageBuf, Rgb};
+
+    #[test]
+    fn test_match_template() {
+        let image: ImageBuf<u8, Rgb> = ImageBuf::new_from(
+            40,
+            30,
+            (0..40 * 30 * 3)
+                .map(|i: u64| {
+                    let x = (i ^ (i >> 3)).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+                    let x = (x ^ (x >> 31)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+                    (x >> 56) as u8
+                })
+                .collect(),
+        );
+        let template = image.crop(12, 9, 20, 15);
+
+        for method in &[Method::Ssd, Method::Ncc, Method::ZeroMeanNcc] {
+            let m = match_template(&image, &template, *method).unwrap();
+            assert_eq!(m.scores().width(), 21);
+            let (x, y, _) = m.best_match().unwrap();
+            assert_eq!((x, y), (12, 9));
+
+            let direct = match_template_impl(&image, &template, *method, false).unwrap();
+            for (a, b) in m.scores().data().iter().zip(direct.scores().data()) {
+                assert!((a - b).abs() < 1e-6);
+            }
+        }
+
+        let m = match_template(&image, &template, Method::ZeroMeanNcc).unwrap();
+        assert!((m.score(12, 9) - 1.0).abs() < 1e-9);
+        assert_eq!(m.matches(0.99).len(), 1);
+    }
+}
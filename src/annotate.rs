@@ -0,0 +1,274 @@
+//! Visualization of detection, pose estimation and segmentation results
+//!
+//! Each class is drawn using a distinct color from `class_color`. Text labels require the
+//! `text` feature and a font to be set in `Style`, otherwise only the label background is
+//! drawn.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::color::{Gray, Rgb};
+use crate::draw;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::pixel::PixelVec;
+use crate::rect::Rect;
+
+/// A detected object
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Detection {
+    pub bbox: Rect,
+    pub class: usize,
+    /// Text drawn above the bounding box, no label is drawn when empty
+    pub label: String,
+}
+
+/// A set of keypoints belonging to one object, points that were not detected are `None`
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pose {
+    pub class: usize,
+    pub points: Vec<Option<(f64, f64)>>,
+}
+
+/// A segmentation mask, pixels with a non-zero value belong to the object
+#[derive(Debug, Clone)]
+pub struct Mask {
+    pub class: usize,
+    pub mask: ImageBuf<u8, Gray>,
+}
+
+/// Everything drawn by `annotate`
+#[derive(Debug, Clone, Default)]
+pub struct Annotations {
+    pub detections: Vec<Detection>,
+    pub poses: Vec<Pose>,
+    /// Pairs of keypoint indices connected by a line when drawing poses
+    pub skeleton: Vec<(usize, usize)>,
+    pub masks: Vec<Mask>,
+}
+
+/// Drawing options
+#[derive(Debug, Clone)]
+pub struct Style {
+    /// Bounding box border width in pixels
+    pub thickness: usize,
+    /// Keypoint marker radius in pixels
+    pub keypoint_radius: f64,
+    /// Opacity of segmentation masks
+    pub mask_opacity: f64,
+    /// Font used for labels
+    #[cfg(feature = "text")]
+    pub font: Option<draw::Font>,
+    /// Label font size in pixels
+    pub font_size: f64,
+}
+
+impl Default for Style {
+    fn default() -> Style {
+        Style {
+            thickness: 2,
+            keypoint_radius: 3.0,
+            mask_opacity: 0.5,
+            #[cfg(feature = "text")]
+            font: None,
+            font_size: 14.0,
+        }
+    }
+}
+
+/// A color for the given class, consecutive classes are spread around the hue circle using the
+/// golden ratio so that they are easy to tell apart
+pub fn class_color(class: usize) -> PixelVec<f64> {
+    let h = (class as f64 * 0.618_033_988_749_895).fract() * 6.0;
+    let (s, v) = (0.75, 0.95);
+    let c = v * s;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as usize {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = v - c;
+    PixelVec::new(r + m, g + m, b + m, 1.0)
+}
+
+/// Black or white, whichever is more readable on `color`
+fn contrasting(color: &PixelVec<f64>) -> PixelVec<f64> {
+    let c = color.as_ref();
+    if c[0] * 0.21 + c[1] * 0.72 + c[2] * 0.07 > 0.5 {
+        PixelVec::new(0.0, 0.0, 0.0, 1.0)
+    } else {
+        PixelVec::new(1.0, 1.0, 1.0, 1.0)
+    }
+}
+
+/// Draw a label with a solid background above the top-left corner of `bbox`, or just inside of
+/// it when there is no room above
+#[cfg_attr(not(feature = "text"), allow(unused_variables))]
+fn label(
+    image: &mut ImageBuf<u8, Rgb>,
+    bbox: &Rect,
+    text: &str,
+    color: &PixelVec<f64>,
+    style: &Style,
+) {
+    let padding: f64 = 2.0;
+
+    #[cfg(feature = "text")]
+    let (width, height) = match &style.font {
+        Some(font) => {
+            let (w, h) = draw::text_size(text, font, style.font_size, &Default::default());
+            (w + 2.0 * padding, h + 2.0 * padding)
+        }
+        None => (0.0, style.font_size + 2.0 * padding),
+    };
+
+    #[cfg(not(feature = "text"))]
+    let (width, height): (f64, f64) = (0.0, style.font_size + 2.0 * padding);
+
+    // Without a font the label background is as wide as the box
+    let width = if width > 0.0 {
+        width.ceil() as usize
+    } else {
+        bbox.width
+    };
+    let height = height.ceil() as usize;
+    let y = if bbox.y >= height {
+        bbox.y - height
+    } else {
+        bbox.y
+    };
+    draw::rect_filled(image, Rect::new(bbox.x, y, width, height), color);
+
+    #[cfg(feature = "text")]
+    {
+        if let Some(font) = &style.font {
+            let pos = (bbox.x as f64 + padding, y as f64 + padding);
+            draw::text(image, text, font, style.font_size, pos, &contrasting(color));
+        }
+    }
+}
+
+/// Draw segmentation masks blended with the image
+pub fn masks(image: &mut ImageBuf<u8, Rgb>, masks: &[Mask], style: &Style) {
+    for mask in masks {
+        let color = class_color(mask.class);
+        let width = mask.mask.width().min(image.width());
+        let height = mask.mask.height().min(image.height());
+        for y in 0..height {
+            for x in 0..width {
+                if mask.mask.at(x, y)[0] != 0 {
+                    draw::plot(image, x as isize, y as isize, &color, style.mask_opacity);
+                }
+            }
+        }
+    }
+}
+
+/// Draw bounding boxes and their labels
+pub fn detections(image: &mut ImageBuf<u8, Rgb>, detections: &[Detection], style: &Style) {
+    for detection in detections {
+        let color = class_color(detection.class);
+        draw::rect(image, detection.bbox, &color, style.thickness);
+    }
+
+    // Labels are drawn last so they are not covered by other boxes
+    for detection in detections {
+        if !detection.label.is_empty() {
+            let color = class_color(detection.class);
+            label(image, &detection.bbox, &detection.label, &color, style);
+        }
+    }
+}
+
+/// Draw keypoints, connecting the pairs listed in `skeleton` when both points are present
+pub fn poses(
+    image: &mut ImageBuf<u8, Rgb>,
+    poses: &[Pose],
+    skeleton: &[(usize, usize)],
+    style: &Style,
+) {
+    for pose in poses {
+        let color = class_color(pose.class);
+        for (a, b) in skeleton {
+            let a = pose.points.get(*a).cloned().flatten();
+            let b = pose.points.get(*b).cloned().flatten();
+            if let (Some(a), Some(b)) = (a, b) {
+                draw::line_aa(image, a, b, &color, style.thickness as f64);
+            }
+        }
+
+        for point in pose.points.iter().flatten() {
+            draw::circle_filled(image, *point, style.keypoint_radius, &color, true);
+            draw::circle(
+                image,
+                *point,
+                style.keypoint_radius,
+                &contrasting(&color),
+                1.0,
+                true,
+            );
+        }
+    }
+}
+
+/// Draw masks, bounding boxes with labels and poses in a single call
+pub fn annotate(image: &mut ImageBuf<u8, Rgb>, annotations: &Annotations, style: &Style) {
+    masks(image, &annotations.masks, style);
+    detections(image, &annotations.detections, style);
+    poses(image, &annotations.poses, &annotations.skeleton, style);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::annotate::{self, Annotations, Detection, Mask, Pose, Style};
+    use crate::{Gray, Image, ImageBuf, Rect, Rgb};
+
+    #[test]
+    fn test_annotate() {
+        let mut image: ImageBuf<u8, Rgb> = ImageBuf::new(64, 64);
+        let mut mask: ImageBuf<u8, Gray> = ImageBuf::new(64, 64);
+        mask.at_mut(40, 40)[0] = 1;
+
+        let annotations = Annotations {
+            detections: vec![Detection {
+                bbox: Rect::new(10, 30, 20, 20),
+                class: 1,
+                label: String::from("cat"),
+            }],
+            poses: vec![Pose {
+                class: 2,
+                points: vec![Some((50.0, 10.0)), None, Some((50.0, 20.0))],
+            }],
+            skeleton: vec![(0, 1), (0, 2)],
+            masks: vec![Mask { class: 3, mask }],
+        };
+        annotate::annotate(&mut image, &annotations, &Style::default());
+
+        let box_color = annotate::class_color(1).map(|x| (x * 255.0) as u8);
+        assert_eq!(image.at(10, 40), &box_color.as_ref()[..3]);
+        assert_eq!(image.at(15, 40), &[0, 0, 0]);
+        // The label is drawn above the box
+        assert_eq!(image.at(12, 20), &box_color.as_ref()[..3]);
+
+        assert_ne!(image.at(50, 15), &[0, 0, 0]);
+        let mask_color = annotate::class_color(3);
+        assert_eq!(
+            image.at(40, 40)[0],
+            (mask_color.as_ref()[0] * 0.5 * 255.0) as u8
+        );
+        assert_eq!(image.at(41, 40), &[0, 0, 0]);
+
+        assert_ne!(
+            annotate::class_color(0).as_ref(),
+            annotate::class_color(1).as_ref()
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! Command line interface built on top of the image2 crate, enabled by the `cli` feature
+
+use std::env;
+use std::fs;
+use std::process;
+
+use image2::io::{guess_format, Format};
+use image2::{diff, metrics, transform, AnyImage, Color, Error, Image, ImageBuf, Type};
+
+const USAGE: &str = "Usage: image2 <command> [arguments]
+
+Commands:
+    info <input>...                                   Print the size, color and type of images
+    convert <input> <output>                          Convert between formats
+    resize <input> <output> <width> <height>          Resize an image, use 0 for either size to
+                                                      keep the aspect ratio
+    crop <input> <output> <x> <y> <width> <height>    Crop an image
+    diff <a> <b> [heatmap]                            Compare two images, exits with status 1 when
+                                                      they differ
+    montage <output> <columns> <input>...             Arrange images in a grid
+
+The output format is determined by the file extension";
+
+/// Apply `$e` to the image inside of `$any`, the result is converted back into an `AnyImage`
+macro_rules! map_image {
+    ($any:expr, $image:ident => $e:expr) => {
+        match $any {
+            AnyImage::Gray8($image) => AnyImage::from($e),
+            AnyImage::Rgb8($image) => AnyImage::from($e),
+            AnyImage::Rgba8($image) => AnyImage::from($e),
+            AnyImage::Gray16($image) => AnyImage::from($e),
+            AnyImage::Rgb16($image) => AnyImage::from($e),
+            AnyImage::Rgba16($image) => AnyImage::from($e),
+            AnyImage::GrayF32($image) => AnyImage::from($e),
+            AnyImage::RgbF32($image) => AnyImage::from($e),
+            AnyImage::RgbaF32($image) => AnyImage::from($e),
+        }
+    };
+}
+
+fn usage_error() -> Error {
+    Error::Message(USAGE.to_string())
+}
+
+fn parse_usize(name: &str, s: &str) -> Result<usize, Error> {
+    s.parse()
+        .map_err(|_| Error::Message(format!("Invalid {}: {}", name, s)))
+}
+
+fn sample_type(image: &AnyImage) -> &'static str {
+    match image {
+        AnyImage::Gray8(_) | AnyImage::Rgb8(_) | AnyImage::Rgba8(_) => "u8",
+        AnyImage::Gray16(_) | AnyImage::Rgb16(_) | AnyImage::Rgba16(_) => "u16",
+        AnyImage::GrayF32(_) | AnyImage::RgbF32(_) | AnyImage::RgbaF32(_) => "f32",
+    }
+}
+
+fn info(path: &str) -> Result<String, Error> {
+    let data = fs::read(path).map_err(|err| Error::from(err).with_path(path))?;
+    let format = guess_format(&data).or_else(|| Format::from_path(path));
+    let image = match AnyImage::decode(&data) {
+        Ok(image) => image,
+        // Formats that stb_image can't decode from memory are read by `open` using ImageMagick
+        Err(Error::InvalidImageData) | Err(Error::UnsupportedFormat(_)) => image2::open(path)?,
+        Err(err) => return Err(err.with_path(path)),
+    };
+    let mut s = format!(
+        "{}: {}x{} {} {}",
+        path,
+        image.width(),
+        image.height(),
+        image.color(),
+        sample_type(&image)
+    );
+    if let Some(format) = format {
+        s += &format!(" {:?}", format).to_lowercase();
+    }
+
+    let resolution = match &image {
+        AnyImage::Gray8(image) => image.resolution(),
+        AnyImage::Rgb8(image) => image.resolution(),
+        AnyImage::Rgba8(image) => image.resolution(),
+        AnyImage::Gray16(image) => image.resolution(),
+        AnyImage::Rgb16(image) => image.resolution(),
+        AnyImage::Rgba16(image) => image.resolution(),
+        AnyImage::GrayF32(image) => image.resolution(),
+        AnyImage::RgbF32(image) => image.resolution(),
+        AnyImage::RgbaF32(image) => image.resolution(),
+    };
+    if let Some((x, y)) = resolution.and_then(|r| r.to_dpi()) {
+        s += &format!(" {}x{} dpi", x.round(), y.round());
+    }
+    Ok(s)
+}
+
+fn crop<T: Type, C: Color>(
+    image: &ImageBuf<T, C>,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+) -> Result<ImageBuf<T, C>, Error> {
+    let inside = |start: usize, len: usize, max: usize| {
+        len > 0 && start.checked_add(len).map_or(false, |end| end <= max)
+    };
+    if !inside(x, width, image.width()) || !inside(y, height, image.height()) {
+        return Err(Error::Message(format!(
+            "Crop region {}x{}+{}+{} is outside of the {}x{} image",
+            width,
+            height,
+            x,
+            y,
+            image.width(),
+            image.height()
+        )));
+    }
+
+    Ok(image.crop(x, y, width, height))
+}
+
+/// Place each image in the center of a cell of the grid, every cell has the size of the largest
+/// image and the other images are converted to the type and color of `first`
+fn montage<T: Type, C: Color>(
+    first: &ImageBuf<T, C>,
+    rest: &[AnyImage],
+    columns: usize,
+) -> ImageBuf<T, C> {
+    let images: Vec<ImageBuf<T, C>> = std::iter::once(first.clone())
+        .chain(rest.iter().map(AnyImage::to_image))
+        .collect();
+    let cell_width = images.iter().map(|i| i.width()).max().unwrap_or(0);
+    let cell_height = images.iter().map(|i| i.height()).max().unwrap_or(0);
+    let rows = images.len().div_ceil(columns);
+    let mut dest = ImageBuf::new(cell_width * columns, cell_height * rows);
+    if cell_width == 0 || cell_height == 0 {
+        return dest;
+    }
+
+    dest.for_each(|(x, y), px| {
+        let index = (y / cell_height) * columns + x / cell_width;
+        let image = match images.get(index) {
+            Some(image) => image,
+            None => return,
+        };
+        let x = (x % cell_width) as isize - ((cell_width - image.width()) / 2) as isize;
+        let y = (y % cell_height) as isize - ((cell_height - image.height()) / 2) as isize;
+        if x >= 0 && y >= 0 && (x as usize) < image.width() && (y as usize) < image.height() {
+            px.copy_from_slice(image.at(x as usize, y as usize));
+        }
+    });
+    dest
+}
+
+/// Run a command, returns `false` when `diff` finds differences
+fn run(args: &[String]) -> Result<bool, Error> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["info", inputs @ ..] if !inputs.is_empty() => {
+            for input in inputs {
+                println!("{}", info(input)?);
+            }
+        }
+        ["convert", input, output] => {
+            image2::open(input)?.save(output)?;
+        }
+        ["resize", input, output, width, height] => {
+            let (width, height) = (parse_usize("width", width)?, parse_usize("height", height)?);
+            let image = image2::open(input)?;
+            map_image!(&image, image => transform::resized(image, width, height)).save(output)?;
+        }
+        ["crop", input, output, x, y, width, height] => {
+            let (x, y) = (parse_usize("x", x)?, parse_usize("y", y)?);
+            let (width, height) = (parse_usize("width", width)?, parse_usize("height", height)?);
+            let image = image2::open(input)?;
+            map_image!(&image, image => crop(image, x, y, width, height)?).save(output)?;
+        }
+        ["diff", a, b, heatmap @ ..] if heatmap.len() <= 1 => {
+            let a = image2::open(a)?.to_image::<f32, image2::Rgba>();
+            let b = image2::open(b)?.to_image::<f32, image2::Rgba>();
+            let visualization = diff::visualize(&a, &b)?;
+            let stats = visualization.stats;
+            println!(
+                "changed: {} of {} pixels ({:.2}%)\nmax: {:.4}\nmean: {:.4}\npsnr: {:.2} dB\nregions: {}",
+                stats.changed,
+                stats.total,
+                stats.changed_fraction() * 100.0,
+                stats.max,
+                stats.mean,
+                metrics::psnr(&a, &b)?,
+                visualization.regions.len()
+            );
+            if let [heatmap] = heatmap {
+                image2::save(heatmap, &AnyImage::from(visualization.heatmap))?;
+            }
+            return Ok(stats.is_identical());
+        }
+        ["montage", output, columns, inputs @ ..] if !inputs.is_empty() => {
+            let columns = parse_usize("columns", columns)?.max(1);
+            let images = inputs
+                .iter()
+                .map(image2::open)
+                .collect::<Result<Vec<_>, Error>>()?;
+            let image = map_image!(&images[0], first => montage(first, &images[1..], columns));
+            image.save(output)?;
+        }
+        _ => return Err(usage_error()),
+    }
+    Ok(true)
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match run(&args) {
+        Ok(true) => (),
+        Ok(false) => process::exit(1),
+        Err(err) => {
+            eprintln!("image2: {}", err);
+            process::exit(2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run_args(args: &[&str]) -> Result<bool, Error> {
+        run(&args.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_cli() {
+        assert!(run_args(&["info", "test/test.jpg"]).unwrap());
+        assert!(info("test/test.jpg").unwrap().contains(" rgb u8 jpeg"));
+
+        assert!(run_args(&["convert", "test/test.jpg", "test/test-cli.png"]).unwrap());
+        assert!(run_args(&["diff", "test/test-cli.png", "test/test-cli.png"]).unwrap());
+
+        let input = image2::open("test/test.jpg").unwrap();
+        run_args(&[
+            "resize",
+            "test/test.jpg",
+            "test/test-cli-resize.png",
+            "100",
+            "0",
+        ])
+        .unwrap();
+        let resized = image2::open("test/test-cli-resize.png").unwrap();
+        assert_eq!(resized.width(), 100);
+        assert_eq!(resized.height(), 100 * input.height() / input.width());
+
+        run_args(&[
+            "crop",
+            "test/test.jpg",
+            "test/test-cli-crop.png",
+            "10",
+            "20",
+            "30",
+            "40",
+        ])
+        .unwrap();
+        let cropped = image2::open("test/test-cli-crop.png").unwrap();
+        assert_eq!((cropped.width(), cropped.height()), (30, 40));
+        assert!(run_args(&[
+            "crop",
+            "test/test.jpg",
+            "test/test-cli-crop.png",
+            "0",
+            "0",
+            "0",
+            "1"
+        ])
+        .is_err());
+        let small: ImageBuf<u8, image2::Rgb> = ImageBuf::new(4, 4);
+        assert!(crop(&small, usize::MAX, 0, 2, 2).is_err());
+        assert!(crop(&small, 0, 1, 2, usize::MAX).is_err());
+
+        run_args(&[
+            "crop",
+            "test/test.jpg",
+            "test/test-cli-crop2.png",
+            "0",
+            "0",
+            "30",
+            "40",
+        ])
+        .unwrap();
+        let args = [
+            "diff",
+            "test/test-cli-crop.png",
+            "test/test-cli-crop2.png",
+            "test/test-cli-heatmap.png",
+        ];
+        assert!(!run_args(&args).unwrap());
+        assert!(run_args(&["diff", "test/test.jpg", "test/test-cli-resize.png"]).is_err());
+
+        run_args(&[
+            "montage",
+            "test/test-cli-montage.png",
+            "2",
+            "test/test-cli-crop.png",
+            "test/test-cli-resize.png",
+            "test/test-cli-crop.png",
+        ])
+        .unwrap();
+        let montage = image2::open("test/test-cli-montage.png").unwrap();
+        assert_eq!(montage.width(), 200);
+        assert_eq!(montage.height(), resized.height().max(40) * 2);
+
+        assert!(run_args(&["resize", "test/test.jpg"]).is_err());
+    }
+}
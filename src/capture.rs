@@ -0,0 +1,211 @@
+//! Synchronized capture from multiple webcams
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use image2::capture::SyncGroup;
+//! use image2::io::v4l::Webcam;
+//!
+//! let left = Webcam::builder("/dev/video0").fps(30).start()?;
+//! let right = Webcam::builder("/dev/video2").fps(30).start()?;
+//! let mut group = SyncGroup::new(vec![left, right], Duration::from_millis(10));
+//! for frames in group.by_ref().take(100) {
+//!     let frames = frames?;
+//!     println!("{:?} {:?}", frames[0].timestamp, frames[1].timestamp);
+//! }
+//! println!("{} frames had no match", group.discarded());
+//! # Ok::<(), image2::Error>(())
+//! ```
+//!
+//! Frames are matched using the timestamps reported by the drivers, which use the same monotonic
+//! clock for every camera on a machine. Cameras are read in turn on the calling thread, the
+//! oldest frame is discarded until the frames of every camera are within the tolerance of each
+//! other. Cameras running at the same frame rate with a tolerance of at least half the frame
+//! interval always match; hardware triggered cameras can use a much smaller tolerance.
+
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::io::v4l::{Frame, FrameCounter, Webcam};
+
+struct Camera {
+    webcam: Webcam,
+    counter: FrameCounter,
+    /// Latest frame that hasn't been matched yet
+    pending: Option<Frame>,
+}
+
+/// Captures sets of frames from several webcams, see the module documentation for an example
+pub struct SyncGroup {
+    cameras: Vec<Camera>,
+    tolerance: Duration,
+    discarded: u64,
+}
+
+/// Returns the frames when their timestamps are within `tolerance`, otherwise removes the frames
+/// that are too old to match the newest frame and returns how many were removed
+fn align(pending: &mut [Option<Frame>], tolerance: Duration) -> Result<Vec<Frame>, u64> {
+    let newest = pending
+        .iter()
+        .flatten()
+        .map(|frame| frame.timestamp)
+        .max()
+        .unwrap_or_default();
+
+    if pending
+        .iter()
+        .all(|frame| matches!(frame, Some(frame) if frame.timestamp + tolerance >= newest))
+    {
+        return Ok(pending.iter_mut().flat_map(Option::take).collect());
+    }
+
+    let mut removed = 0;
+    for frame in pending.iter_mut() {
+        if matches!(frame, Some(f) if f.timestamp + tolerance < newest) {
+            *frame = None;
+            removed += 1;
+        }
+    }
+    Err(removed)
+}
+
+impl SyncGroup {
+    /// Create a group from webcams that were already started, frames whose timestamps differ by
+    /// at most `tolerance` are returned together
+    pub fn new(webcams: Vec<Webcam>, tolerance: Duration) -> SyncGroup {
+        let cameras = webcams
+            .into_iter()
+            .map(|webcam| Camera {
+                counter: FrameCounter::new(webcam.interval()),
+                webcam,
+                pending: None,
+            })
+            .collect();
+
+        SyncGroup {
+            cameras,
+            tolerance,
+            discarded: 0,
+        }
+    }
+
+    /// Start every webcam that isn't capturing yet and create a group
+    pub fn start(mut webcams: Vec<Webcam>, tolerance: Duration) -> Result<SyncGroup, Error> {
+        for webcam in &mut webcams {
+            if webcam.config().is_none() {
+                webcam.start()?;
+            }
+        }
+        Ok(SyncGroup::new(webcams, tolerance))
+    }
+
+    /// Number of cameras in the group
+    pub fn len(&self) -> usize {
+        self.cameras.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cameras.is_empty()
+    }
+
+    pub fn tolerance(&self) -> Duration {
+        self.tolerance
+    }
+
+    pub fn webcam(&self, index: usize) -> &Webcam {
+        &self.cameras[index].webcam
+    }
+
+    /// Access a webcam to change its controls while capturing
+    pub fn webcam_mut(&mut self, index: usize) -> &mut Webcam {
+        &mut self.cameras[index].webcam
+    }
+
+    /// Number of frames that were discarded because no matching frame was captured by the other
+    /// cameras
+    pub fn discarded(&self) -> u64 {
+        self.discarded
+    }
+
+    /// Capture the next set of matching frames, with one frame for each camera in the order they
+    /// were added to the group
+    pub fn capture(&mut self) -> Result<Vec<Frame>, Error> {
+        loop {
+            for camera in &mut self.cameras {
+                if camera.pending.is_none() {
+                    let webcam = &mut camera.webcam;
+                    let frame = camera.counter.next(0, || webcam.capture_timestamp())?;
+                    camera.pending = Some(frame);
+                }
+            }
+
+            let mut pending: Vec<Option<Frame>> = self
+                .cameras
+                .iter_mut()
+                .map(|camera| camera.pending.take())
+                .collect();
+            let result = align(&mut pending, self.tolerance);
+            for (camera, frame) in self.cameras.iter_mut().zip(pending) {
+                camera.pending = frame;
+            }
+
+            match result {
+                Ok(frames) => return Ok(frames),
+                Err(removed) => self.discarded += removed,
+            }
+        }
+    }
+
+    /// Return the images of a processed set of frames to the webcams' pools, see
+    /// `Webcam::recycle`
+    pub fn recycle(&mut self, frames: Vec<Frame>) {
+        for (camera, frame) in self.cameras.iter_mut().zip(frames) {
+            camera.webcam.recycle(frame.image);
+        }
+    }
+}
+
+impl Iterator for SyncGroup {
+    type Item = Result<Vec<Frame>, Error>;
+
+    fn next(&mut self) -> Option<Result<Vec<Frame>, Error>> {
+        if self.cameras.is_empty() {
+            return None;
+        }
+        Some(self.capture())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ImageBuf;
+
+    fn frame(ms: u64) -> Option<Frame> {
+        Some(Frame {
+            image: ImageBuf::new(1, 1),
+            timestamp: Duration::from_millis(ms),
+            sequence: 0,
+            dropped: 0,
+        })
+    }
+
+    #[test]
+    fn test_capture_align() {
+        let tolerance = Duration::from_millis(5);
+
+        let mut pending = vec![frame(100), frame(103), frame(98)];
+        let frames = align(&mut pending, tolerance).unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[1].timestamp, Duration::from_millis(103));
+        assert!(pending.iter().all(Option::is_none));
+
+        // The first camera is a frame behind, only its frame is discarded
+        let mut pending = vec![frame(100), frame(133), frame(131)];
+        assert_eq!(align(&mut pending, tolerance), Err(1));
+        assert!(pending[0].is_none());
+        assert!(pending[1].is_some() && pending[2].is_some());
+
+        let mut pending = vec![frame(133), frame(133), frame(131)];
+        assert_eq!(align(&mut pending, tolerance).unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,196 @@
+//! Geometric correction of scanned documents
+
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::color::Color;
+use crate::feature::luminance;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::ty::Type;
+
+/// Default largest skew angle searched by `deskew`, in degrees
+pub const MAX_SKEW: f64 = 15.0;
+
+/// Step size of the coarse angle search in degrees
+const COARSE_STEP: f64 = 0.5;
+
+/// Step size of the fine angle search in degrees
+const FINE_STEP: f64 = 0.05;
+
+/// Coordinates of the ink pixels of a document, relative to the center of the image. Ink is
+/// assumed to be darker than the background unless most of the image is dark.
+fn ink<T: Type, C: Color, I: Image<T, C>>(image: &I) -> Vec<(f64, f64)> {
+    let (width, height) = (image.width(), image.height());
+    let gray = luminance(image);
+    let min = gray.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = gray.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    if max - min < 1e-6 {
+        return Vec::new();
+    }
+
+    let threshold = (min + max) / 2.0;
+    let dark = gray.iter().filter(|l| **l < threshold).count();
+    let invert = dark * 2 > gray.len();
+
+    let (cx, cy) = (width as f64 / 2.0, height as f64 / 2.0);
+    let mut points = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            if (gray[y * width + x] < threshold) != invert {
+                points.push((x as f64 - cx, y as f64 - cy));
+            }
+        }
+    }
+    points
+}
+
+/// Projection profile score: the sum of squared row counts after rotating the ink by `-angle`,
+/// which is largest when lines of text are horizontal
+fn score(points: &[(f64, f64)], angle: f64, offset: f64, rows: usize) -> f64 {
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let mut profile = vec![0.0; rows];
+    for (x, y) in points {
+        let v = -x * sin + y * cos + offset;
+        if v >= 0.0 && (v as usize) < rows {
+            profile[v as usize] += 1.0;
+        }
+    }
+    profile.iter().map(|p| p * p).sum()
+}
+
+/// Estimate the skew of a document in degrees using a projection profile, searching angles
+/// between `-max_angle` and `max_angle`. Positive angles are clockwise. Returns 0 for images
+/// without any contrast.
+pub fn skew_angle<T: Type, C: Color, I: Image<T, C>>(image: &I, max_angle: f64) -> f64 {
+    let points = ink(image);
+    if points.is_empty() {
+        return 0.0;
+    }
+
+    let (width, height) = (image.width() as f64, image.height() as f64);
+    let diagonal = (width * width + height * height).sqrt();
+    let rows = diagonal.ceil() as usize + 1;
+    let offset = diagonal / 2.0;
+
+    let search = |from: f64, to: f64, step: f64| {
+        let steps = ((to - from) / step).round() as isize;
+        let mut best: (f64, f64) = (0.0, f64::NEG_INFINITY);
+        for i in 0..=steps {
+            let angle = from + i as f64 * step;
+            let s = score(&points, angle, offset, rows);
+            // Prefer the smallest correction when scores are equal
+            if s > best.1 || (s == best.1 && angle.abs() < best.0.abs()) {
+                best = (angle, s);
+            }
+        }
+        best.0
+    };
+
+    let max_angle = max_angle.abs();
+    let coarse = search(-max_angle, max_angle, COARSE_STEP);
+    search(
+        (coarse - COARSE_STEP).max(-max_angle),
+        (coarse + COARSE_STEP).min(max_angle),
+        FINE_STEP,
+    )
+}
+
+/// Rotate `image` by `-angle` degrees around its center using bilinear interpolation. Pixels
+/// that fall outside of the source are filled with the mean color of the image border.
+fn unrotate<T: Type, C: Color, I: Image<T, C>>(image: &I, angle: f64) -> ImageBuf<T, C> {
+    let (width, height, channels) = image.shape();
+    let mut background = vec![0.0; channels];
+    let mut count = 0.0;
+    for y in 0..height {
+        for x in 0..width {
+            if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+                for (c, b) in background.iter_mut().enumerate() {
+                    *b += image.get_f(x, y, c);
+                }
+                count += 1.0;
+            }
+        }
+    }
+    background
+        .iter_mut()
+        .for_each(|b| *b /= f64::max(count, 1.0));
+
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let (cx, cy) = (width as f64 / 2.0, height as f64 / 2.0);
+    let mut dest = ImageBuf::new(width, height);
+    dest.copy_metadata(image);
+    dest.for_each(|(x, y), px| {
+        let (dx, dy) = (x as f64 - cx, y as f64 - cy);
+        let sx = cos * dx - sin * dy + cx;
+        let sy = sin * dx + cos * dy + cy;
+        if sx < 0.0 || sy < 0.0 || sx > (width - 1) as f64 || sy > (height - 1) as f64 {
+            for (item, b) in px.iter_mut().zip(&background) {
+                *item = T::from_f(*b);
+            }
+            return;
+        }
+
+        let (x0, y0) = (sx.floor() as usize, sy.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+        let (ax, ay) = (sx - x0 as f64, sy - y0 as f64);
+        for (c, item) in px.iter_mut().enumerate() {
+            let top = image.get_f(x0, y0, c) * (1.0 - ax) + image.get_f(x1, y0, c) * ax;
+            let bottom = image.get_f(x0, y1, c) * (1.0 - ax) + image.get_f(x1, y1, c) * ax;
+            *item = T::from_f(top * (1.0 - ay) + bottom * ay);
+        }
+    });
+    dest
+}
+
+/// Straighten a scanned document, returning the corrected image and the detected skew angle in
+/// degrees. Skew of up to `MAX_SKEW` degrees in either direction is detected.
+pub fn deskew<T: Type, C: Color, I: Image<T, C>>(image: &I) -> (ImageBuf<T, C>, f64) {
+    deskew_with(image, MAX_SKEW)
+}
+
+/// Like `deskew`, searching angles between `-max_angle` and `max_angle` degrees
+pub fn deskew_with<T: Type, C: Color, I: Image<T, C>>(
+    image: &I,
+    max_angle: f64,
+) -> (ImageBuf<T, C>, f64) {
+    let angle = skew_angle(image, max_angle);
+    (unrotate(image, angle), angle)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{correct, Gray, Image, ImageBuf};
+
+    /// Rows of dark words on a white page, rotated clockwise by `angle` degrees
+    fn page(angle: f64) -> ImageBuf<u8, Gray> {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let mut image: ImageBuf<u8, Gray> = ImageBuf::new(160, 120);
+        image.for_each(|(x, y), px| {
+            let (dx, dy) = (x as f64 - 80.0, y as f64 - 60.0);
+            let u = cos * dx + sin * dy + 80.0;
+            let v = -sin * dx + cos * dy + 60.0;
+            let text = (20.0..140.0).contains(&u)
+                && (20.0..100.0).contains(&v)
+                && v.rem_euclid(12.0) < 3.0
+                && u.rem_euclid(20.0) < 14.0;
+            px[0] = if text { 0 } else { 255 };
+        });
+        image
+    }
+
+    #[test]
+    fn test_deskew() {
+        for angle in &[3.0, -5.5] {
+            let (corrected, detected) = correct::deskew(&page(*angle));
+            assert!((detected - angle).abs() < 0.2, "{} {}", angle, detected);
+            assert!(corrected.at(40, 25)[0] < 64);
+            assert!(corrected.at(40, 30)[0] > 192);
+            assert_eq!(corrected.at(2, 2)[0], 255);
+        }
+
+        let blank: ImageBuf<u8, Gray> = ImageBuf::new(16, 16);
+        assert_eq!(correct::skew_angle(&blank, 10.0), 0.0);
+    }
+}
@@ -0,0 +1,286 @@
+//! Store labeled images in Arrow record batches and Parquet files
+//!
+//! Each row contains the raw pixel data as a binary column, along with the `width`, `height`,
+//! `channels`, `dtype` and `color` needed to decode it, an optional integer `label` and a map of
+//! string `metadata`. Pixel data is stored in little-endian byte order.
+//!
+//! ```rust,no_run
+//! use image2::{dataset::{self, Sample}, ImageBuf, Rgb};
+//!
+//! let image: ImageBuf<u8, Rgb> = ImageBuf::new(32, 32);
+//! let samples = vec![Sample::new(image).with_label(3).with_metadata("source", "camera0")];
+//! dataset::write_parquet("train.parquet", &samples)?;
+//!
+//! let samples: Vec<Sample<u8, Rgb>> = dataset::read_parquet("train.parquet")?;
+//! # Ok::<(), image2::Error>(())
+//! ```
+
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+use arrow_array::builder::{Int64Builder, LargeBinaryBuilder, MapBuilder, StringBuilder};
+use arrow_array::builder::{UInt32Builder, UInt8Builder};
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Int64Type, UInt32Type, UInt8Type};
+use arrow_array::{Array, ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Fields, Schema, SchemaRef};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+
+use crate::color::Color;
+use crate::error::{with_path, Error};
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::ty::{dtype_name, Type};
+
+/// An image with an optional label and metadata
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample<T: Type, C: Color> {
+    pub image: ImageBuf<T, C>,
+    pub label: Option<i64>,
+    pub metadata: Vec<(String, String)>,
+}
+
+impl<T: Type, C: Color> Sample<T, C> {
+    /// Create a new sample without a label or metadata
+    pub fn new(image: ImageBuf<T, C>) -> Sample<T, C> {
+        Sample {
+            image,
+            label: None,
+            metadata: Vec::new(),
+        }
+    }
+
+    /// Set the label
+    pub fn with_label(mut self, label: i64) -> Sample<T, C> {
+        self.label = Some(label);
+        self
+    }
+
+    /// Add a metadata entry
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.push((key.into(), value.into()));
+        self
+    }
+}
+
+fn metadata_type() -> DataType {
+    let entries = Fields::from(vec![
+        Field::new("keys", DataType::Utf8, false),
+        Field::new("values", DataType::Utf8, true),
+    ]);
+    DataType::Map(
+        Arc::new(Field::new("entries", DataType::Struct(entries), false)),
+        false,
+    )
+}
+
+/// The schema used for record batches and Parquet files
+pub fn schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("image", DataType::LargeBinary, false),
+        Field::new("width", DataType::UInt32, false),
+        Field::new("height", DataType::UInt32, false),
+        Field::new("channels", DataType::UInt8, false),
+        Field::new("dtype", DataType::Utf8, false),
+        Field::new("color", DataType::Utf8, false),
+        Field::new("label", DataType::Int64, true),
+        Field::new("metadata", metadata_type(), false),
+    ]))
+}
+
+/// Convert samples to a record batch
+pub fn to_record_batch<T: Type, C: Color>(samples: &[Sample<T, C>]) -> Result<RecordBatch, Error> {
+    let dtype = dtype_name::<T>();
+    let mut image = LargeBinaryBuilder::new();
+    let mut width = UInt32Builder::new();
+    let mut height = UInt32Builder::new();
+    let mut channels = UInt8Builder::new();
+    let mut dtypes = StringBuilder::new();
+    let mut colors = StringBuilder::new();
+    let mut label = Int64Builder::new();
+    let mut metadata = MapBuilder::new(None, StringBuilder::new(), StringBuilder::new());
+
+    for sample in samples {
+        if cfg!(target_endian = "little") {
+            image.append_value(sample.image.buffer());
+        } else {
+            let size = std::mem::size_of::<T>();
+            let bytes: Vec<u8> = sample
+                .image
+                .buffer()
+                .chunks(size)
+                .flat_map(|x| x.iter().rev())
+                .copied()
+                .collect();
+            image.append_value(bytes);
+        }
+        width.append_value(sample.image.width() as u32);
+        height.append_value(sample.image.height() as u32);
+        channels.append_value(C::channels() as u8);
+        dtypes.append_value(&dtype);
+        colors.append_value(C::name());
+        label.append_option(sample.label);
+        for (key, value) in &sample.metadata {
+            metadata.keys().append_value(key);
+            metadata.values().append_value(value);
+        }
+        metadata.append(true)?;
+    }
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(image.finish()),
+        Arc::new(width.finish()),
+        Arc::new(height.finish()),
+        Arc::new(channels.finish()),
+        Arc::new(dtypes.finish()),
+        Arc::new(colors.finish()),
+        Arc::new(label.finish()),
+        Arc::new(metadata.finish()),
+    ];
+    Ok(RecordBatch::try_new(schema(), columns)?)
+}
+
+fn column<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a ArrayRef, Error> {
+    batch
+        .column_by_name(name)
+        .ok_or_else(|| Error::Message(format!("Missing column: {}", name)))
+}
+
+/// Convert a record batch to samples, returning an error if the element type or color of any
+/// image does not match
+pub fn from_record_batch<T: Type, C: Color>(
+    batch: &RecordBatch,
+) -> Result<Vec<Sample<T, C>>, Error> {
+    let mismatch = |name: &str| Error::Message(format!("Unexpected type for column: {}", name));
+    let image = column(batch, "image")?
+        .as_binary_opt::<i64>()
+        .ok_or_else(|| mismatch("image"))?;
+    let width = column(batch, "width")?
+        .as_primitive_opt::<UInt32Type>()
+        .ok_or_else(|| mismatch("width"))?;
+    let height = column(batch, "height")?
+        .as_primitive_opt::<UInt32Type>()
+        .ok_or_else(|| mismatch("height"))?;
+    let channels = column(batch, "channels")?
+        .as_primitive_opt::<UInt8Type>()
+        .ok_or_else(|| mismatch("channels"))?;
+    let dtype = column(batch, "dtype")?
+        .as_string_opt::<i32>()
+        .ok_or_else(|| mismatch("dtype"))?;
+    let label = column(batch, "label")?
+        .as_primitive_opt::<Int64Type>()
+        .ok_or_else(|| mismatch("label"))?;
+    let metadata = column(batch, "metadata")?
+        .as_map_opt()
+        .ok_or_else(|| mismatch("metadata"))?;
+
+    let expected = dtype_name::<T>();
+    let mut samples = Vec::with_capacity(batch.num_rows());
+    for i in 0..batch.num_rows() {
+        if dtype.value(i) != expected {
+            return Err(Error::InvalidType);
+        }
+
+        if channels.value(i) as usize != C::channels() {
+            return Err(Error::InvalidColor);
+        }
+
+        let mut dest = ImageBuf::<T, C>::new(width.value(i) as usize, height.value(i) as usize);
+        let bytes = image.value(i);
+        if bytes.len() != dest.total_bytes() {
+            return Err(Error::Message(format!(
+                "Expected {} bytes of image data, got {}",
+                dest.total_bytes(),
+                bytes.len()
+            )));
+        }
+
+        let size = std::mem::size_of::<T>();
+        let data = dest.data_mut();
+        let data = unsafe {
+            std::slice::from_raw_parts_mut(
+                data.as_mut_ptr() as *mut u8,
+                std::mem::size_of_val(data),
+            )
+        };
+        data.copy_from_slice(bytes);
+        if cfg!(target_endian = "big") {
+            data.chunks_mut(size).for_each(|x| x.reverse());
+        }
+
+        let entries = metadata.value(i);
+        let keys = entries.column(0).as_string::<i32>();
+        let values = entries.column(1).as_string::<i32>();
+        let metadata = (0..entries.len())
+            .map(|j| (keys.value(j).to_string(), values.value(j).to_string()))
+            .collect();
+
+        samples.push(Sample {
+            image: dest,
+            label: if label.is_null(i) {
+                None
+            } else {
+                Some(label.value(i))
+            },
+            metadata,
+        });
+    }
+
+    Ok(samples)
+}
+
+/// Write samples to a Parquet file
+pub fn write_parquet<P: AsRef<Path>, T: Type, C: Color>(
+    path: P,
+    samples: &[Sample<T, C>],
+) -> Result<(), Error> {
+    with_path(path, |path| {
+        let mut writer = ArrowWriter::try_new(File::create(path)?, schema(), None)?;
+        writer.write(&to_record_batch(samples)?)?;
+        writer.close()?;
+        Ok(())
+    })
+}
+
+/// Read all samples from a Parquet file
+pub fn read_parquet<P: AsRef<Path>, T: Type, C: Color>(
+    path: P,
+) -> Result<Vec<Sample<T, C>>, Error> {
+    with_path(path, |path| {
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?.build()?;
+        let mut samples = Vec::new();
+        for batch in reader {
+            samples.extend(from_record_batch(&batch?)?);
+        }
+        Ok(samples)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Gray, Rgb};
+
+    #[test]
+    fn test_dataset() {
+        let mut a: ImageBuf<u16, Rgb> = ImageBuf::new(3, 2);
+        a.at_mut(2, 1).copy_from_slice(&[1, 500, 65535]);
+        let b: ImageBuf<u16, Rgb> = ImageBuf::new(1, 4);
+        let samples = vec![
+            Sample::new(a).with_label(7).with_metadata("source", "test"),
+            Sample::new(b),
+        ];
+
+        let batch = to_record_batch(&samples).unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(from_record_batch::<u16, Rgb>(&batch).unwrap(), samples);
+        assert!(from_record_batch::<u8, Rgb>(&batch).is_err());
+        assert!(from_record_batch::<u16, Gray>(&batch).is_err());
+
+        write_parquet("test/test-dataset.parquet", &samples).unwrap();
+        let read: Vec<Sample<u16, Rgb>> = read_parquet("test/test-dataset.parquet").unwrap();
+        assert_eq!(read, samples);
+    }
+}
@@ -0,0 +1,246 @@
+//! Discrete cosine transforms
+//!
+//! Orthonormal DCT-II and its inverse (DCT-III), for whole images or independent 8x8 blocks as
+//! used by JPEG. Power-of-two lengths are computed using the FFT.
+
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::color::Color;
+use crate::fft::{self, Complex};
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::ty::Type;
+
+use core::f64::consts::PI;
+
+/// Size of the blocks used by `forward_blocks` and `inverse_blocks`
+pub const BLOCK_SIZE: usize = 8;
+
+#[inline]
+fn scale(k: usize, n: usize) -> f64 {
+    if k == 0 {
+        (1.0 / n as f64).sqrt()
+    } else {
+        (2.0 / n as f64).sqrt()
+    }
+}
+
+fn dct_direct(data: &[f64], dest: &mut [f64]) {
+    let n = data.len();
+    for (k, d) in dest.iter_mut().enumerate() {
+        let sum: f64 = data
+            .iter()
+            .enumerate()
+            .map(|(i, x)| x * (PI * (2 * i + 1) as f64 * k as f64 / (2 * n) as f64).cos())
+            .sum();
+        *d = sum * scale(k, n);
+    }
+}
+
+fn idct_direct(data: &[f64], dest: &mut [f64]) {
+    let n = data.len();
+    for (i, d) in dest.iter_mut().enumerate() {
+        *d = data
+            .iter()
+            .enumerate()
+            .map(|(k, x)| {
+                x * scale(k, n) * (PI * (2 * i + 1) as f64 * k as f64 / (2 * n) as f64).cos()
+            })
+            .sum();
+    }
+}
+
+/// DCT using an FFT of the same length (Makhoul's algorithm)
+fn dct_fft(data: &[f64], dest: &mut [f64]) {
+    let n = data.len();
+    let mut v = vec![Complex::new(0.0, 0.0); n];
+    for k in 0..n / 2 {
+        v[k] = Complex::new(data[2 * k], 0.0);
+        v[n - 1 - k] = Complex::new(data[2 * k + 1], 0.0);
+    }
+    fft::fft(&mut v);
+
+    for (k, d) in dest.iter_mut().enumerate() {
+        let w = fft::from_polar(1.0, -PI * k as f64 / (2 * n) as f64);
+        *d = (v[k] * w).re * scale(k, n);
+    }
+}
+
+fn idct_fft(data: &[f64], dest: &mut [f64]) {
+    let n = data.len();
+    let c = |k: usize| if k < n { data[k] / scale(k, n) } else { 0.0 };
+    let mut v: Vec<Complex<f64>> = (0..n)
+        .map(|k| {
+            let w = fft::from_polar(1.0, PI * k as f64 / (2 * n) as f64);
+            if k == 0 {
+                Complex::new(c(0), 0.0)
+            } else {
+                w * Complex::new(c(k), -c(n - k))
+            }
+        })
+        .collect();
+    fft::ifft(&mut v);
+
+    for k in 0..n / 2 {
+        dest[2 * k] = v[k].re;
+        dest[2 * k + 1] = v[n - 1 - k].re;
+    }
+}
+
+/// One-dimensional forward transform
+pub fn dct(data: &[f64]) -> Vec<f64> {
+    let mut dest = vec![0.0; data.len()];
+    if data.len() > 1 && data.len().is_power_of_two() {
+        dct_fft(data, &mut dest);
+    } else {
+        dct_direct(data, &mut dest);
+    }
+    dest
+}
+
+/// One-dimensional inverse transform
+pub fn idct(data: &[f64]) -> Vec<f64> {
+    let mut dest = vec![0.0; data.len()];
+    if data.len() > 1 && data.len().is_power_of_two() {
+        idct_fft(data, &mut dest);
+    } else {
+        idct_direct(data, &mut dest);
+    }
+    dest
+}
+
+fn transform_2d(data: &mut [f64], width: usize, height: usize, f: fn(&[f64]) -> Vec<f64>) {
+    assert_eq!(data.len(), width * height);
+
+    for row in data.chunks_mut(width) {
+        let out = f(row);
+        row.copy_from_slice(&out);
+    }
+
+    let mut column = vec![0.0; height];
+    for x in 0..width {
+        for (y, c) in column.iter_mut().enumerate() {
+            *c = data[y * width + x];
+        }
+        for (y, c) in f(&column).into_iter().enumerate() {
+            data[y * width + x] = c;
+        }
+    }
+}
+
+/// In-place two-dimensional forward transform of row-major data
+pub fn dct_2d(data: &mut [f64], width: usize, height: usize) {
+    transform_2d(data, width, height, dct)
+}
+
+/// In-place two-dimensional inverse transform of row-major data
+pub fn idct_2d(data: &mut [f64], width: usize, height: usize) {
+    transform_2d(data, width, height, idct)
+}
+
+/// Apply `f` to each channel of each `block_size` x `block_size` block, blocks at the right and
+/// bottom edges may be smaller
+fn blocks<T: Type, C: Color, I: Image<T, C>, U: Type, F: Fn(&mut [f64], usize, usize)>(
+    image: &I,
+    block_size: usize,
+    input: impl Fn(&T) -> f64,
+    output: impl Fn(f64) -> U,
+    f: F,
+) -> ImageBuf<U, C> {
+    let (width, height, channels) = image.shape();
+    let mut dest = ImageBuf::new(width, height);
+    // The whole image is a single block in `forward`, so the block size is zero for empty images
+    if width == 0 || height == 0 {
+        return dest;
+    }
+
+    let mut block = Vec::with_capacity(block_size * block_size);
+    for by in (0..height).step_by(block_size) {
+        let bh = block_size.min(height - by);
+        for bx in (0..width).step_by(block_size) {
+            let bw = block_size.min(width - bx);
+            for c in 0..channels {
+                block.clear();
+                for y in by..by + bh {
+                    for x in bx..bx + bw {
+                        block.push(input(&image.at(x, y)[c]));
+                    }
+                }
+
+                f(&mut block, bw, bh);
+
+                for (i, v) in block.iter().enumerate() {
+                    dest.at_mut(bx + i % bw, by + i / bw)[c] = output(*v);
+                }
+            }
+        }
+    }
+    dest
+}
+
+/// Transform each channel of the whole image, using normalized values. The resulting
+/// coefficients are not clamped.
+pub fn forward<T: Type, C: Color, I: Image<T, C>>(image: &I) -> ImageBuf<f64, C> {
+    let size = image.width().max(image.height());
+    blocks(image, size, T::to_f, |v| v, dct_2d)
+}
+
+/// Inverse of `forward`
+pub fn inverse<T: Type, C: Color, I: Image<f64, C>>(coefficients: &I) -> ImageBuf<T, C> {
+    let size = coefficients.width().max(coefficients.height());
+    blocks(coefficients, size, |v| *v, T::from_f, idct_2d)
+}
+
+/// Transform each 8x8 block of the image independently, using normalized values
+pub fn forward_blocks<T: Type, C: Color, I: Image<T, C>>(image: &I) -> ImageBuf<f64, C> {
+    blocks(image, BLOCK_SIZE, T::to_f, |v| v, dct_2d)
+}
+
+/// Inverse of `forward_blocks`
+pub fn inverse_blocks<T: Type, C: Color, I: Image<f64, C>>(coefficients: &I) -> ImageBuf<T, C> {
+    blocks(coefficients, BLOCK_SIZE, |v| *v, T::from_f, idct_2d)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dct::{self, dct_direct};
+    use crate::{Gray, Image, ImageBuf};
+
+    #[test]
+    fn test_dct() {
+        let data: Vec<f64> = (0..8).map(|i| ((i * 5) % 7) as f64).collect();
+        let mut expected = vec![0.0; 8];
+        dct_direct(&data, &mut expected);
+        let coefficients = dct::dct(&data);
+        for (a, b) in coefficients.iter().zip(&expected) {
+            assert!((a - b).abs() < 1e-9);
+        }
+        for (a, b) in dct::idct(&coefficients).iter().zip(&data) {
+            assert!((a - b).abs() < 1e-9);
+        }
+
+        let image: ImageBuf<u8, Gray> =
+            ImageBuf::new_from(12, 10, (0..120).map(|i| (i * 37 % 256) as u8).collect());
+        let coefficients = dct::forward_blocks(&image);
+        let block = dct::forward(&image.crop(0, 0, 8, 8));
+        assert!((coefficients.at(3, 5)[0] - block.at(3, 5)[0]).abs() < 1e-9);
+
+        // Converting back to u8 truncates, so allow an error of 1
+        let output: ImageBuf<u8, Gray> = dct::inverse_blocks(&coefficients);
+        for (a, b) in output.data().iter().zip(image.data()) {
+            assert!((*a as i32 - *b as i32).abs() <= 1);
+        }
+        let output: ImageBuf<u8, Gray> = dct::inverse(&dct::forward(&image));
+        for (a, b) in output.data().iter().zip(image.data()) {
+            assert!((*a as i32 - *b as i32).abs() <= 1);
+        }
+
+        let empty: ImageBuf<u8, Gray> = ImageBuf::new(0, 0);
+        let coefficients = dct::forward(&empty);
+        assert_eq!(coefficients.shape(), (0, 0, 1));
+        let output: ImageBuf<u8, Gray> = dct::inverse(&coefficients);
+        assert_eq!(output.shape(), (0, 0, 1));
+    }
+}
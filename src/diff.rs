@@ -0,0 +1,228 @@
+//! Visual differences between images
+//!
+//! `visualize` compares two images of the same size and produces a heatmap of the changes along
+//! with summary statistics and the bounding boxes of changed regions, which is useful for
+//! screenshot regression testing.
+
+use alloc::vec::Vec;
+
+use crate::analysis::{connected_components, Connectivity};
+use crate::color::{Color, Gray, Rgb};
+use crate::error::Error;
+use crate::gradient::Gradient;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::pixel::PixelVec;
+use crate::rect::Rect;
+use crate::ty::Type;
+
+/// Options used by `visualize_with`
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Pixels are considered changed when the difference of any channel is greater than this
+    /// normalized value
+    pub threshold: f64,
+    /// Changed pixels within this many pixels of each other are grouped into the same region
+    pub merge_distance: usize,
+    /// Maps differences, scaled so the largest difference is 1, onto heatmap colors
+    pub gradient: Gradient,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            threshold: 0.0,
+            merge_distance: 2,
+            gradient: Gradient::from_colors(&[
+                PixelVec::new(0.0, 0.0, 1.0, 1.0),
+                PixelVec::new(1.0, 0.0, 0.0, 1.0),
+                PixelVec::new(1.0, 1.0, 0.0, 1.0),
+            ]),
+        }
+    }
+}
+
+/// Summary of the differences between two images
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Stats {
+    /// Number of changed pixels
+    pub changed: usize,
+    /// Total number of pixels
+    pub total: usize,
+    /// Largest normalized difference
+    pub max: f64,
+    /// Mean normalized difference over all pixels
+    pub mean: f64,
+}
+
+impl Stats {
+    /// The fraction of pixels that changed
+    pub fn changed_fraction(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+
+        self.changed as f64 / self.total as f64
+    }
+
+    /// Returns true if no pixels changed
+    pub fn is_identical(&self) -> bool {
+        self.changed == 0
+    }
+}
+
+/// The result of `visualize`
+#[derive(Debug, Clone)]
+pub struct Visualization {
+    /// Changed pixels are colored by the size of the difference, unchanged pixels show a dimmed
+    /// grayscale version of the first image
+    pub heatmap: ImageBuf<u8, Rgb>,
+    pub stats: Stats,
+    /// Bounding boxes of changed regions in raster order
+    pub regions: Vec<Rect>,
+}
+
+/// Compare two images using the default options
+pub fn visualize<T: Type, U: Type, C: Color, A: Image<T, C>, B: Image<U, C>>(
+    a: &A,
+    b: &B,
+) -> Result<Visualization, Error> {
+    visualize_with(a, b, &Options::default())
+}
+
+/// Compare two images, the difference of each pixel is the largest normalized difference of
+/// its channels
+pub fn visualize_with<T: Type, U: Type, C: Color, A: Image<T, C>, B: Image<U, C>>(
+    a: &A,
+    b: &B,
+    options: &Options,
+) -> Result<Visualization, Error> {
+    let (width, height) = (a.width(), a.height());
+    if width != b.width() || height != b.height() {
+        return Err(Error::Message(format!(
+            "Image sizes do not match: {}x{} and {}x{}",
+            width,
+            height,
+            b.width(),
+            b.height()
+        )));
+    }
+
+    let channels = C::channels();
+    let diff: Vec<f64> = a
+        .data()
+        .chunks(channels)
+        .zip(b.data().chunks(channels))
+        .map(|(pa, pb)| {
+            pa.iter()
+                .zip(pb)
+                .map(|(x, y)| (T::to_f(x) - U::to_f(y)).abs())
+                .fold(0.0, f64::max)
+        })
+        .collect();
+
+    let total = width * height;
+    let changed: Vec<bool> = diff.iter().map(|d| *d > options.threshold).collect();
+    let stats = Stats {
+        changed: changed.iter().filter(|c| **c).count(),
+        total,
+        max: diff.iter().cloned().fold(0.0, f64::max),
+        mean: diff.iter().sum::<f64>() / total.max(1) as f64,
+    };
+
+    let mut heatmap = ImageBuf::new(width, height);
+    heatmap.for_each(|(x, y), px| {
+        let i = y * width + x;
+        if changed[i] {
+            let color = options.gradient.at(diff[i] / stats.max);
+            for (item, v) in px.iter_mut().zip(color.as_ref()) {
+                *item = u8::from_f(*v);
+            }
+        } else {
+            let src = a.at(x, y);
+            let l = if channels < 3 {
+                T::to_f(&src[0])
+            } else {
+                T::to_f(&src[0]) * 0.21 + T::to_f(&src[1]) * 0.72 + T::to_f(&src[2]) * 0.07
+            };
+            let v = u8::from_f(l * 0.3);
+            px.iter_mut().for_each(|item| *item = v);
+        }
+    });
+
+    Ok(Visualization {
+        heatmap,
+        stats,
+        regions: regions(&changed, width, height, options.merge_distance),
+    })
+}
+
+/// Group changed pixels into regions by dilating the changes by `distance` pixels and labeling
+/// the connected components of the result
+fn regions(changed: &[bool], width: usize, height: usize, distance: usize) -> Vec<Rect> {
+    let mut mask: ImageBuf<u8, Gray> = ImageBuf::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            if !changed[y * width + x] {
+                continue;
+            }
+
+            for j in y.saturating_sub(distance)..(y + distance + 1).min(height) {
+                for i in x.saturating_sub(distance)..(x + distance + 1).min(width) {
+                    mask.at_mut(i, j)[0] = 255;
+                }
+            }
+        }
+    }
+
+    // The components of the dilated mask are larger than the changes, so the bounding boxes
+    // are computed from the changed pixels only
+    let (labels, components) = connected_components(&mask, Connectivity::Eight);
+    let mut regions = vec![Rect::default(); components.len()];
+    for y in 0..height {
+        for x in 0..width {
+            if changed[y * width + x] {
+                let label = labels.at(x, y)[0] as usize;
+                regions[label - 1] = regions[label - 1].union(&Rect::point(x, y));
+            }
+        }
+    }
+    regions
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{diff, Image, ImageBuf, Rect, Rgb};
+
+    #[test]
+    fn test_visualize() {
+        let a: ImageBuf<u8, Rgb> = ImageBuf::new(32, 16);
+        let mut b = Image::clone(&a);
+        b.at_mut(3, 4)[0] = 255;
+        b.at_mut(5, 5)[1] = 128;
+        b.at_mut(20, 10)[2] = 64;
+
+        let result = diff::visualize(&a, &b).unwrap();
+        assert_eq!(result.stats.changed, 3);
+        assert_eq!(result.stats.total, 512);
+        assert_eq!(result.stats.max, 1.0);
+        assert_eq!(
+            result.regions,
+            vec![Rect::new(3, 4, 3, 2), Rect::new(20, 10, 1, 1)]
+        );
+        assert_eq!(result.heatmap.at(3, 4), &[255, 255, 0]);
+        assert_eq!(result.heatmap.at(0, 0), &[0, 0, 0]);
+
+        let options = diff::Options {
+            threshold: 0.3,
+            ..Default::default()
+        };
+        let result = diff::visualize_with(&a, &b, &options).unwrap();
+        assert_eq!(result.stats.changed, 2);
+        assert!(diff::visualize(&a, &a).unwrap().stats.is_identical());
+
+        let c: ImageBuf<u8, Rgb> = ImageBuf::new(16, 16);
+        assert!(diff::visualize(&a, &c).is_err());
+    }
+}
@@ -0,0 +1,46 @@
+use super::{base64, to_rgb8};
+use crate::color::{Color, Rgb};
+use crate::error::Error;
+use crate::facade::Image2;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::io;
+use crate::ty::Type;
+
+/// PNG encoded image wrapped in the markers evcxr uses to detect rich output
+pub(super) fn evcxr_content<T: Type, C: Color, I: Image<T, C>>(image: &I) -> Result<String, Error> {
+    let png = match C::name() {
+        "gray" | "rgb" | "rgba" => io::encode_png(image)?,
+        _ => {
+            let (width, height) = (image.width(), image.height());
+            let data = to_rgb8(image).into_iter().flatten().collect();
+            io::encode_png_u8(&ImageBuf::<u8, Rgb>::new_from(width, height, data))?
+        }
+    };
+    Ok(format!(
+        "EVCXR_BEGIN_CONTENT image/png\n{}\nEVCXR_END_CONTENT",
+        base64(&png)
+    ))
+}
+
+fn evcxr_display<T: Type, C: Color, I: Image<T, C>>(image: &I) {
+    match evcxr_content(image) {
+        Ok(content) => println!("{}", content),
+        Err(err) => eprintln!("Unable to display image: {:?}", err),
+    }
+}
+
+impl<T: Type, C: Color> ImageBuf<T, C> {
+    /// Display the image inline in Jupyter notebooks using the evcxr kernel, which calls this
+    /// method automatically when an image is the result of a cell
+    pub fn evcxr_display(&self) {
+        evcxr_display(self)
+    }
+}
+
+impl Image2 {
+    /// Display the image inline in Jupyter notebooks using the evcxr kernel
+    pub fn evcxr_display(&self) {
+        evcxr_display(self.as_image_buf())
+    }
+}
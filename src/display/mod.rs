@@ -0,0 +1,187 @@
+//! Display images for debugging, either in the terminal or in a window
+//!
+//! ```rust,no_run
+//! use image2::{display, io, ImageBuf, Rgb};
+//!
+//! let image: ImageBuf<f32, Rgb> = io::read("test/test.jpg")?;
+//! display::print_terminal(&image)?;
+//! # Ok::<(), image2::Error>(())
+//! ```
+//!
+//! With the `display` feature enabled, `show` opens a window and blocks until it is closed:
+//! - Scroll to zoom around the cursor, or use `+` and `-`
+//! - Drag with the left mouse button to pan
+//! - `0` fits the image to the window and `1` shows it at its actual size
+//! - `Escape` or `Q` closes the window
+//!
+//! The position and value of the pixel under the cursor are shown in the title bar.
+//!
+//! With the `evcxr` feature enabled, `ImageBuf` and `Image2` are displayed inline in Jupyter
+//! notebooks using the [evcxr](https://github.com/evcxr/evcxr) kernel.
+
+#[cfg(feature = "evcxr")]
+mod evcxr;
+mod terminal;
+#[cfg(feature = "display")]
+mod window;
+
+pub use self::terminal::*;
+#[cfg(feature = "display")]
+pub use self::window::show;
+
+use crate::color::Color;
+use crate::image::Image;
+use crate::ty::Type;
+
+/// Gray level that transparent pixels are blended with
+const BACKGROUND: u8 = 0x30;
+
+/// Convert an image to 8-bit RGB, grayscale values are copied to each color channel and alpha
+/// is blended over the background
+fn to_rgb8<T: Type, C: Color, I: Image<T, C>>(image: &I) -> Vec<[u8; 3]> {
+    let channels = C::channels();
+    let bgr = C::name().starts_with("bgr");
+    let background = f64::from(BACKGROUND) / 255.0;
+    image
+        .data()
+        .chunks(channels)
+        .map(|px| {
+            let alpha = if C::has_alpha() {
+                px[channels - 1].to_f()
+            } else {
+                1.0
+            };
+            let rgb = if channels < 3 {
+                [px[0].to_f(); 3]
+            } else if bgr {
+                [px[2].to_f(), px[1].to_f(), px[0].to_f()]
+            } else {
+                [px[0].to_f(), px[1].to_f(), px[2].to_f()]
+            };
+            rgb.map(|c| {
+                ((c * alpha + background * (1.0 - alpha)).clamp(0.0, 1.0) * 255.0).round() as u8
+            })
+        })
+        .collect()
+}
+
+/// Standard base64 encoding with padding
+fn base64(data: &[u8]) -> String {
+    const TABLE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(TABLE[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Gray, ImageBuf, Rgb, Rgba};
+
+    #[test]
+    fn test_display_terminal() {
+        assert_eq!(base64(b"image2"), "aW1hZ2Uy");
+        assert_eq!(base64(b"ab"), "YWI=");
+        assert_eq!(base64(b"a"), "YQ==");
+
+        let mut rgba: ImageBuf<u8, Rgba> = ImageBuf::new(2, 1);
+        rgba.at_mut(0, 0).copy_from_slice(&[255, 0, 0, 255]);
+        assert_eq!(to_rgb8(&rgba), vec![[255, 0, 0], [0x30; 3]]);
+
+        let mut image: ImageBuf<u8, Rgb> = ImageBuf::new(2, 2);
+        image.at_mut(0, 0).copy_from_slice(&[255, 0, 0]);
+        image.at_mut(1, 1).copy_from_slice(&[0, 0, 255]);
+
+        let mut out = Vec::new();
+        write_terminal(&mut out, &image, TerminalProtocol::HalfBlock, 80).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\x1b[38;2;255;0;0m\x1b[48;2;0;0;0m\u{2580}\
+             \x1b[38;2;0;0;0m\x1b[48;2;0;0;255m\u{2580}\x1b[0m\n"
+        );
+
+        let mut out = Vec::new();
+        write_terminal(&mut out, &image, TerminalProtocol::Kitty, 80).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("\x1b_Ga=T,f=24,s=2,v=2,m=0;"));
+        assert!(out.ends_with("\x1b\\\n"));
+
+        let mut out = Vec::new();
+        write_terminal(&mut out, &image, TerminalProtocol::Sixel, 80).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("\x1bPq\"1;1;2;2"));
+        assert!(out.ends_with("\x1b\\\n"));
+
+        // Images are scaled down to fit the number of columns
+        let gray: ImageBuf<f32, Gray> = ImageBuf::new(100, 10);
+        let mut out = Vec::new();
+        write_terminal(&mut out, &gray, TerminalProtocol::HalfBlock, 10).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 1);
+    }
+
+    #[cfg(feature = "evcxr")]
+    #[test]
+    fn test_display_evcxr() {
+        use crate::color::Bgr;
+
+        let image: ImageBuf<f32, Bgr> = ImageBuf::new(4, 4);
+        let content = super::evcxr::evcxr_content(&image).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines[0], "EVCXR_BEGIN_CONTENT image/png");
+        assert!(lines[1].starts_with(&base64(b"\x89PNG")[..6]));
+        assert_eq!(lines[2], "EVCXR_END_CONTENT");
+    }
+
+    #[cfg(feature = "display")]
+    #[test]
+    fn test_display_window() {
+        use super::window::{render, to_argb, View};
+
+        let mut view = View::fit((200, 100), (400, 400));
+        assert_eq!(view.zoom, 2.0);
+        assert_eq!(view.offset, (0.0, 100.0));
+        assert_eq!(view.to_image(200.0, 200.0), (100.0, 50.0));
+
+        view.zoom_at(2.0, 100.0, 150.0);
+        assert_eq!(view.zoom, 4.0);
+        assert_eq!(view.to_image(100.0, 150.0), (50.0, 25.0));
+
+        let mut gray: ImageBuf<f32, Gray> = ImageBuf::new(2, 1);
+        gray.at_mut(1, 0)[0] = 1.0;
+        assert_eq!(to_argb(&gray), vec![0, 0x00ff_ffff]);
+
+        let mut buffer = vec![0; 8];
+        render(
+            &to_argb(&gray),
+            (2, 1),
+            &View::fit((2, 1), (4, 2)),
+            &mut buffer,
+            4,
+        );
+        assert_eq!(
+            buffer,
+            vec![
+                0,
+                0,
+                0x00ff_ffff,
+                0x00ff_ffff,
+                0,
+                0,
+                0x00ff_ffff,
+                0x00ff_ffff
+            ]
+        );
+    }
+}
@@ -0,0 +1,255 @@
+use std::io::Write;
+
+use super::{base64, to_rgb8};
+use crate::color::Color;
+use crate::error::Error;
+use crate::image::Image;
+use crate::ty::Type;
+
+/// Approximate width of a terminal cell in pixels, used to limit the size of graphics
+const CELL_WIDTH: usize = 10;
+
+/// Maximum payload size of a single kitty graphics escape sequence
+const KITTY_CHUNK: usize = 4096;
+
+/// The method used to draw images in a terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TerminalProtocol {
+    /// Two pixels per character using the upper half block and 24-bit ANSI colors, supported by
+    /// almost every terminal
+    HalfBlock,
+    /// DEC Sixel graphics, using a 216 color palette
+    Sixel,
+    /// Kitty graphics protocol, supported by kitty, WezTerm and Ghostty
+    Kitty,
+}
+
+impl TerminalProtocol {
+    /// Guess the best protocol supported by the current terminal using environment variables.
+    /// `IMAGE2_TERMINAL` can be set to `halfblock`, `sixel` or `kitty` to override the
+    /// detection, which is useful over SSH where most terminal variables are not forwarded.
+    pub fn detect() -> TerminalProtocol {
+        let var = |name| std::env::var(name).unwrap_or_default();
+
+        match var("IMAGE2_TERMINAL").to_lowercase().as_str() {
+            "halfblock" => return TerminalProtocol::HalfBlock,
+            "sixel" => return TerminalProtocol::Sixel,
+            "kitty" => return TerminalProtocol::Kitty,
+            _ => (),
+        }
+
+        let term = var("TERM");
+        let program = var("TERM_PROGRAM");
+        if std::env::var_os("KITTY_WINDOW_ID").is_some()
+            || term == "xterm-kitty"
+            || term == "xterm-ghostty"
+            || program == "WezTerm"
+            || program == "ghostty"
+        {
+            TerminalProtocol::Kitty
+        } else if term.contains("sixel")
+            || term.starts_with("mlterm")
+            || term.starts_with("foot")
+            || term.starts_with("yaft")
+            || program == "iTerm.app"
+            || program == "mintty"
+        {
+            TerminalProtocol::Sixel
+        } else {
+            TerminalProtocol::HalfBlock
+        }
+    }
+}
+
+/// Scale RGB pixels down to `dest` using the average of each covered area
+fn scale(pixels: &[[u8; 3]], src: (usize, usize), dest: (usize, usize)) -> Vec<[u8; 3]> {
+    if src == dest {
+        return pixels.to_vec();
+    }
+
+    let mut out = Vec::with_capacity(dest.0 * dest.1);
+    for y in 0..dest.1 {
+        let (y0, y1) = (
+            y * src.1 / dest.1,
+            ((y + 1) * src.1 / dest.1).max(y * src.1 / dest.1 + 1),
+        );
+        for x in 0..dest.0 {
+            let (x0, x1) = (
+                x * src.0 / dest.0,
+                ((x + 1) * src.0 / dest.0).max(x * src.0 / dest.0 + 1),
+            );
+            let mut sum = [0usize; 3];
+            for row in pixels[y0 * src.0..y1 * src.0].chunks(src.0) {
+                for px in &row[x0..x1] {
+                    for c in 0..3 {
+                        sum[c] += px[c] as usize;
+                    }
+                }
+            }
+            let n = (x1 - x0) * (y1 - y0);
+            out.push(sum.map(|s| (s / n) as u8));
+        }
+    }
+    out
+}
+
+/// Size of an image scaled down to be at most `max_width` pixels wide
+fn fit(size: (usize, usize), max_width: usize) -> (usize, usize) {
+    if size.0 <= max_width {
+        size
+    } else {
+        (max_width, (size.1 * max_width / size.0).max(1))
+    }
+}
+
+fn write_half_block<W: Write>(
+    out: &mut W,
+    pixels: &[[u8; 3]],
+    size: (usize, usize),
+) -> Result<(), Error> {
+    for y in (0..size.1).step_by(2) {
+        for x in 0..size.0 {
+            let [r, g, b] = pixels[y * size.0 + x];
+            write!(out, "\x1b[38;2;{};{};{}m", r, g, b)?;
+            if y + 1 < size.1 {
+                let [r, g, b] = pixels[(y + 1) * size.0 + x];
+                write!(out, "\x1b[48;2;{};{};{}m\u{2580}", r, g, b)?;
+            } else {
+                write!(out, "\x1b[49m\u{2580}")?;
+            }
+        }
+        writeln!(out, "\x1b[0m")?;
+    }
+    Ok(())
+}
+
+fn write_kitty<W: Write>(
+    out: &mut W,
+    pixels: &[[u8; 3]],
+    size: (usize, usize),
+) -> Result<(), Error> {
+    let data: Vec<u8> = pixels.iter().flatten().copied().collect();
+    let encoded = base64(&data);
+    let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(KITTY_CHUNK).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = (i + 1 < chunks.len()) as u8;
+        if i == 0 {
+            write!(out, "\x1b_Ga=T,f=24,s={},v={},m={};", size.0, size.1, more)?;
+        } else {
+            write!(out, "\x1b_Gm={};", more)?;
+        }
+        out.write_all(chunk)?;
+        write!(out, "\x1b\\")?;
+    }
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Index of the closest color in a 6x6x6 color cube
+fn palette_index(px: [u8; 3]) -> usize {
+    let [r, g, b] = px.map(|c| (c as usize * 5 + 127) / 255);
+    r * 36 + g * 6 + b
+}
+
+fn write_sixel<W: Write>(
+    out: &mut W,
+    pixels: &[[u8; 3]],
+    size: (usize, usize),
+) -> Result<(), Error> {
+    let indices: Vec<usize> = pixels.iter().map(|&px| palette_index(px)).collect();
+
+    write!(out, "\x1bPq\"1;1;{};{}", size.0, size.1)?;
+    let mut used = [false; 216];
+    indices.iter().for_each(|&i| used[i] = true);
+    for (i, _) in used.iter().enumerate().filter(|(_, used)| **used) {
+        let [r, g, b] = [i / 36, i / 6 % 6, i % 6].map(|c| c * 20);
+        write!(out, "#{};2;{};{};{}", i, r, g, b)?;
+    }
+
+    for band in (0..size.1).step_by(6) {
+        let rows = (size.1 - band).min(6);
+        let mut colors: Vec<usize> = indices[band * size.0..(band + rows) * size.0].to_vec();
+        colors.sort_unstable();
+        colors.dedup();
+
+        for (n, &color) in colors.iter().enumerate() {
+            if n > 0 {
+                write!(out, "$")?;
+            }
+            write!(out, "#{}", color)?;
+
+            // Run-length encode the sixels of this color
+            let mut run: Option<(u8, usize)> = None;
+            for x in 0..size.0 {
+                let bits = (0..rows)
+                    .filter(|r| indices[(band + r) * size.0 + x] == color)
+                    .fold(0u8, |bits, r| bits | 1 << r);
+                let sixel = 63 + bits;
+                run = match run {
+                    Some((c, count)) if c == sixel => Some((c, count + 1)),
+                    Some((c, count)) => {
+                        write_run(out, c, count)?;
+                        Some((sixel, 1))
+                    }
+                    None => Some((sixel, 1)),
+                };
+            }
+            if let Some((c, count)) = run {
+                write_run(out, c, count)?;
+            }
+        }
+        write!(out, "-")?;
+    }
+
+    writeln!(out, "\x1b\\")?;
+    Ok(())
+}
+
+fn write_run<W: Write>(out: &mut W, sixel: u8, count: usize) -> Result<(), Error> {
+    if count > 3 {
+        write!(out, "!{}{}", count, sixel as char)?;
+    } else {
+        for _ in 0..count {
+            write!(out, "{}", sixel as char)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write an image to a terminal using the given protocol, scaling it down to fit in `columns`
+/// character cells
+pub fn write_terminal<W: Write, T: Type, C: Color, I: Image<T, C>>(
+    out: &mut W,
+    image: &I,
+    protocol: TerminalProtocol,
+    columns: usize,
+) -> Result<(), Error> {
+    let size = (image.width(), image.height());
+    let pixels = to_rgb8(image);
+    let max_width = match protocol {
+        TerminalProtocol::HalfBlock => columns,
+        TerminalProtocol::Sixel | TerminalProtocol::Kitty => columns * CELL_WIDTH,
+    };
+    let dest = fit(size, max_width.max(1));
+    let pixels = scale(&pixels, size, dest);
+
+    match protocol {
+        TerminalProtocol::HalfBlock => write_half_block(out, &pixels, dest)?,
+        TerminalProtocol::Sixel => write_sixel(out, &pixels, dest)?,
+        TerminalProtocol::Kitty => write_kitty(out, &pixels, dest)?,
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Print an image to stdout using the protocol returned by `TerminalProtocol::detect`, scaled
+/// down to the width given by the `COLUMNS` environment variable or 80 columns
+pub fn print_terminal<T: Type, C: Color, I: Image<T, C>>(image: &I) -> Result<(), Error> {
+    let columns = std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .unwrap_or(80);
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    write_terminal(&mut out, image, TerminalProtocol::detect(), columns)
+}
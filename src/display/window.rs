@@ -0,0 +1,171 @@
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window, WindowOptions};
+
+use super::{to_rgb8, BACKGROUND};
+use crate::color::Color;
+use crate::error::Error;
+use crate::image::Image;
+use crate::ty::Type;
+
+const MAX_WIDTH: usize = 1280;
+const MAX_HEIGHT: usize = 800;
+const BACKGROUND_ARGB: u32 =
+    (BACKGROUND as u32) << 16 | (BACKGROUND as u32) << 8 | BACKGROUND as u32;
+
+/// Zoom and pan state, mapping window coordinates to image coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct View {
+    pub(super) zoom: f64,
+    pub(super) offset: (f64, f64),
+}
+
+impl View {
+    /// Scale the image to fit inside the window and center it
+    pub(super) fn fit(image: (usize, usize), window: (usize, usize)) -> View {
+        let zoom =
+            (window.0 as f64 / image.0.max(1) as f64).min(window.1 as f64 / image.1.max(1) as f64);
+        View {
+            zoom,
+            offset: (
+                (window.0 as f64 - image.0 as f64 * zoom) / 2.0,
+                (window.1 as f64 - image.1 as f64 * zoom) / 2.0,
+            ),
+        }
+    }
+
+    /// Image coordinates of a point in the window
+    pub(super) fn to_image(self, x: f64, y: f64) -> (f64, f64) {
+        (
+            (x - self.offset.0) / self.zoom,
+            (y - self.offset.1) / self.zoom,
+        )
+    }
+
+    /// Multiply the zoom by `factor`, keeping the point under `(x, y)` in place
+    pub(super) fn zoom_at(&mut self, factor: f64, x: f64, y: f64) {
+        let (ix, iy) = self.to_image(x, y);
+        self.zoom = (self.zoom * factor).clamp(0.01, 256.0);
+        self.offset = (x - ix * self.zoom, y - iy * self.zoom);
+    }
+}
+
+/// Convert an image to 0RGB pixels
+pub(super) fn to_argb<T: Type, C: Color, I: Image<T, C>>(image: &I) -> Vec<u32> {
+    to_rgb8(image)
+        .iter()
+        .map(|px| u32::from(px[0]) << 16 | u32::from(px[1]) << 8 | u32::from(px[2]))
+        .collect()
+}
+
+/// Render the visible part of the image into a window sized buffer
+pub(super) fn render(
+    pixels: &[u32],
+    size: (usize, usize),
+    view: &View,
+    buffer: &mut [u32],
+    width: usize,
+) {
+    for (y, row) in buffer.chunks_mut(width).enumerate() {
+        let iy = ((y as f64 + 0.5 - view.offset.1) / view.zoom).floor();
+        for (x, px) in row.iter_mut().enumerate() {
+            let ix = ((x as f64 + 0.5 - view.offset.0) / view.zoom).floor();
+            *px = if ix >= 0.0 && iy >= 0.0 && (ix as usize) < size.0 && (iy as usize) < size.1 {
+                pixels[iy as usize * size.0 + ix as usize]
+            } else {
+                BACKGROUND_ARGB
+            };
+        }
+    }
+}
+
+/// Open a window displaying `image` and block until it is closed
+pub fn show<T: Type, C: Color, I: Image<T, C>>(image: &I, title: &str) -> Result<(), Error> {
+    let size = (image.width(), image.height());
+    let pixels = to_argb(image);
+
+    let mut window = Window::new(
+        title,
+        size.0.clamp(64, MAX_WIDTH),
+        size.1.clamp(64, MAX_HEIGHT),
+        WindowOptions {
+            resize: true,
+            ..WindowOptions::default()
+        },
+    )
+    .map_err(|err| Error::Message(format!("Unable to open window: {}", err)))?;
+    window.set_target_fps(60);
+
+    let mut window_size = window.get_size();
+    let mut view = View::fit(size, window_size);
+    let mut buffer = vec![BACKGROUND_ARGB; window_size.0 * window_size.1];
+    let mut drag: Option<(f32, f32)> = None;
+    let mut current_title = String::from(title);
+
+    while window.is_open() && !window.is_key_down(Key::Escape) && !window.is_key_down(Key::Q) {
+        let new_size = window.get_size();
+        if new_size != window_size {
+            window_size = new_size;
+            buffer.resize(window_size.0 * window_size.1, BACKGROUND_ARGB);
+            view = View::fit(size, window_size);
+        }
+
+        let mouse = window.get_mouse_pos(MouseMode::Discard);
+        let center = (window_size.0 as f64 / 2.0, window_size.1 as f64 / 2.0);
+        let (zx, zy) = mouse.map_or(center, |(x, y)| (x as f64, y as f64));
+
+        if let Some((_, scroll)) = window.get_scroll_wheel() {
+            view.zoom_at(1.1f64.powf(scroll as f64), zx, zy);
+        }
+
+        for key in window.get_keys_pressed(KeyRepeat::Yes) {
+            match key {
+                Key::Equal | Key::NumPadPlus => view.zoom_at(1.25, zx, zy),
+                Key::Minus | Key::NumPadMinus => view.zoom_at(0.8, zx, zy),
+                Key::Key0 => view = View::fit(size, window_size),
+                Key::Key1 => view.zoom_at(1.0 / view.zoom, zx, zy),
+                _ => (),
+            }
+        }
+
+        match (window.get_mouse_down(MouseButton::Left), mouse, drag) {
+            (true, Some((x, y)), Some((px, py))) => {
+                view.offset.0 += (x - px) as f64;
+                view.offset.1 += (y - py) as f64;
+                drag = Some((x, y));
+            }
+            (true, Some(pos), None) => drag = Some(pos),
+            (false, _, _) => drag = None,
+            _ => (),
+        }
+
+        let mut new_title = String::from(title);
+        if let Some((x, y)) = mouse {
+            let (ix, iy) = view.to_image(x as f64, y as f64);
+            if ix >= 0.0 && iy >= 0.0 && (ix as usize) < size.0 && (iy as usize) < size.1 {
+                let values: Vec<String> = image
+                    .at(ix as usize, iy as usize)
+                    .iter()
+                    .map(|v| T::to_float(v).to_string())
+                    .collect();
+                new_title = format!(
+                    "{} - ({}, {}) [{}] - {:.0}%",
+                    title,
+                    ix as usize,
+                    iy as usize,
+                    values.join(", "),
+                    view.zoom * 100.0
+                );
+            }
+        }
+        if new_title != current_title {
+            window.set_title(&new_title);
+            current_title = new_title;
+        }
+
+        render(&pixels, size, &view, &mut buffer, window_size.0);
+        window
+            .update_with_buffer(&buffer, window_size.0, window_size.1)
+            .map_err(|err| Error::Message(format!("Unable to update window: {}", err)))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,314 @@
+//! Exchange images with machine learning frameworks using DLPack
+//!
+//! DLPack is a C ABI for sharing tensors between libraries without copying, supported by
+//! PyTorch, TensorFlow, JAX, CuPy and others. Images are exposed as tensors with the shape
+//! `(height, width, channels)`.
+//!
+//! ```rust
+//! use image2::{dlpack::DLPackImage, Image, ImageBuf, Rgb};
+//!
+//! let image: ImageBuf<f32, Rgb> = ImageBuf::new(640, 480);
+//! let tensor = image.into_dlpack();
+//!
+//! // The tensor can be passed to another library, which calls its deleter when it is done with
+//! // it, or imported again without copying
+//! let image = unsafe { DLPackImage::<f32, Rgb>::from_raw(tensor) }.unwrap();
+//! assert_eq!(image.width(), 640);
+//! # Ok::<(), image2::Error>(())
+//! ```
+
+use std::ffi::c_void;
+use std::marker::PhantomData;
+
+use crate::color::Color;
+use crate::error::Error;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::image_ref::ImageRef;
+use crate::ty::Type;
+
+/// `kDLCPU` device type
+pub const DL_CPU: i32 = 1;
+
+/// `kDLCUDAHost` device type, pinned memory that is accessible from the CPU
+pub const DL_CUDA_HOST: i32 = 3;
+
+/// `kDLInt` type code
+pub const DL_INT: u8 = 0;
+
+/// `kDLUInt` type code
+pub const DL_UINT: u8 = 1;
+
+/// `kDLFloat` type code
+pub const DL_FLOAT: u8 = 2;
+
+/// The device a tensor is stored on
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DLDevice {
+    pub device_type: i32,
+    pub device_id: i32,
+}
+
+/// Element type of a tensor
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DLDataType {
+    pub code: u8,
+    pub bits: u8,
+    pub lanes: u16,
+}
+
+impl DLDataType {
+    /// The data type matching `T`
+    pub fn of<T: Type>() -> DLDataType {
+        let code = if T::is_float() {
+            DL_FLOAT
+        } else if T::is_signed() {
+            DL_INT
+        } else {
+            DL_UINT
+        };
+
+        DLDataType {
+            code,
+            bits: (std::mem::size_of::<T>() * 8) as u8,
+            lanes: 1,
+        }
+    }
+}
+
+/// A tensor that does not own its data
+#[repr(C)]
+#[derive(Debug)]
+pub struct DLTensor {
+    pub data: *mut c_void,
+    pub device: DLDevice,
+    pub ndim: i32,
+    pub dtype: DLDataType,
+    pub shape: *mut i64,
+    pub strides: *mut i64,
+    pub byte_offset: u64,
+}
+
+/// A tensor along with the function used to free it
+#[repr(C)]
+#[derive(Debug)]
+pub struct DLManagedTensor {
+    pub dl_tensor: DLTensor,
+    pub manager_ctx: *mut c_void,
+    pub deleter: Option<unsafe extern "C" fn(*mut DLManagedTensor)>,
+}
+
+/// Owns an exported image, along with the shape and strides referenced by the tensor
+struct ExportContext<T: Type, C: Color> {
+    image: ImageBuf<T, C>,
+    shape: [i64; 3],
+    strides: [i64; 3],
+}
+
+unsafe extern "C" fn delete_export<T: Type, C: Color>(tensor: *mut DLManagedTensor) {
+    if tensor.is_null() {
+        return;
+    }
+
+    let tensor = Box::from_raw(tensor);
+    drop(Box::from_raw(
+        tensor.manager_ctx as *mut ExportContext<T, C>,
+    ));
+}
+
+impl<T: Type, C: Color> ImageBuf<T, C> {
+    /// Move the image into a DLPack tensor without copying. Ownership is transferred to the
+    /// receiver, which is responsible for calling the tensor's deleter.
+    pub fn into_dlpack(self) -> *mut DLManagedTensor {
+        let (width, height, channels) = self.shape();
+        let mut ctx = Box::new(ExportContext {
+            image: self,
+            shape: [height as i64, width as i64, channels as i64],
+            strides: [(width * channels) as i64, channels as i64, 1],
+        });
+
+        let dl_tensor = DLTensor {
+            data: ctx.image.data_mut().as_mut_ptr() as *mut c_void,
+            device: DLDevice {
+                device_type: DL_CPU,
+                device_id: 0,
+            },
+            ndim: 3,
+            dtype: DLDataType::of::<T>(),
+            shape: ctx.shape.as_mut_ptr(),
+            strides: ctx.strides.as_mut_ptr(),
+            byte_offset: 0,
+        };
+
+        Box::into_raw(Box::new(DLManagedTensor {
+            dl_tensor,
+            manager_ctx: Box::into_raw(ctx) as *mut c_void,
+            deleter: Some(delete_export::<T, C>),
+        }))
+    }
+}
+
+/// Returns the width and height of an image stored in `tensor`, which must be a contiguous CPU
+/// tensor with the shape `(height, width, channels)`, or `(height, width)` for single channel
+/// images
+unsafe fn check_tensor<T: Type, C: Color>(tensor: &DLTensor) -> Result<(usize, usize), Error> {
+    if tensor.device.device_type != DL_CPU && tensor.device.device_type != DL_CUDA_HOST {
+        return Err(Error::Message(format!(
+            "Tensor is not stored in CPU memory, device type: {}",
+            tensor.device.device_type
+        )));
+    }
+
+    if tensor.dtype != DLDataType::of::<T>() {
+        return Err(Error::InvalidType);
+    }
+
+    if tensor.data.is_null() || tensor.shape.is_null() {
+        return Err(Error::Message(String::from("Tensor data is NULL")));
+    }
+
+    let ndim = tensor.ndim as usize;
+    let shape = std::slice::from_raw_parts(tensor.shape, ndim);
+    let channels = match ndim {
+        2 => 1,
+        3 => shape[2],
+        _ => {
+            return Err(Error::Message(format!(
+                "Expected a tensor with 2 or 3 dimensions, got {}",
+                ndim
+            )))
+        }
+    };
+
+    if channels != C::channels() as i64 {
+        return Err(Error::InvalidColor);
+    }
+
+    if !tensor.strides.is_null() {
+        let strides = std::slice::from_raw_parts(tensor.strides, ndim);
+        let expected = [shape[1] * channels, channels, 1];
+        if strides != &expected[..ndim] {
+            return Err(Error::Message(String::from("Tensor is not contiguous")));
+        }
+    }
+
+    Ok((shape[1] as usize, shape[0] as usize))
+}
+
+unsafe fn tensor_data<'a, T: Type>(tensor: &DLTensor, len: usize) -> &'a mut [T] {
+    let ptr = (tensor.data as *mut u8).add(tensor.byte_offset as usize) as *mut T;
+    std::slice::from_raw_parts_mut(ptr, len)
+}
+
+impl<'a, T: Type, C: Color> ImageRef<'a, T, C> {
+    /// Borrow the data of a contiguous CPU tensor without copying
+    ///
+    /// # Safety
+    /// `tensor` must describe valid memory that is not accessed elsewhere for the lifetime of
+    /// the image
+    pub unsafe fn from_dlpack(tensor: &'a DLTensor) -> Result<ImageRef<'a, T, C>, Error> {
+        let (width, height) = check_tensor::<T, C>(tensor)?;
+        let data = tensor_data(tensor, width * height * C::channels());
+        Ok(ImageRef::new(width, height, data))
+    }
+}
+
+/// An image backed by an imported DLPack tensor, the tensor's deleter is called when the image
+/// is dropped
+#[derive(Debug)]
+pub struct DLPackImage<T: Type, C: Color> {
+    width: usize,
+    height: usize,
+    tensor: *mut DLManagedTensor,
+    data: *mut T,
+    _color: PhantomData<C>,
+}
+
+// The tensor is only accessed through the image, which requires exclusive access to modify it
+unsafe impl<T: Type, C: Color> Send for DLPackImage<T, C> {}
+unsafe impl<T: Type, C: Color> Sync for DLPackImage<T, C> {}
+
+impl<T: Type, C: Color> DLPackImage<T, C> {
+    /// Take ownership of a contiguous CPU tensor without copying. The tensor is left untouched
+    /// when an error is returned.
+    ///
+    /// # Safety
+    /// `tensor` must point to a valid `DLManagedTensor` that is not used after this call
+    pub unsafe fn from_raw(tensor: *mut DLManagedTensor) -> Result<DLPackImage<T, C>, Error> {
+        let managed = match tensor.as_ref() {
+            Some(managed) => managed,
+            None => return Err(Error::Message(String::from("Tensor is NULL"))),
+        };
+
+        let (width, height) = check_tensor::<T, C>(&managed.dl_tensor)?;
+        let data = tensor_data::<T>(&managed.dl_tensor, 0).as_mut_ptr();
+        Ok(DLPackImage {
+            width,
+            height,
+            tensor,
+            data,
+            _color: PhantomData,
+        })
+    }
+}
+
+impl<T: Type, C: Color> Image<T, C> for DLPackImage<T, C> {
+    fn shape(&self) -> (usize, usize, usize) {
+        (self.width, self.height, C::channels())
+    }
+
+    fn data(&self) -> &[T] {
+        unsafe { std::slice::from_raw_parts(self.data, self.width * self.height * C::channels()) }
+    }
+
+    fn data_mut(&mut self) -> &mut [T] {
+        unsafe {
+            std::slice::from_raw_parts_mut(self.data, self.width * self.height * C::channels())
+        }
+    }
+}
+
+impl<T: Type, C: Color> Drop for DLPackImage<T, C> {
+    fn drop(&mut self) {
+        unsafe {
+            if let Some(deleter) = (*self.tensor).deleter {
+                deleter(self.tensor);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Gray, Rgb};
+
+    #[test]
+    fn test_dlpack() {
+        let mut image: ImageBuf<u16, Rgb> = ImageBuf::new(5, 4);
+        image.at_mut(3, 2)[1] = 1000;
+
+        let tensor = image.into_dlpack();
+        unsafe {
+            let t = &(*tensor).dl_tensor;
+            assert_eq!(std::slice::from_raw_parts(t.shape, 3), &[4, 5, 3]);
+            assert_eq!(t.dtype, DLDataType::of::<u16>());
+
+            let view = ImageRef::<u16, Rgb>::from_dlpack(t).unwrap();
+            assert_eq!(view.at(3, 2)[1], 1000);
+            assert!(ImageRef::<u8, Rgb>::from_dlpack(t).is_err());
+            assert!(DLPackImage::<u16, Gray>::from_raw(tensor).is_err());
+        }
+
+        let mut image = unsafe { DLPackImage::<u16, Rgb>::from_raw(tensor) }.unwrap();
+        assert_eq!(image.shape(), (5, 4, 3));
+        assert_eq!(image.at(3, 2)[1], 1000);
+        image.at_mut(0, 0)[0] = 7;
+        assert_eq!(image.data()[0], 7);
+
+        assert_eq!(DLDataType::of::<f32>().code, DL_FLOAT);
+        assert_eq!(DLDataType::of::<i32>().code, DL_INT);
+    }
+}
@@ -0,0 +1,104 @@
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::color::Color;
+use crate::draw::polyline;
+use crate::image::Image;
+use crate::pixel::PixelVec;
+use crate::ty::Type;
+
+/// Number of line segments used to approximate a curve whose control polygon has the given
+/// length, roughly one segment every two pixels
+fn segments(control: &[(f64, f64)]) -> usize {
+    let length: f64 = control
+        .windows(2)
+        .map(|w| ((w[1].0 - w[0].0).powi(2) + (w[1].1 - w[0].1).powi(2)).sqrt())
+        .sum();
+    ((length / 2.0).ceil() as usize).clamp(1, 4096)
+}
+
+fn lerp(a: (f64, f64), b: (f64, f64), t: f64) -> (f64, f64) {
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+}
+
+/// Evaluate a Bezier curve of any degree using de Casteljau's algorithm
+fn bezier_at(control: &[(f64, f64)], t: f64) -> (f64, f64) {
+    let mut points = control.to_vec();
+    while points.len() > 1 {
+        for i in 0..points.len() - 1 {
+            points[i] = lerp(points[i], points[i + 1], t);
+        }
+        points.pop();
+    }
+    points[0]
+}
+
+fn bezier<T: Type, C: Color, I: Image<T, C>>(
+    image: &mut I,
+    control: &[(f64, f64)],
+    color: &PixelVec<f64>,
+    thickness: f64,
+    aa: bool,
+) {
+    let n = segments(control);
+    let points: Vec<_> = (0..=n)
+        .map(|i| bezier_at(control, i as f64 / n as f64))
+        .collect();
+    polyline(image, &points, false, color, thickness, aa);
+}
+
+/// Draw a quadratic Bezier curve from `points[0]` to `points[2]` with control point
+/// `points[1]`
+pub fn quadratic_bezier<T: Type, C: Color, I: Image<T, C>>(
+    image: &mut I,
+    points: [(f64, f64); 3],
+    color: &PixelVec<f64>,
+    thickness: f64,
+    aa: bool,
+) {
+    bezier(image, &points, color, thickness, aa);
+}
+
+/// Draw a cubic Bezier curve from `points[0]` to `points[3]` with control points `points[1]`
+/// and `points[2]`
+pub fn cubic_bezier<T: Type, C: Color, I: Image<T, C>>(
+    image: &mut I,
+    points: [(f64, f64); 4],
+    color: &PixelVec<f64>,
+    thickness: f64,
+    aa: bool,
+) {
+    bezier(image, &points, color, thickness, aa);
+}
+
+/// Draw a smooth curve passing through each of `points` using a uniform Catmull-Rom spline
+pub fn catmull_rom<T: Type, C: Color, I: Image<T, C>>(
+    image: &mut I,
+    points: &[(f64, f64)],
+    color: &PixelVec<f64>,
+    thickness: f64,
+    aa: bool,
+) {
+    if points.len() < 3 {
+        polyline(image, points, false, color, thickness, aa);
+        return;
+    }
+
+    let n = points.len();
+    let mut path = vec![points[0]];
+    for i in 0..n - 1 {
+        // The first and last points are repeated to define the end tangents
+        let p0 = points[i.saturating_sub(1)];
+        let (p1, p2) = (points[i], points[i + 1]);
+        let p3 = points[(i + 2).min(n - 1)];
+
+        // Convert the segment to cubic Bezier form
+        let c1 = (p1.0 + (p2.0 - p0.0) / 6.0, p1.1 + (p2.1 - p0.1) / 6.0);
+        let c2 = (p2.0 - (p3.0 - p1.0) / 6.0, p2.1 - (p3.1 - p1.1) / 6.0);
+        let control = [p1, c1, c2, p2];
+        let steps = segments(&control);
+        path.extend((1..=steps).map(|s| bezier_at(&control, s as f64 / steps as f64)));
+    }
+    polyline(image, &path, false, color, thickness, aa);
+}
@@ -0,0 +1,29 @@
+use crate::analysis::Connectivity;
+use crate::color::Color;
+use crate::draw::plot;
+use crate::image::Image;
+use crate::pixel::PixelVec;
+use crate::select;
+use crate::ty::Type;
+
+/// Paint the region of similar pixels connected to `seed` with `color`, like a paint bucket
+/// tool. A pixel is similar when the normalized difference between each of its channels and the
+/// seed color is at most `tolerance`. Returns the number of pixels that were painted.
+pub fn flood_fill<T: Type, C: Color, I: Image<T, C>>(
+    image: &mut I,
+    seed: (usize, usize),
+    color: &PixelVec<f64>,
+    tolerance: f64,
+    connectivity: Connectivity,
+) -> usize {
+    let selection = select::flood_fill(image, seed, tolerance, connectivity);
+    let bbox = selection.bbox();
+    for y in bbox.y..bbox.y + bbox.height {
+        for x in bbox.x..bbox.x + bbox.width {
+            if selection.contains(x, y) {
+                plot(image, x as isize, y as isize, color, 1.0);
+            }
+        }
+    }
+    selection.count()
+}
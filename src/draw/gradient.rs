@@ -0,0 +1,70 @@
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::color::Color;
+use crate::draw::plot;
+use crate::gradient::Gradient;
+use crate::image::Image;
+use crate::rect::Rect;
+use crate::ty::Type;
+
+/// Determines how positions in an image are mapped onto a gradient, coordinates are in pixels
+/// relative to the image
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GradientShape {
+    /// Varies along the line from `start` to `end`, and is constant perpendicular to it
+    Linear { start: (f64, f64), end: (f64, f64) },
+    /// Varies with the distance from `center`, reaching the end of the gradient at `radius`
+    Radial { center: (f64, f64), radius: f64 },
+    /// Varies with the angle around `center`, starting at `angle` degrees and increasing
+    /// clockwise
+    Conic { center: (f64, f64), angle: f64 },
+}
+
+impl GradientShape {
+    /// The position on the gradient at (x, y), between 0 and 1
+    pub fn position(&self, x: f64, y: f64) -> f64 {
+        let t = match *self {
+            GradientShape::Linear { start, end } => {
+                let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+                let length2 = dx * dx + dy * dy;
+                if length2 == 0.0 {
+                    0.0
+                } else {
+                    ((x - start.0) * dx + (y - start.1) * dy) / length2
+                }
+            }
+            GradientShape::Radial { center, radius } => {
+                let d = ((x - center.0).powi(2) + (y - center.1).powi(2)).sqrt();
+                if radius > 0.0 {
+                    d / radius
+                } else {
+                    1.0
+                }
+            }
+            GradientShape::Conic { center, angle } => {
+                let a = (y - center.1).atan2(x - center.0).to_degrees() - angle;
+                (a % 360.0 + 360.0) % 360.0 / 360.0
+            }
+        };
+        t.clamp(0.0, 1.0)
+    }
+}
+
+/// Fill `region` with colors from `gradient`, positions are sampled at pixel centers
+pub fn fill_gradient<T: Type, C: Color, I: Image<T, C>>(
+    image: &mut I,
+    region: Rect,
+    gradient: &Gradient,
+    shape: GradientShape,
+) {
+    let x1 = (region.x + region.width).min(image.width());
+    let y1 = (region.y + region.height).min(image.height());
+    for y in region.y..y1 {
+        for x in region.x..x1 {
+            let color = gradient.at(shape.position(x as f64, y as f64));
+            plot(image, x as isize, y as isize, &color, 1.0);
+        }
+    }
+}
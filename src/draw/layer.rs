@@ -0,0 +1,81 @@
+use crate::color::{Color, Rgba};
+use crate::draw::{blend, AlphaMode};
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::ty::Type;
+
+/// A transparent image that shapes are drawn onto before being composited onto another image.
+/// Overlapping shapes drawn onto the same layer do not blend with each other, so the whole
+/// layer is faded evenly by its opacity.
+#[derive(Debug, Clone)]
+pub struct Layer {
+    image: ImageBuf<f32, Rgba>,
+    /// Opacity applied to the whole layer when compositing
+    pub opacity: f64,
+}
+
+impl Layer {
+    /// Create a fully transparent layer with an opacity of 1
+    pub fn new(width: usize, height: usize) -> Layer {
+        Layer {
+            image: ImageBuf::new(width, height),
+            opacity: 1.0,
+        }
+    }
+
+    /// Set the opacity and return the layer
+    pub fn with_opacity(mut self, opacity: f64) -> Layer {
+        self.opacity = opacity;
+        self
+    }
+
+    /// The contents of the layer, using straight alpha
+    pub fn image(&self) -> &ImageBuf<f32, Rgba> {
+        &self.image
+    }
+
+    /// Draw onto the layer by passing this to any of the drawing functions
+    pub fn image_mut(&mut self) -> &mut ImageBuf<f32, Rgba> {
+        &mut self.image
+    }
+
+    /// Make the whole layer transparent again
+    pub fn clear(&mut self) {
+        self.image.data_mut().iter_mut().for_each(|x| *x = 0.0);
+    }
+
+    /// Composite the layer over `dest` with its top-left corner at `offset`. `mode` describes
+    /// how `dest` stores its colors and is ignored for images without an alpha channel.
+    pub fn composite<T: Type, C: Color, I: Image<T, C>>(
+        &self,
+        dest: &mut I,
+        offset: (isize, isize),
+        mode: AlphaMode,
+    ) {
+        let opacity = self.opacity.clamp(0.0, 1.0);
+        if opacity <= 0.0 {
+            return;
+        }
+
+        for y in 0..self.image.height() {
+            let dy = y as isize + offset.1;
+            if dy < 0 || dy as usize >= dest.height() {
+                continue;
+            }
+
+            for x in 0..self.image.width() {
+                let dx = x as isize + offset.0;
+                if dx < 0 || dx as usize >= dest.width() {
+                    continue;
+                }
+
+                let px = self.image.at(x, y);
+                let alpha = px[3] as f64 * opacity;
+                if alpha > 0.0 {
+                    let rgb = [px[0] as f64, px[1] as f64, px[2] as f64];
+                    blend::<T, C>(dest.at_mut(dx as usize, dy as usize), rgb, alpha, mode);
+                }
+            }
+        }
+    }
+}
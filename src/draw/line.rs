@@ -0,0 +1,194 @@
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::color::Color;
+use crate::draw::plot;
+use crate::image::Image;
+use crate::pixel::PixelVec;
+use crate::ty::Type;
+
+/// Distance from `p` to the segment between `a` and `b`
+pub(crate) fn segment_distance(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length2 = dx * dx + dy * dy;
+    let t = if length2 > 0.0 {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length2).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let (x, y) = (a.0 + t * dx - p.0, a.1 + t * dy - p.1);
+    (x * x + y * y).sqrt()
+}
+
+/// Draw a path through `points` with round caps and joins by testing the distance of each
+/// nearby pixel from it, so that every pixel is drawn at most once. When `aa` is set the edges
+/// are anti-aliased, otherwise pixels within `width / 2` are filled.
+pub(crate) fn stroke<T: Type, C: Color, I: Image<T, C>>(
+    image: &mut I,
+    points: &[(f64, f64)],
+    closed: bool,
+    color: &PixelVec<f64>,
+    width: f64,
+    aa: bool,
+) {
+    if points.is_empty() {
+        return;
+    }
+
+    let mut segments: Vec<_> = points.windows(2).map(|w| (w[0], w[1])).collect();
+    if segments.is_empty() {
+        segments.push((points[0], points[0]));
+    }
+    if closed && points.len() > 2 {
+        segments.push((points[points.len() - 1], points[0]));
+    }
+
+    let r = width / 2.0;
+    let pad = r + 1.0;
+    let (mut x0, mut y0) = (f64::INFINITY, f64::INFINITY);
+    let (mut x1, mut y1) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+    for (x, y) in points {
+        x0 = x0.min(*x);
+        y0 = y0.min(*y);
+        x1 = x1.max(*x);
+        y1 = y1.max(*y);
+    }
+    let x0 = (x0 - pad).floor().max(0.0);
+    let y0 = (y0 - pad).floor().max(0.0);
+    let x1 = (x1 + pad).ceil().min(image.width() as f64 - 1.0);
+    let y1 = (y1 + pad).ceil().min(image.height() as f64 - 1.0);
+    if x1 < x0 || y1 < y0 {
+        return;
+    }
+
+    for y in y0 as isize..=y1 as isize {
+        for x in x0 as isize..=x1 as isize {
+            let p = (x as f64, y as f64);
+            let d = segments
+                .iter()
+                .map(|(a, b)| segment_distance(p, *a, *b))
+                .fold(f64::INFINITY, f64::min);
+            let coverage = if aa {
+                (r + 0.5 - d).clamp(0.0, 1.0)
+            } else if d <= r {
+                1.0
+            } else {
+                0.0
+            };
+            plot(image, x, y, color, coverage);
+        }
+    }
+}
+
+/// Call `f` for each pixel on the line from `p0` to `p1`, including both endpoints
+pub(crate) fn bresenham<F: FnMut(isize, isize)>(p0: (isize, isize), p1: (isize, isize), mut f: F) {
+    let (mut x, mut y) = p0;
+    let dx = (p1.0 - x).abs();
+    let dy = -(p1.1 - y).abs();
+    let sx = if x < p1.0 { 1 } else { -1 };
+    let sy = if y < p1.1 { 1 } else { -1 };
+    let mut err = dx + dy;
+    loop {
+        f(x, y);
+        if x == p1.0 && y == p1.1 {
+            break;
+        }
+
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+}
+
+/// Draw a line between two pixels using Bresenham's algorithm. Lines thicker than one pixel
+/// have round caps.
+pub fn line<T: Type, C: Color, I: Image<T, C>>(
+    image: &mut I,
+    p0: (isize, isize),
+    p1: (isize, isize),
+    color: &PixelVec<f64>,
+    thickness: usize,
+) {
+    if thickness > 1 {
+        // Even widths are centered between pixels so that the line has the requested width
+        let offset = if thickness.is_multiple_of(2) {
+            0.5
+        } else {
+            0.0
+        };
+        let a = (p0.0 as f64 + offset, p0.1 as f64 + offset);
+        let b = (p1.0 as f64 + offset, p1.1 as f64 + offset);
+        stroke(image, &[a, b], false, color, thickness as f64, false);
+        return;
+    }
+
+    bresenham(p0, p1, |x, y| plot(image, x, y, color, 1.0));
+}
+
+/// Draw an anti-aliased line between two points. Lines with a thickness of one pixel or less
+/// use Xiaolin Wu's algorithm, thicker lines have round caps.
+pub fn line_aa<T: Type, C: Color, I: Image<T, C>>(
+    image: &mut I,
+    p0: (f64, f64),
+    p1: (f64, f64),
+    color: &PixelVec<f64>,
+    thickness: f64,
+) {
+    if thickness > 1.0 {
+        stroke(image, &[p0, p1], false, color, thickness, true);
+        return;
+    }
+
+    let steep = (p1.1 - p0.1).abs() > (p1.0 - p0.0).abs();
+    let (mut a, mut b) = (p0, p1);
+    if steep {
+        a = (a.1, a.0);
+        b = (b.1, b.0);
+    }
+    if a.0 > b.0 {
+        core::mem::swap(&mut a, &mut b);
+    }
+
+    let mut put = |x: f64, y: f64, coverage: f64| {
+        let (x, y) = if steep { (y, x) } else { (x, y) };
+        plot(image, x as isize, y as isize, color, coverage * thickness);
+    };
+
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let gradient = if dx == 0.0 { 1.0 } else { dy / dx };
+
+    // Endpoints are weighted by how much of their pixel the line covers horizontally
+    let mut endpoint = |p: (f64, f64), first: bool| -> (f64, f64) {
+        let x = p.0.round();
+        let y = p.1 + gradient * (x - p.0);
+        let gap = if first {
+            1.0 - (p.0 + 0.5).fract()
+        } else {
+            (p.0 + 0.5).fract()
+        };
+        let (yi, yf) = (y.floor(), y - y.floor());
+        put(x, yi, (1.0 - yf) * gap);
+        put(x, yi + 1.0, yf * gap);
+        (x, y)
+    };
+
+    let (xa, ya) = endpoint(a, true);
+    let (xb, _) = endpoint(b, false);
+
+    let mut y = ya + gradient;
+    let mut x = xa + 1.0;
+    while x < xb {
+        let (yi, yf) = (y.floor(), y - y.floor());
+        put(x, yi, 1.0 - yf);
+        put(x, yi + 1.0, yf);
+        y += gradient;
+        x += 1.0;
+    }
+}
@@ -0,0 +1,406 @@
+//! Drawing primitives
+//!
+//! Colors are normalized `PixelVec<f64>` values, the same representation used by `Gradient`.
+//! When drawing onto grayscale images the luminance of the color is used. Shapes are clipped
+//! to the bounds of the image, so coordinates may be negative or lie outside of the image.
+//!
+//! The alpha of a color controls its opacity. Images with an alpha channel are composited onto
+//! using straight alpha; to draw onto premultiplied images, or to draw several overlapping
+//! shapes with a shared opacity, draw onto a `Layer` and composite it afterwards.
+
+mod curve;
+mod fill;
+mod gradient;
+mod layer;
+mod line;
+mod polygon;
+mod shape;
+#[cfg(feature = "text")]
+mod text;
+
+pub use self::curve::*;
+pub use self::fill::*;
+pub use self::gradient::*;
+pub use self::layer::*;
+pub use self::line::*;
+pub use self::polygon::*;
+pub use self::shape::*;
+#[cfg(feature = "text")]
+pub use self::text::*;
+
+use crate::color::Color;
+use crate::image::Image;
+use crate::pixel::PixelVec;
+use crate::ty::Type;
+
+/// How color channels are stored in images with an alpha channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AlphaMode {
+    /// Color channels are independent of alpha
+    #[default]
+    Straight,
+    /// Color channels are multiplied by alpha
+    Premultiplied,
+}
+
+/// Composite an RGB color with the given alpha over a single pixel. Grayscale pixels receive
+/// the luminance of the color and BGR pixels are written in reverse order.
+pub(crate) fn blend<T: Type, C: Color>(px: &mut [T], rgb: [f64; 3], alpha: f64, mode: AlphaMode) {
+    let alpha = alpha.clamp(0.0, 1.0);
+    if alpha <= 0.0 {
+        return;
+    }
+
+    let channels = C::channels();
+    let colors = if C::has_alpha() {
+        channels - 1
+    } else {
+        channels
+    };
+    let gray = rgb[0] * 0.21 + rgb[1] * 0.72 + rgb[2] * 0.07;
+    let bgr = C::name().starts_with("bgr");
+    let value = |c: usize| {
+        if colors < 3 {
+            gray
+        } else if bgr && c < 3 {
+            rgb[2 - c]
+        } else {
+            rgb[c.min(2)]
+        }
+    };
+
+    if !C::has_alpha() {
+        for (c, item) in px.iter_mut().enumerate() {
+            *item = if alpha >= 1.0 {
+                T::from_f(value(c))
+            } else {
+                T::from_f(T::to_f(item) * (1.0 - alpha) + value(c) * alpha)
+            };
+        }
+        return;
+    }
+
+    // Porter-Duff "over"
+    let dest_alpha = T::to_f(&px[colors]);
+    let out_alpha = alpha + dest_alpha * (1.0 - alpha);
+    for (c, item) in px[..colors].iter_mut().enumerate() {
+        let dest = T::to_f(item);
+        *item = T::from_f(match mode {
+            AlphaMode::Premultiplied => value(c) * alpha + dest * (1.0 - alpha),
+            AlphaMode::Straight if out_alpha > 0.0 => {
+                (value(c) * alpha + dest * dest_alpha * (1.0 - alpha)) / out_alpha
+            }
+            AlphaMode::Straight => 0.0,
+        });
+    }
+    px[colors] = T::from_f(out_alpha);
+}
+
+/// Blend `color` into the pixel at (x, y), where `coverage` is the fraction of the pixel
+/// covered by the shape. The alpha of `color` scales the coverage, and targets with an alpha
+/// channel are composited using straight alpha. Pixels outside of the image are ignored.
+pub(crate) fn plot<T: Type, C: Color, I: Image<T, C>>(
+    image: &mut I,
+    x: isize,
+    y: isize,
+    color: &PixelVec<f64>,
+    coverage: f64,
+) {
+    if x < 0
+        || y < 0
+        || x as usize >= image.width()
+        || y as usize >= image.height()
+        || coverage <= 0.0
+    {
+        return;
+    }
+
+    let color = color.as_ref();
+    blend::<T, C>(
+        image.at_mut(x as usize, y as usize),
+        [color[0], color[1], color[2]],
+        coverage.min(1.0) * color[3],
+        AlphaMode::Straight,
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use crate::analysis::Connectivity;
+    use crate::color::Bgra;
+    use crate::gradient::{Gradient, Interpolation};
+    use crate::{draw, Gray, Image, ImageBuf, PixelVec, Rect, Rgb, Rgba};
+
+    fn count(image: &ImageBuf<u8, Gray>) -> usize {
+        image.data().iter().filter(|x| **x > 0).count()
+    }
+
+    #[test]
+    fn test_line() {
+        let white = PixelVec::new(1.0, 1.0, 1.0, 1.0);
+        let mut image: ImageBuf<u8, Gray> = ImageBuf::new(16, 16);
+        draw::line(&mut image, (2, 3), (12, 8), &white, 1);
+        assert_eq!(count(&image), 11);
+        assert_eq!(image.at(2, 3)[0], 255);
+        assert_eq!(image.at(12, 8)[0], 255);
+
+        // Clipped against the image bounds
+        let mut image: ImageBuf<u8, Gray> = ImageBuf::new(16, 16);
+        draw::line(&mut image, (-10, 4), (30, 4), &white, 3);
+        assert_eq!(count(&image), 48);
+        assert_eq!(image.at(0, 3)[0], 255);
+        assert_eq!(image.at(15, 5)[0], 255);
+
+        let mut image: ImageBuf<u8, Gray> = ImageBuf::new(16, 16);
+        draw::line(&mut image, (2, 4), (12, 4), &white, 2);
+        assert_eq!(count(&image), 24);
+
+        let mut image: ImageBuf<u8, Rgb> = ImageBuf::new(16, 16);
+        let red = PixelVec::new(1.0, 0.0, 0.0, 1.0);
+        draw::line_aa(&mut image, (1.0, 1.0), (14.0, 6.5), &red, 1.0);
+        assert_eq!(image.at(1, 1), &[127, 0, 0]);
+        assert!(image.at(8, 4)[0] > 0 && image.at(8, 4)[0] < 255);
+        assert_eq!(image.at(8, 10), &[0, 0, 0]);
+
+        let mut image: ImageBuf<u8, Gray> = ImageBuf::new(16, 16);
+        draw::line_aa(&mut image, (2.0, 8.0), (13.0, 8.0), &white, 4.0);
+        assert_eq!(image.at(8, 7)[0], 255);
+        assert_eq!(image.at(8, 10)[0], 127);
+        assert_eq!(image.at(8, 12)[0], 0);
+    }
+
+    #[test]
+    fn test_rect() {
+        let white = PixelVec::new(1.0, 1.0, 1.0, 1.0);
+        let mut image: ImageBuf<u8, Gray> = ImageBuf::new(16, 16);
+        draw::rect(&mut image, Rect::new(2, 2, 8, 6), &white, 2);
+        assert_eq!(count(&image), 8 * 6 - 4 * 2);
+        assert_eq!(image.at(3, 3)[0], 255);
+        assert_eq!(image.at(4, 4)[0], 0);
+        assert_eq!(image.at(9, 7)[0], 255);
+        assert_eq!(image.at(10, 7)[0], 0);
+
+        // Clipped against the image bounds
+        let mut image: ImageBuf<u8, Gray> = ImageBuf::new(16, 16);
+        draw::rect(&mut image, Rect::new(10, 10, 20, 20), &white, 1);
+        assert_eq!(count(&image), 11);
+
+        let mut image: ImageBuf<u8, Gray> = ImageBuf::new(16, 16);
+        draw::rect_filled(&mut image, Rect::new(12, 1, 10, 3), &white);
+        assert_eq!(count(&image), 12);
+    }
+
+    #[test]
+    fn test_circle() {
+        let white = PixelVec::new(1.0, 1.0, 1.0, 1.0);
+        let mut image: ImageBuf<u8, Gray> = ImageBuf::new(20, 20);
+        draw::circle_filled(&mut image, (8.0, 8.0), 5.0, &white, false);
+        assert_eq!(count(&image), 81);
+        assert_eq!(image.at(13, 8)[0], 255);
+        assert_eq!(image.at(12, 12)[0], 0);
+
+        let mut image: ImageBuf<u8, Gray> = ImageBuf::new(20, 20);
+        draw::circle(&mut image, (8.0, 8.0), 5.0, &white, 1.0, false);
+        assert_eq!(image.at(8, 8)[0], 0);
+        assert_eq!(image.at(3, 8)[0], 255);
+        assert_eq!(image.at(2, 8)[0], 0);
+
+        let mut image: ImageBuf<u8, Gray> = ImageBuf::new(20, 20);
+        draw::circle_filled(&mut image, (8.0, 8.0), 5.5, &white, true);
+        assert_eq!(image.at(8, 8)[0], 255);
+        assert_eq!(image.at(14, 8)[0], 0);
+        assert!(image.at(12, 12)[0] > 0 && image.at(12, 12)[0] < 255);
+
+        // An ellipse rotated by 90 degrees is taller than it is wide
+        let mut image: ImageBuf<u8, Gray> = ImageBuf::new(20, 20);
+        draw::ellipse(
+            &mut image,
+            (10.0, 10.0),
+            (8.0, 3.0),
+            90.0,
+            &white,
+            None,
+            false,
+        );
+        assert_eq!(image.at(10, 2)[0], 255);
+        assert_eq!(image.at(10, 18)[0], 255);
+        assert_eq!(image.at(2, 10)[0], 0);
+        assert_eq!(image.at(13, 10)[0], 255);
+    }
+
+    #[test]
+    fn test_polygon() {
+        let white = PixelVec::new(1.0, 1.0, 1.0, 1.0);
+        let square = [(2.0, 2.0), (10.0, 2.0), (10.0, 8.0), (2.0, 8.0)];
+        let mut image: ImageBuf<u8, Gray> = ImageBuf::new(16, 16);
+        draw::polygon_filled(&mut image, &square, &white, draw::FillRule::NonZero);
+        assert_eq!(count(&image), 48);
+
+        let mut image: ImageBuf<u8, Gray> = ImageBuf::new(16, 16);
+        draw::polyline(&mut image, &square, true, &white, 1.0, false);
+        assert_eq!(count(&image), 28);
+
+        // Concave
+        let l = [
+            (1.0, 1.0),
+            (5.0, 1.0),
+            (5.0, 9.0),
+            (12.0, 9.0),
+            (12.0, 13.0),
+            (1.0, 13.0),
+        ];
+        let mut image: ImageBuf<u8, Gray> = ImageBuf::new(16, 16);
+        draw::polygon_filled(&mut image, &l, &white, draw::FillRule::EvenOdd);
+        assert_eq!(image.at(3, 3)[0], 255);
+        assert_eq!(image.at(8, 5)[0], 0);
+        assert_eq!(image.at(10, 11)[0], 255);
+
+        // The center of a pentagram is only filled using the non-zero rule
+        let star: Vec<(f64, f64)> = (0..5)
+            .map(|i| {
+                let a = (i as f64 * 144.0 - 90.0).to_radians();
+                (16.0 + 14.0 * a.cos(), 16.0 + 14.0 * a.sin())
+            })
+            .collect();
+        let mut image: ImageBuf<u8, Gray> = ImageBuf::new(32, 32);
+        draw::polygon_filled(&mut image, &star, &white, draw::FillRule::EvenOdd);
+        assert_eq!(image.at(16, 16)[0], 0);
+        assert_eq!(image.at(16, 5)[0], 255);
+        draw::polygon_filled(&mut image, &star, &white, draw::FillRule::NonZero);
+        assert_eq!(image.at(16, 16)[0], 255);
+    }
+
+    #[test]
+    fn test_curves() {
+        let white = PixelVec::new(1.0, 1.0, 1.0, 1.0);
+        let mut image: ImageBuf<u8, Gray> = ImageBuf::new(32, 32);
+        draw::quadratic_bezier(
+            &mut image,
+            [(2.0, 28.0), (16.0, -12.0), (30.0, 28.0)],
+            &white,
+            1.0,
+            false,
+        );
+        assert_eq!(image.at(2, 28)[0], 255);
+        assert_eq!(image.at(30, 28)[0], 255);
+        // The curve passes through the midpoint of the control polygon's midpoints
+        assert_eq!(image.at(16, 8)[0], 255);
+        assert_eq!(image.at(16, 20)[0], 0);
+
+        let mut image: ImageBuf<u8, Gray> = ImageBuf::new(32, 32);
+        draw::cubic_bezier(
+            &mut image,
+            [(2.0, 16.0), (10.0, 2.0), (22.0, 30.0), (30.0, 16.0)],
+            &white,
+            3.0,
+            true,
+        );
+        assert_eq!(image.at(16, 16)[0], 255);
+        assert_eq!(image.at(16, 2)[0], 0);
+
+        let points = [(2.0, 2.0), (10.0, 20.0), (20.0, 6.0), (29.0, 25.0)];
+        let mut image: ImageBuf<u8, Gray> = ImageBuf::new(32, 32);
+        draw::catmull_rom(&mut image, &points, &white, 1.0, false);
+        for (x, y) in &points {
+            assert_eq!(image.at(*x as usize, *y as usize)[0], 255);
+        }
+    }
+
+    #[test]
+    fn test_fill_gradient() {
+        let gradient = Gradient::from_colors(&[
+            PixelVec::new(1.0, 0.0, 0.0, 1.0),
+            PixelVec::new(0.0, 1.0, 0.0, 1.0),
+        ]);
+        let mut image: ImageBuf<u8, Rgb> = ImageBuf::new(11, 4);
+        let linear = draw::GradientShape::Linear {
+            start: (0.0, 0.0),
+            end: (10.0, 0.0),
+        };
+        draw::fill_gradient(&mut image, Rect::new(0, 0, 11, 4), &gradient, linear);
+        assert_eq!(image.at(0, 2), &[255, 0, 0]);
+        assert_eq!(image.at(5, 1), &[127, 127, 0]);
+        assert_eq!(image.at(10, 3), &[0, 255, 0]);
+
+        // Oklab keeps the endpoints and produces a brighter midpoint
+        let oklab = gradient.clone().with_interpolation(Interpolation::Oklab);
+        draw::fill_gradient(&mut image, Rect::new(0, 0, 11, 4), &oklab, linear);
+        assert_eq!(image.at(0, 0), &[255, 0, 0]);
+        assert_eq!(image.at(10, 0), &[0, 255, 0]);
+        assert!(image.at(5, 0)[0] > 127 && image.at(5, 0)[1] > 127);
+
+        let radial = draw::GradientShape::Radial {
+            center: (5.0, 5.0),
+            radius: 4.0,
+        };
+        assert_eq!(radial.position(5.0, 5.0), 0.0);
+        assert_eq!(radial.position(7.0, 5.0), 0.5);
+        assert_eq!(radial.position(20.0, 5.0), 1.0);
+
+        let conic = draw::GradientShape::Conic {
+            center: (0.0, 0.0),
+            angle: 0.0,
+        };
+        assert_eq!(conic.position(1.0, 0.0), 0.0);
+        assert_eq!(conic.position(0.0, 1.0), 0.25);
+        assert_eq!(conic.position(-1.0, 0.0), 0.5);
+    }
+
+    #[test]
+    fn test_flood_fill() {
+        let white = PixelVec::new(1.0, 1.0, 1.0, 1.0);
+        let mut image: ImageBuf<u8, Gray> = ImageBuf::new(16, 16);
+        draw::rect(&mut image, Rect::new(2, 2, 8, 8), &white, 1);
+
+        let gray = PixelVec::new(0.5, 0.5, 0.5, 1.0);
+        let n = draw::flood_fill(&mut image, (5, 5), &gray, 0.0, Connectivity::Four);
+        assert_eq!(n, 36);
+        assert_eq!(image.at(5, 5)[0], 127);
+        assert_eq!(image.at(2, 5)[0], 255);
+        assert_eq!(image.at(12, 12)[0], 0);
+
+        // The painted interior is not similar to the outside, only the removed corner is added
+        image.at_mut(9, 9)[0] = 0;
+        let n = draw::flood_fill(&mut image, (0, 0), &gray, 0.1, Connectivity::Eight);
+        assert_eq!(n, 16 * 16 - 64 + 1);
+        assert_eq!(
+            draw::flood_fill(&mut image, (20, 0), &white, 0.1, Connectivity::Eight),
+            0
+        );
+    }
+
+    #[test]
+    fn test_alpha() {
+        let red = PixelVec::new(1.0, 0.0, 0.0, 0.5);
+        let mut image: ImageBuf<u8, Rgb> = ImageBuf::new(4, 4);
+        draw::rect_filled(&mut image, Rect::new(0, 0, 2, 2), &red);
+        assert_eq!(image.at(0, 0), &[127, 0, 0]);
+
+        // Over compositing onto a transparent target keeps the color
+        let mut image: ImageBuf<f32, Rgba> = ImageBuf::new(4, 4);
+        draw::rect_filled(&mut image, Rect::new(0, 0, 2, 2), &red);
+        assert_eq!(image.at(0, 0), &[1.0, 0.0, 0.0, 0.5]);
+        draw::rect_filled(&mut image, Rect::new(0, 0, 2, 2), &red);
+        assert_eq!(image.at(0, 0), &[1.0, 0.0, 0.0, 0.75]);
+
+        let mut image: ImageBuf<u8, Bgra> = ImageBuf::new(4, 4);
+        draw::rect_filled(&mut image, Rect::new(0, 0, 2, 2), &red);
+        assert_eq!(image.at(1, 1), &[0, 0, 255, 127]);
+
+        // Overlapping shapes on a layer do not blend with each other
+        let opaque = PixelVec::new(0.0, 1.0, 0.0, 1.0);
+        let mut layer = draw::Layer::new(4, 4).with_opacity(0.5);
+        draw::rect_filled(layer.image_mut(), Rect::new(0, 0, 3, 3), &opaque);
+        draw::rect_filled(layer.image_mut(), Rect::new(1, 1, 3, 3), &opaque);
+        let mut image: ImageBuf<u8, Rgb> = ImageBuf::new(4, 4);
+        layer.composite(&mut image, (0, 0), draw::AlphaMode::Straight);
+        assert_eq!(image.at(0, 0), &[0, 127, 0]);
+        assert_eq!(image.at(2, 2), &[0, 127, 0]);
+        assert_eq!(image.at(3, 0), &[0, 0, 0]);
+
+        let mut image: ImageBuf<f32, Rgba> = ImageBuf::new(4, 4);
+        layer.composite(&mut image, (2, 2), draw::AlphaMode::Premultiplied);
+        assert_eq!(image.at(3, 3), &[0.0, 0.5, 0.0, 0.5]);
+        assert_eq!(image.at(1, 1), &[0.0, 0.0, 0.0, 0.0]);
+    }
+}
@@ -0,0 +1,125 @@
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::color::Color;
+use crate::draw::line::{bresenham, stroke};
+use crate::draw::plot;
+use crate::image::Image;
+use crate::pixel::PixelVec;
+use crate::ty::Type;
+
+/// Determines which parts of a self-intersecting polygon are filled
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FillRule {
+    /// Points are inside when a ray from them crosses the outline an odd number of times
+    EvenOdd,
+    /// Points are inside when the outline winds around them at least once
+    #[default]
+    NonZero,
+}
+
+/// Draw lines connecting consecutive points, when `closed` is set the last point is connected
+/// to the first. One pixel wide lines without anti-aliasing use Bresenham's algorithm, other
+/// lines have round joins. Every pixel is drawn at most once.
+pub fn polyline<T: Type, C: Color, I: Image<T, C>>(
+    image: &mut I,
+    points: &[(f64, f64)],
+    closed: bool,
+    color: &PixelVec<f64>,
+    thickness: f64,
+    aa: bool,
+) {
+    if aa || thickness > 1.0 {
+        stroke(image, points, closed, color, thickness.max(1.0), aa);
+        return;
+    }
+
+    let pixels: Vec<(isize, isize)> = points
+        .iter()
+        .map(|(x, y)| (x.round() as isize, y.round() as isize))
+        .collect();
+    if pixels.is_empty() {
+        return;
+    }
+
+    let mut segments: Vec<_> = pixels.windows(2).map(|w| (w[0], w[1])).collect();
+    if closed && pixels.len() > 2 {
+        segments.push((pixels[pixels.len() - 1], pixels[0]));
+    }
+
+    plot(image, pixels[0].0, pixels[0].1, color, 1.0);
+    let mut last = pixels[0];
+    for (i, (a, b)) in segments.iter().enumerate() {
+        let closing = closed && i == segments.len() - 1;
+        bresenham(*a, *b, |x, y| {
+            // Skip the pixels shared with the previous segment, and the first pixel when closing
+            if (x, y) == last || (closing && (x, y) == pixels[0]) {
+                return;
+            }
+
+            plot(image, x, y, color, 1.0);
+            last = (x, y);
+        });
+    }
+}
+
+/// Fill a polygon using scanline filling, concave and self-intersecting polygons are filled
+/// according to `rule`. A pixel is filled when its center lies inside of the polygon.
+pub fn polygon_filled<T: Type, C: Color, I: Image<T, C>>(
+    image: &mut I,
+    points: &[(f64, f64)],
+    color: &PixelVec<f64>,
+    rule: FillRule,
+) {
+    if points.len() < 3 {
+        return;
+    }
+
+    let n = points.len();
+    let min_y = points.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+    let max_y = points.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max);
+    let y0 = min_y.ceil().max(0.0) as isize;
+    let y1 = max_y.ceil().min(image.height() as f64) as isize;
+    let width = image.width() as f64;
+
+    let mut crossings: Vec<(f64, i32)> = Vec::new();
+    for y in y0..y1 {
+        let fy = y as f64;
+        crossings.clear();
+        for i in 0..n {
+            let (a, b) = (points[i], points[(i + 1) % n]);
+            // Edges include their upper endpoint but not their lower one, so vertices shared
+            // by two edges are only counted once
+            let direction = if a.1 <= fy && fy < b.1 {
+                1
+            } else if b.1 <= fy && fy < a.1 {
+                -1
+            } else {
+                continue;
+            };
+            let x = a.0 + (fy - a.1) / (b.1 - a.1) * (b.0 - a.0);
+            crossings.push((x, direction));
+        }
+        crossings.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(core::cmp::Ordering::Equal));
+
+        let mut winding = 0;
+        for pair in crossings.windows(2) {
+            winding += pair[0].1;
+            let inside = match rule {
+                FillRule::EvenOdd => winding % 2 != 0,
+                FillRule::NonZero => winding != 0,
+            };
+            if !inside {
+                continue;
+            }
+
+            let start = pair[0].0.ceil().max(0.0);
+            let end = pair[1].0.ceil().min(width);
+            for x in start as isize..end as isize {
+                plot(image, x, y, color, 1.0);
+            }
+        }
+    }
+}
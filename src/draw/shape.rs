@@ -0,0 +1,190 @@
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::color::Color;
+use crate::draw::plot;
+use crate::image::Image;
+use crate::pixel::PixelVec;
+use crate::rect::Rect;
+use crate::ty::Type;
+
+/// Fill the pixels from (x0, y0) up to but not including (x1, y1), clipped to the image
+fn fill_area<T: Type, C: Color, I: Image<T, C>>(
+    image: &mut I,
+    (x0, y0): (usize, usize),
+    (x1, y1): (usize, usize),
+    color: &PixelVec<f64>,
+) {
+    let x1 = x1.min(image.width());
+    let y1 = y1.min(image.height());
+    for y in y0..y1 {
+        for x in x0..x1 {
+            plot(image, x as isize, y as isize, color, 1.0);
+        }
+    }
+}
+
+/// Draw the outline of a rectangle, the border is `thickness` pixels wide and lies inside of
+/// `rect`
+pub fn rect<T: Type, C: Color, I: Image<T, C>>(
+    image: &mut I,
+    rect: Rect,
+    color: &PixelVec<f64>,
+    thickness: usize,
+) {
+    let t = thickness.max(1);
+    if rect.is_empty() || 2 * t >= rect.width || 2 * t >= rect.height {
+        rect_filled(image, rect, color);
+        return;
+    }
+
+    let (x0, y0) = (rect.x, rect.y);
+    let (x1, y1) = (rect.x + rect.width, rect.y + rect.height);
+    fill_area(image, (x0, y0), (x1, y0 + t), color);
+    fill_area(image, (x0, y1 - t), (x1, y1), color);
+    fill_area(image, (x0, y0 + t), (x0 + t, y1 - t), color);
+    fill_area(image, (x1 - t, y0 + t), (x1, y1 - t), color);
+}
+
+/// Fill a rectangle
+pub fn rect_filled<T: Type, C: Color, I: Image<T, C>>(
+    image: &mut I,
+    rect: Rect,
+    color: &PixelVec<f64>,
+) {
+    fill_area(
+        image,
+        (rect.x, rect.y),
+        (rect.x + rect.width, rect.y + rect.height),
+        color,
+    );
+}
+
+/// Fill the pixels covered by a shape described by a signed distance function, which is
+/// negative inside of the shape. `bounds` is the (min x, min y, max x, max y) extent of the
+/// shape. When `outline` is set only pixels within `outline / 2` of the edge are drawn.
+fn fill_distance<T: Type, C: Color, I: Image<T, C>, F: Fn(f64, f64) -> f64>(
+    image: &mut I,
+    bounds: (f64, f64, f64, f64),
+    color: &PixelVec<f64>,
+    outline: Option<f64>,
+    aa: bool,
+    distance: F,
+) {
+    let pad = outline.unwrap_or(0.0) / 2.0 + 1.0;
+    let x0 = (bounds.0 - pad).floor().max(0.0);
+    let y0 = (bounds.1 - pad).floor().max(0.0);
+    let x1 = (bounds.2 + pad).ceil().min(image.width() as f64 - 1.0);
+    let y1 = (bounds.3 + pad).ceil().min(image.height() as f64 - 1.0);
+    if x1 < x0 || y1 < y0 {
+        return;
+    }
+
+    for y in y0 as isize..=y1 as isize {
+        for x in x0 as isize..=x1 as isize {
+            let mut d = distance(x as f64, y as f64);
+            if let Some(width) = outline {
+                d = d.abs() - width / 2.0;
+            }
+
+            let coverage = if aa {
+                (0.5 - d).clamp(0.0, 1.0)
+            } else if d <= 0.0 {
+                1.0
+            } else {
+                0.0
+            };
+            plot(image, x, y, color, coverage);
+        }
+    }
+}
+
+fn circle_bounds(center: (f64, f64), radius: f64) -> (f64, f64, f64, f64) {
+    (
+        center.0 - radius,
+        center.1 - radius,
+        center.0 + radius,
+        center.1 + radius,
+    )
+}
+
+/// Draw the outline of a circle, when `aa` is set the edges are anti-aliased
+pub fn circle<T: Type, C: Color, I: Image<T, C>>(
+    image: &mut I,
+    center: (f64, f64),
+    radius: f64,
+    color: &PixelVec<f64>,
+    thickness: f64,
+    aa: bool,
+) {
+    fill_distance(
+        image,
+        circle_bounds(center, radius),
+        color,
+        Some(thickness.max(1.0)),
+        aa,
+        |x, y| ((x - center.0).powi(2) + (y - center.1).powi(2)).sqrt() - radius,
+    );
+}
+
+/// Fill a circle, when `aa` is set the edges are anti-aliased
+pub fn circle_filled<T: Type, C: Color, I: Image<T, C>>(
+    image: &mut I,
+    center: (f64, f64),
+    radius: f64,
+    color: &PixelVec<f64>,
+    aa: bool,
+) {
+    fill_distance(
+        image,
+        circle_bounds(center, radius),
+        color,
+        None,
+        aa,
+        |x, y| ((x - center.0).powi(2) + (y - center.1).powi(2)).sqrt() - radius,
+    );
+}
+
+/// Approximate signed distance from a point to an ellipse with radii `(rx, ry)` rotated by
+/// `angle` degrees around `center`
+fn ellipse_distance(
+    (x, y): (f64, f64),
+    center: (f64, f64),
+    (rx, ry): (f64, f64),
+    angle: f64,
+) -> f64 {
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let (dx, dy) = (x - center.0, y - center.1);
+    let (u, v) = (cos * dx + sin * dy, -sin * dx + cos * dy);
+    let (rx, ry) = (rx.max(1e-6), ry.max(1e-6));
+
+    // First order approximation: the implicit function divided by the length of its gradient
+    let f = (u / rx).powi(2) + (v / ry).powi(2) - 1.0;
+    let gradient = ((2.0 * u / (rx * rx)).powi(2) + (2.0 * v / (ry * ry)).powi(2)).sqrt();
+    if gradient < 1e-12 {
+        return -rx.min(ry);
+    }
+    f / gradient
+}
+
+/// Draw an ellipse with radii `(rx, ry)` rotated clockwise by `angle` degrees. When `thickness`
+/// is `None` the ellipse is filled, when `aa` is set the edges are anti-aliased.
+pub fn ellipse<T: Type, C: Color, I: Image<T, C>>(
+    image: &mut I,
+    center: (f64, f64),
+    radii: (f64, f64),
+    angle: f64,
+    color: &PixelVec<f64>,
+    thickness: Option<f64>,
+    aa: bool,
+) {
+    let r = radii.0.max(radii.1);
+    fill_distance(
+        image,
+        circle_bounds(center, r),
+        color,
+        thickness.map(|t| t.max(1.0)),
+        aa,
+        |x, y| ellipse_distance((x, y), center, radii, angle),
+    );
+}
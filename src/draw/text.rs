@@ -0,0 +1,251 @@
+use std::path::Path;
+
+use ab_glyph::{Font as _, FontArc, PxScale, PxScaleFont, ScaleFont};
+
+use crate::color::Color;
+use crate::draw::plot;
+use crate::error::{with_path, Error};
+use crate::image::Image;
+use crate::pixel::PixelVec;
+use crate::ty::Type;
+
+/// A TrueType or OpenType font
+#[derive(Clone)]
+pub struct Font(FontArc);
+
+impl Font {
+    /// Load a font from the contents of a TTF or OTF file
+    pub fn from_bytes(data: Vec<u8>) -> Result<Font, Error> {
+        FontArc::try_from_vec(data)
+            .map(Font)
+            .map_err(|e| Error::Message(format!("Invalid font: {}", e)))
+    }
+
+    /// Load a font from a TTF or OTF file
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Font, Error> {
+        with_path(path, |path| Font::from_bytes(std::fs::read(path)?))
+    }
+}
+
+impl std::fmt::Debug for Font {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_tuple("Font").finish()
+    }
+}
+
+/// Horizontal alignment of each line of text relative to the position it is drawn at
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// Text layout options
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextOptions {
+    pub align: Align,
+    /// Wrap lines at word boundaries so they are no wider than this many pixels
+    pub max_width: Option<f64>,
+    /// Multiplier applied to the default line height of the font
+    pub line_spacing: f64,
+}
+
+impl Default for TextOptions {
+    fn default() -> TextOptions {
+        TextOptions {
+            align: Align::Left,
+            max_width: None,
+            line_spacing: 1.0,
+        }
+    }
+}
+
+fn line_width(font: &PxScaleFont<&FontArc>, line: &str) -> f64 {
+    let mut width = 0.0;
+    let mut prev = None;
+    for c in line.chars() {
+        let id = font.glyph_id(c);
+        if let Some(prev) = prev {
+            width += font.kern(prev, id);
+        }
+        width += font.h_advance(id);
+        prev = Some(id);
+    }
+    width as f64
+}
+
+/// Split `text` into lines at newlines, and at spaces when a line would be wider than
+/// `max_width`. Words that are wider than `max_width` are not broken.
+fn layout(font: &PxScaleFont<&FontArc>, text: &str, max_width: Option<f64>) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        let max_width = match max_width {
+            Some(w) => w,
+            None => {
+                lines.push(paragraph.to_string());
+                continue;
+            }
+        };
+
+        let mut line = String::new();
+        for word in paragraph.split(' ') {
+            let candidate = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", line, word)
+            };
+
+            if line.is_empty() || line_width(font, &candidate) <= max_width {
+                line = candidate;
+            } else {
+                lines.push(std::mem::replace(&mut line, word.to_string()));
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Returns the width and height of the box covered by `text` when drawn using `text_with`
+pub fn text_size(text: &str, font: &Font, size: f64, options: &TextOptions) -> (f64, f64) {
+    let font = font.0.as_scaled(PxScale::from(size as f32));
+    let lines = layout(&font, text, options.max_width);
+    let line_height = (font.height() + font.line_gap()) as f64 * options.line_spacing;
+    let width = lines
+        .iter()
+        .map(|l| line_width(&font, l))
+        .fold(0.0, f64::max);
+    let height = if lines.is_empty() {
+        0.0
+    } else {
+        (lines.len() - 1) as f64 * line_height + font.height() as f64
+    };
+    (width, height)
+}
+
+/// Draw `text` with its top-left corner at `pos`, using a font size of `size` pixels
+pub fn text<T: Type, C: Color, I: Image<T, C>>(
+    image: &mut I,
+    text: &str,
+    font: &Font,
+    size: f64,
+    pos: (f64, f64),
+    color: &PixelVec<f64>,
+) {
+    text_with(image, text, font, size, pos, color, &TextOptions::default());
+}
+
+/// Draw `text` using a font size of `size` pixels. The top of the first line is placed at
+/// `pos.1`, and each line starts at, is centered on or ends at `pos.0` depending on
+/// `options.align`.
+pub fn text_with<T: Type, C: Color, I: Image<T, C>>(
+    image: &mut I,
+    text: &str,
+    font: &Font,
+    size: f64,
+    pos: (f64, f64),
+    color: &PixelVec<f64>,
+    options: &TextOptions,
+) {
+    let scale = PxScale::from(size as f32);
+    let font = font.0.as_scaled(scale);
+    let line_height = (font.height() + font.line_gap()) as f64 * options.line_spacing;
+
+    for (i, line) in layout(&font, text, options.max_width).iter().enumerate() {
+        let width = line_width(&font, line);
+        let mut x = match options.align {
+            Align::Left => pos.0,
+            Align::Center => pos.0 - width / 2.0,
+            Align::Right => pos.0 - width,
+        } as f32;
+        let baseline = (pos.1 + i as f64 * line_height) as f32 + font.ascent();
+
+        let mut prev = None;
+        for c in line.chars() {
+            let id = font.glyph_id(c);
+            if let Some(prev) = prev {
+                x += font.kern(prev, id);
+            }
+            prev = Some(id);
+
+            let glyph = id.with_scale_and_position(scale, ab_glyph::point(x, baseline));
+            x += font.h_advance(id);
+            if let Some(outline) = font.outline_glyph(glyph) {
+                let bounds = outline.px_bounds();
+                outline.draw(|gx, gy, coverage| {
+                    plot(
+                        image,
+                        bounds.min.x as isize + gx as isize,
+                        bounds.min.y as isize + gy as isize,
+                        color,
+                        coverage as f64,
+                    );
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Gray, ImageBuf};
+
+    /// `test/boxes.ttf` maps every ASCII letter to a 500x700 unit box with an advance of 600
+    /// units and an ascent of 800 units, spaces advance 300 units. At a size of 10 pixels each
+    /// letter covers x + 0.5 to x + 5.5 and advances 6 pixels, lines are 10 pixels high.
+    fn bounds(image: &ImageBuf<f32, Gray>) -> (usize, usize, usize, usize) {
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (usize::MAX, usize::MAX, 0, 0);
+        image.for_each_pixel(|(x, y), px| {
+            if px[0] > 0.0 {
+                min_x = min_x.min(x);
+                min_y = min_y.min(y);
+                max_x = max_x.max(x);
+                max_y = max_y.max(y);
+            }
+        });
+        (min_x, min_y, max_x, max_y)
+    }
+
+    #[test]
+    fn test_text_layout() {
+        let font = Font::open("test/boxes.ttf").unwrap();
+        let white = PixelVec::new(1.0, 1.0, 1.0, 1.0);
+        let draw = |text: &str, options: &TextOptions| {
+            let mut image: ImageBuf<f32, Gray> = ImageBuf::new(64, 40);
+            text_with(&mut image, text, &font, 10.0, (20.0, 5.0), &white, options);
+            bounds(&image)
+        };
+
+        // The glyphs of the first line cover rows 6 to 12, from 1 to 8 pixels below the top
+        let align = |align| TextOptions {
+            align,
+            ..TextOptions::default()
+        };
+        assert_eq!(draw("ab", &align(Align::Left)), (20, 6, 31, 12));
+        assert_eq!(draw("ab", &align(Align::Center)), (14, 6, 25, 12));
+        assert_eq!(draw("ab", &align(Align::Right)), (8, 6, 19, 12));
+        assert_eq!(
+            text_size("ab", &font, 10.0, &TextOptions::default()),
+            (12.0, 10.0)
+        );
+
+        // "ab ab" is 27 pixels wide, so it is wrapped into two lines 15 pixels apart
+        let wrapped = TextOptions {
+            align: Align::Left,
+            max_width: Some(14.0),
+            line_spacing: 1.5,
+        };
+        assert_eq!(draw("ab ab", &wrapped), (20, 6, 31, 27));
+        assert_eq!(text_size("ab ab", &font, 10.0, &wrapped), (12.0, 25.0));
+        let unwrapped = TextOptions {
+            max_width: Some(27.0),
+            ..wrapped
+        };
+        assert_eq!(draw("ab ab", &unwrapped), (20, 6, 46, 12));
+    }
+}
@@ -0,0 +1,410 @@
+//! Stylistic effects
+
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::color::Color;
+use crate::filter::Filter;
+use crate::gradient::Gradient;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::kernel;
+use crate::parallel;
+use crate::pixel::PixelVec;
+use crate::ty::Type;
+
+fn luminance<T: Type, C: Color, I: Image<T, C>>(image: &I, x: usize, y: usize) -> f64 {
+    if C::channels() < 3 {
+        return image.get_f(x, y, 0);
+    }
+
+    image.get_f(x, y, 0) * 0.21 + image.get_f(x, y, 1) * 0.72 + image.get_f(x, y, 2) * 0.07
+}
+
+fn tone<T: Type, C: Color, I: Image<T, C>>(
+    image: &I,
+    x: usize,
+    y: usize,
+    c: usize,
+    color: PixelVec<f64>,
+) -> f64 {
+    let color = color.as_ref();
+    if C::channels() < 3 {
+        return (color[0] + color[1] + color[2]) / 3.0;
+    }
+
+    if c >= 3 {
+        return image.get_f(x, y, c);
+    }
+
+    color[c]
+}
+
+/// Tints the luminance of an image using the classic sepia tone coefficients
+pub struct Sepia;
+filter!(Sepia, input, |_, x, y, c| {
+    let l = luminance(input[0], x, y);
+    let color = PixelVec::new(
+        (l * 1.351).min(1.0),
+        (l * 1.203).min(1.0),
+        (l * 0.937).min(1.0),
+        1.0,
+    );
+    tone(input[0], x, y, c, color)
+});
+
+/// Maps luminance onto a gradient between two colors
+pub struct Duotone {
+    gradient: Gradient,
+}
+
+impl Duotone {
+    pub fn new(dark: PixelVec<f64>, light: PixelVec<f64>) -> Duotone {
+        Duotone {
+            gradient: Gradient::from_colors(&[dark, light]),
+        }
+    }
+}
+
+filter!(Duotone, input, |this: &Duotone, x, y, c| {
+    let l = luminance(input[0], x, y);
+    tone(input[0], x, y, c, this.gradient.at(l))
+});
+
+/// Maps luminance onto an arbitrary gradient
+pub struct GradientMap(pub Gradient);
+filter!(GradientMap, input, |this: &GradientMap, x, y, c| {
+    let l = luminance(input[0], x, y);
+    tone(input[0], x, y, c, this.0.at(l))
+});
+
+fn apply<T: Type, C: Color, I: Image<T, C>, F: Filter>(image: &I, filter: F) -> ImageBuf<T, C> {
+    let mut dest = ImageBuf::new(image.width(), image.height());
+    dest.copy_metadata(image);
+    filter.eval(&mut dest, &[image]);
+    dest
+}
+
+/// Apply a sepia tone
+pub fn sepia<T: Type, C: Color, I: Image<T, C>>(image: &I) -> ImageBuf<T, C> {
+    apply(image, Sepia)
+}
+
+/// Map shadows to `dark` and highlights to `light`
+pub fn duotone<T: Type, C: Color, I: Image<T, C>>(
+    image: &I,
+    dark: PixelVec<f64>,
+    light: PixelVec<f64>,
+) -> ImageBuf<T, C> {
+    apply(image, Duotone::new(dark, light))
+}
+
+/// Recolor an image by looking up the luminance of each pixel in `gradient`
+pub fn gradient_map<T: Type, C: Color, I: Image<T, C>>(
+    image: &I,
+    gradient: &Gradient,
+) -> ImageBuf<T, C> {
+    apply(image, GradientMap(gradient.clone()))
+}
+
+fn pixelate_into<T: Type, C: Color, I: Image<T, C>>(
+    dest: &mut ImageBuf<T, C>,
+    image: &I,
+    (x, y, width, height): (usize, usize, usize, usize),
+    block_size: usize,
+) {
+    let block_size = block_size.max(1);
+    let x1 = (x + width).min(image.width());
+    let y1 = (y + height).min(image.height());
+    let mut sum = vec![0.0; C::channels()];
+
+    for by in (y..y1).step_by(block_size) {
+        let by1 = (by + block_size).min(y1);
+        for bx in (x..x1).step_by(block_size) {
+            let bx1 = (bx + block_size).min(x1);
+            let count = ((by1 - by) * (bx1 - bx)) as f64;
+
+            sum.iter_mut().for_each(|s| *s = 0.0);
+            for j in by..by1 {
+                for i in bx..bx1 {
+                    for (s, v) in sum.iter_mut().zip(image.at(i, j)) {
+                        *s += T::to_float(v);
+                    }
+                }
+            }
+
+            for j in by..by1 {
+                for i in bx..bx1 {
+                    for (px, s) in dest.at_mut(i, j).iter_mut().zip(&sum) {
+                        *px = T::from_float(T::clamp(s / count));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Pixelate an image by replacing each `block_size` x `block_size` block with its average color
+pub fn pixelate<T: Type, C: Color, I: Image<T, C>>(image: &I, block_size: usize) -> ImageBuf<T, C> {
+    let mut dest = ImageBuf::new(image.width(), image.height());
+    dest.copy_metadata(image);
+    pixelate_into(
+        &mut dest,
+        image,
+        (0, 0, image.width(), image.height()),
+        block_size,
+    );
+    dest
+}
+
+/// Pixelate only the region specified by (x, y, width, height), the rest of the image is copied
+/// unchanged. This is typically used to redact faces or license plates.
+pub fn pixelate_region<T: Type, C: Color, I: Image<T, C>>(
+    image: &I,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    block_size: usize,
+) -> ImageBuf<T, C> {
+    let mut dest = Image::clone(image);
+    pixelate_into(&mut dest, image, (x, y, width, height), block_size);
+    dest
+}
+
+/// Apply a generalized Kuwahara filter: an edge-preserving smoothing filter that produces a
+/// painterly look. Each pixel is set to the weighted mean of eight circular sectors of the given
+/// `radius`, where sectors with lower variance receive higher weights.
+pub fn kuwahara<T: Type, C: Color, I: Image<T, C>>(image: &I, radius: usize) -> ImageBuf<T, C> {
+    const SECTORS: usize = 8;
+    const SHARPNESS: f64 = 8.0;
+
+    let r = radius.max(1) as isize;
+    let sigma = r as f64 / 2.0;
+    let mut offsets = Vec::new();
+    for dy in -r..=r {
+        for dx in -r..=r {
+            let d2 = dx * dx + dy * dy;
+            if d2 > r * r {
+                continue;
+            }
+
+            let w = (-(d2 as f64) / (2.0 * sigma * sigma)).exp();
+            if d2 == 0 {
+                for k in 0..SECTORS {
+                    offsets.push((dx, dy, k, w));
+                }
+                continue;
+            }
+
+            let angle = (dy as f64).atan2(dx as f64) + core::f64::consts::PI;
+            let k = (angle / (2.0 * core::f64::consts::PI) * SECTORS as f64) as usize % SECTORS;
+            offsets.push((dx, dy, k, w));
+        }
+    }
+
+    let (width, height, channels) = image.shape();
+    let color_channels = if C::has_alpha() {
+        channels - 1
+    } else {
+        channels
+    };
+    let max_x = width as isize - 1;
+    let max_y = height as isize - 1;
+
+    let mut dest = ImageBuf::new(width, height);
+    dest.copy_metadata(image);
+    parallel::for_each_row(dest.data_mut(), width * channels, |y, row| {
+        // Weighted sums of each channel, stored at `k * channels + c` for sector `k`
+        let mut sum = vec![0.0; SECTORS * channels];
+        let mut sq = vec![0.0; SECTORS * channels];
+        let mut out = vec![0.0; channels];
+
+        for (x, px) in row.chunks_exact_mut(channels).enumerate() {
+            sum.iter_mut().for_each(|v| *v = 0.0);
+            sq.iter_mut().for_each(|v| *v = 0.0);
+            out.iter_mut().for_each(|v| *v = 0.0);
+            let mut weight = [0.0; SECTORS];
+
+            for &(dx, dy, k, w) in &offsets {
+                let i = (x as isize + dx).max(0).min(max_x) as usize;
+                let j = (y as isize + dy).max(0).min(max_y) as usize;
+                let src = image.at(i, j);
+                for (c, v) in src.iter().enumerate() {
+                    let v = T::to_f(v);
+                    sum[k * channels + c] += w * v;
+                    sq[k * channels + c] += w * v * v;
+                }
+                weight[k] += w;
+            }
+
+            let mut total = 0.0;
+            for k in 0..SECTORS {
+                let sum = &mut sum[k * channels..(k + 1) * channels];
+                let sq = &sq[k * channels..(k + 1) * channels];
+                let mut variance = 0.0;
+                for c in 0..channels {
+                    sum[c] /= weight[k];
+                    if c < color_channels {
+                        variance += (sq[c] / weight[k] - sum[c] * sum[c]).abs();
+                    }
+                }
+
+                let w = 1.0 / (1.0 + (255.0 * variance).powf(SHARPNESS / 2.0));
+                for (o, s) in out.iter_mut().zip(sum.iter()) {
+                    *o += w * s;
+                }
+                total += w;
+            }
+
+            for (p, o) in px.iter_mut().zip(&out) {
+                *p = T::from_f(o / total);
+            }
+        }
+    });
+    dest
+}
+
+/// Simulate linear motion by blurring along a line of `length` pixels at `angle` degrees
+pub fn motion_blur<T: Type, C: Color, I: Image<T, C>>(
+    image: &I,
+    angle: f64,
+    length: usize,
+) -> ImageBuf<T, C> {
+    apply(image, kernel::line(length, angle))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::gradient::Gradient;
+    use crate::{effect, Color, Gray, Image, ImageBuf, PixelVec, Rgb, Rgba};
+
+    /// Color with more channels than any of the built-in colors
+    struct Bands;
+
+    impl Color for Bands {
+        fn name() -> &'static str {
+            "bands"
+        }
+
+        fn channels() -> usize {
+            6
+        }
+
+        fn has_alpha() -> bool {
+            false
+        }
+    }
+
+    fn close(a: &[f32], b: &[f32]) -> bool {
+        a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5)
+    }
+
+    #[test]
+    fn test_color_effects() {
+        // Black, mid gray and white
+        let image: ImageBuf<f32, Rgba> = ImageBuf::new_from(
+            3,
+            1,
+            vec![0.0, 0.0, 0.0, 1.0, 0.5, 0.5, 0.5, 0.5, 1.0, 1.0, 1.0, 1.0],
+        );
+
+        // Sepia keeps black and tints everything else, the alpha channel is unchanged
+        let sepia = effect::sepia(&image);
+        assert!(close(sepia.at(0, 0), &[0.0, 0.0, 0.0, 1.0]));
+        assert!(close(sepia.at(1, 0), &[0.6755, 0.6015, 0.4685, 0.5]));
+        assert!(close(sepia.at(2, 0), &[1.0, 1.0, 0.937, 1.0]));
+
+        // Duotone maps black to the dark color and white to the light color
+        let dark = PixelVec::new(0.1, 0.0, 0.3, 1.0);
+        let light = PixelVec::new(1.0, 0.9, 0.5, 1.0);
+        let duotone = effect::duotone(&image, dark, light);
+        assert!(close(duotone.at(0, 0), &[0.1, 0.0, 0.3, 1.0]));
+        assert!(close(duotone.at(1, 0), &[0.55, 0.45, 0.4, 0.5]));
+        assert!(close(duotone.at(2, 0), &[1.0, 0.9, 0.5, 1.0]));
+
+        // Gray images use the average of the color
+        let gray: ImageBuf<f32, Gray> = ImageBuf::new_from(2, 1, vec![0.0, 1.0]);
+        let duotone = effect::duotone(&gray, dark, light);
+        assert!(close(duotone.data(), &[0.4 / 3.0, 2.4 / 3.0]));
+
+        // The gradient is sampled at the luminance of each pixel
+        let gradient = Gradient::from_colors(&[
+            PixelVec::new(1.0, 0.0, 0.0, 1.0),
+            PixelVec::new(0.0, 1.0, 0.0, 1.0),
+            PixelVec::new(0.0, 0.0, 1.0, 1.0),
+        ]);
+        let mapped = effect::gradient_map(&image, &gradient);
+        assert!(close(mapped.at(0, 0), &[1.0, 0.0, 0.0, 1.0]));
+        assert!(close(mapped.at(1, 0), &[0.0, 1.0, 0.0, 0.5]));
+        assert!(close(mapped.at(2, 0), &[0.0, 0.0, 1.0, 1.0]));
+        let quarter: ImageBuf<f32, Rgb> = ImageBuf::new_from(1, 1, vec![0.25, 0.25, 0.25]);
+        let mapped = effect::gradient_map(&quarter, &gradient);
+        assert!(close(mapped.data(), &[0.5, 0.5, 0.0]));
+    }
+
+    #[test]
+    fn test_motion_blur() {
+        let mut image: ImageBuf<f32, Gray> = ImageBuf::new(15, 15);
+        image.at_mut(7, 7)[0] = 1.0;
+
+        // A single point is spread along the direction of motion, keeping its total brightness
+        let horizontal = effect::motion_blur(&image, 0.0, 5);
+        assert!((horizontal.data().iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        assert!((5..=9).all(|x| horizontal.at(x, 7)[0] > 0.0));
+        assert_eq!(horizontal.at(7, 6)[0], 0.0);
+        assert_eq!(horizontal.at(7, 8)[0], 0.0);
+
+        let vertical = effect::motion_blur(&image, 90.0, 5);
+        assert!((5..=9).all(|y| vertical.at(7, y)[0] > 0.0));
+        assert_eq!(vertical.at(6, 7)[0], 0.0);
+        assert_eq!(vertical.at(8, 7)[0], 0.0);
+    }
+
+    #[test]
+    fn test_kuwahara() {
+        // A vertical edge between two noisy regions
+        let noise = |x: usize, y: usize| if (x + y).is_multiple_of(2) { 0.05 } else { -0.05 };
+        let mut image: ImageBuf<f32, Gray> = ImageBuf::new(24, 16);
+        image.for_each(|(x, y), px| {
+            let base = if x < 12 { 0.2 } else { 0.8 };
+            px[0] = base + noise(x, y);
+        });
+
+        let dest = effect::kuwahara(&image, 3);
+        let row: Vec<f32> = (0..24).map(|x| dest.at(x, 8)[0]).collect();
+
+        // The edge stays sharp
+        assert!(row[11] < 0.3 && row[12] > 0.7, "{:?}", row);
+
+        // Flat regions are smoothed
+        let deviation = |image: &ImageBuf<f32, Gray>, x0: usize| {
+            let values: Vec<f32> = (x0..x0 + 6).map(|x| image.at(x, 8)[0]).collect();
+            let mean = values.iter().sum::<f32>() / 6.0;
+            values.iter().map(|v| (v - mean).abs()).fold(0.0, f32::max)
+        };
+        for x0 in &[2, 16] {
+            assert!(deviation(&dest, *x0) < deviation(&image, *x0) / 2.0);
+        }
+
+        // Any number of channels is supported
+        let bands: ImageBuf<f32, Bands> =
+            ImageBuf::new_from(3, 3, (0..54).map(|i| (i % 6) as f32 / 10.0).collect());
+        let dest = effect::kuwahara(&bands, 1);
+        for c in 0..6 {
+            assert!((dest.at(1, 1)[c] - c as f32 / 10.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_pixelate() {
+        let image: ImageBuf<u8, Gray> =
+            ImageBuf::new_from(4, 2, vec![0, 10, 20, 30, 40, 50, 60, 70]);
+        let dest = effect::pixelate(&image, 2);
+        assert_eq!(dest.data(), &[25, 25, 45, 45, 25, 25, 45, 45]);
+
+        let dest = effect::pixelate_region(&image, 2, 0, 2, 2, 2);
+        assert_eq!(dest.data(), &[0, 10, 45, 45, 40, 50, 45, 45]);
+    }
+}
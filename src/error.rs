@@ -1,21 +1,131 @@
-#[cfg(feature = "io")]
-use crate::io;
+use alloc::string::String;
+use core::fmt;
+#[cfg(feature = "std")]
 use std::io::Error as IOError;
+#[cfg(feature = "std")]
+use std::path::{Path, PathBuf};
 
+/// Error type used by every module in image2, some variants are only available with certain
+/// features so new variants may be added without a breaking change
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
-    #[cfg(feature = "io")]
-    Magick(io::magick::Error),
+    /// Reading or writing data failed
+    #[cfg(feature = "std")]
     IO(IOError),
+    /// Any other error, described by the message
     Message(String),
+    /// The color isn't supported by the operation or format
     InvalidColor,
+    /// The type isn't supported by the operation or format
     InvalidType,
+    /// The size of an image doesn't match its data
+    InvalidImageShape,
+    /// The data can't be decoded as an image
+    InvalidImageData,
+    /// The file or pixel format isn't supported
+    UnsupportedFormat(String),
+    /// An external command, like ImageMagick's `convert`, couldn't be started or failed
+    #[cfg(feature = "std")]
+    Command {
+        command: String,
+        message: String,
+        source: Option<IOError>,
+    },
+    /// An error that occurred while reading or writing the file at `path`
+    #[cfg(feature = "std")]
+    Path { path: PathBuf, source: Box<Error> },
+    /// An error returned by another library, such as OpenCV or GStreamer
+    #[cfg(feature = "std")]
+    External(Box<dyn std::error::Error + Send + Sync>),
 }
 
+#[cfg(feature = "std")]
+impl Error {
+    /// Add the path of the file being read or written, errors that already have a path are
+    /// returned unchanged
+    pub fn with_path<P: AsRef<Path>>(self, path: P) -> Error {
+        match self {
+            err @ Error::Path { .. } => err,
+            err => Error::Path {
+                path: path.as_ref().to_path_buf(),
+                source: Box::new(err),
+            },
+        }
+    }
+
+    /// Path of the file that caused the error, if known
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Error::Path { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// The error without the path added by `with_path`
+    pub fn inner(&self) -> &Error {
+        match self {
+            Error::Path { source, .. } => source.inner(),
+            err => err,
+        }
+    }
+}
+
+/// Call `f` with `path`, adding the path to any error it returns
+#[cfg(feature = "std")]
+pub(crate) fn with_path<P: AsRef<Path>, T, F: FnOnce(&Path) -> Result<T, Error>>(
+    path: P,
+    f: F,
+) -> Result<T, Error> {
+    let path = path.as_ref();
+    f(path).map_err(|err| err.with_path(path))
+}
+
+/// Error for an external command, with the IO error that caused it if there is one
 #[cfg(feature = "io")]
-impl From<io::magick::Error> for Error {
-    fn from(err: io::magick::Error) -> Error {
-        Error::Magick(err)
+pub(crate) fn command_error(command: &str, message: String, source: Option<IOError>) -> Error {
+    Error::Command {
+        command: command.to_string(),
+        message,
+        source,
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            Error::IO(err) => write!(f, "{}", err),
+            Error::Message(msg) => f.write_str(msg),
+            Error::InvalidColor => f.write_str("Invalid color"),
+            Error::InvalidType => f.write_str("Invalid type"),
+            Error::InvalidImageShape => f.write_str("Invalid image shape"),
+            Error::InvalidImageData => f.write_str("Invalid image data"),
+            Error::UnsupportedFormat(format) => write!(f, "Unsupported format: {}", format),
+            #[cfg(feature = "std")]
+            Error::Command {
+                command, message, ..
+            } => write!(f, "{}: {}", command, message),
+            #[cfg(feature = "std")]
+            Error::Path { path, source } => write!(f, "{}: {}", path.display(), source),
+            #[cfg(feature = "std")]
+            Error::External(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::IO(err) => Some(err),
+            Error::Command {
+                source: Some(err), ..
+            } => Some(err),
+            Error::Path { source, .. } => Some(source.as_ref()),
+            Error::External(err) => Some(err.as_ref()),
+            _ => None,
+        }
     }
 }
 
@@ -25,8 +135,77 @@ impl From<String> for Error {
     }
 }
 
+#[cfg(feature = "std")]
 impl From<IOError> for Error {
     fn from(err: IOError) -> Error {
         Error::IO(err)
     }
 }
+
+#[cfg(feature = "v4l")]
+impl From<rscam::Error> for Error {
+    fn from(err: rscam::Error) -> Error {
+        match err {
+            rscam::Error::Io(err) => Error::IO(err),
+            err => Error::External(Box::new(err)),
+        }
+    }
+}
+
+#[cfg(feature = "opencv")]
+impl From<opencv::Error> for Error {
+    fn from(err: opencv::Error) -> Error {
+        Error::External(Box::new(err))
+    }
+}
+
+// ocl errors implement `failure::Fail` instead of `std::error::Error`, so only the message is
+// kept
+#[cfg(feature = "opencl")]
+impl From<ocl::Error> for Error {
+    fn from(err: ocl::Error) -> Error {
+        Error::External(err.to_string().into())
+    }
+}
+
+#[cfg(feature = "gst")]
+impl From<gstreamer::glib::Error> for Error {
+    fn from(err: gstreamer::glib::Error) -> Error {
+        Error::External(Box::new(err))
+    }
+}
+
+#[cfg(feature = "gst")]
+impl From<gstreamer::glib::BoolError> for Error {
+    fn from(err: gstreamer::glib::BoolError) -> Error {
+        Error::External(Box::new(err))
+    }
+}
+
+#[cfg(feature = "gst")]
+impl From<gstreamer::StateChangeError> for Error {
+    fn from(err: gstreamer::StateChangeError) -> Error {
+        Error::External(Box::new(err))
+    }
+}
+
+#[cfg(feature = "dataset")]
+impl From<arrow_schema::ArrowError> for Error {
+    fn from(err: arrow_schema::ArrowError) -> Error {
+        Error::External(Box::new(err))
+    }
+}
+
+#[cfg(feature = "dataset")]
+impl From<parquet::errors::ParquetError> for Error {
+    fn from(err: parquet::errors::ParquetError) -> Error {
+        Error::External(Box::new(err))
+    }
+}
+
+#[cfg(feature = "npz")]
+impl From<zip::result::ZipError> for Error {
+    fn from(err: zip::result::ZipError) -> Error {
+        Error::External(Box::new(err))
+    }
+}
@@ -0,0 +1,178 @@
+use std::path::Path;
+
+use crate::color::{Color, Rgba};
+use crate::error::Error;
+use crate::filter::{Filter, Invert};
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::io;
+use crate::kernel;
+use crate::transform;
+use crate::ty::Type;
+
+/// `Image2` is a high-level wrapper around `ImageBuf<u8, Rgba>` providing a fluent interface for
+/// the most common operations:
+///
+/// ```rust,no_run
+/// use image2::Image2;
+///
+/// Image2::open("input.jpg")?
+///     .resize(320, 240)
+///     .grayscale()
+///     .save("output.png")?;
+/// # Ok::<(), image2::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image2(ImageBuf<u8, Rgba>);
+
+impl Image2 {
+    /// Create a new, transparent image with the given size
+    pub fn new(width: usize, height: usize) -> Image2 {
+        Image2(ImageBuf::new(width, height))
+    }
+
+    /// Read an image from disk
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Image2, Error> {
+        Ok(Image2(io::read(path)?))
+    }
+
+    /// Write an image to disk, the format is determined by the file extension
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        io::write(path, &self.0)
+    }
+
+    /// Convert from any image type
+    pub fn from_image<T: Type, C: Color, I: Image<T, C>>(image: &I) -> Image2 {
+        let mut tmp: ImageBuf<u8, C> = ImageBuf::new(image.width(), image.height());
+        image.convert_type(&mut tmp);
+
+        let mut dest = ImageBuf::new(image.width(), image.height());
+        dest.for_each(|(x, y), px| {
+            let src = tmp.at(x, y);
+            if C::channels() < 3 {
+                px[0] = src[0];
+                px[1] = src[0];
+                px[2] = src[0];
+            } else {
+                px[..3].copy_from_slice(&src[..3]);
+            }
+            px[3] = if C::has_alpha() {
+                src[C::channels() - 1]
+            } else {
+                u8::MAX
+            };
+        });
+        Image2(dest)
+    }
+
+    pub fn width(&self) -> usize {
+        self.0.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.0.height()
+    }
+
+    /// Get a reference to the underlying `ImageBuf`
+    pub fn as_image_buf(&self) -> &ImageBuf<u8, Rgba> {
+        &self.0
+    }
+
+    /// Get a mutable reference to the underlying `ImageBuf`
+    pub fn as_image_buf_mut(&mut self) -> &mut ImageBuf<u8, Rgba> {
+        &mut self.0
+    }
+
+    /// Convert to the underlying `ImageBuf`
+    pub fn into_image_buf(self) -> ImageBuf<u8, Rgba> {
+        self.0
+    }
+
+    /// Resize to the given width and height, if one of the dimensions is 0 it will be calculated
+    /// to preserve the aspect ratio
+    pub fn resize(self, width: usize, height: usize) -> Image2 {
+        if width == 0 && height == 0 {
+            return self;
+        }
+        Image2(transform::resized(&self.0, width, height))
+    }
+
+    /// Scale the width and height by the given factor
+    pub fn scale(self, factor: f64) -> Image2 {
+        let width = (self.width() as f64 * factor).round() as usize;
+        let height = (self.height() as f64 * factor).round() as usize;
+        self.resize(width.max(1), height.max(1))
+    }
+
+    /// Extract the region specified by (x, y, width, height)
+    pub fn crop(self, x: usize, y: usize, width: usize, height: usize) -> Image2 {
+        Image2(self.0.crop(x, y, width, height))
+    }
+
+    /// Convert color channels to grayscale, the alpha channel is preserved
+    pub fn grayscale(mut self) -> Image2 {
+        self.0.for_each(|_, px| {
+            let v = f64::from(px[0]) * 0.21 + f64::from(px[1]) * 0.72 + f64::from(px[2]) * 0.07;
+            let v = v.round().min(255.0) as u8;
+            px[0] = v;
+            px[1] = v;
+            px[2] = v;
+        });
+        self
+    }
+
+    /// Invert color channels, the alpha channel is preserved
+    pub fn invert(mut self) -> Image2 {
+        let src = Clone::clone(&self.0);
+        Invert.eval(&mut self.0, &[&src]);
+        self.0.for_each2(&src, |_, px, src| px[3] = src[3]);
+        self
+    }
+
+    /// Apply a 5x5 gaussian blur
+    pub fn blur(self) -> Image2 {
+        self.filter(&kernel::gaussian_5x5())
+    }
+
+    /// Rotate 90 degrees clockwise
+    pub fn rotate90(self) -> Image2 {
+        let mut dest = ImageBuf::new(self.height(), self.width());
+        dest.copy_metadata(&self.0);
+        transform::rotate90(&mut dest, &self.0);
+        Image2(dest)
+    }
+
+    /// Rotate 180 degrees
+    pub fn rotate180(self) -> Image2 {
+        let mut dest = self.0.new_like();
+        transform::rotate180(&mut dest, &self.0);
+        Image2(dest)
+    }
+
+    /// Rotate 270 degrees clockwise
+    pub fn rotate270(self) -> Image2 {
+        let mut dest = ImageBuf::new(self.height(), self.width());
+        dest.copy_metadata(&self.0);
+        transform::rotate270(&mut dest, &self.0);
+        Image2(dest)
+    }
+
+    /// Apply any `Filter`
+    pub fn filter<F: Filter>(self, filter: &F) -> Image2 {
+        let mut dest = self.0.new_like();
+        filter.eval(&mut dest, &[&self.0]);
+        Image2(dest)
+    }
+}
+
+impl From<ImageBuf<u8, Rgba>> for Image2 {
+    fn from(image: ImageBuf<u8, Rgba>) -> Image2 {
+        Image2(image)
+    }
+}
+
+impl From<Image2> for ImageBuf<u8, Rgba> {
+    fn from(image: Image2) -> ImageBuf<u8, Rgba> {
+        image.0
+    }
+}
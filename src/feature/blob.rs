@@ -0,0 +1,103 @@
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::analysis::{connected_components, find_contours, Connectivity};
+use crate::color::{Color, Gray};
+use crate::feature::luminance;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::ty::Type;
+
+/// Parameters for `blob_detect`
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlobParams {
+    /// Normalized luminance threshold used to separate blobs from the background
+    pub threshold: f64,
+    /// Detect dark blobs on a light background instead of light blobs on a dark background
+    pub dark: bool,
+    /// Minimum area in pixels
+    pub min_area: usize,
+    /// Maximum area in pixels
+    pub max_area: usize,
+    /// Minimum circularity, `4 * pi * area / perimeter^2`, between 0 and 1 where 1 is a
+    /// perfect circle
+    pub min_circularity: f64,
+}
+
+impl Default for BlobParams {
+    fn default() -> BlobParams {
+        BlobParams {
+            threshold: 0.5,
+            dark: false,
+            min_area: 10,
+            max_area: usize::MAX,
+            min_circularity: 0.0,
+        }
+    }
+}
+
+/// A detected blob
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Blob {
+    pub x: f64,
+    pub y: f64,
+    /// Radius of a circle with the same area
+    pub radius: f64,
+    /// Area in pixels
+    pub area: usize,
+    pub circularity: f64,
+}
+
+/// Detect blobs by thresholding the image and measuring each connected region. Regions
+/// touching the image border are included.
+pub fn blob_detect<T: Type, C: Color, I: Image<T, C>>(image: &I, params: &BlobParams) -> Vec<Blob> {
+    let (width, height) = (image.width(), image.height());
+    let binary: ImageBuf<u8, Gray> = ImageBuf::new_from(
+        width,
+        height,
+        luminance(image)
+            .into_iter()
+            .map(|l| ((l > params.threshold) != params.dark) as u8)
+            .collect(),
+    );
+
+    let (labels, components) = connected_components(&binary, Connectivity::Eight);
+    let mut perimeters = vec![0.0; components.len()];
+    for contour in find_contours(&binary) {
+        if contour.is_hole {
+            continue;
+        }
+
+        let (x, y) = contour.points[0];
+        let label = labels.at(x, y)[0];
+        if label > 0 {
+            perimeters[label as usize - 1] = contour.perimeter();
+        }
+    }
+
+    components
+        .iter()
+        .zip(perimeters)
+        .filter(|(c, _)| c.area >= params.min_area && c.area <= params.max_area)
+        .filter_map(|(c, perimeter)| {
+            // Contours pass through pixel centers, so add half a pixel on each side
+            let perimeter = perimeter + core::f64::consts::PI;
+            let circularity =
+                (4.0 * core::f64::consts::PI * c.area as f64 / (perimeter * perimeter)).min(1.0);
+            if circularity < params.min_circularity {
+                return None;
+            }
+
+            Some(Blob {
+                x: c.centroid.0,
+                y: c.centroid.1,
+                radius: (c.area as f64 / core::f64::consts::PI).sqrt(),
+                area: c.area,
+                circularity,
+            })
+        })
+        .collect()
+}
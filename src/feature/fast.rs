@@ -0,0 +1,80 @@
+use alloc::vec::Vec;
+
+use crate::color::Color;
+use crate::feature::{local_maxima, luminance, Keypoint};
+use crate::image::Image;
+use crate::ty::Type;
+
+/// Offsets of the 16 pixels on a Bresenham circle of radius 3
+const CIRCLE: [(isize, isize); 16] = [
+    (0, -3),
+    (1, -3),
+    (2, -2),
+    (3, -1),
+    (3, 0),
+    (3, 1),
+    (2, 2),
+    (1, 3),
+    (0, 3),
+    (-1, 3),
+    (-2, 2),
+    (-3, 1),
+    (-3, 0),
+    (-3, -1),
+    (-2, -2),
+    (-1, -3),
+];
+
+/// Number of contiguous pixels required to detect a corner
+const ARC: usize = 9;
+
+/// Detect corners using the FAST-9 segment test. A pixel is a corner when at least 9 contiguous
+/// pixels on the surrounding circle are all brighter or all darker than the center by more than
+/// `threshold`, which is a normalized value. The score of each corner is the sum of the absolute
+/// differences exceeding the threshold, and only local maxima are returned.
+pub fn fast<T: Type, C: Color, I: Image<T, C>>(image: &I, threshold: f64) -> Vec<Keypoint> {
+    let (width, height) = (image.width(), image.height());
+    if width < 7 || height < 7 {
+        return Vec::new();
+    }
+
+    let gray = luminance(image);
+    let mut scores = vec![0.0; width * height];
+    let mut diff = [0.0; 16];
+
+    for y in 3..height - 3 {
+        for x in 3..width - 3 {
+            let center = gray[y * width + x];
+            for (d, (dx, dy)) in diff.iter_mut().zip(CIRCLE.iter()) {
+                let i = (y as isize + dy) as usize * width + (x as isize + dx) as usize;
+                *d = gray[i] - center;
+            }
+
+            let mut is_corner = false;
+            for sign in &[1.0, -1.0] {
+                let mut run = 0;
+                for i in 0..16 + ARC {
+                    if diff[i % 16] * sign > threshold {
+                        run += 1;
+                        if run >= ARC {
+                            is_corner = true;
+                            break;
+                        }
+                    } else {
+                        run = 0;
+                    }
+                }
+            }
+
+            if !is_corner {
+                continue;
+            }
+
+            let bright: f64 = diff.iter().map(|d| (d - threshold).max(0.0)).sum();
+            let dark: f64 = diff.iter().map(|d| (-d - threshold).max(0.0)).sum();
+            scores[y * width + x] = bright.max(dark);
+        }
+    }
+
+    local_maxima(&scores, width, height, 0.0)
+}
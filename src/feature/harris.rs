@@ -0,0 +1,92 @@
+use alloc::vec::Vec;
+
+use crate::color::Color;
+use crate::feature::{local_maxima, luminance, Keypoint};
+use crate::image::Image;
+use crate::ty::Type;
+
+/// Blur a plane using a 1-dimensional kernel in both directions, clamping at the edges
+fn blur(data: &[f64], width: usize, height: usize, kernel: &[f64]) -> Vec<f64> {
+    let r = (kernel.len() / 2) as isize;
+    let sample = |v: &[f64], x: isize, y: isize| {
+        let x = x.max(0).min(width as isize - 1) as usize;
+        let y = y.max(0).min(height as isize - 1) as usize;
+        v[y * width + x]
+    };
+
+    let mut tmp = vec![0.0; data.len()];
+    for y in 0..height as isize {
+        for x in 0..width as isize {
+            tmp[y as usize * width + x as usize] = kernel
+                .iter()
+                .enumerate()
+                .map(|(k, w)| w * sample(data, x + k as isize - r, y))
+                .sum();
+        }
+    }
+
+    let mut dest = vec![0.0; data.len()];
+    for y in 0..height as isize {
+        for x in 0..width as isize {
+            dest[y as usize * width + x as usize] = kernel
+                .iter()
+                .enumerate()
+                .map(|(k, w)| w * sample(&tmp, x, y + k as isize - r))
+                .sum();
+        }
+    }
+    dest
+}
+
+/// Detect corners using the Harris corner detector. `k` is the sensitivity parameter, typically
+/// between 0.04 and 0.06. Only local maxima with a response greater than `threshold` multiplied
+/// by the strongest response in the image are returned.
+pub fn harris<T: Type, C: Color, I: Image<T, C>>(
+    image: &I,
+    k: f64,
+    threshold: f64,
+) -> Vec<Keypoint> {
+    let (width, height) = (image.width(), image.height());
+    if width < 3 || height < 3 {
+        return Vec::new();
+    }
+
+    let gray = luminance(image);
+    let at = |x: usize, y: usize| gray[y * width + x];
+
+    // Sobel gradients
+    let n = width * height;
+    let (mut xx, mut yy, mut xy) = (vec![0.0; n], vec![0.0; n], vec![0.0; n]);
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let dx = (at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1))
+                - (at(x - 1, y - 1) + 2.0 * at(x - 1, y) + at(x - 1, y + 1));
+            let dy = (at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1))
+                - (at(x - 1, y - 1) + 2.0 * at(x, y - 1) + at(x + 1, y - 1));
+            let i = y * width + x;
+            xx[i] = dx * dx;
+            yy[i] = dy * dy;
+            xy[i] = dx * dy;
+        }
+    }
+
+    let window = [0.0545, 0.2442, 0.4026, 0.2442, 0.0545];
+    let xx = blur(&xx, width, height, &window);
+    let yy = blur(&yy, width, height, &window);
+    let xy = blur(&xy, width, height, &window);
+
+    let response: Vec<f64> = (0..n)
+        .map(|i| {
+            let det = xx[i] * yy[i] - xy[i] * xy[i];
+            let trace = xx[i] + yy[i];
+            det - k * trace * trace
+        })
+        .collect();
+
+    let max = response.iter().cloned().fold(0.0, f64::max);
+    if max <= 0.0 {
+        return Vec::new();
+    }
+
+    local_maxima(&response, width, height, threshold * max)
+}
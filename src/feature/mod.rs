@@ -0,0 +1,163 @@
+//! Feature detection
+
+mod blob;
+mod fast;
+mod harris;
+
+use alloc::vec::Vec;
+
+pub use self::blob::{blob_detect, Blob, BlobParams};
+pub use self::fast::fast;
+pub use self::harris::harris;
+
+use crate::color::Color;
+use crate::image::Image;
+use crate::ty::Type;
+
+/// A detected point of interest
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keypoint {
+    pub x: usize,
+    pub y: usize,
+    /// Detector response, higher values are stronger features
+    pub score: f64,
+}
+
+/// Convert an image to a grayscale plane of normalized values
+pub(crate) fn luminance<T: Type, C: Color, I: Image<T, C>>(image: &I) -> Vec<f64> {
+    let channels = C::channels();
+    image
+        .data()
+        .chunks(channels)
+        .map(|px| {
+            if channels < 3 {
+                T::to_f(&px[0])
+            } else {
+                T::to_f(&px[0]) * 0.21 + T::to_f(&px[1]) * 0.72 + T::to_f(&px[2]) * 0.07
+            }
+        })
+        .collect()
+}
+
+/// Keep only the points of `scores` that are greater than `threshold` and are the maximum of
+/// their 3x3 neighborhood
+pub(crate) fn local_maxima(
+    scores: &[f64],
+    width: usize,
+    height: usize,
+    threshold: f64,
+) -> Vec<Keypoint> {
+    let mut keypoints = Vec::new();
+    for y in 1..height.saturating_sub(1) {
+        for x in 1..width.saturating_sub(1) {
+            let score = scores[y * width + x];
+            if score <= threshold {
+                continue;
+            }
+
+            let mut is_max = true;
+            'outer: for j in y - 1..=y + 1 {
+                for i in x - 1..=x + 1 {
+                    let other = scores[j * width + i];
+                    // Break ties using raster order so plateaus produce a single point
+                    if other > score || (other == score && (j, i) < (y, x)) {
+                        is_max = false;
+                        break 'outer;
+                    }
+                }
+            }
+
+            if is_max {
+                keypoints.push(Keypoint { x, y, score });
+            }
+        }
+    }
+    keypoints
+}
+
+/// Remove keypoints that are within `radius` pixels of a keypoint with a higher score, the
+/// result is sorted by descending score
+pub fn non_max_suppression(mut keypoints: Vec<Keypoint>, radius: f64) -> Vec<Keypoint> {
+    keypoints.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(core::cmp::Ordering::Equal)
+    });
+
+    let r2 = radius * radius;
+    let mut dest: Vec<Keypoint> = Vec::new();
+    for k in keypoints {
+        let suppressed = dest.iter().any(|d| {
+            let dx = d.x as f64 - k.x as f64;
+            let dy = d.y as f64 - k.y as f64;
+            dx * dx + dy * dy <= r2
+        });
+        if !suppressed {
+            dest.push(k);
+        }
+    }
+    dest
+}
+
+#[cfg(test)]
+mod test {
+    use crate::feature::{blob_detect, fast, harris, non_max_suppression, BlobParams, Keypoint};
+    use crate::{Gray, Image, ImageBuf};
+
+    fn square() -> ImageBuf<u8, Gray> {
+        let mut image: ImageBuf<u8, Gray> = ImageBuf::new(32, 32);
+        image.for_each(|(x, y), px| {
+            if (8..24).contains(&x) && (8..24).contains(&y) {
+                px[0] = 255;
+            }
+        });
+        image
+    }
+
+    fn near(points: &[Keypoint], x: usize, y: usize) -> bool {
+        points.iter().any(|k| {
+            (k.x as isize - x as isize).abs() <= 1 && (k.y as isize - y as isize).abs() <= 1
+        })
+    }
+
+    #[test]
+    fn test_harris() {
+        let corners = non_max_suppression(harris(&square(), 0.04, 0.1), 3.0);
+        assert_eq!(corners.len(), 4);
+        assert!(near(&corners, 8, 8));
+        assert!(near(&corners, 23, 23));
+    }
+
+    #[test]
+    fn test_fast() {
+        let corners = fast(&square(), 0.2);
+        assert_eq!(corners.len(), 4);
+        assert!(near(&corners, 8, 23));
+        assert!(near(&corners, 23, 8));
+    }
+
+    #[test]
+    fn test_blob_detect() {
+        let mut image: ImageBuf<u8, Gray> = ImageBuf::new(64, 32);
+        image.for_each(|(x, y), px| {
+            let dx = x as f64 - 16.0;
+            let dy = y as f64 - 16.0;
+            if dx * dx + dy * dy <= 64.0 || ((40..56).contains(&x) && (14..18).contains(&y)) {
+                px[0] = 255;
+            }
+        });
+
+        let blobs = blob_detect(&image, &BlobParams::default());
+        assert_eq!(blobs.len(), 2);
+
+        let params = BlobParams {
+            min_circularity: 0.8,
+            ..Default::default()
+        };
+        let blobs = blob_detect(&image, &params);
+        assert_eq!(blobs.len(), 1);
+        assert_eq!((blobs[0].x, blobs[0].y), (16.0, 16.0));
+        assert!((blobs[0].radius - 8.0).abs() < 0.5);
+    }
+}
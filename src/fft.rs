@@ -0,0 +1,136 @@
+//! Fast Fourier transforms
+//!
+//! Radix-2 Cooley-Tukey transforms, all lengths must be powers of two. Inverse transforms are
+//! scaled by `1 / n` so `ifft(fft(x)) == x`.
+
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+pub use num::complex::Complex;
+
+/// Magnitude of `c`, `Complex::norm` is only available with the `std` feature
+pub(crate) fn norm(c: &Complex<f64>) -> f64 {
+    c.re.hypot(c.im)
+}
+
+/// Complex number with magnitude `r` and phase `theta`
+pub(crate) fn from_polar(r: f64, theta: f64) -> Complex<f64> {
+    Complex::new(r * theta.cos(), r * theta.sin())
+}
+
+fn transform(data: &mut [Complex<f64>], inverse: bool) {
+    let n = data.len();
+    assert!(n.is_power_of_two(), "FFT length must be a power of two");
+
+    // Bit-reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut len = 2;
+    while len <= n {
+        let angle = sign * 2.0 * core::f64::consts::PI / len as f64;
+        let w = Complex::new(angle.cos(), angle.sin());
+        for start in (0..n).step_by(len) {
+            let mut wk = Complex::new(1.0, 0.0);
+            for k in 0..len / 2 {
+                let a = data[start + k];
+                let b = data[start + k + len / 2] * wk;
+                data[start + k] = a + b;
+                data[start + k + len / 2] = a - b;
+                wk *= w;
+            }
+        }
+        len <<= 1;
+    }
+
+    if inverse {
+        let scale = 1.0 / n as f64;
+        data.iter_mut().for_each(|x| *x *= scale);
+    }
+}
+
+fn transform_2d(data: &mut [Complex<f64>], width: usize, height: usize, inverse: bool) {
+    assert_eq!(data.len(), width * height);
+
+    for row in data.chunks_mut(width) {
+        transform(row, inverse);
+    }
+
+    let mut column = vec![Complex::new(0.0, 0.0); height];
+    for x in 0..width {
+        for (y, c) in column.iter_mut().enumerate() {
+            *c = data[y * width + x];
+        }
+        transform(&mut column, inverse);
+        for (y, c) in column.iter().enumerate() {
+            data[y * width + x] = *c;
+        }
+    }
+}
+
+/// In-place forward transform
+pub fn fft(data: &mut [Complex<f64>]) {
+    transform(data, false)
+}
+
+/// In-place inverse transform
+pub fn ifft(data: &mut [Complex<f64>]) {
+    transform(data, true)
+}
+
+/// In-place forward transform of row-major 2-dimensional data
+pub fn fft_2d(data: &mut [Complex<f64>], width: usize, height: usize) {
+    transform_2d(data, width, height, false)
+}
+
+/// In-place inverse transform of row-major 2-dimensional data
+pub fn ifft_2d(data: &mut [Complex<f64>], width: usize, height: usize) {
+    transform_2d(data, width, height, true)
+}
+
+/// Copy `data` into the top-left corner of a zero-filled `width` x `height` complex buffer
+pub fn pad(data: &[f64], data_width: usize, width: usize, height: usize) -> Vec<Complex<f64>> {
+    let mut dest = vec![Complex::new(0.0, 0.0); width * height];
+    for (y, row) in data.chunks(data_width.max(1)).enumerate().take(height) {
+        for (x, v) in row.iter().enumerate().take(width) {
+            dest[y * width + x] = Complex::new(*v, 0.0);
+        }
+    }
+    dest
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fft::{fft, fft_2d, ifft, ifft_2d, Complex};
+
+    #[test]
+    fn test_fft() {
+        let input: Vec<Complex<f64>> = (0..8).map(|i| Complex::new(i as f64, 0.0)).collect();
+        let mut data = input.clone();
+        fft(&mut data);
+        assert!((data[0].re - 28.0).abs() < 1e-9);
+        ifft(&mut data);
+        for (a, b) in data.iter().zip(&input) {
+            assert!((a - b).norm() < 1e-9);
+        }
+
+        let mut data = input.clone();
+        fft_2d(&mut data, 4, 2);
+        ifft_2d(&mut data, 4, 2);
+        for (a, b) in data.iter().zip(&input) {
+            assert!((a - b).norm() < 1e-9);
+        }
+    }
+}
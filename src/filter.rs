@@ -1,5 +1,12 @@
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
 use crate::color::Color;
 use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::parallel;
+use crate::rect::Rect;
 use crate::ty::Type;
 
 /// Executes `a` then `b` and passes the results to `f`
@@ -44,6 +51,95 @@ impl<'a, A: Filter, B: Filter, F: Sync + Fn(f64, f64) -> f64> Filter for Join<'a
     }
 }
 
+/// Executes `a` only inside of `rect`, see `Filter::apply_roi`
+pub struct Roi<'a, A: 'a + Filter> {
+    a: &'a A,
+    rect: Rect,
+}
+
+impl<'a, A: Filter> Roi<'a, A> {
+    /// The region of interest
+    pub fn rect(&self) -> Rect {
+        self.rect
+    }
+}
+
+impl<'a, A: Filter> Filter for Roi<'a, A> {
+    fn compute_at<T: Type, C: Color, I: Image<T, C>>(
+        &self,
+        x: usize,
+        y: usize,
+        c: usize,
+        input: &[&I],
+    ) -> f64 {
+        if self.rect.contains(x, y) {
+            self.a.compute_at(x, y, c, input)
+        } else {
+            input[0].get_f(x, y, c)
+        }
+    }
+
+    fn eval_partial<T: Type, C: Color, U: Type, D: Color, I: Image<T, C>, J: Image<U, D>>(
+        &self,
+        start_x: usize,
+        start_y: usize,
+        width: usize,
+        height: usize,
+        output: &mut I,
+        input: &[&J],
+    ) {
+        if let Some(r) = self
+            .rect
+            .intersect(&Rect::new(start_x, start_y, width, height))
+        {
+            self.a
+                .eval_partial(r.x, r.y, r.width, r.height, output, input)
+        }
+    }
+
+    fn eval<
+        T: Send + Type,
+        C: Color,
+        U: Type,
+        D: Color,
+        I: Sync + Send + Image<T, C>,
+        J: Sync + Image<U, D>,
+    >(
+        &self,
+        output: &mut I,
+        input: &[&J],
+    ) {
+        let (width, height, channels) = output.shape();
+        let r = match self.rect.intersect(&Rect::new(0, 0, width, height)) {
+            Some(r) => r,
+            None => return,
+        };
+        parallel::for_each_row(output.data_mut(), width * channels, |y, row| {
+            if y < r.y || y >= r.y + r.height {
+                return;
+            }
+
+            let row = &mut row[r.x * channels..(r.x + r.width) * channels];
+            for (i, pixel) in row.chunks_exact_mut(channels).enumerate() {
+                for (c, value) in pixel.iter_mut().enumerate() {
+                    *value = T::from_f(self.a.compute_at(r.x + i, y, c, input));
+                }
+            }
+        });
+    }
+
+    fn eval_in_place<T: Send + Type, C: Color, I: Sync + Send + Image<T, C>>(&self, image: &mut I) {
+        let input_image: crate::ImagePtr<T, C> = crate::ImagePtr::new(
+            image.width(),
+            image.height(),
+            image.data_mut().as_mut_ptr(),
+            crate::image_ptr::Free::Ignore,
+        );
+
+        self.eval(image, &[&input_image]);
+    }
+}
+
 /// Filters are used to manipulate images in a generic, composable manner
 pub trait Filter: Sized + Sync {
     fn compute_at<T: Type, C: Color, I: Image<T, C>>(
@@ -122,6 +218,13 @@ pub trait Filter: Sized + Sync {
     fn and_then<F: Fn(f64) -> f64>(&self, f: F) -> AndThen<Self, F> {
         AndThen { a: self, f }
     }
+
+    /// Only evaluate the filter inside of `rect`, pixels of the output image outside of it are
+    /// left untouched. When the result is combined with other filters, pixels outside of `rect`
+    /// are copied from the first input
+    fn apply_roi(&self, rect: Rect) -> Roi<'_, Self> {
+        Roi { a: self, rect }
+    }
 }
 
 /// filter is used to simplify the process of defining `compute_at` to create new filters
@@ -214,3 +317,200 @@ filter!(
         input[0].get_f(x, y, c)
     }
 );
+
+/// Mean of a `2 * radius + 1` square window around each value, clipped at the edges
+fn box_mean(data: &[f64], width: usize, height: usize, radius: usize) -> Vec<f64> {
+    let stride = width + 1;
+    let mut table = vec![0.0; stride * (height + 1)];
+    for y in 0..height {
+        let mut row = 0.0;
+        for x in 0..width {
+            row += data[y * width + x];
+            table[(y + 1) * stride + x + 1] = table[y * stride + x + 1] + row;
+        }
+    }
+
+    let mut dest = Vec::with_capacity(data.len());
+    for y in 0..height {
+        let (y0, y1) = (y.saturating_sub(radius), (y + radius + 1).min(height));
+        for x in 0..width {
+            let (x0, x1) = (x.saturating_sub(radius), (x + radius + 1).min(width));
+            let sum = table[y1 * stride + x1] - table[y0 * stride + x1] - table[y1 * stride + x0]
+                + table[y0 * stride + x0];
+            dest.push(sum / ((x1 - x0) * (y1 - y0)) as f64);
+        }
+    }
+    dest
+}
+
+/// Edge-preserving guided filter (He et al.). Each channel of `image` is smoothed while
+/// following the edges of the luminance of `guide`, which must have the same size. `radius`
+/// controls the window size and `eps` the amount of smoothing: edges with a variance much
+/// smaller than `eps` are smoothed away. Runs in linear time regardless of `radius`.
+pub fn guided<T: Type, C: Color, U: Type, D: Color, I: Image<T, C>, G: Image<U, D>>(
+    image: &I,
+    guide: &G,
+    radius: usize,
+    eps: f64,
+) -> ImageBuf<T, C> {
+    let (width, height, channels) = image.shape();
+    let guide = crate::feature::luminance(guide);
+    let mean_i = box_mean(&guide, width, height, radius);
+    let ii: Vec<f64> = guide.iter().map(|x| x * x).collect();
+    let var_i: Vec<f64> = box_mean(&ii, width, height, radius)
+        .iter()
+        .zip(&mean_i)
+        .map(|(c, m)| c - m * m)
+        .collect();
+
+    let mut dest = ImageBuf::new(width, height);
+    dest.copy_metadata(image);
+    for c in 0..channels {
+        let p: Vec<f64> = image
+            .data()
+            .iter()
+            .skip(c)
+            .step_by(channels)
+            .map(T::to_f)
+            .collect();
+        let mean_p = box_mean(&p, width, height, radius);
+        let ip: Vec<f64> = guide.iter().zip(&p).map(|(a, b)| a * b).collect();
+        let corr_ip = box_mean(&ip, width, height, radius);
+
+        let mut a = Vec::with_capacity(p.len());
+        let mut b = Vec::with_capacity(p.len());
+        for i in 0..p.len() {
+            let cov = corr_ip[i] - mean_i[i] * mean_p[i];
+            let ai = cov / (var_i[i] + eps);
+            a.push(ai);
+            b.push(mean_p[i] - ai * mean_i[i]);
+        }
+
+        let mean_a = box_mean(&a, width, height, radius);
+        let mean_b = box_mean(&b, width, height, radius);
+        for (i, px) in dest.data_mut().chunks_mut(channels).enumerate() {
+            px[c] = T::from_f(mean_a[i] * guide[i] + mean_b[i]);
+        }
+    }
+    dest
+}
+
+/// Apply `filter` to `image` and blend the result with the original using `mask`, which must
+/// have the same size. Where the mask is 0 the original is kept, where it is 1 the filtered value
+/// is used and values in between are mixed linearly. The alpha channel is used as the weight when
+/// `D` has one, otherwise the first channel, so both grayscale and alpha masks can be used. The
+/// filter is not evaluated where the mask is 0, for example to blur only the background:
+///
+/// ```rust
+/// use image2::{filter, kernel, Gray, Image, ImageBuf, Rgb};
+///
+/// let image: ImageBuf<f32, Rgb> = ImageBuf::from_fn(16, 16, |x, _| [(x % 2) as f32; 3]);
+/// let background: ImageBuf<u8, Gray> = ImageBuf::from_fn(16, 16, |_, y| [(y >= 8) as u8 * 255]);
+/// let output = filter::apply_masked(&image, &background, &kernel::gaussian_3x3());
+/// assert_eq!(output.at(3, 2), image.at(3, 2));
+/// assert_ne!(output.at(3, 12), image.at(3, 12));
+/// ```
+pub fn apply_masked<
+    T: Type,
+    C: Color,
+    U: Type,
+    D: Color,
+    I: Sync + Image<T, C>,
+    M: Image<U, D>,
+    F: Filter,
+>(
+    image: &I,
+    mask: &M,
+    filter: &F,
+) -> ImageBuf<T, C> {
+    assert!(
+        mask.width() == image.width() && mask.height() == image.height(),
+        "apply_masked requires a mask with the same width and height as the image"
+    );
+    let weight_channel = if D::has_alpha() { D::channels() - 1 } else { 0 };
+    let input = &[image];
+    let mut dest = ImageBuf::new(image.width(), image.height());
+    dest.copy_metadata(image);
+    dest.for_each(|(x, y), px| {
+        let weight = mask.get_f(x, y, weight_channel).clamp(0.0, 1.0);
+        for (c, value) in px.iter_mut().enumerate() {
+            let a = image.get_f(x, y, c);
+            *value = if weight == 0.0 {
+                T::from_f(a)
+            } else {
+                let b = filter.compute_at(x, y, c, input);
+                T::from_f(a + (b - a) * weight)
+            };
+        }
+    });
+    dest
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{filter, Filter, Gray, Image, ImageBuf, Rect, Rgb, Rgba};
+
+    #[test]
+    fn test_guided() {
+        let mut image: ImageBuf<f32, Gray> = ImageBuf::new(20, 10);
+        image.for_each(|(x, y), px| {
+            let noise = ((x * 7 + y * 3) % 5) as f32 * 0.02;
+            px[0] = if x < 10 { 0.2 } else { 0.8 } + noise;
+        });
+
+        let output = filter::guided(&image, &image, 3, 0.01);
+
+        // Noise in flat regions is removed while the edge is preserved
+        assert!((output.at(4, 5)[0] - 0.24).abs() < 0.02);
+        assert!((output.at(15, 5)[0] - 0.84).abs() < 0.02);
+        assert!(output.at(10, 5)[0] - output.at(9, 5)[0] > 0.4);
+    }
+
+    #[test]
+    fn test_apply_roi() {
+        let mut image: ImageBuf<f32, Gray> = ImageBuf::new_with(10, 10, [0.25]);
+        let rect = Rect::new(2, 3, 4, 2);
+        filter::Invert.apply_roi(rect).eval_in_place(&mut image);
+        image.for_each_pixel(|(x, y), px| {
+            let expected = if rect.contains(x, y) { 0.75 } else { 0.25 };
+            assert_eq!(px[0], expected);
+        });
+
+        // The rectangle is clipped to the output image
+        let input: ImageBuf<u8, Rgb> = ImageBuf::new(8, 8);
+        let mut output: ImageBuf<u8, Rgb> = ImageBuf::new_with(8, 8, [1, 2, 3]);
+        let roi = filter::Invert.apply_roi(Rect::new(6, 6, 10, 10));
+        roi.eval(&mut output, &[&input]);
+        assert_eq!(output.at(7, 7), &[255, 255, 255]);
+        assert_eq!(output.at(5, 7), &[1, 2, 3]);
+        roi.eval_partial(0, 0, 7, 7, &mut output, &[&input]);
+        assert_eq!(output.at(6, 6), &[255, 255, 255]);
+        assert_eq!(output.at(0, 0), &[1, 2, 3]);
+
+        // Outside of the rectangle the first input is passed through
+        let gray: ImageBuf<u8, Rgb> = ImageBuf::new_with(8, 8, [51, 51, 51]);
+        assert_eq!(roi.compute_at(0, 0, 1, &[&gray]), 0.2);
+        assert_eq!(roi.compute_at(7, 7, 1, &[&gray]), 255.0 - 0.2);
+    }
+
+    #[test]
+    fn test_apply_masked() {
+        let image: ImageBuf<f32, Gray> = ImageBuf::from_fn(10, 4, |x, _| [x as f32 * 0.1]);
+        let mask: ImageBuf<f32, Gray> = ImageBuf::from_fn(10, 4, |_, y| [y as f32 / 2.0]);
+        let output = filter::apply_masked(&image, &mask, &filter::Invert);
+        assert_eq!(output.at(4, 0), image.at(4, 0));
+        assert!((output.get(4, 1, 0) - 0.5).abs() < 1e-6);
+        assert!((output.get(4, 2, 0) - 0.6).abs() < 1e-6);
+        assert!((output.get(4, 3, 0) - 0.6).abs() < 1e-6);
+
+        // The alpha channel is used as the weight for masks with alpha
+        let mask: ImageBuf<u8, Rgba> = ImageBuf::from_fn(10, 4, |x, _| {
+            let a = if x < 5 { 0 } else { 255 };
+            [255, 255, 255, a]
+        });
+        let image: ImageBuf<f32, Rgb> = ImageBuf::new_with(10, 4, [0.25, 0.5, 1.0]);
+        let output = filter::apply_masked(&image, &mask, &filter::Invert);
+        assert_eq!(output.at(4, 3), &[0.25, 0.5, 1.0]);
+        assert_eq!(output.at(5, 3), &[0.75, 0.5, 0.0]);
+    }
+}
@@ -0,0 +1,186 @@
+//! Optical flow
+
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::color::{Color, Gray};
+use crate::feature::luminance;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::pyramid;
+use crate::ty::Type;
+
+/// Maximum number of pyramid levels used by `lucas_kanade`
+const LEVELS: usize = 4;
+
+/// Maximum number of iterations per level
+const ITERATIONS: usize = 20;
+
+/// Iteration stops when the update is smaller than this many pixels
+const EPSILON: f64 = 0.01;
+
+/// Bilinear sample, clamping coordinates to the image
+#[inline]
+fn sample(image: &ImageBuf<f64, Gray>, x: f64, y: f64) -> f64 {
+    let max_x = image.width() - 1;
+    let max_y = image.height() - 1;
+    let x = x.max(0.0).min(max_x as f64);
+    let y = y.max(0.0).min(max_y as f64);
+    let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+    let (x1, y1) = ((x0 + 1).min(max_x), (y0 + 1).min(max_y));
+    let (ax, ay) = (x - x0 as f64, y - y0 as f64);
+    let data = image.data();
+    let w = image.width();
+    let top = data[y0 * w + x0] * (1.0 - ax) + data[y0 * w + x1] * ax;
+    let bottom = data[y1 * w + x0] * (1.0 - ax) + data[y1 * w + x1] * ax;
+    top * (1.0 - ay) + bottom * ay
+}
+
+fn gray<T: Type, C: Color, I: Image<T, C>>(image: &I) -> ImageBuf<f64, Gray> {
+    ImageBuf::new_from(image.width(), image.height(), luminance(image))
+}
+
+/// Track the refined displacement of a single point at one pyramid level, starting from the
+/// guess `g`
+fn track_level(
+    prev: &ImageBuf<f64, Gray>,
+    next: &ImageBuf<f64, Gray>,
+    (px, py): (f64, f64),
+    (gx, gy): (f64, f64),
+    radius: isize,
+) -> Option<(f64, f64)> {
+    // Spatial gradient matrix of the window in the previous image
+    let mut window = Vec::with_capacity(((2 * radius + 1) * (2 * radius + 1)) as usize);
+    let (mut gxx, mut gxy, mut gyy) = (0.0, 0.0, 0.0);
+    for j in -radius..=radius {
+        for i in -radius..=radius {
+            let (x, y) = (px + i as f64, py + j as f64);
+            let ix = (sample(prev, x + 1.0, y) - sample(prev, x - 1.0, y)) / 2.0;
+            let iy = (sample(prev, x, y + 1.0) - sample(prev, x, y - 1.0)) / 2.0;
+            gxx += ix * ix;
+            gxy += ix * iy;
+            gyy += iy * iy;
+            window.push((x, y, sample(prev, x, y), ix, iy));
+        }
+    }
+
+    let det = gxx * gyy - gxy * gxy;
+    if det.abs() < 1e-12 {
+        return None;
+    }
+
+    let (mut vx, mut vy) = (0.0, 0.0);
+    for _ in 0..ITERATIONS {
+        let (mut bx, mut by) = (0.0, 0.0);
+        for &(x, y, value, ix, iy) in &window {
+            let diff = value - sample(next, x + gx + vx, y + gy + vy);
+            bx += diff * ix;
+            by += diff * iy;
+        }
+
+        let ex = (gyy * bx - gxy * by) / det;
+        let ey = (gxx * by - gxy * bx) / det;
+        vx += ex;
+        vy += ey;
+        if ex * ex + ey * ey < EPSILON * EPSILON {
+            break;
+        }
+    }
+
+    Some((gx + vx, gy + vy))
+}
+
+/// Track `points` from `prev` to `next` using pyramidal Lucas-Kanade optical flow. `window` is
+/// the width of the square neighborhood used to estimate the motion of each point. Returns the
+/// new location of each point, or `None` if the point could not be tracked. No points can be
+/// tracked when either image is empty.
+pub fn lucas_kanade<T: Type, C: Color, I: Image<T, C>, J: Image<T, C>>(
+    prev: &I,
+    next: &J,
+    points: &[(f64, f64)],
+    window: usize,
+) -> Vec<Option<(f64, f64)>> {
+    if prev.width() == 0 || prev.height() == 0 || next.width() == 0 || next.height() == 0 {
+        return vec![None; points.len()];
+    }
+
+    let radius = (window / 2).max(1) as isize;
+
+    // Stop adding levels once the image is not much bigger than the window
+    let mut levels = 1;
+    let mut size = prev.width().min(prev.height());
+    while levels < LEVELS && size / 2 > window.max(3) * 2 {
+        size /= 2;
+        levels += 1;
+    }
+
+    let prev = pyramid::gaussian(&gray(prev), levels);
+    let next = pyramid::gaussian(&gray(next), levels);
+    let (width, height) = (next[0].width() as f64, next[0].height() as f64);
+
+    points
+        .iter()
+        .map(|&(x, y)| {
+            let mut g = (0.0, 0.0);
+            for level in (0..levels).rev() {
+                let scale = (1 << level) as f64;
+                let d = track_level(
+                    &prev[level],
+                    &next[level],
+                    (x / scale, y / scale),
+                    g,
+                    radius,
+                )?;
+                g = if level == 0 {
+                    d
+                } else {
+                    (d.0 * 2.0, d.1 * 2.0)
+                };
+            }
+
+            let (nx, ny) = (x + g.0, y + g.1);
+            if nx < 0.0 || ny < 0.0 || nx > width - 1.0 || ny > height - 1.0 {
+                return None;
+            }
+
+            Some((nx, ny))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{flow, Gray, Image, ImageBuf};
+
+    fn blob(cx: f64, cy: f64) -> ImageBuf<f32, Gray> {
+        let mut image = ImageBuf::new(64, 64);
+        image.for_each(|(x, y), px| {
+            let dx = x as f64 - cx;
+            let dy = y as f64 - cy;
+            px[0] = (-(dx * dx + dy * dy) / 50.0).exp() as f32;
+        });
+        image
+    }
+
+    #[test]
+    fn test_lucas_kanade() {
+        let a = blob(30.0, 30.0);
+        let b = blob(33.5, 28.0);
+        let points = flow::lucas_kanade(&a, &b, &[(30.0, 30.0), (26.0, 33.0)], 9);
+        for (p, (x, y)) in points.iter().zip(&[(33.5, 28.0), (29.5, 31.0)]) {
+            let (px, py) = p.unwrap();
+            assert!((px - x).abs() < 0.1 && (py - y).abs() < 0.1, "{:?}", p);
+        }
+
+        let empty: ImageBuf<f32, Gray> = ImageBuf::new(0, 0);
+        assert_eq!(
+            flow::lucas_kanade(&empty, &empty, &[(0.0, 0.0), (1.0, 1.0)], 9),
+            vec![None, None]
+        );
+        assert_eq!(
+            flow::lucas_kanade(&a, &empty, &[(30.0, 30.0)], 9),
+            vec![None]
+        );
+    }
+}
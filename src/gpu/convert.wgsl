@@ -0,0 +1,60 @@
+struct Params {
+    width: u32,
+    height: u32,
+    src_channels: u32,
+    dst_channels: u32,
+    src_alpha: u32,
+    dst_alpha: u32,
+    swap: u32,
+    _pad0: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> src: array<f32>;
+@group(0) @binding(2) var<storage, read_write> dst: array<f32>;
+
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.width || id.y >= params.height) {
+        return;
+    }
+
+    let index = id.y * params.width + id.x;
+    let s = index * params.src_channels;
+    let d = index * params.dst_channels;
+
+    var alpha = 1.0;
+    if (params.src_alpha != 0u) {
+        alpha = src[s + params.src_channels - 1u];
+    }
+
+    let src_color = params.src_channels - params.src_alpha;
+    let dst_color = params.dst_channels - params.dst_alpha;
+
+    var rgb = vec3<f32>(0.0, 0.0, 0.0);
+    if (src_color >= 3u) {
+        rgb = vec3<f32>(src[s], src[s + 1u], src[s + 2u]);
+        if (params.swap != 0u) {
+            rgb = rgb.zyx;
+        }
+    } else {
+        rgb = vec3<f32>(src[s], src[s], src[s]);
+    }
+
+    // Alpha is only kept when the destination has an alpha channel
+    if (params.dst_alpha == 0u) {
+        rgb = rgb * alpha;
+    }
+
+    if (dst_color >= 3u) {
+        dst[d] = rgb.x;
+        dst[d + 1u] = rgb.y;
+        dst[d + 2u] = rgb.z;
+    } else {
+        dst[d] = rgb.x * 0.21 + rgb.y * 0.72 + rgb.z * 0.07;
+    }
+
+    if (params.dst_alpha != 0u) {
+        dst[d + params.dst_channels - 1u] = alpha;
+    }
+}
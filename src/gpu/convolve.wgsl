@@ -0,0 +1,40 @@
+struct Params {
+    width: u32,
+    height: u32,
+    channels: u32,
+    rows: u32,
+    cols: u32,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> src: array<f32>;
+@group(0) @binding(2) var<storage, read_write> dst: array<f32>;
+@group(0) @binding(3) var<storage, read> weights: array<f32>;
+
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.width || id.y >= params.height) {
+        return;
+    }
+
+    let r2 = i32(params.rows / 2u);
+    let c2 = i32(params.cols / 2u);
+    let max_x = i32(params.width) - 1;
+    let max_y = i32(params.height) - 1;
+
+    for (var c = 0u; c < params.channels; c = c + 1u) {
+        var sum = 0.0;
+        for (var ky = -r2; ky <= r2; ky = ky + 1) {
+            let y = u32(clamp(i32(id.y) + ky, 0, max_y));
+            for (var kx = -c2; kx <= c2; kx = kx + 1) {
+                let x = u32(clamp(i32(id.x) + kx, 0, max_x));
+                let w = weights[u32(ky + r2) * params.cols + u32(kx + c2)];
+                sum = sum + w * src[(y * params.width + x) * params.channels + c];
+            }
+        }
+        dst[(id.y * params.width + id.x) * params.channels + c] = sum;
+    }
+}
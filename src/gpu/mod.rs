@@ -0,0 +1,440 @@
+//! GPU accelerated image processing using `wgpu` compute shaders
+//!
+//! Images are uploaded as normalized `f32` storage buffers, so any `ImageBuf` type can be processed
+//! and results are converted back to the original type when downloaded.
+//!
+//! ```rust,no_run
+//! use image2::{gpu, kernel, ImageBuf, Rgb};
+//!
+//! let ctx = gpu::Context::new()?;
+//! let image: ImageBuf<u8, Rgb> = ImageBuf::new(3840, 2160);
+//! let blurred = gpu::convolve(&ctx, &image, &kernel::gaussian_5x5())?;
+//! # Ok::<(), image2::Error>(())
+//! ```
+
+mod texture;
+
+pub use self::texture::*;
+
+use std::borrow::Cow;
+use std::marker::PhantomData;
+
+use wgpu::util::DeviceExt;
+
+use crate::color::Color;
+use crate::error::Error;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::kernel::Kernel;
+use crate::ty::Type;
+
+const WORKGROUP_SIZE: u32 = 8;
+
+/// Owns the `wgpu` device and the compute pipelines used to process images
+pub struct Context {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    convolve: wgpu::ComputePipeline,
+    resize: wgpu::ComputePipeline,
+    convert: wgpu::ComputePipeline,
+}
+
+/// An image stored on the GPU as normalized `f32` values
+pub struct GpuImage<C: Color> {
+    buffer: wgpu::Buffer,
+    width: usize,
+    height: usize,
+    _color: PhantomData<C>,
+}
+
+impl<C: Color> GpuImage<C> {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The underlying storage buffer
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    fn size(&self) -> u64 {
+        (self.width * self.height * C::channels() * std::mem::size_of::<f32>()) as u64
+    }
+}
+
+fn params(values: &[u32]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(32);
+    for i in 0..8 {
+        bytes.extend_from_slice(&values.get(i).cloned().unwrap_or(0).to_ne_bytes());
+    }
+    bytes
+}
+
+fn floats(values: &[f32]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|f| f.to_ne_bytes().to_vec())
+        .collect()
+}
+
+fn workgroups(n: usize) -> u32 {
+    (n as u32).div_ceil(WORKGROUP_SIZE)
+}
+
+impl Context {
+    /// Create a new context using the default high-performance adapter, blocking until the device
+    /// is ready
+    pub fn new() -> Result<Context, Error> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            force_fallback_adapter: false,
+            compatible_surface: None,
+        }));
+
+        let adapter = match adapter {
+            Some(adapter) => adapter,
+            None => return Err(Error::Message(String::from("No GPU adapter found"))),
+        };
+
+        let (device, queue) = match pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("image2"),
+                required_features: wgpu::Features::empty(),
+                required_limits: adapter.limits(),
+                memory_hints: wgpu::MemoryHints::Performance,
+            },
+            None,
+        )) {
+            Ok(x) => x,
+            Err(e) => return Err(Error::Message(format!("Unable to open GPU device: {}", e))),
+        };
+
+        Ok(Self::from_device(device, queue))
+    }
+
+    /// Create a new context from an existing device and queue, this allows images to be shared
+    /// with an application's own rendering code
+    pub fn from_device(device: wgpu::Device, queue: wgpu::Queue) -> Context {
+        let pipeline = |name, source| {
+            let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(name),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(source)),
+            });
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(name),
+                layout: None,
+                module: &module,
+                entry_point: "main",
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+
+        let convolve = pipeline("convolve", include_str!("convolve.wgsl"));
+        let resize = pipeline("resize", include_str!("resize.wgsl"));
+        let convert = pipeline("convert", include_str!("convert.wgsl"));
+
+        Context {
+            device,
+            queue,
+            convolve,
+            resize,
+            convert,
+        }
+    }
+
+    pub fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    pub fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+
+    fn storage<C: Color>(&self, width: usize, height: usize) -> GpuImage<C> {
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (width * height * C::channels() * std::mem::size_of::<f32>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        GpuImage {
+            buffer,
+            width,
+            height,
+            _color: PhantomData,
+        }
+    }
+
+    fn dispatch(
+        &self,
+        pipeline: &wgpu::ComputePipeline,
+        buffers: &[&wgpu::Buffer],
+        width: usize,
+        height: usize,
+    ) {
+        let layout = pipeline.get_bind_group_layout(0);
+        let entries: Vec<wgpu::BindGroupEntry> = buffers
+            .iter()
+            .enumerate()
+            .map(|(i, buffer)| wgpu::BindGroupEntry {
+                binding: i as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect();
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &layout,
+            entries: &entries,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: None,
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(workgroups(width), workgroups(height), 1);
+        }
+        self.queue.submit(Some(encoder.finish()));
+    }
+
+    fn uniform(&self, values: &[u32]) -> wgpu::Buffer {
+        self.device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: &params(values),
+                usage: wgpu::BufferUsages::UNIFORM,
+            })
+    }
+
+    /// Copy an image to the GPU
+    pub fn upload<T: Type, C: Color, I: Image<T, C>>(&self, image: &I) -> GpuImage<C> {
+        let data: Vec<f32> = image.data().iter().map(|x| T::to_f(x) as f32).collect();
+        let buffer = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: &floats(&data),
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_SRC
+                    | wgpu::BufferUsages::COPY_DST,
+            });
+
+        GpuImage {
+            buffer,
+            width: image.width(),
+            height: image.height(),
+            _color: PhantomData,
+        }
+    }
+
+    /// Copy an image from the GPU, blocking until all pending work is finished
+    pub fn download<T: Type, C: Color>(
+        &self,
+        image: &GpuImage<C>,
+    ) -> Result<ImageBuf<T, C>, Error> {
+        let size = image.size();
+        let staging = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_buffer_to_buffer(&image.buffer, 0, &staging, 0, size);
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = staging.slice(..);
+        let (tx, rx) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |res| {
+            let _ = tx.send(res);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+
+        match rx.recv() {
+            Ok(Ok(())) => (),
+            Ok(Err(e)) => return Err(Error::Message(format!("Unable to read GPU buffer: {}", e))),
+            Err(e) => return Err(Error::Message(format!("Unable to read GPU buffer: {}", e))),
+        }
+
+        let data = {
+            let bytes = slice.get_mapped_range();
+            bytes
+                .chunks_exact(4)
+                .map(|b| T::from_f(f64::from(f32::from_ne_bytes([b[0], b[1], b[2], b[3]]))))
+                .collect()
+        };
+        staging.unmap();
+
+        Ok(ImageBuf::new_from(image.width, image.height, data))
+    }
+
+    /// Convolve an image with the given kernel, pixels outside of the image are clamped to the
+    /// nearest edge
+    pub fn convolve<C: Color>(&self, image: &GpuImage<C>, kernel: &Kernel) -> GpuImage<C> {
+        let dest = self.storage(image.width, image.height);
+        let weights: Vec<f32> = kernel
+            .data
+            .iter()
+            .flat_map(|row| row.iter().map(|x| *x as f32))
+            .collect();
+        let weights = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: &floats(&weights),
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+        let uniform = self.uniform(&[
+            image.width as u32,
+            image.height as u32,
+            C::channels() as u32,
+            kernel.rows as u32,
+            kernel.cols as u32,
+        ]);
+
+        self.dispatch(
+            &self.convolve,
+            &[&uniform, &image.buffer, &dest.buffer, &weights],
+            image.width,
+            image.height,
+        );
+        dest
+    }
+
+    /// Resize an image using bilinear interpolation
+    pub fn resize<C: Color>(
+        &self,
+        image: &GpuImage<C>,
+        width: usize,
+        height: usize,
+    ) -> GpuImage<C> {
+        let dest = self.storage(width, height);
+        let uniform = self.uniform(&[
+            image.width as u32,
+            image.height as u32,
+            width as u32,
+            height as u32,
+            C::channels() as u32,
+        ]);
+
+        self.dispatch(
+            &self.resize,
+            &[&uniform, &image.buffer, &dest.buffer],
+            width,
+            height,
+        );
+        dest
+    }
+
+    /// Convert between colors, supports any combination of gray, RGB, BGR and images with an
+    /// alpha channel
+    pub fn convert<C: Color, D: Color>(&self, image: &GpuImage<C>) -> GpuImage<D> {
+        let dest = self.storage(image.width, image.height);
+        let is_bgr = |name: &str| name.starts_with("bgr");
+        let uniform = self.uniform(&[
+            image.width as u32,
+            image.height as u32,
+            C::channels() as u32,
+            D::channels() as u32,
+            C::has_alpha() as u32,
+            D::has_alpha() as u32,
+            (is_bgr(C::name()) != is_bgr(D::name())) as u32,
+        ]);
+
+        self.dispatch(
+            &self.convert,
+            &[&uniform, &image.buffer, &dest.buffer],
+            image.width,
+            image.height,
+        );
+        dest
+    }
+}
+
+/// Upload, convolve and download an image
+pub fn convolve<T: Type, C: Color, I: Image<T, C>>(
+    ctx: &Context,
+    image: &I,
+    kernel: &Kernel,
+) -> Result<ImageBuf<T, C>, Error> {
+    let src = ctx.upload(image);
+    ctx.download(&ctx.convolve(&src, kernel))
+}
+
+/// Upload, resize and download an image
+pub fn resize<T: Type, C: Color, I: Image<T, C>>(
+    ctx: &Context,
+    image: &I,
+    width: usize,
+    height: usize,
+) -> Result<ImageBuf<T, C>, Error> {
+    let src = ctx.upload(image);
+    ctx.download(&ctx.resize(&src, width, height))
+}
+
+/// Upload, convert and download an image
+pub fn convert<T: Type, C: Color, D: Color, I: Image<T, C>>(
+    ctx: &Context,
+    image: &I,
+) -> Result<ImageBuf<T, D>, Error> {
+    let src = ctx.upload(image);
+    ctx.download(&ctx.convert::<C, D>(&src))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{gpu, kernel, Gray, Image, ImageBuf, Rgb, Rgba};
+
+    #[test]
+    fn test_gpu() {
+        // Skip when no adapter is available
+        let ctx = match gpu::Context::new() {
+            Ok(ctx) => ctx,
+            Err(_) => return,
+        };
+
+        let image: ImageBuf<u8, Rgb> = ImageBuf::new_from(2, 1, vec![255, 0, 0, 0, 255, 0]);
+        let gray: ImageBuf<u8, Gray> = gpu::convert(&ctx, &image).unwrap();
+        assert_eq!(gray.data(), &[53, 183]);
+
+        let blurred = gpu::convolve(&ctx, &image, &kernel::gaussian_3x3()).unwrap();
+        assert_eq!(blurred.shape(), image.shape());
+
+        let resized = gpu::resize(&ctx, &image, 4, 2).unwrap();
+        assert_eq!(resized.shape(), (4, 2, 3));
+
+        // Rows of a 100 pixel wide texture are padded when reading it back
+        let mut image: ImageBuf<u8, Rgb> = ImageBuf::new(100, 3);
+        image.at_mut(99, 2).copy_from_slice(&[10, 20, 30]);
+        let texture = gpu::to_texture(&image, ctx.device(), ctx.queue());
+        assert_eq!(texture.format(), wgpu::TextureFormat::Rgba8Unorm);
+        let rgba: ImageBuf<u8, Rgba> =
+            gpu::from_texture(&texture, ctx.device(), ctx.queue()).unwrap();
+        assert_eq!(rgba.at(99, 2), &[10, 20, 30, 255]);
+
+        let image: ImageBuf<f32, Gray> = ImageBuf::new_from(2, 1, vec![0.25, 1.0]);
+        let texture = gpu::to_texture(&image, ctx.device(), ctx.queue());
+        assert_eq!(texture.format(), wgpu::TextureFormat::Rgba16Float);
+        let future = gpu::from_texture_async(&texture, ctx.device(), ctx.queue());
+        ctx.device().poll(wgpu::Maintain::Wait);
+        let back: ImageBuf<f32, Gray> = pollster::block_on(future).unwrap();
+        assert_eq!(back.data(), &[0.25, 1.0]);
+    }
+}
@@ -0,0 +1,42 @@
+struct Params {
+    src_width: u32,
+    src_height: u32,
+    width: u32,
+    height: u32,
+    channels: u32,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> src: array<f32>;
+@group(0) @binding(2) var<storage, read_write> dst: array<f32>;
+
+fn at(x: u32, y: u32, c: u32) -> f32 {
+    return src[(y * params.src_width + x) * params.channels + c];
+}
+
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.width || id.y >= params.height) {
+        return;
+    }
+
+    let sx = f32(params.src_width) / f32(params.width);
+    let sy = f32(params.src_height) / f32(params.height);
+    let fx = max((f32(id.x) + 0.5) * sx - 0.5, 0.0);
+    let fy = max((f32(id.y) + 0.5) * sy - 0.5, 0.0);
+    let x0 = min(u32(floor(fx)), params.src_width - 1u);
+    let y0 = min(u32(floor(fy)), params.src_height - 1u);
+    let x1 = min(x0 + 1u, params.src_width - 1u);
+    let y1 = min(y0 + 1u, params.src_height - 1u);
+    let ax = fx - f32(x0);
+    let ay = fy - f32(y0);
+
+    for (var c = 0u; c < params.channels; c = c + 1u) {
+        let top = mix(at(x0, y0, c), at(x1, y0, c), ax);
+        let bottom = mix(at(x0, y1, c), at(x1, y1, c), ax);
+        dst[(id.y * params.width + id.x) * params.channels + c] = mix(top, bottom, ay);
+    }
+}
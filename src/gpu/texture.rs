@@ -0,0 +1,348 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context as TaskContext, Poll, Waker};
+
+use crate::color::Color;
+use crate::error::Error;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::ty::Type;
+
+/// The texture format used by `to_texture`: `Rgba8Unorm` for 8-bit images and `Rgba16Float`
+/// for everything else
+pub fn texture_format<T: Type>() -> wgpu::TextureFormat {
+    if !T::is_float() && T::max_f() - T::min_f() <= 255.0 {
+        wgpu::TextureFormat::Rgba8Unorm
+    } else {
+        wgpu::TextureFormat::Rgba16Float
+    }
+}
+
+/// Convert to a half precision float, rounding to nearest even
+fn f16_from_f32(f: f32) -> u16 {
+    let bits = f.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    let mantissa = bits & 0x007f_ffff;
+
+    if f.is_nan() {
+        return sign | 0x7e00;
+    }
+
+    if exp >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    if exp <= 0 {
+        if exp < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - exp) as u32;
+        let half = 1 << (shift - 1);
+        let rest = mantissa & ((1 << shift) - 1);
+        let mut value = (mantissa >> shift) as u16;
+        if rest > half || (rest == half && value & 1 == 1) {
+            value += 1;
+        }
+        return sign | value;
+    }
+
+    let mut value = ((exp as u32) << 10 | mantissa >> 13) as u16;
+    let rest = mantissa & 0x1fff;
+    if rest > 0x1000 || (rest == 0x1000 && value & 1 == 1) {
+        value += 1;
+    }
+    sign | value
+}
+
+fn f16_to_f32(h: u16) -> f32 {
+    let sign = if h & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exp = ((h >> 10) & 0x1f) as i32;
+    let mantissa = f32::from(h & 0x03ff);
+    match exp {
+        0 => sign * mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => sign * f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exp - 15),
+    }
+}
+
+/// Normalized RGBA values of a pixel, grayscale values are copied to each color channel and
+/// images without an alpha channel are opaque
+fn to_rgba<T: Type, C: Color>(px: &[T]) -> [f64; 4] {
+    let channels = C::channels();
+    let alpha = if C::has_alpha() {
+        T::to_f(&px[channels - 1])
+    } else {
+        1.0
+    };
+    if channels < 3 {
+        let v = T::to_f(&px[0]);
+        [v, v, v, alpha]
+    } else if C::name().starts_with("bgr") {
+        [T::to_f(&px[2]), T::to_f(&px[1]), T::to_f(&px[0]), alpha]
+    } else {
+        [T::to_f(&px[0]), T::to_f(&px[1]), T::to_f(&px[2]), alpha]
+    }
+}
+
+fn from_rgba<T: Type, C: Color>(rgba: [f64; 4], px: &mut [T]) {
+    let channels = C::channels();
+    let colors = if C::has_alpha() {
+        channels - 1
+    } else {
+        channels
+    };
+    let bgr = C::name().starts_with("bgr");
+    for (c, item) in px[..colors].iter_mut().enumerate() {
+        let value = if colors < 3 {
+            rgba[0] * 0.21 + rgba[1] * 0.72 + rgba[2] * 0.07
+        } else if bgr && c < 3 {
+            rgba[2 - c]
+        } else {
+            rgba[c.min(2)]
+        };
+        *item = T::from_f(value);
+    }
+    if C::has_alpha() {
+        px[colors] = T::from_f(rgba[3]);
+    }
+}
+
+/// Upload an image to a new 2D texture using the format returned by `texture_format`. The
+/// texture can be sampled, rendered to and copied.
+pub fn to_texture<T: Type, C: Color, I: Image<T, C>>(
+    image: &I,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> wgpu::Texture {
+    let (width, height, channels) = image.shape();
+    let format = texture_format::<T>();
+    let size = wgpu::Extent3d {
+        width: width as u32,
+        height: height as u32,
+        depth_or_array_layers: 1,
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::COPY_SRC
+            | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+
+    let pixels = image.data().chunks(channels).map(to_rgba::<T, C>);
+    let bytes: Vec<u8> = if format == wgpu::TextureFormat::Rgba8Unorm {
+        pixels.flat_map(|px| px.map(u8::from_f)).collect()
+    } else {
+        pixels
+            .flat_map(|px| px.map(|v| f16_from_f32(v as f32)))
+            .flat_map(|h| h.to_le_bytes())
+            .collect()
+    };
+
+    let bytes_per_pixel = format.block_copy_size(None).unwrap_or(4);
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        &bytes,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(width as u32 * bytes_per_pixel),
+            rows_per_image: Some(height as u32),
+        },
+        size,
+    );
+    texture
+}
+
+/// A texture copied into a buffer that is waiting to be mapped
+struct Readback {
+    buffer: wgpu::Buffer,
+    format: wgpu::TextureFormat,
+    width: usize,
+    height: usize,
+    bytes_per_pixel: usize,
+    bytes_per_row: usize,
+}
+
+/// Copy a texture into a mappable buffer, rows are padded to `COPY_BYTES_PER_ROW_ALIGNMENT`
+fn readback(
+    texture: &wgpu::Texture,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> Result<Readback, Error> {
+    use wgpu::TextureFormat::*;
+
+    let format = texture.format();
+    let bytes_per_pixel = match format {
+        Rgba8Unorm | Rgba8UnormSrgb | Bgra8Unorm | Bgra8UnormSrgb => 4,
+        Rgba16Float => 8,
+        Rgba32Float => 16,
+        _ => {
+            return Err(Error::Message(format!(
+                "Unsupported texture format: {:?}",
+                format
+            )))
+        }
+    };
+
+    let (width, height) = (texture.width() as usize, texture.height() as usize);
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize;
+    let bytes_per_row = (width * bytes_per_pixel).div_ceil(align) * align;
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: (bytes_per_row * height) as u64,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder =
+        device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row as u32),
+                rows_per_image: Some(height as u32),
+            },
+        },
+        texture.size(),
+    );
+    queue.submit(Some(encoder.finish()));
+
+    Ok(Readback {
+        buffer,
+        format,
+        width,
+        height,
+        bytes_per_pixel,
+        bytes_per_row,
+    })
+}
+
+impl Readback {
+    /// Convert the mapped buffer into an image
+    fn finish<T: Type, C: Color>(self) -> ImageBuf<T, C> {
+        use wgpu::TextureFormat::*;
+
+        let mut image = ImageBuf::new(self.width, self.height);
+        {
+            let bytes = self.buffer.slice(..).get_mapped_range();
+            image.for_each(|(x, y), px| {
+                let i = y * self.bytes_per_row + x * self.bytes_per_pixel;
+                let b = &bytes[i..i + self.bytes_per_pixel];
+                let rgba = match self.format {
+                    Rgba8Unorm | Rgba8UnormSrgb => [b[0], b[1], b[2], b[3]].map(|v| v.to_f()),
+                    Bgra8Unorm | Bgra8UnormSrgb => [b[2], b[1], b[0], b[3]].map(|v| v.to_f()),
+                    Rgba16Float => [0, 1, 2, 3].map(|c| {
+                        f64::from(f16_to_f32(u16::from_le_bytes([b[c * 2], b[c * 2 + 1]])))
+                    }),
+                    _ => [0, 1, 2, 3].map(|c| {
+                        let v = &b[c * 4..c * 4 + 4];
+                        f64::from(f32::from_le_bytes([v[0], v[1], v[2], v[3]]))
+                    }),
+                };
+                from_rgba::<T, C>(rgba, px);
+            });
+        }
+        self.buffer.unmap();
+        image
+    }
+}
+
+/// Copy a texture into an image, blocking until the GPU is finished. Supported formats are
+/// `Rgba8Unorm`, `Bgra8Unorm` and their sRGB variants, `Rgba16Float` and `Rgba32Float`.
+pub fn from_texture<T: Type, C: Color>(
+    texture: &wgpu::Texture,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> Result<ImageBuf<T, C>, Error> {
+    let readback = readback(texture, device, queue)?;
+    let (tx, rx) = std::sync::mpsc::channel();
+    readback
+        .buffer
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, move |res| {
+            let _ = tx.send(res);
+        });
+    device.poll(wgpu::Maintain::Wait);
+
+    match rx.recv() {
+        Ok(Ok(())) => Ok(readback.finish()),
+        Ok(Err(e)) => Err(Error::Message(format!("Unable to read texture: {}", e))),
+        Err(e) => Err(Error::Message(format!("Unable to read texture: {}", e))),
+    }
+}
+
+/// Shared state between a `map_async` callback and the future waiting for it
+type MapState = Arc<Mutex<(Option<Result<(), wgpu::BufferAsyncError>>, Option<Waker>)>>;
+
+struct MapFuture(MapState);
+
+impl Future for MapFuture {
+    type Output = Result<(), wgpu::BufferAsyncError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+        let mut state = self.0.lock().unwrap();
+        match state.0.take() {
+            Some(res) => Poll::Ready(res),
+            None => {
+                state.1 = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Like `from_texture`, without blocking. The copy is submitted immediately and the returned
+/// future resolves once the data has been mapped, on native platforms this only happens when the
+/// device is polled, for example by calling `device.poll(wgpu::Maintain::Poll)` regularly from
+/// the render loop.
+pub fn from_texture_async<T: Type, C: Color>(
+    texture: &wgpu::Texture,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> impl Future<Output = Result<ImageBuf<T, C>, Error>> {
+    let state: MapState = Arc::new(Mutex::new((None, None)));
+    let readback = readback(texture, device, queue).inspect(|readback| {
+        let callback = state.clone();
+        readback
+            .buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |res| {
+                let mut state = callback.lock().unwrap();
+                state.0 = Some(res);
+                if let Some(waker) = state.1.take() {
+                    waker.wake();
+                }
+            });
+    });
+
+    async move {
+        let readback = readback?;
+        match MapFuture(state).await {
+            Ok(()) => Ok(readback.finish()),
+            Err(e) => Err(Error::Message(format!("Unable to read texture: {}", e))),
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::pixel::PixelVec;
+
+/// The colorspace used to interpolate between gradient stops
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Interpolation {
+    /// Interpolate the sRGB encoded values directly
+    #[default]
+    Srgb,
+    /// Interpolate in the perceptually uniform Oklab colorspace, which avoids the dark or
+    /// desaturated midpoints produced by sRGB interpolation
+    Oklab,
+}
+
+/// A multi-stop color gradient, colors are normalized RGBA values
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone)]
+pub struct Gradient {
+    stops: Vec<(f64, PixelVec<f64>)>,
+    #[cfg_attr(feature = "ser", serde(default))]
+    interpolation: Interpolation,
+}
+
+fn srgb_to_linear(x: f64) -> f64 {
+    if x <= 0.04045 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(x: f64) -> f64 {
+    if x <= 0.003_130_8 {
+        x * 12.92
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Convert normalized sRGB values to Oklab
+pub(crate) fn to_oklab(rgb: [f64; 3]) -> [f64; 3] {
+    let [r, g, b] = [
+        srgb_to_linear(rgb[0]),
+        srgb_to_linear(rgb[1]),
+        srgb_to_linear(rgb[2]),
+    ];
+    let l = (0.412_221_470_8 * r + 0.536_332_536_3 * g + 0.051_445_992_9 * b).cbrt();
+    let m = (0.211_903_498_2 * r + 0.680_699_545_1 * g + 0.107_396_956_6 * b).cbrt();
+    let s = (0.088_302_461_9 * r + 0.281_718_837_6 * g + 0.629_978_700_5 * b).cbrt();
+    [
+        0.210_454_255_3 * l + 0.793_617_785 * m - 0.004_072_046_8 * s,
+        1.977_998_495_1 * l - 2.428_592_205 * m + 0.450_593_709_9 * s,
+        0.025_904_037_1 * l + 0.782_771_766_2 * m - 0.808_675_766 * s,
+    ]
+}
+
+/// Convert Oklab values to normalized sRGB
+pub(crate) fn from_oklab(lab: [f64; 3]) -> [f64; 3] {
+    let l = (lab[0] + 0.396_337_777_4 * lab[1] + 0.215_803_757_3 * lab[2]).powi(3);
+    let m = (lab[0] - 0.105_561_345_8 * lab[1] - 0.063_854_172_8 * lab[2]).powi(3);
+    let s = (lab[0] - 0.089_484_177_5 * lab[1] - 1.291_485_548 * lab[2]).powi(3);
+    [
+        linear_to_srgb(4.076_741_662_1 * l - 3.307_711_591_3 * m + 0.230_969_929_2 * s),
+        linear_to_srgb(-1.268_438_004_6 * l + 2.609_757_401_1 * m - 0.341_319_396_5 * s),
+        linear_to_srgb(-0.004_196_086_3 * l - 0.703_418_614_7 * m + 1.707_614_701 * s),
+    ]
+}
+
+impl Gradient {
+    /// Create a new gradient with no stops
+    pub fn new() -> Gradient {
+        Gradient {
+            stops: Vec::new(),
+            interpolation: Interpolation::Srgb,
+        }
+    }
+
+    /// Create a new gradient from colors spaced evenly between 0 and 1
+    pub fn from_colors(colors: &[PixelVec<f64>]) -> Gradient {
+        let mut gradient = Gradient::new();
+        let n = colors.len().max(2) - 1;
+        for (i, color) in colors.iter().enumerate() {
+            gradient.add_stop(i as f64 / n as f64, *color);
+        }
+        gradient
+    }
+
+    /// Add a stop at `position`, which should be between 0 and 1
+    pub fn add_stop(&mut self, position: f64, color: PixelVec<f64>) {
+        let index = self
+            .stops
+            .iter()
+            .position(|(p, _)| *p > position)
+            .unwrap_or(self.stops.len());
+        self.stops.insert(index, (position, color));
+    }
+
+    /// Builder-style variant of `add_stop`
+    pub fn with_stop(mut self, position: f64, color: PixelVec<f64>) -> Gradient {
+        self.add_stop(position, color);
+        self
+    }
+
+    /// Set the colorspace used to interpolate between stops
+    pub fn set_interpolation(&mut self, interpolation: Interpolation) {
+        self.interpolation = interpolation;
+    }
+
+    /// Builder-style variant of `set_interpolation`
+    pub fn with_interpolation(mut self, interpolation: Interpolation) -> Gradient {
+        self.set_interpolation(interpolation);
+        self
+    }
+
+    /// The colorspace used to interpolate between stops
+    pub fn interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
+
+    /// Returns the stops sorted by position
+    pub fn stops(&self) -> &[(f64, PixelVec<f64>)] {
+        &self.stops
+    }
+
+    /// Get the color at position `t`
+    pub fn at(&self, t: f64) -> PixelVec<f64> {
+        let stops = &self.stops;
+        if stops.is_empty() {
+            return PixelVec::empty();
+        }
+
+        if t <= stops[0].0 {
+            return stops[0].1;
+        }
+
+        for w in stops.windows(2) {
+            let (p0, c0) = w[0];
+            let (p1, c1) = w[1];
+            if t <= p1 {
+                let f = if p1 > p0 { (t - p0) / (p1 - p0) } else { 1.0 };
+                let mut dest = PixelVec::empty();
+                for i in 0..4 {
+                    dest.as_mut()[i] = c0.as_ref()[i] + (c1.as_ref()[i] - c0.as_ref()[i]) * f;
+                }
+
+                if self.interpolation == Interpolation::Oklab && f > 0.0 && f < 1.0 {
+                    let (a, b) = (c0.as_ref(), c1.as_ref());
+                    let a = to_oklab([a[0], a[1], a[2]]);
+                    let b = to_oklab([b[0], b[1], b[2]]);
+                    let rgb = from_oklab([
+                        a[0] + (b[0] - a[0]) * f,
+                        a[1] + (b[1] - a[1]) * f,
+                        a[2] + (b[2] - a[2]) * f,
+                    ]);
+                    dest.as_mut()[..3].copy_from_slice(&rgb);
+                }
+                return dest;
+            }
+        }
+
+        stops[stops.len() - 1].1
+    }
+}
+
+impl Default for Gradient {
+    fn default() -> Gradient {
+        Gradient::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn close(a: PixelVec<f64>, b: [f64; 4]) -> bool {
+        a.as_ref().iter().zip(&b).all(|(a, b)| (a - b).abs() < 1e-9)
+    }
+
+    #[test]
+    fn test_gradient() {
+        assert!(close(Gradient::new().at(0.5), [0.0; 4]));
+
+        // Stops are kept sorted and colors are clamped to the first and last stop
+        let gradient = Gradient::new()
+            .with_stop(1.0, PixelVec::new(0.0, 0.0, 1.0, 1.0))
+            .with_stop(0.0, PixelVec::new(1.0, 0.0, 0.0, 1.0))
+            .with_stop(0.5, PixelVec::new(0.0, 1.0, 0.0, 0.5));
+        let positions: Vec<f64> = gradient.stops().iter().map(|(p, _)| *p).collect();
+        assert_eq!(positions, vec![0.0, 0.5, 1.0]);
+        assert!(close(gradient.at(-1.0), [1.0, 0.0, 0.0, 1.0]));
+        assert!(close(gradient.at(0.5), [0.0, 1.0, 0.0, 0.5]));
+        assert!(close(gradient.at(2.0), [0.0, 0.0, 1.0, 1.0]));
+        assert!(close(gradient.at(0.25), [0.5, 0.5, 0.0, 0.75]));
+        assert!(close(gradient.at(0.875), [0.0, 0.25, 0.75, 0.875]));
+
+        let evenly = Gradient::from_colors(&[PixelVec::new_gray(0.0); 3]);
+        let positions: Vec<f64> = evenly.stops().iter().map(|(p, _)| *p).collect();
+        assert_eq!(positions, vec![0.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn test_oklab() {
+        for rgb in &[
+            [0.0, 0.0, 0.0],
+            [1.0, 1.0, 1.0],
+            [0.2, 0.5, 0.9],
+            [1.0, 0.0, 0.0],
+        ] {
+            let back = from_oklab(to_oklab(*rgb));
+            for (a, b) in back.iter().zip(rgb) {
+                assert!((a - b).abs() < 1e-6, "{:?} {:?}", rgb, back);
+            }
+        }
+
+        // White has a lightness of 1 and no chroma
+        let white = to_oklab([1.0, 1.0, 1.0]);
+        assert!((white[0] - 1.0).abs() < 1e-6);
+        assert!(white[1].abs() < 1e-6 && white[2].abs() < 1e-6);
+
+        // Only the midpoints change, the alpha channel is still interpolated linearly
+        let gradient = Gradient::from_colors(&[
+            PixelVec::new(0.0, 0.0, 0.0, 0.0),
+            PixelVec::new(1.0, 1.0, 1.0, 1.0),
+        ])
+        .with_interpolation(Interpolation::Oklab);
+        assert!(close(gradient.at(0.0), [0.0, 0.0, 0.0, 0.0]));
+        assert!(close(gradient.at(1.0), [1.0, 1.0, 1.0, 1.0]));
+        let mid = gradient.at(0.5);
+        assert!((mid.as_ref()[3] - 0.5).abs() < 1e-9);
+        assert!((mid.as_ref()[0] - mid.as_ref()[2]).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,414 @@
+//! High dynamic range imaging
+
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::color::{Gray, Rgb};
+use crate::error::Error;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::pyramid;
+use crate::ty::Type;
+
+/// Number of quantized pixel levels used for response curves
+const LEVELS: usize = 256;
+
+/// Number of sample positions along each axis used to estimate the response curve
+const SAMPLES: usize = 16;
+
+/// Smoothness of the estimated response curve
+const SMOOTHNESS: f64 = 10.0;
+
+/// Standard deviation of the well-exposedness weight used by exposure fusion
+const EXPOSEDNESS_SIGMA: f64 = 0.2;
+
+/// Camera response curve: the natural log of the exposure that produces each of 256 pixel
+/// levels, for each channel
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    curves: Vec<Vec<f64>>,
+}
+
+impl Response {
+    /// A linear response where pixel values are proportional to exposure
+    pub fn linear() -> Response {
+        let curve: Vec<f64> = (0..LEVELS)
+            .map(|z| ((z.max(1) as f64) / (LEVELS / 2) as f64).ln())
+            .collect();
+        Response {
+            curves: vec![curve; 3],
+        }
+    }
+
+    /// Get the curve for channel `c`
+    pub fn curve(&self, c: usize) -> &[f64] {
+        &self.curves[c]
+    }
+}
+
+#[inline]
+fn level<T: Type>(x: &T) -> usize {
+    ((T::to_f(x) * (LEVELS - 1) as f64).round() as usize).min(LEVELS - 1)
+}
+
+/// Triangle weighting function favoring mid-tones
+#[inline]
+fn weight(z: usize) -> f64 {
+    if z < LEVELS / 2 {
+        z as f64
+    } else {
+        (LEVELS - 1 - z) as f64
+    }
+}
+
+fn check<T: Type, I: Image<T, Rgb>>(images: &[I], times: &[f64]) -> Result<(), Error> {
+    if images.is_empty() || images.len() != times.len() {
+        return Err(Error::Message(format!(
+            "Expected one exposure time per image, got {} images and {} times",
+            images.len(),
+            times.len()
+        )));
+    }
+
+    if times.iter().any(|t| *t <= 0.0) {
+        return Err(Error::Message(String::from(
+            "Exposure times must be positive",
+        )));
+    }
+
+    check_size(images)
+}
+
+fn check_size<T: Type, I: Image<T, Rgb>>(images: &[I]) -> Result<(), Error> {
+    if images.is_empty() {
+        return Err(Error::Message(String::from("No exposures provided")));
+    }
+
+    let (width, height) = (images[0].width(), images[0].height());
+    if images
+        .iter()
+        .any(|i| i.width() != width || i.height() != height)
+    {
+        return Err(Error::Message(String::from(
+            "All exposures must have the same size",
+        )));
+    }
+
+    if width == 0 || height == 0 {
+        return Err(Error::Message(String::from("Exposures are empty")));
+    }
+
+    Ok(())
+}
+
+/// Solve `a * x = b` in place using Gaussian elimination with partial pivoting
+pub(crate) fn solve(a: &mut [f64], b: &mut [f64], n: usize) -> Option<Vec<f64>> {
+    for col in 0..n {
+        let pivot = (col..n).max_by(|i, j| {
+            a[i * n + col]
+                .abs()
+                .partial_cmp(&a[j * n + col].abs())
+                .unwrap_or(core::cmp::Ordering::Equal)
+        })?;
+        if a[pivot * n + col].abs() < 1e-12 {
+            return None;
+        }
+
+        if pivot != col {
+            for k in 0..n {
+                a.swap(pivot * n + k, col * n + k);
+            }
+            b.swap(pivot, col);
+        }
+
+        for row in col + 1..n {
+            let f = a[row * n + col] / a[col * n + col];
+            if f == 0.0 {
+                continue;
+            }
+
+            for k in col..n {
+                a[row * n + k] -= f * a[col * n + k];
+            }
+            b[row] -= f * b[col];
+        }
+    }
+
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|k| a[row * n + k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row * n + row];
+    }
+    Some(x)
+}
+
+/// Estimate the camera response curve from a set of exposures of the same scene using the
+/// method of Debevec and Malik. `smoothness` weights the second derivative penalty; larger
+/// values produce smoother curves.
+pub fn estimate_response<T: Type, I: Image<T, Rgb>>(
+    images: &[I],
+    times: &[f64],
+    smoothness: f64,
+) -> Result<Response, Error> {
+    check(images, times)?;
+
+    let (width, height) = (images[0].width(), images[0].height());
+    let mut positions = Vec::new();
+    for j in 0..SAMPLES {
+        for i in 0..SAMPLES {
+            let x = (i * 2 + 1) * width / (SAMPLES * 2);
+            let y = (j * 2 + 1) * height / (SAMPLES * 2);
+            if !positions.contains(&(x, y)) {
+                positions.push((x, y));
+            }
+        }
+    }
+
+    // Unknowns are the 256 curve values followed by the log exposure of each sample. The
+    // system is accumulated directly as normal equations since each row is sparse.
+    let n = LEVELS + positions.len();
+    let mut curves = Vec::with_capacity(3);
+    for c in 0..3 {
+        let mut ata = vec![0.0; n * n];
+        let mut atb = vec![0.0; n];
+        let mut add = |row: &[(usize, f64)], b: f64| {
+            for (i, vi) in row {
+                for (j, vj) in row {
+                    ata[i * n + j] += vi * vj;
+                }
+                atb[*i] += vi * b;
+            }
+        };
+
+        for (s, (x, y)) in positions.iter().enumerate() {
+            for (image, time) in images.iter().zip(times) {
+                let z = level(&image.at(*x, *y)[c]);
+                let w = weight(z) + 1e-3;
+                add(&[(z, w), (LEVELS + s, -w)], w * time.ln());
+            }
+        }
+
+        // Fix the middle of the curve at zero
+        add(&[(LEVELS / 2, 1.0)], 0.0);
+
+        for z in 1..LEVELS - 1 {
+            let w = smoothness * weight(z);
+            add(&[(z - 1, w), (z, -2.0 * w), (z + 1, w)], 0.0);
+        }
+
+        let x = match solve(&mut ata, &mut atb, n) {
+            Some(x) => x,
+            None => {
+                return Err(Error::Message(String::from(
+                    "Unable to estimate camera response",
+                )))
+            }
+        };
+        curves.push(x[..LEVELS].to_vec());
+    }
+
+    Ok(Response { curves })
+}
+
+/// Merge exposures into a radiance map using a known camera response
+pub fn merge_with_response<T: Type, I: Image<T, Rgb>>(
+    images: &[I],
+    times: &[f64],
+    response: &Response,
+) -> Result<ImageBuf<f32, Rgb>, Error> {
+    check(images, times)?;
+
+    let (width, height) = (images[0].width(), images[0].height());
+    let mut dest = ImageBuf::new(width, height);
+    dest.for_each(|(x, y), px| {
+        for (c, item) in px.iter_mut().enumerate() {
+            let curve = response.curve(c);
+            let (mut sum, mut total) = (0.0, 0.0);
+
+            // Used when every exposure is under or over exposed
+            let mut fallback = (f64::INFINITY, 0.0);
+            for (image, time) in images.iter().zip(times) {
+                let z = level(&image.at(x, y)[c]);
+                let value = curve[z] - time.ln();
+                let w = weight(z);
+                sum += w * value;
+                total += w;
+
+                let distance = (z as f64 - (LEVELS / 2) as f64).abs();
+                if distance < fallback.0 {
+                    fallback = (distance, value);
+                }
+            }
+
+            let log = if total > 0.0 { sum / total } else { fallback.1 };
+            *item = log.exp() as f32;
+        }
+    });
+    Ok(dest)
+}
+
+/// Merge a set of exposures of the same scene into a linear radiance map using the method of
+/// Debevec and Malik. The camera response is estimated from the images, `times` contains the
+/// exposure time of each image.
+pub fn merge_debevec<T: Type, I: Image<T, Rgb>>(
+    images: &[I],
+    times: &[f64],
+) -> Result<ImageBuf<f32, Rgb>, Error> {
+    let response = estimate_response(images, times, SMOOTHNESS)?;
+    merge_with_response(images, times, &response)
+}
+
+/// Per-pixel exposure fusion weights: local contrast, saturation and well-exposedness
+fn fusion_weights<T: Type, I: Image<T, Rgb>>(image: &I) -> Vec<f64> {
+    let (width, height) = (image.width(), image.height());
+    let gray: Vec<f64> = image
+        .data()
+        .chunks(3)
+        .map(|px| (T::to_f(&px[0]) + T::to_f(&px[1]) + T::to_f(&px[2])) / 3.0)
+        .collect();
+
+    let mut weights = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let at = |dx: isize, dy: isize| {
+                let i = (x as isize + dx).clamp(0, width as isize - 1) as usize;
+                let j = (y as isize + dy).clamp(0, height as isize - 1) as usize;
+                gray[j * width + i]
+            };
+            let contrast = (at(-1, 0) + at(1, 0) + at(0, -1) + at(0, 1) - 4.0 * at(0, 0)).abs();
+
+            let px = image.at(x, y);
+            let rgb = [T::to_f(&px[0]), T::to_f(&px[1]), T::to_f(&px[2])];
+            let mean = (rgb[0] + rgb[1] + rgb[2]) / 3.0;
+            let saturation =
+                (rgb.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / 3.0).sqrt();
+            let exposedness: f64 = rgb
+                .iter()
+                .map(|v| {
+                    (-(v - 0.5) * (v - 0.5) / (2.0 * EXPOSEDNESS_SIGMA * EXPOSEDNESS_SIGMA)).exp()
+                })
+                .product();
+
+            weights.push(contrast * saturation * exposedness + 1e-12);
+        }
+    }
+    weights
+}
+
+/// Fuse a set of exposures of the same scene directly into a low dynamic range image using the
+/// method of Mertens et al. Each pixel is weighted by local contrast, saturation and
+/// well-exposedness, and the images are blended using Laplacian pyramids to avoid seams. No
+/// exposure times or tone mapping are required.
+pub fn exposure_fusion<T: Type, I: Image<T, Rgb>>(images: &[I]) -> Result<ImageBuf<T, Rgb>, Error> {
+    check_size(images)?;
+
+    let (width, height) = (images[0].width(), images[0].height());
+    let weights: Vec<Vec<f64>> = images.iter().map(fusion_weights).collect();
+    let totals: Vec<f64> = (0..width * height)
+        .map(|i| weights.iter().map(|w| w[i]).sum())
+        .collect();
+
+    let mut levels = 1;
+    while (width.min(height) >> levels) >= 8 {
+        levels += 1;
+    }
+
+    let mut blended: Vec<ImageBuf<f64, Rgb>> = Vec::new();
+    for (image, weight) in images.iter().zip(weights) {
+        let weight: Vec<f64> = weight.iter().zip(&totals).map(|(w, t)| w / t).collect();
+        let weight: ImageBuf<f64, Gray> = ImageBuf::new_from(width, height, weight);
+        let weight = pyramid::gaussian(&weight, levels);
+        let laplacian = pyramid::laplacian(image, levels);
+
+        if blended.is_empty() {
+            blended = laplacian
+                .iter()
+                .map(|l| ImageBuf::new(l.width(), l.height()))
+                .collect();
+        }
+
+        for ((dest, l), w) in blended.iter_mut().zip(&laplacian).zip(&weight) {
+            for ((d, px), w) in dest
+                .data_mut()
+                .chunks_mut(3)
+                .zip(l.data().chunks(3))
+                .zip(w.data())
+            {
+                for (d, v) in d.iter_mut().zip(px) {
+                    *d += w * v;
+                }
+            }
+        }
+    }
+
+    let fused = pyramid::collapse(&blended);
+    let mut dest = ImageBuf::new(width, height);
+    dest.copy_metadata(&images[0]);
+    for (d, v) in dest.data_mut().iter_mut().zip(fused.data()) {
+        *d = T::from_f(*v);
+    }
+    Ok(dest)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{hdr, Image, ImageBuf, Rgb};
+
+    #[test]
+    fn test_merge_debevec() {
+        // A scene with radiance increasing from left to right, captured by a camera with a
+        // gamma response
+        let exposure = |time: f64| {
+            let mut image: ImageBuf<u8, Rgb> = ImageBuf::new(64, 16);
+            image.for_each(|(x, _), px| {
+                let radiance = 0.02 * 1.08f64.powi(x as i32);
+                let v = ((radiance * time).min(1.0).powf(1.0 / 2.2) * 255.0).round() as u8;
+                px.copy_from_slice(&[v, v, v]);
+            });
+            image
+        };
+
+        let times = [0.25, 1.0, 4.0];
+        let images: Vec<_> = times.iter().map(|t| exposure(*t)).collect();
+        let hdr = hdr::merge_debevec(&images, &times).unwrap();
+
+        // The ratio between radiance values is recovered
+        for x in &[5, 20, 40, 60] {
+            let ratio = hdr.at(x + 1, 8)[0] as f64 / hdr.at(*x, 8)[0] as f64;
+            assert!((ratio - 1.08).abs() < 0.05, "{} {}", x, ratio);
+        }
+        assert!(hdr::merge_debevec(&images, &times[..2]).is_err());
+
+        let empty: Vec<ImageBuf<u8, Rgb>> = vec![ImageBuf::new(0, 0); 3];
+        assert!(hdr::merge_debevec(&empty, &times).is_err());
+    }
+
+    #[test]
+    fn test_exposure_fusion() {
+        // A gradient that is clipped in the bright exposure and crushed in the dark one
+        let exposure = |gain: f64| {
+            let mut image: ImageBuf<u8, Rgb> = ImageBuf::new(64, 32);
+            image.for_each(|(x, y), px| {
+                let v = (x as f64 / 63.0 * gain).min(1.0);
+                let v = (v * 255.0).round() as u8;
+                px.copy_from_slice(&[v, v / 2, (y * 4) as u8]);
+            });
+            image
+        };
+
+        let images = [exposure(0.5), exposure(1.0), exposure(2.0)];
+        let fused = hdr::exposure_fusion(&images).unwrap();
+        assert_eq!((fused.width(), fused.height()), (64, 32));
+
+        // Detail is kept at both ends of the gradient
+        assert!(fused.at(4, 16)[0] > images[0].at(4, 16)[0]);
+        assert!(fused.at(60, 16)[0] < 255);
+        assert!(fused.at(60, 16)[0] > fused.at(30, 16)[0]);
+
+        let empty: [ImageBuf<u8, Rgb>; 0] = [];
+        assert!(hdr::exposure_fusion(&empty).is_err());
+        assert!(hdr::exposure_fusion(&[ImageBuf::<u8, Rgb>::new(0, 4)]).is_err());
+    }
+}
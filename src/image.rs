@@ -1,21 +1,26 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
 use crate::color::{Bgr, Color, Gray, Rgb, Rgba};
 use crate::filter::{AlphaBlend, Filter, SwapChannel, ToColor, ToGrayscale};
-use crate::image_buf::ImageBuf;
+use crate::image_buf::{ImageBuf, Resolution};
 use crate::image_ptr::{Free, ImagePtr};
 use crate::image_ref::ImageRef;
-use crate::pixel::{Pixel, PixelMut};
+use crate::parallel;
+use crate::pipeline::Pipeline;
+use crate::pixel::{Pixel, PixelMut, Px};
 use crate::ty::Type;
 
-#[cfg(feature = "parallel")]
-use rayon::prelude::*;
-
 #[inline]
 pub fn index(width: usize, channels: usize, x: usize, y: usize, c: usize) -> usize {
     width * channels * y + channels * x + c
 }
 
 #[derive(Debug, Clone)]
-pub struct Diff(std::collections::HashMap<(usize, usize, usize), f64>);
+pub struct Diff(BTreeMap<(usize, usize, usize), f64>);
 
 impl Diff {
     pub fn len(&self) -> usize {
@@ -64,8 +69,8 @@ impl From<Hash> for u64 {
 }
 
 fn free_slice<T: Type>(ptr: *mut T, size: usize) {
-    let slice = unsafe { std::slice::from_raw_parts_mut(ptr, size) };
-    std::mem::drop(slice)
+    let slice = unsafe { core::slice::from_raw_parts_mut(ptr, size) };
+    core::mem::drop(slice)
 }
 
 /// The Image trait defines many methods for interaction with images in a generic manner
@@ -79,12 +84,22 @@ pub trait Image<T: Type, C: Color>: Sized + Sync + Send {
     /// A mutable reference to the underlying image data
     fn data_mut(&mut self) -> &mut [T];
 
+    /// Physical resolution of the image, only `ImageBuf` keeps track of it
+    fn resolution(&self) -> Option<Resolution> {
+        None
+    }
+
+    /// Embedded ICC profile, only `ImageBuf` keeps track of it
+    fn profile(&self) -> Option<&[u8]> {
+        None
+    }
+
     fn buffer(&self) -> &[u8] {
         let data = self.data();
         unsafe {
-            std::slice::from_raw_parts(
+            core::slice::from_raw_parts(
                 data.as_ptr() as *const u8,
-                data.len() * std::mem::size_of::<T>(),
+                data.len() * core::mem::size_of::<T>(),
             )
         }
     }
@@ -92,9 +107,9 @@ pub trait Image<T: Type, C: Color>: Sized + Sync + Send {
     fn buffer_mut(&self) -> &[u8] {
         let data = self.data();
         unsafe {
-            std::slice::from_raw_parts_mut(
+            core::slice::from_raw_parts_mut(
                 data.as_ptr() as *mut u8,
-                data.len() * std::mem::size_of::<T>(),
+                data.len() * core::mem::size_of::<T>(),
             )
         }
     }
@@ -122,7 +137,7 @@ pub trait Image<T: Type, C: Color>: Sized + Sync + Send {
 
     /// Get the total number of bytes needed to store the image data
     fn total_bytes(&self) -> usize {
-        self.len() * std::mem::size_of::<T>()
+        self.len() * core::mem::size_of::<T>()
     }
 
     /// Get the offset of the component at (x, y, c)
@@ -155,6 +170,11 @@ pub trait Image<T: Type, C: Color>: Sized + Sync + Send {
         &self.data()[index..index + C::channels()]
     }
 
+    /// Get a copy of the pixel at (x, y) as a `Px`, which supports arithmetic
+    fn px(&self, x: usize, y: usize) -> Px<T, C> {
+        Px::new(self.at(x, y))
+    }
+
     /// Load data from the pixel at (x, y) into px
     fn get_pixel<'a, P: PixelMut<'a, T, C>>(&self, x: usize, y: usize, px: &mut P) {
         let data = self.data();
@@ -244,10 +264,19 @@ pub trait Image<T: Type, C: Color>: Sized + Sync + Send {
 
     /// Convert from type T to type U
     fn convert_type<U: Type, I: Image<U, C>>(&self, dest: &mut I) {
-        let ddata = dest.data_mut();
-        for (i, x) in self.data().iter().enumerate() {
-            ddata[i] = x.convert();
-        }
+        let row_len = self.width() * self.channels();
+        let src = self.data();
+        parallel::for_each_row(dest.data_mut(), row_len, |y, row| {
+            let src = &src[(y * row_len).min(src.len())..];
+            let n = row.len().min(src.len());
+            if crate::simd::convert(&src[..n], &mut row[..n]) {
+                return;
+            }
+
+            for (d, x) in row.iter_mut().zip(src) {
+                *d = x.convert();
+            }
+        });
     }
 
     /// Convert Image to ImageRef
@@ -258,81 +287,175 @@ pub trait Image<T: Type, C: Color>: Sized + Sync + Send {
     /// Consume and convert Image to ImagePtr
     fn to_image_ptr<'a>(mut self) -> ImagePtr<'a, T, C> {
         let ptr = self.data_mut().as_mut_ptr();
-        std::mem::forget(ptr);
+        core::mem::forget(ptr);
         ImagePtr::new(self.width(), self.height(), ptr, Free::Function(free_slice))
     }
 
-    /// Iterate over each pixel
-    #[cfg(feature = "parallel")]
-    fn for_each<F: Sync + Send + Fn((usize, usize), &mut [T])>(&mut self, f: F) {
-        let (width, _height, channels) = self.shape();
-        self.data_mut()
-            .par_chunks_mut(channels)
-            .enumerate()
-            .for_each(|(n, pixel)| {
-                let y = n / width;
-                let x = n - (y * width);
-                f((x, y), pixel)
-            });
+    /// Create a lazily evaluated pipeline using this image as input
+    fn lazy(&self) -> Pipeline<'_, T, C, Self> {
+        Pipeline::new(self)
     }
 
-    /// Iterate over each pixel
-    #[cfg(not(feature = "parallel"))]
+    /// Iterate over each pixel, rows are processed in parallel when the `parallel` feature is
+    /// enabled
     fn for_each<F: Sync + Send + Fn((usize, usize), &mut [T])>(&mut self, f: F) {
         let (width, _height, channels) = self.shape();
-        self.data_mut()
-            .chunks_exact_mut(channels)
-            .enumerate()
-            .for_each(|(n, pixel)| {
-                let y = n / width;
-                let x = n - (y * width);
+        parallel::for_each_row(self.data_mut(), width * channels, |y, row| {
+            for (x, pixel) in row.chunks_exact_mut(channels).enumerate() {
                 f((x, y), pixel)
-            });
+            }
+        });
     }
 
-    /// Iterate over each pixel
-    #[cfg(feature = "parallel")]
+    /// Iterate over each pixel of two images
     fn for_each2<F: Sync + Send + Fn((usize, usize), &mut [T], &[T]), I: Image<T, C>>(
         &mut self,
         other: &I,
         f: F,
     ) {
         let (width, _height, channels) = self.shape();
-        let b = other.data().par_chunks(channels);
-        self.data_mut()
-            .par_chunks_mut(channels)
-            .zip(b)
-            .enumerate()
-            .for_each(|(n, (pixel, pixel1))| {
-                let y = n / width;
-                let x = n - (y * width);
+        let row_len = width * channels;
+        let b = other.data();
+        parallel::for_each_row(self.data_mut(), row_len, |y, row| {
+            let b = &b[(y * row_len).min(b.len())..];
+            for (x, (pixel, pixel1)) in row.chunks_mut(channels).zip(b.chunks(channels)).enumerate()
+            {
                 f((x, y), pixel, pixel1)
-            });
+            }
+        });
     }
 
-    /// Iterate over each pixel
-    #[cfg(not(feature = "parallel"))]
-    fn for_each2<F: Sync + Send + Fn((usize, usize), &mut [T], &[T]), I: Image<T, C>>(
-        &mut self,
+    /// Create a new image by applying `f` to the normalized value of each component. The whole
+    /// expression is compiled into a single loop, so `image.map_pixels(|x| (x - 0.5).abs() * 2.0)`
+    /// only allocates the result
+    fn map_pixels<F: Fn(f64) -> f64>(&self, f: F) -> ImageBuf<T, C> {
+        let mut dest = ImageBuf::new(self.width(), self.height());
+        dest.copy_metadata(self);
+        for (d, x) in dest.data_mut().iter_mut().zip(self.data()) {
+            *d = T::from_f(f(x.to_f()));
+        }
+        dest
+    }
+
+    /// Like `map_pixels`, rows are processed in parallel when the `parallel` feature is enabled,
+    /// see `map_to`
+    fn par_map_pixels<F: Sync + Send + Fn(f64) -> f64>(&self, f: F) -> ImageBuf<T, C> {
+        let mut dest = ImageBuf::new(self.width(), self.height());
+        dest.copy_metadata(self);
+        self.map_to(&mut dest, |src, dest| {
+            for (d, x) in dest.iter_mut().zip(src) {
+                *d = T::from_f(f(x.to_f()));
+            }
+        });
+        dest
+    }
+
+    /// Create a new image by combining the normalized values of each component of two images with
+    /// the same width and height, for example `a.zip_map(&b, |a, b| (a - b).abs() * 2.0)` computes the
+    /// scaled difference in a single loop without intermediate images
+    fn zip_map<U: Type, I: Image<U, C>, F: Fn(f64, f64) -> f64>(
+        &self,
         other: &I,
         f: F,
+    ) -> ImageBuf<T, C> {
+        assert!(
+            other.width() == self.width() && other.height() == self.height(),
+            "zip_map requires images with the same width and height"
+        );
+        let mut dest = ImageBuf::new(self.width(), self.height());
+        dest.copy_metadata(self);
+        for (d, (a, b)) in dest
+            .data_mut()
+            .iter_mut()
+            .zip(self.data().iter().zip(other.data()))
+        {
+            *d = T::from_f(f(a.to_f(), b.to_f()));
+        }
+        dest
+    }
+
+    /// Like `zip_map`, rows are processed in parallel when the `parallel` feature is enabled,
+    /// see `zip_with`
+    fn par_zip_map<U: Type, I: Image<U, C>, F: Sync + Send + Fn(f64, f64) -> f64>(
+        &self,
+        other: &I,
+        f: F,
+    ) -> ImageBuf<T, C> {
+        self.zip_with(other, |a, b, dest| {
+            for (d, (a, b)) in dest.iter_mut().zip(a.iter().zip(b)) {
+                *d = T::from_f(f(a.to_f(), b.to_f()));
+            }
+        })
+    }
+
+    /// Call `f` with the coordinates and channels of each pixel, in row-major order
+    fn for_each_pixel<F: FnMut((usize, usize), &[T])>(&self, mut f: F) {
+        let (width, _height, channels) = self.shape();
+        for (i, px) in self.data().chunks_exact(channels).enumerate() {
+            f((i % width, i / width), px)
+        }
+    }
+
+    /// Write `f(src, dest)` for each pixel of this image to the pixel at the same position in
+    /// `dest`, which can have a different type and color but must have the same width and height.
+    /// Rows are processed in parallel when the `parallel` feature is enabled
+    fn map_to<U: Type, D: Color, I: Image<U, D>, F: Sync + Send + Fn(&[T], &mut [U])>(
+        &self,
+        dest: &mut I,
+        f: F,
     ) {
+        assert!(
+            dest.width() == self.width() && dest.height() == self.height(),
+            "map_to requires images with the same width and height"
+        );
         let (width, _height, channels) = self.shape();
-        let b = other.data().chunks(channels);
-        self.data_mut()
-            .chunks_mut(channels)
-            .zip(b)
-            .enumerate()
-            .for_each(|(n, (pixel, pixel1))| {
-                let y = n / width;
-                let x = n - (y * width);
-                f((x, y), pixel, pixel1)
-            });
+        let (src_row_len, dest_row_len) = (width * channels, width * D::channels());
+        let src = self.data();
+        parallel::for_each_row(dest.data_mut(), dest_row_len, |y, row| {
+            let src = &src[y * src_row_len..(y + 1) * src_row_len];
+            for (d, s) in row
+                .chunks_exact_mut(D::channels())
+                .zip(src.chunks_exact(channels))
+            {
+                f(s, d)
+            }
+        });
+    }
+
+    /// Create a new image by calling `f(a, b, dest)` with the pixels at the same position in this
+    /// image and `other`, which must have the same width and height. Rows are processed in
+    /// parallel when the `parallel` feature is enabled
+    fn zip_with<U: Type, I: Image<U, C>, F: Sync + Send + Fn(&[T], &[U], &mut [T])>(
+        &self,
+        other: &I,
+        f: F,
+    ) -> ImageBuf<T, C> {
+        assert!(
+            other.width() == self.width() && other.height() == self.height(),
+            "zip_with requires images with the same width and height"
+        );
+        let mut dest = ImageBuf::new(self.width(), self.height());
+        dest.copy_metadata(self);
+        let channels = C::channels();
+        let row_len = self.width() * channels;
+        let (a, b) = (self.data(), other.data());
+        parallel::for_each_row(dest.data_mut(), row_len, |y, row| {
+            let range = y * row_len..(y + 1) * row_len;
+            for (d, (a, b)) in row.chunks_exact_mut(channels).zip(
+                a[range.clone()]
+                    .chunks_exact(channels)
+                    .zip(b[range].chunks_exact(channels)),
+            ) {
+                f(a, b, d)
+            }
+        });
+        dest
     }
 
     /// Create a new image from the region specified by (x, y, width, height)
     fn crop(&self, x: usize, y: usize, width: usize, height: usize) -> ImageBuf<T, C> {
         let mut dest = ImageBuf::new(width, height);
+        dest.copy_metadata(self);
 
         dest.for_each(|(i, j), px| {
             let src = self.at(x + i, y + j);
@@ -347,6 +470,7 @@ pub trait Image<T: Type, C: Color>: Sized + Sync + Send {
     fn clone(&self) -> ImageBuf<T, C> {
         let (width, height, _) = self.shape();
         let mut dest = ImageBuf::new(width, height);
+        dest.copy_metadata(self);
 
         dest.for_each(|(i, j), px| {
             let src = self.at(i, j);
@@ -358,45 +482,19 @@ pub trait Image<T: Type, C: Color>: Sized + Sync + Send {
         dest
     }
 
-    #[cfg(feature = "parallel")]
     fn multiply<'a, P: Pixel<'a, f64, C>>(&mut self, px: &P) {
-        let data = self.data_mut();
         let px = px.as_ref();
-        data.par_chunks_mut(C::channels()).for_each(|x| {
-            for (n, i) in x.into_iter().enumerate() {
+        self.for_each(|_, x| {
+            for (n, i) in x.iter_mut().enumerate() {
                 *i = T::from_float(T::clamp(px[n] * T::to_float(i)));
             }
         });
     }
 
-    #[cfg(feature = "parallel")]
     fn add<'a, P: Pixel<'a, f64, C>>(&mut self, px: &P) {
-        let data = self.data_mut();
         let px = px.as_ref();
-        data.par_chunks_mut(C::channels()).for_each(|x| {
-            for (n, i) in x.into_iter().enumerate() {
-                *i = T::from_float(T::clamp(px[n] + T::to_float(i)));
-            }
-        });
-    }
-
-    #[cfg(not(feature = "parallel"))]
-    fn multiply<'a, P: Pixel<'a, f64, C>>(&mut self, px: &P) {
-        let data = self.data_mut();
-        let mut px = px.as_ref();
-        data.chunks_mut(C::channels()).for_each(|x| {
-            for (n, i) in x.into_iter().enumerate() {
-                *i = T::from_float(T::clamp(px[n] * T::to_float(i)));
-            }
-        });
-    }
-
-    #[cfg(not(feature = "parallel"))]
-    fn add<'a, P: Pixel<'a, f64, C>>(&mut self, px: &P) {
-        let data = self.data_mut();
-        let mut px = px.as_ref();
-        data.chunks_mut(C::channels()).for_each(|x| {
-            for (n, i) in x.into_iter().enumerate() {
+        self.for_each(|_, x| {
+            for (n, i) in x.iter_mut().enumerate() {
                 *i = T::from_float(T::clamp(px[n] + T::to_float(i)));
             }
         });
@@ -425,7 +523,7 @@ pub trait Image<T: Type, C: Color>: Sized + Sync + Send {
     }
 
     fn diff<I: Image<T, C>>(&self, other: &I) -> Diff {
-        let mut map = std::collections::HashMap::new();
+        let mut map = BTreeMap::new();
 
         for j in 0..self.height() {
             for i in 0..self.width() {
@@ -443,6 +541,34 @@ pub trait Image<T: Type, C: Color>: Sized + Sync + Send {
 
         Diff(map)
     }
+
+    /// Render a grayscale preview of the image using ASCII characters, `width` characters wide.
+    /// Each line covers twice as many pixels vertically as each character covers horizontally
+    /// to make up for the shape of terminal fonts
+    fn preview_ascii(&self, width: usize) -> String {
+        const RAMP: &[u8] = b" .:-=+*#%@";
+
+        let (w, h) = (self.width(), self.height());
+        if width == 0 || w == 0 || h == 0 {
+            return String::new();
+        }
+        let height = ((h * width) as f64 / w as f64 / 2.0).round().max(1.0) as usize;
+        let mut small = ImageBuf::new(width, height);
+        crate::transform::resize(&mut small, self, width, height);
+
+        // The alpha channel is ignored, the brightness is the mean of the color channels
+        let colors = C::channels() - C::has_alpha() as usize;
+        let mut s = String::with_capacity((width + 1) * height);
+        for y in 0..height {
+            for x in 0..width {
+                let f = (0..colors).map(|c| small.get_f(x, y, c)).sum::<f64>() / colors as f64;
+                let index = (f.clamp(0.0, 1.0) * (RAMP.len() - 1) as f64).round() as usize;
+                s.push(RAMP[index] as char);
+            }
+            s.push('\n');
+        }
+        s
+    }
 }
 
 /// Provides a way to convert between image types
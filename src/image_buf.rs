@@ -1,30 +1,342 @@
+use alloc::vec::Vec;
+
 use crate::color::Color;
+use crate::error::Error;
 use crate::image::Image;
 use crate::ty::Type;
 
-use std::marker::PhantomData;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+
+/// Images with at most this many bytes of data are stored inside of the `ImageBuf` instead of
+/// allocating them on the heap
+pub const INLINE_BYTES: usize = 256;
+
+/// Alignment of the data of images created using `ImageBuf::new_aligned`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alignment {
+    /// 32 bytes, the width of an AVX2 register
+    Bytes32,
+    /// 64 bytes, the width of an AVX-512 register and a cache line
+    Bytes64,
+}
+
+impl Alignment {
+    /// Number of bytes
+    pub fn bytes(self) -> usize {
+        match self {
+            Alignment::Bytes32 => 32,
+            Alignment::Bytes64 => 64,
+        }
+    }
+}
 
-/// Image implementation using `Vec<T>` to store data
+/// Unit of a `Resolution`
 #[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResolutionUnit {
+    /// Only the aspect ratio of the pixels is known
+    None,
+    Inch,
+    Centimeter,
+}
+
+/// Physical resolution of an image as the number of pixels per unit in each direction
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Resolution {
+    pub x: f64,
+    pub y: f64,
+    pub unit: ResolutionUnit,
+}
+
+impl Resolution {
+    pub fn new(x: f64, y: f64, unit: ResolutionUnit) -> Resolution {
+        Resolution { x, y, unit }
+    }
+
+    /// The same number of pixels per inch in both directions
+    pub fn dpi(dpi: f64) -> Resolution {
+        Resolution::new(dpi, dpi, ResolutionUnit::Inch)
+    }
+
+    /// Pixels per inch in each direction, `None` when the unit is unknown
+    pub fn to_dpi(&self) -> Option<(f64, f64)> {
+        match self.unit {
+            ResolutionUnit::None => None,
+            ResolutionUnit::Inch => Some((self.x, self.y)),
+            ResolutionUnit::Centimeter => Some((self.x * 2.54, self.y * 2.54)),
+        }
+    }
+
+    /// Width of a pixel divided by its height
+    pub fn pixel_aspect(&self) -> f64 {
+        self.y / self.x
+    }
+}
+
+#[derive(Clone, Copy)]
+#[repr(C, align(32))]
+struct Block32([MaybeUninit<u8>; 32]);
+
+#[derive(Clone, Copy)]
+#[repr(C, align(64))]
+struct Block64([MaybeUninit<u8>; 64]);
+
+const BLOCK32: Block32 = Block32([MaybeUninit::uninit(); 32]);
+const BLOCK64: Block64 = Block64([MaybeUninit::uninit(); 64]);
+
+/// Storage for the data of an `ImageBuf`, the inline variant is large on purpose
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone)]
+enum Storage<T: Type> {
+    /// The first `len` values of the array, only used for types aligned to at most 8 bytes
+    Inline([MaybeUninit<u64>; INLINE_BYTES / 8], usize),
+    Heap(Vec<T>),
+    /// The first `len` values of the blocks, followed by at least one block of padding
+    Aligned32(Vec<Block32>, usize),
+    Aligned64(Vec<Block64>, usize),
+}
+
+/// Number of blocks needed to store `len` values followed by one block of padding
+fn blocks<T: Type>(len: usize, block: usize) -> usize {
+    match len.checked_mul(core::mem::size_of::<T>()) {
+        Some(bytes) => bytes.div_ceil(block) + 1,
+        None => panic!("image data is too large"),
+    }
+}
+
+impl<T: Type> Storage<T> {
+    /// Returns true when `len` values can be stored inline
+    fn fits(len: usize) -> bool {
+        core::mem::align_of::<T>() <= core::mem::align_of::<u64>()
+            && len
+                .checked_mul(core::mem::size_of::<T>())
+                .is_some_and(|bytes| bytes <= INLINE_BYTES)
+    }
+
+    fn new(len: usize) -> Self {
+        if Self::fits(len) {
+            let mut storage = Storage::Inline([MaybeUninit::uninit(); INLINE_BYTES / 8], len);
+            storage.zero_from(0);
+            storage
+        } else {
+            Storage::Heap(vec![T::zero(); len])
+        }
+    }
+
+    /// Set the values from `start` up to the end of the data and padding to `T::zero()`, these
+    /// values may not have been initialized yet
+    fn zero_from(&mut self, start: usize) {
+        let end = self.len() + self.padding();
+        let ptr = self.as_mut_ptr();
+        for i in start..end {
+            // Safety: `ptr` is aligned for `T` and the storage has room for `end` values
+            unsafe { ptr.add(i).write(T::zero()) }
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Storage::Heap(data) => data.len(),
+            Storage::Inline(_, len) | Storage::Aligned32(_, len) | Storage::Aligned64(_, len) => {
+                *len
+            }
+        }
+    }
+
+    fn as_ptr(&self) -> *const T {
+        match self {
+            Storage::Inline(data, _) => data.as_ptr() as *const T,
+            Storage::Heap(data) => data.as_ptr(),
+            Storage::Aligned32(data, _) => data.as_ptr() as *const T,
+            Storage::Aligned64(data, _) => data.as_ptr() as *const T,
+        }
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut T {
+        match self {
+            Storage::Inline(data, _) => data.as_mut_ptr() as *mut T,
+            Storage::Heap(data) => data.as_mut_ptr(),
+            Storage::Aligned32(data, _) => data.as_mut_ptr() as *mut T,
+            Storage::Aligned64(data, _) => data.as_mut_ptr() as *mut T,
+        }
+    }
+
+    fn aligned(len: usize, alignment: Alignment) -> Self {
+        assert!(
+            core::mem::size_of::<T>() > 0 && core::mem::align_of::<T>() <= alignment.bytes(),
+            "aligned storage requires a type with a non-zero size and an alignment of at most {} \
+             bytes",
+            alignment.bytes()
+        );
+        let mut storage = match alignment {
+            Alignment::Bytes32 => Storage::Aligned32(vec![BLOCK32; blocks::<T>(len, 32)], len),
+            Alignment::Bytes64 => Storage::Aligned64(vec![BLOCK64; blocks::<T>(len, 64)], len),
+        };
+        storage.zero_from(0);
+        storage
+    }
+
+    fn alignment(&self) -> Option<Alignment> {
+        match self {
+            Storage::Aligned32(..) => Some(Alignment::Bytes32),
+            Storage::Aligned64(..) => Some(Alignment::Bytes64),
+            _ => None,
+        }
+    }
+
+    /// Number of values that can be read past the end of the data
+    fn padding(&self) -> usize {
+        let (bytes, len) = match self {
+            Storage::Aligned32(data, len) => (data.len() * 32, *len),
+            Storage::Aligned64(data, len) => (data.len() * 64, *len),
+            _ => return 0,
+        };
+        bytes
+            .checked_div(core::mem::size_of::<T>())
+            .and_then(|n| n.checked_sub(len))
+            .unwrap_or(0)
+    }
+
+    // Safety: the data and padding are always initialized, see `zero_from`
+    fn as_padded_slice(&self) -> &[T] {
+        unsafe { core::slice::from_raw_parts(self.as_ptr(), self.len() + self.padding()) }
+    }
+
+    fn as_slice(&self) -> &[T] {
+        unsafe { core::slice::from_raw_parts(self.as_ptr(), self.len()) }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        let len = self.len();
+        unsafe { core::slice::from_raw_parts_mut(self.as_mut_ptr(), len) }
+    }
+
+    fn into_vec(self) -> Vec<T> {
+        match self {
+            Storage::Heap(data) => data,
+            _ => self.as_slice().to_vec(),
+        }
+    }
+
+    /// Change the number of values, new values are set to zero. Heap storage stays on the heap
+    /// so its allocation can be reused and aligned storage keeps its alignment
+    fn resize(&mut self, len: usize) {
+        let old = match self {
+            Storage::Inline(_, n) if Self::fits(len) => core::mem::replace(n, len),
+            Storage::Inline(..) => {
+                let mut data = self.as_slice().to_vec();
+                data.resize(len, T::zero());
+                *self = Storage::Heap(data);
+                return;
+            }
+            Storage::Heap(data) => return data.resize(len, T::zero()),
+            Storage::Aligned32(data, n) => {
+                data.resize(blocks::<T>(len, 32), BLOCK32);
+                core::mem::replace(n, len)
+            }
+            Storage::Aligned64(data, n) => {
+                data.resize(blocks::<T>(len, 64), BLOCK64);
+                core::mem::replace(n, len)
+            }
+        };
+
+        // Values past the old length may be left over from an earlier, larger image or not be
+        // initialized yet
+        if len > old {
+            self.zero_from(old);
+        }
+    }
+}
+
+impl<T: Type> PartialEq for Storage<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+#[cfg(feature = "ser")]
+impl<T: Type + serde::Serialize> serde::Serialize for Storage<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.as_slice().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "ser")]
+impl<'de, T: Type + serde::Deserialize<'de>> serde::Deserialize<'de> for Storage<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::deserialize(deserializer).map(Storage::Heap)
+    }
+}
+
+/// Image implementation that stores small images inline, see `INLINE_BYTES`, and larger images
+/// using `Vec<T>`. Images created using `ImageBuf::new_aligned` are always stored on the heap
+/// with the requested alignment
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone)]
 pub struct ImageBuf<T: Type, C: Color> {
     width: usize,
     height: usize,
-    data: Vec<T>,
+    data: Storage<T>,
+    #[cfg_attr(feature = "ser", serde(default))]
+    resolution: Option<Resolution>,
+    #[cfg_attr(feature = "ser", serde(default))]
+    profile: Option<Vec<u8>>,
     _color: PhantomData<C>,
 }
 
+/// Images are compared by their shape and data, the resolution and profile are ignored
+impl<T: Type, C: Color> PartialEq for ImageBuf<T, C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.width == other.width && self.height == other.height && self.data == other.data
+    }
+}
+
+/// Shows the shape, type, color and the minimum, maximum and mean of each channel instead of the
+/// data, use `data` or `Image::preview_ascii` to inspect the pixels
+impl<T: Type, C: Color> core::fmt::Debug for ImageBuf<T, C> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut s = f.debug_struct("ImageBuf");
+        s.field("width", &self.width)
+            .field("height", &self.height)
+            .field("type", &format_args!("{}", core::any::type_name::<T>()))
+            .field("color", &format_args!("{}", C::name()));
+        if !self.data().is_empty() {
+            let summary = crate::stats::summary(self);
+            s.field("min", &summary.min())
+                .field("max", &summary.max())
+                .field("mean", &summary.mean());
+        }
+        if let Some(resolution) = &self.resolution {
+            s.field("resolution", resolution);
+        }
+        if let Some(profile) = &self.profile {
+            s.field("profile", &format_args!("{} bytes", profile.len()));
+        }
+        s.finish()
+    }
+}
+
 impl<T: Type, C: Color> Image<T, C> for ImageBuf<T, C> {
     fn shape(&self) -> (usize, usize, usize) {
         (self.width, self.height, C::channels())
     }
 
     fn data(&self) -> &[T] {
-        self.data.as_ref()
+        self.data.as_slice()
     }
 
     fn data_mut(&mut self) -> &mut [T] {
-        self.data.as_mut()
+        self.data.as_mut_slice()
+    }
+
+    fn resolution(&self) -> Option<Resolution> {
+        self.resolution
+    }
+
+    fn profile(&self) -> Option<&[u8]> {
+        self.profile.as_deref()
     }
 }
 
@@ -34,41 +346,635 @@ impl<T: Type, C: Color> ImageBuf<T, C> {
         ImageBuf {
             width,
             height,
-            data: vec![T::zero(); width * height * C::channels()],
+            data: Storage::new(width * height * C::channels()),
+            resolution: None,
+            profile: None,
             _color: PhantomData,
         }
     }
 
+    /// Create a new ImageBuf with every pixel set to `px`
+    ///
+    /// ```rust
+    /// use image2::{Image, ImageBuf, Rgb};
+    ///
+    /// let image: ImageBuf<u8, Rgb> = ImageBuf::new_with(4, 3, [255, 0, 0]);
+    /// assert_eq!(image.at(3, 2), &[255, 0, 0]);
+    /// ```
+    ///
+    /// Panics if `px` doesn't have exactly one value per channel
+    pub fn new_with<P: AsRef<[T]>>(width: usize, height: usize, px: P) -> Self {
+        let px = px.as_ref();
+        check_pixel::<T, C>(px);
+        let mut image = Self::new(width, height);
+        for dest in image.data_mut().chunks_exact_mut(C::channels()) {
+            dest.copy_from_slice(px);
+        }
+        image
+    }
+
+    /// Create a new ImageBuf by calling `f` with the coordinates of each pixel, in row-major order
+    ///
+    /// ```rust
+    /// use image2::{Gray, Image, ImageBuf};
+    ///
+    /// let image: ImageBuf<u16, Gray> = ImageBuf::from_fn(3, 2, |x, y| [(y * 3 + x) as u16]);
+    /// assert_eq!(image.data(), &[0, 1, 2, 3, 4, 5]);
+    /// ```
+    ///
+    /// Panics if a returned pixel doesn't have exactly one value per channel
+    pub fn from_fn<P: AsRef<[T]>, F: FnMut(usize, usize) -> P>(
+        width: usize,
+        height: usize,
+        mut f: F,
+    ) -> Self {
+        let mut image = Self::new(width, height);
+        for (i, dest) in image.data_mut().chunks_exact_mut(C::channels()).enumerate() {
+            let px = f(i % width, i / width);
+            check_pixel::<T, C>(px.as_ref());
+            dest.copy_from_slice(px.as_ref());
+        }
+        image
+    }
+
+    /// Create a new ImageBuf from existing data, returns `Error::InvalidImageShape` when the
+    /// length of `data` isn't `width * height * channels`
+    pub fn from_vec_checked(width: usize, height: usize, data: Vec<T>) -> Result<Self, Error> {
+        if data.len() != width * height * C::channels() {
+            return Err(Error::InvalidImageShape);
+        }
+        Ok(Self::new_from(width, height, data))
+    }
+
     /// Convert the ImageBuf back to the underlying data buffer
     pub fn inner(self) -> Vec<T> {
-        self.data
+        self.data.into_vec()
+    }
+
+    /// Create a new ImageBuf with the given size, the data starts at a multiple of `alignment`
+    /// and is followed by at least `alignment` bytes of padding, see `padded_data`
+    ///
+    /// Note: Rows are not padded individually since every `Image` method expects them to be
+    /// `width * channels` values apart, instead any vector load starting inside of a row can
+    /// safely read past its end. Use `PaddedImage` for kernels that need every row to be aligned
+    ///
+    /// Panics for zero sized types and types aligned to more than `alignment`
+    pub fn new_aligned(width: usize, height: usize, alignment: Alignment) -> Self {
+        ImageBuf {
+            width,
+            height,
+            data: Storage::aligned(width * height * C::channels(), alignment),
+            resolution: None,
+            profile: None,
+            _color: PhantomData,
+        }
+    }
+
+    /// Returns true when the data is stored inline instead of on the heap
+    pub fn is_inline(&self) -> bool {
+        matches!(self.data, Storage::Inline(..))
+    }
+
+    /// Returns the alignment of images created using `new_aligned`
+    pub fn alignment(&self) -> Option<Alignment> {
+        self.data.alignment()
+    }
+
+    /// Get the image data followed by the padding of aligned images, the padding is zero when
+    /// the image is created but may be overwritten by vectorized kernels, so its contents should
+    /// be ignored. For other images this is the same as `data`
+    pub fn padded_data(&self) -> &[T] {
+        self.data.as_padded_slice()
     }
 
-    /// Create a new image with the same type, shape and color as an existing image
+    /// Create a new image with the same type, shape, color, alignment, resolution and profile as
+    /// an existing image
     pub fn new_like(&self) -> Self {
-        Self::new(self.width, self.height)
+        let mut image = match self.alignment() {
+            Some(alignment) => Self::new_aligned(self.width, self.height, alignment),
+            None => Self::new(self.width, self.height),
+        };
+        image.copy_metadata(self);
+        image
     }
 
-    /// Create a new image with the given type and the same shape and color
+    /// Create a new image with the given type and the same shape, color, resolution and profile
     pub fn new_like_with_type<U: Type>(&self) -> ImageBuf<U, C> {
-        ImageBuf::new(self.width, self.height)
+        let mut image = ImageBuf::new(self.width, self.height);
+        image.copy_metadata(self);
+        image
     }
 
-    /// Create a new image with the given color and the same shape and type
+    /// Create a new image with the given color and the same shape, type and resolution. The
+    /// profile only describes the original color, so it is not copied
     pub fn new_like_with_color<D: Color>(&self) -> ImageBuf<T, D> {
-        ImageBuf::new(self.width, self.height)
+        let mut image = ImageBuf::new(self.width, self.height);
+        image.resolution = self.resolution;
+        image
+    }
+
+    /// Copy the resolution and ICC profile of another image, used to keep the metadata of a file
+    /// when creating new images from it
+    pub fn copy_metadata<U: Type, I: Image<U, C>>(&mut self, other: &I) {
+        self.resolution = other.resolution();
+        self.profile = other.profile().map(<[u8]>::to_vec);
+    }
+
+    /// Set the embedded ICC profile, it is written to PNG, JPEG and TIFF files by `io::write`
+    pub fn set_profile(&mut self, profile: Option<Vec<u8>>) {
+        self.profile = profile;
+    }
+
+    /// Remove the embedded ICC profile so it isn't written when the image is saved
+    pub fn strip_profile(&mut self) {
+        self.profile = None;
+    }
+
+    /// Set the physical resolution, it is written to PNG, JPEG and TIFF files by `io::write`
+    pub fn set_resolution(&mut self, resolution: Option<Resolution>) {
+        self.resolution = resolution;
+    }
+
+    /// Physical size of the image in inches, `None` when the resolution is unknown
+    pub fn size_in_inches(&self) -> Option<(f64, f64)> {
+        let (x, y) = self.resolution?.to_dpi()?;
+        Some((self.width as f64 / x, self.height as f64 / y))
+    }
+
+    /// Physical size of the image in millimeters, `None` when the resolution is unknown
+    pub fn size_in_mm(&self) -> Option<(f64, f64)> {
+        let (width, height) = self.size_in_inches()?;
+        Some((width * 25.4, height * 25.4))
+    }
+
+    /// Change the size of the image, reusing the existing allocation when it is large enough
+    ///
+    /// Note: The contents of the image are not cleared, callers are expected to overwrite every
+    /// pixel
+    pub fn reshape(&mut self, width: usize, height: usize) {
+        if self.width == width && self.height == height {
+            return;
+        }
+
+        self.width = width;
+        self.height = height;
+        self.data.resize(width * height * C::channels());
+    }
+
+    /// Rotate and flip the image so it is upright, `orientation` is the value of the EXIF
+    /// orientation tag from 1 to 8. Other values leave the image unchanged
+    pub fn apply_orientation(&mut self, orientation: u16) {
+        if !(2..=8).contains(&orientation) {
+            return;
+        }
+
+        let (w, h) = (self.width, self.height);
+        let (width, height) = if orientation >= 5 { (h, w) } else { (w, h) };
+        let mut dest = match self.alignment() {
+            Some(alignment) => Self::new_aligned(width, height, alignment),
+            None => Self::new(width, height),
+        };
+
+        let channels = C::channels();
+        let src = self.data();
+        for (i, px) in dest.data_mut().chunks_exact_mut(channels).enumerate() {
+            let (x, y) = (i % width, i / width);
+            let (x, y) = match orientation {
+                2 => (w - 1 - x, y),
+                3 => (w - 1 - x, h - 1 - y),
+                4 => (x, h - 1 - y),
+                5 => (y, x),
+                6 => (y, h - 1 - x),
+                7 => (w - 1 - y, h - 1 - x),
+                _ => (w - 1 - y, x),
+            };
+            let index = (y * w + x) * channels;
+            px.copy_from_slice(&src[index..index + channels]);
+        }
+        dest.resolution = match self.resolution {
+            Some(r) if orientation >= 5 => Some(Resolution::new(r.y, r.x, r.unit)),
+            r => r,
+        };
+        dest.profile = self.profile.take();
+        *self = dest;
     }
 
     /// Create a new image from existing data
     ///
     /// Note: This function does not do bounds checking, so you need to ensure that `data` is the
-    /// correct length to handle the specified width and height
+    /// correct length to handle the specified width and height. Prefer `from_vec_checked` unless
+    /// the length is already known to be correct
     pub fn new_from(width: usize, height: usize, data: Vec<T>) -> Self {
         ImageBuf {
             width,
             height,
-            data,
+            data: Storage::Heap(data),
+            resolution: None,
+            profile: None,
             _color: PhantomData,
         }
     }
 }
+
+/// Image data with every row starting at a multiple of the alignment and padded to `stride`
+/// values, for vectorized kernels and GPU uploads that need aligned rows which can be read past
+/// their end. `Image` methods expect rows without padding, so images are converted using
+/// `from_image` and `copy_to`
+#[derive(Clone)]
+pub struct PaddedImage<T: Type, C: Color> {
+    width: usize,
+    height: usize,
+    stride: usize,
+    alignment: Alignment,
+    data: Storage<T>,
+    _color: PhantomData<C>,
+}
+
+impl<T: Type, C: Color> PaddedImage<T, C> {
+    /// Create a new image with every value set to zero, panics when the size of `T` is not a
+    /// divisor of `alignment` since rows couldn't all be aligned
+    pub fn new(width: usize, height: usize, alignment: Alignment) -> Self {
+        let size = core::mem::size_of::<T>();
+        assert!(
+            size > 0 && alignment.bytes().is_multiple_of(size),
+            "PaddedImage requires a type whose size divides {} bytes",
+            alignment.bytes()
+        );
+        let row = width * C::channels() * size;
+        let stride = row.div_ceil(alignment.bytes()) * alignment.bytes() / size;
+        PaddedImage {
+            width,
+            height,
+            stride,
+            alignment,
+            data: Storage::aligned(stride * height, alignment),
+            _color: PhantomData,
+        }
+    }
+
+    /// Copy an image into new padded storage
+    pub fn from_image<I: Image<T, C>>(image: &I, alignment: Alignment) -> Self {
+        let mut dest = Self::new(image.width(), image.height(), alignment);
+        let row = image.width() * C::channels();
+        if row > 0 {
+            for (d, src) in dest
+                .data
+                .as_mut_slice()
+                .chunks_exact_mut(dest.stride)
+                .zip(image.data().chunks_exact(row))
+            {
+                d[..row].copy_from_slice(src);
+            }
+        }
+        dest
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Number of values from the start of one row to the start of the next
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    pub fn alignment(&self) -> Alignment {
+        self.alignment
+    }
+
+    /// Row `y` followed by its padding, the padding should be ignored since kernels may write to
+    /// it
+    pub fn row(&self, y: usize) -> &[T] {
+        &self.data.as_slice()[y * self.stride..(y + 1) * self.stride]
+    }
+
+    /// Mutable row `y` followed by its padding
+    pub fn row_mut(&mut self, y: usize) -> &mut [T] {
+        let stride = self.stride;
+        &mut self.data.as_mut_slice()[y * stride..(y + 1) * stride]
+    }
+
+    /// Every row including padding, followed by at least one alignment of padding so a vector
+    /// load starting in the last row can read past its end
+    pub fn padded_data(&self) -> &[T] {
+        self.data.as_padded_slice()
+    }
+
+    /// Copy the image into `dest` without the padding, reshaping it if needed
+    pub fn copy_to(&self, dest: &mut ImageBuf<T, C>) {
+        dest.reshape(self.width, self.height);
+        let row = self.width * C::channels();
+        if row == 0 {
+            return;
+        }
+        for (d, src) in dest
+            .data_mut()
+            .chunks_exact_mut(row)
+            .zip(self.data.as_slice().chunks_exact(self.stride))
+        {
+            d.copy_from_slice(&src[..row]);
+        }
+    }
+
+    /// Copy the image into a new `ImageBuf` without the padding
+    pub fn to_image(&self) -> ImageBuf<T, C> {
+        let mut image = ImageBuf::new(self.width, self.height);
+        self.copy_to(&mut image);
+        image
+    }
+}
+
+fn check_pixel<T, C: Color>(px: &[T]) {
+    assert!(
+        px.len() == C::channels(),
+        "expected a {} pixel with {} channels, got {} values",
+        C::name(),
+        C::channels(),
+        px.len()
+    );
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::{Gray, Rgb};
+
+    /// Define a `Type` with the given alignment that stores zero with an offset, so its bits are
+    /// not all zero
+    macro_rules! shifted_type {
+        ($name:ident, $align:literal) => {
+            #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+            #[repr(align($align))]
+            pub(crate) struct $name(u8);
+
+            impl $name {
+                pub(crate) fn value(self) -> u8 {
+                    self.0.wrapping_sub(1)
+                }
+
+                pub(crate) fn new(x: u8) -> $name {
+                    $name(x.wrapping_add(1))
+                }
+            }
+
+            shifted_type!($name, Add, add, wrapping_add);
+            shifted_type!($name, Sub, sub, wrapping_sub);
+            shifted_type!($name, Mul, mul, wrapping_mul);
+            shifted_type!($name, Div, div, wrapping_div);
+            shifted_type!($name, Rem, rem, wrapping_rem);
+
+            impl core::iter::Sum for $name {
+                fn sum<I: Iterator<Item = $name>>(iter: I) -> $name {
+                    iter.fold($name::new(0), |a, b| a + b)
+                }
+            }
+
+            impl num::Zero for $name {
+                fn zero() -> $name {
+                    $name::new(0)
+                }
+
+                fn is_zero(&self) -> bool {
+                    self.value() == 0
+                }
+            }
+
+            impl num::ToPrimitive for $name {
+                fn to_i64(&self) -> Option<i64> {
+                    Some(self.value().into())
+                }
+
+                fn to_u64(&self) -> Option<u64> {
+                    Some(self.value().into())
+                }
+            }
+
+            impl num::FromPrimitive for $name {
+                fn from_i64(x: i64) -> Option<$name> {
+                    u8::from_i64(x).map($name::new)
+                }
+
+                fn from_u64(x: u64) -> Option<$name> {
+                    u8::from_u64(x).map($name::new)
+                }
+            }
+
+            impl Type for $name {
+                fn min_f() -> f64 {
+                    0.0
+                }
+
+                fn max_f() -> f64 {
+                    255.0
+                }
+            }
+        };
+
+        ($name:ident, $op:ident, $f:ident, $g:ident) => {
+            impl core::ops::$op for $name {
+                type Output = $name;
+                fn $f(self, other: $name) -> $name {
+                    $name::new(self.value().$g(other.value()))
+                }
+            }
+        };
+    }
+
+    shifted_type!(Shifted, 1);
+    shifted_type!(Shifted16, 16);
+    shifted_type!(Shifted64, 64);
+
+    #[test]
+    fn test_image_buf_storage() {
+        let mut small: ImageBuf<f32, Gray> = ImageBuf::new(8, 8);
+        assert!(small.is_inline());
+        small.set(7, 7, 0, 1.0);
+        assert_eq!(small.get(7, 7, 0), 1.0);
+        assert_eq!(Clone::clone(&small), small);
+        assert_eq!(Clone::clone(&small).inner().len(), 64);
+
+        // Growing keeps existing values and zeroes the new ones
+        small.reshape(8, 8);
+        small.reshape(4, 4);
+        small.reshape(8, 8);
+        assert!(small.is_inline());
+        assert_eq!(small.data()[15], 0.0);
+        small.data_mut()[63] = 2.0;
+        small.reshape(9, 9);
+        assert!(!small.is_inline());
+        assert_eq!(small.data()[63], 2.0);
+        assert_eq!(small.data()[80], 0.0);
+
+        // Heap storage is reused when shrinking
+        let mut large: ImageBuf<u8, Rgb> = ImageBuf::new(100, 100);
+        assert!(!large.is_inline());
+        large.reshape(2, 2);
+        assert!(!large.is_inline());
+        assert_eq!(large.data().len(), 12);
+        assert_eq!(large, ImageBuf::new(2, 2));
+        assert!(!ImageBuf::<u8, Rgb>::new_from(1, 1, vec![1, 2, 3]).is_inline());
+    }
+
+    #[test]
+    fn test_image_buf_user_type() {
+        // New values are zero even when zero is not stored as zero bits
+        let mut image: ImageBuf<Shifted, Gray> = ImageBuf::new(4, 4);
+        assert!(image.is_inline());
+        assert!(image.data().iter().all(|x| x.value() == 0));
+        image.data_mut()[15] = Shifted::new(7);
+        image.reshape(2, 2);
+        image.reshape(4, 4);
+        assert!(image.data().iter().all(|x| x.value() == 0));
+
+        // Types aligned to more than 8 bytes are never stored inline
+        let mut image: ImageBuf<Shifted16, Gray> = ImageBuf::new(2, 2);
+        assert!(!image.is_inline());
+        assert_eq!(image.data().as_ptr() as usize % 16, 0);
+        assert!(image.data().iter().all(|x| x.value() == 0));
+        image.reshape(3, 3);
+        assert!(image.data().iter().all(|x| x.value() == 0));
+        assert!(!Storage::<u8>::fits(usize::MAX));
+
+        // Aligned storage initializes the padding too
+        let mut image: ImageBuf<Shifted16, Gray> = ImageBuf::new_aligned(3, 1, Alignment::Bytes32);
+        assert!(image.padded_data().len() > 3);
+        assert!(image.padded_data().iter().all(|x| x.value() == 0));
+        image.reshape(5, 5);
+        assert!(image.padded_data().iter().all(|x| x.value() == 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "alignment of at most 32 bytes")]
+    fn test_image_buf_aligned_over_aligned() {
+        let _: ImageBuf<Shifted64, Gray> = ImageBuf::new_aligned(1, 1, Alignment::Bytes32);
+    }
+
+    #[test]
+    fn test_image_buf_constructors() {
+        let image: ImageBuf<u8, Rgb> = ImageBuf::new_with(5, 5, [1, 2, 3]);
+        assert!(image.is_inline());
+        assert!(image.data().chunks(3).all(|px| px == [1, 2, 3]));
+
+        let image: ImageBuf<f32, Gray> = ImageBuf::from_fn(20, 10, |x, y| [x as f32 - y as f32]);
+        assert!(!image.is_inline());
+        assert_eq!(image.get(19, 0, 0), 19.0);
+        assert_eq!(image.get(0, 9, 0), -9.0);
+
+        let image = ImageBuf::<u8, Rgb>::from_vec_checked(2, 1, vec![1, 2, 3, 4, 5, 6]).unwrap();
+        assert_eq!(image.at(1, 0), &[4, 5, 6]);
+        assert!(matches!(
+            ImageBuf::<u8, Rgb>::from_vec_checked(2, 2, vec![0; 6]),
+            Err(Error::InvalidImageShape)
+        ));
+    }
+
+    #[test]
+    fn test_image_buf_aligned() {
+        for alignment in [Alignment::Bytes32, Alignment::Bytes64] {
+            let mut image: ImageBuf<f32, Rgb> = ImageBuf::new_aligned(5, 3, alignment);
+            assert_eq!(image.alignment(), Some(alignment));
+            assert_eq!(image.data().as_ptr() as usize % alignment.bytes(), 0);
+            assert_eq!(image.data().len(), 45);
+            assert!((image.padded_data().len() - 45) * 4 >= alignment.bytes());
+            assert!(!image.is_inline());
+
+            image.set(4, 2, 2, 1.0);
+            assert_eq!(image.new_like().alignment(), Some(alignment));
+            assert_eq!(Clone::clone(&image), image);
+
+            image.reshape(2, 2);
+            image.reshape(100, 100);
+            assert_eq!(image.alignment(), Some(alignment));
+            assert_eq!(image.data().as_ptr() as usize % alignment.bytes(), 0);
+            assert!(image.data().iter().all(|x| *x == 0.0));
+            assert!((image.padded_data().len() - 30000) * 4 >= alignment.bytes());
+        }
+
+        assert_eq!(ImageBuf::<u8, Rgb>::new(2, 2).alignment(), None);
+        assert_eq!(ImageBuf::<u8, Rgb>::new(2, 2).padded_data().len(), 12);
+    }
+
+    #[test]
+    fn test_padded_image() {
+        let image: ImageBuf<u16, Rgb> =
+            ImageBuf::from_fn(7, 3, |x, y| [x as u16, y as u16, (x * y) as u16]);
+        for alignment in [Alignment::Bytes32, Alignment::Bytes64] {
+            let mut padded = PaddedImage::from_image(&image, alignment);
+            assert_eq!(padded.alignment(), alignment);
+
+            // The 42 bytes of each row are padded to 64
+            assert_eq!(padded.stride() * 2, 64);
+            for y in 0..3 {
+                assert_eq!(padded.row(y).as_ptr() as usize % alignment.bytes(), 0);
+                assert_eq!(&padded.row(y)[..21], &image.data()[y * 21..(y + 1) * 21]);
+            }
+            assert!(padded.padded_data().len() >= padded.stride() * 3 + alignment.bytes() / 2);
+
+            // Padding is dropped when converting back
+            padded.row_mut(1)[21] = 1000;
+            assert_eq!(padded.to_image(), image);
+            let mut dest = ImageBuf::new(1, 1);
+            padded.copy_to(&mut dest);
+            assert_eq!(dest, image);
+        }
+    }
+
+    #[test]
+    fn test_apply_orientation() {
+        // 1 2 3
+        // 4 5 6
+        let image: ImageBuf<u8, Gray> = ImageBuf::new_from(3, 2, vec![1, 2, 3, 4, 5, 6]);
+        let expected: [(u16, usize, &[u8]); 8] = [
+            (1, 3, &[1, 2, 3, 4, 5, 6]),
+            (2, 3, &[3, 2, 1, 6, 5, 4]),
+            (3, 3, &[6, 5, 4, 3, 2, 1]),
+            (4, 3, &[4, 5, 6, 1, 2, 3]),
+            (5, 2, &[1, 4, 2, 5, 3, 6]),
+            (6, 2, &[4, 1, 5, 2, 6, 3]),
+            (7, 2, &[6, 3, 5, 2, 4, 1]),
+            (8, 2, &[3, 6, 2, 5, 1, 4]),
+        ];
+        for (orientation, width, data) in expected.iter() {
+            let mut oriented = Clone::clone(&image);
+            oriented.apply_orientation(*orientation);
+            assert_eq!(oriented.width(), *width);
+            assert_eq!(oriented.data(), *data, "orientation {}", orientation);
+        }
+
+        // The resolution is rotated with the image
+        let mut image = image;
+        image.set_resolution(Some(Resolution::new(300.0, 150.0, ResolutionUnit::Inch)));
+        image.apply_orientation(6);
+        assert_eq!(image.resolution().unwrap().x, 150.0);
+        assert_eq!(image.size_in_inches(), Some((2.0 / 150.0, 3.0 / 300.0)));
+    }
+
+    #[test]
+    fn test_image_buf_debug() {
+        let mut image: ImageBuf<u8, Rgb> = ImageBuf::new(400, 300);
+        image.set(10, 10, 0, 200);
+        let s = format!("{:?}", image);
+        assert!(s.starts_with("ImageBuf { width: 400, height: 300, type: u8, color: rgb"));
+        assert!(s.contains("max: [200.0, 0.0, 0.0]"), "{}", s);
+        assert!(s.len() < 200);
+
+        let empty: ImageBuf<f32, Gray> = ImageBuf::new(0, 0);
+        assert_eq!(
+            format!("{:?}", empty),
+            "ImageBuf { width: 0, height: 0, type: f32, color: gray }"
+        );
+    }
+
+    #[test]
+    fn test_preview_ascii() {
+        // Black on the left, white on the right
+        let mut image: ImageBuf<u8, Gray> = ImageBuf::new(8, 4);
+        image.for_each(|(x, _), px| px[0] = if x < 4 { 0 } else { 255 });
+        assert_eq!(image.preview_ascii(8), "    @@@@\n    @@@@\n");
+        assert_eq!(image.preview_ascii(0), "");
+    }
+}
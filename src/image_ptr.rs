@@ -1,4 +1,4 @@
-use std::marker::PhantomData;
+use core::marker::PhantomData;
 
 use crate::{Color, Image, Type};
 
@@ -27,16 +27,32 @@ impl<'a, T: Type, C: Color> Image<T, C> for ImagePtr<'a, T, C> {
     }
 }
 
+#[cfg(all(
+    feature = "std",
+    not(all(target_arch = "wasm32", target_os = "unknown"))
+))]
 extern "C" {
-    pub(crate) fn free(ptr: *mut std::ffi::c_void);
+    pub(crate) fn free(ptr: *mut core::ffi::c_void);
 }
 
+#[cfg(all(
+    feature = "std",
+    not(all(target_arch = "wasm32", target_os = "unknown"))
+))]
 fn default_free<T>(ptr: *mut T, _: usize) {
     unsafe {
-        free(ptr as *mut std::ffi::c_void);
+        free(ptr as *mut core::ffi::c_void);
     }
 }
 
+// There is no C allocator on wasm32-unknown-unknown or without std, so nothing could have been
+// allocated using malloc
+#[cfg(any(
+    not(feature = "std"),
+    all(target_arch = "wasm32", target_os = "unknown")
+))]
+fn default_free<T>(_: *mut T, _: usize) {}
+
 fn ignore_free<T>(_: *mut T, _: usize) {}
 
 /// Determines how to free a pointer stored in an ImagePtr
@@ -53,7 +69,7 @@ impl<'a, T: 'a + Type, C: Color> ImagePtr<'a, T, C> {
     /// Create a new ImagePtr with the given `free` function used when the image is dropped, if
     /// no free function is provided then `free` from the C stdlib will be used
     pub fn new(width: usize, height: usize, data: *mut T, free: Free<T>) -> Self {
-        let data = unsafe { std::slice::from_raw_parts_mut(data, width * height * C::channels()) };
+        let data = unsafe { core::slice::from_raw_parts_mut(data, width * height * C::channels()) };
 
         let free = match free {
             Free::Default => default_free,
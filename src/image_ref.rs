@@ -2,7 +2,7 @@ use crate::color::Color;
 use crate::image::Image;
 use crate::ty::Type;
 
-use std::marker::PhantomData;
+use core::marker::PhantomData;
 
 /// Image implementation backed by a mutable array reference
 #[derive(Debug, PartialEq)]
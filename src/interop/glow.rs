@@ -0,0 +1,225 @@
+//! OpenGL textures and framebuffers using `glow`
+//!
+//! The first row of an image is the top row, while OpenGL stores the bottom row first. Passing
+//! `flip = true` reverses the order of the rows when data is copied, so images appear upright
+//! when sampled with texture coordinates that start in the bottom-left corner.
+//!
+//! All functions require the given context to be current on the calling thread.
+
+use ::glow::HasContext;
+
+use crate::color::Color;
+use crate::error::Error;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::ty::Type;
+
+/// OpenGL internal format, format and type matching an image
+struct GlFormat {
+    internal_format: u32,
+    format: u32,
+    ty: u32,
+    integer: bool,
+}
+
+fn gl_format<T: Type, C: Color>() -> Result<GlFormat, Error> {
+    let integer = !T::is_float() && std::mem::size_of::<T>() == 4;
+    let format = match (C::name(), integer) {
+        ("gray", false) => ::glow::RED,
+        ("rgb", false) => ::glow::RGB,
+        ("bgr", false) => ::glow::BGR,
+        ("rgba", false) => ::glow::RGBA,
+        ("bgra", false) => ::glow::BGRA,
+        ("gray", true) => ::glow::RED_INTEGER,
+        ("rgb", true) => ::glow::RGB_INTEGER,
+        ("bgr", true) => ::glow::BGR_INTEGER,
+        ("rgba", true) => ::glow::RGBA_INTEGER,
+        ("bgra", true) => ::glow::BGRA_INTEGER,
+        _ => return Err(Error::InvalidColor),
+    };
+
+    let index = match C::channels() {
+        1 => 0,
+        3 => 1,
+        _ => 2,
+    };
+    let size = std::mem::size_of::<T>();
+    let (ty, internal_formats) = match (size, T::is_float(), T::is_signed()) {
+        (1, false, false) => (
+            ::glow::UNSIGNED_BYTE,
+            [::glow::R8, ::glow::RGB8, ::glow::RGBA8],
+        ),
+        (2, false, false) => (
+            ::glow::UNSIGNED_SHORT,
+            [::glow::R16, ::glow::RGB16, ::glow::RGBA16],
+        ),
+        (4, false, false) => (
+            ::glow::UNSIGNED_INT,
+            [::glow::R32UI, ::glow::RGB32UI, ::glow::RGBA32UI],
+        ),
+        (4, false, true) => (::glow::INT, [::glow::R32I, ::glow::RGB32I, ::glow::RGBA32I]),
+        (4, true, _) => (
+            ::glow::FLOAT,
+            [::glow::R32F, ::glow::RGB32F, ::glow::RGBA32F],
+        ),
+        _ => return Err(Error::InvalidType),
+    };
+
+    Ok(GlFormat {
+        internal_format: internal_formats[index],
+        format,
+        ty,
+        integer,
+    })
+}
+
+/// The raw bytes of an image, with the rows reversed when `flip` is set
+fn bytes<T: Type, C: Color>(image: &ImageBuf<T, C>, flip: bool) -> Vec<u8> {
+    let data = image.data();
+    let data = unsafe {
+        std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data))
+    };
+
+    if !flip {
+        return data.to_vec();
+    }
+
+    let row = data.len() / image.height().max(1);
+    data.chunks(row.max(1)).rev().flatten().copied().collect()
+}
+
+impl<T: Type, C: Color> ImageBuf<T, C> {
+    /// Upload the image to a new `TEXTURE_2D` using linear filtering, the texture is left bound
+    ///
+    /// # Safety
+    /// `gl` must be the current context
+    pub unsafe fn to_gl_texture<G: HasContext>(
+        &self,
+        gl: &G,
+        flip: bool,
+    ) -> Result<G::Texture, Error> {
+        let format = gl_format::<T, C>()?;
+        let texture = gl.create_texture().map_err(Error::Message)?;
+        gl.bind_texture(::glow::TEXTURE_2D, Some(texture));
+
+        let filter = if format.integer {
+            // Integer textures are incomplete when sampled with linear filtering
+            ::glow::NEAREST
+        } else {
+            ::glow::LINEAR
+        };
+        gl.tex_parameter_i32(
+            ::glow::TEXTURE_2D,
+            ::glow::TEXTURE_MIN_FILTER,
+            filter as i32,
+        );
+        gl.tex_parameter_i32(
+            ::glow::TEXTURE_2D,
+            ::glow::TEXTURE_MAG_FILTER,
+            filter as i32,
+        );
+        gl.tex_parameter_i32(
+            ::glow::TEXTURE_2D,
+            ::glow::TEXTURE_WRAP_S,
+            ::glow::CLAMP_TO_EDGE as i32,
+        );
+        gl.tex_parameter_i32(
+            ::glow::TEXTURE_2D,
+            ::glow::TEXTURE_WRAP_T,
+            ::glow::CLAMP_TO_EDGE as i32,
+        );
+
+        gl.pixel_store_i32(::glow::UNPACK_ALIGNMENT, 1);
+        gl.tex_image_2d(
+            ::glow::TEXTURE_2D,
+            0,
+            format.internal_format as i32,
+            self.width() as i32,
+            self.height() as i32,
+            0,
+            format.format,
+            format.ty,
+            Some(&bytes(self, flip)),
+        );
+        Ok(texture)
+    }
+
+    /// Replace the contents of an existing `TEXTURE_2D` with the same size as the image
+    ///
+    /// # Safety
+    /// `gl` must be the current context and `texture` must belong to it
+    pub unsafe fn update_gl_texture<G: HasContext>(
+        &self,
+        gl: &G,
+        texture: G::Texture,
+        flip: bool,
+    ) -> Result<(), Error> {
+        let format = gl_format::<T, C>()?;
+        gl.bind_texture(::glow::TEXTURE_2D, Some(texture));
+        gl.pixel_store_i32(::glow::UNPACK_ALIGNMENT, 1);
+        gl.tex_sub_image_2d(
+            ::glow::TEXTURE_2D,
+            0,
+            0,
+            0,
+            self.width() as i32,
+            self.height() as i32,
+            format.format,
+            format.ty,
+            ::glow::PixelUnpackData::Slice(&bytes(self, flip)),
+        );
+        Ok(())
+    }
+
+    /// Read a region of the bound read framebuffer, `x` and `y` are the bottom-left corner of
+    /// the region in framebuffer coordinates. Pass `flip = true` to get an upright image.
+    ///
+    /// # Safety
+    /// `gl` must be the current context
+    pub unsafe fn from_gl_framebuffer<G: HasContext>(
+        gl: &G,
+        x: i32,
+        y: i32,
+        width: usize,
+        height: usize,
+        flip: bool,
+    ) -> Result<ImageBuf<T, C>, Error> {
+        let format = gl_format::<T, C>()?;
+        let mut image = ImageBuf::new(width, height);
+        {
+            let data = image.data_mut();
+            let data = std::slice::from_raw_parts_mut(
+                data.as_mut_ptr() as *mut u8,
+                std::mem::size_of_val(data),
+            );
+            gl.pixel_store_i32(::glow::PACK_ALIGNMENT, 1);
+            gl.read_pixels(
+                x,
+                y,
+                width as i32,
+                height as i32,
+                format.format,
+                format.ty,
+                ::glow::PixelPackData::Slice(data),
+            );
+        }
+
+        let error = gl.get_error();
+        if error != ::glow::NO_ERROR {
+            return Err(Error::Message(format!(
+                "Unable to read framebuffer: GL error 0x{:x}",
+                error
+            )));
+        }
+
+        if flip {
+            let row = width * C::channels();
+            let data = image.data_mut();
+            for y in 0..height / 2 {
+                let (top, bottom) = data.split_at_mut((height - y - 1) * row);
+                top[y * row..(y + 1) * row].swap_with_slice(&mut bottom[..row]);
+            }
+        }
+        Ok(image)
+    }
+}
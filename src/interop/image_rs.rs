@@ -0,0 +1,118 @@
+//! `ImageBuffer` conversions move the underlying data without copying, `DynamicImage`
+//! conversions copy and convert between types and colors as needed
+
+use std::convert::TryFrom;
+
+use ::image_rs::{DynamicImage, ImageBuffer, Luma, Rgb as RgbPixel, Rgba as RgbaPixel};
+
+use crate::color::{Color, Gray, Rgb, Rgba};
+use crate::error::Error;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::ty::Type;
+
+macro_rules! image_buffer {
+    ($t:ty, $color:ty, $pixel:ident) => {
+        impl From<ImageBuf<$t, $color>> for ImageBuffer<$pixel<$t>, Vec<$t>> {
+            fn from(image: ImageBuf<$t, $color>) -> Self {
+                let (width, height, _) = image.shape();
+                ImageBuffer::from_raw(width as u32, height as u32, image.inner())
+                    .expect("Image data does not match its shape")
+            }
+        }
+
+        impl From<ImageBuffer<$pixel<$t>, Vec<$t>>> for ImageBuf<$t, $color> {
+            fn from(image: ImageBuffer<$pixel<$t>, Vec<$t>>) -> Self {
+                let (width, height) = image.dimensions();
+                ImageBuf::new_from(width as usize, height as usize, image.into_raw())
+            }
+        }
+    };
+}
+
+image_buffer!(u8, Gray, Luma);
+image_buffer!(u16, Gray, Luma);
+image_buffer!(f32, Gray, Luma);
+image_buffer!(u8, Rgb, RgbPixel);
+image_buffer!(u16, Rgb, RgbPixel);
+image_buffer!(f32, Rgb, RgbPixel);
+image_buffer!(u8, Rgba, RgbaPixel);
+image_buffer!(u16, Rgba, RgbaPixel);
+image_buffer!(f32, Rgba, RgbaPixel);
+
+fn convert<T: Type, U: Type>(data: &[T]) -> Vec<U> {
+    data.iter().map(|x| x.convert()).collect()
+}
+
+impl<'a, T: Type, C: Color> TryFrom<&'a DynamicImage> for ImageBuf<T, C> {
+    type Error = Error;
+
+    /// Converts to the color of the output image, keeping the bit depth of the input. Only
+    /// `Gray`, `Rgb` and `Rgba` images are supported.
+    fn try_from(image: &'a DynamicImage) -> Result<ImageBuf<T, C>, Error> {
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let color = image.color();
+        let depth = color.bytes_per_pixel() / color.channel_count();
+        let data = match (C::name(), depth) {
+            ("gray", 1) => convert(image.to_luma8().as_raw()),
+            ("gray", 2) => convert(image.to_luma16().as_raw()),
+            ("gray", _) => convert(image.to_luma32f().as_raw()),
+            ("rgb", 1) => convert(image.to_rgb8().as_raw()),
+            ("rgb", 2) => convert(image.to_rgb16().as_raw()),
+            ("rgb", _) => convert(image.to_rgb32f().as_raw()),
+            ("rgba", 1) => convert(image.to_rgba8().as_raw()),
+            ("rgba", 2) => convert(image.to_rgba16().as_raw()),
+            ("rgba", _) => convert(image.to_rgba32f().as_raw()),
+            _ => return Err(Error::InvalidColor),
+        };
+        ImageBuf::from_vec_checked(width, height, data)
+    }
+}
+
+impl<T: Type, C: Color> TryFrom<DynamicImage> for ImageBuf<T, C> {
+    type Error = Error;
+
+    fn try_from(image: DynamicImage) -> Result<ImageBuf<T, C>, Error> {
+        ImageBuf::try_from(&image)
+    }
+}
+
+impl<'a, T: Type, C: Color> TryFrom<&'a ImageBuf<T, C>> for DynamicImage {
+    type Error = Error;
+
+    /// Float images are stored as 32-bit floats, or 16-bit integers for grayscale images, and
+    /// integer images larger than 8 bits are stored as 16-bit integers. Only `Gray`, `Rgb` and
+    /// `Rgba` images are supported.
+    fn try_from(image: &'a ImageBuf<T, C>) -> Result<DynamicImage, Error> {
+        let (width, height) = (image.width() as u32, image.height() as u32);
+        let data = image.data();
+        let depth = if T::is_float() {
+            4
+        } else if T::max_f() <= 255.0 {
+            1
+        } else {
+            2
+        };
+
+        let image = match (C::name(), depth) {
+            ("gray", 1) => ImageBuffer::from_raw(width, height, convert::<T, u8>(data))
+                .map(DynamicImage::ImageLuma8),
+            ("gray", _) => ImageBuffer::from_raw(width, height, convert::<T, u16>(data))
+                .map(DynamicImage::ImageLuma16),
+            ("rgb", 1) => ImageBuffer::from_raw(width, height, convert::<T, u8>(data))
+                .map(DynamicImage::ImageRgb8),
+            ("rgb", 2) => ImageBuffer::from_raw(width, height, convert::<T, u16>(data))
+                .map(DynamicImage::ImageRgb16),
+            ("rgb", _) => ImageBuffer::from_raw(width, height, convert::<T, f32>(data))
+                .map(DynamicImage::ImageRgb32F),
+            ("rgba", 1) => ImageBuffer::from_raw(width, height, convert::<T, u8>(data))
+                .map(DynamicImage::ImageRgba8),
+            ("rgba", 2) => ImageBuffer::from_raw(width, height, convert::<T, u16>(data))
+                .map(DynamicImage::ImageRgba16),
+            ("rgba", _) => ImageBuffer::from_raw(width, height, convert::<T, f32>(data))
+                .map(DynamicImage::ImageRgba32F),
+            _ => return Err(Error::InvalidColor),
+        };
+        image.ok_or_else(|| Error::Message(String::from("Image data does not match its shape")))
+    }
+}
@@ -0,0 +1,78 @@
+//! Conversions between images and types from other crates, each integration is enabled using a
+//! crate feature of the same name
+
+#[cfg(feature = "glow")]
+mod glow;
+#[cfg(feature = "image_rs")]
+mod image_rs;
+#[cfg(feature = "ndarray")]
+mod ndarray;
+#[cfg(feature = "opencv")]
+mod opencv;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+#[cfg(test)]
+mod test {
+    #[cfg(feature = "image_rs")]
+    #[test]
+    fn test_image_rs() {
+        use crate::{Gray, Image, ImageBuf, Rgb, Rgba};
+        use image_rs::{DynamicImage, RgbImage};
+        use std::convert::TryFrom;
+
+        let mut image: ImageBuf<u8, Rgb> = ImageBuf::new(4, 3);
+        image.at_mut(2, 1).copy_from_slice(&[7, 128, 255]);
+
+        let buffer = RgbImage::from(Image::clone(&image));
+        assert_eq!(buffer.get_pixel(2, 1).0, [7, 128, 255]);
+        assert_eq!(ImageBuf::from(buffer.clone()), image);
+
+        let dynamic = DynamicImage::ImageRgb8(buffer);
+        let rgba: ImageBuf<u8, Rgba> = ImageBuf::try_from(&dynamic).unwrap();
+        assert_eq!(rgba.at(2, 1), &[7, 128, 255, 255]);
+        let gray: ImageBuf<f32, Gray> = ImageBuf::try_from(&dynamic).unwrap();
+        assert!(gray.at(2, 1)[0] > 0.0);
+        assert!(ImageBuf::<u8, crate::color::Cmyk>::try_from(&dynamic).is_err());
+
+        let back = DynamicImage::try_from(&image).unwrap();
+        assert_eq!(back, dynamic);
+        let float: ImageBuf<f32, Rgb> = ImageBuf::try_from(&dynamic).unwrap();
+        assert!(matches!(
+            DynamicImage::try_from(&float).unwrap(),
+            DynamicImage::ImageRgb32F(_)
+        ));
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn test_ndarray() {
+        use crate::{Gray, Image, ImageBuf, ImageRef, Rgb};
+        use ndarray::{s, Array3};
+        use std::convert::TryFrom;
+
+        let mut image: ImageBuf<u8, Rgb> = ImageBuf::new(4, 3);
+        image.at_mut(2, 1)[1] = 7;
+        assert_eq!(image.as_array().shape(), &[3, 4, 3]);
+        assert_eq!(image.as_array()[[1, 2, 1]], 7);
+        image.as_array_mut()[[2, 3, 0]] = 9;
+        assert_eq!(image.at(3, 2)[0], 9);
+
+        let array = Array3::from(Image::clone(&image));
+        let back = ImageBuf::<u8, Rgb>::try_from(array.clone()).unwrap();
+        assert_eq!(back, image);
+        assert!(ImageBuf::<u8, Gray>::try_from(array.clone()).is_err());
+
+        // Non-contiguous arrays are copied
+        let view = array.slice(s![.., 1..3, ..]);
+        let cropped = ImageBuf::<u8, Rgb>::try_from(view).unwrap();
+        assert_eq!(cropped.width(), 2);
+        assert_eq!(cropped.at(1, 1)[1], 7);
+
+        let mut array = array;
+        let mut view = ImageRef::<u8, Rgb>::try_from(array.view_mut()).unwrap();
+        view.at_mut(0, 0)[2] = 3;
+        assert_eq!(array[[0, 0, 2]], 3);
+        assert!(ImageRef::<u8, Rgb>::try_from(array.slice_mut(s![.., 1..3, ..])).is_err());
+    }
+}
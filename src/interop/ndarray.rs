@@ -0,0 +1,93 @@
+//! Images are represented as arrays with the shape `(height, width, channels)`, which matches
+//! the memory layout of `ImageBuf`, so conversions of standard layout arrays do not copy
+
+use std::convert::TryFrom;
+
+use ::ndarray::{Array3, ArrayView3, ArrayViewMut3};
+
+use crate::color::Color;
+use crate::error::Error;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::image_ref::ImageRef;
+use crate::ty::Type;
+
+/// Returns the width and height of an image with the given array shape
+fn check_shape<C: Color>(shape: &[usize]) -> Result<(usize, usize), Error> {
+    if shape[2] != C::channels() {
+        return Err(Error::InvalidColor);
+    }
+
+    Ok((shape[1], shape[0]))
+}
+
+impl<T: Type, C: Color> ImageBuf<T, C> {
+    /// A view of the image data with the shape `(height, width, channels)`
+    pub fn as_array(&self) -> ArrayView3<'_, T> {
+        let (width, height, channels) = self.shape();
+        ArrayView3::from_shape((height, width, channels), self.data())
+            .expect("Image data does not match its shape")
+    }
+
+    /// A mutable view of the image data with the shape `(height, width, channels)`
+    pub fn as_array_mut(&mut self) -> ArrayViewMut3<'_, T> {
+        let (width, height, channels) = self.shape();
+        ArrayViewMut3::from_shape((height, width, channels), self.data_mut())
+            .expect("Image data does not match its shape")
+    }
+}
+
+impl<T: Type, C: Color> From<ImageBuf<T, C>> for Array3<T> {
+    fn from(image: ImageBuf<T, C>) -> Array3<T> {
+        let (width, height, channels) = image.shape();
+        Array3::from_shape_vec((height, width, channels), image.inner())
+            .expect("Image data does not match its shape")
+    }
+}
+
+impl<T: Type, C: Color> TryFrom<Array3<T>> for ImageBuf<T, C> {
+    type Error = Error;
+
+    fn try_from(array: Array3<T>) -> Result<ImageBuf<T, C>, Error> {
+        let (width, height) = check_shape::<C>(array.shape())?;
+        let data = if array.is_standard_layout() {
+            match array.into_raw_vec_and_offset() {
+                (data, Some(0)) | (data, None) => data,
+                (data, Some(offset)) => {
+                    data[offset..offset + width * height * C::channels()].to_vec()
+                }
+            }
+        } else {
+            array.iter().cloned().collect()
+        };
+        ImageBuf::from_vec_checked(width, height, data)
+    }
+}
+
+impl<'a, T: Type, C: Color> TryFrom<ArrayView3<'a, T>> for ImageBuf<T, C> {
+    type Error = Error;
+
+    fn try_from(array: ArrayView3<'a, T>) -> Result<ImageBuf<T, C>, Error> {
+        let (width, height) = check_shape::<C>(array.shape())?;
+        Ok(ImageBuf::new_from(
+            width,
+            height,
+            array.iter().cloned().collect(),
+        ))
+    }
+}
+
+impl<'a, T: Type, C: Color> TryFrom<ArrayViewMut3<'a, T>> for ImageRef<'a, T, C> {
+    type Error = Error;
+
+    /// Borrows the array data, which must be in standard layout
+    fn try_from(array: ArrayViewMut3<'a, T>) -> Result<ImageRef<'a, T, C>, Error> {
+        let (width, height) = check_shape::<C>(array.shape())?;
+        match array.into_slice() {
+            Some(data) => Ok(ImageRef::new(width, height, data)),
+            None => Err(Error::Message(String::from(
+                "Array is not in standard layout",
+            ))),
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! OpenCV stores color images as BGR, so copying conversions swap the red and blue channels of
+//! `Rgb` and `Rgba` images. Borrowed views never reorder channels and should be used with
+//! `Gray`, `Bgr` and `Bgra` images.
+
+use std::convert::TryFrom;
+use std::ffi::c_void;
+
+use ::opencv::boxed_ref::{BoxedRef, BoxedRefMut};
+use ::opencv::core::{DataType, Mat, Scalar, CV_MAKETYPE};
+use ::opencv::prelude::*;
+
+use crate::color::Color;
+use crate::error::Error;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::image_ref::ImageRef;
+use crate::ty::Type;
+
+/// OpenCV type of an image with the given color and element type
+fn mat_type<T: DataType, C: Color>() -> i32 {
+    CV_MAKETYPE(T::opencv_depth(), C::channels() as i32)
+}
+
+/// Returns true when the red and blue channels are swapped between the image and a `Mat`
+fn swap_channels<C: Color>() -> bool {
+    C::name() == "rgb" || C::name() == "rgba"
+}
+
+fn check_mat<T: DataType, C: Color>(mat: &impl MatTraitConst) -> Result<(), Error> {
+    if mat.dims() != 2 {
+        return Err(Error::Message(format!(
+            "Expected a two dimensional Mat, got {} dimensions",
+            mat.dims()
+        )));
+    }
+
+    if mat.channels() != C::channels() as i32 {
+        return Err(Error::InvalidColor);
+    }
+
+    if mat.depth() != T::opencv_depth() {
+        return Err(Error::InvalidType);
+    }
+
+    Ok(())
+}
+
+impl<T: Type + DataType, C: Color> ImageBuf<T, C> {
+    /// Borrow the image as a `Mat` without copying, channels are not reordered
+    pub fn as_mat(&self) -> Result<BoxedRef<'_, Mat>, Error> {
+        let (width, height, _) = self.shape();
+        let mat = unsafe {
+            Mat::new_rows_cols_with_data_unsafe_def(
+                height as i32,
+                width as i32,
+                mat_type::<T, C>(),
+                self.data().as_ptr() as *mut c_void,
+            )?
+        };
+        Ok(BoxedRef::from(mat))
+    }
+
+    /// Mutably borrow the image as a `Mat` without copying, channels are not reordered
+    pub fn as_mat_mut(&mut self) -> Result<BoxedRefMut<'_, Mat>, Error> {
+        let (width, height, _) = self.shape();
+        let mat = unsafe {
+            Mat::new_rows_cols_with_data_unsafe_def(
+                height as i32,
+                width as i32,
+                mat_type::<T, C>(),
+                self.data_mut().as_mut_ptr() as *mut c_void,
+            )?
+        };
+        Ok(BoxedRefMut::from(mat))
+    }
+}
+
+impl<'a, T: Type + DataType, C: Color> TryFrom<&'a mut Mat> for ImageRef<'a, T, C> {
+    type Error = Error;
+
+    /// Borrow the data of a continuous `Mat` without copying, channels are not reordered
+    fn try_from(mat: &'a mut Mat) -> Result<ImageRef<'a, T, C>, Error> {
+        check_mat::<T, C>(mat)?;
+        if !mat.is_continuous() {
+            return Err(Error::Message(String::from("Mat is not continuous")));
+        }
+
+        let (width, height) = (mat.cols() as usize, mat.rows() as usize);
+        let len = width * height * C::channels();
+        let data = unsafe { std::slice::from_raw_parts_mut(mat.data_mut() as *mut T, len) };
+        Ok(ImageRef::new(width, height, data))
+    }
+}
+
+impl<'a, T: Type + DataType, C: Color> TryFrom<&'a ImageBuf<T, C>> for Mat {
+    type Error = Error;
+
+    /// Copy an image into a new `Mat`, `Rgb` and `Rgba` images are converted to BGR order
+    fn try_from(image: &'a ImageBuf<T, C>) -> Result<Mat, Error> {
+        let (width, height, channels) = image.shape();
+        let mut mat = Mat::new_rows_cols_with_default(
+            height as i32,
+            width as i32,
+            mat_type::<T, C>(),
+            Scalar::all(0.0),
+        )?;
+
+        let data = unsafe {
+            std::slice::from_raw_parts_mut(mat.data_mut() as *mut T, width * height * channels)
+        };
+        data.copy_from_slice(image.data());
+        if swap_channels::<C>() {
+            data.chunks_mut(channels).for_each(|px| px.swap(0, 2));
+        }
+        Ok(mat)
+    }
+}
+
+impl<'a, T: Type + DataType, C: Color> TryFrom<&'a Mat> for ImageBuf<T, C> {
+    type Error = Error;
+
+    /// Copy a `Mat` into a new image, converting from BGR order for `Rgb` and `Rgba` images.
+    /// The `Mat` does not need to be continuous.
+    fn try_from(mat: &'a Mat) -> Result<ImageBuf<T, C>, Error> {
+        check_mat::<T, C>(mat)?;
+
+        let (width, height) = (mat.cols() as usize, mat.rows() as usize);
+        let channels = C::channels();
+        let mut image = ImageBuf::new(width, height);
+        for (y, row) in image.data_mut().chunks_mut(width * channels).enumerate() {
+            let src = unsafe {
+                std::slice::from_raw_parts(mat.ptr(y as i32)? as *const T, width * channels)
+            };
+            row.copy_from_slice(src);
+        }
+
+        if swap_channels::<C>() {
+            image
+                .data_mut()
+                .chunks_mut(channels)
+                .for_each(|px| px.swap(0, 2));
+        }
+        Ok(image)
+    }
+}
@@ -0,0 +1,65 @@
+//! Browser interop using `wasm-bindgen`
+//!
+//! Data is copied between JavaScript and WebAssembly memory. Typed arrays can be converted to
+//! images using `ImageBuf::from_vec_checked(width, height, array.to_vec())`.
+
+use std::convert::TryFrom;
+
+use ::js_sys::{Float32Array, Float64Array, Uint16Array, Uint8Array, Uint8ClampedArray};
+use ::wasm_bindgen::Clamped;
+use ::web_sys::ImageData;
+
+use crate::color::{Color, Rgba};
+use crate::error::Error;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::ty::Type;
+
+macro_rules! typed_array {
+    ($t:ty, $array:ty) => {
+        impl<'a, C: Color> From<&'a ImageBuf<$t, C>> for $array {
+            fn from(image: &'a ImageBuf<$t, C>) -> $array {
+                <$array>::from(image.data())
+            }
+        }
+    };
+}
+
+typed_array!(u8, Uint8Array);
+typed_array!(u8, Uint8ClampedArray);
+typed_array!(u16, Uint16Array);
+typed_array!(f32, Float32Array);
+typed_array!(f64, Float64Array);
+
+impl<'a> From<&'a ImageData> for ImageBuf<u8, Rgba> {
+    fn from(data: &'a ImageData) -> ImageBuf<u8, Rgba> {
+        ImageBuf::new_from(data.width() as usize, data.height() as usize, data.data().0)
+    }
+}
+
+impl<'a, T: Type, C: Color> TryFrom<&'a ImageBuf<T, C>> for ImageData {
+    type Error = Error;
+
+    /// Converts to 8-bit RGBA, grayscale values are copied to each color channel and images
+    /// without an alpha channel are opaque
+    fn try_from(image: &'a ImageBuf<T, C>) -> Result<ImageData, Error> {
+        let (width, height, channels) = image.shape();
+        let mut data = Vec::with_capacity(width * height * 4);
+        for px in image.data().chunks(channels) {
+            let px: Vec<u8> = px.iter().map(|x| x.convert()).collect();
+            if channels < 3 {
+                data.extend_from_slice(&[px[0], px[0], px[0]]);
+            } else {
+                data.extend_from_slice(&px[..3]);
+            }
+            data.push(if C::has_alpha() {
+                px[channels - 1]
+            } else {
+                255
+            });
+        }
+
+        ImageData::new_with_u8_clamped_array_and_sh(Clamped(&data), width as u32, height as u32)
+            .map_err(|err| Error::Message(format!("Unable to create ImageData: {:?}", err)))
+    }
+}
@@ -0,0 +1,468 @@
+//! Read and write images without knowing their format or pixel type in advance
+//!
+//! `open` detects the format of a file from its first bytes and returns an `AnyImage` using the
+//! color and sample type stored in the file, `save` picks the encoder from the extension of the
+//! output path:
+//!
+//! ```rust,no_run
+//! use image2::{AnyImage, Gray, ImageBuf};
+//!
+//! let image = image2::open("input.png")?;
+//! println!("{}x{} {:?}", image.width(), image.height(), image);
+//! if let AnyImage::Gray16(image) = &image {
+//!     image2::io::write("output.tiff", image)?;
+//! }
+//!
+//! let gray: ImageBuf<f32, Gray> = image.to_image();
+//! image2::save("output.jpg", &AnyImage::from(gray))?;
+//! # Ok::<(), image2::Error>(())
+//! ```
+
+use std::path::Path;
+use std::time::Duration;
+
+use crate::color::{Color, Gray, Rgb, Rgba};
+use crate::error::{with_path, Error};
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::metadata::icc;
+use crate::ty::Type;
+
+use super::{gif, magick, npy, stb, webp};
+
+/// File formats recognized by `guess_format`, `open` and `save`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Png,
+    Jpeg,
+    Gif,
+    Bmp,
+    Tga,
+    Psd,
+    Pnm,
+    Hdr,
+    Tiff,
+    WebP,
+    Exr,
+    Qoi,
+    Npy,
+}
+
+impl Format {
+    /// Get the format used for a file extension, ignoring case
+    pub fn from_extension(ext: &str) -> Option<Format> {
+        match ext.to_ascii_lowercase().as_str() {
+            "png" => Some(Format::Png),
+            "jpg" | "jpeg" => Some(Format::Jpeg),
+            "gif" => Some(Format::Gif),
+            "bmp" => Some(Format::Bmp),
+            "tga" => Some(Format::Tga),
+            "psd" => Some(Format::Psd),
+            "pbm" | "pgm" | "ppm" | "pnm" | "pam" => Some(Format::Pnm),
+            "hdr" => Some(Format::Hdr),
+            "tif" | "tiff" => Some(Format::Tiff),
+            "webp" => Some(Format::WebP),
+            "exr" => Some(Format::Exr),
+            "qoi" => Some(Format::Qoi),
+            "npy" => Some(Format::Npy),
+            _ => None,
+        }
+    }
+
+    /// Get the format used for a path from its extension
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Format> {
+        path.as_ref()
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(Format::from_extension)
+    }
+}
+
+/// Guess the format of an encoded image from its signature, without relying on a file name.
+/// TGA files have no signature and are never detected
+///
+/// ```rust
+/// use image2::io::{guess_format, Format};
+///
+/// assert_eq!(guess_format(b"\x89PNG\r\n\x1a\n"), Some(Format::Png));
+/// assert_eq!(guess_format(b"qoif"), Some(Format::Qoi));
+/// assert_eq!(guess_format(b"<html>"), None);
+/// ```
+pub fn guess_format(data: &[u8]) -> Option<Format> {
+    match data {
+        [0x89, b'P', b'N', b'G', ..] => Some(Format::Png),
+        [0xff, 0xd8, 0xff, ..] => Some(Format::Jpeg),
+        [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Some(Format::Gif),
+        [b'B', b'M', ..] => Some(Format::Bmp),
+        [b'8', b'B', b'P', b'S', ..] => Some(Format::Psd),
+        [b'P', b'1'..=b'7', ..] => Some(Format::Pnm),
+        [b'#', b'?', ..] => Some(Format::Hdr),
+        [b'I', b'I', 42, 0, ..] | [b'M', b'M', 0, 42, ..] => Some(Format::Tiff),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some(Format::WebP),
+        [0x76, 0x2f, 0x31, 0x01, ..] => Some(Format::Exr),
+        [b'q', b'o', b'i', b'f', ..] => Some(Format::Qoi),
+        [0x93, b'N', b'U', b'M', b'P', b'Y', ..] => Some(Format::Npy),
+        _ => None,
+    }
+}
+
+/// An image using the color and sample type stored in a file, returned by `open`. Images with two
+/// channels are loaded as `Rgba`, with the gray value copied to the color channels
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnyImage {
+    Gray8(ImageBuf<u8, Gray>),
+    Rgb8(ImageBuf<u8, Rgb>),
+    Rgba8(ImageBuf<u8, Rgba>),
+    Gray16(ImageBuf<u16, Gray>),
+    Rgb16(ImageBuf<u16, Rgb>),
+    Rgba16(ImageBuf<u16, Rgba>),
+    GrayF32(ImageBuf<f32, Gray>),
+    RgbF32(ImageBuf<f32, Rgb>),
+    RgbaF32(ImageBuf<f32, Rgba>),
+}
+
+macro_rules! with_image {
+    ($any:expr, $image:ident => $e:expr) => {
+        match $any {
+            AnyImage::Gray8($image) => $e,
+            AnyImage::Rgb8($image) => $e,
+            AnyImage::Rgba8($image) => $e,
+            AnyImage::Gray16($image) => $e,
+            AnyImage::Rgb16($image) => $e,
+            AnyImage::Rgba16($image) => $e,
+            AnyImage::GrayF32($image) => $e,
+            AnyImage::RgbF32($image) => $e,
+            AnyImage::RgbaF32($image) => $e,
+        }
+    };
+}
+
+macro_rules! any_image_from {
+    ($($variant:ident: $t:ty, $c:ty),*) => {
+        $(
+            impl From<ImageBuf<$t, $c>> for AnyImage {
+                fn from(image: ImageBuf<$t, $c>) -> AnyImage {
+                    AnyImage::$variant(image)
+                }
+            }
+        )*
+    };
+}
+
+any_image_from!(
+    Gray8: u8, Gray,
+    Rgb8: u8, Rgb,
+    Rgba8: u8, Rgba,
+    Gray16: u16, Gray,
+    Rgb16: u16, Rgb,
+    Rgba16: u16, Rgba,
+    GrayF32: f32, Gray,
+    RgbF32: f32, Rgb,
+    RgbaF32: f32, Rgba
+);
+
+/// Sample type stored in a file
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Sample {
+    U8,
+    U16,
+    F32,
+}
+
+impl Sample {
+    fn from_depth(depth: usize) -> Sample {
+        match depth {
+            0..=8 => Sample::U8,
+            9..=16 => Sample::U16,
+            _ => Sample::F32,
+        }
+    }
+}
+
+/// Convert the type and color of `src`, color channels are matched by position and gray images
+/// are created using the luma of the source
+fn convert<T: Type, C: Color, U: Type, D: Color>(src: &ImageBuf<T, C>) -> ImageBuf<U, D> {
+    let mut dest: ImageBuf<U, D> = ImageBuf::new(src.width(), src.height());
+    dest.set_resolution(src.resolution());
+    let profile = src.profile().filter(|p| icc::is_compatible::<D>(p));
+    dest.set_profile(profile.map(<[u8]>::to_vec));
+
+    let src_colors = C::channels() - C::has_alpha() as usize;
+    let dest_colors = D::channels() - D::has_alpha() as usize;
+    let src_px = src.data().chunks_exact(C::channels());
+    let dest_px = dest.data_mut().chunks_exact_mut(D::channels());
+    for (px, out) in src_px.zip(dest_px) {
+        if dest_colors == 1 && src_colors >= 3 {
+            let v = px[0].to_f() * 0.21 + px[1].to_f() * 0.72 + px[2].to_f() * 0.07;
+            out[0] = U::from_f(v);
+        } else {
+            for (i, x) in out[..dest_colors].iter_mut().enumerate() {
+                *x = U::from_f(px[i.min(src_colors - 1)].to_f());
+            }
+        }
+
+        if D::has_alpha() {
+            out[dest_colors] = if C::has_alpha() {
+                U::from_f(px[src_colors].to_f())
+            } else {
+                U::max()
+            };
+        }
+    }
+    dest
+}
+
+impl AnyImage {
+    /// Create an empty image with the given number of channels and sample type
+    fn empty(channels: usize, sample: Sample) -> AnyImage {
+        match (channels, sample) {
+            (1, Sample::U8) => AnyImage::Gray8(ImageBuf::new(0, 0)),
+            (3, Sample::U8) => AnyImage::Rgb8(ImageBuf::new(0, 0)),
+            (_, Sample::U8) => AnyImage::Rgba8(ImageBuf::new(0, 0)),
+            (1, Sample::U16) => AnyImage::Gray16(ImageBuf::new(0, 0)),
+            (3, Sample::U16) => AnyImage::Rgb16(ImageBuf::new(0, 0)),
+            (_, Sample::U16) => AnyImage::Rgba16(ImageBuf::new(0, 0)),
+            (1, Sample::F32) => AnyImage::GrayF32(ImageBuf::new(0, 0)),
+            (3, Sample::F32) => AnyImage::RgbF32(ImageBuf::new(0, 0)),
+            (_, Sample::F32) => AnyImage::RgbaF32(ImageBuf::new(0, 0)),
+        }
+    }
+
+    /// Decode an image from memory, only the first frame of animations is used
+    pub fn decode<Data: AsRef<[u8]>>(data: Data) -> Result<AnyImage, Error> {
+        let data = data.as_ref();
+        let mut image = match guess_format(data) {
+            Some(Format::Npy) => {
+                let (channels, descr) = npy::layout(data)?;
+                let sample = match &descr[1..] {
+                    "u1" | "b1" => Sample::U8,
+                    "u2" => Sample::U16,
+                    _ => Sample::F32,
+                };
+                if channels == 2 {
+                    return Err(Error::InvalidColor);
+                }
+                let mut image = AnyImage::empty(channels, sample);
+                with_image!(&mut image, image => npy::decode_into(data, image)?);
+                return Ok(image);
+            }
+            Some(Format::WebP) => match webp::decode(data)?.into_iter().next() {
+                Some((frame, _)) => AnyImage::Rgba8(frame),
+                None => return Err(Error::InvalidImageData),
+            },
+            Some(Format::Tiff) => {
+                return Err(Error::UnsupportedFormat(String::from(
+                    "TIFF images can only be read from disk",
+                )))
+            }
+            _ => {
+                let (mut width, mut height, mut channels) = (0, 0, 0);
+                let len = data.len() as i32;
+                let ok = unsafe {
+                    stb::stbi_info_from_memory(
+                        data.as_ptr(),
+                        len,
+                        &mut width,
+                        &mut height,
+                        &mut channels,
+                    )
+                };
+                if ok == 0 {
+                    return Err(Error::InvalidImageData);
+                }
+
+                let sample = if unsafe { stb::stbi_is_hdr_from_memory(data.as_ptr(), len) } != 0 {
+                    Sample::F32
+                } else if unsafe { stb::stbi_is_16_bit_from_memory(data.as_ptr(), len) } != 0 {
+                    Sample::U16
+                } else {
+                    Sample::U8
+                };
+
+                let mut image = AnyImage::empty(channels as usize, sample);
+                match &mut image {
+                    AnyImage::Gray8(image) => decode_stb(super::decode_u8(data)?, image),
+                    AnyImage::Rgb8(image) => decode_stb(super::decode_u8(data)?, image),
+                    AnyImage::Rgba8(image) => decode_stb(super::decode_u8(data)?, image),
+                    AnyImage::Gray16(image) => decode_stb(super::decode_u16(data)?, image),
+                    AnyImage::Rgb16(image) => decode_stb(super::decode_u16(data)?, image),
+                    AnyImage::Rgba16(image) => decode_stb(super::decode_u16(data)?, image),
+                    AnyImage::GrayF32(image) => decode_stb(super::decode_f32(data)?, image),
+                    AnyImage::RgbF32(image) => decode_stb(super::decode_f32(data)?, image),
+                    AnyImage::RgbaF32(image) => decode_stb(super::decode_f32(data)?, image),
+                }
+                image
+            }
+        };
+
+        with_image!(&mut image, image => super::load_metadata(data, image));
+        Ok(image)
+    }
+
+    pub fn width(&self) -> usize {
+        with_image!(self, image => image.width())
+    }
+
+    pub fn height(&self) -> usize {
+        with_image!(self, image => image.height())
+    }
+
+    /// Number of channels, including alpha
+    pub fn channels(&self) -> usize {
+        with_image!(self, image => image.channels())
+    }
+
+    /// Name of the color type, like `rgb`
+    pub fn color(&self) -> &'static str {
+        match self {
+            AnyImage::Gray8(_) | AnyImage::Gray16(_) | AnyImage::GrayF32(_) => Gray::name(),
+            AnyImage::Rgb8(_) | AnyImage::Rgb16(_) | AnyImage::RgbF32(_) => Rgb::name(),
+            AnyImage::Rgba8(_) | AnyImage::Rgba16(_) | AnyImage::RgbaF32(_) => Rgba::name(),
+        }
+    }
+
+    /// Convert to an image with the given type and color, values are normalized between types.
+    /// Color images are converted to gray using their luma, gray images are copied to every
+    /// color channel and the alpha channel is opaque when the image doesn't have one
+    pub fn to_image<T: Type, C: Color>(&self) -> ImageBuf<T, C> {
+        with_image!(self, image => convert(image))
+    }
+
+    /// Write the image to disk, see `save`
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        save(path, self)
+    }
+}
+
+fn decode_stb<T: Type, C: Color, I: Image<T, C>>(src: I, image: &mut ImageBuf<T, C>) {
+    image.reshape(src.width(), src.height());
+    image.data_mut().copy_from_slice(src.data());
+}
+
+/// Read an image from disk, detecting the format from the contents of the file. The color and
+/// sample type of the file are kept, 16-bit PNG files are returned as `Gray16`, `Rgb16` or
+/// `Rgba16` and HDR files as `RgbF32`. Formats not supported by stb_image are read using
+/// ImageMagick
+pub fn open<P: AsRef<Path>>(path: P) -> Result<AnyImage, Error> {
+    with_path(path, |path| {
+        let data = std::fs::read(path)?;
+        match AnyImage::decode(&data) {
+            Ok(image) => Ok(image),
+            Err(Error::InvalidImageData) | Err(Error::UnsupportedFormat(_)) => {
+                let (channels, depth) = magick::default().get_image_layout(path)?;
+                let mut image = AnyImage::empty(channels, Sample::from_depth(depth));
+                with_image!(&mut image, image => {
+                    magick::read_into(path, image)?;
+                    super::load_metadata(&data, image);
+                });
+                Ok(image)
+            }
+            Err(err) => Err(err),
+        }
+    })
+}
+
+/// Write an image to disk, the format is determined by the extension of `path`. GIF and WebP
+/// files are written as a single frame, other formats are written using `io::write`
+pub fn save<P: AsRef<Path>>(path: P, image: &AnyImage) -> Result<(), Error> {
+    match Format::from_path(&path) {
+        Some(Format::Gif) => with_image!(image, image => {
+            let mut encoder = gif::Encoder::create(path, image.width(), image.height())?;
+            encoder.add_frame(image, Duration::ZERO)?;
+            encoder.finish()?;
+            Ok(())
+        }),
+        Some(Format::WebP) => with_image!(image, image => {
+            let mut encoder = webp::Encoder::create(path, image.width(), image.height())?;
+            encoder.add_frame(image, Duration::ZERO)?;
+            encoder.finish()?;
+            Ok(())
+        }),
+        Some(_) => with_image!(image, image => super::write(path, image)),
+        None => {
+            Err(Error::Message(String::from("Unable to determine output format")).with_path(path))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format_detect() {
+        let jpeg = std::fs::read("test/test.jpg").unwrap();
+        assert_eq!(guess_format(&jpeg), Some(Format::Jpeg));
+        assert_eq!(guess_format(b"GIF89a"), Some(Format::Gif));
+        assert_eq!(guess_format(b"GIF8"), None);
+        assert_eq!(guess_format(b"RIFF\0\0\0\0WEBPVP8L"), Some(Format::WebP));
+        assert_eq!(guess_format(b"RIFF\0\0\0\0WAVEfmt "), None);
+        assert_eq!(guess_format(b"P6\n"), Some(Format::Pnm));
+        assert_eq!(guess_format(b"II*\0"), Some(Format::Tiff));
+        assert_eq!(guess_format(b"v/1\x01\x02\0\0\0"), Some(Format::Exr));
+        assert_eq!(guess_format(b"#?RADIANCE\n"), Some(Format::Hdr));
+        assert_eq!(
+            guess_format(&npy::encode(&ImageBuf::<u8, Gray>::new(1, 1))),
+            Some(Format::Npy)
+        );
+        assert_eq!(guess_format(b""), None);
+        assert_eq!(Format::from_path("a/b.JPEG"), Some(Format::Jpeg));
+        assert_eq!(Format::from_path("a/b.tif"), Some(Format::Tiff));
+        assert_eq!(Format::from_path("a/b"), None);
+    }
+
+    #[test]
+    fn test_open_save() {
+        let image = open("test/test.jpg").unwrap();
+        assert_eq!(image.color(), "rgb");
+        let rgb = match &image {
+            AnyImage::Rgb8(rgb) => rgb,
+            _ => panic!("expected an 8-bit RGB image"),
+        };
+
+        let gray: ImageBuf<u8, Gray> = image.to_image();
+        assert_eq!(gray.shape(), (rgb.width(), rgb.height(), 1));
+        save(
+            "test/test-any-gray.png",
+            &AnyImage::from(Clone::clone(&gray)),
+        )
+        .unwrap();
+        assert_eq!(
+            open("test/test-any-gray.png").unwrap(),
+            AnyImage::Gray8(gray)
+        );
+
+        let hdr: ImageBuf<f32, Rgb> = image.to_image();
+        save("test/test-any.hdr", &AnyImage::from(hdr)).unwrap();
+        assert!(matches!(
+            open("test/test-any.hdr").unwrap(),
+            AnyImage::RgbF32(_)
+        ));
+
+        let npy: ImageBuf<u16, Rgba> = image.to_image();
+        save("test/test-any.npy", &AnyImage::from(Clone::clone(&npy))).unwrap();
+        assert_eq!(open("test/test-any.npy").unwrap(), AnyImage::Rgba16(npy));
+
+        save("test/test-any.gif", &image).unwrap();
+        let gif = open("test/test-any.gif").unwrap();
+        assert_eq!(
+            (gif.width(), gif.height(), gif.channels()),
+            (rgb.width(), rgb.height(), 4)
+        );
+
+        assert!(save("test/test-any", &image).unwrap_err().path().is_some());
+    }
+
+    #[test]
+    fn test_to_image() {
+        let mut gray: ImageBuf<u16, Gray> = ImageBuf::new(2, 1);
+        gray.data_mut().copy_from_slice(&[0, u16::MAX]);
+        let rgba: ImageBuf<u8, Rgba> = AnyImage::from(gray).to_image();
+        assert_eq!(rgba.data(), &[0, 0, 0, 255, 255, 255, 255, 255]);
+
+        let mut rgb: ImageBuf<f32, Rgb> = ImageBuf::new(1, 1);
+        rgb.data_mut().copy_from_slice(&[1.0, 1.0, 0.0]);
+        let gray: ImageBuf<u8, Gray> = AnyImage::from(rgb).to_image();
+        assert_eq!(gray.data(), &[237]);
+    }
+}
@@ -0,0 +1,832 @@
+//! Encode and decode animated GIF files
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use image2::io::gif::{Encoder, PaletteMode, Repeat};
+//! use image2::{ImageBuf, Rgb};
+//!
+//! let mut encoder = Encoder::create("animation.gif", 320, 240)?
+//!     .palette(PaletteMode::PerFrame)
+//!     .repeat(Repeat::Infinite);
+//! for _ in 0..10 {
+//!     let frame: ImageBuf<u8, Rgb> = ImageBuf::new(320, 240);
+//!     encoder.add_frame(&frame, Duration::from_millis(100))?;
+//! }
+//! encoder.finish()?;
+//! # Ok::<(), image2::Error>(())
+//! ```
+//!
+//! GIF images are limited to 255 colors plus transparency. Frames with more colors are
+//! quantized using median cut and, unless disabled, Floyd-Steinberg dithering. Only the part of
+//! each frame that changed since the previous frame is stored, unchanged pixels are written as
+//! transparent so they compress well.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::color::{Color, Rgba};
+use crate::error::{with_path, Error};
+use crate::image::Image;
+use crate::ty::Type;
+
+use super::Animation;
+pub use super::Repeat;
+
+/// Maximum number of colors in a palette, the last palette entry is used for transparency
+const MAX_COLORS: usize = 255;
+
+/// Largest LZW code
+const MAX_CODE: u16 = 4095;
+
+pub(super) type Rgba8 = [u8; 4];
+
+/// How palettes are chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteMode {
+    /// A single palette for the whole animation, computed from every frame. Frames are kept in
+    /// memory until `Encoder::finish` is called.
+    Global,
+    /// A palette for each frame, computed from the pixels that changed since the previous frame.
+    /// Frames are written as they are added.
+    PerFrame,
+}
+
+/// Converts a gray, RGB, RGBA, BGR or BGRA image to 8-bit RGBA
+pub(super) fn to_rgba8<T: Type, C: Color, I: Image<T, C>>(image: &I) -> Result<Vec<Rgba8>, Error> {
+    let order: &[usize] = match C::name() {
+        "gray" => &[0, 0, 0],
+        "rgb" | "rgba" => &[0, 1, 2],
+        "bgr" | "bgra" => &[2, 1, 0],
+        _ => return Err(Error::InvalidColor),
+    };
+
+    let channels = C::channels();
+    let to_u8 = |c: &T| (c.to_f().clamp(0.0, 1.0) * 255.0).round() as u8;
+    Ok(image
+        .data()
+        .chunks(channels)
+        .map(|px| {
+            let alpha = if C::has_alpha() {
+                to_u8(&px[channels - 1])
+            } else {
+                255
+            };
+            [
+                to_u8(&px[order[0]]),
+                to_u8(&px[order[1]]),
+                to_u8(&px[order[2]]),
+                alpha,
+            ]
+        })
+        .collect())
+}
+
+fn is_transparent(px: Rgba8) -> bool {
+    px[3] < 128
+}
+
+fn rgb(px: Rgba8) -> [u8; 3] {
+    [px[0], px[1], px[2]]
+}
+
+/// Choose at most `MAX_COLORS` colors, using the colors themselves when there are few enough
+/// and median cut otherwise
+fn quantize<It: Iterator<Item = [u8; 3]>>(colors: It) -> Vec<[u8; 3]> {
+    let mut exact: HashSet<[u8; 3]> = HashSet::new();
+    // Colors are binned using 6 bits per channel, storing the count and sum of each bin
+    let mut bins: HashMap<[u8; 3], (u64, [u64; 3])> = HashMap::new();
+    for c in colors {
+        if exact.len() <= MAX_COLORS {
+            exact.insert(c);
+        }
+        let bin = bins.entry(c.map(|c| c >> 2)).or_insert((0, [0; 3]));
+        bin.0 += 1;
+        for (sum, c) in bin.1.iter_mut().zip(c) {
+            *sum += u64::from(c);
+        }
+    }
+
+    if exact.len() <= MAX_COLORS {
+        let mut colors: Vec<[u8; 3]> = exact.into_iter().collect();
+        colors.sort_unstable();
+        return colors;
+    }
+
+    let mut entries: Vec<([u8; 3], u64, [u64; 3])> = bins
+        .into_values()
+        .map(|(count, sum)| (sum.map(|s| (s / count) as u8), count, sum))
+        .collect();
+    entries.sort_unstable_by_key(|e| e.0);
+
+    let mut boxes = vec![(0, entries.len())];
+    while boxes.len() < MAX_COLORS {
+        // Split the box with the largest range weighted by the number of pixels
+        let mut best = None;
+        let mut best_score = 0;
+        for (i, &(start, end)) in boxes.iter().enumerate() {
+            let items = &entries[start..end];
+            let (channel, range) = (0..3)
+                .map(|c| {
+                    let min = items.iter().map(|e| e.0[c]).min().unwrap_or(0);
+                    let max = items.iter().map(|e| e.0[c]).max().unwrap_or(0);
+                    (c, u64::from(max - min))
+                })
+                .max_by_key(|(_, range)| *range)
+                .unwrap();
+            let count: u64 = items.iter().map(|e| e.1).sum();
+            if range > 0 && end - start > 1 && range * count > best_score {
+                best_score = range * count;
+                best = Some((i, channel));
+            }
+        }
+
+        let (i, channel) = match best {
+            Some(best) => best,
+            None => break,
+        };
+
+        let (start, end) = boxes[i];
+        let items = &mut entries[start..end];
+        items.sort_unstable_by_key(|e| e.0[channel]);
+        let half = items.iter().map(|e| e.1).sum::<u64>() / 2;
+        let mut total = 0;
+        let mut split = 1;
+        for (j, e) in items.iter().enumerate() {
+            total += e.1;
+            if total >= half {
+                split = j + 1;
+                break;
+            }
+        }
+        let split = start + split.clamp(1, items.len() - 1);
+        boxes[i] = (start, split);
+        boxes.push((split, end));
+    }
+
+    boxes
+        .into_iter()
+        .map(|(start, end)| {
+            let items = &entries[start..end];
+            let count: u64 = items.iter().map(|e| e.1).sum();
+            let mut color = [0; 3];
+            for (c, value) in color.iter_mut().enumerate() {
+                let sum: u64 = items.iter().map(|e| e.2[c]).sum();
+                *value = (sum / count.max(1)) as u8;
+            }
+            color
+        })
+        .collect()
+}
+
+/// A color table along with a cache of nearest color lookups
+struct Palette {
+    colors: Vec<[u8; 3]>,
+    cache: HashMap<[u8; 3], u8>,
+}
+
+impl Palette {
+    fn new(colors: Vec<[u8; 3]>) -> Palette {
+        Palette {
+            colors,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Index used for transparent pixels
+    fn transparent(&self) -> u8 {
+        self.colors.len() as u8
+    }
+
+    /// The color table has `2^bits` entries
+    fn bits(&self) -> u8 {
+        let mut bits = 1;
+        while (1 << bits) < self.colors.len() + 1 {
+            bits += 1;
+        }
+        bits
+    }
+
+    fn index(&mut self, color: [u8; 3]) -> u8 {
+        let colors = &self.colors;
+        *self.cache.entry(color).or_insert_with(|| {
+            let distance = |c: &[u8; 3]| -> i32 {
+                (0..3)
+                    .map(|i| (i32::from(c[i]) - i32::from(color[i])).pow(2))
+                    .sum()
+            };
+            (0..colors.len())
+                .min_by_key(|&i| distance(&colors[i]))
+                .unwrap_or(0) as u8
+        })
+    }
+
+    fn write_table<W: Write>(&self, out: &mut W) -> Result<(), Error> {
+        for i in 0..1 << self.bits() {
+            out.write_all(&self.colors.get(i).copied().unwrap_or([0; 3]))?;
+        }
+        Ok(())
+    }
+}
+
+/// Packs variable length codes, least significant bit first
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u32,
+    len: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u8) {
+        self.bits |= u32::from(code) << self.len;
+        self.len += size;
+        while self.len >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.len -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.len > 0 {
+            self.out.push(self.bits as u8);
+        }
+        self.out
+    }
+}
+
+/// GIF variant of LZW compression
+fn lzw_encode(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+    let mut writer = BitWriter {
+        out: Vec::new(),
+        bits: 0,
+        len: 0,
+    };
+    let mut size = min_code_size + 1;
+    let mut next = end + 1;
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+
+    writer.write(clear, size);
+    let mut prefix = match indices.first() {
+        Some(&index) => u16::from(index),
+        None => {
+            writer.write(end, size);
+            return writer.finish();
+        }
+    };
+
+    for &index in &indices[1..] {
+        if let Some(&code) = table.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+
+        writer.write(prefix, size);
+        if next >= 1 << size && size < 12 {
+            size += 1;
+        }
+
+        if next >= MAX_CODE {
+            writer.write(clear, size);
+            table.clear();
+            next = end + 1;
+            size = min_code_size + 1;
+        } else {
+            table.insert((prefix, index), next);
+            next += 1;
+        }
+        prefix = u16::from(index);
+    }
+
+    writer.write(prefix, size);
+    if next >= 1 << size && size < 12 {
+        size += 1;
+    }
+    writer.write(end, size);
+    writer.finish()
+}
+
+fn write_u16<W: Write>(out: &mut W, value: u16) -> Result<(), Error> {
+    out.write_all(&value.to_le_bytes())?;
+    Ok(())
+}
+
+/// A frame that hasn't been written yet
+struct Pending {
+    pixels: Vec<Rgba8>,
+    delay: u16,
+    /// Pixels that were opaque in the previous frame become transparent, so the frame can't be
+    /// drawn on top of the previous frame
+    keyframe: bool,
+}
+
+/// How a frame is written
+struct FrameOptions {
+    /// GIF disposal method, 1 keeps the frame and 2 clears it before drawing the next frame
+    disposal: u8,
+    /// Write the whole frame instead of the part that changed
+    full: bool,
+    dither: bool,
+}
+
+/// Writes animated GIF files, see the module documentation for an example
+pub struct Encoder<W: Write> {
+    out: W,
+    width: usize,
+    height: usize,
+    mode: PaletteMode,
+    dither: bool,
+    repeat: Repeat,
+    header: bool,
+    pending: Vec<Pending>,
+    /// Pixels of the last frame that was written
+    previous: Option<Vec<Rgba8>>,
+}
+
+impl Encoder<BufWriter<File>> {
+    /// Create a GIF file
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        width: usize,
+        height: usize,
+    ) -> Result<Encoder<BufWriter<File>>, Error> {
+        Ok(Encoder::new(
+            BufWriter::new(with_path(path, |path| Ok(File::create(path)?))?),
+            width,
+            height,
+        ))
+    }
+}
+
+impl<W: Write> Encoder<W> {
+    /// Write an animation to `out` using a global palette and dithering, repeating forever
+    pub fn new(out: W, width: usize, height: usize) -> Encoder<W> {
+        Encoder {
+            out,
+            width,
+            height,
+            mode: PaletteMode::Global,
+            dither: true,
+            repeat: Repeat::Infinite,
+            header: false,
+            pending: Vec::new(),
+            previous: None,
+        }
+    }
+
+    pub fn palette(mut self, mode: PaletteMode) -> Encoder<W> {
+        self.mode = mode;
+        self
+    }
+
+    /// Enable or disable Floyd-Steinberg dithering, which is only used for frames with more
+    /// colors than the palette
+    pub fn dither(mut self, dither: bool) -> Encoder<W> {
+        self.dither = dither;
+        self
+    }
+
+    pub fn repeat(mut self, repeat: Repeat) -> Encoder<W> {
+        self.repeat = repeat;
+        self
+    }
+
+    /// Add a frame, which is shown for `delay` rounded to hundredths of a second. Gray, RGB,
+    /// RGBA, BGR and BGRA images are supported.
+    pub fn add_frame<T: Type, C: Color, I: Image<T, C>>(
+        &mut self,
+        image: &I,
+        delay: Duration,
+    ) -> Result<(), Error> {
+        if image.width() != self.width || image.height() != self.height {
+            return Err(Error::Message(format!(
+                "Expected a {}x{} image, got {}x{}",
+                self.width,
+                self.height,
+                image.width(),
+                image.height()
+            )));
+        }
+
+        let pixels = to_rgba8(image)?;
+        let keyframe = match self
+            .pending
+            .last()
+            .map(|p| &p.pixels)
+            .or(self.previous.as_ref())
+        {
+            Some(previous) => previous
+                .iter()
+                .zip(&pixels)
+                .any(|(&a, &b)| !is_transparent(a) && is_transparent(b)),
+            None => true,
+        };
+        let delay = (delay.as_millis() as f64 / 10.0).round().min(65535.0) as u16;
+        self.pending.push(Pending {
+            pixels,
+            delay,
+            keyframe,
+        });
+
+        if self.mode == PaletteMode::PerFrame && self.pending.len() > 1 {
+            self.write_header(None)?;
+            self.write_pending(None)?;
+        }
+        Ok(())
+    }
+
+    fn write_header(&mut self, global: Option<&Palette>) -> Result<(), Error> {
+        if self.header {
+            return Ok(());
+        }
+
+        self.out.write_all(b"GIF89a")?;
+        write_u16(&mut self.out, self.width as u16)?;
+        write_u16(&mut self.out, self.height as u16)?;
+        match global {
+            Some(palette) => {
+                self.out.write_all(&[0xf0 | (palette.bits() - 1), 0, 0])?;
+                palette.write_table(&mut self.out)?;
+            }
+            None => self.out.write_all(&[0, 0, 0])?,
+        }
+
+        let count = match self.repeat {
+            Repeat::Infinite => Some(0),
+            Repeat::Finite(0) => None,
+            Repeat::Finite(n) => Some(n),
+        };
+        if let Some(count) = count {
+            self.out.write_all(b"\x21\xff\x0bNETSCAPE2.0\x03\x01")?;
+            write_u16(&mut self.out, count)?;
+            self.out.write_all(&[0])?;
+        }
+
+        self.header = true;
+        Ok(())
+    }
+
+    /// Write the oldest pending frame, which needs the next frame to be known to choose the
+    /// disposal method
+    fn write_pending(&mut self, global: Option<&mut Palette>) -> Result<(), Error> {
+        let frame = self.pending.remove(0);
+        let next_keyframe = self.pending.first().map(|p| p.keyframe).unwrap_or(false);
+        let options = FrameOptions {
+            disposal: if next_keyframe { 2 } else { 1 },
+            full: self.previous.is_none() || next_keyframe,
+            dither: self.dither,
+        };
+        let previous = if frame.keyframe {
+            None
+        } else {
+            self.previous.as_deref()
+        };
+
+        write_frame(
+            &mut self.out,
+            (self.width, self.height),
+            &frame.pixels,
+            previous,
+            frame.delay,
+            &options,
+            global,
+        )?;
+        self.previous = Some(frame.pixels);
+        Ok(())
+    }
+
+    /// Write the remaining frames and return the output
+    pub fn finish(mut self) -> Result<W, Error> {
+        if self.pending.is_empty() && !self.header {
+            return Err(Error::Message(String::from("No frames were added")));
+        }
+
+        let mut global = match self.mode {
+            PaletteMode::Global => Some(Palette::new(quantize(
+                self.pending
+                    .iter()
+                    .flat_map(|p| p.pixels.iter())
+                    .filter(|&&px| !is_transparent(px))
+                    .map(|&px| rgb(px)),
+            ))),
+            PaletteMode::PerFrame => None,
+        };
+
+        self.write_header(global.as_ref())?;
+        while !self.pending.is_empty() {
+            self.write_pending(global.as_mut())?;
+        }
+
+        self.out.write_all(&[0x3b])?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Write a graphic control extension followed by an image
+fn write_frame<W: Write>(
+    out: &mut W,
+    size: (usize, usize),
+    pixels: &[Rgba8],
+    previous: Option<&[Rgba8]>,
+    delay: u16,
+    options: &FrameOptions,
+    global: Option<&mut Palette>,
+) -> Result<(), Error> {
+    let (width, height) = size;
+    let changed: Vec<bool> = match previous {
+        Some(previous) => pixels
+            .iter()
+            .zip(previous)
+            .map(|(&a, &b)| !(is_transparent(a) && is_transparent(b)) && a != b)
+            .collect(),
+        None => pixels.iter().map(|&px| !is_transparent(px)).collect(),
+    };
+
+    // Bounding box of the changed pixels
+    let (mut x0, mut y0, mut x1, mut y1) = (width, height, 0, 0);
+    for (i, _) in changed.iter().enumerate().filter(|(_, c)| **c) {
+        let (x, y) = (i % width, i / width);
+        x0 = x0.min(x);
+        y0 = y0.min(y);
+        x1 = x1.max(x + 1);
+        y1 = y1.max(y + 1);
+    }
+    if options.full {
+        (x0, y0, x1, y1) = (0, 0, width, height);
+    } else if x0 >= x1 {
+        (x0, y0, x1, y1) = (0, 0, 1, 1);
+    }
+
+    let is_global = global.is_some();
+    let mut local;
+    let palette = match global {
+        Some(palette) => palette,
+        None => {
+            local = Palette::new(quantize((y0..y1).flat_map(|y| {
+                (x0..x1)
+                    .map(move |x| y * width + x)
+                    .filter(|&i| changed[i])
+                    .map(|i| rgb(pixels[i]))
+            })));
+            &mut local
+        }
+    };
+
+    // Floyd-Steinberg error for the current and next row
+    let w = x1 - x0;
+    let mut error = vec![[0i32; 3]; w + 2];
+    let mut next_error = vec![[0i32; 3]; w + 2];
+    let mut indices = Vec::with_capacity(w * (y1 - y0));
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let i = y * width + x;
+            if !changed[i] {
+                indices.push(palette.transparent());
+                continue;
+            }
+
+            let mut color = rgb(pixels[i]);
+            if options.dither {
+                let e = error[x - x0 + 1];
+                for c in 0..3 {
+                    color[c] = (i32::from(color[c]) + e[c] / 16).clamp(0, 255) as u8;
+                }
+            }
+
+            let index = palette.index(color);
+            indices.push(index);
+
+            if options.dither {
+                let chosen = palette.colors[index as usize];
+                let j = x - x0 + 1;
+                for c in 0..3 {
+                    let e = i32::from(color[c]) - i32::from(chosen[c]);
+                    error[j + 1][c] += e * 7;
+                    next_error[j - 1][c] += e * 3;
+                    next_error[j][c] += e * 5;
+                    next_error[j + 1][c] += e;
+                }
+            }
+        }
+        std::mem::swap(&mut error, &mut next_error);
+        next_error.iter_mut().for_each(|e| *e = [0; 3]);
+    }
+
+    out.write_all(&[0x21, 0xf9, 4, options.disposal << 2 | 1])?;
+    write_u16(out, delay)?;
+    out.write_all(&[palette.transparent(), 0])?;
+
+    out.write_all(&[0x2c])?;
+    for value in [x0, y0, w, y1 - y0] {
+        write_u16(out, value as u16)?;
+    }
+    let bits = palette.bits();
+    if is_global {
+        out.write_all(&[0])?;
+    } else {
+        out.write_all(&[0x80 | (bits - 1)])?;
+        palette.write_table(out)?;
+    }
+
+    let min_code_size = bits.max(2);
+    out.write_all(&[min_code_size])?;
+    for block in lzw_encode(&indices, min_code_size).chunks(255) {
+        out.write_all(&[block.len() as u8])?;
+        out.write_all(block)?;
+    }
+    out.write_all(&[0])?;
+    Ok(())
+}
+
+/// Decode every frame of a GIF file along with the time each frame is shown
+pub fn decode<Data: AsRef<[u8]>>(data: Data) -> Result<Animation<u8, Rgba>, Error> {
+    let mut frames = Vec::new();
+    decode_into(data, &mut frames)?;
+    Ok(frames)
+}
+
+/// Decode every frame of a GIF file into an existing animation, reusing the images of its frames
+pub fn decode_into<Data: AsRef<[u8]>>(
+    data: Data,
+    animation: &mut Animation<u8, Rgba>,
+) -> Result<(), Error> {
+    let data = data.as_ref();
+    let mut delays = std::ptr::null_mut();
+    let (mut width, mut height, mut frames, mut channels) = (0, 0, 0, 0);
+    let ptr = unsafe {
+        super::stbi_load_gif_from_memory(
+            data.as_ptr(),
+            data.len() as i32,
+            &mut delays,
+            &mut width,
+            &mut height,
+            &mut frames,
+            &mut channels,
+            4,
+        )
+    };
+
+    if ptr.is_null() {
+        return Err(Error::Message(String::from("Unable to decode GIF")));
+    }
+
+    let (width, height, frames) = (width as usize, height as usize, frames as usize);
+    let size = width * height * 4;
+    let (pixels, delays_ms) = unsafe {
+        (
+            std::slice::from_raw_parts(ptr, size * frames),
+            std::slice::from_raw_parts(delays, if delays.is_null() { 0 } else { frames }),
+        )
+    };
+
+    for (i, frame) in pixels.chunks(size).enumerate() {
+        let delay = delays_ms.get(i).copied().unwrap_or(0).max(0) as u64;
+        super::store_frame(
+            animation,
+            i,
+            width,
+            height,
+            frame,
+            Duration::from_millis(delay),
+        );
+    }
+    animation.truncate(frames);
+
+    unsafe {
+        super::stbi_image_free(ptr as *mut std::ffi::c_void);
+        if !delays.is_null() {
+            super::stbi_image_free(delays as *mut std::ffi::c_void);
+        }
+    }
+    Ok(())
+}
+
+/// Read every frame of a GIF file
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Animation<u8, Rgba>, Error> {
+    with_path(path, |path| decode(std::fs::read(path)?))
+}
+
+/// Read every frame of a GIF file into an existing animation, reusing the images of its frames
+pub fn read_into<P: AsRef<Path>>(
+    path: P,
+    animation: &mut Animation<u8, Rgba>,
+) -> Result<(), Error> {
+    with_path(path, |path| decode_into(std::fs::read(path)?, animation))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::color::Rgb;
+    use crate::image_buf::ImageBuf;
+
+    #[test]
+    fn test_gif_lzw() {
+        let palette = Palette::new(vec![[0, 0, 0], [255, 255, 255], [255, 0, 0]]);
+        assert_eq!(palette.bits(), 2);
+        assert_eq!(palette.transparent(), 3);
+
+        // Pseudo random indices force the code table to be reset
+        let mut state = 1u32;
+        let indices: Vec<u8> = (0..64 * 64)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect();
+        let colors: Vec<[u8; 3]> = (0..=255).map(|i| [i, 255 - i, i / 2]).collect();
+
+        let mut gif = Vec::new();
+        gif.extend_from_slice(b"GIF89a\x40\x00\x40\x00\xf7\x00\x00");
+        colors.iter().for_each(|c| gif.extend_from_slice(c));
+        gif.extend_from_slice(b"\x2c\x00\x00\x00\x00\x40\x00\x40\x00\x00\x08");
+        for block in lzw_encode(&indices, 8).chunks(255) {
+            gif.push(block.len() as u8);
+            gif.extend_from_slice(block);
+        }
+        gif.extend_from_slice(b"\x00\x3b");
+
+        let frames = decode(&gif).unwrap();
+        assert_eq!(frames.len(), 1);
+        for (px, &index) in frames[0].0.data().chunks(4).zip(&indices) {
+            assert_eq!(px[..3], colors[index as usize]);
+        }
+    }
+
+    #[test]
+    fn test_gif_encoder() {
+        let mut a: ImageBuf<u8, Rgb> = ImageBuf::new(40, 30);
+        for y in 0..30 {
+            for x in 0..40 {
+                a.at_mut(x, y)
+                    .copy_from_slice(&[(x / 4) as u8 * 25, (y / 3) as u8 * 25, 128]);
+            }
+        }
+        let mut b = Clone::clone(&a);
+        b.at_mut(10, 10).copy_from_slice(&[255, 255, 255]);
+
+        // Few enough colors to be stored exactly
+        let mut encoder = Encoder::new(Vec::new(), 40, 30).palette(PaletteMode::PerFrame);
+        encoder.add_frame(&a, Duration::from_millis(100)).unwrap();
+        encoder.add_frame(&b, Duration::from_millis(250)).unwrap();
+        let gif = encoder.finish().unwrap();
+
+        let frames = decode(&gif).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].1, Duration::from_millis(100));
+        assert_eq!(frames[1].1, Duration::from_millis(250));
+        for (frame, expected) in frames.iter().zip([&a, &b]) {
+            for (px, e) in frame.0.data().chunks(4).zip(expected.data().chunks(3)) {
+                assert_eq!(&px[..3], e);
+            }
+        }
+
+        // Quantized frames are close to the original
+        let mut noise: ImageBuf<f32, Rgb> = ImageBuf::new(40, 30);
+        for (i, c) in noise.data_mut().iter_mut().enumerate() {
+            *c = ((i * 7919) % 1000) as f32 / 1000.0;
+        }
+        let mut encoder = Encoder::new(Vec::new(), 40, 30).repeat(Repeat::Finite(0));
+        encoder
+            .add_frame(&noise, Duration::from_millis(50))
+            .unwrap();
+        encoder.add_frame(&a, Duration::from_millis(50)).unwrap();
+        let gif = encoder.finish().unwrap();
+        assert!(!gif.windows(8).any(|w| w == b"NETSCAPE"));
+
+        let frames = decode(&gif).unwrap();
+        let error: f64 = frames[0]
+            .0
+            .data()
+            .chunks(4)
+            .zip(noise.data().chunks(3))
+            .map(|(px, e)| {
+                (0..3)
+                    .map(|c| (px[c] as f64 / 255.0 - e[c] as f64).abs())
+                    .sum::<f64>()
+            })
+            .sum::<f64>()
+            / (40.0 * 30.0 * 3.0);
+        assert!(error < 0.05);
+
+        // Pixels that become transparent clear the previous frame
+        let mut c: ImageBuf<u8, Rgba> = ImageBuf::new(40, 30);
+        c.at_mut(0, 0).copy_from_slice(&[255, 0, 0, 255]);
+        let mut encoder = Encoder::new(Vec::new(), 40, 30);
+        encoder.add_frame(&a, Duration::from_millis(50)).unwrap();
+        encoder.add_frame(&c, Duration::from_millis(50)).unwrap();
+        let frames = decode(encoder.finish().unwrap()).unwrap();
+        assert_eq!(frames[1].0.at(0, 0), &[255, 0, 0, 255]);
+        assert_eq!(frames[1].0.at(20, 20)[3], 0);
+
+        assert!(Encoder::new(Vec::new(), 1, 1).finish().is_err());
+        assert!(Encoder::new(Vec::new(), 1, 1)
+            .add_frame(&a, Duration::ZERO)
+            .is_err());
+    }
+}
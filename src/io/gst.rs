@@ -0,0 +1,441 @@
+//! Exchange frames with GStreamer pipelines using `appsink` and `appsrc` elements
+//!
+//! ```rust,no_run
+//! use image2::io::gst::{Sink, Source};
+//! use image2::Rgb;
+//!
+//! // Read frames from a pipeline ending in an appsink called `sink`
+//! let mut sink = Sink::<u8, Rgb>::launch("v4l2src ! videoconvert ! appsink name=sink")?;
+//! let frame = sink.pull()?.unwrap();
+//!
+//! // Write frames to a pipeline starting with an appsrc called `src`
+//! let mut source = Source::<u8, Rgb>::launch(
+//!     "appsrc name=src ! videoconvert ! x264enc ! mp4mux ! filesink location=out.mp4",
+//!     frame.image.width(),
+//!     frame.image.height(),
+//!     30.0,
+//! )?;
+//! for frame in sink.take(300) {
+//!     source.push(&frame?.image)?;
+//! }
+//! source.finish()?;
+//! # Ok::<(), image2::Error>(())
+//! ```
+//!
+//! The caps of the `appsink` are restricted to the raw video format matching the image type, so
+//! the pipeline needs a `videoconvert` element when the upstream format is different. Width,
+//! height and frame rate are negotiated by the pipeline. The caps of the `appsrc` are set from
+//! the image type, size and frame rate. 8-bit gray, RGB, BGR, RGBA and BGRA images and 16-bit
+//! gray images are supported.
+
+use std::marker::PhantomData;
+use std::time::Duration;
+
+use gstreamer as gst;
+use gstreamer::prelude::*;
+use gstreamer_app as gst_app;
+use gstreamer_video as gst_video;
+
+use crate::color::Color;
+use crate::error::Error;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::ty::Type;
+
+use super::image_bytes;
+
+/// Frame pulled from a pipeline
+pub struct Frame<T: Type, C: Color> {
+    pub image: ImageBuf<T, C>,
+    /// Presentation timestamp of the buffer, if it has one
+    pub timestamp: Option<Duration>,
+}
+
+/// GStreamer video format with the same memory layout as an image
+fn video_format<T: Type, C: Color>() -> Result<gst_video::VideoFormat, Error> {
+    use gst_video::VideoFormat;
+
+    match (std::mem::size_of::<T>(), T::is_float(), C::name()) {
+        (1, false, "gray") => Ok(VideoFormat::Gray8),
+        (1, false, "rgb") => Ok(VideoFormat::Rgb),
+        (1, false, "bgr") => Ok(VideoFormat::Bgr),
+        (1, false, "rgba") => Ok(VideoFormat::Rgba),
+        (1, false, "bgra") => Ok(VideoFormat::Bgra),
+        (2, false, "gray") if cfg!(target_endian = "little") => Ok(VideoFormat::Gray16Le),
+        (2, false, "gray") => Ok(VideoFormat::Gray16Be),
+        (_, _, "gray" | "rgb" | "bgr" | "rgba" | "bgra") => Err(Error::InvalidType),
+        _ => Err(Error::InvalidColor),
+    }
+}
+
+/// Frame rate as a fraction, GStreamer caps can't contain floating point frame rates
+fn fps_fraction(fps: f64) -> (i32, i32) {
+    if (fps - fps.round()).abs() < 1e-6 {
+        (fps.round() as i32, 1)
+    } else {
+        ((fps * 1001.0).round() as i32, 1001)
+    }
+}
+
+/// Copy the first `row_len` bytes of each row between buffers with different row strides
+fn copy_rows(src: &[u8], src_stride: usize, dest: &mut [u8], dest_stride: usize, row_len: usize) {
+    for (dest, src) in dest.chunks_mut(dest_stride).zip(src.chunks(src_stride)) {
+        dest[..row_len].copy_from_slice(&src[..row_len]);
+    }
+}
+
+/// Find a named element and cast it to an app element
+fn app_element<E: IsA<gst::Element>>(pipeline: &gst::Pipeline, name: &str) -> Result<E, Error> {
+    pipeline
+        .by_name(name)
+        .and_then(|element| element.dynamic_cast::<E>().ok())
+        .ok_or_else(|| {
+            Error::Message(format!(
+                "Pipeline has no {} element called {:?}",
+                E::static_type().name(),
+                name
+            ))
+        })
+}
+
+fn launch(description: &str) -> Result<gst::Pipeline, Error> {
+    gst::init()?;
+    gst::parse::launch(description)?
+        .downcast::<gst::Pipeline>()
+        .map_err(|_| Error::Message(String::from("Pipeline description is not a pipeline")))
+}
+
+/// Return the error posted on the bus of a pipeline, if there is one
+fn bus_error(pipeline: &gst::Pipeline) -> Option<Error> {
+    let message = pipeline.bus()?.pop_filtered(&[gst::MessageType::Error])?;
+    match message.view() {
+        gst::MessageView::Error(err) => {
+            Some(Error::Message(format!("GStreamer error: {}", err.error())))
+        }
+        _ => None,
+    }
+}
+
+/// Pulls frames from an `appsink` at the end of a pipeline
+pub struct Sink<T: Type, C: Color> {
+    pipeline: gst::Pipeline,
+    appsink: gst_app::AppSink,
+    _type: PhantomData<(T, C)>,
+}
+
+impl<T: Type, C: Color> Sink<T, C> {
+    /// Start a pipeline containing an appsink called `sink`
+    pub fn launch(description: &str) -> Result<Sink<T, C>, Error> {
+        Self::launch_with(description, "sink")
+    }
+
+    /// Start a pipeline containing an appsink called `name`
+    pub fn launch_with(description: &str, name: &str) -> Result<Sink<T, C>, Error> {
+        let pipeline = launch(description)?;
+        Self::from_pipeline(pipeline, name)
+    }
+
+    /// Use an existing pipeline containing an appsink called `name`, the pipeline is started
+    /// after the caps of the appsink are set
+    pub fn from_pipeline(pipeline: gst::Pipeline, name: &str) -> Result<Sink<T, C>, Error> {
+        let appsink: gst_app::AppSink = app_element(&pipeline, name)?;
+        let caps = gst_video::VideoCapsBuilder::new()
+            .format(video_format::<T, C>()?)
+            .build();
+        appsink.set_caps(Some(&caps));
+        pipeline.set_state(gst::State::Playing)?;
+
+        Ok(Sink {
+            pipeline,
+            appsink,
+            _type: PhantomData,
+        })
+    }
+
+    pub fn pipeline(&self) -> &gst::Pipeline {
+        &self.pipeline
+    }
+
+    /// Copy a sample into `frame`, the image is resized if the size of the video changed
+    fn fill(&self, sample: gst::Sample, frame: &mut Frame<T, C>) -> Result<(), Error> {
+        let (caps, buffer) = match (sample.caps(), sample.buffer()) {
+            (Some(caps), Some(buffer)) => (caps, buffer),
+            _ => return Err(Error::Message(String::from("Sample has no caps or buffer"))),
+        };
+
+        let info = gst_video::VideoInfo::from_caps(caps)?;
+        let (width, height) = (info.width() as usize, info.height() as usize);
+        let map = buffer.map_readable()?;
+
+        frame.image.reshape(width, height);
+        let row_len = width * C::channels() * std::mem::size_of::<T>();
+        copy_rows(
+            &map.as_slice()[info.offset()[0]..],
+            info.stride()[0] as usize,
+            image_bytes(&mut frame.image),
+            row_len,
+            row_len,
+        );
+
+        frame.timestamp = buffer.pts().map(|pts| Duration::from_nanos(pts.nseconds()));
+        Ok(())
+    }
+
+    fn frame(&self, sample: gst::Sample) -> Result<Frame<T, C>, Error> {
+        let mut frame = Frame {
+            image: ImageBuf::new(0, 0),
+            timestamp: None,
+        };
+        self.fill(sample, &mut frame)?;
+        Ok(frame)
+    }
+
+    /// Wait for the next frame, returns `None` at the end of the stream
+    pub fn pull(&mut self) -> Result<Option<Frame<T, C>>, Error> {
+        match self.appsink.pull_sample() {
+            Ok(sample) => self.frame(sample).map(Some),
+            Err(_) => match bus_error(&self.pipeline) {
+                Some(err) => Err(err),
+                None => Ok(None),
+            },
+        }
+    }
+
+    /// Wait at most `timeout` for the next frame, returns `None` when no frame is available or
+    /// at the end of the stream
+    pub fn try_pull(&mut self, timeout: Duration) -> Result<Option<Frame<T, C>>, Error> {
+        let timeout = gst::ClockTime::from_nseconds(timeout.as_nanos() as u64);
+        match self.appsink.try_pull_sample(timeout) {
+            Some(sample) => self.frame(sample).map(Some),
+            None => match bus_error(&self.pipeline) {
+                Some(err) => Err(err),
+                None => Ok(None),
+            },
+        }
+    }
+
+    /// Wait for the next frame and store it in an existing frame, reusing its image. Returns
+    /// false at the end of the stream
+    pub fn pull_into(&mut self, frame: &mut Frame<T, C>) -> Result<bool, Error> {
+        match self.appsink.pull_sample() {
+            Ok(sample) => self.fill(sample, frame).map(|()| true),
+            Err(_) => match bus_error(&self.pipeline) {
+                Some(err) => Err(err),
+                None => Ok(false),
+            },
+        }
+    }
+
+    /// Wait at most `timeout` for the next frame and store it in an existing frame, reusing its
+    /// image. Returns false when no frame is available or at the end of the stream
+    pub fn try_pull_into(
+        &mut self,
+        frame: &mut Frame<T, C>,
+        timeout: Duration,
+    ) -> Result<bool, Error> {
+        let timeout = gst::ClockTime::from_nseconds(timeout.as_nanos() as u64);
+        match self.appsink.try_pull_sample(timeout) {
+            Some(sample) => self.fill(sample, frame).map(|()| true),
+            None => match bus_error(&self.pipeline) {
+                Some(err) => Err(err),
+                None => Ok(false),
+            },
+        }
+    }
+
+    /// Returns true when the pipeline reached the end of the stream
+    pub fn is_eos(&self) -> bool {
+        self.appsink.is_eos()
+    }
+}
+
+impl<T: Type, C: Color> Iterator for Sink<T, C> {
+    type Item = Result<Frame<T, C>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.pull().transpose()
+    }
+}
+
+impl<T: Type, C: Color> Drop for Sink<T, C> {
+    fn drop(&mut self) {
+        let _ = self.pipeline.set_state(gst::State::Null);
+    }
+}
+
+/// Pushes frames into an `appsrc` at the start of a pipeline
+pub struct Source<T: Type, C: Color> {
+    pipeline: gst::Pipeline,
+    appsrc: gst_app::AppSrc,
+    info: gst_video::VideoInfo,
+    frame_duration: Duration,
+    timestamp: Duration,
+    _type: PhantomData<(T, C)>,
+}
+
+impl<T: Type, C: Color> Source<T, C> {
+    /// Start a pipeline containing an appsrc called `src`, frames have the given size and are
+    /// timestamped using the frame rate
+    pub fn launch(
+        description: &str,
+        width: usize,
+        height: usize,
+        fps: f64,
+    ) -> Result<Source<T, C>, Error> {
+        Self::launch_with(description, "src", width, height, fps)
+    }
+
+    /// Start a pipeline containing an appsrc called `name`
+    pub fn launch_with(
+        description: &str,
+        name: &str,
+        width: usize,
+        height: usize,
+        fps: f64,
+    ) -> Result<Source<T, C>, Error> {
+        let pipeline = launch(description)?;
+        Self::from_pipeline(pipeline, name, width, height, fps)
+    }
+
+    /// Use an existing pipeline containing an appsrc called `name`, the pipeline is started
+    /// after the caps of the appsrc are set
+    pub fn from_pipeline(
+        pipeline: gst::Pipeline,
+        name: &str,
+        width: usize,
+        height: usize,
+        fps: f64,
+    ) -> Result<Source<T, C>, Error> {
+        if fps <= 0.0 {
+            return Err(Error::Message(format!("Invalid frame rate: {}", fps)));
+        }
+
+        let appsrc: gst_app::AppSrc = app_element(&pipeline, name)?;
+        let (num, den) = fps_fraction(fps);
+        let info =
+            gst_video::VideoInfo::builder(video_format::<T, C>()?, width as u32, height as u32)
+                .fps(gst::Fraction::new(num, den))
+                .build()?;
+        appsrc.set_caps(Some(&info.to_caps()?));
+        appsrc.set_format(gst::Format::Time);
+        pipeline.set_state(gst::State::Playing)?;
+
+        Ok(Source {
+            pipeline,
+            appsrc,
+            info,
+            frame_duration: Duration::from_secs_f64(1.0 / fps),
+            timestamp: Duration::from_secs(0),
+            _type: PhantomData,
+        })
+    }
+
+    pub fn pipeline(&self) -> &gst::Pipeline {
+        &self.pipeline
+    }
+
+    /// Push a frame, timestamped one frame after the previous frame
+    pub fn push<I: Image<T, C>>(&mut self, image: &I) -> Result<(), Error> {
+        let timestamp = self.timestamp;
+        self.push_at(image, timestamp)
+    }
+
+    /// Push a frame with the given presentation timestamp
+    pub fn push_at<I: Image<T, C>>(&mut self, image: &I, timestamp: Duration) -> Result<(), Error> {
+        let (width, height) = (self.info.width() as usize, self.info.height() as usize);
+        if image.width() != width || image.height() != height {
+            return Err(Error::Message(format!(
+                "Expected a {}x{} image, got {}x{}",
+                width,
+                height,
+                image.width(),
+                image.height()
+            )));
+        }
+
+        // GStreamer rows are padded to the stride of the video format
+        let mut data = vec![0; self.info.size()];
+        let row_len = width * C::channels() * std::mem::size_of::<T>();
+        let offset = self.info.offset()[0];
+        copy_rows(
+            image.buffer(),
+            row_len,
+            &mut data[offset..],
+            self.info.stride()[0] as usize,
+            row_len,
+        );
+
+        let mut buffer = gst::Buffer::from_mut_slice(data);
+        {
+            let buffer = buffer.get_mut().unwrap();
+            buffer.set_pts(gst::ClockTime::from_nseconds(timestamp.as_nanos() as u64));
+            buffer.set_duration(gst::ClockTime::from_nseconds(
+                self.frame_duration.as_nanos() as u64,
+            ));
+        }
+
+        if self.appsrc.push_buffer(buffer).is_err() {
+            return Err(bus_error(&self.pipeline)
+                .unwrap_or_else(|| Error::Message(String::from("Pipeline is not running"))));
+        }
+
+        self.timestamp = timestamp + self.frame_duration;
+        Ok(())
+    }
+
+    /// Send the end of the stream and wait for the pipeline to finish, which is required for
+    /// muxers to finish writing files
+    pub fn finish(self) -> Result<(), Error> {
+        let _ = self.appsrc.end_of_stream();
+        if let Some(bus) = self.pipeline.bus() {
+            let message = bus.timed_pop_filtered(
+                gst::ClockTime::NONE,
+                &[gst::MessageType::Eos, gst::MessageType::Error],
+            );
+            if let Some(message) = message {
+                if let gst::MessageView::Error(err) = message.view() {
+                    return Err(Error::Message(format!("GStreamer error: {}", err.error())));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T: Type, C: Color> Drop for Source<T, C> {
+    fn drop(&mut self) {
+        let _ = self.pipeline.set_state(gst::State::Null);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::color::{Gray, Rgb, Yuv};
+
+    #[test]
+    fn test_gst_layout() {
+        assert_eq!(
+            video_format::<u8, Rgb>().unwrap(),
+            gst_video::VideoFormat::Rgb
+        );
+        assert!(matches!(
+            video_format::<f32, Rgb>(),
+            Err(Error::InvalidType)
+        ));
+        assert!(matches!(
+            video_format::<u8, Yuv>(),
+            Err(Error::InvalidColor)
+        ));
+        assert!(video_format::<u16, Gray>().is_ok());
+
+        assert_eq!(fps_fraction(30.0), (30, 1));
+        assert_eq!(fps_fraction(30000.0 / 1001.0), (30000, 1001));
+
+        // RGB rows of odd widths are padded to 4 bytes
+        let src = [1, 2, 3, 4, 5, 6, 0, 0, 7, 8, 9, 10, 11, 12];
+        let mut dest = [0; 12];
+        copy_rows(&src, 8, &mut dest, 6, 6);
+        assert_eq!(dest, [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
+    }
+}
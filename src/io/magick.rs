@@ -1,24 +1,16 @@
 use std::io::{Read, Write};
 use std::num::ParseIntError;
 use std::path::Path;
-use std::process::{Command, Stdio};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::usize;
 
 use crate::color::Color;
+use crate::error::{command_error, Error};
 use crate::image::Image;
 use crate::image_buf::ImageBuf;
 use crate::ty::Type;
 
-#[derive(Debug)]
-pub enum Error {
-    InvalidImageShape,
-    InvalidColor,
-    FileDoesNotExist,
-    InvalidImageData,
-    UnableToExecuteCommand,
-    ErrorWritingImage,
-}
-
 pub struct Magick {
     identify: &'static [&'static str],
     convert: &'static [&'static str],
@@ -50,6 +42,9 @@ pub const GM: Magick = Magick {
 
 pub static mut DEFAULT: Magick = IM;
 
+/// Number of temporary profile files created by `write`, used to make their names unique
+static PROFILES: AtomicUsize = AtomicUsize::new(0);
+
 /// Change default command
 pub fn set_default(magick: Magick) {
     unsafe {
@@ -57,6 +52,11 @@ pub fn set_default(magick: Magick) {
     }
 }
 
+/// Get default command
+pub fn default() -> &'static Magick {
+    unsafe { &*std::ptr::addr_of!(DEFAULT) }
+}
+
 impl Magick {
     /// Get size of image using identify command
     pub fn get_image_shape<P: AsRef<Path>>(&self, path: P) -> Result<(usize, usize), Error> {
@@ -68,7 +68,13 @@ impl Magick {
 
         let shape = match identify {
             Ok(shape) => shape,
-            Err(_) => return Err(Error::InvalidImageShape),
+            Err(err) => {
+                return Err(command_error(
+                    &self.identify.join(" "),
+                    String::from("Unable to start command"),
+                    Some(err),
+                ))
+            }
         };
 
         let shape = match String::from_utf8(shape.stdout) {
@@ -91,45 +97,138 @@ impl Magick {
         }
     }
 
+    /// Get the number of channels and the bit depth of an image using identify command. Gray
+    /// images have one color channel and images in any other color space three, followed by an
+    /// alpha channel when the image has one
+    pub fn get_image_layout<P: AsRef<Path>>(&self, path: P) -> Result<(usize, usize), Error> {
+        let output = Command::new(self.identify[0])
+            .args(self.identify[1..].iter())
+            .args(["-format", "%z %[channels] "])
+            .arg(path.as_ref())
+            .output();
+
+        let output = match output {
+            Ok(output) => output,
+            Err(err) => {
+                return Err(command_error(
+                    &self.identify.join(" "),
+                    String::from("Unable to start command"),
+                    Some(err),
+                ))
+            }
+        };
+
+        // Only the first frame is used
+        let output = String::from_utf8_lossy(&output.stdout).to_ascii_lowercase();
+        let mut fields = output.split_whitespace();
+        let depth = match fields.next().map(str::parse::<usize>) {
+            Some(Ok(depth)) => depth,
+            _ => return Err(Error::InvalidImageData),
+        };
+        let channels = match fields.next() {
+            Some(space) if space.starts_with("gray") => 1 + space.ends_with('a') as usize,
+            Some(space) => 3 + space.ends_with('a') as usize,
+            None => 4,
+        };
+        Ok((channels, depth))
+    }
+
     /// Read image from disk using ImageMagick/GraphicsMagick
     pub fn read<P: AsRef<Path>, T: Type, C: Color>(
         &self,
         path: P,
     ) -> Result<ImageBuf<T, C>, Error> {
-        let (width, height) = match self.get_image_shape(&path) {
-            Ok((width, height)) => (width, height),
-            Err(e) => return Err(e),
-        };
+        let mut image = ImageBuf::new(0, 0);
+        self.read_into(path, &mut image)?;
+        Ok(image)
+    }
+
+    /// Error for a convert command that couldn't be started or waited for
+    fn spawn_error(&self, err: std::io::Error) -> Error {
+        command_error(
+            &self.convert.join(" "),
+            String::from("Unable to start command"),
+            Some(err),
+        )
+    }
+
+    /// Error for a convert command whose input couldn't be written
+    fn write_error(&self, err: std::io::Error) -> Error {
+        command_error(
+            &self.convert.join(" "),
+            String::from("Unable to write image data"),
+            Some(err),
+        )
+    }
+
+    /// Read image from disk into an existing image, which is resized if it doesn't match the
+    /// size of the file
+    pub fn read_into<P: AsRef<Path>, T: Type, C: Color>(
+        &self,
+        path: P,
+        image: &mut ImageBuf<T, C>,
+    ) -> Result<(), Error> {
+        self.read_path(path.as_ref(), image)
+            .map_err(|err| err.with_path(path))
+    }
+
+    fn read_path<T: Type, C: Color>(
+        &self,
+        path: &Path,
+        image: &mut ImageBuf<T, C>,
+    ) -> Result<(), Error> {
+        let (width, height) = self.get_image_shape(path)?;
 
         let kind = kind::<C>();
         let mut cmd = Command::new(self.convert[0]);
-        cmd.args(self.convert[1..].iter()).arg(path.as_ref());
+        cmd.args(self.convert[1..].iter())
+            .arg(path)
+            .stdout(Stdio::piped());
         depth::<T, C>(&mut cmd);
         cmd.arg(kind);
 
-        let mut cmd = match cmd.output() {
-            Ok(cmd) => cmd,
-            Err(_) => return Err(Error::InvalidImageData),
-        };
-
-        let data = unsafe {
-            Vec::from_raw_parts(
-                cmd.stdout.as_mut_ptr() as *mut T,
-                cmd.stdout.len() / std::mem::size_of::<T>(),
-                cmd.stdout.capacity() / std::mem::size_of::<T>(),
-            )
+        let proc = match cmd.spawn() {
+            Ok(c) => c,
+            Err(err) => return Err(self.spawn_error(err)),
         };
 
-        std::mem::forget(cmd);
-
-        Ok(ImageBuf::new_from(width, height, data))
+        image.reshape(width, height);
+        self.read_output(proc, image)
     }
 
-    /// Write image to disk using ImageMagick/GraphicsMagick
+    /// Write image to disk using ImageMagick/GraphicsMagick, the ICC profile of the image is
+    /// embedded when the format supports it
     pub fn write<P: AsRef<Path>, T: Type, C: Color, I: Image<T, C>>(
         &self,
         path: P,
         image: &I,
+    ) -> Result<(), Error> {
+        // The profile is passed using a temporary file, which is removed once the image is written
+        let profile = match image.profile() {
+            Some(profile) => {
+                let n = PROFILES.fetch_add(1, Ordering::Relaxed);
+                let name = format!("image2-{}-{}.icc", std::process::id(), n);
+                let path = std::env::temp_dir().join(name);
+                if let Err(err) = std::fs::write(&path, profile) {
+                    return Err(Error::from(err).with_path(path));
+                }
+                Some(path)
+            }
+            None => None,
+        };
+
+        let result = self.write_with_profile(path.as_ref(), image, profile.as_deref());
+        if let Some(profile) = profile {
+            let _ = std::fs::remove_file(profile);
+        }
+        result.map_err(|err| err.with_path(path))
+    }
+
+    fn write_with_profile<T: Type, C: Color, I: Image<T, C>>(
+        &self,
+        path: &Path,
+        image: &I,
+        profile: Option<&Path>,
     ) -> Result<(), Error> {
         let kind = kind::<C>();
         let (width, height, _) = image.shape();
@@ -137,27 +236,33 @@ impl Magick {
         let mut cmd = Command::new(self.convert[0]);
         cmd.args(self.convert[1..].iter()).stdin(Stdio::piped());
         depth::<T, C>(&mut cmd);
-        cmd.args(&["-size", size.as_str()])
-            .arg(kind)
-            .arg(path.as_ref());
+        cmd.args(&["-size", size.as_str()]).arg(kind);
+        if let Some(profile) = profile {
+            cmd.arg("-profile").arg(profile);
+        }
+        cmd.arg(path);
 
         let mut proc = match cmd.spawn() {
             Ok(c) => c,
-            Err(_) => return Err(Error::UnableToExecuteCommand),
+            Err(err) => return Err(self.spawn_error(err)),
         };
 
         {
             let mut stdin = proc.stdin.take().unwrap();
-            match stdin.write_all(image.buffer()) {
-                Ok(()) => (),
-                Err(_) => return Err(Error::ErrorWritingImage),
+            if let Err(err) = stdin.write_all(image.buffer()) {
+                return Err(self.write_error(err));
             }
             let _ = stdin.flush();
         }
 
         match proc.wait() {
-            Ok(_) => Ok(()),
-            Err(_) => Err(Error::UnableToExecuteCommand),
+            Ok(status) if status.success() => Ok(()),
+            Ok(status) => Err(command_error(
+                &self.convert.join(" "),
+                format!("Unable to write image: {}", status),
+                None,
+            )),
+            Err(err) => Err(self.spawn_error(err)),
         }
     }
 
@@ -166,6 +271,17 @@ impl Magick {
         &self,
         format: &str,
         image: &I,
+    ) -> Result<Vec<u8>, Error> {
+        self.encode_with(format, image, &[])
+    }
+
+    /// Encode image to an in-memory buffer, passing additional options such as `-quality` to
+    /// the convert command
+    pub fn encode_with<T: Type, C: Color, I: Image<T, C>>(
+        &self,
+        format: &str,
+        image: &I,
+        args: &[&str],
     ) -> Result<Vec<u8>, Error> {
         let kind = kind::<C>();
         let (width, height, _) = image.shape();
@@ -175,41 +291,205 @@ impl Magick {
             .stdin(Stdio::piped())
             .stdout(Stdio::piped());
         depth::<T, C>(&mut cmd);
-        cmd.args(&["-size", size.as_str()])
+        cmd.args(["-size", size.as_str()])
             .arg(&kind)
+            .args(args)
             .arg(format!("{}:-", format));
 
         let mut proc = match cmd.spawn() {
             Ok(c) => c,
-            Err(_) => return Err(Error::UnableToExecuteCommand),
+            Err(err) => return Err(self.spawn_error(err)),
         };
 
         {
             let mut stdin = proc.stdin.take().unwrap();
-            match stdin.write_all(image.buffer()) {
-                Ok(()) => (),
-                Err(_) => return Err(Error::ErrorWritingImage),
+            if let Err(err) = stdin.write_all(image.buffer()) {
+                return Err(self.write_error(err));
             }
             let _ = stdin.flush();
         }
 
-        match proc.wait() {
-            Ok(_) => {
-                let mut buffer: Vec<u8> = Vec::new();
-                match proc.stdout.unwrap().read_to_end(&mut buffer) {
-                    Ok(_) => (),
-                    Err(_) => return Err(Error::InvalidImageData),
+        // Reading the output while waiting avoids blocking when it doesn't fit in the pipe
+        match proc.wait_with_output() {
+            Ok(output) if output.status.success() => Ok(output.stdout),
+            Ok(output) => Err(command_error(
+                &self.convert.join(" "),
+                format!("Unable to encode {}: {}", format, output.status),
+                None,
+            )),
+            Err(err) => Err(self.spawn_error(err)),
+        }
+    }
+
+    /// Decode an in-memory image with the given format and size using
+    /// ImageMagick/GraphicsMagick
+    pub fn decode<T: Type, C: Color>(
+        &self,
+        format: &str,
+        data: &[u8],
+        width: usize,
+        height: usize,
+    ) -> Result<ImageBuf<T, C>, Error> {
+        let mut image = ImageBuf::new(width, height);
+        self.decode_into(format, data, width, height, &mut image)?;
+        Ok(image)
+    }
+
+    /// Decode an in-memory image with the given format and size into an existing image, which is
+    /// resized if it doesn't match `width` and `height`
+    pub fn decode_into<T: Type, C: Color>(
+        &self,
+        format: &str,
+        data: &[u8],
+        width: usize,
+        height: usize,
+        image: &mut ImageBuf<T, C>,
+    ) -> Result<(), Error> {
+        let kind = kind::<C>();
+        let mut cmd = Command::new(self.convert[0]);
+        cmd.args(self.convert[1..].iter())
+            .arg(format!("{}:-", format))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped());
+        depth::<T, C>(&mut cmd);
+        cmd.arg(kind);
+
+        let mut proc = match cmd.spawn() {
+            Ok(c) => c,
+            Err(err) => return Err(self.spawn_error(err)),
+        };
+
+        {
+            let mut stdin = proc.stdin.take().unwrap();
+            if let Err(err) = stdin.write_all(data) {
+                return Err(self.write_error(err));
+            }
+        }
+
+        image.reshape(width, height);
+        self.read_output(proc, image)
+    }
+}
+
+impl Magick {
+    /// Convert a single file, `options` are passed to the convert command between the input and
+    /// output files
+    fn convert_file(&self, input: &Path, output: &Path, options: &[&str]) -> Result<(), Error> {
+        let result = Command::new(self.convert[0])
+            .args(self.convert[1..].iter())
+            .arg(input)
+            .args(options)
+            .arg(output)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .output();
+
+        match result {
+            Ok(result) if result.status.success() => Ok(()),
+            Ok(result) => Err(command_error(
+                &self.convert.join(" "),
+                String::from_utf8_lossy(&result.stderr).trim().to_string(),
+                None,
+            )
+            .with_path(input)),
+            Err(err) => Err(self.spawn_error(err).with_path(input)),
+        }
+    }
+
+    /// Convert each `(input, output)` pair of files, running at most `concurrency` convert
+    /// commands at the same time, 0 uses one command per CPU. `options` such as `-resize` or
+    /// `-quality` are passed to every command. Returns the result of each conversion in the same
+    /// order as `inputs`
+    pub fn batch_convert<P: AsRef<Path> + Sync, Q: AsRef<Path> + Sync>(
+        &self,
+        inputs: &[(P, Q)],
+        options: &[&str],
+        concurrency: usize,
+    ) -> Vec<Result<(), Error>> {
+        let concurrency = match concurrency {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
+
+        let next = AtomicUsize::new(0);
+        let mut results: Vec<Option<Result<(), Error>>> = inputs.iter().map(|_| None).collect();
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..concurrency.min(inputs.len()))
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut done = Vec::new();
+                        loop {
+                            let index = next.fetch_add(1, Ordering::Relaxed);
+                            let (input, output) = match inputs.get(index) {
+                                Some(paths) => paths,
+                                None => return done,
+                            };
+                            let result =
+                                self.convert_file(input.as_ref(), output.as_ref(), options);
+                            done.push((index, result));
+                        }
+                    })
+                })
+                .collect();
+
+            for worker in workers {
+                for (index, result) in worker.join().unwrap() {
+                    results[index] = Some(result);
                 }
-                Ok(buffer)
             }
-            Err(_) => Err(Error::UnableToExecuteCommand),
+        });
+
+        results.into_iter().map(Option::unwrap).collect()
+    }
+}
+
+impl Magick {
+    /// Read the raw pixels written to stdout by `proc` directly into `image`, which already has
+    /// the expected size
+    fn read_output<T: Type, C: Color>(
+        &self,
+        mut proc: Child,
+        image: &mut ImageBuf<T, C>,
+    ) -> Result<(), Error> {
+        let mut stdout = proc.stdout.take().unwrap();
+        let data = super::image_bytes(image);
+        let mut filled = 0;
+        while filled < data.len() {
+            match stdout.read(&mut data[filled..]) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => filled += n,
+            }
+        }
+
+        // Any output that is left means the image has a different size
+        let extra = matches!(stdout.read(&mut [0]), Ok(n) if n > 0);
+        drop(stdout);
+
+        match proc.wait() {
+            Ok(status) if !status.success() => Err(command_error(
+                &self.convert.join(" "),
+                format!("Unable to read image: {}", status),
+                None,
+            )),
+            Ok(_) if filled < data.len() || extra => Err(Error::InvalidImageShape),
+            Ok(_) => Ok(()),
+            Err(err) => Err(self.spawn_error(err)),
         }
     }
 }
 
 /// Read image from disk using default command-line tool
 pub fn read<P: AsRef<Path>, T: Type, C: Color>(path: P) -> Result<ImageBuf<T, C>, Error> {
-    unsafe { DEFAULT.read(path) }
+    default().read(path)
+}
+
+/// Read image from disk into an existing image using default command-line tool
+pub fn read_into<P: AsRef<Path>, T: Type, C: Color>(
+    path: P,
+    image: &mut ImageBuf<T, C>,
+) -> Result<(), Error> {
+    default().read_into(path, image)
 }
 
 /// Write image to disk using default command-line tool
@@ -217,5 +497,56 @@ pub fn write<P: AsRef<Path>, T: Type, C: Color, I: Image<T, C>>(
     path: P,
     image: &I,
 ) -> Result<(), Error> {
-    unsafe { DEFAULT.write(path, image) }
+    default().write(path, image)
+}
+
+/// Convert each `(input, output)` pair of files using default command-line tool, see
+/// `Magick::batch_convert`
+pub fn batch_convert<P: AsRef<Path> + Sync, Q: AsRef<Path> + Sync>(
+    inputs: &[(P, Q)],
+    options: &[&str],
+    concurrency: usize,
+) -> Vec<Result<(), Error>> {
+    default().batch_convert(inputs, options, concurrency)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_magick_batch_convert() {
+        // Copying files behaves like converting them without options
+        let copy = Magick {
+            identify: &["true"],
+            convert: &["cp"],
+        };
+
+        let mut inputs: Vec<(String, String)> = (0..5)
+            .map(|i| {
+                let input = format!("test/test-batch-convert{}.txt", i);
+                std::fs::write(&input, i.to_string()).unwrap();
+                (input, format!("test/test-batch-convert{}-out.txt", i))
+            })
+            .collect();
+        inputs.insert(
+            2,
+            ("test/missing.txt".into(), "test/missing-out.txt".into()),
+        );
+
+        let results = copy.batch_convert(&inputs, &[], 2);
+        assert_eq!(results.len(), 6);
+        let err = results[2].as_ref().unwrap_err();
+        assert_eq!(err.path(), Some(Path::new("test/missing.txt")));
+        assert!(matches!(err.inner(), Error::Command { command, .. } if command == "cp"));
+        assert!(err.to_string().contains("missing.txt"));
+        for (i, (_, output)) in inputs.iter().enumerate().filter(|(i, _)| *i != 2) {
+            assert!(results[i].is_ok());
+            assert_eq!(
+                std::fs::read_to_string(output).unwrap(),
+                std::fs::read_to_string(&inputs[i].0).unwrap()
+            );
+        }
+        assert!(copy.batch_convert::<&str, &str>(&[], &[], 0).is_empty());
+    }
 }
@@ -1,10 +1,25 @@
+pub mod any;
+pub mod gif;
 pub mod magick;
+pub mod npy;
+pub mod png;
+pub mod progressive;
 mod stb;
+pub mod webp;
 
+#[cfg(feature = "gst")]
+pub mod gst;
+#[cfg(feature = "video")]
+pub mod rtsp;
 #[cfg(feature = "v4l")]
 pub mod v4l;
+#[cfg(feature = "video")]
+pub mod video;
 
+use std::cell::RefCell;
+use std::io::Read;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::color::Color;
 use crate::error::Error;
@@ -13,8 +28,20 @@ use crate::image_buf::ImageBuf;
 use crate::image_ptr::{Free, ImagePtr};
 use crate::ty::Type;
 
+pub use self::any::{guess_format, Format};
 pub use self::stb::*;
 
+/// Frames of an animation along with the time each frame is shown
+pub type Animation<T, C> = Vec<(ImageBuf<T, C>, std::time::Duration)>;
+
+/// Number of times an animation is played
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repeat {
+    Infinite,
+    /// Repeat the animation `n` times after it is played once
+    Finite(u16),
+}
+
 macro_rules! cstring {
     ($s:expr) => {
         format!("{}\0", $s);
@@ -35,6 +62,64 @@ macro_rules! path {
     };
 }
 
+static AUTO_ORIENT: AtomicBool = AtomicBool::new(false);
+
+/// Rotate and flip images returned by `read`, `read_into`, `decode` and `decode_into` according
+/// to their EXIF orientation tag, this is disabled by default
+pub fn set_auto_orient(enabled: bool) {
+    AUTO_ORIENT.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns true when images are rotated according to their EXIF orientation when loaded
+pub fn auto_orient() -> bool {
+    AUTO_ORIENT.load(Ordering::Relaxed)
+}
+
+/// Set the resolution and ICC profile of `image` from the file in `data` and apply its EXIF
+/// orientation when auto-orientation is enabled, missing or invalid metadata is ignored
+pub(crate) fn load_metadata<T: Type, C: Color>(data: &[u8], image: &mut ImageBuf<T, C>) {
+    let resolution = crate::metadata::resolution::decode(data);
+    image.set_resolution(resolution.ok().flatten());
+    let profile = crate::metadata::icc::decode(data).ok().flatten();
+    image.set_profile(profile.filter(|p| crate::metadata::icc::is_compatible::<C>(p)));
+    if !auto_orient() {
+        return;
+    }
+
+    let exif = crate::metadata::exif::Exif::decode(data);
+    if let Some(orientation) = exif.ok().flatten().and_then(|exif| exif.orientation()) {
+        image.apply_orientation(orientation);
+    }
+}
+
+/// The data of an image as bytes
+pub(crate) fn image_bytes<T: Type, C: Color>(image: &mut ImageBuf<T, C>) -> &mut [u8] {
+    let data = image.data_mut();
+    unsafe {
+        std::slice::from_raw_parts_mut(data.as_mut_ptr() as *mut u8, std::mem::size_of_val(data))
+    }
+}
+
+/// Store frame `index` of a decoded animation, reusing the image that is already stored there.
+/// Frames are stored in order, so `index` is never larger than the number of frames
+pub(crate) fn store_frame<T: Type, C: Color>(
+    frames: &mut Animation<T, C>,
+    index: usize,
+    width: usize,
+    height: usize,
+    pixels: &[T],
+    delay: std::time::Duration,
+) {
+    match frames.get_mut(index) {
+        Some((image, d)) => {
+            image.reshape(width, height);
+            image.data_mut().copy_from_slice(pixels);
+            *d = delay;
+        }
+        None => frames.push((ImageBuf::new_from(width, height, pixels.to_vec()), delay)),
+    }
+}
+
 /// Read an image with u8 components using stb_image
 pub fn read_u8<'a, P: AsRef<Path>, C: Color>(path: P) -> Result<ImagePtr<'a, u8, C>, Error> {
     let f = path!(path);
@@ -118,13 +203,62 @@ pub fn read_f32<'a, P: AsRef<Path>, C: Color>(path: P) -> Result<ImagePtr<'a, f3
 
 /// Read any type of image using stb_image
 pub fn read<'a, P: AsRef<Path>, T: Type, C: Color>(path: P) -> Result<ImageBuf<T, C>, Error> {
-    let x = match read_u8(&path) {
-        Ok(x) => x,
-        Err(_) => magick::read(path)?.to_image_ptr(),
-    };
-    let mut y = ImageBuf::new(x.width(), x.height());
-    x.convert_type(&mut y);
-    Ok(y)
+    let mut image = ImageBuf::new(0, 0);
+    read_into(path, &mut image)?;
+    Ok(image)
+}
+
+/// Read any type of image into an existing image, which is resized if it doesn't match the size
+/// of the file
+///
+/// Note: the file is read into a buffer that is reused by later calls on the same thread, and
+/// `.npy` data is decoded directly into `image`. stb_image still decodes each file into a
+/// temporary buffer before it is converted into `image`, ImageMagick writes directly into `image`
+/// but starts a new process for every file.
+pub fn read_into<P: AsRef<Path>, T: Type, C: Color>(
+    path: P,
+    image: &mut ImageBuf<T, C>,
+) -> Result<(), Error> {
+    read_path(path.as_ref(), image).map_err(|err| err.with_path(path))
+}
+
+thread_local! {
+    /// Contents of the last file read by `read_into` on this thread
+    static READ_BUFFER: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+fn read_path<T: Type, C: Color>(path: &Path, image: &mut ImageBuf<T, C>) -> Result<(), Error> {
+    READ_BUFFER.with(|buffer| match buffer.try_borrow_mut() {
+        Ok(mut buffer) => {
+            buffer.clear();
+            std::fs::File::open(path)?.read_to_end(&mut buffer)?;
+            decode_path(path, &buffer, image)
+        }
+        // Only reachable if decoding reads another file, which then gets its own buffer
+        Err(_) => decode_path(path, &std::fs::read(path)?, image),
+    })
+}
+
+fn decode_path<T: Type, C: Color>(
+    path: &Path,
+    data: &[u8],
+    image: &mut ImageBuf<T, C>,
+) -> Result<(), Error> {
+    // The file is only read once, the metadata is loaded from the same data
+    if guess_format(data) == Some(Format::Npy) {
+        return npy::decode_into(data, image);
+    }
+
+    match decode_u8::<_, C>(data) {
+        Ok(x) => {
+            image.reshape(x.width(), x.height());
+            x.convert_type(image);
+        }
+        Err(_) => magick::read_into(path, image)?,
+    }
+
+    load_metadata(data, image);
+    Ok(())
 }
 
 /// Decode an image with u8 components from memory
@@ -146,6 +280,10 @@ pub fn decode_u8<'a, Data: AsRef<[u8]>, C: Color>(
         )
     };
 
+    if ptr.is_null() {
+        return Err(Error::InvalidImageData);
+    }
+
     Ok(ImagePtr::new(
         width as usize,
         height as usize,
@@ -173,6 +311,10 @@ pub fn decode_u16<'a, Data: AsRef<[u8]>, C: Color>(
         )
     };
 
+    if ptr.is_null() {
+        return Err(Error::InvalidImageData);
+    }
+
     Ok(ImagePtr::new(
         width as usize,
         height as usize,
@@ -200,6 +342,10 @@ pub fn decode_f32<'a, Data: AsRef<[u8]>, C: Color>(
         )
     };
 
+    if ptr.is_null() {
+        return Err(Error::InvalidImageData);
+    }
+
     Ok(ImagePtr::new(
         width as usize,
         height as usize,
@@ -212,10 +358,22 @@ pub fn decode_f32<'a, Data: AsRef<[u8]>, C: Color>(
 pub fn decode<'a, Data: AsRef<[u8]>, T: Type, C: Color>(
     data: Data,
 ) -> Result<ImageBuf<T, C>, Error> {
-    let x = decode_u8(data)?;
-    let mut y = ImageBuf::new(x.width(), x.height());
-    x.convert_type(&mut y);
-    Ok(y)
+    let mut image = ImageBuf::new(0, 0);
+    decode_into(data, &mut image)?;
+    Ok(image)
+}
+
+/// Decode an image from memory into an existing image, which is resized if it doesn't match the
+/// size of the encoded image
+pub fn decode_into<Data: AsRef<[u8]>, T: Type, C: Color>(
+    data: Data,
+    image: &mut ImageBuf<T, C>,
+) -> Result<(), Error> {
+    let x = decode_u8::<_, C>(&data)?;
+    image.reshape(x.width(), x.height());
+    x.convert_type(image);
+    load_metadata(data.as_ref(), image);
+    Ok(())
 }
 
 /// Write png image to disk
@@ -351,13 +509,53 @@ pub fn write_hdr_f32<C: Color, I: Image<f32, C>, P: AsRef<Path>>(
     Ok(())
 }
 
-/// Write image to disk, the output type is determined by the extension of the output image path
+/// Write image to disk, the output type is determined by the extension of the output image path.
+/// The resolution and ICC profile of the image are stored in PNG, JPEG and TIFF files and the
+/// profile in WebP files. Other formats written using ImageMagick keep the profile when the format
+/// supports one, BMP, TGA, HDR and NPY files store neither.
 pub fn write<P: AsRef<Path>, T: Type, C: Color, I: Image<T, C>>(
     path: P,
     image: &I,
 ) -> Result<(), Error> {
-    let path = path.as_ref();
+    write_path(path.as_ref(), image).map_err(|err| err.with_path(path))
+}
+
+fn write_path<T: Type, C: Color, I: Image<T, C>>(path: &Path, image: &I) -> Result<(), Error> {
+    write_data(path, image)?;
+
+    let ext = path.extension().and_then(|ext| ext.to_str());
+    let ext = ext.map(|ext| ext.to_ascii_lowercase());
+    let has_metadata = image.resolution().is_some() || image.profile().is_some();
+    match ext.as_deref() {
+        Some("png" | "jpg" | "jpeg" | "tif" | "tiff") if has_metadata => {
+            let data = encode_metadata(std::fs::read(path)?, image)?;
+            std::fs::write(path, data)?;
+            Ok(())
+        }
+        Some("webp") if image.profile().is_some() => {
+            let data = webp::encode_profile(std::fs::read(path)?, image.profile())?;
+            std::fs::write(path, data)?;
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Store the resolution and ICC profile of `image` in an encoded PNG, JPEG or TIFF file
+fn encode_metadata<T: Type, C: Color, I: Image<T, C>>(
+    mut data: Vec<u8>,
+    image: &I,
+) -> Result<Vec<u8>, Error> {
+    if let Some(resolution) = image.resolution() {
+        data = crate::metadata::resolution::encode(&data, &resolution)?;
+    }
+    if let Some(profile) = image.profile() {
+        data = crate::metadata::icc::encode(&data, Some(profile))?;
+    }
+    Ok(data)
+}
 
+fn write_data<T: Type, C: Color, I: Image<T, C>>(path: &Path, image: &I) -> Result<(), Error> {
     match path.extension() {
         Some(s) => match s.to_str() {
             Some("jpg") | Some("jpeg") | Some("JPG") | Some("JPEG") => {
@@ -385,11 +583,11 @@ pub fn write<P: AsRef<Path>, T: Type, C: Color, I: Image<T, C>>(
                 image.convert_type(&mut tmp);
                 write_png_u8(path, &tmp)
             }
-            Some(_) => Ok(magick::write(path, image)?),
+            Some("npy") => npy::write(path, image),
+            Some(_) => magick::write(path, image),
         },
-        None => Err(Error::Message(format!(
-            "Unable to determine output format: {:?}",
-            path,
+        None => Err(Error::Message(String::from(
+            "Unable to determine output format",
         ))),
     }
 }
@@ -417,12 +615,45 @@ pub fn encode_png_u8<C: Color, I: Image<u8, C>>(image: &I) -> Result<Vec<u8>, Er
 
     unsafe { crate::image_ptr::free(ptr as *mut std::ffi::c_void) }
 
-    Ok(dest)
+    encode_metadata(dest, image)
+}
+
+/// Encode u8 image to jpg in memory, `quality` is between 1 and 100
+pub fn encode_jpg_u8<C: Color, I: Image<u8, C>>(image: &I, quality: i32) -> Result<Vec<u8>, Error> {
+    unsafe extern "C" fn append(
+        context: *mut std::ffi::c_void,
+        data: *mut std::ffi::c_void,
+        size: std::os::raw::c_int,
+    ) {
+        let out = &mut *(context as *mut Vec<u8>);
+        out.extend_from_slice(std::slice::from_raw_parts(data as *const u8, size as usize));
+    }
+
+    let (w, h, c) = image.shape();
+    let mut dest: Vec<u8> = Vec::new();
+    let result = unsafe {
+        stbi_write_jpg_to_func(
+            Some(append),
+            &mut dest as *mut Vec<u8> as *mut std::ffi::c_void,
+            w as i32,
+            h as i32,
+            c as i32,
+            image.data().as_ptr() as *const std::ffi::c_void,
+            quality,
+        )
+    };
+
+    if result == 0 {
+        return Err(Error::Message(String::from("Unable to encode JPEG image")));
+    }
+
+    encode_metadata(dest, image)
 }
 
 /// Encode image to png in memory
 pub fn encode_png<T: Type, C: Color, I: Image<T, C>>(image: &I) -> Result<Vec<u8>, Error> {
     let mut tmp = ImageBuf::new(image.width(), image.height());
+    tmp.copy_metadata(image);
     image.convert_type(&mut tmp);
     encode_png_u8(&tmp)
 }
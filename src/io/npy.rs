@@ -0,0 +1,423 @@
+//! Read and write NumPy `.npy` files, along with `.npz` archives when the `npz` feature is
+//! enabled
+//!
+//! Images are stored as arrays with the shape `(height, width, channels)`. When reading, arrays
+//! with the shape `(height, width)` are accepted for single channel images and data stored
+//! using a different element type is converted to the requested type.
+
+use std::fs::File;
+#[cfg(feature = "npz")]
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+
+use crate::color::Color;
+use crate::error::{with_path, Error};
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::ty::Type;
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+
+/// The numpy type descriptor of `T` in native byte order, for example: `<f4`
+fn descr<T: Type>() -> String {
+    let size = std::mem::size_of::<T>();
+    let order = if size == 1 {
+        '|'
+    } else if cfg!(target_endian = "little") {
+        '<'
+    } else {
+        '>'
+    };
+    let kind = if T::is_float() {
+        'f'
+    } else if T::is_signed() {
+        'i'
+    } else {
+        'u'
+    };
+    format!("{}{}{}", order, kind, size)
+}
+
+/// Encode an image as a `.npy` file in memory
+pub fn encode<T: Type, C: Color, I: Image<T, C>>(image: &I) -> Vec<u8> {
+    let (width, height, channels) = image.shape();
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': ({}, {}, {}), }}",
+        descr::<T>(),
+        height,
+        width,
+        channels
+    );
+
+    // The magic string, version, header length, header and newline are padded to a multiple of
+    // 64 bytes
+    let unpadded = MAGIC.len() + 4 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+    header.push('\n');
+
+    let mut data = Vec::with_capacity(MAGIC.len() + 4 + header.len() + image.total_bytes());
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&[1, 0]);
+    data.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    data.extend_from_slice(header.as_bytes());
+    data.extend_from_slice(image.buffer());
+    data
+}
+
+/// Returns the value of `key` in a header dictionary, up to the end of the header
+fn field<'a>(header: &'a str, key: &str) -> Result<&'a str, Error> {
+    let pattern = format!("'{}':", key);
+    match header.find(&pattern) {
+        Some(index) => Ok(header[index + pattern.len()..].trim_start()),
+        None => Err(Error::Message(format!("Missing npy header field: {}", key))),
+    }
+}
+
+/// Parsed `.npy` header
+struct Header {
+    descr: String,
+    fortran_order: bool,
+    shape: Vec<usize>,
+}
+
+fn parse_header(header: &str) -> Result<Header, Error> {
+    let invalid = || Error::Message(format!("Invalid npy header: {}", header.trim()));
+
+    let mut descr = field(header, "descr")?.chars();
+    let quote = descr.next().ok_or_else(invalid)?;
+    let descr = descr.as_str().split(quote).next().ok_or_else(invalid)?;
+
+    let fortran_order = field(header, "fortran_order")?.starts_with("True");
+
+    let shape = field(header, "shape")?;
+    let end = shape.find(')').ok_or_else(invalid)?;
+    let shape = shape[..end]
+        .trim_start_matches('(')
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| s.trim_end_matches('L').parse().map_err(|_| invalid()))
+        .collect::<Result<Vec<usize>, Error>>()?;
+
+    Ok(Header {
+        descr: descr.to_string(),
+        fortran_order,
+        shape,
+    })
+}
+
+impl Header {
+    /// Height, width and number of channels of the image stored in the array
+    fn dims(&self) -> Result<(usize, usize, usize), Error> {
+        match self.shape[..] {
+            [height, width] => Ok((height, width, 1)),
+            [height, width, channels] => Ok((height, width, channels)),
+            _ => Err(Error::Message(format!(
+                "Expected an array with 2 or 3 dimensions, got {}",
+                self.shape.len()
+            ))),
+        }
+    }
+}
+
+/// Parse the header of a `.npy` file, returning it along with the offset of the data
+fn read_header(data: &[u8]) -> Result<(Header, usize), Error> {
+    if data.len() < 10 || &data[..MAGIC.len()] != MAGIC {
+        return Err(Error::Message(String::from("Not an npy file")));
+    }
+
+    let (header_len, offset) = match data[6] {
+        1 => (u16::from_le_bytes([data[8], data[9]]) as usize, 10),
+        2 | 3 if data.len() >= 12 => (
+            u32::from_le_bytes([data[8], data[9], data[10], data[11]]) as usize,
+            12,
+        ),
+        version => {
+            return Err(Error::Message(format!(
+                "Unsupported npy version: {}",
+                version
+            )))
+        }
+    };
+
+    if data.len() < offset + header_len {
+        return Err(Error::Message(String::from("Truncated npy header")));
+    }
+
+    let header = String::from_utf8_lossy(&data[offset..offset + header_len]);
+    Ok((parse_header(&header)?, offset + header_len))
+}
+
+/// Number of channels and type descriptor, like `<u2`, of the array in a `.npy` file
+pub(crate) fn layout(data: &[u8]) -> Result<(usize, String), Error> {
+    let (header, _) = read_header(data)?;
+    let (_, _, channels) = header.dims()?;
+    Ok((channels, header.descr))
+}
+
+/// Convert the raw data of an array with element type `X` into `dest`, which already has the
+/// shape of the array
+fn decode_data<X: Type, T: Type, C: Color>(
+    bytes: &[u8],
+    swap: bool,
+    fortran_order: bool,
+    dest: &mut ImageBuf<T, C>,
+) {
+    let (width, height, channels) = dest.shape();
+    let size = std::mem::size_of::<X>();
+
+    // Offset of the value stored at `index` in the image
+    let offset = |index: usize| {
+        if !fortran_order {
+            return index * size;
+        }
+        let (c, x, y) = (
+            index % channels,
+            index / channels % width,
+            index / channels / width,
+        );
+        (y + height * (x + width * c)) * size
+    };
+
+    if descr::<X>() == descr::<T>() {
+        // Same type, copy the data without normalizing it
+        let data = super::image_bytes(dest);
+        if !swap && !fortran_order {
+            data.copy_from_slice(bytes);
+            return;
+        }
+
+        for (index, value) in data.chunks_exact_mut(size).enumerate() {
+            let i = offset(index);
+            value.copy_from_slice(&bytes[i..i + size]);
+            if swap {
+                value.reverse();
+            }
+        }
+        return;
+    }
+
+    for (index, value) in dest.data_mut().iter_mut().enumerate() {
+        let i = offset(index);
+        let mut x = X::zero();
+        let b = unsafe { std::slice::from_raw_parts_mut(&mut x as *mut X as *mut u8, size) };
+        b.copy_from_slice(&bytes[i..i + size]);
+        if swap {
+            b.reverse();
+        }
+        *value = x.convert();
+    }
+}
+
+/// Decode a `.npy` file from memory
+pub fn decode<T: Type, C: Color>(data: &[u8]) -> Result<ImageBuf<T, C>, Error> {
+    let mut image = ImageBuf::new(0, 0);
+    decode_into(data, &mut image)?;
+    Ok(image)
+}
+
+/// Decode a `.npy` file from memory into an existing image, which is resized if it doesn't match
+/// the shape of the array
+pub fn decode_into<T: Type, C: Color>(
+    data: &[u8],
+    image: &mut ImageBuf<T, C>,
+) -> Result<(), Error> {
+    let (header, offset) = read_header(data)?;
+    let (height, width, channels) = header.dims()?;
+
+    if channels != C::channels() {
+        return Err(Error::InvalidColor);
+    }
+
+    let bytes = &data[offset..];
+    let descr = header.descr.as_bytes();
+    if descr.len() < 3 {
+        return Err(Error::Message(format!(
+            "Unsupported npy dtype: {}",
+            header.descr
+        )));
+    }
+
+    let swap = match descr[0] {
+        b'<' => cfg!(target_endian = "big"),
+        b'>' => cfg!(target_endian = "little"),
+        _ => false,
+    };
+
+    let unsupported = || Error::Message(format!("Unsupported npy dtype: {}", header.descr));
+    let size: usize = header
+        .descr
+        .get(2..)
+        .and_then(|size| size.parse().ok())
+        .ok_or_else(unsupported)?;
+    let len = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(channels))
+        .and_then(|n| n.checked_mul(size))
+        .ok_or_else(|| Error::Message(String::from("npy array is too large")))?;
+    if bytes.len() < len {
+        return Err(Error::Message(String::from("Truncated npy data")));
+    }
+    let bytes = &bytes[..len];
+    image.reshape(width, height);
+
+    macro_rules! decode_as {
+        ($x:ty) => {
+            decode_data::<$x, T, C>(bytes, swap, header.fortran_order, image)
+        };
+    }
+
+    match (descr[1], size) {
+        (b'u', 1) | (b'b', 1) => decode_as!(u8),
+        (b'u', 2) => decode_as!(u16),
+        (b'u', 4) => decode_as!(u32),
+        (b'u', 8) => decode_as!(u64),
+        (b'i', 4) => decode_as!(i32),
+        (b'i', 8) => decode_as!(i64),
+        (b'f', 4) => decode_as!(f32),
+        (b'f', 8) => decode_as!(f64),
+        _ => return Err(unsupported()),
+    }
+
+    Ok(())
+}
+
+/// Write an image to a `.npy` file
+pub fn write<P: AsRef<Path>, T: Type, C: Color, I: Image<T, C>>(
+    path: P,
+    image: &I,
+) -> Result<(), Error> {
+    with_path(path, |path| {
+        Ok(File::create(path)?.write_all(&encode(image))?)
+    })
+}
+
+/// Read an image from a `.npy` file
+pub fn read<P: AsRef<Path>, T: Type, C: Color>(path: P) -> Result<ImageBuf<T, C>, Error> {
+    with_path(path, |path| decode(&std::fs::read(path)?))
+}
+
+/// Read an image from a `.npy` file into an existing image, which is resized if it doesn't match
+/// the shape of the array
+pub fn read_into<P: AsRef<Path>, T: Type, C: Color>(
+    path: P,
+    image: &mut ImageBuf<T, C>,
+) -> Result<(), Error> {
+    with_path(path, |path| decode_into(&std::fs::read(path)?, image))
+}
+
+/// Write images to an uncompressed `.npz` archive, like `numpy.savez`
+#[cfg(feature = "npz")]
+pub fn write_npz<P: AsRef<Path>, T: Type, C: Color, I: Image<T, C>>(
+    path: P,
+    images: &[(&str, &I)],
+) -> Result<(), Error> {
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Stored)
+        .large_file(true);
+    with_path(path, |path| {
+        let mut archive = zip::ZipWriter::new(File::create(path)?);
+        for (name, image) in images {
+            archive.start_file(format!("{}.npy", name), options)?;
+            archive.write_all(&encode(*image))?;
+        }
+        archive.finish()?;
+        Ok(())
+    })
+}
+
+/// Read the array called `name` from a `.npz` archive, compressed archives created using
+/// `numpy.savez_compressed` are supported
+#[cfg(feature = "npz")]
+pub fn read_npz<P: AsRef<Path>, T: Type, C: Color>(
+    path: P,
+    name: &str,
+) -> Result<ImageBuf<T, C>, Error> {
+    with_path(path, |path| {
+        let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+        let mut file = archive.by_name(&format!("{}.npy", name))?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        decode(&data)
+    })
+}
+
+/// The names of the arrays stored in a `.npz` archive
+#[cfg(feature = "npz")]
+pub fn npz_names<P: AsRef<Path>>(path: P) -> Result<Vec<String>, Error> {
+    with_path(path, |path| {
+        let archive = zip::ZipArchive::new(File::open(path)?)?;
+        Ok(archive
+            .file_names()
+            .map(|name| name.trim_end_matches(".npy").to_string())
+            .collect())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Gray, Rgb};
+
+    #[test]
+    fn test_npy() {
+        let mut image: ImageBuf<u16, Rgb> = ImageBuf::new(5, 3);
+        image.at_mut(4, 2).copy_from_slice(&[1, 300, 65535]);
+
+        let data = encode(&image);
+        assert_eq!(
+            data[MAGIC.len() + 4..].iter().position(|&b| b == b'\n'),
+            Some(117)
+        );
+        assert_eq!(decode::<u16, Rgb>(&data).unwrap(), image);
+        assert!(decode::<u16, Gray>(&data).is_err());
+
+        let float: ImageBuf<f32, Rgb> = decode(&data).unwrap();
+        assert_eq!(float.at(4, 2)[2], 1.0);
+
+        // Decoding into an image that is large enough reuses its data
+        let mut dest: ImageBuf<f32, Rgb> = ImageBuf::new(10, 10);
+        let ptr = dest.data().as_ptr();
+        decode_into(&data, &mut dest).unwrap();
+        assert_eq!(dest, float);
+        assert_eq!(dest.data().as_ptr(), ptr);
+
+        // A 2x3 big-endian, fortran order array created using numpy
+        let mut data = MAGIC.to_vec();
+        let header = "{'descr': '>i4', 'fortran_order': True, 'shape': (2, 3), }\n";
+        data.extend_from_slice(&[1, 0, header.len() as u8, 0]);
+        data.extend_from_slice(header.as_bytes());
+        for x in &[0i32, 3, 1, 4, 2, 5] {
+            data.extend_from_slice(&x.to_be_bytes());
+        }
+        let gray: ImageBuf<i32, Gray> = decode(&data).unwrap();
+        assert_eq!(gray.data(), &[0, 1, 2, 3, 4, 5]);
+
+        // Malformed headers are errors
+        for header in &[
+            "{'descr': \u{e9}, 'fortran_order': False, 'shape': (1, 1), }\n",
+            "{'descr': '<\u{e9}', 'fortran_order': False, 'shape': (1, 1), }\n",
+            "{'descr': '<u2', 'fortran_order': False, 'shape': (4294967296, 4294967296), }\n",
+        ] {
+            let mut data = MAGIC.to_vec();
+            data.extend_from_slice(&[1, 0, header.len() as u8, 0]);
+            data.extend_from_slice(header.as_bytes());
+            data.extend_from_slice(&[0; 64]);
+            assert!(decode::<u16, Gray>(&data).is_err());
+        }
+
+        write("test/test-npy.npy", &image).unwrap();
+        assert_eq!(read::<_, u16, Rgb>("test/test-npy.npy").unwrap(), image);
+
+        #[cfg(feature = "npz")]
+        {
+            write_npz("test/test-npy.npz", &[("a", &image), ("b", &image)]).unwrap();
+            assert_eq!(
+                read_npz::<_, u16, Rgb>("test/test-npy.npz", "b").unwrap(),
+                image
+            );
+            assert_eq!(npz_names("test/test-npy.npz").unwrap(), vec!["a", "b"]);
+        }
+    }
+}
@@ -0,0 +1,708 @@
+//! Encode and decode animated PNG (APNG) files
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use image2::io::png::{self, Encoder};
+//! use image2::io::Repeat;
+//! use image2::{ImageBuf, Rgba};
+//!
+//! let mut encoder = Encoder::create("animation.png", 320, 240)?.repeat(Repeat::Infinite);
+//! for _ in 0..10 {
+//!     let frame: ImageBuf<u8, Rgba> = ImageBuf::new(320, 240);
+//!     encoder.add_frame(&frame, Duration::from_millis(100))?;
+//! }
+//! encoder.finish()?;
+//!
+//! let frames = png::read("animation.png")?;
+//! assert_eq!(frames.len(), 10);
+//! # Ok::<(), image2::Error>(())
+//! ```
+//!
+//! Frames are stored as 8-bit RGBA, so animations made of 8-bit gray, RGB or RGBA images
+//! round-trip without loss. Only the part of each frame that changed since the previous frame is
+//! stored. When decoding, the blend and dispose operations of each frame are applied and every
+//! frame is returned at the full size of the animation. PNG files without animation chunks are
+//! decoded as a single frame.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::color::{Color, Rgba};
+use crate::error::{with_path, Error};
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::ty::Type;
+
+use super::gif::{to_rgba8, Rgba8};
+use super::{Animation, Repeat};
+
+pub(crate) const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Largest width or height allowed by the PNG specification
+const MAX_DIMENSION: usize = (1 << 31) - 1;
+
+/// What happens to the region of a frame before the next frame is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dispose {
+    None,
+    /// Clear the region to transparent black
+    Background,
+    /// Restore the region to what it was before the frame was drawn
+    Previous,
+}
+
+/// How a frame is drawn onto the canvas
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Blend {
+    /// Replace the region, including alpha
+    Source,
+    /// Alpha composite the frame over the region
+    Over,
+}
+
+/// Contents of an `fcTL` chunk, without the sequence number
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FrameControl {
+    width: usize,
+    height: usize,
+    x: usize,
+    y: usize,
+    delay_num: u16,
+    delay_den: u16,
+    dispose: Dispose,
+    blend: Blend,
+}
+
+fn read_u32(data: &[u8]) -> u32 {
+    u32::from_be_bytes([data[0], data[1], data[2], data[3]])
+}
+
+impl FrameControl {
+    fn parse(data: &[u8]) -> Result<FrameControl, Error> {
+        if data.len() < 26 {
+            return Err(Error::Message(String::from("Invalid fcTL chunk")));
+        }
+
+        let dispose = match data[24] {
+            0 => Dispose::None,
+            1 => Dispose::Background,
+            2 => Dispose::Previous,
+            x => return Err(Error::Message(format!("Invalid dispose op: {}", x))),
+        };
+        let blend = match data[25] {
+            0 => Blend::Source,
+            1 => Blend::Over,
+            x => return Err(Error::Message(format!("Invalid blend op: {}", x))),
+        };
+
+        Ok(FrameControl {
+            width: read_u32(&data[4..]) as usize,
+            height: read_u32(&data[8..]) as usize,
+            x: read_u32(&data[12..]) as usize,
+            y: read_u32(&data[16..]) as usize,
+            delay_num: u16::from_be_bytes([data[20], data[21]]),
+            delay_den: u16::from_be_bytes([data[22], data[23]]),
+            dispose,
+            blend,
+        })
+    }
+
+    fn to_bytes(self, sequence: u32) -> Vec<u8> {
+        let mut data = Vec::with_capacity(26);
+        for n in &[
+            sequence,
+            self.width as u32,
+            self.height as u32,
+            self.x as u32,
+            self.y as u32,
+        ] {
+            data.extend_from_slice(&n.to_be_bytes());
+        }
+        data.extend_from_slice(&self.delay_num.to_be_bytes());
+        data.extend_from_slice(&self.delay_den.to_be_bytes());
+        data.push(self.dispose as u8);
+        data.push(self.blend as u8);
+        data
+    }
+
+    /// A denominator of 0 means hundredths of a second
+    fn delay(&self) -> Duration {
+        let den = if self.delay_den == 0 {
+            100
+        } else {
+            u64::from(self.delay_den)
+        };
+        Duration::from_nanos(u64::from(self.delay_num) * 1_000_000_000 / den)
+    }
+}
+
+/// Delay as a fraction of a second, in milliseconds when it fits and hundredths otherwise
+fn delay_fraction(delay: Duration) -> (u16, u16) {
+    let ms = delay.as_millis();
+    if ms <= u128::from(u16::MAX) {
+        (ms as u16, 1000)
+    } else {
+        ((ms / 10).min(u128::from(u16::MAX)) as u16, 100)
+    }
+}
+
+fn crc32(kind: &[u8], data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in kind.iter().chain(data) {
+        crc ^= u32::from(b);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+pub(crate) fn write_chunk<W: Write>(out: &mut W, kind: &[u8], data: &[u8]) -> Result<(), Error> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    out.write_all(&crc32(kind, data).to_be_bytes())?;
+    Ok(())
+}
+
+/// Type and data of a chunk
+pub(crate) type Chunk<'a> = (&'a [u8], &'a [u8]);
+
+/// Split a PNG file into chunks, stopping at `IEND`
+pub(crate) fn chunks(data: &[u8]) -> Result<Vec<Chunk<'_>>, Error> {
+    if !data.starts_with(SIGNATURE) {
+        return Err(Error::Message(String::from("Invalid PNG signature")));
+    }
+
+    let mut chunks = Vec::new();
+    let mut data = &data[SIGNATURE.len()..];
+    while data.len() >= 12 {
+        let len = read_u32(data) as usize;
+        if data.len() < len + 12 {
+            break;
+        }
+
+        let kind = &data[4..8];
+        chunks.push((kind, &data[8..8 + len]));
+        if kind == b"IEND" {
+            return Ok(chunks);
+        }
+        data = &data[len + 12..];
+    }
+
+    Err(Error::Message(String::from("Truncated PNG file")))
+}
+
+/// Decompress zlib data, such as the ICC profile in an `iCCP` chunk, returns an error instead
+/// of decompressing more than `limit` bytes
+pub(crate) fn inflate(data: &[u8], limit: usize) -> Result<Vec<u8>, Error> {
+    super::progressive::inflate(data, limit)
+}
+
+/// Compress data using zlib
+pub(crate) fn deflate(data: &[u8]) -> Result<Vec<u8>, Error> {
+    if data.len() > i32::MAX as usize {
+        return Err(Error::Message(String::from(
+            "Data is too large to compress",
+        )));
+    }
+
+    let mut len = 0;
+    let ptr = unsafe {
+        super::stbi_zlib_compress(data.as_ptr() as *mut u8, data.len() as i32, &mut len, 8)
+    };
+    if ptr.is_null() {
+        return Err(Error::Message(String::from("Unable to compress data")));
+    }
+
+    let out = unsafe { std::slice::from_raw_parts(ptr, len as usize).to_vec() };
+    unsafe { super::stbi_image_free(ptr as *mut std::ffi::c_void) };
+    Ok(out)
+}
+
+/// Bounding box of the pixels that differ, as `(x, y, width, height)`
+pub(super) fn changed_region(
+    a: &[Rgba8],
+    b: &[Rgba8],
+    width: usize,
+) -> Option<(usize, usize, usize, usize)> {
+    let (mut x0, mut y0, mut x1, mut y1) = (usize::MAX, usize::MAX, 0, 0);
+    for (i, _) in a.iter().zip(b).enumerate().filter(|(_, (a, b))| a != b) {
+        let (x, y) = (i % width, i / width);
+        x0 = x0.min(x);
+        y0 = y0.min(y);
+        x1 = x1.max(x);
+        y1 = y1.max(y);
+    }
+
+    if x0 == usize::MAX {
+        None
+    } else {
+        Some((x0, y0, x1 - x0 + 1, y1 - y0 + 1))
+    }
+}
+
+/// Writes animated PNG files, see the module documentation for an example
+pub struct Encoder<W: Write> {
+    out: W,
+    width: usize,
+    height: usize,
+    repeat: Repeat,
+    /// `IHDR` of the first frame, which has the size of the animation
+    header: Option<Vec<u8>>,
+    /// Compressed frames, which are kept until `finish` because the number of frames is written
+    /// before the first frame
+    frames: Vec<(FrameControl, Vec<u8>)>,
+    previous: Option<Vec<Rgba8>>,
+}
+
+impl Encoder<BufWriter<File>> {
+    /// Create an APNG file
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        width: usize,
+        height: usize,
+    ) -> Result<Encoder<BufWriter<File>>, Error> {
+        Ok(Encoder::new(
+            BufWriter::new(with_path(path, |path| Ok(File::create(path)?))?),
+            width,
+            height,
+        ))
+    }
+}
+
+impl<W: Write> Encoder<W> {
+    /// Write an animation to `out`, repeating forever
+    pub fn new(out: W, width: usize, height: usize) -> Encoder<W> {
+        Encoder {
+            out,
+            width,
+            height,
+            repeat: Repeat::Infinite,
+            header: None,
+            frames: Vec::new(),
+            previous: None,
+        }
+    }
+
+    pub fn repeat(mut self, repeat: Repeat) -> Encoder<W> {
+        self.repeat = repeat;
+        self
+    }
+
+    /// Add a frame, which is shown for `delay` rounded to milliseconds. Gray, RGB, RGBA, BGR and
+    /// BGRA images are supported.
+    pub fn add_frame<T: Type, C: Color, I: Image<T, C>>(
+        &mut self,
+        image: &I,
+        delay: Duration,
+    ) -> Result<(), Error> {
+        if image.width() != self.width || image.height() != self.height {
+            return Err(Error::Message(format!(
+                "Expected a {}x{} image, got {}x{}",
+                self.width,
+                self.height,
+                image.width(),
+                image.height()
+            )));
+        }
+
+        let pixels = to_rgba8(image)?;
+        // Unchanged frames still need a region, a single pixel is enough
+        let (x, y, width, height) = match &self.previous {
+            Some(previous) => changed_region(previous, &pixels, self.width).unwrap_or((0, 0, 1, 1)),
+            None => (0, 0, self.width, self.height),
+        };
+
+        let data = (y..y + height)
+            .flat_map(|row| &pixels[row * self.width + x..row * self.width + x + width])
+            .flatten()
+            .copied()
+            .collect();
+        let png = super::encode_png_u8(&ImageBuf::<u8, Rgba>::new_from(width, height, data))?;
+
+        let mut idat = Vec::new();
+        for (kind, data) in chunks(&png)? {
+            if kind == b"IHDR" && self.header.is_none() {
+                self.header = Some(data.to_vec());
+            } else if kind == b"IDAT" {
+                idat.extend_from_slice(data);
+            }
+        }
+
+        let (delay_num, delay_den) = delay_fraction(delay);
+        let control = FrameControl {
+            width,
+            height,
+            x,
+            y,
+            delay_num,
+            delay_den,
+            dispose: Dispose::None,
+            blend: Blend::Source,
+        };
+        self.frames.push((control, idat));
+        self.previous = Some(pixels);
+        Ok(())
+    }
+
+    /// Write the animation, returning the underlying writer
+    pub fn finish(mut self) -> Result<W, Error> {
+        let header = match self.header.take() {
+            Some(header) => header,
+            None => return Err(Error::Message(String::from("No frames were added"))),
+        };
+
+        let plays = match self.repeat {
+            Repeat::Infinite => 0,
+            Repeat::Finite(n) => u32::from(n) + 1,
+        };
+        let mut actl = (self.frames.len() as u32).to_be_bytes().to_vec();
+        actl.extend_from_slice(&plays.to_be_bytes());
+
+        self.out.write_all(SIGNATURE)?;
+        write_chunk(&mut self.out, b"IHDR", &header)?;
+        write_chunk(&mut self.out, b"acTL", &actl)?;
+
+        let mut sequence = 0;
+        for (i, (control, data)) in self.frames.iter().enumerate() {
+            write_chunk(&mut self.out, b"fcTL", &control.to_bytes(sequence))?;
+            sequence += 1;
+            if i == 0 {
+                write_chunk(&mut self.out, b"IDAT", data)?;
+            } else {
+                let mut fdat = sequence.to_be_bytes().to_vec();
+                fdat.extend_from_slice(data);
+                write_chunk(&mut self.out, b"fdAT", &fdat)?;
+                sequence += 1;
+            }
+        }
+
+        write_chunk(&mut self.out, b"IEND", &[])?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Encode frames as an animated PNG file in memory, repeating forever
+pub fn encode<T: Type, C: Color>(frames: &[(ImageBuf<T, C>, Duration)]) -> Result<Vec<u8>, Error> {
+    let (width, height) = match frames.first() {
+        Some((image, _)) => (image.width(), image.height()),
+        None => return Err(Error::Message(String::from("No frames were added"))),
+    };
+
+    let mut encoder = Encoder::new(Vec::new(), width, height);
+    for (image, delay) in frames {
+        encoder.add_frame(image, *delay)?;
+    }
+    encoder.finish()
+}
+
+/// Write frames to an animated PNG file, repeating forever
+pub fn write<P: AsRef<Path>, T: Type, C: Color>(
+    path: P,
+    frames: &[(ImageBuf<T, C>, Duration)],
+) -> Result<(), Error> {
+    let data = encode(frames)?;
+    with_path(path, |path| Ok(std::fs::write(path, data)?))
+}
+
+/// Decode a standalone PNG file to 8-bit RGBA
+fn decode_rgba8(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let (mut width, mut height, mut channels) = (0, 0, 0);
+    let ptr = unsafe {
+        super::stbi_load_from_memory(
+            data.as_ptr(),
+            data.len() as i32,
+            &mut width,
+            &mut height,
+            &mut channels,
+            4,
+        )
+    };
+
+    if ptr.is_null() {
+        return Err(Error::Message(String::from("Unable to decode PNG frame")));
+    }
+
+    let len = width as usize * height as usize * 4;
+    let pixels = unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec();
+    unsafe { super::stbi_image_free(ptr as *mut std::ffi::c_void) };
+    Ok(pixels)
+}
+
+/// Number of bytes of 8-bit RGBA pixels for an image of the size stored in an IHDR chunk,
+/// returns an error for sizes that aren't allowed or don't fit in memory
+pub(crate) fn rgba8_len(width: usize, height: usize) -> Result<usize, Error> {
+    let invalid = || Error::Message(format!("Invalid PNG image size {}x{}", width, height));
+    if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(invalid());
+    }
+
+    width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(4))
+        .ok_or_else(invalid)
+}
+
+/// Allocate zeroed 8-bit RGBA pixels for an image of the size stored in an IHDR chunk, invalid
+/// sizes and failed allocations return an error instead of aborting
+pub(crate) fn alloc_rgba8(width: usize, height: usize) -> Result<Vec<u8>, Error> {
+    let len = rgba8_len(width, height)?;
+    let mut data = Vec::new();
+    data.try_reserve_exact(len)
+        .map_err(|_| Error::Message(format!("Unable to allocate a {}x{} image", width, height)))?;
+    data.resize(len, 0);
+    Ok(data)
+}
+
+/// Alpha composite a straight alpha pixel over another
+pub(super) fn blend_over(src: &[u8], dest: &mut [u8]) {
+    let (sa, da) = (u32::from(src[3]), u32::from(dest[3]));
+    if sa == 255 || da == 0 {
+        dest.copy_from_slice(src);
+        return;
+    }
+
+    let da = da * (255 - sa) / 255;
+    let alpha = sa + da;
+    if alpha == 0 {
+        dest.copy_from_slice(&[0; 4]);
+        return;
+    }
+
+    for c in 0..3 {
+        dest[c] = ((u32::from(src[c]) * sa + u32::from(dest[c]) * da + alpha / 2) / alpha) as u8;
+    }
+    dest[3] = alpha as u8;
+}
+
+/// Decode every frame of an animated PNG file along with the time each frame is shown
+pub fn decode<Data: AsRef<[u8]>>(data: Data) -> Result<Animation<u8, Rgba>, Error> {
+    let mut frames = Vec::new();
+    decode_into(data, &mut frames)?;
+    Ok(frames)
+}
+
+/// Decode every frame of an animated PNG file into an existing animation, reusing the images of
+/// its frames
+pub fn decode_into<Data: AsRef<[u8]>>(
+    data: Data,
+    animation: &mut Animation<u8, Rgba>,
+) -> Result<(), Error> {
+    let chunks = chunks(data.as_ref())?;
+    let ihdr = match chunks.first() {
+        Some((kind, data)) if *kind == b"IHDR" && data.len() == 13 => *data,
+        _ => return Err(Error::Message(String::from("Missing IHDR chunk"))),
+    };
+    let (width, height) = (read_u32(ihdr) as usize, read_u32(&ihdr[4..]) as usize);
+
+    // Chunks such as PLTE and tRNS are needed to decode each frame
+    let mut shared = Vec::new();
+    let mut animated = false;
+    let mut frames: Vec<(FrameControl, Vec<u8>)> = Vec::new();
+    let mut default = Vec::new();
+    for (kind, data) in &chunks[1..] {
+        match *kind {
+            b"acTL" => animated = true,
+            b"fcTL" => frames.push((FrameControl::parse(data)?, Vec::new())),
+            b"IDAT" => match frames.last_mut() {
+                Some((_, frame)) => frame.extend_from_slice(data),
+                None => default.extend_from_slice(data),
+            },
+            b"fdAT" if data.len() >= 4 => {
+                if let Some((_, frame)) = frames.last_mut() {
+                    frame.extend_from_slice(&data[4..]);
+                }
+            }
+            b"IEND" => (),
+            _ if frames.is_empty() && default.is_empty() => shared.push((*kind, *data)),
+            _ => (),
+        }
+    }
+
+    // The default image is not part of the animation when it has no fcTL chunk
+    if !animated || frames.is_empty() {
+        let control = FrameControl {
+            width,
+            height,
+            x: 0,
+            y: 0,
+            delay_num: 0,
+            delay_den: 0,
+            dispose: Dispose::None,
+            blend: Blend::Source,
+        };
+        frames = vec![(control, default)];
+    }
+
+    let mut canvas = alloc_rgba8(width, height)?;
+    for (i, (control, data)) in frames.iter().enumerate() {
+        if control.width == 0
+            || control.height == 0
+            || control.x + control.width > width
+            || control.y + control.height > height
+        {
+            return Err(Error::Message(String::from(
+                "Frame is outside of the image bounds",
+            )));
+        }
+
+        let mut ihdr = ihdr.to_vec();
+        ihdr[..4].copy_from_slice(&(control.width as u32).to_be_bytes());
+        ihdr[4..8].copy_from_slice(&(control.height as u32).to_be_bytes());
+
+        let mut png = SIGNATURE.to_vec();
+        write_chunk(&mut png, b"IHDR", &ihdr)?;
+        for (kind, data) in &shared {
+            write_chunk(&mut png, kind, data)?;
+        }
+        write_chunk(&mut png, b"IDAT", data)?;
+        write_chunk(&mut png, b"IEND", &[])?;
+        let pixels = decode_rgba8(&png)?;
+
+        let row_len = control.width * 4;
+        let rows = || {
+            (control.y..control.y + control.height)
+                .map(|y| (y * width + control.x) * 4)
+                .map(|start| start..start + row_len)
+        };
+
+        let previous = match control.dispose {
+            // Restoring before the first frame clears the region
+            Dispose::Previous if i > 0 => Some(canvas.clone()),
+            _ => None,
+        };
+
+        for (range, src) in rows().zip(pixels.chunks(row_len)) {
+            let dest = &mut canvas[range];
+            match control.blend {
+                Blend::Source => dest.copy_from_slice(src),
+                Blend::Over => {
+                    for (src, dest) in src.chunks(4).zip(dest.chunks_mut(4)) {
+                        blend_over(src, dest);
+                    }
+                }
+            }
+        }
+
+        super::store_frame(animation, i, width, height, &canvas, control.delay());
+
+        match (control.dispose, previous) {
+            (Dispose::None, _) => (),
+            (Dispose::Previous, Some(previous)) => canvas = previous,
+            _ => {
+                for range in rows() {
+                    canvas[range].iter_mut().for_each(|x| *x = 0);
+                }
+            }
+        }
+    }
+
+    animation.truncate(frames.len());
+    Ok(())
+}
+
+/// Read every frame of an animated PNG file
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Animation<u8, Rgba>, Error> {
+    with_path(path, |path| decode(std::fs::read(path)?))
+}
+
+/// Read every frame of an animated PNG file into an existing animation, reusing the images of
+/// its frames
+pub fn read_into<P: AsRef<Path>>(
+    path: P,
+    animation: &mut Animation<u8, Rgba>,
+) -> Result<(), Error> {
+    with_path(path, |path| decode_into(std::fs::read(path)?, animation))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::color::Rgb;
+
+    #[test]
+    fn test_png_animation() {
+        assert_eq!(crc32(b"IEND", &[]), 0xae42_6082);
+        assert_eq!(delay_fraction(Duration::from_millis(40)), (40, 1000));
+        assert_eq!(delay_fraction(Duration::from_secs(100)), (10000, 100));
+
+        let mut frames: Animation<u8, Rgba> = Vec::new();
+        for i in 0..4 {
+            let mut image: ImageBuf<u8, Rgba> = ImageBuf::new(16, 12);
+            image.for_each(|(x, y), px| {
+                px.copy_from_slice(&[(x * 16) as u8, (y * 20) as u8, 128, 255])
+            });
+            image.at_mut(i * 3, 2).copy_from_slice(&[255, 0, 0, 64]);
+            if i == 2 {
+                image.at_mut(15, 11).copy_from_slice(&[0, 0, 0, 0]);
+            }
+            frames.push((image, Duration::from_millis(50 + i as u64 * 10)));
+        }
+        // Unchanged frame
+        frames.push((Clone::clone(&frames[3].0), Duration::from_millis(500)));
+
+        let data = encode(&frames).unwrap();
+        std::fs::write("test/test-animation.png", &data).unwrap();
+        assert_eq!(decode(&data).unwrap(), frames);
+
+        // The first frame is the default image, so other decoders show it
+        let first: crate::ImagePtr<u8, Rgba> = super::super::decode_u8(&data).unwrap();
+        assert_eq!(first.data(), frames[0].0.data());
+
+        // Plain PNG files decode as a single frame
+        let rgb: ImageBuf<u8, Rgb> = ImageBuf::new_from(2, 1, vec![1, 2, 3, 4, 5, 6]);
+        let plain = decode(super::super::encode_png_u8(&rgb).unwrap()).unwrap();
+        assert_eq!(plain.len(), 1);
+        assert_eq!(plain[0].0.data(), &[1, 2, 3, 255, 4, 5, 6, 255]);
+
+        // Clearing the first frame leaves only the changed pixel of the second frame
+        let red: ImageBuf<u8, Rgba> = ImageBuf::new_from(2, 2, [255, 0, 0, 255].repeat(4));
+        let mut blue = Clone::clone(&red);
+        blue.at_mut(1, 1).copy_from_slice(&[0, 0, 255, 128]);
+        let mut data = encode(&[(red, Duration::ZERO), (blue, Duration::ZERO)]).unwrap();
+        let fctl: Vec<usize> = (0..data.len() - 4)
+            .filter(|&i| &data[i..i + 4] == b"fcTL")
+            .collect();
+        data[fctl[0] + 4 + 24] = Dispose::Background as u8;
+        data[fctl[1] + 4 + 25] = Blend::Over as u8;
+        let frames = decode(&data).unwrap();
+        let mut expected = vec![0; 16];
+        expected[12..].copy_from_slice(&[0, 0, 255, 128]);
+        assert_eq!(frames[1].0.data(), expected.as_slice());
+
+        let mut dest = [0, 0, 255, 255];
+        blend_over(&[255, 0, 0, 128], &mut dest);
+        assert_eq!(dest, [128, 0, 127, 255]);
+
+        // Invalid sizes are errors instead of failed allocations
+        for (width, height) in [(0u32, 1u32), (1 << 31, 1), (0x7fff_ffff, 0x7fff_ffff)] {
+            let mut ihdr = width.to_be_bytes().to_vec();
+            ihdr.extend_from_slice(&height.to_be_bytes());
+            ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+            let mut data = SIGNATURE.to_vec();
+            write_chunk(&mut data, b"IHDR", &ihdr).unwrap();
+            write_chunk(&mut data, b"IEND", &[]).unwrap();
+            assert!(decode(&data).is_err());
+        }
+    }
+
+    #[test]
+    fn test_inflate() {
+        let data: Vec<u8> = (0..100_000u32).map(|x| (x / 1000) as u8).collect();
+        let compressed = deflate(&data).unwrap();
+        assert_eq!(inflate(&compressed, data.len()).unwrap(), data);
+
+        // Highly compressed data is rejected once it grows past the limit
+        assert!(inflate(&compressed, 10_000).is_err());
+        assert!(inflate(&compressed[..compressed.len() / 2], data.len()).is_err());
+        assert!(inflate(b"not zlib", data.len()).is_err());
+    }
+}
@@ -0,0 +1,1651 @@
+//! Incremental decoding of images that are still being received
+//!
+//! `Decoder` accepts data as it arrives and calls a callback whenever more of the image can be
+//! shown, so viewers and thumbnails can display content before the whole file is available:
+//!
+//! ```rust,no_run
+//! use image2::io::progressive::{Decoder, Update};
+//! use image2::Image;
+//!
+//! let mut decoder = Decoder::new(|image, update| match update {
+//!     Update::Rows(rows) => println!("rows {:?} of {}", rows, image.height()),
+//!     Update::Pass(n) => println!("pass {} finished", n),
+//!     Update::Complete => println!("done"),
+//! });
+//! for chunk in std::fs::read("image.png")?.chunks(4096) {
+//!     decoder.push(chunk)?;
+//! }
+//! let image = decoder.finish()?;
+//! # Ok::<(), image2::Error>(())
+//! ```
+//!
+//! - PNG files are decompressed as the data arrives. Rows of non-interlaced files are reported
+//!   as soon as they are complete, interlaced files report each of the seven Adam7 passes with
+//!   the missing pixels filled in from the nearest pixel decoded so far
+//! - Progressive JPEG files report a lower quality version of the whole image after each scan
+//! - Baseline JPEG files report rows as soon as the MCUs covering them have arrived
+//! - Other formats are decoded by `finish`, once all of the data has been pushed
+//!
+//! Images are decoded to 8-bit RGBA and pixels that haven't been decoded yet are transparent
+//! black. JPEG updates are decoded from all of the data received so far using stb_image, so rows
+//! of baseline files are only reported each time the amount of data has doubled.
+
+use std::io::Read;
+use std::ops::Range;
+
+use crate::color::Rgba;
+use crate::error::Error;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+
+use super::png::{alloc_rgba8, rgba8_len, SIGNATURE};
+
+/// Progress reported to the callback of a `Decoder`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Update {
+    /// The rows in the range have their final values
+    Rows(Range<usize>),
+    /// Interlacing pass or progressive scan `n`, starting at 1, finished and the whole image
+    /// contains a preview
+    Pass(usize),
+    /// The image is fully decoded
+    Complete,
+}
+
+/// Decodes an image incrementally, see the module documentation
+pub struct Decoder<F: FnMut(&ImageBuf<u8, Rgba>, Update)> {
+    data: Vec<u8>,
+    image: ImageBuf<u8, Rgba>,
+    state: State,
+    complete: bool,
+    callback: F,
+}
+
+enum State {
+    /// Not enough data to detect the format
+    Unknown,
+    Png(Box<Png>),
+    Jpeg(Box<Jpeg>),
+    /// Decoded by `finish`
+    Other,
+}
+
+/// Receives updates from the format specific decoders as soon as they happen
+type Report<'a> = dyn FnMut(&mut ImageBuf<u8, Rgba>, Update) + 'a;
+
+impl<F: FnMut(&ImageBuf<u8, Rgba>, Update)> Decoder<F> {
+    /// Create a decoder that calls `callback` with the partially decoded image whenever more of
+    /// it is available
+    pub fn new(callback: F) -> Decoder<F> {
+        Decoder {
+            data: Vec::new(),
+            image: ImageBuf::new(0, 0),
+            state: State::Unknown,
+            complete: false,
+            callback,
+        }
+    }
+
+    /// The image decoded so far, empty until the header has been received
+    pub fn image(&self) -> &ImageBuf<u8, Rgba> {
+        &self.image
+    }
+
+    /// Returns true once the whole image has been decoded
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Add more data and decode as much of it as possible, data pushed after the image is
+    /// complete is ignored
+    pub fn push(&mut self, data: &[u8]) -> Result<(), Error> {
+        if self.complete {
+            return Ok(());
+        }
+
+        self.data.extend_from_slice(data);
+        if let State::Unknown = self.state {
+            if self.data.starts_with(SIGNATURE) {
+                self.state = State::Png(Box::new(Png::new()));
+            } else if self.data.starts_with(&[0xff, 0xd8, 0xff]) {
+                self.state = State::Jpeg(Box::new(Jpeg::new()));
+            } else if self.data.len() >= SIGNATURE.len() {
+                self.state = State::Other;
+            }
+        }
+
+        let Decoder {
+            data: buffer,
+            image,
+            state,
+            complete,
+            callback,
+        } = self;
+        let mut report = |image: &mut ImageBuf<u8, Rgba>, update: Update| {
+            if update == Update::Complete {
+                super::load_metadata(buffer, image);
+                *complete = true;
+            }
+            callback(image, update);
+        };
+        match state {
+            State::Png(png) => png.advance(buffer, image, &mut report),
+            State::Jpeg(jpeg) => jpeg.advance(buffer, image, &mut report),
+            State::Unknown | State::Other => Ok(()),
+        }
+    }
+
+    /// Finish decoding and return the image. Formats that can't be decoded incrementally are
+    /// decoded here, truncated PNG and JPEG files return an error
+    pub fn finish(mut self) -> Result<ImageBuf<u8, Rgba>, Error> {
+        if self.complete {
+            return Ok(self.image);
+        }
+
+        match self.state {
+            State::Png(_) | State::Jpeg(_) => {
+                Err(Error::Message(String::from("Truncated image data")))
+            }
+            State::Unknown | State::Other => {
+                let mut image = ImageBuf::new(0, 0);
+                super::decode_into(&self.data, &mut image)?;
+                (self.callback)(&image, Update::Complete);
+                Ok(image)
+            }
+        }
+    }
+}
+
+/// Read an image from `reader` using a `Decoder`, `callback` is called as data arrives
+pub fn decode_from<R: Read, F: FnMut(&ImageBuf<u8, Rgba>, Update)>(
+    mut reader: R,
+    callback: F,
+) -> Result<ImageBuf<u8, Rgba>, Error> {
+    let mut decoder = Decoder::new(callback);
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => decoder.push(&buf[..n])?,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    decoder.finish()
+}
+
+fn invalid_compressed_data() -> Error {
+    Error::Message(String::from("Invalid compressed data"))
+}
+
+/// Decompress all of `data`, which is passed to the inflater in small pieces so decompression
+/// stops soon after the output grows past `limit`
+pub(super) fn inflate(data: &[u8], limit: usize) -> Result<Vec<u8>, Error> {
+    let mut inflater = Inflater::new();
+    for chunk in data.chunks(4096) {
+        inflater.push(chunk)?;
+        if inflater.out.len() > limit {
+            return Err(Error::Message(format!(
+                "Decompressed data is larger than {} bytes",
+                limit
+            )));
+        }
+        if inflater.done {
+            break;
+        }
+    }
+
+    if !inflater.done {
+        return Err(invalid_compressed_data());
+    }
+    Ok(inflater.out)
+}
+
+/// Canonical Huffman code, used by both zlib and JPEG
+struct Huffman {
+    /// Number of codes of each length
+    counts: [u16; 17],
+    /// Symbols ordered by code
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    /// Create a code from the length of the code of each symbol, 0 for unused symbols
+    fn new(lengths: &[u8]) -> Result<Huffman, Error> {
+        let mut counts = [0u16; 17];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut left = 1i32;
+        for count in &counts[1..] {
+            left = left * 2 - i32::from(*count);
+            if left < 0 {
+                return Err(invalid_compressed_data());
+            }
+        }
+
+        let mut offsets = [0u16; 17];
+        for len in 1..16 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Ok(Huffman { counts, symbols })
+    }
+
+    /// Create a code from the number of codes of each length from 1 to 16 and the symbols in
+    /// order, as stored in a JPEG `DHT` segment
+    fn from_counts(counts: &[u8], symbols: &[u8]) -> Huffman {
+        let mut c = [0u16; 17];
+        for (dest, count) in c[1..].iter_mut().zip(counts) {
+            *dest = u16::from(*count);
+        }
+        Huffman {
+            counts: c,
+            symbols: symbols.iter().map(|&x| u16::from(x)).collect(),
+        }
+    }
+
+    /// Decode a symbol reading one bit at a time using `bit`, returns `None` when `bit` runs out
+    /// of data
+    fn decode<B: FnMut() -> Option<u32>>(&self, mut bit: B) -> Result<Option<u16>, Error> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= match bit() {
+                Some(b) => b as i32,
+                None => return Ok(None),
+            };
+            let count = i32::from(count);
+            if code - count < first {
+                return match self.symbols.get((index + code - first) as usize) {
+                    Some(&symbol) => Ok(Some(symbol)),
+                    None => Err(invalid_compressed_data()),
+                };
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid_compressed_data())
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Size of the zlib window, the decompressed data that has to be kept for back references
+const WINDOW: usize = 32 * 1024;
+
+/// Reads bits from the least significant bit of each byte, as used by zlib
+#[derive(Default)]
+struct Bits {
+    input: Vec<u8>,
+    bit: usize,
+}
+
+impl Bits {
+    fn read(&mut self, n: u8) -> Option<u32> {
+        if self.bit + n as usize > self.input.len() * 8 {
+            return None;
+        }
+
+        let mut value = 0;
+        for i in 0..n as usize {
+            let b = self.bit + i;
+            value |= u32::from((self.input[b / 8] >> (b % 8)) & 1) << i;
+        }
+        self.bit += n as usize;
+        Some(value)
+    }
+
+    fn decode(&mut self, code: &Huffman) -> Result<Option<u16>, Error> {
+        code.decode(|| self.read(1))
+    }
+}
+
+enum Block {
+    Header,
+    Stored(usize),
+    Codes,
+}
+
+/// zlib decompressor that stops when it runs out of input and continues from the same place
+/// when more input is added
+struct Inflater {
+    bits: Bits,
+    out: Vec<u8>,
+    block: Block,
+    literals: Huffman,
+    distances: Huffman,
+    last: bool,
+    started: bool,
+    done: bool,
+}
+
+/// Return `Ok(false)` from the current step when the input runs out
+macro_rules! need {
+    ($e:expr) => {
+        match $e {
+            Some(x) => x,
+            None => return Ok(false),
+        }
+    };
+}
+
+impl Inflater {
+    fn new() -> Inflater {
+        Inflater {
+            bits: Bits::default(),
+            out: Vec::new(),
+            block: Block::Header,
+            literals: Huffman::from_counts(&[], &[]),
+            distances: Huffman::from_counts(&[], &[]),
+            last: false,
+            started: false,
+            done: false,
+        }
+    }
+
+    /// Add compressed data and decompress as much as possible
+    fn push(&mut self, data: &[u8]) -> Result<(), Error> {
+        let consumed = self.bits.bit / 8;
+        if consumed > WINDOW {
+            self.bits.input.drain(..consumed);
+            self.bits.bit -= consumed * 8;
+        }
+        self.bits.input.extend_from_slice(data);
+
+        while !self.done {
+            let start = self.bits.bit;
+            if !self.step()? {
+                self.bits.bit = start;
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove decompressed data before `*offset` that is no longer needed for back references,
+    /// adjusting `offset` to point to the same data
+    fn discard(&mut self, offset: &mut usize) {
+        if *offset > WINDOW * 2 {
+            let n = *offset - WINDOW;
+            self.out.drain(..n);
+            *offset -= n;
+        }
+    }
+
+    /// Decode the zlib header, a block header, part of a stored block or a single symbol,
+    /// returns false when more input is needed
+    fn step(&mut self) -> Result<bool, Error> {
+        if !self.started {
+            let cmf = need!(self.bits.read(8));
+            let flg = need!(self.bits.read(8));
+            if cmf & 15 != 8 || (cmf * 256 + flg) % 31 != 0 || flg & 32 != 0 {
+                return Err(invalid_compressed_data());
+            }
+            self.started = true;
+            return Ok(true);
+        }
+
+        match self.block {
+            Block::Header => {
+                self.last = need!(self.bits.read(1)) == 1;
+                match need!(self.bits.read(2)) {
+                    0 => {
+                        self.bits.bit = self.bits.bit.div_ceil(8) * 8;
+                        let len = need!(self.bits.read(16));
+                        let nlen = need!(self.bits.read(16));
+                        if len != !nlen & 0xffff {
+                            return Err(invalid_compressed_data());
+                        }
+                        self.block = Block::Stored(len as usize);
+                    }
+                    1 => {
+                        let mut lengths = [8u8; 288];
+                        lengths[144..256].iter_mut().for_each(|x| *x = 9);
+                        lengths[256..280].iter_mut().for_each(|x| *x = 7);
+                        self.literals = Huffman::new(&lengths)?;
+                        self.distances = Huffman::new(&[5; 30])?;
+                        self.block = Block::Codes;
+                    }
+                    2 => {
+                        if !self.dynamic_header()? {
+                            return Ok(false);
+                        }
+                        self.block = Block::Codes;
+                    }
+                    _ => return Err(invalid_compressed_data()),
+                }
+            }
+            Block::Stored(0) => self.end_block(),
+            Block::Stored(n) => {
+                let start = self.bits.bit / 8;
+                let available = (self.bits.input.len() - start).min(n);
+                if available == 0 {
+                    return Ok(false);
+                }
+                self.out
+                    .extend_from_slice(&self.bits.input[start..start + available]);
+                self.bits.bit += available * 8;
+                self.block = Block::Stored(n - available);
+            }
+            Block::Codes => {
+                let symbol = need!(self.bits.decode(&self.literals)?) as usize;
+                if symbol < 256 {
+                    self.out.push(symbol as u8);
+                } else if symbol == 256 {
+                    self.end_block();
+                } else {
+                    let i = symbol - 257;
+                    if i >= LENGTH_BASE.len() {
+                        return Err(invalid_compressed_data());
+                    }
+                    let len =
+                        LENGTH_BASE[i] as usize + need!(self.bits.read(LENGTH_EXTRA[i])) as usize;
+                    let d = need!(self.bits.decode(&self.distances)?) as usize;
+                    if d >= DISTANCE_BASE.len() {
+                        return Err(invalid_compressed_data());
+                    }
+                    let distance = DISTANCE_BASE[d] as usize
+                        + need!(self.bits.read(DISTANCE_EXTRA[d])) as usize;
+                    if distance > self.out.len() {
+                        return Err(invalid_compressed_data());
+                    }
+                    for _ in 0..len {
+                        self.out.push(self.out[self.out.len() - distance]);
+                    }
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    fn end_block(&mut self) {
+        if self.last {
+            self.done = true;
+        } else {
+            self.block = Block::Header;
+        }
+    }
+
+    /// Read the code lengths of a block with dynamic Huffman codes
+    fn dynamic_header(&mut self) -> Result<bool, Error> {
+        let nlen = need!(self.bits.read(5)) as usize + 257;
+        let ndist = need!(self.bits.read(5)) as usize + 1;
+        let ncode = need!(self.bits.read(4)) as usize + 4;
+        let mut lengths = [0u8; 19];
+        for &i in &CODE_LENGTH_ORDER[..ncode] {
+            lengths[i] = need!(self.bits.read(3)) as u8;
+        }
+        let code = Huffman::new(&lengths)?;
+
+        let mut lengths = vec![0u8; nlen + ndist];
+        let mut i = 0;
+        while i < lengths.len() {
+            let symbol = need!(self.bits.decode(&code)?);
+            let (value, count) = match symbol {
+                0..=15 => (symbol as u8, 1),
+                16 if i > 0 => (lengths[i - 1], 3 + need!(self.bits.read(2)) as usize),
+                17 => (0, 3 + need!(self.bits.read(3)) as usize),
+                18 => (0, 11 + need!(self.bits.read(7)) as usize),
+                _ => return Err(invalid_compressed_data()),
+            };
+            if i + count > lengths.len() {
+                return Err(invalid_compressed_data());
+            }
+            lengths[i..i + count].iter_mut().for_each(|x| *x = value);
+            i += count;
+        }
+
+        if lengths[256] == 0 {
+            return Err(invalid_compressed_data());
+        }
+        self.literals = Huffman::new(&lengths[..nlen])?;
+        self.distances = Huffman::new(&lengths[nlen..])?;
+        Ok(true)
+    }
+}
+
+/// Start, spacing and size of the block each pixel fills for the seven Adam7 passes, as
+/// `(x, y, dx, dy, block width, block height)`
+const ADAM7: [(usize, usize, usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8, 8, 8),
+    (4, 0, 8, 8, 4, 8),
+    (0, 4, 4, 8, 4, 4),
+    (2, 0, 4, 4, 2, 4),
+    (0, 2, 2, 4, 2, 2),
+    (1, 0, 2, 2, 1, 2),
+    (0, 1, 1, 2, 1, 1),
+];
+
+/// Contents of the `IHDR` chunk
+struct Header {
+    width: usize,
+    height: usize,
+    depth: usize,
+    color: u8,
+    interlaced: bool,
+}
+
+impl Header {
+    fn parse(data: &[u8]) -> Result<Header, Error> {
+        let invalid = || Error::Message(String::from("Invalid IHDR chunk"));
+        if data.len() != 13 {
+            return Err(invalid());
+        }
+
+        let width = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        let height = u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as usize;
+        let (depth, color) = (data[8] as usize, data[9]);
+        let valid = match color {
+            0 => matches!(depth, 1 | 2 | 4 | 8 | 16),
+            3 => matches!(depth, 1 | 2 | 4 | 8),
+            2 | 4 | 6 => matches!(depth, 8 | 16),
+            _ => false,
+        };
+        if !valid || data[10] != 0 || data[11] != 0 || data[12] > 1 {
+            return Err(invalid());
+        }
+        rgba8_len(width, height)?;
+
+        Ok(Header {
+            width,
+            height,
+            depth,
+            color,
+            interlaced: data[12] == 1,
+        })
+    }
+
+    fn channels(&self) -> usize {
+        match self.color {
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => 1,
+        }
+    }
+
+    /// Bytes per complete pixel, used by the filters
+    fn filter_bytes(&self) -> usize {
+        (self.channels() * self.depth / 8).max(1)
+    }
+
+    /// Size of the image of an Adam7 pass, or of the whole image when it isn't interlaced
+    fn pass_size(&self, pass: usize) -> (usize, usize) {
+        if !self.interlaced {
+            return (self.width, self.height);
+        }
+
+        let (x, y, dx, dy, _, _) = ADAM7[pass];
+        (
+            (self.width + dx - 1 - x) / dx,
+            (self.height + dy - 1 - y) / dy,
+        )
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = i16::from(a) + i16::from(b) - i16::from(c);
+    let (pa, pb, pc) = (
+        (p - i16::from(a)).abs(),
+        (p - i16::from(b)).abs(),
+        (p - i16::from(c)).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Reverse the filter of a scanline in place, `prev` is the unfiltered previous scanline
+fn unfilter(filter: u8, row: &mut [u8], prev: &[u8], bpp: usize) -> Result<(), Error> {
+    match filter {
+        0 => (),
+        1 => {
+            for i in bpp..row.len() {
+                row[i] = row[i].wrapping_add(row[i - bpp]);
+            }
+        }
+        2 => row
+            .iter_mut()
+            .zip(prev)
+            .for_each(|(x, p)| *x = x.wrapping_add(*p)),
+        3 => {
+            for i in 0..row.len() {
+                let left = if i >= bpp { row[i - bpp] } else { 0 };
+                let avg = ((u16::from(left) + u16::from(prev[i])) / 2) as u8;
+                row[i] = row[i].wrapping_add(avg);
+            }
+        }
+        4 => {
+            for i in 0..row.len() {
+                let (left, up_left) = if i >= bpp {
+                    (row[i - bpp], prev[i - bpp])
+                } else {
+                    (0, 0)
+                };
+                row[i] = row[i].wrapping_add(paeth(left, prev[i], up_left));
+            }
+        }
+        _ => return Err(Error::Message(String::from("Invalid PNG filter type"))),
+    }
+    Ok(())
+}
+
+/// Incremental PNG decoder state
+struct Png {
+    /// Offset of the next chunk
+    pos: usize,
+    /// Number of bytes of the current `IDAT` chunk passed to the inflater
+    fed: usize,
+    header: Option<Header>,
+    palette: Vec<[u8; 4]>,
+    /// Color that is transparent in gray and RGB images
+    transparent: Option<[u16; 3]>,
+    inflater: Inflater,
+    /// Offset of the next scanline in the decompressed data
+    offset: usize,
+    /// Current Adam7 pass, always 0 for images that aren't interlaced
+    pass: usize,
+    /// Next row of the current pass
+    row: usize,
+    /// The previous unfiltered scanline
+    prev: Vec<u8>,
+    done: bool,
+}
+
+impl Png {
+    fn new() -> Png {
+        Png {
+            pos: SIGNATURE.len(),
+            fed: 0,
+            header: None,
+            palette: Vec::new(),
+            transparent: None,
+            inflater: Inflater::new(),
+            offset: 0,
+            pass: 0,
+            row: 0,
+            prev: Vec::new(),
+            done: false,
+        }
+    }
+
+    fn advance(
+        &mut self,
+        data: &[u8],
+        image: &mut ImageBuf<u8, Rgba>,
+        report: &mut Report,
+    ) -> Result<(), Error> {
+        while !self.done && data.len() >= self.pos + 8 {
+            let chunk = &data[self.pos..];
+            let len = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) as usize;
+            let kind = &chunk[4..8];
+            let available = (chunk.len() - 8).min(len);
+
+            if kind == b"IDAT" && available > self.fed {
+                if self.header.is_none() {
+                    return Err(Error::Message(String::from("Missing IHDR chunk")));
+                }
+                self.inflater.push(&chunk[8 + self.fed..8 + available])?;
+                self.fed = available;
+                self.decode_rows(image, report)?;
+            }
+
+            if chunk.len() < len + 12 {
+                break;
+            }
+
+            let body = &chunk[8..8 + len];
+            match kind {
+                b"IHDR" => {
+                    if self.header.is_some() {
+                        return Err(Error::Message(String::from("Duplicate IHDR chunk")));
+                    }
+                    let header = Header::parse(body)?;
+                    let pixels = alloc_rgba8(header.width, header.height)?;
+                    *image = ImageBuf::new_from(header.width, header.height, pixels);
+                    self.header = Some(header);
+                }
+                b"PLTE" => {
+                    self.palette = body
+                        .chunks_exact(3)
+                        .map(|c| [c[0], c[1], c[2], 255])
+                        .collect()
+                }
+                b"tRNS" => match self.header.as_ref().map(|h| h.color) {
+                    Some(3) => self
+                        .palette
+                        .iter_mut()
+                        .zip(body)
+                        .for_each(|(px, a)| px[3] = *a),
+                    Some(0) if body.len() >= 2 => {
+                        let v = u16::from_be_bytes([body[0], body[1]]);
+                        self.transparent = Some([v; 3]);
+                    }
+                    Some(2) if body.len() >= 6 => {
+                        let v = |i: usize| u16::from_be_bytes([body[i], body[i + 1]]);
+                        self.transparent = Some([v(0), v(2), v(4)]);
+                    }
+                    _ => (),
+                },
+                b"IEND" => return Err(Error::Message(String::from("Truncated PNG image data"))),
+                _ => (),
+            }
+            self.pos += len + 12;
+            self.fed = 0;
+        }
+        Ok(())
+    }
+
+    /// Unfilter and store every complete scanline of decompressed data
+    fn decode_rows(
+        &mut self,
+        image: &mut ImageBuf<u8, Rgba>,
+        report: &mut Report,
+    ) -> Result<(), Error> {
+        let header = match &self.header {
+            Some(header) => header,
+            None => return Ok(()),
+        };
+        let first_row = self.row;
+        let bits = header.channels() * header.depth;
+        while !self.done {
+            let (width, height) = header.pass_size(self.pass);
+            let stride = (width * bits).div_ceil(8);
+            if width > 0 && height > 0 {
+                let out = &self.inflater.out;
+                if out.len() < self.offset + stride + 1 {
+                    break;
+                }
+
+                if self.row == 0 {
+                    self.prev.clear();
+                    self.prev.resize(stride, 0);
+                }
+                let mut row = out[self.offset + 1..self.offset + 1 + stride].to_vec();
+                unfilter(
+                    out[self.offset],
+                    &mut row,
+                    &self.prev,
+                    header.filter_bytes(),
+                )?;
+                self.store_row(header, &row, image);
+                self.prev = row;
+                self.offset += stride + 1;
+                self.row += 1;
+                if self.row < height {
+                    continue;
+                }
+            }
+
+            if !header.interlaced {
+                report(image, Update::Rows(first_row..self.row));
+                self.done = true;
+            } else {
+                if width > 0 && height > 0 {
+                    report(image, Update::Pass(self.pass + 1));
+                }
+                self.pass += 1;
+                self.row = 0;
+                self.done = self.pass == ADAM7.len();
+            }
+        }
+
+        if !header.interlaced && !self.done && self.row > first_row {
+            report(image, Update::Rows(first_row..self.row));
+        }
+        if self.done {
+            report(image, Update::Complete);
+        }
+        self.inflater.discard(&mut self.offset);
+        Ok(())
+    }
+
+    /// Convert an unfiltered scanline to RGBA and store it, pixels of interlaced images fill the
+    /// block of the image that hasn't been decoded yet
+    fn store_row(&self, header: &Header, row: &[u8], image: &mut ImageBuf<u8, Rgba>) {
+        let (width, height) = (header.width, header.height);
+        let (x0, y0, dx, dy, bw, bh) = if header.interlaced {
+            ADAM7[self.pass]
+        } else {
+            (0, 0, 1, 1, 1, 1)
+        };
+        let y = y0 + self.row * dy;
+        let (pass_width, _) = header.pass_size(self.pass);
+        let data = image.data_mut();
+        for i in 0..pass_width {
+            let px = self.pixel(header, row, i);
+            let x = x0 + i * dx;
+            for y in y..(y + bh).min(height) {
+                for x in x..(x + bw).min(width) {
+                    let index = (y * width + x) * 4;
+                    data[index..index + 4].copy_from_slice(&px);
+                }
+            }
+        }
+    }
+
+    /// Convert pixel `i` of an unfiltered scanline to RGBA
+    fn pixel(&self, header: &Header, row: &[u8], i: usize) -> [u8; 4] {
+        let depth = header.depth;
+        let sample = |k: usize| -> u16 {
+            match depth {
+                16 => u16::from_be_bytes([row[k * 2], row[k * 2 + 1]]),
+                8 => u16::from(row[k]),
+                _ => {
+                    let bit = k * depth;
+                    u16::from((row[bit / 8] >> (8 - depth - bit % 8)) & ((1 << depth) - 1) as u8)
+                }
+            }
+        };
+        let scale = |v: u16| -> u8 {
+            match depth {
+                16 => (v >> 8) as u8,
+                8 => v as u8,
+                _ => (u32::from(v) * 255 / ((1 << depth) - 1)) as u8,
+            }
+        };
+
+        match header.color {
+            0 => {
+                let v = sample(i);
+                let alpha = if self.transparent == Some([v; 3]) {
+                    0
+                } else {
+                    255
+                };
+                let v = scale(v);
+                [v, v, v, alpha]
+            }
+            2 => {
+                let rgb = [sample(i * 3), sample(i * 3 + 1), sample(i * 3 + 2)];
+                let alpha = if self.transparent == Some(rgb) {
+                    0
+                } else {
+                    255
+                };
+                [scale(rgb[0]), scale(rgb[1]), scale(rgb[2]), alpha]
+            }
+            3 => self
+                .palette
+                .get(sample(i) as usize)
+                .copied()
+                .unwrap_or([0, 0, 0, 255]),
+            4 => {
+                let v = scale(sample(i * 2));
+                [v, v, v, scale(sample(i * 2 + 1))]
+            }
+            _ => [
+                scale(sample(i * 4)),
+                scale(sample(i * 4 + 1)),
+                scale(sample(i * 4 + 2)),
+                scale(sample(i * 4 + 3)),
+            ],
+        }
+    }
+}
+
+/// Reads bits of JPEG entropy coded data from the most significant bit, skipping stuffed zero
+/// bytes
+#[derive(Clone)]
+struct EntropyBits {
+    pos: usize,
+    acc: u32,
+    count: u32,
+}
+
+impl EntropyBits {
+    fn bit(&mut self, data: &[u8]) -> Option<u32> {
+        if self.count == 0 {
+            let byte = *data.get(self.pos)?;
+            if byte == 0xff {
+                // Markers inside of an MCU mean the data ends early
+                if *data.get(self.pos + 1)? != 0 {
+                    return None;
+                }
+                self.pos += 1;
+            }
+            self.pos += 1;
+            self.acc = u32::from(byte);
+            self.count = 8;
+        }
+
+        self.count -= 1;
+        Some((self.acc >> self.count) & 1)
+    }
+
+    fn skip(&mut self, data: &[u8], n: u16) -> Option<()> {
+        for _ in 0..n {
+            self.bit(data)?;
+        }
+        Some(())
+    }
+
+    /// Skip to the end of a restart interval, returns `None` if the marker hasn't arrived yet
+    fn restart(&mut self, data: &[u8]) -> Option<()> {
+        if *data.get(self.pos)? != 0xff || !(0xd0..=0xd7).contains(data.get(self.pos + 1)?) {
+            return None;
+        }
+        self.pos += 2;
+        self.count = 0;
+        Some(())
+    }
+}
+
+/// Component of a JPEG frame, with its sampling factors
+struct Component {
+    id: u8,
+    h: usize,
+    v: usize,
+}
+
+/// A baseline JPEG scan containing every component, its MCUs are counted to find out which rows
+/// can be decoded
+struct Scan {
+    /// `(blocks per MCU, DC table, AC table)` for each component
+    components: Vec<(usize, usize, usize)>,
+    bits: EntropyBits,
+    mcus: usize,
+}
+
+/// Incremental JPEG decoder state
+struct Jpeg {
+    /// Offset of the next marker
+    pos: usize,
+    width: usize,
+    height: usize,
+    components: Vec<Component>,
+    progressive: bool,
+    /// Only frames using Huffman coding with a known height are decoded incrementally
+    supported: bool,
+    tables: Vec<Option<Huffman>>,
+    restart_interval: usize,
+    /// Offset of the entropy coded data of the current scan, if any
+    scan_start: Option<usize>,
+    /// Offset used to resume searching for the end of the current scan
+    search: usize,
+    scan: Option<Scan>,
+    scans: usize,
+    rows: usize,
+    /// Length of the data decoded for the last row update
+    decoded: usize,
+}
+
+impl Jpeg {
+    fn new() -> Jpeg {
+        Jpeg {
+            pos: 0,
+            width: 0,
+            height: 0,
+            components: Vec::new(),
+            progressive: false,
+            supported: false,
+            tables: (0..8).map(|_| None).collect(),
+            restart_interval: 0,
+            scan_start: None,
+            search: 0,
+            scan: None,
+            scans: 0,
+            rows: 0,
+            decoded: 0,
+        }
+    }
+
+    fn invalid() -> Error {
+        Error::Message(String::from("Invalid JPEG data"))
+    }
+
+    /// Decode `data` followed by an end of image marker using stb_image
+    fn decode_prefix(data: &[u8]) -> Option<ImageBuf<u8, Rgba>> {
+        let mut prefix = data.to_vec();
+        prefix.extend_from_slice(&[0xff, 0xd9]);
+        let mut image = ImageBuf::new(0, 0);
+        super::decode_into(&prefix, &mut image).ok()?;
+        Some(image)
+    }
+
+    fn copy_rows(src: &ImageBuf<u8, Rgba>, dest: &mut ImageBuf<u8, Rgba>, rows: Range<usize>) {
+        if src.shape() != dest.shape() {
+            return;
+        }
+        let row_len = dest.width() * 4;
+        let range = rows.start * row_len..rows.end * row_len;
+        dest.data_mut()[range.clone()].copy_from_slice(&src.data()[range]);
+    }
+
+    fn advance(
+        &mut self,
+        data: &[u8],
+        image: &mut ImageBuf<u8, Rgba>,
+        report: &mut Report,
+    ) -> Result<(), Error> {
+        loop {
+            if let Some(start) = self.scan_start {
+                self.count_mcus(data, image, report)?;
+                let end = match self.scan_end(data, start) {
+                    Some(end) => end,
+                    None => break,
+                };
+                self.scan_start = None;
+                self.scan = None;
+                self.pos = end;
+                self.scans += 1;
+                if self.progressive && self.supported {
+                    if let Some(decoded) = Jpeg::decode_prefix(&data[..end]) {
+                        Jpeg::copy_rows(&decoded, image, 0..self.height);
+                        report(image, Update::Pass(self.scans));
+                    }
+                }
+            }
+
+            let pos = self.pos;
+            if data.len() < pos + 2 {
+                break;
+            }
+            if data[pos] != 0xff {
+                return Err(Jpeg::invalid());
+            }
+
+            let marker = data[pos + 1];
+            match marker {
+                0xff => self.pos += 1,
+                0xd8 | 0xd0..=0xd7 | 0x01 => self.pos += 2,
+                0xd9 => {
+                    let decoded =
+                        Jpeg::decode_prefix(&data[..pos]).ok_or(Error::InvalidImageData)?;
+                    if image.shape() != decoded.shape() {
+                        *image = decoded;
+                    } else {
+                        let rows = self.rows..self.height;
+                        Jpeg::copy_rows(&decoded, image, 0..self.height);
+                        if !self.progressive && !rows.is_empty() {
+                            report(image, Update::Rows(rows));
+                        }
+                    }
+                    report(image, Update::Complete);
+                    self.pos += 2;
+                    break;
+                }
+                _ => {
+                    if data.len() < pos + 4 {
+                        break;
+                    }
+                    let len = usize::from(u16::from_be_bytes([data[pos + 2], data[pos + 3]]));
+                    if len < 2 {
+                        return Err(Jpeg::invalid());
+                    }
+                    if data.len() < pos + 2 + len {
+                        break;
+                    }
+                    let segment = &data[pos + 4..pos + 2 + len];
+                    match marker {
+                        0xc0..=0xc2 => self.frame(marker, segment, image)?,
+                        0xc3 | 0xc5..=0xc7 | 0xc9..=0xcb | 0xcd..=0xcf => self.supported = false,
+                        0xc4 => self.huffman_tables(segment)?,
+                        0xdd if segment.len() >= 2 => {
+                            self.restart_interval =
+                                usize::from(u16::from_be_bytes([segment[0], segment[1]]))
+                        }
+                        0xda => self.start_scan(segment, pos + 2 + len)?,
+                        _ => (),
+                    }
+                    self.pos += 2 + len;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse a start of frame segment
+    fn frame(
+        &mut self,
+        marker: u8,
+        segment: &[u8],
+        image: &mut ImageBuf<u8, Rgba>,
+    ) -> Result<(), Error> {
+        if segment.len() < 6 || segment.len() < 6 + segment[5] as usize * 3 {
+            return Err(Jpeg::invalid());
+        }
+
+        self.height = usize::from(u16::from_be_bytes([segment[1], segment[2]]));
+        self.width = usize::from(u16::from_be_bytes([segment[3], segment[4]]));
+        self.progressive = marker == 0xc2;
+        self.components = segment[6..6 + segment[5] as usize * 3]
+            .chunks_exact(3)
+            .map(|c| Component {
+                id: c[0],
+                h: usize::from(c[1] >> 4).max(1),
+                v: usize::from(c[1] & 15).max(1),
+            })
+            .collect();
+        self.supported = segment[0] == 8 && self.width > 0 && self.height > 0;
+        if self.supported {
+            *image = ImageBuf::new(self.width, self.height);
+        }
+        Ok(())
+    }
+
+    /// Parse a `DHT` segment, which can contain several tables
+    fn huffman_tables(&mut self, mut segment: &[u8]) -> Result<(), Error> {
+        while segment.len() >= 17 {
+            let (class, id) = (usize::from(segment[0] >> 4), usize::from(segment[0] & 15));
+            let counts = &segment[1..17];
+            let n: usize = counts.iter().map(|&x| usize::from(x)).sum();
+            if class > 1 || id > 3 || segment.len() < 17 + n {
+                return Err(Jpeg::invalid());
+            }
+            self.tables[class * 4 + id] = Some(Huffman::from_counts(counts, &segment[17..17 + n]));
+            segment = &segment[17 + n..];
+        }
+        Ok(())
+    }
+
+    /// Parse a start of scan segment, rows are only counted for sequential scans containing every
+    /// component
+    fn start_scan(&mut self, segment: &[u8], start: usize) -> Result<(), Error> {
+        let n = usize::from(*segment.first().ok_or_else(Jpeg::invalid)?);
+        if segment.len() < 1 + n * 2 {
+            return Err(Jpeg::invalid());
+        }
+
+        self.scan_start = Some(start);
+        self.search = start;
+        self.scan = None;
+        if self.progressive || !self.supported || n != self.components.len() || self.scans > 0 {
+            return Ok(());
+        }
+
+        let mut components = Vec::new();
+        for c in segment[1..1 + n * 2].chunks_exact(2) {
+            let component = match self.components.iter().find(|x| x.id == c[0]) {
+                Some(component) => component,
+                None => return Err(Jpeg::invalid()),
+            };
+            let (dc, ac) = (usize::from(c[1] >> 4), 4 + usize::from(c[1] & 15));
+            if dc > 3 || ac > 7 || self.tables[dc].is_none() || self.tables[ac].is_none() {
+                return Ok(());
+            }
+            let blocks = if n == 1 { 1 } else { component.h * component.v };
+            components.push((blocks, dc, ac));
+        }
+
+        self.scan = Some(Scan {
+            components,
+            bits: EntropyBits {
+                pos: start,
+                acc: 0,
+                count: 0,
+            },
+            mcus: 0,
+        });
+        Ok(())
+    }
+
+    /// Find the marker that ends the entropy coded data starting at `start`
+    fn scan_end(&mut self, data: &[u8], start: usize) -> Option<usize> {
+        let mut i = self.search.max(start);
+        while i + 1 < data.len() {
+            if data[i] == 0xff {
+                let m = data[i + 1];
+                if m != 0 && !(0xd0..=0xd7).contains(&m) {
+                    return Some(i);
+                }
+                i += 2;
+            } else {
+                i += 1;
+            }
+        }
+        self.search = i;
+        None
+    }
+
+    /// Size of an MCU in pixels and the number of MCUs in each row
+    fn mcu_size(&self, scan: &Scan) -> (usize, usize) {
+        let hmax = self.components.iter().map(|c| c.h).max().unwrap_or(1);
+        let vmax = self.components.iter().map(|c| c.v).max().unwrap_or(1);
+        if scan.components.len() == 1 {
+            return (self.width.div_ceil(8), 8);
+        }
+
+        let mcu_width = 8 * hmax;
+        (self.width.div_ceil(mcu_width), 8 * vmax)
+    }
+
+    /// Count the complete MCUs of a sequential scan and report the rows they cover
+    fn count_mcus(
+        &mut self,
+        data: &[u8],
+        image: &mut ImageBuf<u8, Rgba>,
+        report: &mut Report,
+    ) -> Result<(), Error> {
+        let mut scan = match self.scan.take() {
+            Some(scan) => scan,
+            None => return Ok(()),
+        };
+        let (mcus_per_row, mcu_height) = self.mcu_size(&scan);
+        let total = mcus_per_row * (self.height.div_ceil(mcu_height));
+
+        while scan.mcus < total {
+            let mut bits = scan.bits.clone();
+            if self.restart_interval > 0
+                && scan.mcus > 0
+                && scan.mcus % self.restart_interval == 0
+                && bits.restart(data).is_none()
+            {
+                break;
+            }
+            if !self.skip_mcu(data, &scan, &mut bits)? {
+                break;
+            }
+            scan.bits = bits;
+            scan.mcus += 1;
+        }
+
+        // Chroma rows are interpolated when upsampling, so the last row of an MCU depends on the
+        // next one
+        let vmax = self.components.iter().map(|c| c.v).max().unwrap_or(1);
+        let subsampled = self.components.iter().any(|c| c.v < vmax);
+        let mut rows = ((scan.mcus / mcus_per_row) * mcu_height).min(self.height);
+        if subsampled && rows < self.height {
+            rows = rows.saturating_sub(1);
+        }
+
+        // Every update decodes all of the data again, waiting until the data has doubled keeps
+        // the total time linear in the size of the file
+        let end = scan.bits.pos;
+        if rows > self.rows && end >= self.decoded * 2 {
+            if let Some(decoded) = Jpeg::decode_prefix(&data[..end]) {
+                Jpeg::copy_rows(&decoded, image, self.rows..rows);
+                report(image, Update::Rows(self.rows..rows));
+                self.rows = rows;
+                self.decoded = end;
+            }
+        }
+        self.scan = Some(scan);
+        Ok(())
+    }
+
+    /// Skip the coefficients of a single MCU, returns false when the data ends first
+    fn skip_mcu(&self, data: &[u8], scan: &Scan, bits: &mut EntropyBits) -> Result<bool, Error> {
+        for &(blocks, dc, ac) in &scan.components {
+            let (dc, ac) = match (&self.tables[dc], &self.tables[ac]) {
+                (Some(dc), Some(ac)) => (dc, ac),
+                _ => return Err(Jpeg::invalid()),
+            };
+            for _ in 0..blocks {
+                let size = match dc.decode(|| bits.bit(data))? {
+                    Some(size) if size <= 16 => size,
+                    Some(_) => return Err(Jpeg::invalid()),
+                    None => return Ok(false),
+                };
+                if bits.skip(data, size).is_none() {
+                    return Ok(false);
+                }
+
+                let mut k = 1;
+                while k < 64 {
+                    let rs = match ac.decode(|| bits.bit(data))? {
+                        Some(rs) => rs,
+                        None => return Ok(false),
+                    };
+                    let (run, size) = (rs >> 4, rs & 15);
+                    if size == 0 {
+                        if run != 15 {
+                            break;
+                        }
+                        k += 16;
+                    } else {
+                        k += run + 1;
+                        if bits.skip(data, size).is_none() {
+                            return Ok(false);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::io::png::{deflate, write_chunk, Chunk};
+    use crate::io::{decode, encode_jpg_u8, encode_png_u8};
+    use crate::{Gray, Rgb};
+
+    /// Decode `data` pushing `chunk_size` bytes at a time, returns the updates and the image
+    fn decode_chunks(data: &[u8], chunk_size: usize) -> (Vec<Update>, ImageBuf<u8, Rgba>) {
+        let mut updates = Vec::new();
+        let mut decoder = Decoder::new(|_, update| updates.push(update));
+        for chunk in data.chunks(chunk_size) {
+            decoder.push(chunk).unwrap();
+        }
+        let image = decoder.finish().unwrap();
+        (updates, image)
+    }
+
+    /// Check that reported rows are contiguous and cover the whole image
+    fn assert_rows(updates: &[Update], height: usize) {
+        let mut next = 0;
+        for update in &updates[..updates.len() - 1] {
+            match update {
+                Update::Rows(rows) => {
+                    assert_eq!(rows.start, next);
+                    assert!(rows.end > rows.start);
+                    next = rows.end;
+                }
+                _ => panic!("unexpected update {:?}", update),
+            }
+        }
+        assert_eq!(next, height);
+        assert_eq!(updates.last(), Some(&Update::Complete));
+    }
+
+    /// Filter a scanline using `filter`, the inverse of `unfilter`
+    fn filter_row(filter: u8, row: &[u8], prev: &[u8], bpp: usize) -> Vec<u8> {
+        let mut out = vec![filter];
+        for i in 0..row.len() {
+            let (a, c) = if i >= bpp {
+                (row[i - bpp], prev[i - bpp])
+            } else {
+                (0, 0)
+            };
+            let b = prev[i];
+            let predicted = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((u16::from(a) + u16::from(b)) / 2) as u8,
+                _ => paeth(a, b, c),
+            };
+            out.push(row[i].wrapping_sub(predicted));
+        }
+        out
+    }
+
+    /// Encode a PNG file with the given samples, using every filter type
+    fn encode(
+        header: &[u8],
+        chunks: &[Chunk],
+        sample: impl Fn(usize, usize, usize) -> u16,
+    ) -> Vec<u8> {
+        let h = Header::parse(header).unwrap();
+        let passes = if h.interlaced { 7 } else { 1 };
+        let mut raw = Vec::new();
+        for (pass, &(x, y, dx, dy, _, _)) in ADAM7.iter().enumerate().take(passes) {
+            let (width, height) = h.pass_size(pass);
+            let (x0, y0, dx, dy) = if h.interlaced {
+                (x, y, dx, dy)
+            } else {
+                (0, 0, 1, 1)
+            };
+            let stride = (width * h.channels() * h.depth).div_ceil(8);
+            let mut prev = vec![0; stride];
+            for row in 0..height {
+                let mut bytes = vec![0u8; stride];
+                for i in 0..width {
+                    for c in 0..h.channels() {
+                        let v = sample(x0 + i * dx, y0 + row * dy, c);
+                        let k = i * h.channels() + c;
+                        match h.depth {
+                            16 => bytes[k * 2..k * 2 + 2].copy_from_slice(&v.to_be_bytes()),
+                            8 => bytes[k] = v as u8,
+                            d => bytes[k * d / 8] |= (v as u8) << (8 - d - k * d % 8),
+                        }
+                    }
+                }
+                raw.extend(filter_row((row % 5) as u8, &bytes, &prev, h.filter_bytes()));
+                prev = bytes;
+            }
+        }
+
+        let mut png = SIGNATURE.to_vec();
+        write_chunk(&mut png, b"IHDR", header).unwrap();
+        for (kind, data) in chunks {
+            write_chunk(&mut png, kind, data).unwrap();
+        }
+        let compressed = deflate(&raw).unwrap();
+        for part in compressed.chunks(100) {
+            write_chunk(&mut png, b"IDAT", part).unwrap();
+        }
+        write_chunk(&mut png, b"IEND", &[]).unwrap();
+        png
+    }
+
+    fn ihdr(width: u32, height: u32, depth: u8, color: u8, interlaced: bool) -> Vec<u8> {
+        let mut header = width.to_be_bytes().to_vec();
+        header.extend_from_slice(&height.to_be_bytes());
+        header.extend_from_slice(&[depth, color, 0, 0, interlaced as u8]);
+        header
+    }
+
+    #[test]
+    fn test_progressive_png() {
+        let image: ImageBuf<u8, Rgba> = ImageBuf::from_fn(37, 23, |x, y| {
+            [
+                (x * 7) as u8,
+                (y * 11) as u8,
+                (x * y * 37 % 251) as u8,
+                255 - x as u8,
+            ]
+        });
+        let data = encode_png_u8(&image).unwrap();
+        let (updates, output) = decode_chunks(&data, 20);
+        assert_rows(&updates, 23);
+        assert!(updates.len() > 3);
+        assert_eq!(output, image);
+
+        let gray: ImageBuf<u8, Gray> = ImageBuf::from_fn(20, 9, |x, y| [(x * y) as u8]);
+        let data = encode_png_u8(&gray).unwrap();
+        let (updates, output) = decode_chunks(&data, 1);
+        assert_rows(&updates, 9);
+        assert_eq!(output, decode::<_, u8, Rgba>(&data).unwrap());
+
+        // Formats and bit depths not written by stb_image, compared to stb_image's decoder
+        let plte: Vec<u8> = (0..16u8)
+            .flat_map(|i| vec![i * 16, 255 - i * 16, i])
+            .collect();
+        let trns: Vec<u8> = (0..16u8).map(|i| i * 17).collect();
+        let cases: Vec<(Vec<u8>, Vec<Chunk>)> = vec![
+            (ihdr(19, 13, 16, 2, true), vec![]),
+            (
+                ihdr(19, 13, 4, 3, false),
+                vec![(b"PLTE", &plte), (b"tRNS", &trns)],
+            ),
+            (ihdr(19, 13, 2, 0, false), vec![(b"tRNS", &[0, 1])]),
+            (ihdr(19, 13, 1, 0, true), vec![]),
+            (ihdr(19, 13, 8, 4, true), vec![]),
+            (ihdr(19, 13, 16, 6, false), vec![]),
+        ];
+        for (header, chunks) in &cases {
+            let depth = header[8];
+            let max = (1u32 << depth) - 1;
+            let data = encode(header, chunks, |x, y, c| {
+                ((x * 31 + y * 17 + c * 59) as u32 * 2731 % (max + 1)) as u16
+            });
+            let expected: ImageBuf<u8, Rgba> = decode(&data).unwrap();
+            let mut snapshot = None;
+            let mut updates = Vec::new();
+            let mut decoder = Decoder::new(|image, update| {
+                if update == Update::Pass(1) {
+                    snapshot = Some(Clone::clone(image));
+                }
+                updates.push(update);
+            });
+            for chunk in data.chunks(7) {
+                decoder.push(chunk).unwrap();
+            }
+            assert!(decoder.is_complete());
+            assert_eq!(decoder.finish().unwrap(), expected);
+
+            if header[12] == 1 {
+                let passes: Vec<_> = (1..=7)
+                    .map(Update::Pass)
+                    .chain(Some(Update::Complete))
+                    .collect();
+                assert_eq!(updates, passes);
+
+                // The first pass fills 8x8 blocks
+                let snapshot = snapshot.unwrap();
+                assert_eq!(snapshot.at(7, 7), expected.at(0, 0));
+                assert_eq!(snapshot.at(18, 12), expected.at(16, 8));
+            } else {
+                assert_rows(&updates, 13);
+            }
+        }
+    }
+
+    #[test]
+    fn test_progressive_png_errors() {
+        let image: ImageBuf<u8, Rgb> = ImageBuf::new(8, 8);
+        let data = encode_png_u8(&image).unwrap();
+        let mut decoder = Decoder::new(|_, _| ());
+        decoder.push(&data[..data.len() / 2]).unwrap();
+        assert!(!decoder.is_complete());
+        assert!(decoder.finish().is_err());
+
+        let mut data = data;
+        let idat = data.windows(4).position(|x| x == b"IDAT").unwrap();
+        data[idat + 4] = 0x78;
+        data[idat + 5] = 0;
+        let mut decoder = Decoder::new(|_, _| ());
+        assert!(decoder.push(&data).is_err());
+
+        // Sizes that can't be allocated are errors
+        for (width, height) in [(1 << 31, 1), (0x7fff_ffff, 0x7fff_ffff)] {
+            let mut data = SIGNATURE.to_vec();
+            write_chunk(&mut data, b"IHDR", &ihdr(width, height, 8, 6, false)).unwrap();
+            let mut decoder = Decoder::new(|_, _| ());
+            assert!(decoder.push(&data).is_err());
+        }
+
+        // A second IHDR chunk can't replace the image
+        let image: ImageBuf<u8, Rgb> = ImageBuf::from_fn(8, 8, |x, y| [x as u8, y as u8, 0]);
+        let data = encode_png_u8(&image).unwrap();
+        let idat = data.windows(4).position(|x| x == b"IDAT").unwrap() - 4;
+        let len = u32::from_be_bytes([data[idat], data[idat + 1], data[idat + 2], data[idat + 3]]);
+        let compressed = &data[idat + 8..idat + 8 + len as usize];
+        let mut twice = SIGNATURE.to_vec();
+        let header = ihdr(8, 8, 8, 2, false);
+        write_chunk(&mut twice, b"IHDR", &header).unwrap();
+        write_chunk(&mut twice, b"IDAT", &compressed[..2]).unwrap();
+        write_chunk(&mut twice, b"IHDR", &header).unwrap();
+        write_chunk(&mut twice, b"IDAT", &compressed[2..]).unwrap();
+        write_chunk(&mut twice, b"IEND", &[]).unwrap();
+        let mut decoder = Decoder::new(|_, _| ());
+        assert!(decoder.push(&twice).is_err());
+    }
+
+    #[test]
+    fn test_progressive_jpeg() {
+        // Baseline JPEG with 4:2:0 chroma subsampling
+        let image: ImageBuf<u8, Rgb> = ImageBuf::from_fn(64, 48, |x, y| {
+            [(x * 4) as u8, (y * 5) as u8, ((x * y) % 256) as u8]
+        });
+        let data = encode_jpg_u8(&image, 80).unwrap();
+        let expected: ImageBuf<u8, Rgba> = decode(&data).unwrap();
+        let mut updates = Vec::new();
+        let mut decoder = Decoder::new(|image, update| {
+            // Reported rows already have their final values
+            if let Update::Rows(rows) = &update {
+                let row_len = image.width() * 4;
+                let range = rows.start * row_len..rows.end * row_len;
+                assert_eq!(image.data()[range.clone()], expected.data()[range]);
+            }
+            updates.push(update);
+        });
+        for chunk in data.chunks(64) {
+            decoder.push(chunk).unwrap();
+        }
+        assert_eq!(decoder.finish().unwrap(), expected);
+        assert_rows(&updates, 48);
+        assert!(updates.len() > 2);
+
+        let data = std::fs::read("test/test.jpg").unwrap();
+        let (updates, output) = decode_chunks(&data, 32 * 1024);
+        assert_rows(&updates, 900);
+        assert_eq!(output, decode::<_, u8, Rgba>(&data).unwrap());
+
+        // A 16x8 grayscale progressive JPEG with a DC scan followed by an AC scan that adds a
+        // horizontal gradient to the first block
+        let mut data = vec![0xff, 0xd8, 0xff, 0xdb, 0x00, 0x43, 0x00];
+        data.extend_from_slice(&[1; 64]);
+        data.extend_from_slice(&[
+            0xff, 0xc2, 0x00, 0x0b, 0x08, 0x00, 0x08, 0x00, 0x10, 0x01, 0x01, 0x11, 0x00,
+        ]);
+        data.extend_from_slice(&[0xff, 0xc4, 0x00, 0x14, 0x00, 0x01]);
+        data.extend_from_slice(&[0; 15]);
+        data.push(0x00);
+        data.extend_from_slice(&[0xff, 0xc4, 0x00, 0x15, 0x10, 0x00, 0x02]);
+        data.extend_from_slice(&[0; 14]);
+        data.extend_from_slice(&[0x00, 0x06]);
+        data.extend_from_slice(&[0xff, 0xda, 0x00, 0x08, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00]);
+        data.push(0x3f);
+        data.extend_from_slice(&[0xff, 0xda, 0x00, 0x08, 0x01, 0x01, 0x00, 0x01, 0x3f, 0x00]);
+        data.extend_from_slice(&[0x60, 0x0f, 0xff, 0xd9]);
+
+        let expected: ImageBuf<u8, Rgba> = decode(&data).unwrap();
+        let mut previews = Vec::new();
+        let mut updates = Vec::new();
+        let mut decoder = Decoder::new(|image, update| {
+            if let Update::Pass(_) = update {
+                previews.push(Clone::clone(image));
+            }
+            updates.push(update);
+        });
+        for byte in &data {
+            decoder.push(&[*byte]).unwrap();
+        }
+        let output = decoder.finish().unwrap();
+        assert_eq!(
+            updates,
+            vec![Update::Pass(1), Update::Pass(2), Update::Complete]
+        );
+        assert_eq!(output, expected);
+        assert!(previews[0].data().iter().all(|&x| x == 128 || x == 255));
+        assert_eq!(previews[1], expected);
+        assert_ne!(expected.at(0, 0), expected.at(7, 0));
+    }
+
+    #[test]
+    fn test_progressive_other() {
+        let image: ImageBuf<u8, Rgb> = ImageBuf::new_with(5, 4, [1, 2, 3]);
+        crate::io::write_bmp_u8("test/test-progressive.bmp", &image).unwrap();
+        let data = std::fs::read("test/test-progressive.bmp").unwrap();
+        let mut updates = Vec::new();
+        let output = decode_from(&data[..], |_, update| updates.push(update)).unwrap();
+        assert_eq!(updates, vec![Update::Complete]);
+        assert_eq!(output.at(4, 3), &[1, 2, 3, 255]);
+    }
+}
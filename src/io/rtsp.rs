@@ -0,0 +1,390 @@
+//! Receive frames from RTSP and other network cameras
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use image2::io::rtsp::{self, Options, Transport};
+//!
+//! let stream = Options::new()
+//!     .transport(Transport::Tcp)
+//!     .low_latency(true)
+//!     .buffer(1)
+//!     .connect("rtsp://192.168.1.10:554/stream1")?;
+//! for frame in stream.take(100) {
+//!     let frame = frame?;
+//!     println!("{:?}", frame.timestamp);
+//! }
+//!
+//! // Using the default options
+//! let mut stream = rtsp::connect("rtsp://192.168.1.11:554/stream1")?;
+//! let frame = stream.next_timeout(Duration::from_secs(1))?;
+//! # Ok::<(), image2::Error>(())
+//! ```
+//!
+//! Frames are decoded using `video::Decoder` on a separate thread and queued until they are
+//! read. When the queue is full the oldest frame is dropped, so a slow consumer always sees
+//! recent frames instead of falling further behind the camera. When the connection is lost the
+//! stream reconnects with an increasing delay, frame timestamps start from zero again after
+//! reconnecting.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::Error;
+
+use super::video::{Decoder, Frame};
+
+/// Longest delay between reconnection attempts
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// How RTSP media is transported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    /// Interleaved in the RTSP connection, works through firewalls and doesn't lose packets
+    Tcp,
+    /// Separate UDP ports, lower latency but packets can be lost
+    Udp,
+}
+
+/// Connection settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    transport: Transport,
+    timeout: Duration,
+    low_latency: bool,
+    buffer: usize,
+    attempts: Option<u32>,
+    reconnect_delay: Duration,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options::new()
+    }
+}
+
+impl Options {
+    /// Use TCP with a 5 second timeout and a queue of 8 frames, reconnecting forever
+    pub fn new() -> Options {
+        Options {
+            transport: Transport::Tcp,
+            timeout: Duration::from_secs(5),
+            low_latency: false,
+            buffer: 8,
+            attempts: None,
+            reconnect_delay: Duration::from_millis(500),
+        }
+    }
+
+    pub fn transport(mut self, transport: Transport) -> Options {
+        self.transport = transport;
+        self
+    }
+
+    /// Time without data after which the connection is considered lost
+    pub fn timeout(mut self, timeout: Duration) -> Options {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Disable input buffering and frame reordering in the decoder, which reduces latency for
+    /// streams without B-frames
+    pub fn low_latency(mut self, low_latency: bool) -> Options {
+        self.low_latency = low_latency;
+        self
+    }
+
+    /// Number of decoded frames that are queued before the oldest frame is dropped, at least 1
+    pub fn buffer(mut self, frames: usize) -> Options {
+        self.buffer = frames.max(1);
+        self
+    }
+
+    /// Number of consecutive failed reconnection attempts before the stream ends with an error,
+    /// `None` to keep trying forever
+    pub fn reconnect_attempts(mut self, attempts: Option<u32>) -> Options {
+        self.attempts = attempts;
+        self
+    }
+
+    /// Delay before the first reconnection attempt, which doubles after each failed attempt
+    pub fn reconnect_delay(mut self, delay: Duration) -> Options {
+        self.reconnect_delay = delay;
+        self
+    }
+
+    /// `ffmpeg` input arguments
+    fn args(&self) -> Vec<String> {
+        let transport = match self.transport {
+            Transport::Tcp => "tcp",
+            Transport::Udp => "udp",
+        };
+        let mut args = vec![
+            String::from("-rtsp_transport"),
+            String::from(transport),
+            String::from("-timeout"),
+            self.timeout.as_micros().to_string(),
+        ];
+        if self.low_latency {
+            args.extend(["-fflags", "nobuffer", "-flags", "low_delay"].map(String::from));
+        }
+        args
+    }
+
+    /// Delay before reconnection attempt `attempt`, starting from 0
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.reconnect_delay
+            .checked_mul(1 << attempt.min(16))
+            .unwrap_or(MAX_RECONNECT_DELAY)
+            .min(MAX_RECONNECT_DELAY)
+    }
+
+    /// Connect to a camera, returning an error if the first connection fails
+    pub fn connect<S: AsRef<str>>(self, url: S) -> Result<Stream, Error> {
+        let url = url.as_ref().to_string();
+        let decoder = open(&url, &self)?;
+        let shared = Arc::new(Shared::default());
+        let thread_shared = shared.clone();
+        std::thread::spawn(move || receive(url, self, decoder, thread_shared));
+        Ok(Stream { shared })
+    }
+}
+
+fn open(url: &str, options: &Options) -> Result<Decoder, Error> {
+    let args = options.args();
+    let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+    Decoder::open_with(url, &args)
+}
+
+/// Connect to a camera using the default options
+pub fn connect<S: AsRef<str>>(url: S) -> Result<Stream, Error> {
+    Options::new().connect(url)
+}
+
+#[derive(Default)]
+struct State {
+    frames: VecDeque<Frame>,
+    dropped: u64,
+    reconnects: u64,
+    connected: bool,
+    /// Set when the receiving thread gave up, along with the last error
+    finished: bool,
+    error: Option<Error>,
+    /// Set when the stream is dropped
+    stopped: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    ready: Condvar,
+}
+
+impl Shared {
+    fn push(&self, frame: Frame, buffer: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.connected = true;
+        while state.frames.len() >= buffer {
+            state.frames.pop_front();
+            state.dropped += 1;
+        }
+        state.frames.push_back(frame);
+        self.ready.notify_one();
+    }
+
+    fn finish(&self, error: Option<Error>) {
+        let mut state = self.state.lock().unwrap();
+        state.finished = true;
+        state.error = error;
+        self.ready.notify_one();
+    }
+
+    fn stopped(&self) -> bool {
+        self.state.lock().unwrap().stopped
+    }
+}
+
+/// Decode frames until the stream is dropped or reconnecting fails
+fn receive(url: String, options: Options, mut decoder: Decoder, shared: Arc<Shared>) {
+    while !shared.stopped() {
+        let mut error = match decoder.next() {
+            Some(Ok(frame)) => {
+                shared.push(frame, options.buffer);
+                continue;
+            }
+            Some(Err(err)) => Some(err),
+            None => None,
+        };
+
+        shared.state.lock().unwrap().connected = false;
+        let mut attempt = 0;
+        loop {
+            if shared.stopped() {
+                return;
+            }
+
+            if options.attempts.is_some_and(|max| attempt >= max) {
+                shared.finish(error);
+                return;
+            }
+
+            std::thread::sleep(options.backoff(attempt));
+            attempt += 1;
+            match open(&url, &options) {
+                Ok(reconnected) => {
+                    decoder = reconnected;
+                    shared.state.lock().unwrap().reconnects += 1;
+                    break;
+                }
+                Err(err) => error = Some(err),
+            }
+        }
+    }
+}
+
+/// Frames received from a camera, see the module documentation for an example
+pub struct Stream {
+    shared: Arc<Shared>,
+}
+
+impl Stream {
+    fn wait(&mut self, deadline: Option<Instant>) -> Result<Option<Frame>, Error> {
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            if let Some(frame) = state.frames.pop_front() {
+                return Ok(Some(frame));
+            }
+
+            if state.finished {
+                return match state.error.take() {
+                    Some(err) => Err(err),
+                    None => Ok(None),
+                };
+            }
+
+            state = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Ok(None);
+                    }
+                    self.shared
+                        .ready
+                        .wait_timeout(state, deadline - now)
+                        .unwrap()
+                        .0
+                }
+                None => self.shared.ready.wait(state).unwrap(),
+            };
+        }
+    }
+
+    /// Wait for the next frame, returns `None` when the stream ended because reconnecting
+    /// failed and the error was already returned
+    pub fn next_frame(&mut self) -> Result<Option<Frame>, Error> {
+        self.wait(None)
+    }
+
+    /// Wait at most `timeout` for the next frame
+    pub fn next_timeout(&mut self, timeout: Duration) -> Result<Option<Frame>, Error> {
+        self.wait(Some(Instant::now() + timeout))
+    }
+
+    /// Return the most recent frame without waiting, dropping older queued frames
+    pub fn latest(&mut self) -> Option<Frame> {
+        let mut state = self.shared.state.lock().unwrap();
+        let frame = state.frames.pop_back();
+        let older = state.frames.len() as u64;
+        state.frames.clear();
+        state.dropped += older;
+        frame
+    }
+
+    /// Number of frames dropped because they weren't read before the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.shared.state.lock().unwrap().dropped
+    }
+
+    /// Number of times the connection was re-established
+    pub fn reconnects(&self) -> u64 {
+        self.shared.state.lock().unwrap().reconnects
+    }
+
+    /// Returns false while the stream is reconnecting
+    pub fn is_connected(&self) -> bool {
+        let state = self.shared.state.lock().unwrap();
+        state.connected && !state.finished
+    }
+}
+
+impl Iterator for Stream {
+    type Item = Result<Frame, Error>;
+
+    fn next(&mut self) -> Option<Result<Frame, Error>> {
+        self.next_frame().transpose()
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        // The receiving thread stops after the current frame or reconnection attempt
+        self.shared.state.lock().unwrap().stopped = true;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image_buf::ImageBuf;
+
+    #[test]
+    fn test_rtsp_options() {
+        let options = Options::new()
+            .transport(Transport::Udp)
+            .timeout(Duration::from_secs(2))
+            .low_latency(true)
+            .reconnect_delay(Duration::from_secs(1));
+        assert_eq!(
+            options.args(),
+            [
+                "-rtsp_transport",
+                "udp",
+                "-timeout",
+                "2000000",
+                "-fflags",
+                "nobuffer",
+                "-flags",
+                "low_delay"
+            ]
+        );
+        assert_eq!(options.backoff(0), Duration::from_secs(1));
+        assert_eq!(options.backoff(2), Duration::from_secs(4));
+        assert_eq!(options.backoff(100), MAX_RECONNECT_DELAY);
+
+        // Old frames are dropped when the queue is full
+        let shared = Arc::new(Shared::default());
+        let mut stream = Stream {
+            shared: shared.clone(),
+        };
+        for i in 0..3 {
+            let frame = Frame {
+                image: ImageBuf::new(1, 1),
+                timestamp: Duration::from_secs(i),
+            };
+            shared.push(frame, 2);
+        }
+        assert_eq!(stream.dropped(), 1);
+        assert!(stream.is_connected());
+        let frame = stream.next_timeout(Duration::from_millis(10)).unwrap();
+        assert_eq!(frame.unwrap().timestamp, Duration::from_secs(1));
+        assert_eq!(stream.latest().unwrap().timestamp, Duration::from_secs(2));
+        assert!(stream
+            .next_timeout(Duration::from_millis(10))
+            .unwrap()
+            .is_none());
+
+        shared.finish(Some(Error::Message(String::from("Connection refused"))));
+        assert!(stream.next().unwrap().is_err());
+        assert!(stream.next().is_none());
+    }
+}
@@ -120,3 +120,65 @@ extern "C" {
         out_len: *mut ::std::os::raw::c_int,
     ) -> *mut ::std::os::raw::c_uchar;
 }
+extern "C" {
+    pub fn stbi_load_gif_from_memory(
+        buffer: *const stbi_uc,
+        len: ::std::os::raw::c_int,
+        delays: *mut *mut ::std::os::raw::c_int,
+        x: *mut ::std::os::raw::c_int,
+        y: *mut ::std::os::raw::c_int,
+        z: *mut ::std::os::raw::c_int,
+        comp: *mut ::std::os::raw::c_int,
+        req_comp: ::std::os::raw::c_int,
+    ) -> *mut stbi_uc;
+}
+extern "C" {
+    pub fn stbi_image_free(retval_from_stbi_load: *mut ::std::os::raw::c_void);
+}
+extern "C" {
+    pub fn stbi_zlib_compress(
+        data: *mut ::std::os::raw::c_uchar,
+        data_len: ::std::os::raw::c_int,
+        out_len: *mut ::std::os::raw::c_int,
+        quality: ::std::os::raw::c_int,
+    ) -> *mut ::std::os::raw::c_uchar;
+}
+pub type stbi_write_func = ::std::option::Option<
+    unsafe extern "C" fn(
+        context: *mut ::std::os::raw::c_void,
+        data: *mut ::std::os::raw::c_void,
+        size: ::std::os::raw::c_int,
+    ),
+>;
+extern "C" {
+    pub fn stbi_write_jpg_to_func(
+        func: stbi_write_func,
+        context: *mut ::std::os::raw::c_void,
+        x: ::std::os::raw::c_int,
+        y: ::std::os::raw::c_int,
+        comp: ::std::os::raw::c_int,
+        data: *const ::std::os::raw::c_void,
+        quality: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn stbi_info_from_memory(
+        buffer: *const stbi_uc,
+        len: ::std::os::raw::c_int,
+        x: *mut ::std::os::raw::c_int,
+        y: *mut ::std::os::raw::c_int,
+        comp: *mut ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn stbi_is_16_bit_from_memory(
+        buffer: *const stbi_uc,
+        len: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn stbi_is_hdr_from_memory(
+        buffer: *const stbi_uc,
+        len: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
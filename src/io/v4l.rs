@@ -1,44 +1,1246 @@
-use std::io::Result;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use rscam;
 
-pub struct Webcam {
+use crate::error::Error;
+use crate::image::Image;
+use crate::simd;
+
+/// Pixel formats that can be converted to RGB by `Webcam::capture`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// Packed 8-bit RGB
+    Rgb3,
+    /// Packed 4:2:2 YUV, two pixels are stored as Y0 U Y1 V
+    Yuyv,
+    /// Planar 4:2:0 YUV, a full resolution Y plane followed by an interleaved UV plane
+    Nv12,
+    /// Motion JPEG, each frame is a JPEG image
+    Mjpg,
+}
+
+impl PixelFormat {
+    /// Formats in the order they are tried when starting a capture, uncompressed formats are
+    /// preferred because they don't need to be decoded
+    pub const PREFERRED: [PixelFormat; 4] = [
+        PixelFormat::Rgb3,
+        PixelFormat::Yuyv,
+        PixelFormat::Nv12,
+        PixelFormat::Mjpg,
+    ];
+
+    /// V4L2 four character code
+    pub fn fourcc(self) -> &'static [u8; 4] {
+        match self {
+            PixelFormat::Rgb3 => b"RGB3",
+            PixelFormat::Yuyv => b"YUYV",
+            PixelFormat::Nv12 => b"NV12",
+            PixelFormat::Mjpg => b"MJPG",
+        }
+    }
+
+    /// Get the format from a V4L2 four character code
+    pub fn from_fourcc(fourcc: &[u8]) -> Option<PixelFormat> {
+        PixelFormat::PREFERRED
+            .iter()
+            .copied()
+            .find(|f| &f.fourcc()[..] == fourcc)
+    }
+}
+
+/// Common capture resolutions, used to list the modes of devices that accept any resolution
+/// within a range
+const COMMON_RESOLUTIONS: [(u32, u32); 10] = [
+    (320, 240),
+    (640, 360),
+    (640, 480),
+    (800, 600),
+    (1024, 768),
+    (1280, 720),
+    (1280, 960),
+    (1600, 1200),
+    (1920, 1080),
+    (3840, 2160),
+];
+
+/// A video capture device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Device {
+    /// Path of the device node, for example `/dev/video0`
+    pub path: PathBuf,
+    /// Name reported by the driver
+    pub name: String,
+}
+
+/// A combination of pixel format and resolution supported by a device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mode {
+    /// V4L2 four character code
+    pub fourcc: [u8; 4],
+    /// Description of the format reported by the driver
+    pub description: String,
+    pub width: u32,
+    pub height: u32,
+    /// Supported frame intervals as (numerator, denominator) in seconds, for stepwise
+    /// intervals only the shortest and longest are listed
+    pub intervals: Vec<(u32, u32)>,
+}
+
+impl Mode {
+    /// Returns the pixel format if frames in this mode can be converted by `Webcam::capture`
+    pub fn pixel_format(&self) -> Option<PixelFormat> {
+        PixelFormat::from_fourcc(&self.fourcc)
+    }
+
+    /// Highest supported frame rate in frames per second
+    pub fn max_fps(&self) -> f64 {
+        self.intervals
+            .iter()
+            .filter(|(num, _)| *num > 0)
+            .map(|(num, den)| f64::from(*den) / f64::from(*num))
+            .fold(0.0, f64::max)
+    }
+}
+
+/// Common camera controls, see `Webcam::controls` for everything supported by a device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraControl {
+    /// Automatic exposure, a menu control in V4L2
+    AutoExposure,
+    /// Exposure time in units of 100 microseconds
+    Exposure,
+    Gain,
+    AutoFocus,
+    Focus,
+    AutoWhiteBalance,
+    /// White balance temperature in Kelvin
+    WhiteBalanceTemperature,
+}
+
+impl CameraControl {
+    /// V4L2 control ID
+    pub fn id(self) -> u32 {
+        match self {
+            CameraControl::AutoExposure => rscam::CID_EXPOSURE_AUTO,
+            CameraControl::Exposure => rscam::CID_EXPOSURE_ABSOLUTE,
+            CameraControl::Gain => rscam::CID_GAIN,
+            CameraControl::AutoFocus => rscam::CID_FOCUS_AUTO,
+            CameraControl::Focus => rscam::CID_FOCUS_ABSOLUTE,
+            CameraControl::AutoWhiteBalance => rscam::CID_AUTO_WHITE_BALANCE,
+            CameraControl::WhiteBalanceTemperature => rscam::CID_WHITE_BALANCE_TEMPERATURE,
+        }
+    }
+}
+
+/// `CameraControl::AutoExposure` menu values
+const EXPOSURE_AUTO: i64 = 0;
+const EXPOSURE_MANUAL: i64 = 1;
+const EXPOSURE_APERTURE_PRIORITY: i64 = 3;
+
+/// Current value and range of a control
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlValue {
+    Integer {
+        value: i64,
+        default: i64,
+        minimum: i64,
+        maximum: i64,
+        step: i64,
+    },
+    Boolean {
+        value: bool,
+        default: bool,
+    },
+    /// Menu items are listed as (value, name)
+    Menu {
+        value: i64,
+        default: i64,
+        items: Vec<(i64, String)>,
+    },
+    /// Buttons, strings and other controls that can't be set using `Webcam::set_control`
+    Other,
+}
+
+impl ControlValue {
+    /// The current value as an integer, booleans are 0 or 1
+    pub fn value(&self) -> Option<i64> {
+        match self {
+            ControlValue::Integer { value, .. } | ControlValue::Menu { value, .. } => Some(*value),
+            ControlValue::Boolean { value, .. } => Some(*value as i64),
+            ControlValue::Other => None,
+        }
+    }
+}
+
+/// A control supported by a device
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlInfo {
+    pub id: u32,
+    pub name: String,
+    pub value: ControlValue,
+    /// The control can't be changed
+    pub read_only: bool,
+    /// The control can't be changed right now, for example exposure while auto exposure is on
+    pub inactive: bool,
+}
+
+impl From<rscam::Control> for ControlInfo {
+    fn from(control: rscam::Control) -> ControlInfo {
+        let value = match control.data {
+            rscam::CtrlData::Integer {
+                value,
+                default,
+                minimum,
+                maximum,
+                step,
+            } => ControlValue::Integer {
+                value: value.into(),
+                default: default.into(),
+                minimum: minimum.into(),
+                maximum: maximum.into(),
+                step: step.into(),
+            },
+            rscam::CtrlData::Integer64 {
+                value,
+                default,
+                minimum,
+                maximum,
+                step,
+            } => ControlValue::Integer {
+                value,
+                default,
+                minimum,
+                maximum,
+                step,
+            },
+            rscam::CtrlData::Boolean { value, default } => ControlValue::Boolean { value, default },
+            rscam::CtrlData::Menu {
+                value,
+                default,
+                items,
+            } => ControlValue::Menu {
+                value: value.into(),
+                default: default.into(),
+                items: items
+                    .into_iter()
+                    .map(|item| (item.index.into(), item.name))
+                    .collect(),
+            },
+            _ => ControlValue::Other,
+        };
+
+        ControlInfo {
+            id: control.id,
+            name: control.name,
+            value,
+            read_only: control.flags & (rscam::FLAG_READ_ONLY | rscam::FLAG_DISABLED) != 0,
+            inactive: control.flags & rscam::FLAG_INACTIVE != 0,
+        }
+    }
+}
+
+/// Capture settings negotiated with a device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptureConfig {
+    pub format: PixelFormat,
+    pub width: u32,
+    pub height: u32,
+    /// Frame interval as (numerator, denominator) in seconds
+    pub interval: (u32, u32),
+    /// Number of buffers shared with the driver
+    pub buffers: u32,
+}
+
+impl CaptureConfig {
+    /// Frame rate in frames per second
+    pub fn fps(&self) -> f64 {
+        f64::from(self.interval.1) / f64::from(self.interval.0.max(1))
+    }
+}
+
+/// Configures and starts a `Webcam`
+///
+/// ```rust,no_run
+/// use image2::io::v4l::{PixelFormat, WebcamBuilder};
+///
+/// let mut webcam = WebcamBuilder::new("/dev/video0")
+///     .resolution(1920, 1080)
+///     .format(PixelFormat::Mjpg)
+///     .fps(60)
+///     .buffers(4)
+///     .start()?;
+/// println!("{:?}", webcam.config());
+/// let frame = webcam.capture()?;
+/// # Ok::<(), image2::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebcamBuilder {
+    device: String,
     width: u32,
     height: u32,
-    handle: rscam::Camera,
+    format: Option<PixelFormat>,
+    interval: (u32, u32),
+    buffers: u32,
 }
 
-impl Webcam {
-    fn config<'a>(width: u32, height: u32) -> rscam::Config<'a> {
-        let mut cfg = rscam::Config::default();
-        cfg.format = b"RGB3";
-        cfg.resolution = (width, height);
-        cfg.interval = (1, 30);
-        cfg
+impl WebcamBuilder {
+    /// Capture 640x480 frames at 30 frames per second using two buffers, the pixel format is
+    /// negotiated with the device
+    pub fn new(device: &str) -> WebcamBuilder {
+        WebcamBuilder {
+            device: device.to_string(),
+            width: 640,
+            height: 480,
+            format: None,
+            interval: (1, 30),
+            buffers: 2,
+        }
+    }
+
+    pub fn resolution(mut self, width: u32, height: u32) -> WebcamBuilder {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    /// Use a specific pixel format instead of the first supported format in
+    /// `PixelFormat::PREFERRED`
+    pub fn format(mut self, format: PixelFormat) -> WebcamBuilder {
+        self.format = Some(format);
+        self
+    }
+
+    /// Set the frame interval in seconds as a fraction, when the device doesn't support it the
+    /// closest supported interval is used
+    pub fn interval(mut self, numerator: u32, denominator: u32) -> WebcamBuilder {
+        self.interval = (numerator, denominator);
+        self
+    }
+
+    /// Set the frame interval to `1 / fps`
+    pub fn fps(self, fps: u32) -> WebcamBuilder {
+        self.interval(1, fps)
+    }
+
+    /// Set the number of buffers shared with the driver, more buffers make dropped frames less
+    /// likely when processing is slow at the cost of latency
+    pub fn buffers(mut self, buffers: u32) -> WebcamBuilder {
+        self.buffers = buffers.max(1);
+        self
+    }
+
+    /// Open the device and start capturing, use `Webcam::config` to get the negotiated settings
+    pub fn start(self) -> Result<Webcam, Error> {
+        let mut webcam = Webcam {
+            handle: open(&self.device)?,
+            settings: self,
+            config: None,
+            pool: Vec::new(),
+        };
+        webcam.start()?;
+        Ok(webcam)
     }
+}
 
-    pub fn new(device: &str, width: u32, height: u32) -> Result<Webcam> {
-        let cam = rscam::Camera::new(device)?;
+pub struct Webcam {
+    settings: WebcamBuilder,
+    config: Option<CaptureConfig>,
+    /// Images returned by `recycle`, reused by `capture`
+    pool: Vec<crate::ImageBuf<u8, crate::Rgb>>,
+    handle: rscam::Camera,
+}
+
+impl Webcam {
+    /// Open a device, `start` must be called before capturing. Use `WebcamBuilder` to configure
+    /// the pixel format, frame rate or number of buffers.
+    pub fn new(device: &str, width: u32, height: u32) -> Result<Webcam, Error> {
+        let cam = open(device)?;
 
         Ok(Webcam {
-            width,
-            height,
+            settings: WebcamBuilder::new(device).resolution(width, height),
+            config: None,
+            pool: Vec::new(),
             handle: cam,
         })
     }
 
-    pub fn start(&mut self) -> rscam::Result<()> {
-        let cfg = Self::config(self.width, self.height);
-        self.handle.start(&cfg)
+    pub fn builder(device: &str) -> WebcamBuilder {
+        WebcamBuilder::new(device)
+    }
+
+    /// List the video capture devices in `/dev`, sorted by device number. Nodes that don't
+    /// support any capture formats, like the metadata nodes of UVC cameras, are skipped.
+    pub fn list_devices() -> Result<Vec<Device>, Error> {
+        let mut devices = Vec::new();
+        for entry in std::fs::read_dir("/dev")? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let index = match name.to_str().and_then(device_index) {
+                Some(index) => index,
+                None => continue,
+            };
+
+            let path = entry.path();
+            if let Ok(cam) = rscam::Camera::new(&path.to_string_lossy()) {
+                if cam.formats().next().is_none() {
+                    continue;
+                }
+            }
+
+            let sys = Path::new("/sys/class/video4linux").join(&name).join("name");
+            let name = std::fs::read_to_string(sys)
+                .map(|s| s.trim().to_string())
+                .unwrap_or_default();
+            devices.push((index, Device { path, name }));
+        }
+        devices.sort_by_key(|(index, _)| *index);
+        Ok(devices.into_iter().map(|(_, device)| device).collect())
+    }
+
+    /// List the formats, resolutions and frame intervals supported by the device
+    pub fn supported_modes(&self) -> Result<Vec<Mode>, Error> {
+        let mut modes = Vec::new();
+        for format in self.handle.formats() {
+            let format = format?;
+            let resolutions = match self.handle.resolutions(&format.format)? {
+                rscam::ResolutionInfo::Discretes(r) => r,
+                rscam::ResolutionInfo::Stepwise { min, max, step } => {
+                    stepwise_resolutions(min, max, step)
+                }
+            };
+
+            for resolution in resolutions {
+                let intervals = match self.handle.intervals(&format.format, resolution)? {
+                    rscam::IntervalInfo::Discretes(i) => i,
+                    rscam::IntervalInfo::Stepwise { min, max, .. } => vec![min, max],
+                };
+
+                modes.push(Mode {
+                    fourcc: format.format,
+                    description: format.description.clone(),
+                    width: resolution.0,
+                    height: resolution.1,
+                    intervals,
+                });
+            }
+        }
+        Ok(modes)
+    }
+
+    /// List the controls supported by the device
+    pub fn controls(&self) -> Result<Vec<ControlInfo>, Error> {
+        self.handle
+            .controls()
+            .map(|control| Ok(control?.into()))
+            .collect()
+    }
+
+    /// Get the current value and range of a control, `id` is a V4L2 control ID or
+    /// `CameraControl::id`
+    pub fn control(&self, id: u32) -> Result<ControlInfo, Error> {
+        Ok(self.handle.get_control(id)?.into())
+    }
+
+    /// Get the current value of a control
+    pub fn get_control(&self, id: u32) -> Result<i64, Error> {
+        self.control(id)?
+            .value
+            .value()
+            .ok_or_else(|| Error::Message(format!("Control {:#x} has no value", id)))
+    }
+
+    /// Set the value of an integer, boolean or menu control
+    pub fn set_control(&self, id: u32, value: i64) -> Result<(), Error> {
+        let result = match self.handle.get_control(id)?.data {
+            rscam::CtrlData::Boolean { .. } => self.handle.set_control(id, &(value != 0)),
+            rscam::CtrlData::Integer64 { .. } => self.handle.set_control(id, &value),
+            rscam::CtrlData::Integer { .. } | rscam::CtrlData::Menu { .. } => {
+                self.handle.set_control(id, &(value as i32))
+            }
+            _ => return Err(Error::Message(format!("Control {:#x} can't be set", id))),
+        };
+        Ok(result?)
+    }
+
+    /// Turn automatic exposure on or off, using aperture priority mode when the device doesn't
+    /// support fully automatic exposure, which is the case for most UVC cameras
+    pub fn set_auto_exposure(&self, enabled: bool) -> Result<(), Error> {
+        let id = CameraControl::AutoExposure.id();
+        let value = if !enabled {
+            EXPOSURE_MANUAL
+        } else {
+            match self.control(id)?.value {
+                ControlValue::Menu { items, .. }
+                    if !items.iter().any(|(i, _)| *i == EXPOSURE_AUTO) =>
+                {
+                    EXPOSURE_APERTURE_PRIORITY
+                }
+                _ => EXPOSURE_AUTO,
+            }
+        };
+        self.set_control(id, value)
+    }
+
+    /// Set the exposure time in units of 100 microseconds, auto exposure should be turned off
+    /// first
+    pub fn set_exposure(&self, exposure: i64) -> Result<(), Error> {
+        self.set_control(CameraControl::Exposure.id(), exposure)
+    }
+
+    pub fn set_gain(&self, gain: i64) -> Result<(), Error> {
+        self.set_control(CameraControl::Gain.id(), gain)
+    }
+
+    pub fn set_auto_focus(&self, enabled: bool) -> Result<(), Error> {
+        self.set_control(CameraControl::AutoFocus.id(), enabled as i64)
+    }
+
+    /// Set the focus distance, auto focus should be turned off first
+    pub fn set_focus(&self, focus: i64) -> Result<(), Error> {
+        self.set_control(CameraControl::Focus.id(), focus)
+    }
+
+    pub fn set_auto_white_balance(&self, enabled: bool) -> Result<(), Error> {
+        self.set_control(CameraControl::AutoWhiteBalance.id(), enabled as i64)
+    }
+
+    /// Set the white balance temperature in Kelvin, auto white balance should be turned off
+    /// first
+    pub fn set_white_balance_temperature(&self, kelvin: i64) -> Result<(), Error> {
+        self.set_control(CameraControl::WhiteBalanceTemperature.id(), kelvin)
+    }
+
+    /// Pixel format negotiated by `start`
+    pub fn format(&self) -> Option<PixelFormat> {
+        self.config.map(|config| config.format)
+    }
+
+    /// Settings negotiated by `start`
+    pub fn config(&self) -> Option<CaptureConfig> {
+        self.config
+    }
+
+    /// Start capturing using the configured pixel format, or the first format in
+    /// `PixelFormat::PREFERRED` that is supported by the device at the requested resolution
+    pub fn start(&mut self) -> Result<(), Error> {
+        let formats: Vec<PixelFormat> = match self.settings.format {
+            Some(format) => vec![format],
+            None => {
+                let supported: Vec<[u8; 4]> = self
+                    .handle
+                    .formats()
+                    .filter_map(|f| f.ok())
+                    .map(|f| f.format)
+                    .collect();
+                PixelFormat::PREFERRED
+                    .iter()
+                    .copied()
+                    .filter(|format| supported.contains(format.fourcc()))
+                    .collect()
+            }
+        };
+
+        let mut result = Err(rscam::Error::BadFormat);
+        for format in formats {
+            match self.start_format(format) {
+                Ok(config) => {
+                    self.config = Some(config);
+                    return Ok(());
+                }
+                Err(err @ rscam::Error::BadFormat) | Err(err @ rscam::Error::BadResolution) => {
+                    result = Err(err)
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(result?)
+    }
+
+    fn start_format(&mut self, format: PixelFormat) -> rscam::Result<CaptureConfig> {
+        let resolution = (self.settings.width, self.settings.height);
+        let buffers = self.settings.buffers;
+        let config = |interval| rscam::Config {
+            format: format.fourcc(),
+            resolution,
+            interval,
+            nbuffers: buffers,
+            ..rscam::Config::default()
+        };
+
+        let mut interval = self.settings.interval;
+        if let Err(err) = self.handle.start(&config(interval)) {
+            match err {
+                rscam::Error::BadInterval => {
+                    interval = self
+                        .handle
+                        .intervals(format.fourcc(), resolution)
+                        .ok()
+                        .and_then(|supported| closest_interval(interval, &supported))
+                        .ok_or(rscam::Error::BadInterval)?;
+                    self.handle.start(&config(interval))?;
+                }
+                err => return Err(err),
+            }
+        }
+
+        Ok(CaptureConfig {
+            format,
+            width: resolution.0,
+            height: resolution.1,
+            interval,
+            buffers,
+        })
+    }
+
+    /// Capture a frame, converting it to RGB if the camera uses a different format. Images
+    /// passed to `recycle` are reused to avoid allocating a new image for every frame.
+    pub fn capture(&mut self) -> Result<crate::ImageBuf<u8, crate::Rgb>, Error> {
+        self.capture_timestamp().map(|(image, _)| image)
+    }
+
+    /// Capture a frame into an existing image, which is resized if it doesn't match the size of
+    /// the frame. Frames are converted directly from the driver's buffers, so nothing is
+    /// allocated unless the image has to grow.
+    pub fn capture_into(
+        &mut self,
+        image: &mut crate::ImageBuf<u8, crate::Rgb>,
+    ) -> Result<(), Error> {
+        self.capture_into_timestamp(image).map(|_| ())
+    }
+
+    /// Return an image to the pool used by `capture`, the pool holds at most as many images as
+    /// the number of capture buffers
+    pub fn recycle(&mut self, image: crate::ImageBuf<u8, crate::Rgb>) {
+        if self.pool.len() < self.settings.buffers as usize {
+            self.pool.push(image);
+        }
     }
 
-    pub fn capture(&mut self) -> Result<crate::ImageBuf<u8, crate::Rgb>> {
+    /// Capture a frame along with the driver timestamp in microseconds
+    pub(crate) fn capture_timestamp(
+        &mut self,
+    ) -> Result<(crate::ImageBuf<u8, crate::Rgb>, u64), Error> {
+        let mut image = match self.pool.pop() {
+            Some(image) => image,
+            None => crate::ImageBuf::new(0, 0),
+        };
+        let timestamp = self.capture_into_timestamp(&mut image)?;
+        Ok((image, timestamp))
+    }
+
+    fn capture_into_timestamp(
+        &mut self,
+        image: &mut crate::ImageBuf<u8, crate::Rgb>,
+    ) -> Result<u64, Error> {
         let frame = self.handle.capture()?;
-        let (width, height) = frame.resolution;
-        Ok(crate::ImageBuf::new_from(
-            width as usize,
-            height as usize,
-            (*frame).to_vec(),
-        ))
+        let timestamp = frame.get_timestamp();
+        let (width, height) = (frame.resolution.0 as usize, frame.resolution.1 as usize);
+
+        let format = match PixelFormat::from_fourcc(&frame.format) {
+            Some(format) => format,
+            None => {
+                return Err(Error::UnsupportedFormat(
+                    String::from_utf8_lossy(&frame.format).into_owned(),
+                ))
+            }
+        };
+
+        if format == PixelFormat::Mjpg {
+            decode_mjpeg(&frame, image)?;
+        } else {
+            image.reshape(width, height);
+            convert(format, &frame, width, height, image.data_mut())?;
+        }
+        Ok(timestamp)
+    }
+
+    /// Iterate over captured frames, the iterator never ends and yields an error when a frame
+    /// can't be captured
+    ///
+    /// ```rust,no_run
+    /// use image2::io::v4l::Webcam;
+    ///
+    /// let mut webcam = Webcam::builder("/dev/video0").start()?;
+    /// for frame in webcam.frames().take(100) {
+    ///     let frame = frame?;
+    ///     println!("{:?}: {} dropped", frame.timestamp, frame.dropped);
+    /// }
+    /// # Ok::<(), image2::Error>(())
+    /// ```
+    pub fn frames(&mut self) -> Frames<'_> {
+        let interval = self.interval();
+        Frames {
+            webcam: self,
+            counter: FrameCounter::new(interval),
+        }
+    }
+
+    /// Negotiated frame interval, or the requested interval before capturing starts
+    pub(crate) fn interval(&self) -> (u32, u32) {
+        self.config
+            .map(|config| config.interval)
+            .unwrap_or(self.settings.interval)
+    }
+}
+
+/// A captured frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub image: crate::ImageBuf<u8, crate::Rgb>,
+    /// Capture time reported by the driver, usually based on the monotonic system clock
+    pub timestamp: Duration,
+    /// Number of the frame since capturing started, including dropped frames
+    pub sequence: u64,
+    /// Number of frames dropped since the previous frame
+    pub dropped: u64,
+}
+
+/// Numbers frames and detects dropped frames, which show up as gaps between timestamps that are
+/// longer than the frame interval
+#[derive(Debug, Clone)]
+pub(crate) struct FrameCounter {
+    /// Frame interval in microseconds
+    interval: f64,
+    last: Option<u64>,
+    sequence: u64,
+    dropped: u64,
+}
+
+impl FrameCounter {
+    pub(crate) fn new(interval: (u32, u32)) -> FrameCounter {
+        FrameCounter {
+            interval: f64::from(interval.0) * 1e6 / f64::from(interval.1.max(1)),
+            last: None,
+            sequence: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Returns the sequence number and number of dropped frames for a frame captured at
+    /// `timestamp`, along with `skipped` frames that were captured but not delivered
+    fn frame(&mut self, timestamp: u64, skipped: u64) -> (u64, u64) {
+        let mut dropped = skipped;
+        if let Some(last) = self.last {
+            let frames = (timestamp.saturating_sub(last) as f64 / self.interval).round() as u64;
+            dropped += frames.saturating_sub(1 + skipped);
+        }
+        self.last = Some(timestamp);
+        self.sequence += dropped;
+        self.dropped += dropped;
+
+        let sequence = self.sequence;
+        self.sequence += 1;
+        (sequence, dropped)
+    }
+
+    pub(crate) fn next<F: FnOnce() -> Result<(crate::ImageBuf<u8, crate::Rgb>, u64), Error>>(
+        &mut self,
+        skipped: u64,
+        capture: F,
+    ) -> Result<Frame, Error> {
+        let (image, timestamp) = capture()?;
+        let (sequence, dropped) = self.frame(timestamp, skipped);
+        Ok(Frame {
+            image,
+            timestamp: Duration::from_micros(timestamp),
+            sequence,
+            dropped,
+        })
+    }
+}
+
+/// Iterator over captured frames, returned by `Webcam::frames`
+pub struct Frames<'a> {
+    webcam: &'a mut Webcam,
+    counter: FrameCounter,
+}
+
+impl<'a> Frames<'a> {
+    /// Total number of frames dropped since the iterator was created
+    pub fn dropped(&self) -> u64 {
+        self.counter.dropped
+    }
+
+    /// Return the image of a processed frame to the webcam's pool, see `Webcam::recycle`
+    pub fn recycle(&mut self, frame: Frame) {
+        self.webcam.recycle(frame.image)
+    }
+}
+
+impl<'a> Iterator for Frames<'a> {
+    type Item = Result<Frame, Error>;
+
+    fn next(&mut self) -> Option<Result<Frame, Error>> {
+        let webcam = &mut self.webcam;
+        Some(self.counter.next(0, || webcam.capture_timestamp()))
+    }
+}
+
+#[cfg(feature = "v4l-stream")]
+pub use self::stream::FrameStream;
+
+#[cfg(feature = "v4l-stream")]
+mod stream {
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc::{self, Receiver, TryRecvError, TrySendError};
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll, Waker};
+
+    use futures_core::Stream;
+
+    use super::{CaptureConfig, Error, Frame, FrameCounter, WebcamBuilder};
+
+    #[derive(Default)]
+    struct Shared {
+        waker: Mutex<Option<Waker>>,
+        stop: AtomicBool,
+    }
+
+    impl Shared {
+        fn wake(&self) {
+            if let Some(waker) = self.waker.lock().unwrap().take() {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Asynchronous stream of captured frames, returned by `WebcamBuilder::stream`
+    ///
+    /// Frames are captured on a separate thread. When the stream isn't polled fast enough and
+    /// the queue is full, new frames are dropped and counted in `Frame::dropped`.
+    pub struct FrameStream {
+        receiver: Receiver<Result<Frame, Error>>,
+        shared: Arc<Shared>,
+        config: CaptureConfig,
+    }
+
+    impl FrameStream {
+        /// Settings negotiated with the device
+        pub fn config(&self) -> CaptureConfig {
+            self.config
+        }
+    }
+
+    impl Stream for FrameStream {
+        type Item = Result<Frame, Error>;
+
+        fn poll_next(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<Option<Result<Frame, Error>>> {
+            match self.receiver.try_recv() {
+                Ok(frame) => return Poll::Ready(Some(frame)),
+                Err(TryRecvError::Disconnected) => return Poll::Ready(None),
+                Err(TryRecvError::Empty) => (),
+            }
+
+            *self.shared.waker.lock().unwrap() = Some(cx.waker().clone());
+
+            // A frame may have arrived before the waker was stored
+            match self.receiver.try_recv() {
+                Ok(frame) => Poll::Ready(Some(frame)),
+                Err(TryRecvError::Disconnected) => Poll::Ready(None),
+                Err(TryRecvError::Empty) => Poll::Pending,
+            }
+        }
+    }
+
+    impl Drop for FrameStream {
+        fn drop(&mut self) {
+            self.shared.stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    impl WebcamBuilder {
+        /// Open the device and capture frames on a new thread, returning them as an
+        /// asynchronous `Stream`. Capturing stops when the stream is dropped.
+        pub fn stream(self) -> Result<FrameStream, Error> {
+            let queue = self.buffers as usize;
+            let (started, start_result) = mpsc::channel();
+            let (sender, receiver) = mpsc::sync_channel(queue);
+            let shared = Arc::new(Shared::default());
+            let thread_shared = shared.clone();
+
+            std::thread::spawn(move || {
+                let shared = thread_shared;
+                let mut webcam = match self.start() {
+                    Ok(webcam) => webcam,
+                    Err(err) => {
+                        let _ = started.send(Err(err));
+                        return;
+                    }
+                };
+
+                let config = webcam.config.expect("Webcam was started");
+                if started.send(Ok(config)).is_err() {
+                    return;
+                }
+
+                let mut counter = FrameCounter::new(config.interval);
+                let mut skipped = 0;
+                while !shared.stop.load(Ordering::Relaxed) {
+                    let frame = counter.next(skipped, || webcam.capture_timestamp());
+                    skipped = 0;
+                    let failed = frame.is_err();
+                    match sender.try_send(frame) {
+                        Ok(()) => shared.wake(),
+                        Err(TrySendError::Full(_)) => skipped = 1,
+                        Err(TrySendError::Disconnected(_)) => break,
+                    }
+
+                    if failed {
+                        break;
+                    }
+                }
+                shared.wake();
+            });
+
+            let config = start_result
+                .recv()
+                .map_err(|_| Error::from(std::io::Error::from(std::io::ErrorKind::BrokenPipe)))??;
+            Ok(FrameStream {
+                receiver,
+                shared,
+                config,
+            })
+        }
+    }
+}
+
+/// Open a device, errors include the path of the device
+fn open(device: &str) -> Result<rscam::Camera, Error> {
+    rscam::Camera::new(device).map_err(|err| Error::from(err).with_path(device))
+}
+
+/// The supported frame interval closest to `requested`
+fn closest_interval(requested: (u32, u32), supported: &rscam::IntervalInfo) -> Option<(u32, u32)> {
+    let seconds = |(num, den): (u32, u32)| f64::from(num) / f64::from(den.max(1));
+    let target = seconds(requested);
+    match supported {
+        rscam::IntervalInfo::Discretes(intervals) => intervals.iter().copied().min_by(|a, b| {
+            (seconds(*a) - target)
+                .abs()
+                .partial_cmp(&(seconds(*b) - target).abs())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        }),
+        rscam::IntervalInfo::Stepwise { min, max, .. } => {
+            if target < seconds(*min) {
+                Some(*min)
+            } else if target > seconds(*max) {
+                Some(*max)
+            } else {
+                Some(requested)
+            }
+        }
+    }
+}
+
+/// Number of a `videoN` device node
+fn device_index(name: &str) -> Option<u32> {
+    name.strip_prefix("video")?.parse().ok()
+}
+
+/// The smallest and largest resolution of a stepwise range, along with the common resolutions
+/// that fall within it
+fn stepwise_resolutions(min: (u32, u32), max: (u32, u32), step: (u32, u32)) -> Vec<(u32, u32)> {
+    let fits = |value: u32, min: u32, max: u32, step: u32| {
+        value >= min && value <= max && (value - min).is_multiple_of(step.max(1))
+    };
+
+    let mut resolutions = vec![min];
+    resolutions.extend(COMMON_RESOLUTIONS.iter().copied().filter(|&(w, h)| {
+        (w, h) != min
+            && (w, h) != max
+            && fits(w, min.0, max.0, step.0)
+            && fits(h, min.1, max.1, step.1)
+    }));
+    if max != min {
+        resolutions.push(max);
+    }
+    resolutions
+}
+
+/// Convert an uncompressed frame to packed RGB
+fn convert(
+    format: PixelFormat,
+    data: &[u8],
+    width: usize,
+    height: usize,
+    rgb: &mut [u8],
+) -> Result<(), Error> {
+    let expected = match format {
+        PixelFormat::Rgb3 => width * height * 3,
+        PixelFormat::Yuyv => width * height * 2,
+        PixelFormat::Nv12 => width * height * 3 / 2,
+        PixelFormat::Mjpg => 0,
+    };
+
+    if data.len() < expected {
+        return Err(Error::Message(format!(
+            "Frame is too small: expected {} bytes, got {}",
+            expected,
+            data.len()
+        )));
+    }
+
+    match format {
+        PixelFormat::Rgb3 => rgb.copy_from_slice(&data[..expected]),
+        PixelFormat::Yuyv => simd::yuyv_to_rgb(data, rgb),
+        PixelFormat::Nv12 => nv12_to_rgb(data, width, height, rgb),
+        PixelFormat::Mjpg => unreachable!(),
+    }
+    Ok(())
+}
+
+fn nv12_to_rgb(data: &[u8], width: usize, height: usize, rgb: &mut [u8]) {
+    let (luma, chroma) = data.split_at(width * height);
+    for y in 0..height {
+        let uv = &chroma[(y / 2) * width..];
+        for x in 0..width {
+            let i = y * width + x;
+            let j = x & !1;
+            simd::scalar::yuv_to_rgb(luma[i], uv[j], uv[j + 1], &mut rgb[i * 3..i * 3 + 3]);
+        }
+    }
+}
+
+/// Standard huffman tables from the JPEG specification (K.3), most webcams leave them out of
+/// MJPEG frames
+const DHT: &[u8] = &[
+    0xff, 0xc4, 0x01, 0xa2, 0x00, 0x00, 0x01, 0x05, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a,
+    0x0b, 0x10, 0x00, 0x02, 0x01, 0x03, 0x03, 0x02, 0x04, 0x03, 0x05, 0x05, 0x04, 0x04, 0x00, 0x00,
+    0x01, 0x7d, 0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12, 0x21, 0x31, 0x41, 0x06, 0x13, 0x51,
+    0x61, 0x07, 0x22, 0x71, 0x14, 0x32, 0x81, 0x91, 0xa1, 0x08, 0x23, 0x42, 0xb1, 0xc1, 0x15, 0x52,
+    0xd1, 0xf0, 0x24, 0x33, 0x62, 0x72, 0x82, 0x09, 0x0a, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x25, 0x26,
+    0x27, 0x28, 0x29, 0x2a, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44, 0x45, 0x46, 0x47,
+    0x48, 0x49, 0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x63, 0x64, 0x65, 0x66, 0x67,
+    0x68, 0x69, 0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x83, 0x84, 0x85, 0x86, 0x87,
+    0x88, 0x89, 0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5,
+    0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3,
+    0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda,
+    0xe1, 0xe2, 0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6,
+    0xf7, 0xf8, 0xf9, 0xfa, 0x01, 0x00, 0x03, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a,
+    0x0b, 0x11, 0x00, 0x02, 0x01, 0x02, 0x04, 0x04, 0x03, 0x04, 0x07, 0x05, 0x04, 0x04, 0x00, 0x01,
+    0x02, 0x77, 0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21, 0x31, 0x06, 0x12, 0x41, 0x51, 0x07,
+    0x61, 0x71, 0x13, 0x22, 0x32, 0x81, 0x08, 0x14, 0x42, 0x91, 0xa1, 0xb1, 0xc1, 0x09, 0x23, 0x33,
+    0x52, 0xf0, 0x15, 0x62, 0x72, 0xd1, 0x0a, 0x16, 0x24, 0x34, 0xe1, 0x25, 0xf1, 0x17, 0x18, 0x19,
+    0x1a, 0x26, 0x27, 0x28, 0x29, 0x2a, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44, 0x45, 0x46,
+    0x47, 0x48, 0x49, 0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x63, 0x64, 0x65, 0x66,
+    0x67, 0x68, 0x69, 0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x82, 0x83, 0x84, 0x85,
+    0x86, 0x87, 0x88, 0x89, 0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0xa2, 0xa3,
+    0xa4, 0xa5, 0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba,
+    0xc2, 0xc3, 0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8,
+    0xd9, 0xda, 0xe2, 0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6,
+    0xf7, 0xf8, 0xf9, 0xfa,
+];
+
+/// Insert the standard huffman tables before the start of scan if the frame doesn't define any
+fn with_huffman_tables(data: &[u8]) -> std::borrow::Cow<'_, [u8]> {
+    let mut i = 2;
+    while i + 4 <= data.len() && data[i] == 0xff {
+        match data[i + 1] {
+            0xc4 => return data.into(),
+            0xda => {
+                let mut out = Vec::with_capacity(data.len() + DHT.len());
+                out.extend_from_slice(&data[..i]);
+                out.extend_from_slice(DHT);
+                out.extend_from_slice(&data[i..]);
+                return out.into();
+            }
+            _ => i += 2 + (usize::from(data[i + 2]) << 8 | usize::from(data[i + 3])),
+        }
+    }
+    data.into()
+}
+
+fn decode_mjpeg(data: &[u8], image: &mut crate::ImageBuf<u8, crate::Rgb>) -> Result<(), Error> {
+    let data = with_huffman_tables(data);
+    let mut width = 0;
+    let mut height = 0;
+    let mut channels = 0;
+
+    let ptr = unsafe {
+        super::stbi_load_from_memory(
+            data.as_ptr(),
+            data.len() as i32,
+            &mut width,
+            &mut height,
+            &mut channels,
+            3,
+        )
+    };
+
+    if ptr.is_null() {
+        return Err(Error::InvalidImageData);
+    }
+
+    let decoded: crate::ImagePtr<u8, crate::Rgb> =
+        crate::ImagePtr::new(width as usize, height as usize, ptr, crate::Free::Default);
+    image.reshape(decoded.width(), decoded.height());
+    image.data_mut().copy_from_slice(decoded.data());
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_v4l_convert() {
+        let mut rgb = [0; 6];
+        simd::yuyv_to_rgb(&[235, 128, 16, 128], &mut rgb);
+        assert_eq!(rgb, [255, 255, 255, 0, 0, 0]);
+
+        // 2x2 red image
+        let mut rgb = [0; 12];
+        nv12_to_rgb(&[81, 81, 81, 81, 90, 240], 2, 2, &mut rgb);
+        for px in rgb.chunks(3) {
+            assert_eq!(px, [255, 0, 0]);
+        }
+
+        assert!(convert(PixelFormat::Yuyv, &[0; 7], 2, 2, &mut rgb).is_err());
+
+        let mut image: crate::ImageBuf<u8, crate::Rgb> = crate::ImageBuf::new(4, 4);
+        let ptr = image.data().as_ptr();
+        image.reshape(2, 2);
+        assert_eq!((image.width(), image.height()), (2, 2));
+        assert_eq!(image.data().as_ptr(), ptr);
+        assert_eq!(PixelFormat::from_fourcc(b"NV12"), Some(PixelFormat::Nv12));
+        assert_eq!(PixelFormat::from_fourcc(b"H264"), None);
+    }
+
+    #[test]
+    fn test_v4l_modes() {
+        assert_eq!(device_index("video10"), Some(10));
+        assert_eq!(device_index("video"), None);
+        assert_eq!(device_index("vhci"), None);
+
+        assert_eq!(
+            stepwise_resolutions((320, 240), (1280, 720), (16, 8)),
+            vec![(320, 240), (640, 360), (640, 480), (800, 600), (1280, 720)]
+        );
+        assert_eq!(
+            stepwise_resolutions((640, 480), (640, 480), (1, 1)),
+            vec![(640, 480)]
+        );
+
+        let mode = Mode {
+            fourcc: *b"YUYV",
+            description: String::from("YUYV 4:2:2"),
+            width: 640,
+            height: 480,
+            intervals: vec![(1, 15), (1, 30)],
+        };
+        assert_eq!(mode.pixel_format(), Some(PixelFormat::Yuyv));
+        assert_eq!(mode.max_fps(), 30.0);
+
+        let discretes = rscam::IntervalInfo::Discretes(vec![(1, 30), (1, 15), (1, 5)]);
+        assert_eq!(closest_interval((1, 60), &discretes), Some((1, 30)));
+        assert_eq!(closest_interval((1, 10), &discretes), Some((1, 15)));
+        let stepwise = rscam::IntervalInfo::Stepwise {
+            min: (1, 60),
+            max: (1, 1),
+            step: (1, 60),
+        };
+        assert_eq!(closest_interval((1, 120), &stepwise), Some((1, 60)));
+        assert_eq!(closest_interval((1, 25), &stepwise), Some((1, 25)));
+
+        let mut counter = FrameCounter::new((1, 25));
+        assert_eq!(counter.frame(1_000_000, 0), (0, 0));
+        assert_eq!(counter.frame(1_040_000, 0), (1, 0));
+        assert_eq!(counter.frame(1_160_000, 0), (4, 2));
+        assert_eq!(counter.frame(1_240_000, 1), (6, 1));
+        assert_eq!(counter.dropped, 3);
+
+        let builder = Webcam::builder("/dev/video2")
+            .resolution(1280, 720)
+            .fps(60)
+            .buffers(0);
+        assert_eq!(builder.interval, (1, 60));
+        assert_eq!(builder.buffers, 1);
+        assert_eq!(builder.format, None);
+    }
+
+    #[test]
+    fn test_v4l_mjpeg() {
+        let image: crate::ImageBuf<u8, crate::Rgb> = crate::io::read("test/test.jpg").unwrap();
+        crate::io::write_jpg_u8("test/test-v4l.jpg", &image, 95).unwrap();
+        let jpeg = std::fs::read("test/test-v4l.jpg").unwrap();
+        let mut expected = crate::ImageBuf::new(0, 0);
+        decode_mjpeg(&jpeg, &mut expected).unwrap();
+        assert_eq!(
+            (expected.width(), expected.height()),
+            (image.width(), image.height())
+        );
+
+        // Remove the huffman tables, like most webcams do
+        let mut stripped = jpeg[..2].to_vec();
+        let mut i = 2;
+        while jpeg[i + 1] != 0xda {
+            let len = 2 + (usize::from(jpeg[i + 2]) << 8 | usize::from(jpeg[i + 3]));
+            if jpeg[i + 1] != 0xc4 {
+                stripped.extend_from_slice(&jpeg[i..i + len]);
+            }
+            i += len;
+        }
+        stripped.extend_from_slice(&jpeg[i..]);
+        assert!(stripped.len() < jpeg.len());
+
+        let mut decoded = crate::ImageBuf::new(1, 1);
+        decode_mjpeg(&stripped, &mut decoded).unwrap();
+        assert_eq!(decoded, expected);
+        assert!(decode_mjpeg(&[0xff, 0xd8, 0xff, 0xd9], &mut decoded).is_err());
+    }
+
+    #[test]
+    fn test_v4l_controls() {
+        let control = rscam::Control {
+            id: CameraControl::AutoExposure.id(),
+            name: String::from("Auto Exposure"),
+            data: rscam::CtrlData::Menu {
+                value: 3,
+                default: 3,
+                items: vec![
+                    rscam::CtrlMenuItem {
+                        index: 1,
+                        name: String::from("Manual Mode"),
+                    },
+                    rscam::CtrlMenuItem {
+                        index: 3,
+                        name: String::from("Aperture Priority Mode"),
+                    },
+                ],
+            },
+            flags: 0,
+        };
+        let info = ControlInfo::from(control);
+        assert_eq!(info.value.value(), Some(EXPOSURE_APERTURE_PRIORITY));
+        assert!(!info.read_only && !info.inactive);
+        match info.value {
+            ControlValue::Menu { items, .. } => {
+                assert_eq!(items[0], (1, String::from("Manual Mode")))
+            }
+            _ => panic!("Expected a menu control"),
+        }
+
+        let control = rscam::Control {
+            id: CameraControl::Exposure.id(),
+            name: String::from("Exposure Time, Absolute"),
+            data: rscam::CtrlData::Integer {
+                value: 156,
+                default: 156,
+                minimum: 3,
+                maximum: 2047,
+                step: 1,
+            },
+            flags: rscam::FLAG_INACTIVE,
+        };
+        let info = ControlInfo::from(control);
+        assert_eq!(info.value.value(), Some(156));
+        assert!(info.inactive);
     }
 }
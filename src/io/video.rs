@@ -0,0 +1,639 @@
+//! Decode and encode video files and streams using the `ffmpeg` and `ffprobe` commands
+//!
+//! ```rust,no_run
+//! use image2::io::video::Decoder;
+//!
+//! let mut decoder = Decoder::open("input.mp4")?;
+//! println!("{}x{} at {} fps", decoder.width(), decoder.height(), decoder.fps());
+//! for frame in decoder.by_ref().take(10) {
+//!     let frame = frame?;
+//!     println!("{:?}", frame.timestamp);
+//! }
+//! decoder.seek(std::time::Duration::from_secs(60))?;
+//! # Ok::<(), image2::Error>(())
+//! ```
+//!
+//! Anything `ffmpeg` can open can be decoded, including URLs and capture devices. Frames always
+//! have the size of the encoded video reported by `width` and `height`: rotation metadata, such
+//! as that written by phones, isn't applied and streams that change resolution are scaled back to
+//! the original size.
+//!
+//! Videos are encoded from a sequence of images with the same size:
+//!
+//! ```rust,no_run
+//! use image2::io::video::{Codec, Encoder, EncoderOptions};
+//! use image2::{ImageBuf, Rgb};
+//!
+//! let options = EncoderOptions::new(Codec::H264).fps(30.0).crf(20);
+//! let mut encoder = Encoder::<u8, Rgb>::create("output.mp4", 1280, 720, options)?;
+//! for _ in 0..90 {
+//!     let frame: ImageBuf<u8, Rgb> = ImageBuf::new(1280, 720);
+//!     encoder.write(&frame)?;
+//! }
+//! encoder.finish()?;
+//! # Ok::<(), image2::Error>(())
+//! ```
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::marker::PhantomData;
+use std::path::Path;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::color::{Color, Rgb};
+use crate::error::{command_error, Error};
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::ty::Type;
+
+/// Number of lines of `ffmpeg` output kept for error messages
+const ERROR_LINES: usize = 8;
+
+/// A decoded video frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub image: ImageBuf<u8, Rgb>,
+    /// Presentation time of the frame from the start of the video
+    pub timestamp: Duration,
+}
+
+/// Properties of the first video stream reported by `ffprobe`
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Probe {
+    width: usize,
+    height: usize,
+    fps: f64,
+    duration: Option<Duration>,
+}
+
+/// Parse the output of `ffprobe -of default=noprint_wrappers=1`
+fn parse_probe(output: &str) -> Result<Probe, Error> {
+    let rate = |value: &str| match value.split_once('/') {
+        Some((num, den)) => match (num.parse::<f64>(), den.parse::<f64>()) {
+            (Ok(num), Ok(den)) if den > 0.0 => num / den,
+            _ => 0.0,
+        },
+        None => value.parse().unwrap_or(0.0),
+    };
+
+    let mut probe = Probe::default();
+    let mut real_fps = 0.0;
+    for line in output.lines() {
+        let (key, value) = match line.split_once('=') {
+            Some(kv) => kv,
+            None => continue,
+        };
+        let value = value.trim();
+        match key.trim() {
+            "width" => probe.width = value.parse().unwrap_or(0),
+            "height" => probe.height = value.parse().unwrap_or(0),
+            "avg_frame_rate" => probe.fps = rate(value),
+            "r_frame_rate" => real_fps = rate(value),
+            "duration" => {
+                probe.duration = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|d| *d >= 0.0)
+                    .map(Duration::from_secs_f64)
+            }
+            _ => (),
+        }
+    }
+
+    if probe.width == 0 || probe.height == 0 {
+        return Err(Error::Message(String::from("No video stream found")));
+    }
+
+    // The average frame rate is unknown for some streams
+    if probe.fps == 0.0 {
+        probe.fps = real_fps;
+    }
+    Ok(probe)
+}
+
+/// Get the presentation time from a line logged by the `showinfo` filter
+fn parse_showinfo(line: &str) -> Option<Duration> {
+    if !line.contains("showinfo") {
+        return None;
+    }
+    let value = line.split("pts_time:").nth(1)?.split_whitespace().next()?;
+    value
+        .parse::<f64>()
+        .ok()
+        .map(|t| Duration::from_secs_f64(t.max(0.0)))
+}
+
+/// Reads `ffmpeg`'s log output, sending frame timestamps and keeping the last lines for error
+/// messages
+fn read_log<R: Read>(log: R, timestamps: mpsc::Sender<Duration>, errors: Arc<Mutex<Vec<String>>>) {
+    for line in BufReader::new(log).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+
+        match parse_showinfo(&line) {
+            Some(timestamp) => {
+                let _ = timestamps.send(timestamp);
+            }
+            None => {
+                let mut errors = errors.lock().unwrap();
+                if errors.len() == ERROR_LINES {
+                    errors.remove(0);
+                }
+                errors.push(line);
+            }
+        }
+    }
+}
+
+/// Decodes frames from a video file or URL by running `ffmpeg`
+pub struct Decoder {
+    input: String,
+    args: Vec<String>,
+    probe: Probe,
+    child: Child,
+    stdout: BufReader<ChildStdout>,
+    timestamps: Receiver<Duration>,
+    errors: Arc<Mutex<Vec<String>>>,
+    start: Duration,
+    frames: u64,
+}
+
+impl Decoder {
+    /// Open a video file or URL
+    pub fn open<S: AsRef<str>>(input: S) -> Result<Decoder, Error> {
+        Self::open_with(input, &[])
+    }
+
+    /// Open a video file or URL, passing input options such as `-rtsp_transport tcp` to
+    /// `ffprobe` and `ffmpeg`
+    pub fn open_with<S: AsRef<str>>(input: S, args: &[&str]) -> Result<Decoder, Error> {
+        let input = input.as_ref().to_string();
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let output = Command::new("ffprobe")
+            .args(["-v", "error", "-select_streams", "v:0"])
+            .args([
+                "-show_entries",
+                "stream=width,height,avg_frame_rate,r_frame_rate",
+            ])
+            .args(["-show_entries", "format=duration"])
+            .args(["-of", "default=noprint_wrappers=1"])
+            .args(&args)
+            .arg(&input)
+            .output()
+            .map_err(|err| {
+                command_error(
+                    "ffprobe",
+                    String::from("Unable to start command"),
+                    Some(err),
+                )
+            })?;
+
+        if !output.status.success() {
+            return Err(command_error(
+                "ffprobe",
+                format!(
+                    "Unable to open {}: {}",
+                    input,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+                None,
+            ));
+        }
+
+        let probe = parse_probe(&String::from_utf8_lossy(&output.stdout))?;
+        Self::spawn(input, args, probe, Duration::ZERO)
+    }
+
+    fn spawn(
+        input: String,
+        args: Vec<String>,
+        probe: Probe,
+        start: Duration,
+    ) -> Result<Decoder, Error> {
+        let mut cmd = Command::new("ffmpeg");
+        cmd.args(["-hide_banner", "-nostats", "-nostdin", "-loglevel", "info"]);
+        if start > Duration::ZERO {
+            cmd.args(["-ss", &format!("{:.6}", start.as_secs_f64())]);
+        }
+        // The size of each frame read from stdout is fixed, so ffmpeg must not rotate or resize
+        // the video
+        let filters = format!("scale={}:{},showinfo", probe.width, probe.height);
+        cmd.arg("-noautorotate")
+            .args(&args)
+            .args(["-i", &input])
+            .args(["-map", "0:v:0", "-vf", &filters, "-fps_mode", "passthrough"])
+            .args(["-f", "rawvideo", "-pix_fmt", "rgb24", "-"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        let mut child = cmd.spawn().map_err(|err| {
+            command_error("ffmpeg", String::from("Unable to start command"), Some(err))
+        })?;
+
+        let (sender, timestamps) = mpsc::channel();
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let log = child.stderr.take().unwrap();
+        let log_errors = errors.clone();
+        std::thread::spawn(move || read_log(log, sender, log_errors));
+
+        Ok(Decoder {
+            input,
+            args,
+            probe,
+            stdout: BufReader::new(child.stdout.take().unwrap()),
+            child,
+            timestamps,
+            errors,
+            start,
+            frames: 0,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.probe.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.probe.height
+    }
+
+    /// Average frame rate reported by the container, 0 if unknown
+    pub fn fps(&self) -> f64 {
+        self.probe.fps
+    }
+
+    /// Duration of the video, `None` for live streams
+    pub fn duration(&self) -> Option<Duration> {
+        self.probe.duration
+    }
+
+    /// Seek to a position from the start of the video, the next frame is the first frame at or
+    /// after `position`
+    pub fn seek(&mut self, position: Duration) -> Result<(), Error> {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        *self = Self::spawn(
+            self.input.clone(),
+            self.args.clone(),
+            self.probe.clone(),
+            position,
+        )?;
+        Ok(())
+    }
+
+    /// Decode the next frame into an existing image, returning its timestamp or `None` at the
+    /// end of the video
+    pub fn read_into(&mut self, image: &mut ImageBuf<u8, Rgb>) -> Result<Option<Duration>, Error> {
+        let (width, height) = (self.probe.width, self.probe.height);
+        image.reshape(width, height);
+
+        let data = image.data_mut();
+        let mut filled = 0;
+        while filled < data.len() {
+            match self.stdout.read(&mut data[filled..])? {
+                0 => break,
+                n => filled += n,
+            }
+        }
+
+        if filled < data.len() {
+            let status = self.child.wait()?;
+            if filled == 0 && status.success() {
+                return Ok(None);
+            }
+            return Err(command_error(
+                "ffmpeg",
+                format!(
+                    "Unable to decode {}: {}",
+                    self.input,
+                    self.errors.lock().unwrap().join("\n")
+                ),
+                None,
+            ));
+        }
+
+        // Fall back to the frame rate if the timestamp wasn't logged
+        let timestamp = match self.timestamps.recv() {
+            Ok(timestamp) => timestamp,
+            Err(_) => Duration::from_secs_f64(self.frames as f64 / self.probe.fps.max(1.0)),
+        };
+        self.frames += 1;
+        Ok(Some(self.start + timestamp))
+    }
+}
+
+impl Iterator for Decoder {
+    type Item = Result<Frame, Error>;
+
+    fn next(&mut self) -> Option<Result<Frame, Error>> {
+        let mut image = ImageBuf::new(self.probe.width, self.probe.height);
+        match self.read_into(&mut image) {
+            Ok(Some(timestamp)) => Some(Ok(Frame { image, timestamp })),
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+impl Drop for Decoder {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Video codecs supported by `Encoder`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    /// H.264 using libx264, 8-bit 4:2:0
+    H264,
+    /// VP9 using libvpx, 8-bit 4:2:0
+    Vp9,
+    /// Apple ProRes 422 HQ, 10-bit 4:2:2
+    ProRes,
+}
+
+/// Encoding settings for `Encoder`
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncoderOptions {
+    codec: Codec,
+    fps: f64,
+    bitrate: Option<u64>,
+    crf: Option<u32>,
+}
+
+impl EncoderOptions {
+    /// Encode at 30 frames per second using the codec's default quality
+    pub fn new(codec: Codec) -> EncoderOptions {
+        EncoderOptions {
+            codec,
+            fps: 30.0,
+            bitrate: None,
+            crf: None,
+        }
+    }
+
+    pub fn fps(mut self, fps: f64) -> EncoderOptions {
+        self.fps = fps;
+        self
+    }
+
+    /// Target bitrate in bits per second
+    pub fn bitrate(mut self, bitrate: u64) -> EncoderOptions {
+        self.bitrate = Some(bitrate);
+        self
+    }
+
+    /// Constant rate factor, lower values give higher quality. For H.264 and VP9 this takes
+    /// precedence over the bitrate, ProRes ignores it.
+    pub fn crf(mut self, crf: u32) -> EncoderOptions {
+        self.crf = Some(crf);
+        self
+    }
+
+    /// `ffmpeg` output arguments
+    fn args(&self) -> Vec<String> {
+        let mut args: Vec<String> = match self.codec {
+            Codec::H264 => vec!["-c:v", "libx264", "-pix_fmt", "yuv420p"],
+            Codec::Vp9 => vec!["-c:v", "libvpx-vp9", "-pix_fmt", "yuv420p"],
+            Codec::ProRes => vec!["-c:v", "prores_ks", "-profile:v", "3"],
+        }
+        .into_iter()
+        .map(String::from)
+        .collect();
+
+        match self.codec {
+            // 4:2:0 subsampling needs even dimensions
+            Codec::H264 | Codec::Vp9 => {
+                args.extend(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2"].map(String::from));
+            }
+            Codec::ProRes => args.extend(["-pix_fmt", "yuv422p10le"].map(String::from)),
+        }
+
+        match (self.codec, self.crf, self.bitrate) {
+            (Codec::ProRes, _, Some(bitrate)) | (_, None, Some(bitrate)) => {
+                args.extend([String::from("-b:v"), bitrate.to_string()])
+            }
+            (Codec::H264, Some(crf), _) => args.extend([String::from("-crf"), crf.to_string()]),
+            // VP9 only uses constant quality mode when the bitrate is 0
+            (Codec::Vp9, Some(crf), _) => args.extend([
+                String::from("-crf"),
+                crf.to_string(),
+                String::from("-b:v"),
+                String::from("0"),
+            ]),
+            _ => (),
+        }
+        args
+    }
+}
+
+/// The `ffmpeg` raw video pixel format matching the memory layout of `ImageBuf<T, C>`
+fn pixel_format<T: Type, C: Color>() -> Result<String, Error> {
+    let endian = if cfg!(target_endian = "little") {
+        "le"
+    } else {
+        "be"
+    };
+
+    let format = match (std::mem::size_of::<T>(), T::is_float()) {
+        (1, false) => match C::name() {
+            "gray" => "gray",
+            "rgb" => "rgb24",
+            "rgba" => "rgba",
+            "bgr" => "bgr24",
+            "bgra" => "bgra",
+            _ => return Err(Error::InvalidColor),
+        },
+        (2, false) if !T::is_signed() => match C::name() {
+            "gray" => "gray16",
+            "rgb" => "rgb48",
+            "rgba" => "rgba64",
+            "bgr" => "bgr48",
+            "bgra" => "bgra64",
+            _ => return Err(Error::InvalidColor),
+        },
+        (4, true) => match C::name() {
+            "gray" => "grayf32",
+            _ => return Err(Error::InvalidColor),
+        },
+        _ => return Err(Error::InvalidType),
+    };
+
+    if std::mem::size_of::<T>() == 1 {
+        Ok(format.to_string())
+    } else {
+        Ok(format!("{}{}", format, endian))
+    }
+}
+
+/// Encodes a sequence of images as a video by running `ffmpeg`
+///
+/// 8 and 16-bit gray, RGB, RGBA, BGR and BGRA images and 32-bit float gray images are
+/// supported.
+pub struct Encoder<T: Type, C: Color> {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    log: Option<std::thread::JoinHandle<String>>,
+    width: usize,
+    height: usize,
+    _type: PhantomData<(T, C)>,
+}
+
+impl<T: Type, C: Color> Encoder<T, C> {
+    /// Create a video file, the container format is chosen based on the extension
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        width: usize,
+        height: usize,
+        options: EncoderOptions,
+    ) -> Result<Encoder<T, C>, Error> {
+        let size = format!("{}x{}", width, height);
+        let mut child = Command::new("ffmpeg")
+            .args(["-hide_banner", "-nostats", "-loglevel", "error", "-y"])
+            .args(["-f", "rawvideo", "-pix_fmt", &pixel_format::<T, C>()?])
+            .args(["-s", &size, "-framerate", &options.fps.to_string()])
+            .args(["-i", "-"])
+            .args(options.args())
+            .arg(path.as_ref())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| {
+                command_error("ffmpeg", String::from("Unable to start command"), Some(err))
+            })?;
+
+        let mut stderr = child.stderr.take().unwrap();
+        let log = std::thread::spawn(move || {
+            let mut log = String::new();
+            let _ = stderr.read_to_string(&mut log);
+            log
+        });
+
+        Ok(Encoder {
+            stdin: child.stdin.take(),
+            child,
+            log: Some(log),
+            width,
+            height,
+            _type: PhantomData,
+        })
+    }
+
+    /// Add a frame to the video
+    pub fn write<I: Image<T, C>>(&mut self, image: &I) -> Result<(), Error> {
+        if image.width() != self.width || image.height() != self.height {
+            return Err(Error::Message(format!(
+                "Expected a {}x{} image, got {}x{}",
+                self.width,
+                self.height,
+                image.width(),
+                image.height()
+            )));
+        }
+
+        let stdin = self.stdin.as_mut().unwrap();
+        if stdin.write_all(image.buffer()).is_err() {
+            // ffmpeg exited, return its error message
+            return self.close();
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), Error> {
+        drop(self.stdin.take());
+        let status = self.child.wait()?;
+        let log = match self.log.take() {
+            Some(log) => log.join().unwrap_or_default(),
+            None => String::new(),
+        };
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(command_error(
+                "ffmpeg",
+                format!("Unable to encode video: {}", log.trim()),
+                None,
+            ))
+        }
+    }
+
+    /// Finish encoding and wait for `ffmpeg` to write the file
+    pub fn finish(mut self) -> Result<(), Error> {
+        self.close()
+    }
+}
+
+impl<T: Type, C: Color> Drop for Encoder<T, C> {
+    fn drop(&mut self) {
+        if self.log.is_some() {
+            let _ = self.close();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_video_decoder() {
+        let probe = parse_probe(
+            "width=1920\nheight=1080\nr_frame_rate=30000/1001\navg_frame_rate=30000/1001\n\
+             duration=12.500000\n",
+        )
+        .unwrap();
+        assert_eq!((probe.width, probe.height), (1920, 1080));
+        assert!((probe.fps - 29.97).abs() < 0.01);
+        assert_eq!(probe.duration, Some(Duration::from_millis(12500)));
+
+        let probe = parse_probe(
+            "width=640\nheight=480\navg_frame_rate=0/0\nr_frame_rate=25/1\nduration=N/A\n",
+        )
+        .unwrap();
+        assert_eq!(probe.fps, 25.0);
+        assert_eq!(probe.duration, None);
+        assert!(parse_probe("duration=1.0\n").is_err());
+
+        let line =
+            "[Parsed_showinfo_0 @ 0x5581] n:   3 pts:   3072 pts_time:0.2     duration:   1024";
+        assert_eq!(parse_showinfo(line), Some(Duration::from_millis(200)));
+        assert_eq!(parse_showinfo("Stream #0:0: Video: h264"), None);
+    }
+
+    #[test]
+    fn test_video_encoder() {
+        use crate::color::{Bgra, Gray, Rgba, Yuv};
+
+        assert_eq!(pixel_format::<u8, Rgb>().unwrap(), "rgb24");
+        assert_eq!(pixel_format::<u8, Bgra>().unwrap(), "bgra");
+        assert_eq!(pixel_format::<u16, Rgba>().unwrap(), "rgba64le");
+        assert_eq!(pixel_format::<f32, Gray>().unwrap(), "grayf32le");
+        assert!(pixel_format::<f32, Rgb>().is_err());
+        assert!(pixel_format::<u8, Yuv>().is_err());
+        assert!(pixel_format::<i32, Gray>().is_err());
+
+        let args = EncoderOptions::new(Codec::H264)
+            .crf(18)
+            .bitrate(1000)
+            .args();
+        assert!(args.ends_with(&[String::from("-crf"), String::from("18")]));
+
+        let args = EncoderOptions::new(Codec::Vp9).crf(30).args();
+        assert!(args.ends_with(&["-crf", "30", "-b:v", "0"].map(String::from)));
+
+        let args = EncoderOptions::new(Codec::ProRes)
+            .crf(30)
+            .bitrate(50_000_000)
+            .args();
+        assert!(args.contains(&String::from("prores_ks")));
+        assert!(args.ends_with(&[String::from("-b:v"), String::from("50000000")]));
+    }
+}
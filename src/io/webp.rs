@@ -0,0 +1,694 @@
+//! Encode and decode animated WebP files
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use image2::io::webp::{self, Encoder};
+//! use image2::io::Repeat;
+//! use image2::{ImageBuf, Rgba};
+//!
+//! let mut encoder = Encoder::create("animation.webp", 320, 240)?
+//!     .quality(80)
+//!     .repeat(Repeat::Finite(2));
+//! for _ in 0..10 {
+//!     let frame: ImageBuf<u8, Rgba> = ImageBuf::new(320, 240);
+//!     encoder.add_frame(&frame, Duration::from_millis(100))?;
+//! }
+//! encoder.finish()?;
+//!
+//! let data = std::fs::read("animation.webp")?;
+//! assert_eq!(webp::repeat(&data)?, Repeat::Finite(2));
+//! assert_eq!(webp::decode(&data)?.len(), 10);
+//! # Ok::<(), image2::Error>(())
+//! ```
+//!
+//! This module handles the animation container: frame placement, durations, blending, disposal
+//! and the loop count. The image data of each frame is encoded and decoded by ImageMagick or
+//! GraphicsMagick, see `magick::set_default`, which must be built with WebP support. Frames are
+//! lossless unless a quality is set. Still WebP images are decoded as a single frame.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::color::{Color, Rgba};
+use crate::error::{with_path, Error};
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::ty::Type;
+
+use super::gif::{to_rgba8, Rgba8};
+use super::magick;
+use super::png::{blend_over, changed_region};
+use super::{Animation, Repeat};
+
+/// `VP8X` flag set for animations
+const ANIMATION: u8 = 0x02;
+
+/// `VP8X` flag set when the image has transparency
+const ALPHA: u8 = 0x10;
+
+/// `VP8X` flag set when the file contains an ICC profile
+const ICC: u8 = 0x20;
+
+/// Largest frame duration in milliseconds
+const MAX_DURATION: u128 = 0xff_ffff;
+
+/// Type and data of a chunk
+type Chunk<'a> = (&'a [u8], &'a [u8]);
+
+fn read_u24(data: &[u8]) -> usize {
+    usize::from(data[0]) | usize::from(data[1]) << 8 | usize::from(data[2]) << 16
+}
+
+fn push_u24(out: &mut Vec<u8>, n: usize) {
+    out.extend_from_slice(&(n as u32).to_le_bytes()[..3]);
+}
+
+/// Append a chunk, padded to an even size
+fn push_chunk(out: &mut Vec<u8>, kind: &[u8], data: &[u8]) {
+    out.extend_from_slice(kind);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+/// Split a sequence of chunks
+fn chunks(mut data: &[u8]) -> Result<Vec<Chunk<'_>>, Error> {
+    let mut chunks = Vec::new();
+    while !data.is_empty() {
+        if data.len() < 8 {
+            return Err(Error::Message(String::from("Truncated WebP file")));
+        }
+
+        let len = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+        if data.len() < 8 + len {
+            return Err(Error::Message(String::from("Truncated WebP file")));
+        }
+
+        chunks.push((&data[..4], &data[8..8 + len]));
+        data = &data[(8 + len + len % 2).min(data.len())..];
+    }
+    Ok(chunks)
+}
+
+/// Split a WebP file into chunks
+fn parse(data: &[u8]) -> Result<Vec<Chunk<'_>>, Error> {
+    if data.len() < 12 || &data[..4] != b"RIFF" || &data[8..12] != b"WEBP" {
+        return Err(Error::Message(String::from("Invalid WebP header")));
+    }
+
+    let len = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+    chunks(&data[12..(8 + len).min(data.len())])
+}
+
+/// Wrap chunks in a RIFF header
+fn riff(chunks: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(chunks.len() + 12);
+    data.extend_from_slice(b"RIFF");
+    data.extend_from_slice(&(chunks.len() as u32 + 4).to_le_bytes());
+    data.extend_from_slice(b"WEBP");
+    data.extend_from_slice(chunks);
+    data
+}
+
+fn vp8x(flags: u8, width: usize, height: usize) -> Vec<u8> {
+    let mut data = vec![flags, 0, 0, 0];
+    push_u24(&mut data, width - 1);
+    push_u24(&mut data, height - 1);
+    data
+}
+
+/// Size of the image stored in a `VP8` or `VP8L` chunk
+fn bitstream_size(kind: &[u8], data: &[u8]) -> Option<(usize, usize)> {
+    match kind {
+        b"VP8 " if data.len() >= 10 && data[3..6] == [0x9d, 0x01, 0x2a] => Some((
+            usize::from(u16::from_le_bytes([data[6], data[7]]) & 0x3fff),
+            usize::from(u16::from_le_bytes([data[8], data[9]]) & 0x3fff),
+        )),
+        b"VP8L" if data.len() >= 5 && data[0] == 0x2f => {
+            let bits = u32::from_le_bytes([data[1], data[2], data[3], data[4]]) as usize;
+            Some(((bits & 0x3fff) + 1, (bits >> 14 & 0x3fff) + 1))
+        }
+        _ => None,
+    }
+}
+
+/// Flags and canvas size of a file, taken from the `VP8X` chunk or the bitstream of a simple file
+fn features(chunks: &[Chunk<'_>]) -> Result<(u8, usize, usize), Error> {
+    match chunks.first() {
+        Some((b"VP8X", data)) if data.len() >= 10 => {
+            Ok((data[0], read_u24(&data[4..]) + 1, read_u24(&data[7..]) + 1))
+        }
+        Some((kind, data)) => match bitstream_size(kind, data) {
+            // Lossless bitstreams record whether the alpha channel is used
+            Some((width, height)) if *kind == b"VP8L" && data[4] & 0x10 != 0 => {
+                Ok((ALPHA, width, height))
+            }
+            Some((width, height)) => Ok((0, width, height)),
+            None => Err(Error::Message(String::from("Missing WebP image data"))),
+        },
+        None => Err(Error::Message(String::from("Missing WebP image data"))),
+    }
+}
+
+/// Get the ICC profile of a WebP file, returns `None` when the file doesn't embed one
+pub fn profile<Data: AsRef<[u8]>>(data: Data) -> Result<Option<Vec<u8>>, Error> {
+    Ok(parse(data.as_ref())?
+        .into_iter()
+        .find(|(kind, _)| *kind == b"ICCP")
+        .map(|(_, profile)| profile.to_vec()))
+}
+
+/// Replace the ICC profile of a still or animated WebP file, returning the new file. The profile
+/// is removed when `profile` is `None`
+pub fn encode_profile<Data: AsRef<[u8]>>(
+    data: Data,
+    profile: Option<&[u8]>,
+) -> Result<Vec<u8>, Error> {
+    let chunks = parse(data.as_ref())?;
+    let (flags, width, height) = features(&chunks)?;
+    let flags = if profile.is_some() {
+        flags | ICC
+    } else {
+        flags & !ICC
+    };
+
+    // The profile has to follow the `VP8X` chunk, which simple files don't have
+    let mut out = Vec::with_capacity(data.as_ref().len() + profile.map_or(0, <[u8]>::len) + 32);
+    push_chunk(&mut out, b"VP8X", &vp8x(flags, width, height));
+    if let Some(profile) = profile {
+        push_chunk(&mut out, b"ICCP", profile);
+    }
+    for (kind, data) in chunks {
+        if kind != b"VP8X" && kind != b"ICCP" {
+            push_chunk(&mut out, kind, data);
+        }
+    }
+    Ok(riff(&out))
+}
+
+/// Position and timing of a frame, stored at the beginning of an `ANMF` chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FrameInfo {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    duration: Duration,
+    /// Alpha blend the frame with the canvas instead of replacing the region
+    blend: bool,
+    /// Clear the region to transparent after the frame is shown
+    dispose: bool,
+}
+
+impl FrameInfo {
+    fn parse(data: &[u8]) -> Result<FrameInfo, Error> {
+        if data.len() < 16 {
+            return Err(Error::Message(String::from("Invalid ANMF chunk")));
+        }
+
+        Ok(FrameInfo {
+            x: read_u24(data) * 2,
+            y: read_u24(&data[3..]) * 2,
+            width: read_u24(&data[6..]) + 1,
+            height: read_u24(&data[9..]) + 1,
+            duration: Duration::from_millis(read_u24(&data[12..]) as u64),
+            blend: data[15] & 0x02 == 0,
+            dispose: data[15] & 0x01 == 1,
+        })
+    }
+
+    fn to_bytes(self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(16);
+        push_u24(&mut data, self.x / 2);
+        push_u24(&mut data, self.y / 2);
+        push_u24(&mut data, self.width - 1);
+        push_u24(&mut data, self.height - 1);
+        push_u24(
+            &mut data,
+            self.duration.as_millis().min(MAX_DURATION) as usize,
+        );
+        data.push(if self.blend { 0 } else { 0x02 } | self.dispose as u8);
+        data
+    }
+}
+
+/// Writes animated WebP files, see the module documentation for an example
+pub struct Encoder<W: Write> {
+    out: W,
+    width: usize,
+    height: usize,
+    repeat: Repeat,
+    quality: Option<u8>,
+    alpha: bool,
+    /// `ANMF` chunks, which are kept until `finish` because the file starts with its size
+    frames: Vec<u8>,
+    count: usize,
+    previous: Option<Vec<Rgba8>>,
+}
+
+impl Encoder<BufWriter<File>> {
+    /// Create a WebP file
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        width: usize,
+        height: usize,
+    ) -> Result<Encoder<BufWriter<File>>, Error> {
+        Ok(Encoder::new(
+            BufWriter::new(with_path(path, |path| Ok(File::create(path)?))?),
+            width,
+            height,
+        ))
+    }
+}
+
+impl<W: Write> Encoder<W> {
+    /// Write a lossless animation to `out`, repeating forever
+    pub fn new(out: W, width: usize, height: usize) -> Encoder<W> {
+        Encoder {
+            out,
+            width,
+            height,
+            repeat: Repeat::Infinite,
+            quality: None,
+            alpha: false,
+            frames: Vec::new(),
+            count: 0,
+            previous: None,
+        }
+    }
+
+    pub fn repeat(mut self, repeat: Repeat) -> Encoder<W> {
+        self.repeat = repeat;
+        self
+    }
+
+    /// Use lossy compression with a quality from 0 to 100
+    pub fn quality(mut self, quality: u8) -> Encoder<W> {
+        self.quality = Some(quality.min(100));
+        self
+    }
+
+    /// Add a frame, which is shown for `delay` rounded to milliseconds. Gray, RGB, RGBA, BGR and
+    /// BGRA images are supported.
+    pub fn add_frame<T: Type, C: Color, I: Image<T, C>>(
+        &mut self,
+        image: &I,
+        delay: Duration,
+    ) -> Result<(), Error> {
+        if image.width() != self.width || image.height() != self.height {
+            return Err(Error::Message(format!(
+                "Expected a {}x{} image, got {}x{}",
+                self.width,
+                self.height,
+                image.width(),
+                image.height()
+            )));
+        }
+
+        let pixels = to_rgba8(image)?;
+        // Frames can only be placed at even offsets
+        let (x, y, width, height) = match &self.previous {
+            Some(previous) => match changed_region(previous, &pixels, self.width) {
+                Some((x, y, width, height)) => (x & !1, y & !1, width + x % 2, height + y % 2),
+                None => (0, 0, 1, 1),
+            },
+            None => (0, 0, self.width, self.height),
+        };
+
+        let data: Vec<u8> = (y..y + height)
+            .flat_map(|row| &pixels[row * self.width + x..row * self.width + x + width])
+            .flatten()
+            .copied()
+            .collect();
+        self.alpha |= data.chunks(4).any(|px| px[3] < 255);
+
+        let args = match self.quality {
+            Some(quality) => vec![String::from("-quality"), quality.to_string()],
+            None => [
+                "-define",
+                "webp:lossless=true",
+                "-define",
+                "webp:exact=true",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+        };
+        let args: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+        let frame = ImageBuf::<u8, Rgba>::new_from(width, height, data);
+        let webp = magick::default().encode_with("webp", &frame, &args)?;
+
+        let info = FrameInfo {
+            x,
+            y,
+            width,
+            height,
+            duration: delay,
+            blend: false,
+            dispose: false,
+        };
+        push_anmf(&mut self.frames, info, &webp)?;
+        self.count += 1;
+        self.previous = Some(pixels);
+        Ok(())
+    }
+
+    /// Write the animation, returning the underlying writer
+    pub fn finish(mut self) -> Result<W, Error> {
+        if self.count == 0 {
+            return Err(Error::Message(String::from("No frames were added")));
+        }
+
+        let flags = ANIMATION | if self.alpha { ALPHA } else { 0 };
+        let loops = match self.repeat {
+            Repeat::Infinite => 0,
+            Repeat::Finite(n) => n.saturating_add(1),
+        };
+        let mut anim = vec![0; 4];
+        anim.extend_from_slice(&loops.to_le_bytes());
+
+        let mut data = Vec::with_capacity(self.frames.len() + 48);
+        push_chunk(&mut data, b"VP8X", &vp8x(flags, self.width, self.height));
+        push_chunk(&mut data, b"ANIM", &anim);
+        data.extend_from_slice(&self.frames);
+
+        self.out.write_all(&riff(&data))?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Append an `ANMF` chunk containing the image data of a still WebP file
+fn push_anmf(out: &mut Vec<u8>, info: FrameInfo, webp: &[u8]) -> Result<(), Error> {
+    let mut anmf = info.to_bytes();
+    let mut found = false;
+    for (kind, data) in parse(webp)? {
+        match kind {
+            b"ALPH" => push_chunk(&mut anmf, kind, data),
+            b"VP8 " | b"VP8L" => {
+                if bitstream_size(kind, data) != Some((info.width, info.height)) {
+                    return Err(Error::Message(String::from("Invalid WebP frame size")));
+                }
+                push_chunk(&mut anmf, kind, data);
+                found = true;
+            }
+            _ => (),
+        }
+    }
+
+    if !found {
+        return Err(Error::Message(String::from("Missing WebP image data")));
+    }
+    push_chunk(out, b"ANMF", &anmf);
+    Ok(())
+}
+
+/// Encode frames as an animated WebP file in memory, losslessly and repeating forever
+pub fn encode<T: Type, C: Color>(frames: &[(ImageBuf<T, C>, Duration)]) -> Result<Vec<u8>, Error> {
+    let (width, height) = match frames.first() {
+        Some((image, _)) => (image.width(), image.height()),
+        None => return Err(Error::Message(String::from("No frames were added"))),
+    };
+
+    let mut encoder = Encoder::new(Vec::new(), width, height);
+    for (image, delay) in frames {
+        encoder.add_frame(image, *delay)?;
+    }
+    encoder.finish()
+}
+
+/// Write frames to an animated WebP file, losslessly and repeating forever
+pub fn write<P: AsRef<Path>, T: Type, C: Color>(
+    path: P,
+    frames: &[(ImageBuf<T, C>, Duration)],
+) -> Result<(), Error> {
+    let data = encode(frames)?;
+    with_path(path, |path| Ok(std::fs::write(path, data)?))
+}
+
+/// Turn the image data of a frame into a still WebP file, returning its size
+fn still_image(chunks: &[Chunk<'_>]) -> Result<(Vec<u8>, usize, usize), Error> {
+    let alph = chunks.iter().find(|(kind, _)| *kind == b"ALPH");
+    let (kind, data, (width, height)) = match chunks
+        .iter()
+        .find_map(|(kind, data)| bitstream_size(kind, data).map(|size| (*kind, *data, size)))
+    {
+        Some(bitstream) => bitstream,
+        None => return Err(Error::Message(String::from("Missing WebP image data"))),
+    };
+
+    let mut out = Vec::new();
+    // Lossy images store transparency in a separate chunk, which requires the extended format
+    if let (b"VP8 ", Some((_, alph))) = (kind, alph) {
+        push_chunk(&mut out, b"VP8X", &vp8x(ALPHA, width, height));
+        push_chunk(&mut out, b"ALPH", alph);
+    }
+    push_chunk(&mut out, kind, data);
+    Ok((riff(&out), width, height))
+}
+
+fn decode_frame(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>, Error> {
+    let image: ImageBuf<u8, Rgba> = magick::default().decode("webp", data, width, height)?;
+    Ok(image.inner())
+}
+
+/// Decode every frame into `animation` using `decode_frame`, which converts a still WebP file
+/// with the given size to 8-bit RGBA
+fn decode_with<F: FnMut(&[u8], usize, usize) -> Result<Vec<u8>, Error>>(
+    data: &[u8],
+    mut decode_frame: F,
+    animation: &mut Animation<u8, Rgba>,
+) -> Result<(), Error> {
+    let file = parse(data)?;
+    let animated = file
+        .iter()
+        .any(|(kind, data)| *kind == b"VP8X" && !data.is_empty() && data[0] & ANIMATION != 0);
+
+    if !animated {
+        let (still, width, height) = still_image(&file)?;
+        let pixels = decode_frame(&still, width, height)?;
+        super::store_frame(
+            animation,
+            0,
+            width,
+            height,
+            &pixels,
+            Duration::from_millis(0),
+        );
+        animation.truncate(1);
+        return Ok(());
+    }
+
+    let (width, height) = match file.iter().find(|(kind, _)| *kind == b"VP8X") {
+        Some((_, data)) if data.len() >= 10 => (read_u24(&data[4..]) + 1, read_u24(&data[7..]) + 1),
+        _ => return Err(Error::Message(String::from("Invalid VP8X chunk"))),
+    };
+
+    let mut canvas = vec![0u8; width * height * 4];
+    let mut count = 0;
+    for (_, data) in file.iter().filter(|(kind, _)| *kind == b"ANMF") {
+        let info = FrameInfo::parse(data)?;
+        if info.x + info.width > width || info.y + info.height > height {
+            return Err(Error::Message(String::from(
+                "Frame is outside of the image bounds",
+            )));
+        }
+
+        let (still, frame_width, frame_height) = still_image(&chunks(&data[16..])?)?;
+        if (frame_width, frame_height) != (info.width, info.height) {
+            return Err(Error::Message(String::from("Invalid WebP frame size")));
+        }
+        let pixels = decode_frame(&still, info.width, info.height)?;
+
+        let row_len = info.width * 4;
+        let rows = || {
+            (info.y..info.y + info.height)
+                .map(|y| (y * width + info.x) * 4)
+                .map(|start| start..start + row_len)
+        };
+
+        for (range, src) in rows().zip(pixels.chunks(row_len)) {
+            let dest = &mut canvas[range];
+            if info.blend {
+                for (src, dest) in src.chunks(4).zip(dest.chunks_mut(4)) {
+                    blend_over(src, dest);
+                }
+            } else {
+                dest.copy_from_slice(src);
+            }
+        }
+
+        super::store_frame(animation, count, width, height, &canvas, info.duration);
+        count += 1;
+
+        if info.dispose {
+            for range in rows() {
+                canvas[range].iter_mut().for_each(|x| *x = 0);
+            }
+        }
+    }
+
+    animation.truncate(count);
+    Ok(())
+}
+
+/// Decode every frame of an animated WebP file along with the time each frame is shown
+pub fn decode<Data: AsRef<[u8]>>(data: Data) -> Result<Animation<u8, Rgba>, Error> {
+    let mut frames = Vec::new();
+    decode_into(data, &mut frames)?;
+    Ok(frames)
+}
+
+/// Decode every frame of an animated WebP file into an existing animation, reusing the images of
+/// its frames
+pub fn decode_into<Data: AsRef<[u8]>>(
+    data: Data,
+    animation: &mut Animation<u8, Rgba>,
+) -> Result<(), Error> {
+    decode_with(data.as_ref(), decode_frame, animation)
+}
+
+/// Read every frame of an animated WebP file
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Animation<u8, Rgba>, Error> {
+    with_path(path, |path| decode(std::fs::read(path)?))
+}
+
+/// Read every frame of an animated WebP file into an existing animation, reusing the images of
+/// its frames
+pub fn read_into<P: AsRef<Path>>(
+    path: P,
+    animation: &mut Animation<u8, Rgba>,
+) -> Result<(), Error> {
+    with_path(path, |path| decode_into(std::fs::read(path)?, animation))
+}
+
+/// Number of times an animated WebP file is played, still images are played once
+pub fn repeat<Data: AsRef<[u8]>>(data: Data) -> Result<Repeat, Error> {
+    let chunks = parse(data.as_ref())?;
+    match chunks.iter().find(|(kind, _)| *kind == b"ANIM") {
+        Some((_, data)) if data.len() >= 6 => match u16::from_le_bytes([data[4], data[5]]) {
+            0 => Ok(Repeat::Infinite),
+            n => Ok(Repeat::Finite(n - 1)),
+        },
+        Some(_) => Err(Error::Message(String::from("Invalid ANIM chunk"))),
+        None => Ok(Repeat::Finite(0)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// `VP8L` chunk data with a valid header, the pixels are not decoded in the test
+    fn vp8l(width: usize, height: usize, color: u8) -> Vec<u8> {
+        let bits = (width - 1) | (height - 1) << 14;
+        let mut data = vec![0x2f];
+        data.extend_from_slice(&(bits as u32).to_le_bytes());
+        data.push(color);
+        data
+    }
+
+    #[test]
+    fn test_webp_animation() {
+        let mut still = Vec::new();
+        push_chunk(&mut still, b"VP8L", &vp8l(3, 2, 0));
+        let still = riff(&still);
+        assert_eq!(still.len(), 12 + 8 + 6);
+        assert_eq!(repeat(&still).unwrap(), Repeat::Finite(0));
+
+        // Frames are decoded to a solid color stored after the header
+        let decode_frame = |data: &[u8], width: usize, height: usize| {
+            let chunks = parse(data)?;
+            let (_, vp8l) = chunks.last().unwrap();
+            Ok([vp8l[5], 0, 0, 255].repeat(width * height))
+        };
+        let mut frames = Vec::new();
+        decode_with(&still, decode_frame, &mut frames).unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].0.width(), 3);
+
+        let frame = |info: FrameInfo, color: u8| {
+            let mut webp = Vec::new();
+            push_chunk(&mut webp, b"VP8L", &vp8l(info.width, info.height, color));
+            let mut anmf = Vec::new();
+            push_anmf(&mut anmf, info, &riff(&webp)).unwrap();
+            anmf
+        };
+
+        let first = FrameInfo {
+            x: 0,
+            y: 0,
+            width: 4,
+            height: 4,
+            duration: Duration::from_millis(70),
+            blend: false,
+            dispose: true,
+        };
+        let second = FrameInfo {
+            x: 2,
+            y: 2,
+            width: 1,
+            height: 2,
+            duration: Duration::from_millis(1234),
+            blend: true,
+            dispose: false,
+        };
+        assert_eq!(FrameInfo::parse(&second.to_bytes()).unwrap(), second);
+
+        let mut data = Vec::new();
+        push_chunk(&mut data, b"VP8X", &vp8x(ANIMATION, 4, 4));
+        push_chunk(&mut data, b"ANIM", &[0, 0, 0, 0, 3, 0]);
+        data.extend(frame(first, 10));
+        data.extend(frame(second, 20));
+        let data = riff(&data);
+        assert_eq!(repeat(&data).unwrap(), Repeat::Finite(2));
+
+        // The existing frame is reused for the first frame of the animation
+        decode_with(&data, decode_frame, &mut frames).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].1, Duration::from_millis(70));
+        assert_eq!(frames[1].1, Duration::from_millis(1234));
+        assert_eq!(frames[0].0.at(3, 3), &[10, 0, 0, 255]);
+        // The first frame was disposed, so only the second frame is left
+        assert_eq!(frames[1].0.at(3, 3), &[0, 0, 0, 0]);
+
+        assert_eq!(frames[1].0.at(2, 3), &[20, 0, 0, 255]);
+
+        decode_with(&still, decode_frame, &mut frames).unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].0.width(), 3);
+
+        // Frame sizes must match the image data
+        let mut webp = Vec::new();
+        push_chunk(&mut webp, b"VP8L", &vp8l(2, 2, 0));
+        assert!(push_anmf(&mut Vec::new(), first, &riff(&webp)).is_err());
+
+        // Profiles are stored after the `VP8X` chunk, which is added to simple files
+        let with_profile = encode_profile(&still, Some(b"icc")).unwrap();
+        let chunks = parse(&with_profile).unwrap();
+        assert_eq!(chunks[0].0, b"VP8X");
+        assert_eq!(features(&chunks).unwrap(), (ICC, 3, 2));
+        assert_eq!(chunks[1], (&b"ICCP"[..], &b"icc"[..]));
+        assert_eq!(
+            profile(&with_profile).unwrap().as_deref(),
+            Some(&b"icc"[..])
+        );
+        assert_eq!(profile(&still).unwrap(), None);
+        let without = encode_profile(&with_profile, None).unwrap();
+        assert_eq!(profile(&without).unwrap(), None);
+        assert_eq!(features(&parse(&without).unwrap()).unwrap(), (0, 3, 2));
+
+        let animated = encode_profile(&data, Some(b"icc")).unwrap();
+        assert_eq!(repeat(&animated).unwrap(), Repeat::Finite(2));
+        assert_eq!(
+            features(&parse(&animated).unwrap()).unwrap(),
+            (ANIMATION | ICC, 4, 4)
+        );
+        decode_with(&animated, decode_frame, &mut frames).unwrap();
+        assert_eq!(frames.len(), 2);
+    }
+}
@@ -0,0 +1,359 @@
+//! Exchange images between processes using named shared memory
+//!
+//! A `Publisher` creates a shared memory region containing a small header, describing the size,
+//! type and color of the image along with a sequence number, followed by the image data. Any
+//! number of `Subscriber`s can open the region by name and read new frames as they are published.
+//! Frames can be written in place, without copying them into the shared region. Subscribers copy
+//! each frame out of the region, because the publisher may overwrite it at any time.
+//!
+//! ```rust,no_run
+//! use image2::{ipc, ImageBuf, Rgb};
+//!
+//! // Capture process
+//! let mut publisher = ipc::Publisher::<u8, Rgb>::create("camera0", 640, 480)?;
+//! publisher.publish_with(|frame| {
+//!     // Fill `frame` directly
+//! });
+//!
+//! // Processing process
+//! let mut subscriber = ipc::Subscriber::<u8, Rgb>::open("camera0")?;
+//! if let Some(frame) = subscriber.read() {
+//!     // Process `frame`
+//! }
+//! # Ok::<(), image2::Error>(())
+//! ```
+//!
+//! On Linux shared memory regions are files in `/dev/shm`, on other platforms the temporary
+//! directory is used. A publisher removes its region when it is dropped, subscribers need to
+//! reopen it when the publisher is restarted.
+
+use std::convert::TryFrom;
+use std::fs::{File, OpenOptions};
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use memmap2::{Mmap, MmapMut};
+
+use crate::color::Color;
+use crate::error::Error;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::image_ref::ImageRef;
+use crate::ty::{dtype_name, Type};
+
+const MAGIC: [u8; 8] = *b"IMAGE2SH";
+const VERSION: u32 = 1;
+
+/// Offset of the image data, the header is padded so the data is aligned for every type
+const DATA_OFFSET: usize = 64;
+
+/// Header stored at the beginning of a shared memory region
+#[repr(C)]
+struct Header {
+    magic: [u8; 8],
+    version: u32,
+    width: u32,
+    height: u32,
+    channels: u32,
+    dtype: [u8; 16],
+    color: [u8; 16],
+    /// Odd while a frame is being written, each published frame increases it by 2
+    sequence: AtomicU64,
+}
+
+fn name_bytes(name: &str) -> [u8; 16] {
+    let mut bytes = [0; 16];
+    let len = name.len().min(16);
+    bytes[..len].copy_from_slice(&name.as_bytes()[..len]);
+    bytes
+}
+
+/// Path of the file backing the shared memory region called `name`
+fn shm_path(name: &str) -> Result<PathBuf, Error> {
+    if name.is_empty() || name.contains(['/', '\\']) {
+        return Err(Error::Message(format!(
+            "Invalid shared memory name: {:?}",
+            name
+        )));
+    }
+
+    let dir = PathBuf::from("/dev/shm");
+    let dir = if dir.is_dir() {
+        dir
+    } else {
+        std::env::temp_dir()
+    };
+    Ok(dir.join(format!("image2-{}", name)))
+}
+
+/// Writes frames to a named shared memory region
+pub struct Publisher<T: Type, C: Color> {
+    map: MmapMut,
+    path: PathBuf,
+    width: usize,
+    height: usize,
+    _type: PhantomData<(T, C)>,
+}
+
+impl<T: Type, C: Color> Publisher<T, C> {
+    /// Create a shared memory region for images of the given size, replacing any existing region
+    /// with the same name
+    pub fn create(name: &str, width: usize, height: usize) -> Result<Publisher<T, C>, Error> {
+        let path = shm_path(name)?;
+        let too_large = || Error::Message(format!("Image size {}x{} is too large", width, height));
+        let header_width = u32::try_from(width).map_err(|_| too_large())?;
+        let header_height = u32::try_from(height).map_err(|_| too_large())?;
+        let file_len = width
+            .checked_mul(height)
+            .and_then(|n| n.checked_mul(C::channels() * std::mem::size_of::<T>()))
+            .and_then(|n| n.checked_add(DATA_OFFSET))
+            .ok_or_else(too_large)?;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+        file.set_len(file_len as u64)?;
+
+        let mut map = unsafe { MmapMut::map_mut(&file)? };
+        let header = Header {
+            magic: MAGIC,
+            version: VERSION,
+            width: header_width,
+            height: header_height,
+            channels: C::channels() as u32,
+            dtype: name_bytes(&dtype_name::<T>()),
+            color: name_bytes(C::name()),
+            sequence: AtomicU64::new(0),
+        };
+        unsafe { std::ptr::write(map.as_mut_ptr() as *mut Header, header) };
+
+        Ok(Publisher {
+            map,
+            path,
+            width,
+            height,
+            _type: PhantomData,
+        })
+    }
+
+    fn sequence(&self) -> &AtomicU64 {
+        unsafe { &(*(self.map.as_ptr() as *const Header)).sequence }
+    }
+
+    /// Write a frame in place by calling `f` with an image backed by the shared memory, which
+    /// still contains the previous frame. Returns the sequence number of the new frame.
+    pub fn publish_with<F: FnOnce(&mut ImageRef<T, C>)>(&mut self, f: F) -> u64 {
+        let seq = self.sequence().load(Ordering::Relaxed);
+        self.sequence().store(seq + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+
+        let (width, height) = (self.width, self.height);
+        let len = width * height * C::channels();
+        let data = unsafe {
+            std::slice::from_raw_parts_mut(self.map.as_mut_ptr().add(DATA_OFFSET) as *mut T, len)
+        };
+        f(&mut ImageRef::new(width, height, data));
+
+        self.sequence().store(seq + 2, Ordering::Release);
+        (seq + 2) / 2
+    }
+
+    /// Copy an image into shared memory, returning the sequence number of the new frame
+    pub fn publish<I: Image<T, C>>(&mut self, image: &I) -> Result<u64, Error> {
+        if image.width() != self.width || image.height() != self.height {
+            return Err(Error::Message(format!(
+                "Expected a {}x{} image, got {}x{}",
+                self.width,
+                self.height,
+                image.width(),
+                image.height()
+            )));
+        }
+
+        Ok(self.publish_with(|frame| frame.data_mut().copy_from_slice(image.data())))
+    }
+}
+
+impl<T: Type, C: Color> Drop for Publisher<T, C> {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Reads frames from a named shared memory region
+pub struct Subscriber<T: Type, C: Color> {
+    map: Mmap,
+    width: usize,
+    height: usize,
+    last: u64,
+    /// Copy of the latest frame used by `read_with`
+    buffer: Vec<T>,
+    _type: PhantomData<(T, C)>,
+}
+
+impl<T: Type, C: Color> Subscriber<T, C> {
+    /// Open an existing shared memory region, returning an error if it contains images with a
+    /// different type or color
+    pub fn open(name: &str) -> Result<Subscriber<T, C>, Error> {
+        let file = File::open(shm_path(name)?)?;
+        let map = unsafe { Mmap::map(&file)? };
+        if map.len() < DATA_OFFSET {
+            return Err(Error::Message(String::from(
+                "Shared memory region is too small",
+            )));
+        }
+
+        let header = unsafe { &*(map.as_ptr() as *const Header) };
+        if header.magic != MAGIC || header.version != VERSION {
+            return Err(Error::Message(String::from(
+                "Shared memory region was not created by image2",
+            )));
+        }
+
+        if header.dtype != name_bytes(&dtype_name::<T>()) {
+            return Err(Error::InvalidType);
+        }
+
+        if header.color != name_bytes(C::name()) || header.channels as usize != C::channels() {
+            return Err(Error::InvalidColor);
+        }
+
+        let (width, height) = (header.width as usize, header.height as usize);
+        let len = width * height * C::channels() * std::mem::size_of::<T>();
+        if map.len() < DATA_OFFSET + len {
+            return Err(Error::Message(String::from(
+                "Shared memory region is too small",
+            )));
+        }
+
+        Ok(Subscriber {
+            map,
+            width,
+            height,
+            last: 0,
+            buffer: Vec::new(),
+            _type: PhantomData,
+        })
+    }
+
+    /// Width and height of the published images
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    /// Sequence number of the latest complete frame, 0 if nothing has been published
+    pub fn sequence(&self) -> u64 {
+        let header = unsafe { &*(self.map.as_ptr() as *const Header) };
+        header.sequence.load(Ordering::Acquire) / 2
+    }
+
+    /// Copy the latest frame into `dest`, which must have room for the whole frame, if a new
+    /// frame has been published since the last successful read. Returns false when there is no
+    /// new frame or the frame was overwritten while it was being copied, the contents of `dest`
+    /// are unspecified in that case.
+    fn copy_frame(&mut self, dest: &mut [T]) -> bool {
+        let header = unsafe { &*(self.map.as_ptr() as *const Header) };
+        let start = header.sequence.load(Ordering::Acquire);
+        if start % 2 == 1 || start / 2 == self.last {
+            return false;
+        }
+
+        // The publisher may write to the region at the same time, so the data is only read
+        // through volatile reads and never referenced
+        let src = unsafe { self.map.as_ptr().add(DATA_OFFSET) as *const T };
+        for (i, d) in dest.iter_mut().enumerate() {
+            *d = unsafe { std::ptr::read_volatile(src.add(i)) };
+        }
+
+        fence(Ordering::Acquire);
+        if header.sequence.load(Ordering::Relaxed) != start {
+            return false;
+        }
+
+        self.last = start / 2;
+        true
+    }
+
+    /// Call `f` with a copy of the latest frame, if a new frame has been published since the
+    /// last successful read. Returns `None` when there is no new frame or the frame was
+    /// overwritten while it was being copied. The copy is stored in a buffer that is reused by
+    /// every call.
+    pub fn read_with<R, F: FnOnce(&[T]) -> R>(&mut self, f: F) -> Option<R> {
+        let mut buffer = std::mem::take(&mut self.buffer);
+        buffer.resize(self.width * self.height * C::channels(), T::zero());
+        let copied = self.copy_frame(&mut buffer);
+        let result = if copied { Some(f(&buffer)) } else { None };
+        self.buffer = buffer;
+        result
+    }
+
+    /// Copy the latest frame, if a new frame has been published since the last read
+    pub fn read(&mut self) -> Option<ImageBuf<T, C>> {
+        let mut image = ImageBuf::new(self.width, self.height);
+        if self.copy_frame(image.data_mut()) {
+            Some(image)
+        } else {
+            None
+        }
+    }
+
+    /// Wait for a new frame, polling every millisecond until `timeout` has elapsed
+    pub fn wait(&mut self, timeout: Duration) -> Option<ImageBuf<T, C>> {
+        let start = Instant::now();
+        loop {
+            if let Some(image) = self.read() {
+                return Some(image);
+            }
+
+            if start.elapsed() >= timeout {
+                return None;
+            }
+
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Gray, Rgb};
+
+    #[test]
+    fn test_ipc() {
+        let name = format!("test-{}", std::process::id());
+        let mut publisher = Publisher::<u16, Rgb>::create(&name, 4, 3).unwrap();
+        let mut subscriber = Subscriber::<u16, Rgb>::open(&name).unwrap();
+        assert_eq!(subscriber.size(), (4, 3));
+        assert!(subscriber.read().is_none());
+        assert!(Subscriber::<u8, Rgb>::open(&name).is_err());
+        assert!(Subscriber::<u16, Gray>::open(&name).is_err());
+
+        let mut image: ImageBuf<u16, Rgb> = ImageBuf::new(4, 3);
+        image.at_mut(3, 2)[1] = 1000;
+        assert_eq!(publisher.publish(&image).unwrap(), 1);
+        assert_eq!(subscriber.sequence(), 1);
+        assert_eq!(subscriber.read().unwrap(), image);
+        assert!(subscriber.read().is_none());
+
+        assert_eq!(publisher.publish_with(|frame| frame.at_mut(0, 0)[0] = 7), 2);
+        let frame = subscriber.wait(Duration::from_secs(1)).unwrap();
+        assert_eq!(frame.at(0, 0)[0], 7);
+        assert_eq!(frame.at(3, 2)[1], 1000);
+
+        // Frames are copied before they are passed to `read_with`
+        publisher.publish_with(|frame| frame.at_mut(1, 1)[2] = 9);
+        let sum = subscriber.read_with(|data| data.iter().map(|&x| u64::from(x)).sum::<u64>());
+        assert_eq!(sum, Some(1016));
+        assert!(subscriber.read_with(|_| ()).is_none());
+
+        assert!(publisher.publish(&ImageBuf::new(1, 1)).is_err());
+        assert!(Publisher::<u8, Rgb>::create(&name, 1 << 32, 1).is_err());
+        drop(publisher);
+        assert!(Subscriber::<u16, Rgb>::open(&name).is_err());
+    }
+}
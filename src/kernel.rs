@@ -1,6 +1,10 @@
-use std::f64;
-use std::ops;
+use alloc::vec::Vec;
+use core::f64;
+use core::ops;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
 
+#[cfg(feature = "std")]
 use lazy_static::lazy_static;
 
 use crate::color::Color;
@@ -12,9 +16,9 @@ use crate::ty::Type;
 #[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub struct Kernel {
-    rows: usize,
-    cols: usize,
-    data: Vec<Vec<f64>>,
+    pub(crate) rows: usize,
+    pub(crate) cols: usize,
+    pub(crate) data: Vec<Vec<f64>>,
 }
 
 impl From<Vec<Vec<f64>>> for Kernel {
@@ -136,44 +140,92 @@ pub fn gaussian(n: usize, std: f64) -> Kernel {
     k
 }
 
+/// Create a normalized kernel containing an anti-aliased line of the given length, rotated
+/// counter-clockwise by `angle` degrees. This is used to simulate motion blur.
+pub fn line(length: usize, angle: f64) -> Kernel {
+    let half = (length / 2).max(1);
+    let n = half * 2 + 1;
+    let center = half as f64;
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let mut k = Kernel::square(n);
+
+    let steps = length.max(1) * 4;
+    for s in 0..=steps {
+        let t = (s as f64 / steps as f64 - 0.5) * length.max(1) as f64;
+        let x = center + t * cos;
+        let y = center - t * sin;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+
+        for (dx, dy, w) in &[
+            (0, 0, (1.0 - fx) * (1.0 - fy)),
+            (1, 0, fx * (1.0 - fy)),
+            (0, 1, (1.0 - fx) * fy),
+            (1, 1, fx * fy),
+        ] {
+            let i = x0 as isize + dx;
+            let j = y0 as isize + dy;
+            if i >= 0 && j >= 0 && (i as usize) < n && (j as usize) < n {
+                k.data[j as usize][i as usize] += w;
+            }
+        }
+    }
+
+    k.normalize();
+    k
+}
+
+fn sobel_x() -> Kernel {
+    Kernel::from([[1.0, 0.0, -1.0], [2.0, 0.0, -2.0], [1.0, 0.0, -1.0]])
+}
+
+fn sobel_y() -> Kernel {
+    Kernel::from([[1.0, 2.0, 1.0], [0.0, 0.0, 0.0], [-1.0, -2.0, -1.0]])
+}
+
+#[cfg(feature = "std")]
 lazy_static! {
     pub static ref GAUSSIAN_3X3: Kernel = gaussian(3, 1.4);
 }
 
+#[cfg(feature = "std")]
 lazy_static! {
     pub static ref GAUSSIAN_5X5: Kernel = gaussian(5, 1.4);
 }
 
+#[cfg(feature = "std")]
 lazy_static! {
     pub static ref GAUSSIAN_7X7: Kernel = gaussian(7, 1.4);
 }
 
+#[cfg(feature = "std")]
 lazy_static! {
     pub static ref GAUSSIAN_9X9: Kernel = gaussian(9, 1.4);
 }
 
+#[cfg(feature = "std")]
 lazy_static! {
-    pub static ref SOBEL_X: Kernel = Kernel {
-        rows: 3,
-        cols: 3,
-        data: vec![
-            vec![1.0, 0.0, -1.0],
-            vec![2.0, 0.0, -2.0],
-            vec![1.0, 0.0, -1.0],
-        ]
-    };
+    pub static ref SOBEL_X: Kernel = sobel_x();
 }
 
+#[cfg(feature = "std")]
 lazy_static! {
-    pub static ref SOBEL_Y: Kernel = Kernel {
-        rows: 3,
-        cols: 3,
-        data: vec![
-            vec![1.0, 2.0, 1.0],
-            vec![0.0, 0.0, 0.0],
-            vec![-1.0, -2.0, -1.0],
-        ]
-    };
+    pub static ref SOBEL_Y: Kernel = sobel_y();
+}
+
+/// Clone the kernel cached in `$cache`, without std there are no caches so the kernel is created
+/// using `$create` for each call
+macro_rules! cached {
+    ($cache:ident, $create:expr) => {{
+        #[cfg(feature = "std")]
+        {
+            $cache.clone()
+        }
+        #[cfg(not(feature = "std"))]
+        {
+            $create
+        }
+    }};
 }
 
 macro_rules! op {
@@ -227,21 +279,67 @@ op!(Div, div, |a, b| a / b);
 op!(Rem, rem, |a, b| a % b);
 
 pub fn sobel() -> Add {
-    SOBEL_X.clone() + SOBEL_Y.clone()
+    cached!(SOBEL_X, sobel_x()) + cached!(SOBEL_Y, sobel_y())
 }
 
 pub fn gaussian_3x3() -> Kernel {
-    GAUSSIAN_3X3.clone()
+    cached!(GAUSSIAN_3X3, gaussian(3, 1.4))
 }
 
 pub fn gaussian_5x5() -> Kernel {
-    GAUSSIAN_5X5.clone()
+    cached!(GAUSSIAN_5X5, gaussian(5, 1.4))
 }
 
 pub fn gaussian_7x7() -> Kernel {
-    GAUSSIAN_7X7.clone()
+    cached!(GAUSSIAN_7X7, gaussian(7, 1.4))
 }
 
 pub fn gaussian_9x9() -> Kernel {
-    GAUSSIAN_9X9.clone()
+    cached!(GAUSSIAN_9X9, gaussian(9, 1.4))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Sum of the weights in the rows and columns of a kernel
+    fn sums(k: &Kernel) -> (Vec<f64>, Vec<f64>) {
+        let rows = k.data.iter().map(|row| row.iter().sum()).collect();
+        let cols = (0..k.cols)
+            .map(|i| k.data.iter().map(|row| row[i]).sum())
+            .collect();
+        (rows, cols)
+    }
+
+    #[test]
+    fn test_line() {
+        for &(length, angle) in &[(1, 0.0), (5, 0.0), (9, 30.0), (6, 135.0), (15, 270.0)] {
+            let k = line(length, angle);
+            assert_eq!(k.rows % 2, 1);
+            let total: f64 = k.data.iter().flatten().sum();
+            assert!((total - 1.0).abs() < 1e-9, "{} {}", length, angle);
+            assert!(k.data.iter().flatten().all(|w| *w >= 0.0));
+        }
+
+        // Horizontal and vertical lines only cover the middle row or column
+        let horizontal = line(5, 0.0);
+        let (rows, cols) = sums(&horizontal);
+        assert_eq!(horizontal.rows, 5);
+        assert!((rows[2] - 1.0).abs() < 1e-9);
+        assert!(cols.iter().all(|c| *c > 0.1));
+
+        let vertical = line(5, 90.0);
+        let (rows, cols) = sums(&vertical);
+        assert!((cols[2] - 1.0).abs() < 1e-9);
+        assert!(rows.iter().all(|r| *r > 0.1));
+
+        // Positive angles rotate counter-clockwise, so the line goes from the bottom left to the
+        // top right corner
+        let diagonal = line(7, 45.0);
+        let n = diagonal.rows - 1;
+        assert!(diagonal.data[0][n] > 0.0 && diagonal.data[n][0] > 0.0);
+        assert_eq!(diagonal.data[0][0], 0.0);
+        assert_eq!(diagonal.data[n][n], 0.0);
+        assert!((line(7, -45.0).data[0][0] - diagonal.data[0][n]).abs() < 1e-12);
+    }
 }
@@ -31,6 +31,20 @@
 //!    io::write("example.png", &output).unwrap();
 //!}
 //!```
+//!
+//! For quick scripts, `Image2` provides a simpler interface using `u8` RGBA images and the
+//! default codecs:
+//! ```rust,no_run
+//! use image2::prelude::*;
+//!
+//! Image2::open("test/test.jpg")?.resize(320, 0).grayscale().save("example.png")?;
+//! # Ok::<(), image2::Error>(())
+//! ```
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[macro_use]
+extern crate alloc;
 
 #[cfg(test)]
 mod tests;
@@ -39,25 +53,80 @@ mod tests;
 pub mod image;
 #[macro_use]
 pub mod filter;
+pub mod adjust;
+pub mod analysis;
+pub mod annotate;
+#[cfg(feature = "v4l")]
+pub mod capture;
 pub mod color;
+pub mod correct;
+#[cfg(feature = "dataset")]
+pub mod dataset;
+pub mod dct;
+pub mod diff;
+#[cfg(feature = "std")]
+pub mod display;
+#[cfg(feature = "dlpack")]
+pub mod dlpack;
+pub mod draw;
+pub mod effect;
 mod error;
+#[cfg(feature = "io")]
+mod facade;
+pub mod feature;
+pub mod fft;
+pub mod flow;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+pub mod gradient;
+pub mod hdr;
 mod image_buf;
 mod image_ptr;
 mod image_ref;
+#[cfg(feature = "std")]
+mod interop;
 #[cfg(feature = "io")]
 pub mod io;
+#[cfg(feature = "ipc")]
+pub mod ipc;
 pub mod kernel;
+pub mod lut;
+#[cfg(feature = "io")]
+pub mod metadata;
+pub mod metrics;
+#[cfg(feature = "opencl")]
+pub mod opencl;
+pub mod parallel;
+pub mod pipeline;
 mod pixel;
+pub mod prelude;
+pub mod pyramid;
+mod rect;
+pub mod register;
+pub mod segment;
+pub mod select;
+pub mod simd;
+pub mod stats;
+pub mod stitch;
 pub mod transform;
 mod ty;
 
 pub use self::color::{Color, Gray, Rgb, Rgba};
 pub use self::error::Error;
+#[cfg(feature = "io")]
+pub use self::facade::Image2;
 pub use self::filter::Filter;
 pub use self::image::{Convert, Diff, Hash, Image};
-pub use self::image_buf::ImageBuf;
+pub use self::image_buf::{
+    Alignment, ImageBuf, PaddedImage, Resolution, ResolutionUnit, INLINE_BYTES,
+};
 pub use self::image_ptr::{Free, ImagePtr};
 pub use self::image_ref::ImageRef;
+#[cfg(feature = "io")]
+pub use self::io::any::{open, save, AnyImage};
 pub use self::kernel::Kernel;
-pub use self::pixel::{colorspace, Pixel, PixelMut, PixelVec};
+#[cfg(feature = "std")]
+pub use self::pixel::colorspace;
+pub use self::pixel::{Pixel, PixelMut, PixelVec, Px};
+pub use self::rect::Rect;
 pub use self::ty::Type;
@@ -0,0 +1,308 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+#[cfg(feature = "std")]
+use std::path::Path;
+
+use crate::color::Color;
+#[cfg(feature = "std")]
+use crate::error::with_path;
+use crate::error::Error;
+use crate::filter::Filter;
+use crate::image::Image;
+use crate::ty::Type;
+
+/// 3-dimensional color lookup table, typically loaded from a `.cube` file
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lut3d {
+    size: usize,
+    domain_min: [f64; 3],
+    domain_max: [f64; 3],
+    data: Vec<[f64; 3]>,
+}
+
+/// Number of entries of a LUT with `size` entries along each axis
+fn entries(size: usize) -> Result<usize, Error> {
+    size.checked_mul(size)
+        .and_then(|n| n.checked_mul(size))
+        .filter(|n| size >= 2 && *n <= isize::MAX as usize / core::mem::size_of::<[f64; 3]>())
+        .ok_or_else(|| Error::Message(format!("Invalid 3D LUT size: {}", size)))
+}
+
+impl Lut3d {
+    /// Create a new LUT from `size * size * size` entries, the red index changes fastest
+    pub fn new(size: usize, data: Vec<[f64; 3]>) -> Result<Lut3d, Error> {
+        let len = entries(size)?;
+        if data.len() != len {
+            return Err(Error::Message(format!(
+                "Invalid 3D LUT: expected {} entries, got {}",
+                len,
+                data.len()
+            )));
+        }
+
+        Ok(Lut3d {
+            size,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+            data,
+        })
+    }
+
+    /// Create a LUT that maps every color to itself, `size` must be at least 2
+    pub fn identity(size: usize) -> Result<Lut3d, Error> {
+        let mut data = Vec::with_capacity(entries(size)?);
+        let n = (size - 1) as f64;
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    data.push([r as f64 / n, g as f64 / n, b as f64 / n]);
+                }
+            }
+        }
+
+        Ok(Lut3d {
+            size,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+            data,
+        })
+    }
+
+    /// Load a LUT from a `.cube` file
+    #[cfg(feature = "std")]
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Lut3d, Error> {
+        with_path(path, |path| Self::parse(&std::fs::read_to_string(path)?))
+    }
+
+    /// Parse the contents of a `.cube` file
+    pub fn parse(s: &str) -> Result<Lut3d, Error> {
+        let mut size = 0;
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let mut data = Vec::new();
+
+        fn triple(line: &str, parts: &[&str]) -> Result<[f64; 3], Error> {
+            if parts.len() != 3 {
+                return Err(Error::Message(format!("Invalid .cube line: {}", line)));
+            }
+
+            let mut dest = [0.0; 3];
+            for (d, p) in dest.iter_mut().zip(parts) {
+                *d = match p.parse() {
+                    Ok(f) => f,
+                    Err(_) => return Err(Error::Message(format!("Invalid .cube line: {}", line))),
+                }
+            }
+            Ok(dest)
+        }
+
+        for line in s.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            match parts[0] {
+                "TITLE" => (),
+                "LUT_3D_SIZE" => {
+                    size = match parts.get(1).and_then(|x| x.parse().ok()) {
+                        Some(n) => n,
+                        None => return Err(Error::Message(format!("Invalid .cube line: {}", line))),
+                    }
+                }
+                "LUT_1D_SIZE" => {
+                    return Err(Error::Message(String::from(
+                        "1D LUTs are not supported by Lut3d",
+                    )))
+                }
+                "DOMAIN_MIN" => domain_min = triple(line, &parts[1..])?,
+                "DOMAIN_MAX" => domain_max = triple(line, &parts[1..])?,
+                _ => data.push(triple(line, &parts)?),
+            }
+        }
+
+        let mut lut = Self::new(size, data)?;
+        lut.domain_min = domain_min;
+        lut.domain_max = domain_max;
+        Ok(lut)
+    }
+
+    /// Number of entries along each axis
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    #[inline]
+    fn entry(&self, r: usize, g: usize, b: usize) -> &[f64; 3] {
+        &self.data[r + self.size * (g + self.size * b)]
+    }
+
+    /// Map a single normalized RGB value using tetrahedral interpolation
+    pub fn lookup(&self, rgb: [f64; 3]) -> [f64; 3] {
+        let n = (self.size - 1) as f64;
+        let mut index = [0usize; 3];
+        let mut frac = [0.0; 3];
+
+        for i in 0..3 {
+            let range = self.domain_max[i] - self.domain_min[i];
+            let f = ((rgb[i] - self.domain_min[i]) / range).clamp(0.0, 1.0) * n;
+            let base = (f.floor() as usize).min(self.size - 2);
+            index[i] = base;
+            frac[i] = f - base as f64;
+        }
+
+        let (r, g, b) = (index[0], index[1], index[2]);
+        let (fr, fg, fb) = (frac[0], frac[1], frac[2]);
+
+        let c000 = self.entry(r, g, b);
+        let c111 = self.entry(r + 1, g + 1, b + 1);
+
+        // Select the tetrahedron containing the point and the weights of its vertices
+        let (c1, c2, w0, w1, w2, w3) = if fr > fg {
+            if fg > fb {
+                (
+                    self.entry(r + 1, g, b),
+                    self.entry(r + 1, g + 1, b),
+                    1.0 - fr,
+                    fr - fg,
+                    fg - fb,
+                    fb,
+                )
+            } else if fr > fb {
+                (
+                    self.entry(r + 1, g, b),
+                    self.entry(r + 1, g, b + 1),
+                    1.0 - fr,
+                    fr - fb,
+                    fb - fg,
+                    fg,
+                )
+            } else {
+                (
+                    self.entry(r, g, b + 1),
+                    self.entry(r + 1, g, b + 1),
+                    1.0 - fb,
+                    fb - fr,
+                    fr - fg,
+                    fg,
+                )
+            }
+        } else if fb > fg {
+            (
+                self.entry(r, g, b + 1),
+                self.entry(r, g + 1, b + 1),
+                1.0 - fb,
+                fb - fg,
+                fg - fr,
+                fr,
+            )
+        } else if fb > fr {
+            (
+                self.entry(r, g + 1, b),
+                self.entry(r, g + 1, b + 1),
+                1.0 - fg,
+                fg - fb,
+                fb - fr,
+                fr,
+            )
+        } else {
+            (
+                self.entry(r, g + 1, b),
+                self.entry(r + 1, g + 1, b),
+                1.0 - fg,
+                fg - fr,
+                fr - fb,
+                fb,
+            )
+        };
+
+        let mut dest = [0.0; 3];
+        for i in 0..3 {
+            dest[i] = w0 * c000[i] + w1 * c1[i] + w2 * c2[i] + w3 * c111[i];
+        }
+        dest
+    }
+
+    /// Apply the LUT to every pixel of an image in place, any alpha channel is left untouched
+    pub fn apply<T: Type, C: Color, I: Image<T, C>>(&self, image: &mut I) {
+        image.for_each(|_, px| {
+            if C::channels() < 3 {
+                let v = T::to_f(&px[0]);
+                let rgb = self.lookup([v, v, v]);
+                px[0] = T::from_f((rgb[0] + rgb[1] + rgb[2]) / 3.0);
+                return;
+            }
+
+            let rgb = self.lookup([T::to_f(&px[0]), T::to_f(&px[1]), T::to_f(&px[2])]);
+            for c in 0..3 {
+                px[c] = T::from_f(rgb[c]);
+            }
+        });
+    }
+}
+
+impl Filter for Lut3d {
+    fn compute_at<T: Type, C: Color, I: Image<T, C>>(
+        &self,
+        x: usize,
+        y: usize,
+        c: usize,
+        input: &[&I],
+    ) -> f64 {
+        let a = input[0];
+
+        if C::channels() < 3 {
+            let v = a.get_f(x, y, 0);
+            let rgb = self.lookup([v, v, v]);
+            return (rgb[0] + rgb[1] + rgb[2]) / 3.0;
+        }
+
+        if c >= 3 {
+            return a.get_f(x, y, c);
+        }
+
+        self.lookup([a.get_f(x, y, 0), a.get_f(x, y, 1), a.get_f(x, y, 2)])[c]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lut::Lut3d;
+
+    #[test]
+    fn test_lut_parse() {
+        let cube = "TITLE \"invert\"\n# comment\nLUT_3D_SIZE 2\n\
+                    1 1 1\n0 1 1\n1 0 1\n0 0 1\n1 1 0\n0 1 0\n1 0 0\n0 0 0\n";
+        let lut = Lut3d::parse(cube).unwrap();
+        assert_eq!(lut.size(), 2);
+        let px = lut.lookup([0.25, 0.5, 1.0]);
+        assert!((px[0] - 0.75).abs() < 1e-9);
+        assert!((px[1] - 0.5).abs() < 1e-9);
+        assert!(px[2].abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_lut_identity() {
+        let lut = Lut3d::identity(17).unwrap();
+        for rgb in &[[0.1, 0.7, 0.3], [0.9, 0.2, 0.5], [0.0, 1.0, 0.33]] {
+            let px = lut.lookup(*rgb);
+            for i in 0..3 {
+                assert!((px[i] - rgb[i]).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_lut_size() {
+        assert!(Lut3d::identity(1).is_err());
+        assert!(Lut3d::identity(usize::MAX).is_err());
+        assert!(Lut3d::identity(1 << 22).is_err());
+        assert!(Lut3d::new(1 << 40, Vec::new()).is_err());
+        assert!(Lut3d::new(2, vec![[0.0; 3]; 7]).is_err());
+        assert!(Lut3d::parse("LUT_3D_SIZE 18446744073709551615\n0 0 0").is_err());
+    }
+}
@@ -0,0 +1,1114 @@
+//! Read and write EXIF metadata in JPEG, PNG and TIFF files without ImageMagick
+//!
+//! ```rust,no_run
+//! use image2::metadata::exif::Exif;
+//!
+//! let mut exif = Exif::read("photo.jpg")?.unwrap_or_default();
+//! println!("{:?} ISO {:?}", exif.datetime(), exif.iso());
+//! exif.set_orientation(1);
+//! exif.write("photo.jpg")?;
+//! # Ok::<(), image2::Error>(())
+//! ```
+//!
+//! Tags are kept in the primary, Exif, GPS and interoperability IFDs, the pointers between them
+//! are updated automatically when writing. The JPEG thumbnail stored in the second IFD is kept,
+//! other tags of the second IFD are dropped. TIFF files keep their image data untouched and never
+//! get a thumbnail, since their second IFD is the next page: the new IFD is appended to
+//! the end of the file and the tags describing the layout of the image data, like `StripOffsets`,
+//! are always taken from the existing file.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::error::{with_path, Error};
+use crate::image_buf::{Resolution, ResolutionUnit};
+
+use super::{container, jpeg_app, jpeg_replace_app, png_replace_chunk, Container, EXIF_HEADER};
+
+/// Common tags
+pub mod tag {
+    pub const MAKE: u16 = 0x010f;
+    pub const MODEL: u16 = 0x0110;
+    pub const ORIENTATION: u16 = 0x0112;
+    pub const X_RESOLUTION: u16 = 0x011a;
+    pub const Y_RESOLUTION: u16 = 0x011b;
+    pub const RESOLUTION_UNIT: u16 = 0x0128;
+    pub const DATETIME: u16 = 0x0132;
+    pub const EXPOSURE_TIME: u16 = 0x829a;
+    pub const F_NUMBER: u16 = 0x829d;
+    pub const ISO: u16 = 0x8827;
+    pub const DATETIME_ORIGINAL: u16 = 0x9003;
+    pub const FOCAL_LENGTH: u16 = 0x920a;
+    pub const LENS_MAKE: u16 = 0xa433;
+    pub const LENS_MODEL: u16 = 0xa434;
+
+    pub const GPS_VERSION_ID: u16 = 0x0000;
+    pub const GPS_LATITUDE_REF: u16 = 0x0001;
+    pub const GPS_LATITUDE: u16 = 0x0002;
+    pub const GPS_LONGITUDE_REF: u16 = 0x0003;
+    pub const GPS_LONGITUDE: u16 = 0x0004;
+    pub const GPS_ALTITUDE_REF: u16 = 0x0005;
+    pub const GPS_ALTITUDE: u16 = 0x0006;
+    pub const GPS_TIMESTAMP: u16 = 0x0007;
+    pub const GPS_DATESTAMP: u16 = 0x001d;
+
+    pub(super) const THUMBNAIL_OFFSET: u16 = 0x0201;
+    pub(super) const THUMBNAIL_LENGTH: u16 = 0x0202;
+
+    pub(super) const EXIF_IFD: u16 = 0x8769;
+    pub(super) const GPS_IFD: u16 = 0x8825;
+    pub(super) const INTEROP_IFD: u16 = 0xa005;
+}
+
+/// Tags of the primary IFD of TIFF files that describe the image data
+const LAYOUT_TAGS: &[u16] = &[
+    0x00fe, 0x0100, 0x0101, 0x0102, 0x0103, 0x0106, 0x0111, 0x0115, 0x0116, 0x0117, 0x011c, 0x013d,
+    0x0140, 0x0142, 0x0143, 0x0144, 0x0145, 0x014a, 0x0152, 0x0153,
+];
+
+/// Convert degrees, minutes and seconds to decimal degrees
+pub fn dms_to_degrees(dms: [f64; 3]) -> f64 {
+    dms[0] + dms[1] / 60.0 + dms[2] / 3600.0
+}
+
+/// Convert decimal degrees to degrees, minutes and seconds. The sign is dropped, EXIF stores it
+/// separately as the direction
+pub fn degrees_to_dms(degrees: f64) -> [f64; 3] {
+    let degrees = degrees.abs();
+    let d = degrees.trunc();
+    let m = ((degrees - d) * 60.0).trunc();
+    let s = (degrees - d - m / 60.0) * 3600.0;
+    [d, m, s.max(0.0)]
+}
+
+/// Byte order of the TIFF structure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    LittleEndian,
+    BigEndian,
+}
+
+impl ByteOrder {
+    fn u16(self, b: &[u8]) -> u16 {
+        let b = [b[0], b[1]];
+        match self {
+            ByteOrder::LittleEndian => u16::from_le_bytes(b),
+            ByteOrder::BigEndian => u16::from_be_bytes(b),
+        }
+    }
+
+    fn u32(self, b: &[u8]) -> u32 {
+        let b = [b[0], b[1], b[2], b[3]];
+        match self {
+            ByteOrder::LittleEndian => u32::from_le_bytes(b),
+            ByteOrder::BigEndian => u32::from_be_bytes(b),
+        }
+    }
+
+    fn u64(self, b: &[u8]) -> u64 {
+        let (a, b) = (u64::from(self.u32(b)), u64::from(self.u32(&b[4..])));
+        match self {
+            ByteOrder::LittleEndian => b << 32 | a,
+            ByteOrder::BigEndian => a << 32 | b,
+        }
+    }
+
+    fn put_u16(self, out: &mut Vec<u8>, x: u16) {
+        match self {
+            ByteOrder::LittleEndian => out.extend_from_slice(&x.to_le_bytes()),
+            ByteOrder::BigEndian => out.extend_from_slice(&x.to_be_bytes()),
+        }
+    }
+
+    fn put_u32(self, out: &mut Vec<u8>, x: u32) {
+        match self {
+            ByteOrder::LittleEndian => out.extend_from_slice(&x.to_le_bytes()),
+            ByteOrder::BigEndian => out.extend_from_slice(&x.to_be_bytes()),
+        }
+    }
+
+    fn put_u64(self, out: &mut Vec<u8>, x: u64) {
+        match self {
+            ByteOrder::LittleEndian => out.extend_from_slice(&x.to_le_bytes()),
+            ByteOrder::BigEndian => out.extend_from_slice(&x.to_be_bytes()),
+        }
+    }
+}
+
+/// Image file directories, groups of tags
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ifd {
+    Primary,
+    Exif,
+    Gps,
+    Interop,
+}
+
+/// Every IFD, in the order they are written
+const IFDS: [Ifd; 4] = [Ifd::Primary, Ifd::Exif, Ifd::Gps, Ifd::Interop];
+
+impl Ifd {
+    /// The IFD and tag pointing to this IFD
+    fn pointer(self) -> Option<(Ifd, u16)> {
+        match self {
+            Ifd::Primary => None,
+            Ifd::Exif => Some((Ifd::Primary, tag::EXIF_IFD)),
+            Ifd::Gps => Some((Ifd::Primary, tag::GPS_IFD)),
+            Ifd::Interop => Some((Ifd::Exif, tag::INTEROP_IFD)),
+        }
+    }
+
+    /// The IFD that `tag` in `self` points to
+    fn child(self, tag: u16) -> Option<Ifd> {
+        IFDS.iter()
+            .copied()
+            .find(|ifd| ifd.pointer() == Some((self, tag)))
+    }
+}
+
+/// Value of a tag
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Byte(Vec<u8>),
+    Ascii(String),
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    Rational(Vec<(u32, u32)>),
+    SByte(Vec<i8>),
+    Undefined(Vec<u8>),
+    SShort(Vec<i16>),
+    SLong(Vec<i32>),
+    SRational(Vec<(i32, i32)>),
+    Float(Vec<f32>),
+    Double(Vec<f64>),
+}
+
+/// Size in bytes of each value of the given type
+fn type_size(kind: u16) -> Option<usize> {
+    match kind {
+        1 | 2 | 6 | 7 => Some(1),
+        3 | 8 => Some(2),
+        4 | 9 | 11 => Some(4),
+        5 | 10 | 12 => Some(8),
+        _ => None,
+    }
+}
+
+impl Value {
+    /// Get the first value of an unsigned integer tag
+    pub fn as_u32(&self) -> Option<u32> {
+        match self {
+            Value::Byte(x) => x.first().map(|x| u32::from(*x)),
+            Value::Short(x) => x.first().map(|x| u32::from(*x)),
+            Value::Long(x) => x.first().copied(),
+            _ => None,
+        }
+    }
+
+    /// Get the first value of a numeric tag
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Rational(x) => x
+                .first()
+                .filter(|(_, d)| *d != 0)
+                .map(|(n, d)| f64::from(*n) / f64::from(*d)),
+            Value::SRational(x) => x
+                .first()
+                .filter(|(_, d)| *d != 0)
+                .map(|(n, d)| f64::from(*n) / f64::from(*d)),
+            Value::SByte(x) => x.first().map(|x| f64::from(*x)),
+            Value::SShort(x) => x.first().map(|x| f64::from(*x)),
+            Value::SLong(x) => x.first().map(|x| f64::from(*x)),
+            Value::Float(x) => x.first().map(|x| f64::from(*x)),
+            Value::Double(x) => x.first().copied(),
+            _ => self.as_u32().map(f64::from),
+        }
+    }
+
+    /// Get the value of an ASCII tag
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Ascii(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn decode(kind: u16, b: &[u8], order: ByteOrder) -> Option<Value> {
+        let value = match kind {
+            1 => Value::Byte(b.to_vec()),
+            2 => Value::Ascii(
+                String::from_utf8_lossy(b)
+                    .trim_end_matches('\0')
+                    .to_string(),
+            ),
+            3 => Value::Short(b.chunks(2).map(|x| order.u16(x)).collect()),
+            4 => Value::Long(b.chunks(4).map(|x| order.u32(x)).collect()),
+            5 => Value::Rational(
+                b.chunks(8)
+                    .map(|x| (order.u32(x), order.u32(&x[4..])))
+                    .collect(),
+            ),
+            6 => Value::SByte(b.iter().map(|x| *x as i8).collect()),
+            7 => Value::Undefined(b.to_vec()),
+            8 => Value::SShort(b.chunks(2).map(|x| order.u16(x) as i16).collect()),
+            9 => Value::SLong(b.chunks(4).map(|x| order.u32(x) as i32).collect()),
+            10 => Value::SRational(
+                b.chunks(8)
+                    .map(|x| (order.u32(x) as i32, order.u32(&x[4..]) as i32))
+                    .collect(),
+            ),
+            11 => Value::Float(b.chunks(4).map(|x| f32::from_bits(order.u32(x))).collect()),
+            12 => Value::Double(b.chunks(8).map(|x| f64::from_bits(order.u64(x))).collect()),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Returns the type, count and encoded bytes
+    fn encode(&self, order: ByteOrder) -> (u16, u32, Vec<u8>) {
+        let mut out = Vec::new();
+        let (kind, count) = match self {
+            Value::Byte(x) => {
+                out.extend_from_slice(x);
+                (1, x.len())
+            }
+            Value::Ascii(s) => {
+                out.extend_from_slice(s.as_bytes());
+                out.push(0);
+                (2, out.len())
+            }
+            Value::Short(x) => {
+                x.iter().for_each(|x| order.put_u16(&mut out, *x));
+                (3, x.len())
+            }
+            Value::Long(x) => {
+                x.iter().for_each(|x| order.put_u32(&mut out, *x));
+                (4, x.len())
+            }
+            Value::Rational(x) => {
+                for (n, d) in x {
+                    order.put_u32(&mut out, *n);
+                    order.put_u32(&mut out, *d);
+                }
+                (5, x.len())
+            }
+            Value::SByte(x) => {
+                out.extend(x.iter().map(|x| *x as u8));
+                (6, x.len())
+            }
+            Value::Undefined(x) => {
+                out.extend_from_slice(x);
+                (7, x.len())
+            }
+            Value::SShort(x) => {
+                x.iter().for_each(|x| order.put_u16(&mut out, *x as u16));
+                (8, x.len())
+            }
+            Value::SLong(x) => {
+                x.iter().for_each(|x| order.put_u32(&mut out, *x as u32));
+                (9, x.len())
+            }
+            Value::SRational(x) => {
+                for (n, d) in x {
+                    order.put_u32(&mut out, *n as u32);
+                    order.put_u32(&mut out, *d as u32);
+                }
+                (10, x.len())
+            }
+            Value::Float(x) => {
+                x.iter().for_each(|x| order.put_u32(&mut out, x.to_bits()));
+                (11, x.len())
+            }
+            Value::Double(x) => {
+                x.iter().for_each(|x| order.put_u64(&mut out, x.to_bits()));
+                (12, x.len())
+            }
+        };
+        (kind, count as u32, out)
+    }
+}
+
+/// A tag and its value
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub ifd: Ifd,
+    pub tag: u16,
+    pub value: Value,
+}
+
+/// EXIF metadata
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exif {
+    order: ByteOrder,
+    entries: Vec<Entry>,
+    thumbnail: Option<Vec<u8>>,
+}
+
+impl Default for Exif {
+    fn default() -> Exif {
+        Exif::new()
+    }
+}
+
+/// Maximum number of IFDs read by `Exif::parse`
+const MAX_IFDS: usize = 16;
+
+/// Maximum number of entries read by `Exif::parse`
+const MAX_ENTRIES: usize = 1 << 16;
+
+fn invalid(msg: &str) -> Error {
+    Error::Message(format!("Invalid EXIF data: {}", msg))
+}
+
+/// Read the entries of the IFD at `offset`, entries with an unknown type or with values outside
+/// of `data` are skipped
+fn read_ifd(data: &[u8], offset: usize, order: ByteOrder) -> Result<Vec<(u16, Value)>, Error> {
+    let count = match data.get(offset..offset + 2) {
+        Some(b) => order.u16(b) as usize,
+        None => return Err(invalid("IFD is out of bounds")),
+    };
+
+    let mut entries = Vec::with_capacity(count);
+    for i in 0..count {
+        let start = offset + 2 + i * 12;
+        let entry = match data.get(start..start + 12) {
+            Some(entry) => entry,
+            None => return Err(invalid("truncated IFD")),
+        };
+
+        let kind = order.u16(&entry[2..]);
+        let len = match type_size(kind) {
+            Some(size) => (order.u32(&entry[4..]) as usize).saturating_mul(size),
+            None => continue,
+        };
+        let bytes = if len <= 4 {
+            &entry[8..8 + len]
+        } else {
+            let start = order.u32(&entry[8..]) as usize;
+            match data.get(start..start.saturating_add(len)) {
+                Some(bytes) => bytes,
+                None => continue,
+            }
+        };
+
+        if let Some(value) = Value::decode(kind, bytes, order) {
+            entries.push((order.u16(entry), value));
+        }
+    }
+    Ok(entries)
+}
+
+/// Size of an encoded IFD, including values that don't fit in the entries
+fn ifd_size(entries: &[(u16, Value)], order: ByteOrder) -> usize {
+    let values: usize = entries
+        .iter()
+        .map(|(_, value)| value.encode(order).2.len())
+        .filter(|len| *len > 4)
+        .map(|len| len + len % 2)
+        .sum();
+    6 + entries.len() * 12 + values
+}
+
+/// Encode the entries as a list of IFDs. `base` is the offset of the first IFD from the start of
+/// the TIFF header and `next` is the offset of the IFD following the primary IFD
+fn write_ifds(entries: &[Entry], order: ByteOrder, base: usize, next: u32) -> Vec<u8> {
+    let mut ifds: Vec<(Ifd, Vec<(u16, Value)>)> = IFDS
+        .iter()
+        .map(|ifd| {
+            let entries = entries
+                .iter()
+                .filter(|e| e.ifd == *ifd && ifd.child(e.tag).is_none())
+                .map(|e| (e.tag, e.value.clone()))
+                .collect();
+            (*ifd, entries)
+        })
+        .collect();
+
+    // Point to every IFD that isn't empty, in reverse so the Exif IFD isn't dropped when only
+    // the interoperability IFD has entries
+    for i in (1..ifds.len()).rev() {
+        if let (false, Some((parent, tag))) = (ifds[i].1.is_empty(), ifds[i].0.pointer()) {
+            let parent = ifds.iter_mut().find(|(ifd, _)| *ifd == parent).unwrap();
+            parent.1.push((tag, Value::Long(vec![0])));
+        }
+    }
+    ifds.retain(|(ifd, entries)| *ifd == Ifd::Primary || !entries.is_empty());
+    ifds.iter_mut()
+        .for_each(|(_, entries)| entries.sort_by_key(|(tag, _)| *tag));
+
+    let mut offsets = Vec::with_capacity(ifds.len());
+    let mut offset = base;
+    for (ifd, entries) in &ifds {
+        offsets.push((*ifd, offset));
+        offset += ifd_size(entries, order);
+    }
+    let offset_of = |ifd| offsets.iter().find(|(x, _)| *x == ifd).map(|(_, x)| *x);
+
+    let mut out = Vec::new();
+    for (ifd, entries) in &ifds {
+        let mut extra = Vec::new();
+        let values_start = base + out.len() + 6 + entries.len() * 12;
+        order.put_u16(&mut out, entries.len() as u16);
+        for (tag, value) in entries {
+            let (kind, count, bytes) = match ifd.child(*tag).and_then(offset_of) {
+                Some(offset) => Value::Long(vec![offset as u32]).encode(order),
+                None => value.encode(order),
+            };
+            order.put_u16(&mut out, *tag);
+            order.put_u16(&mut out, kind);
+            order.put_u32(&mut out, count);
+            if bytes.len() <= 4 {
+                out.extend_from_slice(&bytes);
+                out.extend_from_slice(&[0; 4][bytes.len()..]);
+            } else {
+                order.put_u32(&mut out, (values_start + extra.len()) as u32);
+                extra.extend_from_slice(&bytes);
+                if extra.len() % 2 == 1 {
+                    extra.push(0);
+                }
+            }
+        }
+        order.put_u32(&mut out, if *ifd == Ifd::Primary { next } else { 0 });
+        out.extend_from_slice(&extra);
+    }
+    out
+}
+
+impl Exif {
+    /// Create empty metadata, stored in little-endian byte order
+    pub fn new() -> Exif {
+        Exif {
+            order: ByteOrder::LittleEndian,
+            entries: Vec::new(),
+            thumbnail: None,
+        }
+    }
+
+    /// Byte order of the file the metadata was read from
+    pub fn byte_order(&self) -> ByteOrder {
+        self.order
+    }
+
+    /// Every tag, except for the pointers between IFDs
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Get the value of a tag
+    pub fn get(&self, ifd: Ifd, tag: u16) -> Option<&Value> {
+        self.entries
+            .iter()
+            .find(|e| e.ifd == ifd && e.tag == tag)
+            .map(|e| &e.value)
+    }
+
+    /// Set the value of a tag, replacing the existing value
+    pub fn set(&mut self, ifd: Ifd, tag: u16, value: Value) {
+        match self
+            .entries
+            .iter_mut()
+            .find(|e| e.ifd == ifd && e.tag == tag)
+        {
+            Some(entry) => entry.value = value,
+            None => self.entries.push(Entry { ifd, tag, value }),
+        }
+    }
+
+    /// Remove a tag, returning its value
+    pub fn remove(&mut self, ifd: Ifd, tag: u16) -> Option<Value> {
+        let index = self
+            .entries
+            .iter()
+            .position(|e| e.ifd == ifd && e.tag == tag)?;
+        Some(self.entries.remove(index).value)
+    }
+
+    /// Parse a TIFF structure, optionally prefixed with `Exif\0\0`
+    pub fn parse(data: &[u8]) -> Result<Exif, Error> {
+        let data = data.strip_prefix(EXIF_HEADER).unwrap_or(data);
+        let order = match data.get(..4) {
+            Some(b"II*\0") => ByteOrder::LittleEndian,
+            Some(b"MM\0*") => ByteOrder::BigEndian,
+            _ => return Err(invalid("invalid TIFF header")),
+        };
+        let first = match data.get(4..8) {
+            Some(b) => order.u32(b) as usize,
+            None => return Err(invalid("truncated TIFF header")),
+        };
+
+        let mut exif = Exif {
+            order,
+            entries: Vec::new(),
+            thumbnail: None,
+        };
+        // Pointers to IFDs that were already read are skipped, so that IFDs pointing at each other
+        // can't make the parser loop or read the same entries over and over
+        let mut visited = HashSet::new();
+        let mut pending = vec![(Ifd::Primary, first)];
+        while let Some((ifd, offset)) = pending.pop() {
+            if !visited.insert(offset) {
+                continue;
+            }
+            if visited.len() > MAX_IFDS {
+                return Err(invalid("too many IFDs"));
+            }
+
+            for (tag, value) in read_ifd(data, offset, order)? {
+                match ifd.child(tag) {
+                    Some(child) => {
+                        if let Some(offset) = value.as_u32() {
+                            pending.push((child, offset as usize));
+                        }
+                    }
+                    None => exif.entries.push(Entry { ifd, tag, value }),
+                }
+            }
+            if exif.entries.len() > MAX_ENTRIES {
+                return Err(invalid("too many entries"));
+            }
+        }
+
+        // The IFD following the primary IFD points to the thumbnail
+        let count = order.u16(&data[first..]) as usize;
+        let next = match data.get(first + 2 + count * 12..first + 6 + count * 12) {
+            Some(b) => order.u32(b) as usize,
+            None => 0,
+        };
+        if next != 0 {
+            let ifd = read_ifd(data, next, order).unwrap_or_default();
+            let get = |tag| {
+                ifd.iter()
+                    .find(|(t, _)| *t == tag)
+                    .and_then(|(_, v)| v.as_u32())
+            };
+            if let (Some(offset), Some(len)) =
+                (get(tag::THUMBNAIL_OFFSET), get(tag::THUMBNAIL_LENGTH))
+            {
+                let range = offset as usize..offset as usize + len as usize;
+                exif.thumbnail = data.get(range).map(<[u8]>::to_vec);
+            }
+        }
+        Ok(exif)
+    }
+
+    /// Encode the metadata as a TIFF structure, as stored in PNG `eXIf` chunks
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = match self.order {
+            ByteOrder::LittleEndian => b"II*\0".to_vec(),
+            ByteOrder::BigEndian => b"MM\0*".to_vec(),
+        };
+        self.order.put_u32(&mut out, 8);
+        let ifds = write_ifds(&self.entries, self.order, 8, 0);
+        let thumbnail = match &self.thumbnail {
+            Some(thumbnail) => thumbnail,
+            None => {
+                out.extend(ifds);
+                return out;
+            }
+        };
+
+        // The IFD of the thumbnail follows the other IFDs, followed by the thumbnail itself
+        let next = 8 + ifds.len();
+        out.extend(write_ifds(&self.entries, self.order, 8, next as u32));
+        let order = self.order;
+        order.put_u16(&mut out, 3);
+        for (tag, kind, value) in [
+            (0x0103, 3, 6),
+            (tag::THUMBNAIL_OFFSET, 4, next as u32 + 2 + 3 * 12 + 4),
+            (tag::THUMBNAIL_LENGTH, 4, thumbnail.len() as u32),
+        ] {
+            order.put_u16(&mut out, tag);
+            order.put_u16(&mut out, kind);
+            order.put_u32(&mut out, 1);
+            if kind == 3 {
+                order.put_u16(&mut out, value as u16);
+                order.put_u16(&mut out, 0);
+            } else {
+                order.put_u32(&mut out, value);
+            }
+        }
+        order.put_u32(&mut out, 0);
+        out.extend_from_slice(thumbnail);
+        out
+    }
+
+    /// Get the metadata of a JPEG, PNG or TIFF file, returns `None` when the file has no EXIF
+    /// data
+    pub fn decode(data: &[u8]) -> Result<Option<Exif>, Error> {
+        match container(data)? {
+            Container::Jpeg => match jpeg_app(data, 0xe1, EXIF_HEADER)? {
+                Some(tiff) => Exif::parse(tiff).map(Some),
+                None => Ok(None),
+            },
+            Container::Png => match crate::io::png::chunks(data)?
+                .iter()
+                .find(|(k, _)| *k == b"eXIf")
+            {
+                Some((_, chunk)) => Exif::parse(chunk).map(Some),
+                None => Ok(None),
+            },
+            Container::Tiff => Exif::parse(data).map(Some),
+        }
+    }
+
+    /// Read the metadata of a JPEG, PNG or TIFF file
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Option<Exif>, Error> {
+        with_path(path, |path| Exif::decode(&std::fs::read(path)?))
+    }
+
+    /// Replace the metadata of a JPEG, PNG or TIFF file, returning the new file
+    pub fn encode(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        match container(data)? {
+            Container::Jpeg => jpeg_replace_app(data, 0xe1, EXIF_HEADER, &self.to_bytes()),
+            Container::Png => {
+                png_replace_chunk(data, |k, _| k == b"eXIf", b"eXIf", &self.to_bytes())
+            }
+            Container::Tiff => self.encode_tiff(data),
+        }
+    }
+
+    /// Replace the metadata of a JPEG, PNG or TIFF file
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        with_path(path, |path| {
+            let data = self.encode(&std::fs::read(path)?)?;
+            std::fs::write(path, data)?;
+            Ok(())
+        })
+    }
+
+    fn encode_tiff(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let existing = Exif::parse(data)?;
+        let order = existing.order;
+        let first = order.u32(&data[4..]) as usize;
+        let count = order.u16(&data[first..]) as usize;
+        let next = match data.get(first + 2 + count * 12..first + 6 + count * 12) {
+            Some(b) => order.u32(b),
+            None => 0,
+        };
+
+        let is_layout = |e: &Entry| e.ifd == Ifd::Primary && LAYOUT_TAGS.contains(&e.tag);
+        let mut entries: Vec<Entry> = existing.entries.into_iter().filter(is_layout).collect();
+        entries.extend(self.entries.iter().filter(|e| !is_layout(e)).cloned());
+
+        let mut out = data.to_vec();
+        if out.len() % 2 == 1 {
+            out.push(0);
+        }
+        let base = out.len();
+        if base > u32::MAX as usize {
+            return Err(Error::Message(String::from("TIFF file is too large")));
+        }
+        out.extend(write_ifds(&entries, order, base, next));
+
+        let mut header = Vec::new();
+        order.put_u32(&mut header, base as u32);
+        out[4..8].copy_from_slice(&header);
+        Ok(out)
+    }
+
+    fn ascii(&self, ifd: Ifd, tag: u16) -> Option<&str> {
+        self.get(ifd, tag).and_then(Value::as_str)
+    }
+
+    fn number(&self, ifd: Ifd, tag: u16) -> Option<f64> {
+        self.get(ifd, tag).and_then(Value::as_f64)
+    }
+
+    /// Three rationals, like the degrees, minutes and seconds of a coordinate
+    fn triple(&self, ifd: Ifd, tag: u16) -> Option<[f64; 3]> {
+        match self.get(ifd, tag)? {
+            Value::Rational(x) if x.len() == 3 && x.iter().all(|(_, d)| *d != 0) => Some([
+                f64::from(x[0].0) / f64::from(x[0].1),
+                f64::from(x[1].0) / f64::from(x[1].1),
+                f64::from(x[2].0) / f64::from(x[2].1),
+            ]),
+            _ => None,
+        }
+    }
+
+    /// A coordinate in decimal degrees, negative when the reference is `negative`
+    fn coordinate(&self, tag: u16, reference: u16, negative: &str) -> Option<f64> {
+        let degrees = dms_to_degrees(self.triple(Ifd::Gps, tag)?);
+        match self.ascii(Ifd::Gps, reference) {
+            Some(r) if r.eq_ignore_ascii_case(negative) => Some(-degrees),
+            _ => Some(degrees),
+        }
+    }
+
+    fn set_coordinate(&mut self, tag: u16, reference: u16, degrees: f64, refs: [&str; 2]) {
+        let [d, m, s] = degrees_to_dms(degrees);
+        let value = vec![
+            (d as u32, 1),
+            (m as u32, 1),
+            ((s * 10000.0).round() as u32, 10000),
+        ];
+        let r = if degrees < 0.0 { refs[1] } else { refs[0] };
+        self.set(Ifd::Gps, reference, Value::Ascii(r.to_string()));
+        self.set(Ifd::Gps, tag, Value::Rational(value));
+        if self.get(Ifd::Gps, tag::GPS_VERSION_ID).is_none() {
+            self.set(Ifd::Gps, tag::GPS_VERSION_ID, Value::Byte(vec![2, 3, 0, 0]));
+        }
+    }
+
+    /// Latitude in decimal degrees, negative in the southern hemisphere
+    pub fn latitude(&self) -> Option<f64> {
+        self.coordinate(tag::GPS_LATITUDE, tag::GPS_LATITUDE_REF, "S")
+    }
+
+    /// Longitude in decimal degrees, negative west of Greenwich
+    pub fn longitude(&self) -> Option<f64> {
+        self.coordinate(tag::GPS_LONGITUDE, tag::GPS_LONGITUDE_REF, "W")
+    }
+
+    /// Set the latitude and longitude in decimal degrees
+    pub fn set_location(&mut self, latitude: f64, longitude: f64) {
+        let (lat, lon) = (tag::GPS_LATITUDE, tag::GPS_LONGITUDE);
+        self.set_coordinate(lat, tag::GPS_LATITUDE_REF, latitude, ["N", "S"]);
+        self.set_coordinate(lon, tag::GPS_LONGITUDE_REF, longitude, ["E", "W"]);
+    }
+
+    /// Altitude in meters, negative below sea level
+    pub fn altitude(&self) -> Option<f64> {
+        let altitude = self.number(Ifd::Gps, tag::GPS_ALTITUDE)?;
+        match self
+            .get(Ifd::Gps, tag::GPS_ALTITUDE_REF)
+            .and_then(Value::as_u32)
+        {
+            Some(1) => Some(-altitude),
+            _ => Some(altitude),
+        }
+    }
+
+    pub fn set_altitude(&mut self, meters: f64) {
+        let value = ((meters.abs() * 100.0).round() as u32, 100);
+        let below = Value::Byte(vec![(meters < 0.0) as u8]);
+        self.set(Ifd::Gps, tag::GPS_ALTITUDE_REF, below);
+        self.set(Ifd::Gps, tag::GPS_ALTITUDE, Value::Rational(vec![value]));
+    }
+
+    /// UTC date and time of the GPS fix formatted as `YYYY:MM:DD HH:MM:SS`
+    pub fn gps_timestamp(&self) -> Option<String> {
+        let date = self.ascii(Ifd::Gps, tag::GPS_DATESTAMP)?;
+        let [h, m, s] = self.triple(Ifd::Gps, tag::GPS_TIMESTAMP)?;
+        Some(format!("{} {:02}:{:02}:{:02}", date, h, m, s.floor()))
+    }
+
+    /// Set the UTC date and time of the GPS fix, formatted as `YYYY:MM:DD HH:MM:SS`
+    pub fn set_gps_timestamp(&mut self, datetime: &str) -> Result<(), Error> {
+        let error = || Error::Message(format!("Invalid GPS timestamp: {}", datetime));
+        let (date, time) = match datetime.split_once(' ') {
+            Some((date, time)) if date.len() == 10 && date.as_bytes()[4] == b':' => (date, time),
+            _ => return Err(error()),
+        };
+        let time = time
+            .split(':')
+            .map(|x| x.parse::<u32>().map_err(|_| error()))
+            .collect::<Result<Vec<u32>, Error>>()?;
+        if time.len() != 3 || time[0] > 23 || time[1] > 59 || time[2] > 60 {
+            return Err(error());
+        }
+
+        let time = time.iter().map(|x| (*x, 1)).collect();
+        self.set(Ifd::Gps, tag::GPS_DATESTAMP, Value::Ascii(date.to_string()));
+        self.set(Ifd::Gps, tag::GPS_TIMESTAMP, Value::Rational(time));
+        Ok(())
+    }
+
+    /// Remove every GPS tag, so the file no longer reveals where it was taken
+    pub fn strip_location(&mut self) {
+        self.entries.retain(|e| e.ifd != Ifd::Gps);
+    }
+
+    /// The embedded JPEG thumbnail
+    pub fn thumbnail(&self) -> Option<&[u8]> {
+        self.thumbnail.as_deref()
+    }
+
+    /// Set the embedded JPEG thumbnail, which is only written to JPEG and PNG files. The EXIF
+    /// data of JPEG files is limited to 64KB, so thumbnails should be small
+    pub fn set_thumbnail(&mut self, thumbnail: Option<Vec<u8>>) {
+        self.thumbnail = thumbnail;
+    }
+
+    /// Camera manufacturer
+    pub fn make(&self) -> Option<&str> {
+        self.ascii(Ifd::Primary, tag::MAKE)
+    }
+
+    /// Camera model
+    pub fn model(&self) -> Option<&str> {
+        self.ascii(Ifd::Primary, tag::MODEL)
+    }
+
+    /// Orientation of the image, from 1 to 8, 1 means the image is upright
+    pub fn orientation(&self) -> Option<u16> {
+        self.get(Ifd::Primary, tag::ORIENTATION)
+            .and_then(Value::as_u32)
+            .map(|x| x as u16)
+    }
+
+    pub fn set_orientation(&mut self, orientation: u16) {
+        self.set(
+            Ifd::Primary,
+            tag::ORIENTATION,
+            Value::Short(vec![orientation]),
+        );
+    }
+
+    /// Physical resolution of the image, the unit defaults to inches when it is missing
+    pub fn resolution(&self) -> Option<Resolution> {
+        let x = self.number(Ifd::Primary, tag::X_RESOLUTION)?;
+        let y = self.number(Ifd::Primary, tag::Y_RESOLUTION)?;
+        let unit = match self
+            .get(Ifd::Primary, tag::RESOLUTION_UNIT)
+            .and_then(Value::as_u32)
+        {
+            Some(1) => ResolutionUnit::None,
+            Some(3) => ResolutionUnit::Centimeter,
+            _ => ResolutionUnit::Inch,
+        };
+        if x > 0.0 && y > 0.0 {
+            Some(Resolution::new(x, y, unit))
+        } else {
+            None
+        }
+    }
+
+    pub fn set_resolution(&mut self, resolution: &Resolution) {
+        let rational = |x: f64| Value::Rational(vec![((x * 1000.0).round() as u32, 1000)]);
+        let unit = match resolution.unit {
+            ResolutionUnit::None => 1,
+            ResolutionUnit::Inch => 2,
+            ResolutionUnit::Centimeter => 3,
+        };
+        self.set(Ifd::Primary, tag::X_RESOLUTION, rational(resolution.x));
+        self.set(Ifd::Primary, tag::Y_RESOLUTION, rational(resolution.y));
+        self.set(Ifd::Primary, tag::RESOLUTION_UNIT, Value::Short(vec![unit]));
+    }
+
+    /// Date and time the image was taken formatted as `YYYY:MM:DD HH:MM:SS`, falls back to the
+    /// date and time the file was changed
+    pub fn datetime(&self) -> Option<&str> {
+        self.ascii(Ifd::Exif, tag::DATETIME_ORIGINAL)
+            .or_else(|| self.ascii(Ifd::Primary, tag::DATETIME))
+    }
+
+    /// Set both the original date and time and the date and time the file was changed
+    pub fn set_datetime(&mut self, datetime: &str) {
+        let value = Value::Ascii(datetime.to_string());
+        self.set(Ifd::Exif, tag::DATETIME_ORIGINAL, value.clone());
+        self.set(Ifd::Primary, tag::DATETIME, value);
+    }
+
+    /// Exposure time in seconds
+    pub fn exposure_time(&self) -> Option<f64> {
+        self.number(Ifd::Exif, tag::EXPOSURE_TIME)
+    }
+
+    /// Set the exposure time in seconds, times shorter than a second are stored as `1/x`
+    pub fn set_exposure_time(&mut self, seconds: f64) {
+        let value = if seconds > 0.0 && seconds < 1.0 {
+            (1, (1.0 / seconds).round() as u32)
+        } else {
+            ((seconds * 1000.0).round() as u32, 1000)
+        };
+        self.set(Ifd::Exif, tag::EXPOSURE_TIME, Value::Rational(vec![value]));
+    }
+
+    /// Aperture as an f-number
+    pub fn f_number(&self) -> Option<f64> {
+        self.number(Ifd::Exif, tag::F_NUMBER)
+    }
+
+    pub fn set_f_number(&mut self, f_number: f64) {
+        let value = ((f_number * 100.0).round() as u32, 100);
+        self.set(Ifd::Exif, tag::F_NUMBER, Value::Rational(vec![value]));
+    }
+
+    /// ISO speed
+    pub fn iso(&self) -> Option<u32> {
+        self.get(Ifd::Exif, tag::ISO).and_then(Value::as_u32)
+    }
+
+    pub fn set_iso(&mut self, iso: u32) {
+        let value = if iso <= u32::from(u16::MAX) {
+            Value::Short(vec![iso as u16])
+        } else {
+            Value::Long(vec![iso])
+        };
+        self.set(Ifd::Exif, tag::ISO, value);
+    }
+
+    /// Focal length in millimeters
+    pub fn focal_length(&self) -> Option<f64> {
+        self.number(Ifd::Exif, tag::FOCAL_LENGTH)
+    }
+
+    /// Lens manufacturer
+    pub fn lens_make(&self) -> Option<&str> {
+        self.ascii(Ifd::Exif, tag::LENS_MAKE)
+    }
+
+    /// Lens model
+    pub fn lens_model(&self) -> Option<&str> {
+        self.ascii(Ifd::Exif, tag::LENS_MODEL)
+    }
+
+    pub fn set_lens_model(&mut self, lens: &str) {
+        self.set(Ifd::Exif, tag::LENS_MODEL, Value::Ascii(lens.to_string()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ImageBuf, Rgb};
+
+    fn example() -> Exif {
+        let mut exif = Exif::new();
+        exif.set_orientation(6);
+        exif.set_datetime("2024:05:01 12:30:00");
+        exif.set_exposure_time(0.004);
+        exif.set_f_number(2.8);
+        exif.set_iso(400);
+        exif.set_lens_model("50mm f/1.8");
+        exif.set(Ifd::Gps, 0x0000, Value::Byte(vec![2, 3, 0, 0]));
+        exif.set(Ifd::Interop, 0x0001, Value::Ascii(String::from("R98")));
+        exif
+    }
+
+    #[test]
+    fn test_exif() {
+        let exif = example();
+        let parsed = Exif::parse(&exif.to_bytes()).unwrap();
+        assert_eq!(parsed.orientation(), Some(6));
+        assert_eq!(parsed.datetime(), Some("2024:05:01 12:30:00"));
+        assert_eq!(parsed.exposure_time(), Some(1.0 / 250.0));
+        assert_eq!(parsed.f_number(), Some(2.8));
+        assert_eq!(parsed.iso(), Some(400));
+        assert_eq!(parsed.lens_model(), Some("50mm f/1.8"));
+        assert_eq!(parsed.get(Ifd::Interop, 1).unwrap().as_str(), Some("R98"));
+        assert_eq!(parsed.entries().len(), exif.entries().len());
+
+        // Big-endian
+        let mut big = Clone::clone(&parsed);
+        big.order = ByteOrder::BigEndian;
+        let data = big.to_bytes();
+        assert!(data.starts_with(b"MM"));
+        assert_eq!(Exif::parse(&data).unwrap().lens_model(), Some("50mm f/1.8"));
+
+        // JPEG, writing twice replaces the existing segment
+        let jpeg = std::fs::read("test/test.jpg").unwrap();
+        let with_exif = exif.encode(&jpeg).unwrap();
+        let mut updated = Exif::decode(&with_exif).unwrap().unwrap();
+        updated.set_iso(800);
+        let with_exif = updated.encode(&with_exif).unwrap();
+        let (segments, _) = super::super::jpeg_segments(&with_exif).unwrap();
+        let exif_segments = segments
+            .iter()
+            .filter(|s| s.is_app(&with_exif, 0xe1, EXIF_HEADER));
+        assert_eq!(exif_segments.count(), 1);
+        assert_eq!(Exif::decode(&with_exif).unwrap().unwrap().iso(), Some(800));
+        let image: ImageBuf<u8, Rgb> = crate::io::decode(&with_exif).unwrap();
+        let original: ImageBuf<u8, Rgb> = crate::io::decode(&jpeg).unwrap();
+        assert_eq!(image, original);
+
+        // PNG
+        let rgb: ImageBuf<u8, Rgb> = ImageBuf::new(4, 4);
+        let data = crate::io::encode_png_u8(&rgb).unwrap();
+        assert!(Exif::decode(&data).unwrap().is_none());
+        let data = exif.encode(&data).unwrap();
+        assert_eq!(Exif::decode(&data).unwrap().unwrap().orientation(), Some(6));
+        let image: ImageBuf<u8, Rgb> = crate::io::decode(&data).unwrap();
+        assert_eq!(image, rgb);
+
+        // TIFF, the layout of the image data is kept
+        let mut tiff = Exif::new();
+        tiff.set(Ifd::Primary, 0x0111, Value::Long(vec![8, 16]));
+        tiff.set_orientation(1);
+        let data = exif.encode(&tiff.to_bytes()).unwrap();
+        let parsed = Exif::decode(&data).unwrap().unwrap();
+        assert_eq!(
+            parsed.get(Ifd::Primary, 0x0111),
+            Some(&Value::Long(vec![8, 16]))
+        );
+        assert_eq!(parsed.orientation(), Some(6));
+        assert_eq!(parsed.iso(), Some(400));
+
+        assert!(Exif::parse(b"II*\0\xff\0\0\0").is_err());
+    }
+
+    #[test]
+    fn test_ifd_loop() {
+        // A primary IFD at offset 8 with an orientation followed by many Exif, GPS and
+        // interoperability pointers back to itself
+        let order = ByteOrder::LittleEndian;
+        let mut data = b"II*\0".to_vec();
+        order.put_u32(&mut data, 8);
+        let count = 1000;
+        order.put_u16(&mut data, count);
+        for i in 0..count {
+            let tag = match i {
+                0 => tag::ORIENTATION,
+                _ => [tag::EXIF_IFD, tag::GPS_IFD, tag::INTEROP_IFD][i as usize % 3],
+            };
+            order.put_u16(&mut data, tag);
+            order.put_u16(&mut data, if i == 0 { 3 } else { 4 });
+            order.put_u32(&mut data, 1);
+            order.put_u32(&mut data, if i == 0 { 6 } else { 8 });
+        }
+        order.put_u32(&mut data, 0);
+
+        let parsed = Exif::parse(&data).unwrap();
+        assert_eq!(parsed.orientation(), Some(6));
+        assert_eq!(parsed.entries().len(), count as usize / 3 + 1);
+
+        // Pointers to many different IFDs are rejected
+        let mut data = b"II*\0".to_vec();
+        order.put_u32(&mut data, 8);
+        order.put_u16(&mut data, 2 * MAX_IFDS as u16);
+        for i in 0..2 * MAX_IFDS as u32 {
+            order.put_u16(&mut data, tag::EXIF_IFD);
+            order.put_u16(&mut data, 4);
+            order.put_u32(&mut data, 1);
+            order.put_u32(&mut data, 1024 + i * 2);
+        }
+        data.resize(2048, 0);
+        assert!(Exif::parse(&data).is_err());
+    }
+
+    #[test]
+    fn test_gps() {
+        let dms = degrees_to_dms(-33.8568);
+        assert_eq!((dms[0], dms[1]), (33.0, 51.0));
+        assert!((dms_to_degrees(dms) - 33.8568).abs() < 1e-9);
+
+        let mut exif = example();
+        exif.set_location(-33.8568, 151.2153);
+        exif.set_altitude(-12.5);
+        exif.set_gps_timestamp("2024:05:01 02:30:15").unwrap();
+        assert!(exif.set_gps_timestamp("2024:05:01 25:00:00").is_err());
+        assert!(exif.set_gps_timestamp("yesterday").is_err());
+
+        let parsed = Exif::parse(&exif.to_bytes()).unwrap();
+        assert!((parsed.latitude().unwrap() + 33.8568).abs() < 1e-6);
+        assert!((parsed.longitude().unwrap() - 151.2153).abs() < 1e-6);
+        assert_eq!(parsed.ascii(Ifd::Gps, tag::GPS_LATITUDE_REF), Some("S"));
+        assert_eq!(parsed.altitude(), Some(-12.5));
+        assert_eq!(
+            parsed.gps_timestamp().as_deref(),
+            Some("2024:05:01 02:30:15")
+        );
+
+        // Stripping removes the GPS IFD and the pointer to it
+        let mut stripped = parsed;
+        stripped.strip_location();
+        let data = stripped.to_bytes();
+        let parsed = Exif::parse(&data).unwrap();
+        assert_eq!(parsed.latitude(), None);
+        assert!(parsed.entries().iter().all(|e| e.ifd != Ifd::Gps));
+        assert_eq!(parsed.get(Ifd::Primary, tag::GPS_IFD), None);
+        assert_eq!(parsed.orientation(), Some(6));
+        assert!(Exif::decode(b"GIF89a").is_err());
+    }
+}
@@ -0,0 +1,223 @@
+//! Read and write embedded ICC color profiles in JPEG, PNG and TIFF files
+//!
+//! JPEG files split the profile into `APP2` segments, PNG files store it compressed in the `iCCP`
+//! chunk and TIFF files use the `InterColorProfile` tag. `io::read` attaches the profile to the
+//! returned `ImageBuf` and `io::write` embeds it again, see `ImageBuf::profile`.
+
+use std::path::Path;
+
+use crate::color::Color;
+use crate::error::{with_path, Error};
+use crate::io::png;
+
+use super::exif::{Exif, Ifd, Value};
+use super::{container, jpeg_replace_apps, jpeg_segments, png_replace_chunk, Container};
+
+/// Prefix of ICC data in JPEG `APP2` segments
+const JPEG_HEADER: &[u8] = b"ICC_PROFILE\0";
+
+/// Largest part of a profile that fits in a JPEG segment after the header and sequence number
+const JPEG_CHUNK: usize = u16::MAX as usize - 2 - JPEG_HEADER.len() - 2;
+
+/// Name of the profile in PNG files, which is required but not used by readers
+const PNG_NAME: &[u8] = b"ICC profile";
+
+/// TIFF tag containing the profile
+pub const TAG: u16 = 0x8773;
+
+/// Maximum size of a decompressed PNG profile, larger profiles are rejected so small compressed
+/// chunks can't use up all of the memory
+const MAX_PNG_PROFILE: usize = 64 << 20;
+
+fn invalid(msg: &str) -> Error {
+    Error::Message(format!("Invalid ICC profile: {}", msg))
+}
+
+fn decode_jpeg(data: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+    let (segments, _) = jpeg_segments(data)?;
+
+    // Each segment starts with its sequence number, counting from 1, and the number of segments
+    let mut parts = Vec::new();
+    for segment in segments
+        .iter()
+        .filter(|s| s.is_app(data, 0xe2, JPEG_HEADER))
+    {
+        match &data[segment.data.start + JPEG_HEADER.len()..segment.data.end] {
+            [index, count, part @ ..] => parts.push((*index, *count, part)),
+            _ => return Err(invalid("truncated segment")),
+        }
+    }
+    if parts.is_empty() {
+        return Ok(None);
+    }
+
+    parts.sort_by_key(|(index, _, _)| *index);
+    let count = parts[0].1 as usize;
+    let ordered = parts
+        .iter()
+        .enumerate()
+        .all(|(i, (index, n, _))| *index as usize == i + 1 && *n as usize == count);
+    if !ordered || parts.len() != count {
+        return Err(invalid("missing or duplicate segments"));
+    }
+
+    Ok(Some(
+        parts
+            .iter()
+            .flat_map(|(_, _, part)| *part)
+            .copied()
+            .collect(),
+    ))
+}
+
+fn decode_png(data: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+    let chunks = png::chunks(data)?;
+    let chunk = match chunks.iter().find(|(k, _)| *k == b"iCCP") {
+        Some((_, chunk)) => chunk,
+        None => return Ok(None),
+    };
+
+    // Profile name, null separator, compression method and the compressed profile
+    let name = match chunk.iter().position(|b| *b == 0) {
+        Some(name) => name,
+        None => return Err(invalid("missing profile name")),
+    };
+    match chunk.get(name + 1) {
+        Some(0) => png::inflate(&chunk[name + 2..], MAX_PNG_PROFILE).map(Some),
+        _ => Err(invalid("unknown compression method")),
+    }
+}
+
+/// Returns true when the color space of `profile` matches `C`: gray profiles for images with one
+/// color channel and RGB profiles for images with three
+pub fn is_compatible<C: Color>(profile: &[u8]) -> bool {
+    let colors = C::channels() - C::has_alpha() as usize;
+    match profile.get(16..20) {
+        Some(b"GRAY") => colors == 1,
+        Some(b"RGB ") => colors == 3,
+        _ => false,
+    }
+}
+
+/// Get the ICC profile of a JPEG, PNG or TIFF file, returns `None` when the file doesn't embed
+/// one
+pub fn decode(data: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+    match container(data)? {
+        Container::Jpeg => decode_jpeg(data),
+        Container::Png => decode_png(data),
+        Container::Tiff => match Exif::parse(data)?.get(Ifd::Primary, TAG) {
+            Some(Value::Undefined(profile)) | Some(Value::Byte(profile)) => {
+                Ok(Some(profile.clone()))
+            }
+            Some(_) => Err(invalid("unexpected TIFF tag type")),
+            None => Ok(None),
+        },
+    }
+}
+
+/// Read the ICC profile of a JPEG, PNG or TIFF file
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Option<Vec<u8>>, Error> {
+    with_path(path, |path| decode(&std::fs::read(path)?))
+}
+
+/// Replace the ICC profile of a JPEG, PNG or TIFF file, returning the new file. The profile is
+/// removed when `profile` is `None`
+pub fn encode(data: &[u8], profile: Option<&[u8]>) -> Result<Vec<u8>, Error> {
+    match container(data)? {
+        Container::Jpeg => {
+            let profile = profile.unwrap_or_default();
+            let count = profile.len().div_ceil(JPEG_CHUNK);
+            if count > u8::MAX as usize {
+                return Err(Error::Message(String::from(
+                    "ICC profile is too large for a JPEG file",
+                )));
+            }
+
+            let parts: Vec<Vec<u8>> = profile
+                .chunks(JPEG_CHUNK)
+                .enumerate()
+                .map(|(i, part)| [&[i as u8 + 1, count as u8], part].concat())
+                .collect();
+            let parts: Vec<&[u8]> = parts.iter().map(Vec::as_slice).collect();
+            jpeg_replace_apps(data, 0xe2, JPEG_HEADER, &parts)
+        }
+        Container::Png => {
+            // The sRGB chunk must not be used together with a profile
+            let is_match = |k: &[u8], _: &[u8]| k == b"iCCP" || k == b"sRGB";
+            match profile {
+                Some(profile) => {
+                    let chunk = [PNG_NAME, &[0, 0], &png::deflate(profile)?].concat();
+                    png_replace_chunk(data, is_match, b"iCCP", &chunk)
+                }
+                None => {
+                    let mut out = png::SIGNATURE.to_vec();
+                    for (k, c) in png::chunks(data)? {
+                        if !is_match(k, c) {
+                            png::write_chunk(&mut out, k, c)?;
+                        }
+                    }
+                    Ok(out)
+                }
+            }
+        }
+        Container::Tiff => {
+            let mut exif = Exif::parse(data)?;
+            match profile {
+                Some(profile) => exif.set(Ifd::Primary, TAG, Value::Undefined(profile.to_vec())),
+                None => {
+                    exif.remove(Ifd::Primary, TAG);
+                }
+            }
+            exif.encode(data)
+        }
+    }
+}
+
+/// Replace the ICC profile of a JPEG, PNG or TIFF file
+pub fn write<P: AsRef<Path>>(path: P, profile: Option<&[u8]>) -> Result<(), Error> {
+    with_path(path, |path| {
+        let data = encode(&std::fs::read(path)?, profile)?;
+        std::fs::write(path, data)?;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ImageBuf, Rgb};
+
+    #[test]
+    fn test_icc() {
+        // Large enough to be split into several JPEG segments
+        let profile: Vec<u8> = (0..150_000u32).map(|x| (x % 251) as u8).collect();
+
+        let jpeg = std::fs::read("test/test.jpg").unwrap();
+        assert_eq!(decode(&jpeg).unwrap(), None);
+        let data = encode(&jpeg, Some(&profile)).unwrap();
+        assert_eq!(decode(&data).unwrap(), Some(profile.clone()));
+        let data = encode(&data, Some(&profile[..100])).unwrap();
+        assert_eq!(decode(&data).unwrap().unwrap(), &profile[..100]);
+        let image: ImageBuf<u8, Rgb> = crate::io::decode(&data).unwrap();
+        let original: ImageBuf<u8, Rgb> = crate::io::decode(&jpeg).unwrap();
+        assert_eq!(image, original);
+        assert_eq!(encode(&data, None).unwrap(), jpeg);
+
+        let rgb: ImageBuf<u8, Rgb> = ImageBuf::new(4, 4);
+        let png = crate::io::encode_png_u8(&rgb).unwrap();
+        let data = encode(&png, Some(&profile)).unwrap();
+        assert_eq!(decode(&data).unwrap(), Some(profile.clone()));
+        assert_eq!(encode(&data, None).unwrap(), png);
+
+        let mut tiff = Exif::new();
+        tiff.set(Ifd::Primary, 0x0111, Value::Long(vec![8]));
+        let data = encode(&tiff.to_bytes(), Some(&profile[..1000])).unwrap();
+        assert_eq!(decode(&data).unwrap().unwrap(), &profile[..1000]);
+
+        // Missing segment
+        let mut parts = [&[1u8, 2][..], &[0xaa][..]].concat();
+        parts.splice(0..0, JPEG_HEADER.iter().copied());
+        let data = super::super::jpeg_replace_app(&jpeg, 0xe2, &[], &parts).unwrap();
+        assert!(decode(&data).is_err());
+    }
+}
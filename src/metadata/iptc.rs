@@ -0,0 +1,525 @@
+//! Read and write IPTC-IIM metadata in JPEG, PNG and TIFF files
+//!
+//! ```rust,no_run
+//! use image2::metadata::iptc::Iptc;
+//!
+//! let mut iptc = Iptc::read("photo.jpg")?.unwrap_or_default();
+//! println!("{:?} {:?}", iptc.caption(), iptc.keywords());
+//! iptc.set_credit("Agency");
+//! iptc.write("photo.jpg")?;
+//! # Ok::<(), image2::Error>(())
+//! ```
+//!
+//! IPTC data is stored in the Photoshop `APP13` segment of JPEG files, where other Photoshop
+//! resources are kept and the IPTC digest is removed when writing since it would no longer
+//! match. TIFF files use the `IPTC-NAA` tag and PNG files use an uncompressed
+//! `Raw profile type iptc` text chunk, the compressed chunks written by ImageMagick are not
+//! supported. Text is written as UTF-8, text in other character sets is read as Latin-1.
+
+use std::path::Path;
+
+use crate::error::{with_path, Error};
+
+use super::exif::{ByteOrder, Exif, Ifd, Value};
+use super::{container, jpeg_app, jpeg_replace_app, png_replace_chunk, Container};
+
+/// Prefix of Photoshop resources in JPEG `APP13` segments
+const PHOTOSHOP_HEADER: &[u8] = b"Photoshop 3.0\0";
+
+/// Photoshop resource containing IPTC data
+const IPTC_RESOURCE: u16 = 0x0404;
+
+/// Photoshop resource containing the MD5 digest of the IPTC data
+const DIGEST_RESOURCE: u16 = 0x0425;
+
+/// Keyword of PNG text chunks containing IPTC data
+const PNG_KEYWORD: &[u8] = b"Raw profile type iptc";
+
+/// TIFF tag containing IPTC data
+const IPTC_NAA: u16 = 33723;
+
+/// Escape sequence marking UTF-8 text in the coded character set dataset
+const UTF8: &[u8] = b"\x1b%G";
+
+/// Common datasets as `(record, dataset)`
+pub mod dataset {
+    pub const CODED_CHARACTER_SET: (u8, u8) = (1, 90);
+    pub const RECORD_VERSION: (u8, u8) = (2, 0);
+    pub const OBJECT_NAME: (u8, u8) = (2, 5);
+    pub const KEYWORDS: (u8, u8) = (2, 25);
+    pub const DATE_CREATED: (u8, u8) = (2, 55);
+    pub const BYLINE: (u8, u8) = (2, 80);
+    pub const CITY: (u8, u8) = (2, 90);
+    pub const COUNTRY: (u8, u8) = (2, 101);
+    pub const HEADLINE: (u8, u8) = (2, 105);
+    pub const CREDIT: (u8, u8) = (2, 110);
+    pub const SOURCE: (u8, u8) = (2, 115);
+    pub const COPYRIGHT_NOTICE: (u8, u8) = (2, 116);
+    pub const CAPTION: (u8, u8) = (2, 120);
+}
+
+/// A dataset and its value
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataSet {
+    pub record: u8,
+    pub dataset: u8,
+    pub data: Vec<u8>,
+}
+
+/// IPTC-IIM metadata
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Iptc {
+    datasets: Vec<DataSet>,
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::Message(format!("Invalid IPTC data: {}", msg))
+}
+
+/// Split Photoshop resources into `(id, resource)`, where `resource` includes the header
+fn photoshop_resources(data: &[u8]) -> Result<Vec<(u16, &[u8])>, Error> {
+    let mut resources = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        if data.get(pos..pos + 4) != Some(b"8BIM") {
+            return Err(invalid("invalid Photoshop resource"));
+        }
+
+        // The name is a Pascal string padded to an even length
+        let truncated = || invalid("truncated Photoshop resource");
+        let id = data.get(pos + 4..pos + 6).ok_or_else(truncated)?;
+        let name_len = *data.get(pos + 6).ok_or_else(truncated)? as usize;
+        let size_pos = pos + 6 + ((name_len + 2) & !1);
+        let size = data.get(size_pos..size_pos + 4).ok_or_else(truncated)?;
+        let size = u32::from_be_bytes([size[0], size[1], size[2], size[3]]) as usize;
+        let end = size_pos + 4 + size;
+        if end > data.len() {
+            return Err(truncated());
+        }
+
+        resources.push((u16::from_be_bytes([id[0], id[1]]), &data[pos..end]));
+        pos = (end + 1) & !1;
+    }
+    Ok(resources)
+}
+
+/// The data of a Photoshop resource
+fn resource_data(resource: &[u8]) -> &[u8] {
+    let name_len = resource[6] as usize;
+    &resource[6 + ((name_len + 2) & !1) + 4..]
+}
+
+/// Decode the hex encoded data of an ImageMagick raw profile
+fn raw_profile(text: &[u8]) -> Result<Vec<u8>, Error> {
+    // "\niptc\n   <length>\n<hex>"
+    let text = String::from_utf8_lossy(text);
+    let mut lines = text.trim_start().splitn(3, '\n');
+    let _name = lines.next();
+    let len: usize = match lines.next().map(|x| x.trim().parse()) {
+        Some(Ok(len)) => len,
+        _ => return Err(invalid("invalid raw profile")),
+    };
+
+    let hex: Vec<u8> = lines
+        .next()
+        .unwrap_or_default()
+        .bytes()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+    let data: Option<Vec<u8>> = hex
+        .chunks(2)
+        .map(|x| u8::from_str_radix(std::str::from_utf8(x).ok()?, 16).ok())
+        .collect();
+    match data {
+        Some(data) if data.len() == len => Ok(data),
+        _ => Err(invalid("invalid raw profile")),
+    }
+}
+
+impl Iptc {
+    /// Create empty metadata
+    pub fn new() -> Iptc {
+        Iptc::default()
+    }
+
+    /// Every dataset, in the order they are stored
+    pub fn datasets(&self) -> &[DataSet] {
+        &self.datasets
+    }
+
+    /// Get the value of the first matching dataset
+    pub fn get(&self, (record, dataset): (u8, u8)) -> Option<&[u8]> {
+        self.get_all((record, dataset)).next()
+    }
+
+    /// Get the values of a repeatable dataset
+    pub fn get_all(&self, (record, dataset): (u8, u8)) -> impl Iterator<Item = &[u8]> {
+        self.datasets
+            .iter()
+            .filter(move |d| d.record == record && d.dataset == dataset)
+            .map(|d| d.data.as_slice())
+    }
+
+    /// Replace every matching dataset with a single value
+    pub fn set(&mut self, id: (u8, u8), data: &[u8]) {
+        self.set_all(id, &[data]);
+    }
+
+    /// Replace every matching dataset with the given values, the new datasets are stored where
+    /// the first existing dataset was
+    pub fn set_all(&mut self, (record, dataset): (u8, u8), values: &[&[u8]]) {
+        let is_match = |d: &DataSet| d.record == record && d.dataset == dataset;
+        let index = match self.datasets.iter().position(is_match) {
+            Some(index) => index,
+            // Keep datasets sorted by record
+            None => self
+                .datasets
+                .iter()
+                .position(|d| (d.record, d.dataset) > (record, dataset))
+                .unwrap_or(self.datasets.len()),
+        };
+        self.datasets.retain(|d| !is_match(d));
+        let values = values.iter().map(|data| DataSet {
+            record,
+            dataset,
+            data: data.to_vec(),
+        });
+        let index = index.min(self.datasets.len());
+        self.datasets.splice(index..index, values);
+    }
+
+    /// Remove every matching dataset
+    pub fn remove(&mut self, (record, dataset): (u8, u8)) {
+        self.datasets
+            .retain(|d| d.record != record || d.dataset != dataset);
+    }
+
+    /// Parse IPTC-IIM datasets
+    pub fn parse(data: &[u8]) -> Result<Iptc, Error> {
+        let mut datasets = Vec::new();
+        let mut pos = 0;
+        // Some writers pad the data with zeros
+        while pos < data.len() && data[pos] != 0 {
+            let header = match data.get(pos..pos + 5) {
+                Some(header) if header[0] == 0x1c => header,
+                _ => return Err(invalid("invalid tag marker")),
+            };
+            pos += 5;
+
+            let mut len = u16::from_be_bytes([header[3], header[4]]) as usize;
+            if len & 0x8000 != 0 {
+                // Extended datasets store the number of bytes of the length
+                let n = len & 0x7fff;
+                let bytes = data
+                    .get(pos..pos + n)
+                    .filter(|_| n <= 4)
+                    .ok_or_else(|| invalid("invalid extended dataset"))?;
+                len = bytes.iter().fold(0, |len, b| len << 8 | *b as usize);
+                pos += n;
+            }
+
+            let value = data
+                .get(pos..pos + len)
+                .ok_or_else(|| invalid("truncated dataset"))?;
+            datasets.push(DataSet {
+                record: header[1],
+                dataset: header[2],
+                data: value.to_vec(),
+            });
+            pos += len;
+        }
+        Ok(Iptc { datasets })
+    }
+
+    /// Encode the datasets
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        for d in &self.datasets {
+            out.extend_from_slice(&[0x1c, d.record, d.dataset]);
+            if d.data.len() < 0x8000 {
+                out.extend_from_slice(&(d.data.len() as u16).to_be_bytes());
+            } else {
+                out.extend_from_slice(&[0x80, 4]);
+                out.extend_from_slice(&(d.data.len() as u32).to_be_bytes());
+            }
+            out.extend_from_slice(&d.data);
+        }
+        out
+    }
+
+    /// Get the metadata of a JPEG, PNG or TIFF file, returns `None` when the file has no IPTC
+    /// data
+    pub fn decode(data: &[u8]) -> Result<Option<Iptc>, Error> {
+        match container(data)? {
+            Container::Jpeg => {
+                let resources = match jpeg_app(data, 0xed, PHOTOSHOP_HEADER)? {
+                    Some(resources) => photoshop_resources(resources)?,
+                    None => return Ok(None),
+                };
+                match resources.iter().find(|(id, _)| *id == IPTC_RESOURCE) {
+                    Some((_, resource)) => Iptc::parse(resource_data(resource)).map(Some),
+                    None => Ok(None),
+                }
+            }
+            Container::Png => {
+                let chunks = crate::io::png::chunks(data)?;
+                let mut profiles = chunks.iter().filter(|(_, c)| is_iptc_chunk(c));
+                match profiles.next() {
+                    Some((kind, _)) if *kind == b"zTXt" => Err(Error::Message(String::from(
+                        "Compressed IPTC profiles are not supported",
+                    ))),
+                    Some((_, chunk)) => {
+                        Iptc::parse(&raw_profile(&chunk[PNG_KEYWORD.len() + 1..])?).map(Some)
+                    }
+                    None => Ok(None),
+                }
+            }
+            Container::Tiff => {
+                let exif = Exif::parse(data)?;
+                let bytes = match exif.get(Ifd::Primary, IPTC_NAA) {
+                    Some(Value::Byte(x)) | Some(Value::Undefined(x)) => x.clone(),
+                    // Photoshop stores the data as longs in the byte order of the file
+                    Some(Value::Long(x)) => x
+                        .iter()
+                        .flat_map(|x| match exif.byte_order() {
+                            ByteOrder::LittleEndian => x.to_le_bytes(),
+                            ByteOrder::BigEndian => x.to_be_bytes(),
+                        })
+                        .collect(),
+                    _ => return Ok(None),
+                };
+                Iptc::parse(&bytes).map(Some)
+            }
+        }
+    }
+
+    /// Read the metadata of a JPEG, PNG or TIFF file
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Option<Iptc>, Error> {
+        with_path(path, |path| Iptc::decode(&std::fs::read(path)?))
+    }
+
+    /// Replace the IPTC data of a JPEG, PNG or TIFF file, returning the new file
+    pub fn encode(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let iptc = self.to_bytes();
+        match container(data)? {
+            Container::Jpeg => {
+                let mut out = Vec::new();
+                if let Some(existing) = jpeg_app(data, 0xed, PHOTOSHOP_HEADER)? {
+                    for (id, resource) in photoshop_resources(existing)? {
+                        if id != IPTC_RESOURCE && id != DIGEST_RESOURCE {
+                            out.extend_from_slice(resource);
+                            if resource.len() % 2 == 1 {
+                                out.push(0);
+                            }
+                        }
+                    }
+                }
+
+                out.extend_from_slice(b"8BIM");
+                out.extend_from_slice(&IPTC_RESOURCE.to_be_bytes());
+                out.extend_from_slice(&[0, 0]);
+                out.extend_from_slice(&(iptc.len() as u32).to_be_bytes());
+                out.extend_from_slice(&iptc);
+                if iptc.len() % 2 == 1 {
+                    out.push(0);
+                }
+                jpeg_replace_app(data, 0xed, PHOTOSHOP_HEADER, &out)
+            }
+            Container::Png => {
+                let mut chunk = PNG_KEYWORD.to_vec();
+                chunk.extend_from_slice(format!("\0\niptc\n{:8}\n", iptc.len()).as_bytes());
+                for line in iptc.chunks(36) {
+                    line.iter()
+                        .for_each(|b| chunk.extend_from_slice(format!("{:02x}", b).as_bytes()));
+                    chunk.push(b'\n');
+                }
+                png_replace_chunk(data, |_, c| is_iptc_chunk(c), b"tEXt", &chunk)
+            }
+            Container::Tiff => {
+                let mut exif = Exif::parse(data)?;
+                exif.set(Ifd::Primary, IPTC_NAA, Value::Undefined(iptc));
+                exif.encode(data)
+            }
+        }
+    }
+
+    /// Replace the IPTC data of a JPEG, PNG or TIFF file
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        with_path(path, |path| {
+            let data = self.encode(&std::fs::read(path)?)?;
+            std::fs::write(path, data)?;
+            Ok(())
+        })
+    }
+
+    fn decode_text(&self, data: &[u8]) -> String {
+        match std::str::from_utf8(data) {
+            Ok(text) => text.to_string(),
+            Err(_) if self.get(dataset::CODED_CHARACTER_SET) == Some(UTF8) => {
+                String::from_utf8_lossy(data).into_owned()
+            }
+            // Latin-1
+            Err(_) => data.iter().map(|b| *b as char).collect(),
+        }
+    }
+
+    /// Get a text dataset
+    pub fn text(&self, id: (u8, u8)) -> Option<String> {
+        self.get(id).map(|data| self.decode_text(data))
+    }
+
+    /// Get a repeatable text dataset
+    pub fn texts(&self, id: (u8, u8)) -> Vec<String> {
+        self.get_all(id)
+            .map(|data| self.decode_text(data))
+            .collect()
+    }
+
+    /// Set a text dataset, the text is stored as UTF-8
+    pub fn set_text(&mut self, id: (u8, u8), text: &str) {
+        self.set_texts(id, &[text]);
+    }
+
+    /// Set a repeatable text dataset, the text is stored as UTF-8
+    pub fn set_texts(&mut self, id: (u8, u8), texts: &[&str]) {
+        // Existing text stays readable since it is only marked as UTF-8 when it is valid UTF-8
+        let utf8 = self.datasets.iter().all(|d| {
+            (d.record, d.dataset) == dataset::CODED_CHARACTER_SET
+                || d.record != 2
+                || std::str::from_utf8(&d.data).is_ok()
+        });
+        if utf8 {
+            self.set(dataset::CODED_CHARACTER_SET, UTF8);
+        }
+        if self.get(dataset::RECORD_VERSION).is_none() {
+            self.set(dataset::RECORD_VERSION, &4u16.to_be_bytes());
+        }
+
+        let values: Vec<&[u8]> = texts.iter().map(|x| x.as_bytes()).collect();
+        self.set_all(id, &values);
+    }
+
+    /// Caption or abstract
+    pub fn caption(&self) -> Option<String> {
+        self.text(dataset::CAPTION)
+    }
+
+    pub fn set_caption(&mut self, caption: &str) {
+        self.set_text(dataset::CAPTION, caption);
+    }
+
+    pub fn keywords(&self) -> Vec<String> {
+        self.texts(dataset::KEYWORDS)
+    }
+
+    pub fn set_keywords(&mut self, keywords: &[&str]) {
+        self.set_texts(dataset::KEYWORDS, keywords);
+    }
+
+    pub fn headline(&self) -> Option<String> {
+        self.text(dataset::HEADLINE)
+    }
+
+    pub fn set_headline(&mut self, headline: &str) {
+        self.set_text(dataset::HEADLINE, headline);
+    }
+
+    /// Names of the creators
+    pub fn bylines(&self) -> Vec<String> {
+        self.texts(dataset::BYLINE)
+    }
+
+    pub fn set_bylines(&mut self, bylines: &[&str]) {
+        self.set_texts(dataset::BYLINE, bylines);
+    }
+
+    /// Provider of the image
+    pub fn credit(&self) -> Option<String> {
+        self.text(dataset::CREDIT)
+    }
+
+    pub fn set_credit(&mut self, credit: &str) {
+        self.set_text(dataset::CREDIT, credit);
+    }
+
+    pub fn copyright(&self) -> Option<String> {
+        self.text(dataset::COPYRIGHT_NOTICE)
+    }
+
+    pub fn set_copyright(&mut self, copyright: &str) {
+        self.set_text(dataset::COPYRIGHT_NOTICE, copyright);
+    }
+}
+
+fn is_iptc_chunk(chunk: &[u8]) -> bool {
+    chunk.starts_with(PNG_KEYWORD) && chunk.get(PNG_KEYWORD.len()) == Some(&0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ImageBuf, Rgb};
+
+    #[test]
+    fn test_iptc() {
+        let mut iptc = Iptc::new();
+        iptc.set_caption("Ein Straßenfest");
+        iptc.set_keywords(&["festival", "street"]);
+        iptc.set_credit("Agency");
+        iptc.set_copyright("(c) 2024 Agency");
+        iptc.set(dataset::OBJECT_NAME, &vec![b'x'; 40000]);
+        assert_eq!(iptc.get(dataset::CODED_CHARACTER_SET), Some(UTF8));
+        assert_eq!(iptc.datasets()[0].record, 1);
+
+        let parsed = Iptc::parse(&iptc.to_bytes()).unwrap();
+        assert_eq!(parsed, iptc);
+        assert_eq!(parsed.caption().as_deref(), Some("Ein Straßenfest"));
+        assert_eq!(parsed.keywords(), vec!["festival", "street"]);
+        assert_eq!(parsed.get(dataset::OBJECT_NAME).unwrap().len(), 40000);
+
+        // Latin-1
+        let latin1 = Iptc::parse(b"\x1c\x02\x78\x00\x05Stra\xdf").unwrap();
+        assert_eq!(latin1.caption().as_deref(), Some("Straß"));
+        assert!(Iptc::parse(b"\x1c\x02\x78\x00\x09Stra\xdf").is_err());
+
+        // JPEG, other Photoshop resources are kept and the digest is removed
+        iptc.remove(dataset::OBJECT_NAME);
+        let mut resources = b"8BIM\x04\x0c\x00\x00\x00\x00\x00\x03abc\x00".to_vec();
+        resources.extend_from_slice(b"8BIM\x04\x25\x00\x00\x00\x00\x00\x02md");
+        let jpeg = std::fs::read("test/test.jpg").unwrap();
+        let jpeg = jpeg_replace_app(&jpeg, 0xed, PHOTOSHOP_HEADER, &resources).unwrap();
+        let data = iptc.encode(&jpeg).unwrap();
+        assert_eq!(Iptc::decode(&data).unwrap(), Some(Clone::clone(&iptc)));
+        let segment = jpeg_app(&data, 0xed, PHOTOSHOP_HEADER).unwrap().unwrap();
+        let ids: Vec<u16> = photoshop_resources(segment)
+            .unwrap()
+            .iter()
+            .map(|(id, _)| *id)
+            .collect();
+        assert_eq!(ids, vec![0x040c, IPTC_RESOURCE]);
+        assert!(crate::metadata::exif::Exif::decode(&data)
+            .unwrap()
+            .is_some());
+
+        // PNG
+        let rgb: ImageBuf<u8, Rgb> = ImageBuf::new(4, 4);
+        let png = crate::io::encode_png_u8(&rgb).unwrap();
+        assert_eq!(Iptc::decode(&png).unwrap(), None);
+        let data = iptc.encode(&png).unwrap();
+        assert_eq!(Iptc::decode(&data).unwrap(), Some(Clone::clone(&iptc)));
+        let image: ImageBuf<u8, Rgb> = crate::io::decode(&data).unwrap();
+        assert_eq!(image, rgb);
+
+        // TIFF
+        let data = iptc.encode(&Exif::new().to_bytes()).unwrap();
+        assert_eq!(Iptc::decode(&data).unwrap(), Some(Clone::clone(&iptc)));
+
+        // Metadata is kept when an image is saved again
+        let metadata = super::super::Metadata::decode(&data).unwrap();
+        let data = metadata.encode(&png).unwrap();
+        assert_eq!(
+            Iptc::decode(&data).unwrap().unwrap().credit().as_deref(),
+            Some("Agency")
+        );
+    }
+}
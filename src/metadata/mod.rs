@@ -0,0 +1,375 @@
+//! Image metadata
+//!
+//! Encoders in `io` only write pixel data, `Metadata` can be used to keep the EXIF, XMP and IPTC
+//! metadata of a file when it is saved again:
+//!
+//! ```rust,no_run
+//! use image2::metadata::Metadata;
+//! use image2::{io, ImageBuf, Rgb};
+//!
+//! let metadata = Metadata::read("photo.jpg")?;
+//! let image: ImageBuf<u8, Rgb> = io::read("photo.jpg")?;
+//! io::write("edited.jpg", &image)?;
+//! metadata.write("edited.jpg")?;
+//! # Ok::<(), image2::Error>(())
+//! ```
+
+pub mod exif;
+pub mod icc;
+pub mod iptc;
+pub mod resolution;
+pub mod xmp;
+
+use std::io::Read;
+use std::path::Path;
+
+use crate::color::Color;
+use crate::error::{with_path, Error};
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::io::png;
+use crate::transform;
+use crate::ty::Type;
+
+use self::exif::Exif;
+use self::iptc::Iptc;
+use self::xmp::Xmp;
+
+/// Prefix of EXIF data in JPEG `APP1` segments
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+
+/// EXIF, XMP and IPTC metadata of a file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    pub exif: Option<Exif>,
+    pub xmp: Option<Xmp>,
+    pub iptc: Option<Iptc>,
+}
+
+impl Metadata {
+    /// Get the metadata of a JPEG, PNG or TIFF file
+    pub fn decode(data: &[u8]) -> Result<Metadata, Error> {
+        Ok(Metadata {
+            exif: Exif::decode(data)?,
+            xmp: Xmp::decode(data)?,
+            iptc: Iptc::decode(data)?,
+        })
+    }
+
+    /// Read the metadata of a JPEG, PNG or TIFF file
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Metadata, Error> {
+        with_path(path, |path| Metadata::decode(&std::fs::read(path)?))
+    }
+
+    /// Remove the GPS location from the EXIF and XMP metadata, for example before sharing a
+    /// photo. Write the metadata to replace the location in a file
+    pub fn strip_location(&mut self) {
+        if let Some(exif) = &mut self.exif {
+            exif.strip_location();
+        }
+        if let Some(xmp) = &mut self.xmp {
+            xmp.strip_location();
+        }
+    }
+
+    /// Add the metadata to a JPEG, PNG or TIFF file, returning the new file. Metadata that is
+    /// `None` is left unchanged
+    pub fn encode(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut data = match &self.exif {
+            Some(exif) => exif.encode(data)?,
+            None => data.to_vec(),
+        };
+        if let Some(xmp) = &self.xmp {
+            data = xmp.encode(&data)?;
+        }
+        if let Some(iptc) = &self.iptc {
+            data = iptc.encode(&data)?;
+        }
+        Ok(data)
+    }
+
+    /// Add the metadata to a JPEG, PNG or TIFF file
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        with_path(path, |path| {
+            let data = self.encode(&std::fs::read(path)?)?;
+            std::fs::write(path, data)?;
+            Ok(())
+        })
+    }
+}
+
+/// Largest width and height of thumbnails created by `write_thumbnail`
+pub const THUMBNAIL_SIZE: usize = 160;
+
+/// Number of bytes read from the start of JPEG files by `thumbnail`, the EXIF segment is almost
+/// always found there. The whole file is read otherwise
+const THUMBNAIL_HEADER: u64 = 256 * 1024;
+
+/// Get the JPEG thumbnail embedded in the EXIF data of a JPEG, PNG or TIFF file
+pub fn thumbnail_data(data: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+    let exif = Exif::decode(data)?;
+    Ok(exif.and_then(|exif| exif.thumbnail().map(<[u8]>::to_vec)))
+}
+
+/// Read and decode the thumbnail embedded in the EXIF data of a JPEG, PNG or TIFF file, which is
+/// much faster than decoding the image itself. Returns `None` when there is no thumbnail
+pub fn thumbnail<P: AsRef<Path>, T: Type, C: Color>(
+    path: P,
+) -> Result<Option<ImageBuf<T, C>>, Error> {
+    with_path(path, |path| {
+        let mut file = std::fs::File::open(path)?;
+        let mut data = Vec::new();
+        (&mut file).take(THUMBNAIL_HEADER).read_to_end(&mut data)?;
+
+        let thumbnail = match thumbnail_data(&data) {
+            Ok(thumbnail) if data.starts_with(&[0xff, 0xd8]) => thumbnail,
+            _ => {
+                file.read_to_end(&mut data)?;
+                thumbnail_data(&data)?
+            }
+        };
+        match thumbnail {
+            Some(thumbnail) => Ok(Some(crate::io::decode(thumbnail)?)),
+            None => Ok(None),
+        }
+    })
+}
+
+/// Embed a thumbnail of `image` in the EXIF data of a JPEG or PNG file, returning the new file.
+/// The thumbnail is scaled to fit in `THUMBNAIL_SIZE` pixels
+pub fn encode_thumbnail<T: Type, C: Color, I: Image<T, C>>(
+    data: &[u8],
+    image: &I,
+) -> Result<Vec<u8>, Error> {
+    if let Container::Tiff = container(data)? {
+        return Err(Error::Message(String::from(
+            "Thumbnails can't be added to TIFF files",
+        )));
+    }
+
+    let (width, height) = (image.width().max(1), image.height().max(1));
+    let scale = (THUMBNAIL_SIZE as f64 / width.max(height) as f64).min(1.0);
+    let w = ((width as f64 * scale).round() as usize).max(1);
+    let h = ((height as f64 * scale).round() as usize).max(1);
+
+    let mut small: ImageBuf<T, C> = ImageBuf::new(w, h);
+    transform::resize(&mut small, image, w, h);
+    let mut tmp: ImageBuf<u8, C> = ImageBuf::new(w, h);
+    small.convert_type(&mut tmp);
+
+    let mut exif = Exif::decode(data)?.unwrap_or_default();
+    exif.set_thumbnail(Some(crate::io::encode_jpg_u8(&tmp, 80)?));
+    exif.encode(data)
+}
+
+/// Embed a thumbnail of `image` in the EXIF data of a JPEG or PNG file, usually the image that
+/// was just written to `path`
+pub fn write_thumbnail<P: AsRef<Path>, T: Type, C: Color, I: Image<T, C>>(
+    path: P,
+    image: &I,
+) -> Result<(), Error> {
+    with_path(path, |path| {
+        let data = encode_thumbnail(&std::fs::read(path)?, image)?;
+        std::fs::write(path, data)?;
+        Ok(())
+    })
+}
+
+enum Container {
+    Jpeg,
+    Png,
+    Tiff,
+}
+
+fn container(data: &[u8]) -> Result<Container, Error> {
+    if data.starts_with(&[0xff, 0xd8]) {
+        Ok(Container::Jpeg)
+    } else if data.starts_with(png::SIGNATURE) {
+        Ok(Container::Png)
+    } else if data.starts_with(b"II*\0") || data.starts_with(b"MM\0*") {
+        Ok(Container::Tiff)
+    } else {
+        Err(Error::UnsupportedFormat(String::from(
+            "expected JPEG, PNG or TIFF",
+        )))
+    }
+}
+
+/// A JPEG segment, `start` is the offset of the marker and `data` is the range of the payload
+struct Segment {
+    marker: u8,
+    start: usize,
+    data: std::ops::Range<usize>,
+}
+
+impl Segment {
+    /// Returns true for segments with the given marker starting with `header`
+    fn is_app(&self, data: &[u8], marker: u8, header: &[u8]) -> bool {
+        self.marker == marker && data[self.data.clone()].starts_with(header)
+    }
+}
+
+/// Split the header of a JPEG file into segments, also returns the offset of the image data
+fn jpeg_segments(data: &[u8]) -> Result<(Vec<Segment>, usize), Error> {
+    let mut segments = Vec::new();
+    let mut pos = 2;
+    loop {
+        let start = pos;
+        while data.get(pos) == Some(&0xff) {
+            pos += 1;
+        }
+
+        let marker = match data.get(pos) {
+            Some(marker) if pos > start => *marker,
+            _ => return Err(Error::Message(String::from("Invalid JPEG marker"))),
+        };
+        pos += 1;
+
+        let len = match marker {
+            // Start of scan or end of image
+            0xda | 0xd9 => return Ok((segments, start)),
+            // Markers without a payload
+            0x01 | 0xd0..=0xd7 => 2,
+            _ => match data.get(pos..pos + 2) {
+                Some(b) => u16::from_be_bytes([b[0], b[1]]) as usize,
+                None => return Err(Error::Message(String::from("Truncated JPEG segment"))),
+            },
+        };
+        if len < 2 || pos + len > data.len() {
+            return Err(Error::Message(String::from("Truncated JPEG segment")));
+        }
+
+        segments.push(Segment {
+            marker,
+            start,
+            data: pos + 2..pos + len,
+        });
+        pos += len;
+    }
+}
+
+/// Get the payload of the first segment with the given marker starting with `header`, without
+/// the header
+fn jpeg_app<'a>(data: &'a [u8], marker: u8, header: &[u8]) -> Result<Option<&'a [u8]>, Error> {
+    let (segments, _) = jpeg_segments(data)?;
+    Ok(segments
+        .iter()
+        .find(|s| s.is_app(data, marker, header))
+        .map(|s| &data[s.data.start + header.len()..s.data.end]))
+}
+
+/// Replace the segments with the given `APPn` marker starting with `header`. The new segment is
+/// placed after the segments with lower `APPn` markers and after the Exif segment, which readers
+/// expect to come first. JFIF `APP0` segments are always placed first
+fn jpeg_replace_app(
+    data: &[u8],
+    marker: u8,
+    header: &[u8],
+    payload: &[u8],
+) -> Result<Vec<u8>, Error> {
+    jpeg_replace_apps(data, marker, header, &[payload])
+}
+
+/// Replace the segments with the given `APPn` marker starting with `header` by one segment for
+/// each payload, see `jpeg_replace_app`. No segments are added when `payloads` is empty
+fn jpeg_replace_apps(
+    data: &[u8],
+    marker: u8,
+    header: &[u8],
+    payloads: &[&[u8]],
+) -> Result<Vec<u8>, Error> {
+    let (segments, image) = jpeg_segments(data)?;
+
+    let mut app = Vec::new();
+    for payload in payloads {
+        let len = 2 + header.len() + payload.len();
+        if len > u16::MAX as usize {
+            return Err(Error::Message(String::from(
+                "Metadata is too large for a JPEG segment",
+            )));
+        }
+
+        app.extend_from_slice(&[0xff, marker]);
+        app.extend_from_slice(&(len as u16).to_be_bytes());
+        app.extend_from_slice(header);
+        app.extend_from_slice(payload);
+    }
+
+    let mut out = data[..2].to_vec();
+    let mut app = Some(app);
+    for segment in &segments {
+        let before = (0xe0..marker).contains(&segment.marker)
+            || (marker > 0xe0 && segment.is_app(data, 0xe1, EXIF_HEADER));
+        if !before {
+            out.extend(app.take().unwrap_or_default());
+        }
+        if !segment.is_app(data, marker, header) {
+            out.extend_from_slice(&data[segment.start..segment.data.end]);
+        }
+    }
+    out.extend(app.unwrap_or_default());
+    out.extend_from_slice(&data[image..]);
+    Ok(out)
+}
+
+/// Replace the PNG chunks matching `is_match` with a new chunk, which is placed before the
+/// palette and image data
+fn png_replace_chunk<F: Fn(&[u8], &[u8]) -> bool>(
+    data: &[u8],
+    is_match: F,
+    kind: &[u8],
+    chunk: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let mut out = png::SIGNATURE.to_vec();
+    let mut inserted = false;
+    for (k, c) in png::chunks(data)? {
+        if !inserted && (k == b"PLTE" || k == b"IDAT" || k == b"IEND") {
+            png::write_chunk(&mut out, kind, chunk)?;
+            inserted = true;
+        }
+        if !is_match(k, c) {
+            png::write_chunk(&mut out, k, c)?;
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Gray, Rgb};
+
+    #[test]
+    fn test_thumbnail() {
+        let mut image: ImageBuf<u8, Rgb> = ImageBuf::new(640, 320);
+        image.for_each(|(x, _), px| px[0] = (x / 3) as u8);
+        crate::io::write("test/test-thumbnail.jpg", &image).unwrap();
+        assert!(thumbnail::<_, u8, Rgb>("test/test-thumbnail.jpg")
+            .unwrap()
+            .is_none());
+
+        write_thumbnail("test/test-thumbnail.jpg", &image).unwrap();
+        let small: ImageBuf<u8, Rgb> = thumbnail("test/test-thumbnail.jpg").unwrap().unwrap();
+        assert_eq!(small.shape(), (160, 80, 3));
+        assert!(small.get(150, 40, 0) > small.get(10, 40, 0));
+        let full: ImageBuf<u8, Rgb> = crate::io::read("test/test-thumbnail.jpg").unwrap();
+        assert_eq!(full.shape(), (640, 320, 3));
+
+        // The thumbnail is kept when the EXIF data is changed, and works in PNG files
+        let data = std::fs::read("test/test-thumbnail.jpg").unwrap();
+        let mut exif = Exif::decode(&data).unwrap().unwrap();
+        exif.set_orientation(1);
+        let data = exif.encode(&data).unwrap();
+        assert!(thumbnail_data(&data)
+            .unwrap()
+            .unwrap()
+            .starts_with(&[0xff, 0xd8]));
+        let gray: ImageBuf<u8, Gray> = ImageBuf::new(10, 20);
+        let png = encode_thumbnail(&crate::io::encode_png_u8(&gray).unwrap(), &gray).unwrap();
+        let small: ImageBuf<u8, Gray> =
+            crate::io::decode(thumbnail_data(&png).unwrap().unwrap()).unwrap();
+        assert_eq!(small.shape(), (10, 20, 1));
+
+        assert!(encode_thumbnail(&Exif::new().to_bytes(), &gray).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+//! Read and write the physical resolution of JPEG, PNG and TIFF files
+//!
+//! JPEG files store the resolution in the JFIF segment, falling back to the EXIF resolution tags
+//! when the JFIF segment only contains the pixel aspect ratio. PNG files use the `pHYs` chunk and
+//! TIFF files the `XResolution`, `YResolution` and `ResolutionUnit` tags. `io::read` and
+//! `io::write` use this module to keep track of the resolution of an `ImageBuf`.
+
+use std::path::Path;
+
+use crate::error::{with_path, Error};
+use crate::image_buf::{Resolution, ResolutionUnit};
+
+use super::exif::Exif;
+use super::{container, jpeg_app, jpeg_replace_app, png_replace_chunk, Container};
+
+/// Prefix of JFIF data in JPEG `APP0` segments
+const JFIF_HEADER: &[u8] = b"JFIF\0";
+
+/// Files without a unit usually store `1:1`, which doesn't say anything about the image
+fn aspect_only(x: f64, y: f64) -> Option<Resolution> {
+    if x > 0.0 && y > 0.0 && x != y {
+        Some(Resolution::new(x, y, ResolutionUnit::None))
+    } else {
+        None
+    }
+}
+
+fn jfif(payload: &[u8]) -> Option<Resolution> {
+    if payload.len() < 7 {
+        return None;
+    }
+    let x = f64::from(u16::from_be_bytes([payload[3], payload[4]]));
+    let y = f64::from(u16::from_be_bytes([payload[5], payload[6]]));
+    let unit = match payload[2] {
+        1 => ResolutionUnit::Inch,
+        2 => ResolutionUnit::Centimeter,
+        _ => return aspect_only(x, y),
+    };
+    if x > 0.0 && y > 0.0 {
+        Some(Resolution::new(x, y, unit))
+    } else {
+        None
+    }
+}
+
+fn phys(chunk: &[u8]) -> Option<Resolution> {
+    if chunk.len() != 9 {
+        return None;
+    }
+    let x = f64::from(u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]));
+    let y = f64::from(u32::from_be_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]));
+    match chunk[8] {
+        // Pixels per meter
+        1 if x > 0.0 && y > 0.0 => Some(Resolution::new(
+            x / 100.0,
+            y / 100.0,
+            ResolutionUnit::Centimeter,
+        )),
+        1 => None,
+        _ => aspect_only(x, y),
+    }
+}
+
+/// Get the resolution of a JPEG, PNG or TIFF file, returns `None` when the file doesn't store
+/// one
+pub fn decode(data: &[u8]) -> Result<Option<Resolution>, Error> {
+    match container(data)? {
+        Container::Jpeg => {
+            let jfif = jpeg_app(data, 0xe0, JFIF_HEADER)?.and_then(jfif);
+            match jfif {
+                Some(r) if r.unit != ResolutionUnit::None => Ok(Some(r)),
+                _ => {
+                    // Invalid EXIF data shouldn't hide the JFIF resolution
+                    let exif = Exif::decode(data).ok().flatten();
+                    Ok(exif.and_then(|exif| exif.resolution()).or(jfif))
+                }
+            }
+        }
+        Container::Png => Ok(crate::io::png::chunks(data)?
+            .iter()
+            .find(|(k, _)| *k == b"pHYs")
+            .and_then(|(_, chunk)| phys(chunk))),
+        Container::Tiff => Ok(Exif::parse(data)?.resolution()),
+    }
+}
+
+/// Read the resolution of a JPEG, PNG or TIFF file
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Option<Resolution>, Error> {
+    with_path(path, |path| decode(&std::fs::read(path)?))
+}
+
+/// Replace the resolution of a JPEG, PNG or TIFF file, returning the new file. JPEG and PNG
+/// files store whole numbers, so the resolution is rounded
+pub fn encode(data: &[u8], resolution: &Resolution) -> Result<Vec<u8>, Error> {
+    match container(data)? {
+        Container::Jpeg => {
+            let density = |x: f64| (x.round().clamp(1.0, f64::from(u16::MAX)) as u16).to_be_bytes();
+            let unit = match resolution.unit {
+                ResolutionUnit::None => 0,
+                ResolutionUnit::Inch => 1,
+                ResolutionUnit::Centimeter => 2,
+            };
+
+            // Keep the version and thumbnail of an existing JFIF segment
+            let mut payload = match jpeg_app(data, 0xe0, JFIF_HEADER)? {
+                Some(jfif) if jfif.len() >= 9 => jfif.to_vec(),
+                _ => vec![1, 1, 0, 0, 0, 0, 0, 0, 0],
+            };
+            payload[2] = unit;
+            payload[3..5].copy_from_slice(&density(resolution.x));
+            payload[5..7].copy_from_slice(&density(resolution.y));
+            jpeg_replace_app(data, 0xe0, JFIF_HEADER, &payload)
+        }
+        Container::Png => {
+            let (scale, unit) = match resolution.unit {
+                ResolutionUnit::None => (1.0, 0),
+                ResolutionUnit::Inch => (1.0 / 0.0254, 1),
+                ResolutionUnit::Centimeter => (100.0, 1),
+            };
+            let ppu = |x: f64| (x * scale).round().clamp(1.0, f64::from(u32::MAX)) as u32;
+
+            let mut chunk = Vec::with_capacity(9);
+            chunk.extend_from_slice(&ppu(resolution.x).to_be_bytes());
+            chunk.extend_from_slice(&ppu(resolution.y).to_be_bytes());
+            chunk.push(unit);
+            png_replace_chunk(data, |k, _| k == b"pHYs", b"pHYs", &chunk)
+        }
+        Container::Tiff => {
+            let mut exif = Exif::parse(data)?;
+            exif.set_resolution(resolution);
+            exif.encode(data)
+        }
+    }
+}
+
+/// Replace the resolution of a JPEG, PNG or TIFF file
+pub fn write<P: AsRef<Path>>(path: P, resolution: &Resolution) -> Result<(), Error> {
+    with_path(path, |path| {
+        let data = encode(&std::fs::read(path)?, resolution)?;
+        std::fs::write(path, data)?;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::metadata::exif::{Exif, Ifd, Value};
+    use crate::{ImageBuf, Rgb};
+
+    #[test]
+    fn test_resolution() {
+        let close = |a: Option<Resolution>, b: Resolution| {
+            let (x, y) = a.unwrap().to_dpi().unwrap();
+            assert!((x - b.x).abs() < 0.01 && (y - b.y).abs() < 0.01, "{:?}", a);
+        };
+
+        // PNG, stored in pixels per meter
+        let rgb: ImageBuf<u8, Rgb> = ImageBuf::new(4, 4);
+        let png = crate::io::encode_png_u8(&rgb).unwrap();
+        assert_eq!(decode(&png).unwrap(), None);
+        let data = encode(&png, &Resolution::dpi(300.0)).unwrap();
+        close(decode(&data).unwrap(), Resolution::dpi(300.0));
+        let data = encode(&data, &Resolution::new(1.0, 2.0, ResolutionUnit::None)).unwrap();
+        assert_eq!(decode(&data).unwrap().unwrap().unit, ResolutionUnit::None);
+        assert_eq!(crate::io::png::chunks(&data).unwrap().len(), 4);
+
+        // JPEG, the JFIF segment is replaced
+        let jpeg = std::fs::read("test/test.jpg").unwrap();
+        let resolution = Resolution::new(300.0, 150.0, ResolutionUnit::Inch);
+        let data = encode(&jpeg, &resolution).unwrap();
+        assert_eq!(decode(&data).unwrap(), Some(resolution));
+        let cm = Resolution::new(118.0, 118.0, ResolutionUnit::Centimeter);
+        let data = encode(&data, &cm).unwrap();
+        assert_eq!(decode(&data).unwrap(), Some(cm));
+        let (segments, _) = crate::metadata::jpeg_segments(&data).unwrap();
+        assert_eq!(segments.iter().filter(|s| s.marker == 0xe0).count(), 1);
+        assert_eq!(segments[0].marker, 0xe0);
+        let image: ImageBuf<u8, Rgb> = crate::io::decode(&data).unwrap();
+        let original: ImageBuf<u8, Rgb> = crate::io::decode(&jpeg).unwrap();
+        assert_eq!(image, original);
+
+        // EXIF is used when JFIF only has the aspect ratio
+        let data = encode(&jpeg, &Resolution::new(1.0, 1.0, ResolutionUnit::None)).unwrap();
+        let mut exif = Exif::new();
+        exif.set_resolution(&Resolution::dpi(72.0));
+        let data = exif.encode(&data).unwrap();
+        assert_eq!(decode(&data).unwrap(), Some(Resolution::dpi(72.0)));
+
+        // TIFF
+        let mut tiff = Exif::new();
+        tiff.set(Ifd::Primary, 0x0111, Value::Long(vec![8]));
+        let data = encode(&tiff.to_bytes(), &Resolution::dpi(600.0)).unwrap();
+        assert_eq!(decode(&data).unwrap(), Some(Resolution::dpi(600.0)));
+
+        assert!(decode(b"GIF89a").is_err());
+    }
+}
@@ -0,0 +1,966 @@
+//! Read and write XMP metadata in JPEG, PNG and TIFF files
+//!
+//! ```rust,no_run
+//! use image2::metadata::xmp::{HistoryEvent, Xmp};
+//!
+//! let mut xmp = Xmp::read("photo.jpg")?.unwrap_or_default();
+//! xmp.set_rating(4);
+//! xmp.set_subject(&["holiday", "beach"]);
+//! xmp.add_history(HistoryEvent::new("saved").with_software_agent("image2"));
+//! xmp.write("photo.jpg")?;
+//! # Ok::<(), image2::Error>(())
+//! ```
+//!
+//! Properties are identified by their prefixed name, like `xmp:Rating`, using the prefixes
+//! declared in the packet. Simple values, arrays, language alternatives and arrays of simple
+//! structures, like `xmpMM:History`, are parsed. Other properties are kept as XML so they are
+//! preserved when the packet is written again. Packets are stored in `APP1` segments of JPEG
+//! files, `iTXt` chunks of PNG files and the `XMLPacket` tag of TIFF files. Extended XMP in JPEG
+//! files and compressed `iTXt` chunks are not supported.
+
+use std::path::Path;
+
+use crate::error::{with_path, Error};
+
+use super::exif::{Exif, Ifd, Value};
+use super::{container, jpeg_app, jpeg_replace_app, png_replace_chunk, Container};
+
+/// Prefix of XMP packets in JPEG `APP1` segments
+const XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+
+/// Keyword of PNG `iTXt` chunks containing XMP packets
+const PNG_KEYWORD: &[u8] = b"XML:com.adobe.xmp";
+
+/// TIFF tag containing XMP packets
+const XML_PACKET: u16 = 700;
+
+const RDF: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+
+/// Namespaces that are declared when they aren't declared by the packet
+const NAMESPACES: &[(&str, &str)] = &[
+    ("dc", "http://purl.org/dc/elements/1.1/"),
+    ("xmp", "http://ns.adobe.com/xap/1.0/"),
+    ("xmpMM", "http://ns.adobe.com/xap/1.0/mm/"),
+    ("xmpRights", "http://ns.adobe.com/xap/1.0/rights/"),
+    ("stEvt", "http://ns.adobe.com/xap/1.0/sType/ResourceEvent#"),
+    ("photoshop", "http://ns.adobe.com/photoshop/1.0/"),
+    ("exif", EXIF),
+];
+
+/// Namespace of the EXIF properties, including the GPS location
+const EXIF: &str = "http://ns.adobe.com/exif/1.0/";
+
+/// Value of a property
+#[derive(Debug, Clone, PartialEq)]
+pub enum Property {
+    Text(String),
+    /// Ordered array, `rdf:Seq`
+    Seq(Vec<String>),
+    /// Unordered array, `rdf:Bag`
+    Bag(Vec<String>),
+    /// Language alternatives as `(language, text)`, `rdf:Alt`
+    Alt(Vec<(String, String)>),
+    /// Ordered array of structures with simple fields, as `(name, value)`
+    Structs(Vec<Vec<(String, String)>>),
+    /// Any other value, as the XML of the whole property element
+    Xml(String),
+}
+
+/// An entry of `xmpMM:History`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HistoryEvent {
+    pub action: String,
+    pub when: Option<String>,
+    pub software_agent: Option<String>,
+    pub changed: Option<String>,
+    pub instance_id: Option<String>,
+}
+
+impl HistoryEvent {
+    /// Create a new event, `action` is usually one of `created`, `saved`, `converted` or
+    /// `derived`
+    pub fn new(action: &str) -> HistoryEvent {
+        HistoryEvent {
+            action: action.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Set the date of the event formatted as `YYYY-MM-DDThh:mm:ss`
+    pub fn with_when(mut self, when: &str) -> HistoryEvent {
+        self.when = Some(when.to_string());
+        self
+    }
+
+    /// Set the application that performed the action
+    pub fn with_software_agent(mut self, software_agent: &str) -> HistoryEvent {
+        self.software_agent = Some(software_agent.to_string());
+        self
+    }
+
+    /// Set the parts of the resource that were changed, like `/metadata`
+    pub fn with_changed(mut self, changed: &str) -> HistoryEvent {
+        self.changed = Some(changed.to_string());
+        self
+    }
+
+    fn fields(&self) -> Vec<(String, String)> {
+        let mut fields = vec![(String::from("stEvt:action"), self.action.clone())];
+        let optional = [
+            ("stEvt:when", &self.when),
+            ("stEvt:softwareAgent", &self.software_agent),
+            ("stEvt:changed", &self.changed),
+            ("stEvt:instanceID", &self.instance_id),
+        ];
+        for (name, value) in optional.iter() {
+            if let Some(value) = value {
+                fields.push((name.to_string(), value.clone()));
+            }
+        }
+        fields
+    }
+
+    fn from_fields(fields: &[(String, String)]) -> HistoryEvent {
+        let get = |name: &str| {
+            fields
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.clone())
+        };
+        HistoryEvent {
+            action: get("stEvt:action").unwrap_or_default(),
+            when: get("stEvt:when"),
+            software_agent: get("stEvt:softwareAgent"),
+            changed: get("stEvt:changed"),
+            instance_id: get("stEvt:instanceID"),
+        }
+    }
+}
+
+/// XMP metadata
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Xmp {
+    /// Namespaces declared by the packet as `(prefix, uri)`
+    namespaces: Vec<(String, String)>,
+    properties: Vec<(String, Property)>,
+}
+
+/// A minimal XML parser, which is enough for the RDF subset used by XMP
+mod xml {
+    use crate::error::Error;
+
+    /// Maximum depth of nested elements
+    const MAX_DEPTH: usize = 64;
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Element {
+        pub name: String,
+        pub attributes: Vec<(String, String)>,
+        pub children: Vec<Node>,
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Node {
+        Element(Element),
+        Text(String),
+    }
+
+    impl Element {
+        pub fn attribute(&self, name: &str) -> Option<&str> {
+            self.attributes
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.as_str())
+        }
+
+        pub fn elements(&self) -> impl Iterator<Item = &Element> {
+            self.children.iter().filter_map(|node| match node {
+                Node::Element(e) => Some(e),
+                Node::Text(_) => None,
+            })
+        }
+
+        pub fn text(&self) -> String {
+            self.children
+                .iter()
+                .filter_map(|node| match node {
+                    Node::Text(t) => Some(t.as_str()),
+                    Node::Element(_) => None,
+                })
+                .collect()
+        }
+    }
+
+    fn invalid(msg: &str) -> Error {
+        Error::Message(format!("Invalid XMP packet: {}", msg))
+    }
+
+    pub fn unescape(s: &str) -> String {
+        let mut out = String::with_capacity(s.len());
+        let mut rest = s;
+        while let Some(i) = rest.find('&') {
+            out.push_str(&rest[..i]);
+            rest = &rest[i..];
+            let end = match rest.find(';') {
+                Some(end) => end,
+                None => break,
+            };
+            let c = match &rest[1..end] {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                x if x.starts_with("#x") => u32::from_str_radix(&x[2..], 16)
+                    .ok()
+                    .and_then(std::char::from_u32),
+                x if x.starts_with('#') => x[1..].parse().ok().and_then(std::char::from_u32),
+                _ => None,
+            };
+            match c {
+                Some(c) => {
+                    out.push(c);
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    out.push('&');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+
+    pub fn escape(s: &str) -> String {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+
+    struct Parser<'a> {
+        s: &'a str,
+        pos: usize,
+    }
+
+    impl<'a> Parser<'a> {
+        fn rest(&self) -> &'a str {
+            &self.s[self.pos..]
+        }
+
+        fn skip_past(&mut self, end: &str) -> Result<&'a str, Error> {
+            match self.rest().find(end) {
+                Some(i) => {
+                    let skipped = &self.rest()[..i];
+                    self.pos += i + end.len();
+                    Ok(skipped)
+                }
+                None => Err(invalid(&format!("missing {}", end))),
+            }
+        }
+
+        fn skip_whitespace(&mut self) {
+            let rest = self.rest();
+            self.pos += rest.len() - rest.trim_start().len();
+        }
+
+        fn name(&mut self) -> &'a str {
+            let rest = self.rest();
+            let len = rest
+                .find(|c: char| c.is_whitespace() || c == '>' || c == '/' || c == '=')
+                .unwrap_or(rest.len());
+            self.pos += len;
+            &rest[..len]
+        }
+
+        fn element(&mut self, depth: usize) -> Result<Element, Error> {
+            self.pos += 1;
+            let name = self.name().to_string();
+            let mut attributes = Vec::new();
+            loop {
+                self.skip_whitespace();
+                if self.rest().starts_with("/>") {
+                    self.pos += 2;
+                    return Ok(Element {
+                        name,
+                        attributes,
+                        children: Vec::new(),
+                    });
+                } else if self.rest().starts_with('>') {
+                    self.pos += 1;
+                    let children = self.nodes(Some(&name), depth + 1)?;
+                    return Ok(Element {
+                        name,
+                        attributes,
+                        children,
+                    });
+                }
+
+                let attribute = self.name().to_string();
+                self.skip_whitespace();
+                if attribute.is_empty() || !self.rest().starts_with('=') {
+                    return Err(invalid("invalid attribute"));
+                }
+                self.pos += 1;
+                self.skip_whitespace();
+                let quote = match self.rest().chars().next() {
+                    Some(q) if q == '"' || q == '\'' => q,
+                    _ => return Err(invalid("invalid attribute")),
+                };
+                self.pos += 1;
+                let value = self.skip_past(if quote == '"' { "\"" } else { "'" })?;
+                attributes.push((attribute, unescape(value)));
+            }
+        }
+
+        fn nodes(&mut self, parent: Option<&str>, depth: usize) -> Result<Vec<Node>, Error> {
+            if depth > MAX_DEPTH {
+                return Err(invalid("too deeply nested"));
+            }
+
+            let mut nodes = Vec::new();
+            loop {
+                let rest = self.rest();
+                if rest.is_empty() {
+                    return match parent {
+                        Some(name) => Err(invalid(&format!("unclosed element {}", name))),
+                        None => Ok(nodes),
+                    };
+                } else if rest.starts_with("</") {
+                    self.pos += 2;
+                    let name = self.name();
+                    self.skip_past(">")?;
+                    return match parent {
+                        Some(parent) if parent == name => Ok(nodes),
+                        _ => Err(invalid(&format!("unexpected closing tag {}", name))),
+                    };
+                } else if rest.starts_with("<!--") {
+                    self.skip_past("-->")?;
+                } else if rest.starts_with("<![CDATA[") {
+                    self.pos += 9;
+                    nodes.push(Node::Text(self.skip_past("]]>")?.to_string()));
+                } else if rest.starts_with("<?") {
+                    self.skip_past("?>")?;
+                } else if rest.starts_with("<!") {
+                    self.skip_past(">")?;
+                } else if rest.starts_with('<') {
+                    nodes.push(Node::Element(self.element(depth)?));
+                } else {
+                    let len = rest.find('<').unwrap_or(rest.len());
+                    nodes.push(Node::Text(unescape(&rest[..len])));
+                    self.pos += len;
+                }
+            }
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Vec<Node>, Error> {
+        Parser { s, pos: 0 }.nodes(None, 0)
+    }
+
+    pub fn write(nodes: &[Node], out: &mut String) {
+        for node in nodes {
+            match node {
+                Node::Text(t) => out.push_str(&escape(t)),
+                Node::Element(e) => {
+                    out.push('<');
+                    out.push_str(&e.name);
+                    for (name, value) in &e.attributes {
+                        out.push_str(&format!(" {}=\"{}\"", name, escape(value)));
+                    }
+                    if e.children.is_empty() {
+                        out.push_str("/>");
+                    } else {
+                        out.push('>');
+                        write(&e.children, out);
+                        out.push_str(&format!("</{}>", e.name));
+                    }
+                }
+            }
+        }
+    }
+}
+
+use self::xml::{Element, Node};
+
+/// Visit every element of the tree
+fn visit<'a, F: FnMut(&'a Element)>(nodes: &'a [Node], f: &mut F) {
+    for node in nodes {
+        if let Node::Element(e) = node {
+            f(e);
+            visit(&e.children, f);
+        }
+    }
+}
+
+/// Attributes that are part of the RDF syntax instead of properties
+fn is_syntax(name: &str) -> bool {
+    name.starts_with("xmlns") || name.starts_with("rdf:") || name.starts_with("xml:")
+}
+
+/// Fields of a structure, `None` when a field isn't a simple value
+fn fields(e: &Element) -> Option<Vec<(String, String)>> {
+    // A structure can also be written as a nested `rdf:Description`
+    let mut elements: Vec<&Element> = e.elements().collect();
+    let mut fields: Vec<(String, String)> = e
+        .attributes
+        .iter()
+        .filter(|(name, _)| !is_syntax(name))
+        .cloned()
+        .collect();
+    if let [description] = elements.as_slice() {
+        if description.name == "rdf:Description" {
+            fields.extend(
+                description
+                    .attributes
+                    .iter()
+                    .filter(|(name, _)| !is_syntax(name))
+                    .cloned(),
+            );
+            elements = description.elements().collect();
+        }
+    }
+
+    for field in elements {
+        if field.elements().next().is_some() {
+            return None;
+        }
+        fields.push((field.name.clone(), field.text()));
+    }
+    Some(fields)
+}
+
+fn property(e: &Element) -> Property {
+    if let Some(resource) = e.attribute("rdf:resource") {
+        return Property::Text(resource.to_string());
+    }
+
+    // Structures written using attributes or `rdf:parseType`
+    if e.attributes.iter().any(|(name, _)| !is_syntax(name))
+        || e.attribute("rdf:parseType").is_some()
+    {
+        return xml_property(e);
+    }
+
+    let elements: Vec<&Element> = e.elements().collect();
+    let array = match elements.as_slice() {
+        [] => return Property::Text(e.text()),
+        [array] => *array,
+        _ => return xml_property(e),
+    };
+
+    let items: Vec<&Element> = array.elements().filter(|e| e.name == "rdf:li").collect();
+    let simple = items.iter().all(|li| {
+        li.elements().next().is_none() && li.attributes.iter().all(|(name, _)| is_syntax(name))
+    }) && items
+        .iter()
+        .all(|li| li.attribute("rdf:parseType").is_none());
+    match array.name.as_str() {
+        "rdf:Alt" if simple => Property::Alt(
+            items
+                .iter()
+                .map(|li| {
+                    let lang = li.attribute("xml:lang").unwrap_or("x-default");
+                    (lang.to_string(), li.text())
+                })
+                .collect(),
+        ),
+        "rdf:Seq" if simple => Property::Seq(items.iter().map(|li| li.text()).collect()),
+        "rdf:Bag" if simple => Property::Bag(items.iter().map(|li| li.text()).collect()),
+        "rdf:Seq" => match items.iter().map(|li| fields(li)).collect() {
+            Some(structs) => Property::Structs(structs),
+            None => xml_property(e),
+        },
+        _ => xml_property(e),
+    }
+}
+
+fn xml_property(e: &Element) -> Property {
+    let mut xml = String::new();
+    xml::write(&[Node::Element(e.clone())], &mut xml);
+    Property::Xml(xml)
+}
+
+fn write_items<'a, I: Iterator<Item = &'a str>>(out: &mut String, kind: &str, items: I) {
+    out.push_str(&format!("<rdf:{}>", kind));
+    for item in items {
+        out.push_str(&format!("<rdf:li>{}</rdf:li>", xml::escape(item)));
+    }
+    out.push_str(&format!("</rdf:{}>", kind));
+}
+
+impl Xmp {
+    /// Create empty metadata
+    pub fn new() -> Xmp {
+        Xmp::default()
+    }
+
+    /// Every property as `(name, value)`
+    pub fn properties(&self) -> &[(String, Property)] {
+        &self.properties
+    }
+
+    /// Get the value of a property
+    pub fn get(&self, name: &str) -> Option<&Property> {
+        self.properties
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, p)| p)
+    }
+
+    /// Set the value of a property, replacing the existing value
+    pub fn set(&mut self, name: &str, value: Property) {
+        match self.properties.iter_mut().find(|(n, _)| n == name) {
+            Some(property) => property.1 = value,
+            None => self.properties.push((name.to_string(), value)),
+        }
+    }
+
+    /// Remove a property, returning its value
+    pub fn remove(&mut self, name: &str) -> Option<Property> {
+        let index = self.properties.iter().position(|(n, _)| n == name)?;
+        Some(self.properties.remove(index).1)
+    }
+
+    /// Remove the `GPS` properties of the EXIF namespace, so the packet no longer reveals where
+    /// the image was taken
+    pub fn strip_location(&mut self) {
+        let prefixes: Vec<&str> = self
+            .namespaces
+            .iter()
+            .filter(|(_, uri)| uri == EXIF)
+            .map(|(prefix, _)| prefix.as_str())
+            .chain(Some("exif"))
+            .collect();
+        self.properties
+            .retain(|(name, _)| match name.split_once(':') {
+                Some((prefix, name)) => !(prefixes.contains(&prefix) && name.starts_with("GPS")),
+                None => true,
+            });
+    }
+
+    /// Parse an XMP packet
+    pub fn parse(packet: &str) -> Result<Xmp, Error> {
+        let nodes = xml::parse(packet)?;
+        let mut xmp = Xmp::new();
+        visit(&nodes, &mut |e| {
+            for (name, value) in &e.attributes {
+                if let Some(prefix) = name.strip_prefix("xmlns:") {
+                    let declared = xmp.namespaces.iter().any(|(p, _)| p == prefix);
+                    if prefix != "rdf" && prefix != "x" && !declared {
+                        xmp.namespaces.push((prefix.to_string(), value.clone()));
+                    }
+                }
+            }
+
+            if e.name != "rdf:Description" {
+                return;
+            }
+            for (name, value) in e.attributes.iter().filter(|(n, _)| !is_syntax(n)) {
+                xmp.set(name, Property::Text(value.clone()));
+            }
+            for child in e.elements() {
+                xmp.set(&child.name, property(child));
+            }
+        });
+        Ok(xmp)
+    }
+
+    /// Encode the metadata as an XMP packet
+    pub fn to_packet(&self) -> String {
+        let mut namespaces = self.namespaces.clone();
+        for (prefix, uri) in NAMESPACES {
+            if !namespaces.iter().any(|(p, _)| p == prefix) {
+                namespaces.push((prefix.to_string(), uri.to_string()));
+            }
+        }
+        // Only declare namespaces that are used
+        namespaces.retain(|(prefix, _)| {
+            let prefix = format!("{}:", prefix);
+            self.properties.iter().any(|(name, property)| {
+                name.starts_with(&prefix)
+                    || match property {
+                        Property::Structs(structs) => structs
+                            .iter()
+                            .any(|s| s.iter().any(|(n, _)| n.starts_with(&prefix))),
+                        Property::Xml(xml) => xml.contains(&prefix),
+                        _ => false,
+                    }
+            })
+        });
+
+        let mut out = String::from(
+            "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+             <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n",
+        );
+        out.push_str(&format!(" <rdf:RDF xmlns:rdf=\"{}\">\n", RDF));
+        out.push_str("  <rdf:Description rdf:about=\"\"");
+        for (prefix, uri) in &namespaces {
+            out.push_str(&format!("\n    xmlns:{}=\"{}\"", prefix, xml::escape(uri)));
+        }
+        out.push_str(">\n");
+
+        for (name, property) in &self.properties {
+            if let Property::Xml(xml) = property {
+                out.push_str(&format!("   {}\n", xml));
+                continue;
+            }
+
+            out.push_str(&format!("   <{}>", name));
+            match property {
+                Property::Text(text) => out.push_str(&xml::escape(text)),
+                Property::Seq(items) => {
+                    write_items(&mut out, "Seq", items.iter().map(|x| x.as_str()))
+                }
+                Property::Bag(items) => {
+                    write_items(&mut out, "Bag", items.iter().map(|x| x.as_str()))
+                }
+                Property::Alt(items) => {
+                    out.push_str("<rdf:Alt>");
+                    for (lang, text) in items {
+                        out.push_str(&format!(
+                            "<rdf:li xml:lang=\"{}\">{}</rdf:li>",
+                            xml::escape(lang),
+                            xml::escape(text)
+                        ));
+                    }
+                    out.push_str("</rdf:Alt>");
+                }
+                Property::Structs(structs) => {
+                    out.push_str("<rdf:Seq>");
+                    for fields in structs {
+                        out.push_str("<rdf:li rdf:parseType=\"Resource\">");
+                        for (name, value) in fields {
+                            out.push_str(&format!("<{}>{}</{}>", name, xml::escape(value), name));
+                        }
+                        out.push_str("</rdf:li>");
+                    }
+                    out.push_str("</rdf:Seq>");
+                }
+                Property::Xml(_) => unreachable!(),
+            }
+            out.push_str(&format!("</{}>\n", name));
+        }
+
+        out.push_str("  </rdf:Description>\n </rdf:RDF>\n</x:xmpmeta>\n<?xpacket end=\"w\"?>");
+        out
+    }
+
+    /// Get the metadata of a JPEG, PNG or TIFF file, returns `None` when the file has no XMP
+    /// packet
+    pub fn decode(data: &[u8]) -> Result<Option<Xmp>, Error> {
+        let packet = match container(data)? {
+            Container::Jpeg => jpeg_app(data, 0xe1, XMP_HEADER)?.map(<[u8]>::to_vec),
+            Container::Png => {
+                let chunks = crate::io::png::chunks(data)?;
+                match chunks
+                    .iter()
+                    .find(|(k, c)| *k == b"iTXt" && is_xmp_chunk(c))
+                {
+                    Some((_, chunk)) => Some(itxt_text(chunk)?.to_vec()),
+                    None => None,
+                }
+            }
+            Container::Tiff => match Exif::parse(data)?.get(Ifd::Primary, XML_PACKET) {
+                Some(Value::Byte(x)) | Some(Value::Undefined(x)) => Some(x.clone()),
+                Some(Value::Ascii(x)) => Some(x.clone().into_bytes()),
+                _ => None,
+            },
+        };
+
+        match packet {
+            Some(packet) => Xmp::parse(&String::from_utf8_lossy(&packet)).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Read the metadata of a JPEG, PNG or TIFF file
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Option<Xmp>, Error> {
+        with_path(path, |path| Xmp::decode(&std::fs::read(path)?))
+    }
+
+    /// Replace the XMP packet of a JPEG, PNG or TIFF file, returning the new file
+    pub fn encode(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let packet = self.to_packet().into_bytes();
+        match container(data)? {
+            Container::Jpeg => jpeg_replace_app(data, 0xe1, XMP_HEADER, &packet),
+            Container::Png => {
+                // Uncompressed, without a language or translated keyword
+                let mut chunk = PNG_KEYWORD.to_vec();
+                chunk.extend_from_slice(&[0, 0, 0, 0, 0]);
+                chunk.extend(packet);
+                let is_match = |k: &[u8], c: &[u8]| k == b"iTXt" && is_xmp_chunk(c);
+                png_replace_chunk(data, is_match, b"iTXt", &chunk)
+            }
+            Container::Tiff => {
+                let mut exif = Exif::parse(data)?;
+                exif.set(Ifd::Primary, XML_PACKET, Value::Byte(packet));
+                exif.encode(data)
+            }
+        }
+    }
+
+    /// Replace the XMP packet of a JPEG, PNG or TIFF file
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        with_path(path, |path| {
+            let data = self.encode(&std::fs::read(path)?)?;
+            std::fs::write(path, data)?;
+            Ok(())
+        })
+    }
+
+    fn text(&self, name: &str) -> Option<&str> {
+        match self.get(name)? {
+            Property::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// The `x-default` language alternative, or the first one
+    fn alt(&self, name: &str) -> Option<&str> {
+        match self.get(name)? {
+            Property::Alt(items) => items
+                .iter()
+                .find(|(lang, _)| lang == "x-default")
+                .or_else(|| items.first())
+                .map(|(_, text)| text.as_str()),
+            Property::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    fn set_alt(&mut self, name: &str, text: &str) {
+        let mut items = match self.remove(name) {
+            Some(Property::Alt(items)) => items,
+            _ => Vec::new(),
+        };
+        match items.iter_mut().find(|(lang, _)| lang == "x-default") {
+            Some(item) => item.1 = text.to_string(),
+            None => items.insert(0, (String::from("x-default"), text.to_string())),
+        }
+        self.set(name, Property::Alt(items));
+    }
+
+    fn list(&self, name: &str) -> Vec<&str> {
+        match self.get(name) {
+            Some(Property::Seq(items)) | Some(Property::Bag(items)) => {
+                items.iter().map(|x| x.as_str()).collect()
+            }
+            Some(Property::Text(text)) => vec![text.as_str()],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Rating from -1 (rejected) to 5, 0 means unrated
+    pub fn rating(&self) -> Option<i32> {
+        let rating: f64 = self.text("xmp:Rating")?.trim().parse().ok()?;
+        Some(rating.round() as i32)
+    }
+
+    pub fn set_rating(&mut self, rating: i32) {
+        self.set("xmp:Rating", Property::Text(rating.to_string()));
+    }
+
+    /// Color label, like `Red`
+    pub fn label(&self) -> Option<&str> {
+        self.text("xmp:Label")
+    }
+
+    pub fn set_label(&mut self, label: &str) {
+        self.set("xmp:Label", Property::Text(label.to_string()));
+    }
+
+    /// `dc:title`
+    pub fn title(&self) -> Option<&str> {
+        self.alt("dc:title")
+    }
+
+    pub fn set_title(&mut self, title: &str) {
+        self.set_alt("dc:title", title);
+    }
+
+    /// `dc:description`
+    pub fn description(&self) -> Option<&str> {
+        self.alt("dc:description")
+    }
+
+    pub fn set_description(&mut self, description: &str) {
+        self.set_alt("dc:description", description);
+    }
+
+    /// `dc:rights`
+    pub fn rights(&self) -> Option<&str> {
+        self.alt("dc:rights")
+    }
+
+    pub fn set_rights(&mut self, rights: &str) {
+        self.set_alt("dc:rights", rights);
+    }
+
+    /// `dc:creator`
+    pub fn creators(&self) -> Vec<&str> {
+        self.list("dc:creator")
+    }
+
+    pub fn set_creators(&mut self, creators: &[&str]) {
+        let creators = creators.iter().map(|x| x.to_string()).collect();
+        self.set("dc:creator", Property::Seq(creators));
+    }
+
+    /// Keywords, `dc:subject`
+    pub fn subject(&self) -> Vec<&str> {
+        self.list("dc:subject")
+    }
+
+    pub fn set_subject(&mut self, subject: &[&str]) {
+        let subject = subject.iter().map(|x| x.to_string()).collect();
+        self.set("dc:subject", Property::Bag(subject));
+    }
+
+    /// Edit history, `xmpMM:History`
+    pub fn history(&self) -> Vec<HistoryEvent> {
+        match self.get("xmpMM:History") {
+            Some(Property::Structs(structs)) => structs
+                .iter()
+                .map(|fields| HistoryEvent::from_fields(fields))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Append an event to the edit history
+    pub fn add_history(&mut self, event: HistoryEvent) {
+        let mut structs = match self.remove("xmpMM:History") {
+            Some(Property::Structs(structs)) => structs,
+            _ => Vec::new(),
+        };
+        structs.push(event.fields());
+        self.set("xmpMM:History", Property::Structs(structs));
+    }
+}
+
+fn is_xmp_chunk(chunk: &[u8]) -> bool {
+    chunk.starts_with(PNG_KEYWORD) && chunk.get(PNG_KEYWORD.len()) == Some(&0)
+}
+
+/// Get the text of an `iTXt` chunk
+fn itxt_text(chunk: &[u8]) -> Result<&[u8], Error> {
+    let truncated = || Error::Message(String::from("Truncated iTXt chunk"));
+    let rest = &chunk[PNG_KEYWORD.len() + 1..];
+    match rest.first() {
+        Some(0) => (),
+        Some(_) => {
+            return Err(Error::Message(String::from(
+                "Compressed XMP packets are not supported",
+            )))
+        }
+        None => return Err(truncated()),
+    }
+
+    // Skip the compression method, language and translated keyword
+    let mut rest = rest.get(2..).ok_or_else(truncated)?;
+    for _ in 0..2 {
+        let end = rest.iter().position(|x| *x == 0).ok_or_else(truncated)?;
+        rest = &rest[end + 1..];
+    }
+    Ok(rest)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ImageBuf, Rgb};
+
+    const PACKET: &str = r#"<?xpacket begin="" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about="" xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+    xmlns:dc="http://purl.org/dc/elements/1.1/"
+    xmlns:xmpMM="http://ns.adobe.com/xap/1.0/mm/"
+    xmlns:stEvt="http://ns.adobe.com/xap/1.0/sType/ResourceEvent#"
+    xmlns:crs="http://ns.adobe.com/camera-raw-settings/1.0/"
+    xmp:Rating="3" xmp:Label="Red">
+   <dc:title><rdf:Alt><rdf:li xml:lang="x-default">Fish &amp; chips</rdf:li></rdf:Alt></dc:title>
+   <dc:subject><rdf:Bag><rdf:li>food</rdf:li><rdf:li>beach</rdf:li></rdf:Bag></dc:subject>
+   <xmpMM:History>
+    <rdf:Seq>
+     <rdf:li stEvt:action="created" stEvt:when="2024-05-01T12:00:00"/>
+     <rdf:li rdf:parseType="Resource">
+      <stEvt:action>saved</stEvt:action>
+      <stEvt:softwareAgent>editor</stEvt:softwareAgent>
+     </rdf:li>
+    </rdf:Seq>
+   </xmpMM:History>
+   <crs:Look><rdf:Description crs:Name="Vivid"><crs:Parameters crs:Amount="1"/></rdf:Description></crs:Look>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#;
+
+    #[test]
+    fn test_xmp() {
+        let mut xmp = Xmp::parse(PACKET).unwrap();
+        assert_eq!(xmp.rating(), Some(3));
+        assert_eq!(xmp.label(), Some("Red"));
+        assert_eq!(xmp.title(), Some("Fish & chips"));
+        assert_eq!(xmp.subject(), vec!["food", "beach"]);
+        let history = xmp.history();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].when.as_deref(), Some("2024-05-01T12:00:00"));
+        assert_eq!(history[1].software_agent.as_deref(), Some("editor"));
+        assert!(matches!(xmp.get("crs:Look"), Some(Property::Xml(_))));
+
+        xmp.set_rating(5);
+        xmp.set_creators(&["Ann", "Bo"]);
+        xmp.set_description("<test>");
+        xmp.add_history(HistoryEvent::new("converted").with_changed("/"));
+
+        // Everything, including unknown properties, survives a round trip
+        let parsed = Xmp::parse(&xmp.to_packet()).unwrap();
+        assert_eq!(parsed.properties(), xmp.properties());
+        assert_eq!(parsed.creators(), vec!["Ann", "Bo"]);
+        assert_eq!(parsed.description(), Some("<test>"));
+        assert_eq!(parsed.history()[2].action, "converted");
+
+        // JPEG, with and without EXIF
+        let jpeg = std::fs::read("test/test.jpg").unwrap();
+        let data = xmp.encode(&jpeg).unwrap();
+        assert_eq!(Xmp::decode(&data).unwrap(), Some(Clone::clone(&parsed)));
+        let data = xmp.encode(&Exif::new().encode(&jpeg).unwrap()).unwrap();
+        assert_eq!(Xmp::decode(&data).unwrap().unwrap().rating(), Some(5));
+        assert!(Exif::decode(&data).unwrap().is_some());
+
+        // PNG
+        let rgb: ImageBuf<u8, Rgb> = ImageBuf::new(4, 4);
+        let png = crate::io::encode_png_u8(&rgb).unwrap();
+        assert_eq!(Xmp::decode(&png).unwrap(), None);
+        let data = xmp.encode(&png).unwrap();
+        assert_eq!(Xmp::decode(&data).unwrap().unwrap().label(), Some("Red"));
+        let image: ImageBuf<u8, Rgb> = crate::io::decode(&data).unwrap();
+        assert_eq!(image, rgb);
+
+        // TIFF
+        let data = xmp.encode(&Exif::new().to_bytes()).unwrap();
+        assert_eq!(Xmp::decode(&data).unwrap().unwrap().rating(), Some(5));
+
+        // Metadata is kept when an image is saved again
+        let metadata = super::super::Metadata::decode(&data).unwrap();
+        let data = metadata.encode(&png).unwrap();
+        assert_eq!(Xmp::decode(&data).unwrap().unwrap().rating(), Some(5));
+
+        let mut located = Clone::clone(&parsed);
+        located.set(
+            "exif:GPSLatitude",
+            Property::Text(String::from("33,51.408S")),
+        );
+        located.set("exif:ExposureTime", Property::Text(String::from("1/250")));
+        let mut parsed = Xmp::parse(&located.to_packet()).unwrap();
+        assert!(parsed.get("exif:GPSLatitude").is_some());
+        parsed.strip_location();
+        assert!(parsed.get("exif:GPSLatitude").is_none());
+        assert!(parsed.get("exif:ExposureTime").is_some());
+
+        assert!(Xmp::parse("<x:xmpmeta><rdf:RDF></x:xmpmeta>").is_err());
+    }
+}
@@ -0,0 +1,297 @@
+//! Image quality metrics
+//!
+//! All metrics are computed using normalized values, so images with different types (for
+//! example `u8` and `f32`) can be compared directly.
+
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::color::Color;
+use crate::error::Error;
+use crate::image::Image;
+use crate::ty::Type;
+
+const K1: f64 = 0.01;
+const K2: f64 = 0.03;
+const SIGMA: f64 = 1.5;
+const MS_SSIM_WEIGHTS: [f64; 5] = [0.0448, 0.2856, 0.3001, 0.2363, 0.1333];
+
+/// A single channel of normalized values
+#[derive(Clone)]
+struct Plane {
+    width: usize,
+    height: usize,
+    data: Vec<f64>,
+}
+
+impl Plane {
+    fn from_image<T: Type, C: Color, I: Image<T, C>>(image: &I, c: usize) -> Plane {
+        let channels = C::channels();
+        Plane {
+            width: image.width(),
+            height: image.height(),
+            data: image
+                .data()
+                .iter()
+                .skip(c)
+                .step_by(channels)
+                .map(|x| T::to_f(x))
+                .collect(),
+        }
+    }
+
+    fn zip(&self, other: &Plane, f: impl Fn(f64, f64) -> f64) -> Plane {
+        Plane {
+            width: self.width,
+            height: self.height,
+            data: self
+                .data
+                .iter()
+                .zip(&other.data)
+                .map(|(a, b)| f(*a, *b))
+                .collect(),
+        }
+    }
+
+    /// Separable gaussian blur, clamping coordinates at the edges
+    fn blur(&self, kernel: &[f64]) -> Plane {
+        let (w, h) = (self.width, self.height);
+        let r = (kernel.len() / 2) as isize;
+        let mut tmp = vec![0.0; w * h];
+        for y in 0..h {
+            for x in 0..w {
+                tmp[y * w + x] = kernel
+                    .iter()
+                    .enumerate()
+                    .map(|(k, v)| {
+                        let i = (x as isize + k as isize - r).max(0).min(w as isize - 1) as usize;
+                        v * self.data[y * w + i]
+                    })
+                    .sum();
+            }
+        }
+
+        let mut data = vec![0.0; w * h];
+        for y in 0..h {
+            for x in 0..w {
+                data[y * w + x] = kernel
+                    .iter()
+                    .enumerate()
+                    .map(|(k, v)| {
+                        let j = (y as isize + k as isize - r).max(0).min(h as isize - 1) as usize;
+                        v * tmp[j * w + x]
+                    })
+                    .sum();
+            }
+        }
+
+        Plane {
+            width: w,
+            height: h,
+            data,
+        }
+    }
+
+    /// Downsample by a factor of two by averaging 2x2 blocks
+    fn half(&self) -> Plane {
+        let (w, h) = (self.width / 2, self.height / 2);
+        let mut data = Vec::with_capacity(w * h);
+        for y in 0..h {
+            for x in 0..w {
+                let i = 2 * y * self.width + 2 * x;
+                data.push(
+                    (self.data[i]
+                        + self.data[i + 1]
+                        + self.data[i + self.width]
+                        + self.data[i + self.width + 1])
+                        / 4.0,
+                );
+            }
+        }
+        Plane {
+            width: w,
+            height: h,
+            data,
+        }
+    }
+}
+
+fn gaussian_kernel() -> Vec<f64> {
+    let k: Vec<f64> = (-5..=5)
+        .map(|i: i32| (-((i * i) as f64) / (2.0 * SIGMA * SIGMA)).exp())
+        .collect();
+    let sum: f64 = k.iter().sum();
+    k.into_iter().map(|x| x / sum).collect()
+}
+
+fn check_size<T: Type, U: Type, C: Color, A: Image<T, C>, B: Image<U, C>>(
+    a: &A,
+    b: &B,
+) -> Result<(), Error> {
+    if a.width() != b.width() || a.height() != b.height() {
+        return Err(Error::Message(format!(
+            "Image sizes do not match: {}x{} and {}x{}",
+            a.width(),
+            a.height(),
+            b.width(),
+            b.height()
+        )));
+    }
+
+    Ok(())
+}
+
+/// The number of channels to compare, alpha channels are ignored
+fn color_channels<C: Color>() -> usize {
+    if C::has_alpha() {
+        C::channels() - 1
+    } else {
+        C::channels()
+    }
+}
+
+/// Returns the mean SSIM and mean contrast-structure values of two planes
+fn ssim_plane(a: &Plane, b: &Plane, kernel: &[f64]) -> (f64, f64) {
+    let c1 = K1 * K1;
+    let c2 = K2 * K2;
+
+    let mu_a = a.blur(kernel);
+    let mu_b = b.blur(kernel);
+    let aa = a.zip(a, |x, y| x * y).blur(kernel);
+    let bb = b.zip(b, |x, y| x * y).blur(kernel);
+    let ab = a.zip(b, |x, y| x * y).blur(kernel);
+
+    let n = a.data.len().max(1) as f64;
+    let mut ssim = 0.0;
+    let mut cs = 0.0;
+    for i in 0..a.data.len() {
+        let (ma, mb) = (mu_a.data[i], mu_b.data[i]);
+        let var_a = aa.data[i] - ma * ma;
+        let var_b = bb.data[i] - mb * mb;
+        let cov = ab.data[i] - ma * mb;
+        let contrast = (2.0 * cov + c2) / (var_a + var_b + c2);
+        ssim += (2.0 * ma * mb + c1) / (ma * ma + mb * mb + c1) * contrast;
+        cs += contrast;
+    }
+
+    (ssim / n, cs / n)
+}
+
+/// Mean squared error between two images
+pub fn mse<T: Type, U: Type, C: Color, A: Image<T, C>, B: Image<U, C>>(
+    a: &A,
+    b: &B,
+) -> Result<f64, Error> {
+    check_size(a, b)?;
+
+    let channels = color_channels::<C>();
+    let mut sum = 0.0;
+    for (pa, pb) in a
+        .data()
+        .chunks(C::channels())
+        .zip(b.data().chunks(C::channels()))
+    {
+        for c in 0..channels {
+            let d = T::to_f(&pa[c]) - U::to_f(&pb[c]);
+            sum += d * d;
+        }
+    }
+
+    Ok(sum / (a.width() * a.height() * channels).max(1) as f64)
+}
+
+/// Peak signal-to-noise ratio in decibels, returns infinity when the images are identical
+pub fn psnr<T: Type, U: Type, C: Color, A: Image<T, C>, B: Image<U, C>>(
+    a: &A,
+    b: &B,
+) -> Result<f64, Error> {
+    let mse = mse(a, b)?;
+    if mse == 0.0 {
+        return Ok(f64::INFINITY);
+    }
+
+    Ok(-10.0 * mse.log10())
+}
+
+/// Structural similarity index, using an 11x11 gaussian window and averaged over all color
+/// channels
+pub fn ssim<T: Type, U: Type, C: Color, A: Image<T, C>, B: Image<U, C>>(
+    a: &A,
+    b: &B,
+) -> Result<f64, Error> {
+    check_size(a, b)?;
+
+    let kernel = gaussian_kernel();
+    let channels = color_channels::<C>();
+    let sum: f64 = (0..channels)
+        .map(|c| ssim_plane(&Plane::from_image(a, c), &Plane::from_image(b, c), &kernel).0)
+        .sum();
+    Ok(sum / channels as f64)
+}
+
+/// Multi-scale structural similarity index using five scales, fewer scales are used when the
+/// images are too small to be downsampled
+pub fn ms_ssim<T: Type, U: Type, C: Color, A: Image<T, C>, B: Image<U, C>>(
+    a: &A,
+    b: &B,
+) -> Result<f64, Error> {
+    check_size(a, b)?;
+
+    let kernel = gaussian_kernel();
+    let mut scales = 1;
+    let mut size = a.width().min(a.height());
+    while scales < MS_SSIM_WEIGHTS.len() && size / 2 >= kernel.len() {
+        size /= 2;
+        scales += 1;
+    }
+    let total: f64 = MS_SSIM_WEIGHTS[..scales].iter().sum();
+
+    let channels = color_channels::<C>();
+    let mut sum = 0.0;
+    for c in 0..channels {
+        let mut pa = Plane::from_image(a, c);
+        let mut pb = Plane::from_image(b, c);
+        let mut value = 1.0;
+        for (i, w) in MS_SSIM_WEIGHTS[..scales].iter().enumerate() {
+            let (ssim, cs) = ssim_plane(&pa, &pb, &kernel);
+            let x = if i == scales - 1 { ssim } else { cs };
+            value *= x.max(0.0).powf(w / total);
+            pa = pa.half();
+            pb = pb.half();
+        }
+        sum += value;
+    }
+
+    Ok(sum / channels as f64)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{metrics, Gray, Image, ImageBuf, Rgb};
+
+    #[test]
+    fn test_metrics() {
+        let a: ImageBuf<u8, Rgb> = ImageBuf::new_from(
+            32,
+            32,
+            (0..32 * 32 * 3).map(|i| ((i * 7) % 256) as u8).collect(),
+        );
+        let mut b: ImageBuf<f32, Rgb> = ImageBuf::new(32, 32);
+        a.convert_type(&mut b);
+        assert!(metrics::psnr(&a, &b).unwrap() > 100.0);
+        assert_eq!(metrics::psnr(&a, &a).unwrap(), f64::INFINITY);
+        assert!((metrics::ssim(&a, &b).unwrap() - 1.0).abs() < 1e-9);
+        assert!((metrics::ms_ssim(&a, &b).unwrap() - 1.0).abs() < 1e-9);
+
+        let c: ImageBuf<u8, Rgb> = ImageBuf::new(32, 32);
+        assert!(metrics::psnr(&a, &c).unwrap() < 10.0);
+        assert!(metrics::ssim(&a, &c).unwrap() < 0.1);
+
+        let d: ImageBuf<u8, Gray> = ImageBuf::new_from(2, 1, vec![0, 255]);
+        let e: ImageBuf<u8, Gray> = ImageBuf::new_from(2, 1, vec![0, 0]);
+        assert!((metrics::mse(&d, &e).unwrap() - 0.5).abs() < 1e-9);
+        let f: ImageBuf<u8, Gray> = ImageBuf::new(3, 1);
+        assert!(metrics::psnr(&d, &f).is_err());
+    }
+}
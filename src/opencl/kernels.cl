@@ -0,0 +1,116 @@
+// Images are stored as normalized floats with interleaved channels, pixels outside of the image
+// are clamped to the nearest edge
+
+int pixel(int x, int y, uint width, uint height, uint channels) {
+    x = clamp(x, 0, (int)width - 1);
+    y = clamp(y, 0, (int)height - 1);
+    return (y * (int)width + x) * (int)channels;
+}
+
+__kernel void convolve(
+    __global const float *src,
+    __global float *dst,
+    __global const float *weights,
+    uint width,
+    uint height,
+    uint channels,
+    uint rows,
+    uint cols
+) {
+    int x = get_global_id(0);
+    int y = get_global_id(1);
+    if (x >= (int)width || y >= (int)height) {
+        return;
+    }
+
+    int r2 = rows / 2;
+    int c2 = cols / 2;
+    for (uint c = 0; c < channels; c++) {
+        float sum = 0.0f;
+        for (int ky = -r2; ky <= r2; ky++) {
+            for (int kx = -c2; kx <= c2; kx++) {
+                float w = weights[(ky + r2) * (int)cols + kx + c2];
+                sum += w * src[pixel(x + kx, y + ky, width, height, channels) + c];
+            }
+        }
+        dst[pixel(x, y, width, height, channels) + c] = sum;
+    }
+}
+
+// One pass of a separable minimum or maximum over a square structuring element
+__kernel void morphology(
+    __global const float *src,
+    __global float *dst,
+    uint width,
+    uint height,
+    uint channels,
+    int radius,
+    int dilate,
+    int horizontal
+) {
+    int x = get_global_id(0);
+    int y = get_global_id(1);
+    if (x >= (int)width || y >= (int)height) {
+        return;
+    }
+
+    int dx = horizontal ? 1 : 0;
+    int dy = horizontal ? 0 : 1;
+    for (uint c = 0; c < channels; c++) {
+        float value = src[pixel(x, y, width, height, channels) + c];
+        for (int i = -radius; i <= radius; i++) {
+            float v = src[pixel(x + i * dx, y + i * dy, width, height, channels) + c];
+            value = dilate ? fmax(value, v) : fmin(value, v);
+        }
+        dst[pixel(x, y, width, height, channels) + c] = value;
+    }
+}
+
+// Non-local means, each pixel is the average of the pixels in the search window weighted by
+// how similar the patches around them are
+__kernel void nl_means(
+    __global const float *src,
+    __global float *dst,
+    uint width,
+    uint height,
+    uint channels,
+    int patch,
+    int search,
+    float inv_h2
+) {
+    int x = get_global_id(0);
+    int y = get_global_id(1);
+    if (x >= (int)width || y >= (int)height) {
+        return;
+    }
+
+    float patch_size = (float)((2 * patch + 1) * (2 * patch + 1) * (int)channels);
+    float sum[4] = {0.0f, 0.0f, 0.0f, 0.0f};
+    float total = 0.0f;
+    for (int sy = -search; sy <= search; sy++) {
+        for (int sx = -search; sx <= search; sx++) {
+            float distance = 0.0f;
+            for (int py = -patch; py <= patch; py++) {
+                for (int px = -patch; px <= patch; px++) {
+                    int a = pixel(x + px, y + py, width, height, channels);
+                    int b = pixel(x + sx + px, y + sy + py, width, height, channels);
+                    for (uint c = 0; c < channels; c++) {
+                        float d = src[a + c] - src[b + c];
+                        distance += d * d;
+                    }
+                }
+            }
+
+            float w = exp(-distance / patch_size * inv_h2);
+            int q = pixel(x + sx, y + sy, width, height, channels);
+            for (uint c = 0; c < channels; c++) {
+                sum[c] += w * src[q + c];
+            }
+            total += w;
+        }
+    }
+
+    for (uint c = 0; c < channels; c++) {
+        dst[pixel(x, y, width, height, channels) + c] = sum[c] / total;
+    }
+}
@@ -0,0 +1,322 @@
+//! GPU accelerated filters using OpenCL, for machines where `wgpu` has no usable driver such as
+//! older servers and integrated GPUs
+//!
+//! Like the `gpu` module, images are uploaded as normalized `f32` buffers so any `ImageBuf` type
+//! can be processed and results are converted back to the original type when downloaded.
+//!
+//! ```rust,no_run
+//! use image2::{kernel, opencl, ImageBuf, Rgb};
+//!
+//! let ctx = opencl::Context::new()?;
+//! let image: ImageBuf<u8, Rgb> = ImageBuf::new(3840, 2160);
+//! let blurred = opencl::convolve(&ctx, &image, &kernel::gaussian_5x5())?;
+//! let denoised = opencl::nl_means(&ctx, &image, 0.1, 1, 5)?;
+//! # Ok::<(), image2::Error>(())
+//! ```
+
+use std::marker::PhantomData;
+
+use crate::color::Color;
+use crate::error::Error;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::kernel::Kernel;
+use crate::ty::Type;
+
+/// Owns the OpenCL queue and the program containing the filters
+pub struct Context {
+    queue: ocl::Queue,
+    program: ocl::Program,
+}
+
+/// An image stored on an OpenCL device as normalized `f32` values
+pub struct ClImage<C: Color> {
+    buffer: ocl::Buffer<f32>,
+    width: usize,
+    height: usize,
+    _color: PhantomData<C>,
+}
+
+impl<C: Color> ClImage<C> {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The underlying buffer
+    pub fn buffer(&self) -> &ocl::Buffer<f32> {
+        &self.buffer
+    }
+
+    fn len(&self) -> usize {
+        self.width * self.height * C::channels()
+    }
+}
+
+impl Context {
+    /// Create a new context using the first GPU of the first platform, falling back to any
+    /// device when there is no GPU
+    pub fn new() -> Result<Context, Error> {
+        let platform = match ocl::Platform::list().into_iter().next() {
+            Some(platform) => platform,
+            None => return Err(Error::Message(String::from("No OpenCL platform found"))),
+        };
+
+        let device = match ocl::Device::list(platform, Some(ocl::flags::DEVICE_TYPE_GPU)) {
+            Ok(devices) if !devices.is_empty() => devices[0],
+            _ => ocl::Device::first(platform)?,
+        };
+
+        let context = ocl::Context::builder()
+            .platform(platform)
+            .devices(device)
+            .build()?;
+        Self::from_queue(ocl::Queue::new(&context, device, None)?)
+    }
+
+    /// Create a new context from an existing queue, this allows buffers to be shared with an
+    /// application's own OpenCL code
+    pub fn from_queue(queue: ocl::Queue) -> Result<Context, Error> {
+        let program = ocl::Program::builder()
+            .src(include_str!("kernels.cl"))
+            .devices(queue.device())
+            .build(&queue.context())?;
+        Ok(Context { queue, program })
+    }
+
+    pub fn queue(&self) -> &ocl::Queue {
+        &self.queue
+    }
+
+    fn storage<C: Color>(&self, width: usize, height: usize) -> Result<ClImage<C>, Error> {
+        let buffer = ocl::Buffer::builder()
+            .queue(self.queue.clone())
+            .len(width * height * C::channels())
+            .build()?;
+
+        Ok(ClImage {
+            buffer,
+            width,
+            height,
+            _color: PhantomData,
+        })
+    }
+
+    /// Build a kernel from the program that runs once for each pixel of `image`, the source and
+    /// destination buffers are the first arguments followed by the image shape
+    fn kernel<'a, C: Color>(
+        &'a self,
+        name: &str,
+        image: &'a ClImage<C>,
+        dest: &'a ClImage<C>,
+    ) -> ocl::builders::KernelBuilder<'a> {
+        let mut builder = ocl::Kernel::builder();
+        builder
+            .program(&self.program)
+            .name(name)
+            .queue(self.queue.clone())
+            .global_work_size([image.width, image.height])
+            .arg(&image.buffer)
+            .arg(&dest.buffer);
+        builder
+    }
+
+    /// Copy an image to the device
+    pub fn upload<T: Type, C: Color, I: Image<T, C>>(
+        &self,
+        image: &I,
+    ) -> Result<ClImage<C>, Error> {
+        let data: Vec<f32> = image.data().iter().map(|x| T::to_f(x) as f32).collect();
+        let buffer = ocl::Buffer::builder()
+            .queue(self.queue.clone())
+            .len(data.len())
+            .copy_host_slice(&data)
+            .build()?;
+
+        Ok(ClImage {
+            buffer,
+            width: image.width(),
+            height: image.height(),
+            _color: PhantomData,
+        })
+    }
+
+    /// Copy an image from the device, blocking until all pending work is finished
+    pub fn download<T: Type, C: Color>(&self, image: &ClImage<C>) -> Result<ImageBuf<T, C>, Error> {
+        let mut data = vec![0.0f32; image.len()];
+        image.buffer.read(&mut data).enq()?;
+        let data = data.iter().map(|x| T::from_f(f64::from(*x))).collect();
+        Ok(ImageBuf::new_from(image.width, image.height, data))
+    }
+
+    /// Convolve an image with the given kernel, pixels outside of the image are clamped to the
+    /// nearest edge
+    pub fn convolve<C: Color>(
+        &self,
+        image: &ClImage<C>,
+        kernel: &Kernel,
+    ) -> Result<ClImage<C>, Error> {
+        let dest = self.storage(image.width, image.height)?;
+        let weights: Vec<f32> = kernel
+            .data
+            .iter()
+            .flat_map(|row| row.iter().map(|x| *x as f32))
+            .collect();
+        let weights = ocl::Buffer::builder()
+            .queue(self.queue.clone())
+            .len(weights.len())
+            .copy_host_slice(&weights)
+            .build()?;
+
+        let convolve = self
+            .kernel("convolve", image, &dest)
+            .arg(&weights)
+            .arg(image.width as u32)
+            .arg(image.height as u32)
+            .arg(C::channels() as u32)
+            .arg(kernel.rows as u32)
+            .arg(kernel.cols as u32)
+            .build()?;
+        unsafe { convolve.enq()? };
+        Ok(dest)
+    }
+
+    fn morphology<C: Color>(
+        &self,
+        image: &ClImage<C>,
+        radius: usize,
+        dilate: bool,
+    ) -> Result<ClImage<C>, Error> {
+        // The square structuring element is separable, so rows and columns are processed
+        // separately
+        let tmp = self.storage(image.width, image.height)?;
+        let dest = self.storage(image.width, image.height)?;
+        for (from, to, horizontal) in [(image, &tmp, true), (&tmp, &dest, false)] {
+            let pass = self
+                .kernel("morphology", from, to)
+                .arg(image.width as u32)
+                .arg(image.height as u32)
+                .arg(C::channels() as u32)
+                .arg(radius as i32)
+                .arg(dilate as i32)
+                .arg(horizontal as i32)
+                .build()?;
+            unsafe { pass.enq()? };
+        }
+        Ok(dest)
+    }
+
+    /// Replace each pixel with the minimum of the `(2 * radius + 1)` square around it
+    pub fn erode<C: Color>(&self, image: &ClImage<C>, radius: usize) -> Result<ClImage<C>, Error> {
+        self.morphology(image, radius, false)
+    }
+
+    /// Replace each pixel with the maximum of the `(2 * radius + 1)` square around it
+    pub fn dilate<C: Color>(&self, image: &ClImage<C>, radius: usize) -> Result<ClImage<C>, Error> {
+        self.morphology(image, radius, true)
+    }
+
+    /// Non-local means denoising, `h` controls the strength of the filter relative to the
+    /// normalized pixel values. Patches of `(2 * patch_radius + 1)` pixels are compared within a
+    /// `(2 * search_radius + 1)` window around each pixel
+    pub fn nl_means<C: Color>(
+        &self,
+        image: &ClImage<C>,
+        h: f32,
+        patch_radius: usize,
+        search_radius: usize,
+    ) -> Result<ClImage<C>, Error> {
+        if C::channels() > 4 {
+            return Err(Error::InvalidColor);
+        }
+
+        let dest = self.storage(image.width, image.height)?;
+        let nl_means = self
+            .kernel("nl_means", image, &dest)
+            .arg(image.width as u32)
+            .arg(image.height as u32)
+            .arg(C::channels() as u32)
+            .arg(patch_radius as i32)
+            .arg(search_radius as i32)
+            .arg(1.0 / (h * h).max(f32::EPSILON))
+            .build()?;
+        unsafe { nl_means.enq()? };
+        Ok(dest)
+    }
+}
+
+/// Upload, convolve and download an image
+pub fn convolve<T: Type, C: Color, I: Image<T, C>>(
+    ctx: &Context,
+    image: &I,
+    kernel: &Kernel,
+) -> Result<ImageBuf<T, C>, Error> {
+    let src = ctx.upload(image)?;
+    ctx.download(&ctx.convolve(&src, kernel)?)
+}
+
+/// Upload, erode and download an image
+pub fn erode<T: Type, C: Color, I: Image<T, C>>(
+    ctx: &Context,
+    image: &I,
+    radius: usize,
+) -> Result<ImageBuf<T, C>, Error> {
+    let src = ctx.upload(image)?;
+    ctx.download(&ctx.erode(&src, radius)?)
+}
+
+/// Upload, dilate and download an image
+pub fn dilate<T: Type, C: Color, I: Image<T, C>>(
+    ctx: &Context,
+    image: &I,
+    radius: usize,
+) -> Result<ImageBuf<T, C>, Error> {
+    let src = ctx.upload(image)?;
+    ctx.download(&ctx.dilate(&src, radius)?)
+}
+
+/// Upload, denoise and download an image
+pub fn nl_means<T: Type, C: Color, I: Image<T, C>>(
+    ctx: &Context,
+    image: &I,
+    h: f32,
+    patch_radius: usize,
+    search_radius: usize,
+) -> Result<ImageBuf<T, C>, Error> {
+    let src = ctx.upload(image)?;
+    ctx.download(&ctx.nl_means(&src, h, patch_radius, search_radius)?)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{kernel, opencl, Gray, Image, ImageBuf, Rgb};
+
+    #[test]
+    fn test_opencl() {
+        // Skip when no device is available
+        let ctx = match opencl::Context::new() {
+            Ok(ctx) => ctx,
+            Err(_) => return,
+        };
+
+        let mut image: ImageBuf<f32, Gray> = ImageBuf::new(5, 5);
+        image.set(2, 2, 0, 1.0);
+
+        let dilated = opencl::dilate(&ctx, &image, 1).unwrap();
+        assert_eq!(dilated.get(1, 3, 0), 1.0);
+        assert_eq!(dilated.get(0, 2, 0), 0.0);
+        let eroded = opencl::erode(&ctx, &dilated, 1).unwrap();
+        assert_eq!(eroded, image);
+
+        let blurred = opencl::convolve(&ctx, &image, &kernel::gaussian_3x3()).unwrap();
+        assert!((blurred.data().iter().sum::<f32>() - 1.0).abs() < 1e-5);
+
+        // A flat image stays the same
+        let mut flat: ImageBuf<u8, Rgb> = ImageBuf::new(8, 8);
+        flat.data_mut().iter_mut().for_each(|x| *x = 100);
+        assert_eq!(opencl::nl_means(&ctx, &flat, 0.1, 1, 3).unwrap(), flat);
+    }
+}
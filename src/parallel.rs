@@ -0,0 +1,180 @@
+//! Control how many threads are used to process images
+//!
+//! With the `parallel` feature, filters, convolution, resizing, color conversion and the
+//! per-pixel maps in `Image` split images into rows and process them using rayon. By default
+//! rayon's global thread pool is used, `set_threads` changes the number of threads for the whole
+//! program and `with_threads` for a single call:
+//!
+//! ```rust
+//! use image2::{parallel, Filter, ImageBuf, Rgb, filter::Invert};
+//!
+//! let image: ImageBuf<f32, Rgb> = ImageBuf::new(64, 64);
+//! let mut dest = image.new_like();
+//!
+//! // Use two threads for everything
+//! parallel::set_threads(2);
+//!
+//! // Run a single filter on the calling thread
+//! parallel::with_threads(1, || Invert.eval(&mut dest, &[&image]));
+//!
+//! // Go back to rayon's global thread pool
+//! parallel::set_threads(0);
+//! ```
+//!
+//! Without the `parallel` feature everything runs on the calling thread and these settings are
+//! ignored.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(feature = "parallel")]
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "parallel")]
+use rayon::{prelude::*, ThreadPool};
+
+static THREADS: AtomicUsize = AtomicUsize::new(0);
+
+/// Maximum number of thread pools kept for reuse
+#[cfg(feature = "parallel")]
+const MAX_POOLS: usize = 4;
+
+/// Thread pools and their number of threads, the most recently used pool is last
+#[cfg(feature = "parallel")]
+static POOLS: Mutex<Vec<(usize, Arc<ThreadPool>)>> = Mutex::new(Vec::new());
+
+/// Set the number of threads used by image operations, 0 uses rayon's global thread pool
+pub fn set_threads(threads: usize) {
+    THREADS.store(threads, Ordering::Relaxed);
+}
+
+/// Number of threads used by image operations on the current thread
+#[cfg(feature = "parallel")]
+pub fn threads() -> usize {
+    match THREADS.load(Ordering::Relaxed) {
+        0 => rayon::current_num_threads(),
+        _ if rayon::current_thread_index().is_some() => rayon::current_num_threads(),
+        n => n,
+    }
+}
+
+/// Number of threads used by image operations on the current thread
+#[cfg(not(feature = "parallel"))]
+pub fn threads() -> usize {
+    1
+}
+
+/// Returns a thread pool with `threads` threads. Pools are reused, but only the `MAX_POOLS` most
+/// recently used pools are kept so that using many different numbers of threads doesn't keep
+/// every pool's threads alive, the threads of a pool stop once it is no longer used
+#[cfg(feature = "parallel")]
+fn pool(threads: usize) -> Option<Arc<ThreadPool>> {
+    let mut pools = POOLS.lock().unwrap_or_else(|err| err.into_inner());
+    let pool = match pools.iter().position(|(n, _)| *n == threads) {
+        Some(index) => pools.remove(index).1,
+        None => {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .ok()?;
+            if pools.len() == MAX_POOLS {
+                pools.remove(0);
+            }
+            Arc::new(pool)
+        }
+    };
+    pools.push((threads, pool.clone()));
+    Some(pool)
+}
+
+/// Run `f` using `threads` threads for image operations, regardless of `set_threads`
+#[cfg(feature = "parallel")]
+pub fn with_threads<R: Send, F: Send + FnOnce() -> R>(threads: usize, f: F) -> R {
+    match pool(threads.max(1)) {
+        Some(pool) => pool.install(f),
+        None => f(),
+    }
+}
+
+/// Run `f` using `threads` threads for image operations, regardless of `set_threads`
+#[cfg(not(feature = "parallel"))]
+pub fn with_threads<R: Send, F: Send + FnOnce() -> R>(_threads: usize, f: F) -> R {
+    f()
+}
+
+/// Run `f` in the thread pool selected by `set_threads`, unless it is already running in a
+/// thread pool
+#[cfg(feature = "parallel")]
+pub(crate) fn install<R: Send, F: Send + FnOnce() -> R>(f: F) -> R {
+    match THREADS.load(Ordering::Relaxed) {
+        0 => f(),
+        _ if rayon::current_thread_index().is_some() => f(),
+        n => with_threads(n, f),
+    }
+}
+
+/// Call `f` with the index and data of each row, where rows are `row_len` values long
+#[cfg(feature = "parallel")]
+pub(crate) fn for_each_row<T: Send, F: Sync + Send + Fn(usize, &mut [T])>(
+    data: &mut [T],
+    row_len: usize,
+    f: F,
+) {
+    if row_len == 0 {
+        return;
+    }
+
+    install(|| {
+        data.par_chunks_mut(row_len)
+            .enumerate()
+            .for_each(|(y, row)| f(y, row))
+    })
+}
+
+/// Call `f` with the index and data of each row, where rows are `row_len` values long
+#[cfg(not(feature = "parallel"))]
+pub(crate) fn for_each_row<T: Send, F: Sync + Send + Fn(usize, &mut [T])>(
+    data: &mut [T],
+    row_len: usize,
+    f: F,
+) {
+    if row_len == 0 {
+        return;
+    }
+
+    data.chunks_mut(row_len)
+        .enumerate()
+        .for_each(|(y, row)| f(y, row))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parallel_rows() {
+        let mut data = vec![0usize; 35];
+        for_each_row(&mut data, 10, |y, row| {
+            for x in row.iter_mut() {
+                *x = y;
+            }
+        });
+        assert_eq!(&data[8..12], &[0, 0, 1, 1]);
+        assert_eq!(data[34], 3);
+
+        #[cfg(feature = "parallel")]
+        with_threads(3, || assert_eq!(threads(), 3));
+        assert_eq!(with_threads(1, threads), 1);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_pools() {
+        // Pools are reused, but only a few of them are kept
+        let a = pool(7).unwrap();
+        assert!(Arc::ptr_eq(&a, &pool(7).unwrap()));
+        for n in 1..=10 {
+            assert_eq!(with_threads(n, threads), n);
+        }
+        assert!(POOLS.lock().unwrap().len() <= MAX_POOLS);
+    }
+}
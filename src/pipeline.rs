@@ -0,0 +1,651 @@
+//! Lazy image pipelines
+//!
+//! A `Pipeline` records a chain of operations without executing them. When `eval` is called
+//! consecutive per-pixel operations are fused into a single pass over the image, and are also
+//! merged into the preceding neighborhood operation (blur, convolution or resize). For example,
+//! `blur -> gamma -> invert -> resize -> gamma` applies the first `gamma` and `invert` while
+//! writing the output of the blur and the second `gamma` while resizing, instead of allocating an
+//! intermediate image for every step.
+//!
+//! Each remaining pass still reads and writes a whole image. For long chains on large images
+//! `tiled` evaluates the pipeline in cache sized tiles instead, which reduces memory bandwidth when
+//! many threads are used.
+//!
+//! ```rust
+//! use image2::{ImageBuf, Image, Rgb};
+//!
+//! let image: ImageBuf<u8, Rgb> = ImageBuf::new(64, 64);
+//! let output = image.lazy().blur(2.0).gamma(2.2).resize(32, 32).eval();
+//! assert_eq!(output.width(), 32);
+//! ```
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::color::Color;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::kernel::Kernel;
+use crate::parallel;
+use crate::ty::Type;
+
+use core::marker::PhantomData;
+
+type PointFn = Box<dyn Fn(f64) -> f64 + Sync + Send>;
+
+/// Operations that need to access neighboring pixels and therefore require their input to be
+/// materialized
+pub(crate) enum Op {
+    Source,
+    Blur(f64),
+    Convolve(Kernel),
+    Resize(usize, usize),
+}
+
+/// A neighborhood operation followed by any number of fused per-pixel operations
+pub(crate) struct Stage {
+    pub(crate) op: Op,
+    pub(crate) points: Vec<PointFn>,
+}
+
+impl Stage {
+    #[inline]
+    fn point(&self, mut f: f64) -> f64 {
+        for p in &self.points {
+            f = p(f);
+        }
+        f
+    }
+}
+
+/// A lazily evaluated chain of image operations, created using `Image::lazy`
+pub struct Pipeline<'a, T: Type, C: Color, I: Image<T, C>> {
+    source: &'a I,
+    pub(crate) stages: Vec<Stage>,
+    tile: Option<(usize, usize)>,
+    _type: PhantomData<(T, C)>,
+}
+
+impl<'a, T: Type, C: Color, I: Image<T, C>> Pipeline<'a, T, C, I> {
+    /// Create a new pipeline reading from `source`
+    pub fn new(source: &'a I) -> Self {
+        Pipeline {
+            source,
+            stages: vec![Stage {
+                op: Op::Source,
+                points: Vec::new(),
+            }],
+            tile: None,
+            _type: PhantomData,
+        }
+    }
+
+    fn push_op(mut self, op: Op) -> Self {
+        self.stages.push(Stage {
+            op,
+            points: Vec::new(),
+        });
+        self
+    }
+
+    /// Apply `f` to each normalized component
+    pub fn map<F: 'static + Sync + Send + Fn(f64) -> f64>(mut self, f: F) -> Self {
+        let index = self.stages.len() - 1;
+        self.stages[index].points.push(Box::new(f));
+        self
+    }
+
+    /// Apply gamma correction
+    pub fn gamma(self, gamma: f64) -> Self {
+        self.map(move |f| f.powf(1.0 / gamma))
+    }
+
+    /// Invert each component
+    pub fn invert(self) -> Self {
+        self.map(|f| 1.0 - f)
+    }
+
+    /// Add a constant to each component
+    pub fn brightness(self, amount: f64) -> Self {
+        self.map(move |f| f + amount)
+    }
+
+    /// Scale the distance of each component from 0.5
+    pub fn contrast(self, amount: f64) -> Self {
+        self.map(move |f| (f - 0.5) * amount + 0.5)
+    }
+
+    /// Set each component to 0 or 1 depending on whether it is above `threshold`
+    pub fn threshold(self, threshold: f64) -> Self {
+        self.map(move |f| if f > threshold { 1.0 } else { 0.0 })
+    }
+
+    /// Gaussian blur with the given standard deviation
+    pub fn blur(self, sigma: f64) -> Self {
+        self.push_op(Op::Blur(sigma))
+    }
+
+    /// Convolve with the given kernel
+    pub fn convolve(self, kernel: Kernel) -> Self {
+        self.push_op(Op::Convolve(kernel))
+    }
+
+    /// Resize using bilinear interpolation
+    pub fn resize(self, width: usize, height: usize) -> Self {
+        self.push_op(Op::Resize(width, height))
+    }
+
+    /// Evaluate the pipeline in tiles that fit in the L2 cache, see `tile_size`
+    pub fn tiled(self) -> Self {
+        let (width, height) = default_tile_size(C::channels());
+        self.tile_size(width, height)
+    }
+
+    /// Evaluate the pipeline one tile of the output at a time: every operation is applied to a
+    /// tile, and the parts of the input it depends on, before moving on to the next tile. Tiles
+    /// are processed in parallel. This is faster for chains of several operations on large
+    /// images because intermediate results stay in the cache, at the cost of computing the pixels
+    /// around the edge of each tile more than once. The result is the same as evaluating the
+    /// whole image at once.
+    pub fn tile_size(mut self, width: usize, height: usize) -> Self {
+        self.tile = Some((width.max(1), height.max(1)));
+        self
+    }
+
+    /// The number of passes over the image needed to evaluate the pipeline
+    pub fn passes(&self) -> usize {
+        self.stages
+            .iter()
+            .map(|s| match s.op {
+                Op::Source => 1,
+                Op::Blur(_) => 2,
+                Op::Convolve(_) | Op::Resize(_, _) => 1,
+            })
+            .sum::<usize>()
+            - if self.stages.len() > 1 && self.stages[0].points.is_empty() {
+                1
+            } else {
+                0
+            }
+    }
+
+    /// Execute the pipeline and return the result
+    pub fn eval(&self) -> ImageBuf<T, C> {
+        self.eval_as()
+    }
+
+    /// Execute the pipeline and return the result using a different type
+    pub fn eval_as<U: Type>(&self) -> ImageBuf<U, C> {
+        let (width, height) = self.output_size();
+        let mut dest = ImageBuf::new(width, height);
+        dest.copy_metadata(self.source);
+        self.eval_into(&mut dest);
+        dest
+    }
+
+    /// Execute the pipeline, writing the result into an existing image. `dest` should have the
+    /// same size as the output of the pipeline.
+    pub fn eval_into<U: Type, J: Image<U, C>>(&self, dest: &mut J) {
+        let stages = &self.stages;
+        let last = stages.len() - 1;
+        if let (Some(tile), true) = (self.tile, last > 0) {
+            self.eval_tiled(dest, tile);
+            return;
+        }
+
+        let source = Whole::new(self.source);
+
+        // The source stage only needs its own pass when it has per-pixel operations
+        let (mut input, start): (ImageBuf<f32, C>, usize) =
+            if stages[0].points.is_empty() && last > 0 {
+                if last == 1 {
+                    run_stage(&stages[1], &source, dest);
+                    return;
+                }
+                (materialize(&stages[1], &source), 2)
+            } else {
+                if last == 0 {
+                    run_stage(&stages[0], &source, dest);
+                    return;
+                }
+                (materialize(&stages[0], &source), 1)
+            };
+
+        for (i, stage) in stages.iter().enumerate().skip(start) {
+            if i == last {
+                run_stage(stage, &Whole::new(&input), dest);
+                return;
+            }
+
+            input = materialize(stage, &Whole::new(&input));
+        }
+    }
+
+    fn eval_tiled<U: Type, J: Image<U, C>>(
+        &self,
+        dest: &mut J,
+        (tile_width, tile_height): (usize, usize),
+    ) {
+        let skip_source = self.stages[0].points.is_empty();
+        let mut passes = Vec::new();
+        for stage in self.stages.iter().skip(skip_source as usize) {
+            let stage_passes = stage.passes();
+            let n = stage_passes.len();
+            for (i, pass) in stage_passes.into_iter().enumerate() {
+                // Per-pixel operations are applied after the last pass of a stage
+                passes.push((pass, if i + 1 == n { Some(stage) } else { None }));
+            }
+        }
+
+        // The input size of each pass, followed by the output size
+        let mut sizes = vec![(self.source.width(), self.source.height())];
+        for (pass, _) in &passes {
+            let (width, height) = sizes[sizes.len() - 1];
+            sizes.push(pass.output_size(width, height));
+        }
+
+        let source = Whole::new(self.source);
+        let (width, _height, channels) = dest.shape();
+        let row_len = width * channels;
+        let last = passes.len() - 1;
+        parallel::for_each_row(dest.data_mut(), row_len * tile_height, |band, data| {
+            let y = band * tile_height;
+            let height = data.len() / row_len;
+            for x in (0..width).step_by(tile_width) {
+                // Work backwards to find the part of each intermediate image that is needed
+                let mut regions = vec![
+                    Region {
+                        x,
+                        y,
+                        width: tile_width.min(width - x),
+                        height,
+                    };
+                    passes.len()
+                ];
+                for i in (1..passes.len()).rev() {
+                    let (w, h) = sizes[i];
+                    regions[i - 1] = passes[i].0.input_region(regions[i], w, h);
+                }
+
+                let mut input: Option<Tile<C>> = None;
+                for (i, ((pass, stage), region)) in passes.iter().zip(&regions).enumerate() {
+                    let point = |f| match stage {
+                        Some(stage) => stage.point(f),
+                        None => f,
+                    };
+
+                    if i == last {
+                        let dest = &mut data[x * channels..];
+                        match &input {
+                            Some(tile) => {
+                                run_region(pass, tile, *region, channels, dest, row_len, &point)
+                            }
+                            None => {
+                                run_region(pass, &source, *region, channels, dest, row_len, &point)
+                            }
+                        }
+                        break;
+                    }
+
+                    let mut image: ImageBuf<f32, C> = ImageBuf::new(region.width, region.height);
+                    let stride = region.width * channels;
+                    let data = image.data_mut();
+                    match &input {
+                        Some(tile) => {
+                            run_region(pass, tile, *region, channels, data, stride, &point)
+                        }
+                        None => run_region(pass, &source, *region, channels, data, stride, &point),
+                    }
+                    let (width, height) = sizes[i + 1];
+                    input = Some(Tile {
+                        image,
+                        region: *region,
+                        width,
+                        height,
+                    });
+                }
+            }
+        });
+    }
+
+    /// The size of the image produced by `eval`
+    pub fn output_size(&self) -> (usize, usize) {
+        self.stages.iter().fold(
+            (self.source.width(), self.source.height()),
+            |(w, h), stage| stage_size(stage, w, h),
+        )
+    }
+}
+
+fn stage_size(stage: &Stage, width: usize, height: usize) -> (usize, usize) {
+    match stage.op {
+        Op::Resize(w, h) => (w, h),
+        _ => (width, height),
+    }
+}
+
+/// Approximate size of the L2 cache of each core, the default tile size keeps the input and output
+/// of a pass within it
+const L2_CACHE_SIZE: usize = 256 * 1024;
+
+fn default_tile_size(channels: usize) -> (usize, usize) {
+    let pixels = L2_CACHE_SIZE / 2 / (channels.max(1) * core::mem::size_of::<f32>());
+    let size = ((pixels as f64).sqrt() as usize / 16 * 16).max(16);
+    (size, size)
+}
+
+/// A rectangle in image coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Region {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+/// Read access to the input of a pass using the coordinates of the whole image
+trait Input: Sync {
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+
+    /// Returns the normalized value of component `c` at (x, y), or 0 outside of the image
+    fn get(&self, x: usize, y: usize, c: usize) -> f64;
+}
+
+struct Whole<'a, T: Type, C: Color, I: Image<T, C>>(&'a I, PhantomData<(T, C)>);
+
+impl<'a, T: Type, C: Color, I: Image<T, C>> Whole<'a, T, C, I> {
+    fn new(image: &'a I) -> Self {
+        Whole(image, PhantomData)
+    }
+}
+
+impl<'a, T: Type, C: Color, I: Image<T, C>> Input for Whole<'a, T, C, I> {
+    fn width(&self) -> usize {
+        self.0.width()
+    }
+
+    fn height(&self) -> usize {
+        self.0.height()
+    }
+
+    fn get(&self, x: usize, y: usize, c: usize) -> f64 {
+        self.0.get_f(x, y, c)
+    }
+}
+
+/// Part of an intermediate image, only the pixels in `region` are stored
+struct Tile<C: Color> {
+    image: ImageBuf<f32, C>,
+    region: Region,
+    width: usize,
+    height: usize,
+}
+
+impl<C: Color> Input for Tile<C> {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn get(&self, x: usize, y: usize, c: usize) -> f64 {
+        if x >= self.width || y >= self.height {
+            return 0.0;
+        }
+
+        self.image.get_f(
+            x.wrapping_sub(self.region.x),
+            y.wrapping_sub(self.region.y),
+            c,
+        )
+    }
+}
+
+/// A single pass over an image, blurs are split into a horizontal and a vertical pass
+enum Pass<'a> {
+    Copy,
+    Blur(Vec<f64>, bool),
+    Convolve(&'a Kernel),
+    Resize(usize, usize),
+}
+
+impl Stage {
+    fn passes(&self) -> Vec<Pass<'_>> {
+        match &self.op {
+            Op::Source => vec![Pass::Copy],
+            Op::Blur(sigma) => {
+                let kernel = gaussian_1d(*sigma);
+                vec![Pass::Blur(kernel.clone(), true), Pass::Blur(kernel, false)]
+            }
+            Op::Convolve(kernel) => vec![Pass::Convolve(kernel)],
+            Op::Resize(width, height) => vec![Pass::Resize(*width, *height)],
+        }
+    }
+}
+
+/// Source pixels and weight of the second pixel used to resample output coordinate `x`
+#[inline]
+fn resample(x: usize, scale: f64, max: usize) -> (usize, usize, f64) {
+    let f = ((x as f64 + 0.5) * scale - 0.5).max(0.0);
+    let x0 = (f.floor() as usize).min(max);
+    (x0, (x0 + 1).min(max), f - x0 as f64)
+}
+
+impl<'a> Pass<'a> {
+    fn output_size(&self, width: usize, height: usize) -> (usize, usize) {
+        match self {
+            Pass::Resize(w, h) => (*w, *h),
+            _ => (width, height),
+        }
+    }
+
+    /// The part of an input of `width` by `height` pixels that is read to compute `region` of
+    /// the output
+    fn input_region(&self, region: Region, width: usize, height: usize) -> Region {
+        let (rx, ry) = match self {
+            Pass::Copy => (0, 0),
+            Pass::Blur(kernel, true) => (kernel.len() / 2, 0),
+            Pass::Blur(kernel, false) => (0, kernel.len() / 2),
+            Pass::Convolve(kernel) => (kernel.cols / 2, kernel.rows / 2),
+            Pass::Resize(w, h) => {
+                let sx = width as f64 / *w as f64;
+                let sy = height as f64 / *h as f64;
+                let (x0, _, _) = resample(region.x, sx, width - 1);
+                let (y0, _, _) = resample(region.y, sy, height - 1);
+                let (_, x1, _) = resample(region.x + region.width - 1, sx, width - 1);
+                let (_, y1, _) = resample(region.y + region.height - 1, sy, height - 1);
+                return Region {
+                    x: x0,
+                    y: y0,
+                    width: x1 + 1 - x0,
+                    height: y1 + 1 - y0,
+                };
+            }
+        };
+
+        let (x, y) = (region.x.saturating_sub(rx), region.y.saturating_sub(ry));
+        Region {
+            x,
+            y,
+            width: (region.x + region.width + rx).min(width) - x,
+            height: (region.y + region.height + ry).min(height) - y,
+        }
+    }
+
+    /// Compute component `c` of output pixel (x, y)
+    #[inline]
+    fn sample<In: Input>(&self, input: &In, x: usize, y: usize, c: usize) -> f64 {
+        match self {
+            Pass::Copy => input.get(x, y, c),
+            Pass::Blur(kernel, horizontal) => {
+                // Coordinates are clamped at the image edges
+                let radius = (kernel.len() / 2) as isize;
+                let max_x = input.width() as isize - 1;
+                let max_y = input.height() as isize - 1;
+                let mut f = 0.0;
+                for (k, w) in kernel.iter().enumerate() {
+                    let o = k as isize - radius;
+                    let (i, j) = if *horizontal {
+                        ((x as isize + o).max(0).min(max_x), y as isize)
+                    } else {
+                        (x as isize, (y as isize + o).max(0).min(max_y))
+                    };
+                    f += w * input.get(i as usize, j as usize, c);
+                }
+                f
+            }
+            Pass::Convolve(kernel) => {
+                // The same as `Kernel::compute_at`, pixels outside of the image are 0
+                let r2 = (kernel.rows / 2) as isize;
+                let c2 = (kernel.cols / 2) as isize;
+                let mut f = 0.0;
+                for ky in -r2..=r2 {
+                    let kr = &kernel.data[(ky + r2) as usize];
+                    for kx in -c2..=c2 {
+                        let i = (x as isize + kx) as usize;
+                        let j = (y as isize + ky) as usize;
+                        f += input.get(i, j, c) * kr[(kx + c2) as usize];
+                    }
+                }
+                f
+            }
+            Pass::Resize(width, height) => {
+                let sx = input.width() as f64 / *width as f64;
+                let sy = input.height() as f64 / *height as f64;
+                let (x0, x1, ax) = resample(x, sx, input.width() - 1);
+                let (y0, y1, ay) = resample(y, sy, input.height() - 1);
+                let top = input.get(x0, y0, c) * (1.0 - ax) + input.get(x1, y0, c) * ax;
+                let bottom = input.get(x0, y1, c) * (1.0 - ax) + input.get(x1, y1, c) * ax;
+                top * (1.0 - ay) + bottom * ay
+            }
+        }
+    }
+}
+
+/// Compute `region` of the output of `pass`, `dest` starts with the first pixel of the region and
+/// rows are `stride` values apart
+fn run_region<In: Input, U: Type, F: Fn(f64) -> f64>(
+    pass: &Pass,
+    input: &In,
+    region: Region,
+    channels: usize,
+    dest: &mut [U],
+    stride: usize,
+    point: &F,
+) {
+    for j in 0..region.height {
+        let row = &mut dest[j * stride..j * stride + region.width * channels];
+        for (i, px) in row.chunks_exact_mut(channels).enumerate() {
+            for (c, item) in px.iter_mut().enumerate() {
+                *item = U::from_f(point(pass.sample(input, region.x + i, region.y + j, c)));
+            }
+        }
+    }
+}
+
+fn run_pass<In: Input, U: Type, C: Color, J: Image<U, C>, F: Sync + Send + Fn(f64) -> f64>(
+    pass: &Pass,
+    input: &In,
+    dest: &mut J,
+    point: F,
+) {
+    let (width, _height, channels) = dest.shape();
+    let row_len = width * channels;
+    parallel::for_each_row(dest.data_mut(), row_len, |y, row| {
+        let region = Region {
+            x: 0,
+            y,
+            width,
+            height: 1,
+        };
+        run_region(pass, input, region, channels, row, row_len, &point)
+    });
+}
+
+fn run_stage<In: Input, U: Type, C: Color, J: Image<U, C>>(
+    stage: &Stage,
+    input: &In,
+    dest: &mut J,
+) {
+    let passes = stage.passes();
+    match passes.as_slice() {
+        [pass] => run_pass(pass, input, dest, |f| stage.point(f)),
+        [first, last] => {
+            let (width, height) = first.output_size(input.width(), input.height());
+            let mut tmp: ImageBuf<f32, C> = ImageBuf::new(width, height);
+            run_pass(first, input, &mut tmp, |f| f);
+            run_pass(last, &Whole::new(&tmp), dest, |f| stage.point(f));
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn materialize<In: Input, C: Color>(stage: &Stage, input: &In) -> ImageBuf<f32, C> {
+    let (width, height) = stage_size(stage, input.width(), input.height());
+    let mut dest = ImageBuf::new(width, height);
+    run_stage(stage, input, &mut dest);
+    dest
+}
+
+fn gaussian_1d(sigma: f64) -> Vec<f64> {
+    let radius = (sigma * 3.0).ceil().max(1.0) as isize;
+    let mut k: Vec<f64> = (-radius..=radius)
+        .map(|i| (-((i * i) as f64) / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let sum: f64 = k.iter().sum();
+    k.iter_mut().for_each(|x| *x /= sum);
+    k
+}
+
+#[cfg(test)]
+mod test {
+    use super::Pipeline;
+    use crate::kernel::{gaussian_3x3, gaussian_5x5};
+    use crate::{Gray, Image, ImageBuf, Rgb};
+
+    #[test]
+    fn test_pipeline_fusion() {
+        let image: ImageBuf<f32, Gray> = ImageBuf::new_from(2, 2, vec![0.0, 0.25, 0.5, 1.0]);
+        let pipeline = image.lazy().invert().map(|f| f * 0.5).invert();
+        assert_eq!(pipeline.passes(), 1);
+        assert_eq!(pipeline.eval().data(), &[0.5, 0.625, 0.75, 1.0]);
+
+        let pipeline = image.lazy().resize(1, 1).invert().gamma(1.0);
+        assert_eq!(pipeline.passes(), 1);
+        assert_eq!(pipeline.eval().data(), &[0.5625]);
+
+        let pipeline = image.lazy().blur(1.0).invert().resize(4, 4).gamma(2.2);
+        assert_eq!(pipeline.passes(), 3);
+        assert_eq!(pipeline.output_size(), (4, 4));
+    }
+
+    fn check_tiled<'a>(pipeline: impl Fn() -> Pipeline<'a, u8, Rgb, ImageBuf<u8, Rgb>>) {
+        let expected = pipeline().eval();
+        assert_eq!(pipeline().tiled().eval(), expected);
+        assert_eq!(pipeline().tile_size(7, 5).eval(), expected);
+        assert_eq!(pipeline().tile_size(1, 100).eval(), expected);
+    }
+
+    #[test]
+    fn test_pipeline_tiled() {
+        let data = (0..37 * 29 * 3).map(|i| (i * 7919 % 256) as u8).collect();
+        let image: ImageBuf<u8, Rgb> = ImageBuf::new_from(37, 29, data);
+
+        check_tiled(|| {
+            image
+                .lazy()
+                .blur(1.5)
+                .gamma(2.2)
+                .convolve(gaussian_3x3())
+                .resize(20, 50)
+                .invert()
+        });
+        check_tiled(|| image.lazy().invert().resize(80, 9).blur(0.8).contrast(1.5));
+        check_tiled(|| image.lazy().convolve(gaussian_5x5()));
+    }
+}
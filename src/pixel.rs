@@ -1,7 +1,11 @@
-use std::ops;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::{fmt, ops};
 
 use crate::{Color, Type};
 
+/// Color spaces from the `palette` crate, which requires the `std` feature
+#[cfg(feature = "std")]
 pub mod colorspace {
     pub use palette::*;
 }
@@ -48,10 +52,11 @@ pub trait Pixel<'a, T: Type, C: Color>: AsRef<[T]> {
         dest
     }
 
-    fn iter(&self) -> std::slice::Iter<T> {
+    fn iter(&self) -> core::slice::Iter<T> {
         self.as_ref().iter()
     }
 
+    #[cfg(feature = "std")]
     fn to_rgb(&self) -> colorspace::LinSrgb {
         let data = self.as_ref();
         palette::LinSrgb::new(
@@ -61,10 +66,12 @@ pub trait Pixel<'a, T: Type, C: Color>: AsRef<[T]> {
         )
     }
 
+    #[cfg(feature = "std")]
     fn from_rgb(px: colorspace::rgb::Rgb) -> PixelVec<f64> {
         PixelVec::new(px.red as f64, px.green as f64, px.blue as f64, 1.0)
     }
 
+    #[cfg(feature = "std")]
     fn to_rgba(&self) -> colorspace::LinSrgba {
         let data = self.as_ref();
         palette::LinSrgba::new(
@@ -75,6 +82,7 @@ pub trait Pixel<'a, T: Type, C: Color>: AsRef<[T]> {
         )
     }
 
+    #[cfg(feature = "std")]
     fn from_rgba(px: colorspace::rgb::Rgba) -> PixelVec<f64> {
         PixelVec::new(
             px.red as f64,
@@ -84,28 +92,34 @@ pub trait Pixel<'a, T: Type, C: Color>: AsRef<[T]> {
         )
     }
 
+    #[cfg(feature = "std")]
     fn to_luma(&self) -> colorspace::LinLuma {
         let data = self.as_ref();
         palette::luma::Luma::new(data[0].to_f() as f32)
     }
 
+    #[cfg(feature = "std")]
     fn from_luma(px: colorspace::luma::Luma) -> PixelVec<f64> {
         PixelVec::new_gray(px.luma as f64)
     }
 
+    #[cfg(feature = "std")]
     fn to_hsv(&self) -> colorspace::Hsv {
         colorspace::Hsv::from(self.to_rgb())
     }
 
+    #[cfg(feature = "std")]
     fn from_hsv(px: colorspace::Hsv) -> PixelVec<f64> {
         let px = colorspace::rgb::Rgb::from(px);
         Self::from_rgb(px)
     }
 
+    #[cfg(feature = "std")]
     fn to_lab(&self) -> colorspace::Lab {
         colorspace::Lab::from(self.to_rgb())
     }
 
+    #[cfg(feature = "std")]
     fn from_lab(px: colorspace::Lab) -> PixelVec<f64> {
         let px = colorspace::rgb::Rgb::from(px);
         Self::from_rgb(px)
@@ -128,7 +142,7 @@ pub trait PixelMut<'a, T: Type, C: Color>: Pixel<'a, T, C> + AsMut<[T]> {
         a.zip(b).for_each(|(x, y)| *x = *y)
     }
 
-    fn iter_mut(&mut self) -> std::slice::IterMut<T> {
+    fn iter_mut(&mut self) -> core::slice::IterMut<T> {
         self.as_mut().iter_mut()
     }
 
@@ -315,3 +329,226 @@ pixelvec_op!(Div, div, |a, b| a / b);
 pixelvec_op_assign!(DivAssign, div_assign, |a, b| a / b);
 pixelvec_op!(Rem, rem, |a, b| a % b);
 pixelvec_op_assign!(RemAssign, rem_assign, |a, b| a % b);
+
+/// Maximum number of channels stored by `Px`
+pub const PX_CHANNELS: usize = 4;
+
+/// Px is a pixel value with one channel per channel of `C`, backed by a static array. Unlike
+/// `PixelVec` the number of channels is taken from the color, so arithmetic only touches the
+/// channels that exist and a `Px` can be passed anywhere a `Pixel` is expected:
+///
+/// ```rust
+/// use image2::{Image, ImageBuf, Px, Rgb};
+///
+/// let mut image: ImageBuf<f32, Rgb> = ImageBuf::new_with(2, 1, [0.5, 0.25, 1.0]);
+/// let a = image.px(0, 0);
+/// let b = Px::new([1.0, 1.0, 0.0]);
+/// image.set_pixel(1, 0, &(a.lerp(&b, 0.5) * 2.0));
+/// assert_eq!(image.at(1, 0), &[1.5, 1.25, 1.0]);
+/// assert_eq!(a.dot(&b), 0.75);
+/// ```
+///
+/// Operators work on the raw values of `T` and follow its overflow rules, for integer types
+/// convert to normalized floats using `to_f` and back using `from_f` first
+pub struct Px<T: Type, C: Color> {
+    data: [T; PX_CHANNELS],
+    _color: PhantomData<C>,
+}
+
+impl<T: Type, C: Color> Px<T, C> {
+    /// Create a new Px with every channel set to 0
+    pub fn zero() -> Self {
+        Self::splat(T::zero())
+    }
+
+    /// Create a new Px with every channel set to `t`
+    pub fn splat(t: T) -> Self {
+        assert!(
+            C::channels() <= PX_CHANNELS,
+            "Px supports at most {} channels, {} has {}",
+            PX_CHANNELS,
+            C::name(),
+            C::channels()
+        );
+        Px {
+            data: [t; PX_CHANNELS],
+            _color: PhantomData,
+        }
+    }
+
+    /// Create a new Px from existing pixel data, which must have one value per channel
+    pub fn new<P: AsRef<[T]>>(px: P) -> Self {
+        let px = px.as_ref();
+        assert!(
+            px.len() == C::channels(),
+            "expected a {} pixel with {} channels, got {} values",
+            C::name(),
+            C::channels(),
+            px.len()
+        );
+        let mut dest = Self::zero();
+        dest.as_mut().copy_from_slice(px);
+        dest
+    }
+
+    /// Create a new Px from normalized values
+    pub fn from_f(px: &Px<f64, C>) -> Self {
+        px.map(T::from_f)
+    }
+
+    /// Convert to normalized f64 values
+    pub fn to_f(&self) -> Px<f64, C> {
+        self.map(|x| x.to_f())
+    }
+
+    /// Convert to f64 values without normalizing them
+    pub fn to_float(&self) -> Px<f64, C> {
+        self.map(|x| T::to_float(&x))
+    }
+
+    /// Create a new Px by applying `f` to each channel
+    pub fn map<U: Type, F: FnMut(T) -> U>(&self, mut f: F) -> Px<U, C> {
+        let mut dest = Px::zero();
+        for (d, x) in dest.as_mut().iter_mut().zip(self.as_ref()) {
+            *d = f(*x);
+        }
+        dest
+    }
+
+    /// Linear interpolation between `self` at `t = 0` and `other` at `t = 1`
+    pub fn lerp(&self, other: &Self, t: f64) -> Self {
+        let mut dest = *self;
+        for (d, b) in dest.as_mut().iter_mut().zip(other.as_ref()) {
+            let a = T::to_float(d);
+            *d = T::from_float(a + (T::to_float(b) - a) * t);
+        }
+        dest
+    }
+
+    /// Sum of the products of each channel, using unnormalized values
+    pub fn dot(&self, other: &Self) -> f64 {
+        self.as_ref()
+            .iter()
+            .zip(other.as_ref())
+            .map(|(a, b)| T::to_float(a) * T::to_float(b))
+            .sum()
+    }
+}
+
+impl<T: Type, C: Color> Clone for Px<T, C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: Type, C: Color> Copy for Px<T, C> {}
+
+impl<T: Type, C: Color> PartialEq for Px<T, C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_ref() == other.as_ref()
+    }
+}
+
+impl<T: Type + fmt::Debug, C: Color> fmt::Debug for Px<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.as_ref()).finish()
+    }
+}
+
+impl<T: Type, C: Color> AsRef<[T]> for Px<T, C> {
+    fn as_ref(&self) -> &[T] {
+        &self.data[..C::channels()]
+    }
+}
+
+impl<T: Type, C: Color> AsMut<[T]> for Px<T, C> {
+    fn as_mut(&mut self) -> &mut [T] {
+        &mut self.data[..C::channels()]
+    }
+}
+
+impl<T: Type, C: Color> ops::Index<usize> for Px<T, C> {
+    type Output = T;
+
+    fn index(&self, c: usize) -> &T {
+        &self.as_ref()[c]
+    }
+}
+
+impl<T: Type, C: Color> ops::IndexMut<usize> for Px<T, C> {
+    fn index_mut(&mut self, c: usize) -> &mut T {
+        &mut self.as_mut()[c]
+    }
+}
+
+impl<'a, T: Type, C: Color> Pixel<'a, T, C> for Px<T, C> {}
+impl<'a, T: Type, C: Color> PixelMut<'a, T, C> for Px<T, C> {}
+
+macro_rules! px_op {
+    ($name:ident, $fx:ident, $name_assign:ident, $fx_assign:ident, $op:tt) => {
+        impl<T: Type, C: Color> ops::$name for Px<T, C> {
+            type Output = Px<T, C>;
+
+            fn $fx(mut self, other: Self) -> Self::Output {
+                for (a, b) in self.as_mut().iter_mut().zip(other.as_ref()) {
+                    *a = *a $op *b;
+                }
+                self
+            }
+        }
+
+        impl<T: Type, C: Color> ops::$name<T> for Px<T, C> {
+            type Output = Px<T, C>;
+
+            fn $fx(self, other: T) -> Self::Output {
+                self.map(|a| a $op other)
+            }
+        }
+
+        impl<T: Type, C: Color> ops::$name_assign for Px<T, C> {
+            fn $fx_assign(&mut self, other: Self) {
+                *self = *self $op other;
+            }
+        }
+
+        impl<T: Type, C: Color> ops::$name_assign<T> for Px<T, C> {
+            fn $fx_assign(&mut self, other: T) {
+                *self = *self $op other;
+            }
+        }
+    };
+}
+
+px_op!(Add, add, AddAssign, add_assign, +);
+px_op!(Sub, sub, SubAssign, sub_assign, -);
+px_op!(Mul, mul, MulAssign, mul_assign, *);
+px_op!(Div, div, DivAssign, div_assign, /);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Gray, Rgba};
+
+    #[test]
+    fn test_px_ops() {
+        let a: Px<u8, Rgba> = Px::new([10, 20, 30, 40]);
+        let b = Px::new([1, 2, 3, 4]);
+        assert_eq!(a + b, Px::new([11, 22, 33, 44]));
+        assert_eq!(a - b * 2, Px::new([8, 16, 24, 32]));
+        assert_eq!(a / b, Px::splat(10));
+        assert_eq!(a[2], 30);
+        assert_eq!(a.dot(&b), 300.0);
+        assert_eq!(a.lerp(&b, 1.0), b);
+
+        let mut c = a;
+        c -= b;
+        c /= 3;
+        assert_eq!(c.as_ref(), &[3, 6, 9, 12]);
+
+        // Only the channels of the color are used
+        let g: Px<f32, Gray> = Px::new([0.5]);
+        assert_eq!(g.as_ref().len(), 1);
+        assert_eq!((g * 2.0).to_f().as_ref(), &[1.0]);
+        assert_eq!(Px::<u16, Gray>::from_f(&(g * 2.0).to_f()), Px::splat(65535));
+    }
+}
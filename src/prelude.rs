@@ -0,0 +1,18 @@
+//! The prelude re-exports the most commonly used traits and types:
+//!
+//! ```rust
+//! use image2::prelude::*;
+//! ```
+
+pub use crate::color::{Color, Gray, Rgb, Rgba};
+pub use crate::filter::Filter;
+pub use crate::image::{Convert, Image};
+pub use crate::image_buf::ImageBuf;
+pub use crate::kernel::Kernel;
+pub use crate::pixel::{Pixel, PixelMut, PixelVec};
+pub use crate::ty::Type;
+
+#[cfg(feature = "io")]
+pub use crate::facade::Image2;
+#[cfg(feature = "io")]
+pub use crate::io;
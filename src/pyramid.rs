@@ -0,0 +1,175 @@
+//! Image pyramids
+
+use alloc::vec::Vec;
+
+use crate::color::Color;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::ty::Type;
+
+const WEIGHTS: [f64; 5] = [1.0 / 16.0, 4.0 / 16.0, 6.0 / 16.0, 4.0 / 16.0, 1.0 / 16.0];
+
+/// Blur with a 5x5 binomial kernel and downsample by a factor of two, the size of the result
+/// is rounded up
+pub fn down<T: Type, C: Color, I: Image<T, C>>(image: &I) -> ImageBuf<T, C> {
+    let (width, height) = (image.width(), image.height());
+    let max_x = width as isize - 1;
+    let max_y = height as isize - 1;
+    let mut dest = ImageBuf::new(width.div_ceil(2), height.div_ceil(2));
+    dest.copy_metadata(image);
+    dest.for_each(|(x, y), px| {
+        for (c, item) in px.iter_mut().enumerate() {
+            let mut f = 0.0;
+            for (j, wy) in WEIGHTS.iter().enumerate() {
+                let sy = (2 * y as isize + j as isize - 2).max(0).min(max_y) as usize;
+                for (i, wx) in WEIGHTS.iter().enumerate() {
+                    let sx = (2 * x as isize + i as isize - 2).max(0).min(max_x) as usize;
+                    f += wx * wy * image.get_f(sx, sy, c);
+                }
+            }
+            *item = T::from_f(f);
+        }
+    });
+    dest
+}
+
+/// Build a gaussian pyramid with at most `levels` levels, the first level is a copy of
+/// `image`. Fewer levels are returned when the image becomes smaller than 2x2.
+pub fn gaussian<T: Type, C: Color, I: Image<T, C>>(
+    image: &I,
+    levels: usize,
+) -> Vec<ImageBuf<T, C>> {
+    let mut pyramid = vec![Image::clone(image)];
+    while pyramid.len() < levels {
+        let last = &pyramid[pyramid.len() - 1];
+        if last.width() < 2 || last.height() < 2 {
+            break;
+        }
+
+        let next = down(last);
+        pyramid.push(next);
+    }
+    pyramid
+}
+
+/// Upsample to `width` x `height`, which should be at most twice the size of `image`, by
+/// inserting zeros and blurring with a 5x5 binomial kernel
+pub fn up<T: Type, C: Color, I: Image<T, C>>(
+    image: &I,
+    width: usize,
+    height: usize,
+) -> ImageBuf<T, C> {
+    let mut dest = ImageBuf::new(width, height);
+    dest.copy_metadata(image);
+    expand(
+        (image.width(), image.height()),
+        |x, y, c| image.get_f(x, y, c),
+        &mut dest,
+        T::from_f,
+    );
+    dest
+}
+
+fn expand<T: Type, C: Color, F: Sync + Fn(usize, usize, usize) -> f64, G: Sync + Fn(f64) -> T>(
+    (width, height): (usize, usize),
+    get: F,
+    dest: &mut ImageBuf<T, C>,
+    set: G,
+) {
+    let max_x = width as isize - 1;
+    let max_y = height as isize - 1;
+    dest.for_each(|(x, y), px| {
+        for (c, item) in px.iter_mut().enumerate() {
+            let mut f = 0.0;
+            for (j, wy) in WEIGHTS.iter().enumerate() {
+                let sy = y as isize + j as isize - 2;
+                if sy % 2 != 0 {
+                    continue;
+                }
+                let sy = (sy / 2).clamp(0, max_y) as usize;
+                for (i, wx) in WEIGHTS.iter().enumerate() {
+                    let sx = x as isize + i as isize - 2;
+                    if sx % 2 != 0 {
+                        continue;
+                    }
+                    let sx = (sx / 2).clamp(0, max_x) as usize;
+                    f += 4.0 * wx * wy * get(sx, sy, c);
+                }
+            }
+            *item = set(f);
+        }
+    });
+}
+
+/// Build a Laplacian pyramid with at most `levels` levels using normalized values. Each level
+/// holds the detail lost when downsampling, except the last which holds the remaining low
+/// frequencies. Values are not clamped.
+pub fn laplacian<T: Type, C: Color, I: Image<T, C>>(
+    image: &I,
+    levels: usize,
+) -> Vec<ImageBuf<f64, C>> {
+    let mut source: ImageBuf<f64, C> = ImageBuf::new(image.width(), image.height());
+    image.convert_type(&mut source);
+
+    let gaussian = gaussian(&source, levels);
+    let mut pyramid = Vec::with_capacity(gaussian.len());
+    for pair in gaussian.windows(2) {
+        let (current, next) = (&pair[0], &pair[1]);
+        let mut level = Image::clone(current);
+        let mut expanded: ImageBuf<f64, C> = ImageBuf::new(current.width(), current.height());
+        expand(
+            (next.width(), next.height()),
+            |x, y, c| next.get_f(x, y, c),
+            &mut expanded,
+            |f| f,
+        );
+        for (a, b) in level.data_mut().iter_mut().zip(expanded.data()) {
+            *a -= b;
+        }
+        pyramid.push(level);
+    }
+    pyramid.push(gaussian[gaussian.len() - 1].clone());
+    pyramid
+}
+
+/// Reconstruct an image from a Laplacian pyramid
+pub fn collapse<C: Color>(pyramid: &[ImageBuf<f64, C>]) -> ImageBuf<f64, C> {
+    let mut image = match pyramid.last() {
+        Some(last) => last.clone(),
+        None => return ImageBuf::new(0, 0),
+    };
+
+    for level in pyramid.iter().rev().skip(1) {
+        let mut expanded = ImageBuf::new(level.width(), level.height());
+        expand(
+            (image.width(), image.height()),
+            |x, y, c| image.at(x, y)[c],
+            &mut expanded,
+            |f| f,
+        );
+        for (a, b) in expanded.data_mut().iter_mut().zip(level.data()) {
+            *a += b;
+        }
+        image = expanded;
+    }
+    image
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{pyramid, Gray, Image, ImageBuf};
+
+    #[test]
+    fn test_laplacian() {
+        let image: ImageBuf<u8, Gray> =
+            ImageBuf::new_from(13, 9, (0..117).map(|i| (i * 53 % 256) as u8).collect());
+        let levels = pyramid::laplacian(&image, 3);
+        assert_eq!(levels.len(), 3);
+        assert_eq!((levels[2].width(), levels[2].height()), (4, 3));
+
+        let output = pyramid::collapse(&levels);
+        for (a, b) in output.data().iter().zip(image.data()) {
+            assert!((a * 255.0 - *b as f64).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,323 @@
+//! Image registration
+
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::color::Color;
+use crate::error::Error;
+use crate::feature::luminance;
+use crate::fft::{self, Complex};
+use crate::image::Image;
+use crate::ty::Type;
+
+use core::f64::consts::PI;
+
+/// Translation between two images
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Translation {
+    /// Horizontal offset in pixels
+    pub x: f64,
+    /// Vertical offset in pixels
+    pub y: f64,
+    /// Height of the correlation peak, between 0 and 1. Low values indicate an unreliable
+    /// result.
+    pub response: f64,
+}
+
+/// Similarity transform between two images
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Similarity {
+    /// Rotation about the image center in degrees, between -90 and 90
+    pub rotation: f64,
+    /// Scale about the image center
+    pub scale: f64,
+    /// Translation applied after rotation and scaling
+    pub translation: Translation,
+}
+
+/// A single channel plane padded to a power of two size
+struct Plane {
+    width: usize,
+    height: usize,
+    data: Vec<f64>,
+}
+
+impl Plane {
+    fn from_image<T: Type, C: Color, I: Image<T, C>>(image: &I) -> Plane {
+        Plane {
+            width: image.width(),
+            height: image.height(),
+            data: luminance(image),
+        }
+    }
+
+    fn sample(&self, x: f64, y: f64) -> f64 {
+        if x < 0.0 || y < 0.0 || x > (self.width - 1) as f64 || y > (self.height - 1) as f64 {
+            return 0.0;
+        }
+
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (ax, ay) = (x - x0 as f64, y - y0 as f64);
+        let at = |x: usize, y: usize| self.data[y * self.width + x];
+        (at(x0, y0) * (1.0 - ax) + at(x1, y0) * ax) * (1.0 - ay)
+            + (at(x0, y1) * (1.0 - ax) + at(x1, y1) * ax) * ay
+    }
+
+    /// Rotate by `angle` degrees and scale by `scale` about the center
+    fn warp(&self, angle: f64, scale: f64) -> Plane {
+        let (cx, cy) = (
+            (self.width as f64 - 1.0) / 2.0,
+            (self.height as f64 - 1.0) / 2.0,
+        );
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let mut data = Vec::with_capacity(self.data.len());
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (dx, dy) = ((x as f64 - cx) / scale, (y as f64 - cy) / scale);
+                data.push(self.sample(cx + dx * cos + dy * sin, cy - dx * sin + dy * cos));
+            }
+        }
+        Plane {
+            width: self.width,
+            height: self.height,
+            data,
+        }
+    }
+
+    /// Apply a Hann window, subtract the mean and zero-pad to a power of two size
+    fn spectrum(&self) -> (Vec<Complex<f64>>, usize, usize) {
+        let (w, h) = (self.width, self.height);
+        let mean = self.data.iter().sum::<f64>() / self.data.len().max(1) as f64;
+        let hann = |i: usize, n: usize| {
+            if n < 2 {
+                1.0
+            } else {
+                0.5 - 0.5 * (2.0 * PI * i as f64 / (n - 1) as f64).cos()
+            }
+        };
+        let windowed: Vec<f64> = self
+            .data
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (v - mean) * hann(i % w, w) * hann(i / w, h))
+            .collect();
+
+        let (pw, ph) = (w.next_power_of_two(), h.next_power_of_two());
+        let mut data = fft::pad(&windowed, w, pw, ph);
+        fft::fft_2d(&mut data, pw, ph);
+        (data, pw, ph)
+    }
+}
+
+/// Find the offset of the peak of the phase correlation of two spectra
+fn correlate(a: &[Complex<f64>], b: &[Complex<f64>], width: usize, height: usize) -> Translation {
+    let mut r: Vec<Complex<f64>> = a
+        .iter()
+        .zip(b)
+        .map(|(a, b)| {
+            let x = b * a.conj();
+            let norm = fft::norm(&x);
+            if norm > 1e-12 {
+                x / norm
+            } else {
+                Complex::new(0.0, 0.0)
+            }
+        })
+        .collect();
+    fft::ifft_2d(&mut r, width, height);
+
+    let (index, peak) = r
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (i, c.re))
+        .fold((0, f64::NEG_INFINITY), |a, b| if b.1 > a.1 { b } else { a });
+    let (px, py) = (index % width, index / width);
+
+    // Parabolic interpolation around the peak for sub-pixel accuracy
+    let at = |x: isize, y: isize| {
+        let x = (x + width as isize) as usize % width;
+        let y = (y + height as isize) as usize % height;
+        r[y * width + x].re
+    };
+    let offset = |l: f64, c: f64, r: f64| {
+        let d = l - 2.0 * c + r;
+        if d.abs() > 1e-12 {
+            ((l - r) / (2.0 * d)).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        }
+    };
+    let (ix, iy) = (px as isize, py as isize);
+    let sx = offset(at(ix - 1, iy), peak, at(ix + 1, iy));
+    let sy = offset(at(ix, iy - 1), peak, at(ix, iy + 1));
+
+    let wrap = |p: usize, n: usize| {
+        if p > n / 2 {
+            p as f64 - n as f64
+        } else {
+            p as f64
+        }
+    };
+    Translation {
+        x: wrap(px, width) + sx,
+        y: wrap(py, height) + sy,
+        response: peak,
+    }
+}
+
+fn check_size<T: Type, C: Color, A: Image<T, C>, B: Image<T, C>>(
+    a: &A,
+    b: &B,
+) -> Result<(), Error> {
+    if a.width() != b.width() || a.height() != b.height() {
+        return Err(Error::Message(format!(
+            "Image sizes do not match: {}x{} and {}x{}",
+            a.width(),
+            a.height(),
+            b.width(),
+            b.height()
+        )));
+    }
+
+    Ok(())
+}
+
+fn translation(a: &Plane, b: &Plane) -> Translation {
+    let (fa, width, height) = a.spectrum();
+    let (fb, _, _) = b.spectrum();
+    correlate(&fa, &fb, width, height)
+}
+
+/// Estimate the translation of `b` relative to `a` with sub-pixel accuracy using phase
+/// correlation, such that `b(x, y) ≈ a(x - t.x, y - t.y)`
+pub fn phase_correlation<T: Type, C: Color, A: Image<T, C>, B: Image<T, C>>(
+    a: &A,
+    b: &B,
+) -> Result<Translation, Error> {
+    check_size(a, b)?;
+    Ok(translation(&Plane::from_image(a), &Plane::from_image(b)))
+}
+
+/// High-passed log-polar transform of the magnitude spectrum, returns an `n` x `n` image where
+/// columns correspond to log radius and rows to angles between 0 and 180 degrees
+fn log_polar(spectrum: &[Complex<f64>], width: usize, height: usize, n: usize) -> Vec<f64> {
+    let magnitude = |x: isize, y: isize| {
+        let x = (x + width as isize) as usize % width;
+        let y = (y + height as isize) as usize % height;
+        let fx = x.min(width - x) as f64 / width as f64;
+        let fy = y.min(height - y) as f64 / height as f64;
+        let c = (PI * fx).cos() * (PI * fy).cos();
+        fft::norm(&spectrum[y * width + x]) * (1.0 - c) * (2.0 - c)
+    };
+
+    let max_radius = (width.min(height) / 2) as f64;
+    let log_base = max_radius.ln() / n as f64;
+    let mut dest = Vec::with_capacity(n * n);
+    for t in 0..n {
+        let (sin, cos) = (PI * t as f64 / n as f64).sin_cos();
+        for r in 0..n {
+            let radius = (r as f64 * log_base).exp();
+            let (x, y) = (radius * cos, radius * sin);
+            let (x0, y0) = (x.floor(), y.floor());
+            let (ax, ay) = (x - x0, y - y0);
+            let (x0, y0) = (x0 as isize, y0 as isize);
+            dest.push(
+                (magnitude(x0, y0) * (1.0 - ax) + magnitude(x0 + 1, y0) * ax) * (1.0 - ay)
+                    + (magnitude(x0, y0 + 1) * (1.0 - ax) + magnitude(x0 + 1, y0 + 1) * ax) * ay,
+            );
+        }
+    }
+    dest
+}
+
+/// Estimate the rotation, scale and translation of `b` relative to `a`. Rotation and scale
+/// are found by phase correlation of the log-polar transformed magnitude spectra, then `a` is
+/// rotated and scaled to match `b` before estimating the translation.
+pub fn phase_correlation_log_polar<T: Type, C: Color, A: Image<T, C>, B: Image<T, C>>(
+    a: &A,
+    b: &B,
+) -> Result<Similarity, Error> {
+    check_size(a, b)?;
+
+    let pa = Plane::from_image(a);
+    let pb = Plane::from_image(b);
+    let (fa, width, height) = pa.spectrum();
+    let (fb, _, _) = pb.spectrum();
+
+    let n = width.min(height);
+    let lp = |s: &[Complex<f64>]| {
+        let data = log_polar(s, width, height, n);
+        let plane = Plane {
+            width: n,
+            height: n,
+            data,
+        };
+        plane.spectrum().0
+    };
+    let t = correlate(&lp(&fa), &lp(&fb), n, n);
+
+    let max_radius = (width.min(height) / 2) as f64;
+    let scale = (-t.x * max_radius.ln() / n as f64).exp();
+    let rotation = t.y * 180.0 / n as f64;
+
+    let warped = pa.warp(rotation, scale);
+    Ok(Similarity {
+        rotation,
+        scale,
+        translation: translation(&warped, &pb),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::register::Plane;
+    use crate::{pyramid, register, Gray, Image, ImageBuf};
+
+    fn pattern(dx: f64, dy: f64) -> ImageBuf<f32, Gray> {
+        let mut image = ImageBuf::new(64, 64);
+        image.for_each(|(x, y), px| {
+            let (x, y) = (x as f64 - dx, y as f64 - dy);
+            let a = (-((x - 20.0).powi(2) + (y - 30.0).powi(2)) / 40.0).exp();
+            let b = (-((x - 40.0).powi(2) + (y - 22.0).powi(2)) / 20.0).exp();
+            let c = (-((x - 34.0).powi(2) + (y - 44.0).powi(2)) / 60.0).exp();
+            px[0] = (a + 0.7 * b + 0.5 * c) as f32;
+        });
+        image
+    }
+
+    #[test]
+    fn test_phase_correlation() {
+        let a = pattern(0.0, 0.0);
+        let b = pattern(3.0, -5.0);
+        let t = register::phase_correlation(&a, &b).unwrap();
+        assert!(
+            (t.x - 3.0).abs() < 0.3 && (t.y + 5.0).abs() < 0.3,
+            "{:?}",
+            t
+        );
+    }
+
+    #[test]
+    fn test_phase_correlation_log_polar() {
+        let mut noise: ImageBuf<f32, Gray> = ImageBuf::new(128, 128);
+        noise.for_each(|(x, y), px| {
+            px[0] = (((x * 7919 + y * 104729) ^ (x * y * 31)) % 251) as f32 / 251.0;
+        });
+        let a = pyramid::down(&pyramid::down(&noise));
+        let warped = Plane::from_image(&a).warp(10.0, 1.1);
+        let b: ImageBuf<f32, Gray> = ImageBuf::new_from(
+            a.width(),
+            a.height(),
+            warped.data.iter().map(|x| *x as f32).collect(),
+        );
+
+        let s = register::phase_correlation_log_polar(&a, &b).unwrap();
+        assert!((s.rotation - 10.0).abs() < 1.0, "{:?}", s);
+        assert!((s.scale - 1.1).abs() < 0.05, "{:?}", s);
+        assert!(s.translation.x.abs() < 0.5 && s.translation.y.abs() < 0.5);
+    }
+}
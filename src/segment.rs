@@ -0,0 +1,372 @@
+//! Image segmentation
+
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::color::{Color, Gray};
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::ty::Type;
+
+use alloc::collections::VecDeque;
+use core::marker::PhantomData;
+
+/// Maximum number of k-means iterations
+const KMEANS_ITERATIONS: usize = 30;
+
+/// Number of SLIC iterations
+const SLIC_ITERATIONS: usize = 10;
+
+/// The result of segmenting an image: a label for each pixel and the mean color of each label
+#[derive(Debug, Clone)]
+pub struct Segmentation<C: Color> {
+    labels: ImageBuf<u32, Gray>,
+    colors: Vec<Vec<f64>>,
+    _color: PhantomData<C>,
+}
+
+impl<C: Color> Segmentation<C> {
+    fn new(labels: ImageBuf<u32, Gray>, colors: Vec<Vec<f64>>) -> Segmentation<C> {
+        Segmentation {
+            labels,
+            colors,
+            _color: PhantomData,
+        }
+    }
+
+    /// Number of segments
+    pub fn len(&self) -> usize {
+        self.colors.len()
+    }
+
+    /// Returns true if there are no segments
+    pub fn is_empty(&self) -> bool {
+        self.colors.is_empty()
+    }
+
+    /// Image containing the label of each pixel, labels are numbered from 0
+    pub fn labels(&self) -> &ImageBuf<u32, Gray> {
+        &self.labels
+    }
+
+    /// Normalized mean color of each segment
+    pub fn colors(&self) -> &[Vec<f64>] {
+        &self.colors
+    }
+
+    /// Draw each pixel using the mean color of its segment
+    pub fn render<T: Type>(&self) -> ImageBuf<T, C> {
+        let mut dest = ImageBuf::new(self.labels.width(), self.labels.height());
+        for (px, label) in dest
+            .data_mut()
+            .chunks_mut(C::channels())
+            .zip(self.labels.data())
+        {
+            for (x, c) in px.iter_mut().zip(&self.colors[*label as usize]) {
+                *x = T::from_f(*c);
+            }
+        }
+        dest
+    }
+}
+
+fn pixels<T: Type, C: Color, I: Image<T, C>>(image: &I) -> Vec<Vec<f64>> {
+    image
+        .data()
+        .chunks(C::channels())
+        .map(|px| px.iter().map(T::to_f).collect())
+        .collect()
+}
+
+#[inline]
+fn distance2(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum()
+}
+
+/// Compute the mean color of each label
+fn mean_colors(
+    pixels: &[Vec<f64>],
+    labels: &[u32],
+    count: usize,
+    channels: usize,
+) -> Vec<Vec<f64>> {
+    let mut sums = vec![vec![0.0; channels]; count];
+    let mut counts = vec![0usize; count];
+    for (px, label) in pixels.iter().zip(labels) {
+        let l = *label as usize;
+        counts[l] += 1;
+        for (s, v) in sums[l].iter_mut().zip(px) {
+            *s += v;
+        }
+    }
+
+    for (s, n) in sums.iter_mut().zip(counts) {
+        if n > 0 {
+            s.iter_mut().for_each(|x| *x /= n as f64);
+        }
+    }
+    sums
+}
+
+/// Cluster the colors of an image into `k` groups using k-means. Initial centers are chosen
+/// deterministically by repeatedly picking the color furthest from all existing centers.
+pub fn kmeans_colors<T: Type, C: Color, I: Image<T, C>>(image: &I, k: usize) -> Segmentation<C> {
+    let (width, height, channels) = image.shape();
+    let pixels = pixels(image);
+    let k = k.max(1).min(pixels.len().max(1));
+    if pixels.is_empty() {
+        return Segmentation::new(ImageBuf::new(width, height), Vec::new());
+    }
+
+    // Sample a subset of pixels when choosing the initial centers
+    let step = (pixels.len() / 4096).max(1);
+    let mean = mean_colors(&pixels, &vec![0; pixels.len()], 1, channels).remove(0);
+    let first = pixels
+        .iter()
+        .step_by(step)
+        .min_by(|a, b| {
+            distance2(a, &mean)
+                .partial_cmp(&distance2(b, &mean))
+                .unwrap_or(core::cmp::Ordering::Equal)
+        })
+        .cloned()
+        .unwrap_or(mean);
+    let mut centers = vec![first];
+    while centers.len() < k {
+        let next = pixels
+            .iter()
+            .step_by(step)
+            .map(|p| {
+                let d = centers
+                    .iter()
+                    .map(|c| distance2(p, c))
+                    .fold(f64::INFINITY, f64::min);
+                (p, d)
+            })
+            .fold((&pixels[0], -1.0), |a, b| if b.1 > a.1 { b } else { a });
+        centers.push(next.0.clone());
+    }
+
+    let mut labels = vec![0u32; pixels.len()];
+    for _ in 0..KMEANS_ITERATIONS {
+        let mut changed = false;
+        for (px, label) in pixels.iter().zip(labels.iter_mut()) {
+            let mut best = (0, f64::INFINITY);
+            for (i, c) in centers.iter().enumerate() {
+                let d = distance2(px, c);
+                if d < best.1 {
+                    best = (i, d);
+                }
+            }
+            if *label != best.0 as u32 {
+                *label = best.0 as u32;
+                changed = true;
+            }
+        }
+
+        let mut counts = vec![0usize; k];
+        labels.iter().for_each(|l| counts[*l as usize] += 1);
+        let means = mean_colors(&pixels, &labels, k, channels);
+        for ((c, m), n) in centers.iter_mut().zip(means).zip(counts) {
+            // Keep the previous center when a cluster becomes empty
+            if n > 0 {
+                *c = m;
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    let colors = mean_colors(&pixels, &labels, k, channels);
+    Segmentation::new(ImageBuf::new_from(width, height, labels), colors)
+}
+
+/// Segment an image into approximately `n_segments` compact superpixels using SLIC. Color
+/// distances are computed from normalized values scaled to 0-100, so `compactness` has the
+/// same meaning as in the original algorithm: typical values are between 1 and 40, and higher
+/// values produce more regular shapes.
+pub fn slic<T: Type, C: Color, I: Image<T, C>>(
+    image: &I,
+    n_segments: usize,
+    compactness: f64,
+) -> Segmentation<C> {
+    let (width, height, channels) = image.shape();
+    let normalized = pixels(image);
+    let pixels: Vec<Vec<f64>> = normalized
+        .iter()
+        .map(|px| px.iter().map(|x| x * 100.0).collect())
+        .collect();
+    if pixels.is_empty() {
+        return Segmentation::new(ImageBuf::new(width, height), Vec::new());
+    }
+
+    let step = ((width * height) as f64 / n_segments.max(1) as f64)
+        .sqrt()
+        .max(1.0);
+    let s = step.round().max(1.0) as usize;
+    let at = |x: usize, y: usize| &pixels[y * width + x];
+
+    // Place centers on a grid, moved to the lowest gradient position in a 3x3 neighborhood
+    let mut centers: Vec<(f64, f64, Vec<f64>)> = Vec::new();
+    let mut y = s / 2;
+    while y < height {
+        let mut x = s / 2;
+        while x < width {
+            let mut best = (x, y, f64::INFINITY);
+            for j in y.saturating_sub(1)..=(y + 1).min(height - 1) {
+                for i in x.saturating_sub(1)..=(x + 1).min(width - 1) {
+                    let gx = distance2(at((i + 1).min(width - 1), j), at(i.saturating_sub(1), j));
+                    let gy = distance2(at(i, (j + 1).min(height - 1)), at(i, j.saturating_sub(1)));
+                    if gx + gy < best.2 {
+                        best = (i, j, gx + gy);
+                    }
+                }
+            }
+            centers.push((best.0 as f64, best.1 as f64, at(best.0, best.1).clone()));
+            x += s;
+        }
+        y += s;
+    }
+
+    let weight = (compactness / step) * (compactness / step);
+    let mut labels = vec![u32::MAX; width * height];
+    let mut distances = vec![f64::INFINITY; width * height];
+    for _ in 0..SLIC_ITERATIONS {
+        distances.iter_mut().for_each(|d| *d = f64::INFINITY);
+        for (k, (cx, cy, color)) in centers.iter().enumerate() {
+            let x0 = (cx - 2.0 * step).max(0.0) as usize;
+            let y0 = (cy - 2.0 * step).max(0.0) as usize;
+            let x1 = ((cx + 2.0 * step) as usize).min(width - 1);
+            let y1 = ((cy + 2.0 * step) as usize).min(height - 1);
+            for y in y0..=y1 {
+                for x in x0..=x1 {
+                    let i = y * width + x;
+                    let dx = x as f64 - cx;
+                    let dy = y as f64 - cy;
+                    let d = distance2(&pixels[i], color) + (dx * dx + dy * dy) * weight;
+                    if d < distances[i] {
+                        distances[i] = d;
+                        labels[i] = k as u32;
+                    }
+                }
+            }
+        }
+
+        // Move each center to the mean position and color of its pixels
+        let mut sums = vec![(0.0, 0.0, vec![0.0; channels], 0usize); centers.len()];
+        for (i, label) in labels.iter().enumerate() {
+            if *label == u32::MAX {
+                continue;
+            }
+
+            let s = &mut sums[*label as usize];
+            s.0 += (i % width) as f64;
+            s.1 += (i / width) as f64;
+            for (a, b) in s.2.iter_mut().zip(&pixels[i]) {
+                *a += b;
+            }
+            s.3 += 1;
+        }
+
+        for (c, (sx, sy, color, n)) in centers.iter_mut().zip(sums) {
+            if n > 0 {
+                let n = n as f64;
+                *c = (sx / n, sy / n, color.into_iter().map(|x| x / n).collect());
+            }
+        }
+    }
+
+    // Enforce connectivity by merging small disconnected fragments into a neighboring segment
+    let min_size = (s * s / 4).max(1);
+    let mut output = vec![u32::MAX; width * height];
+    let mut count = 0u32;
+    let mut queue = VecDeque::new();
+    let mut fragment = Vec::new();
+    for start in 0..width * height {
+        if output[start] != u32::MAX {
+            continue;
+        }
+
+        // Label of an adjacent, already processed segment
+        let (sx, sy) = (start % width, start / width);
+        let adjacent = [(sx > 0).then(|| start - 1), (sy > 0).then(|| start - width)]
+            .iter()
+            .flatten()
+            .map(|i| output[*i])
+            .next();
+
+        fragment.clear();
+        output[start] = count;
+        queue.push_back(start);
+        while let Some(i) = queue.pop_front() {
+            fragment.push(i);
+            let (x, y) = (i % width, i / width);
+            let neighbors = [
+                (x > 0).then(|| i - 1),
+                (x + 1 < width).then(|| i + 1),
+                (y > 0).then(|| i - width),
+                (y + 1 < height).then(|| i + width),
+            ];
+            for n in neighbors.iter().flatten() {
+                if output[*n] == u32::MAX && labels[*n] == labels[start] {
+                    output[*n] = count;
+                    queue.push_back(*n);
+                }
+            }
+        }
+
+        match adjacent {
+            Some(label) if fragment.len() < min_size => {
+                for i in &fragment {
+                    output[*i] = label;
+                }
+            }
+            _ => count += 1,
+        }
+    }
+
+    let colors = mean_colors(&normalized, &output, count as usize, channels);
+    Segmentation::new(ImageBuf::new_from(width, height, output), colors)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{segment, Image, ImageBuf, Rgb};
+
+    fn quadrants() -> ImageBuf<u8, Rgb> {
+        let mut image = ImageBuf::new(40, 40);
+        image.for_each(|(x, y), px| {
+            let color = match (x < 20, y < 20) {
+                (true, true) => [255, 0, 0],
+                (false, true) => [0, 255, 0],
+                (true, false) => [0, 0, 255],
+                (false, false) => [250, 250, 250],
+            };
+            px.copy_from_slice(&color);
+        });
+        image
+    }
+
+    #[test]
+    fn test_kmeans_colors() {
+        let image = quadrants();
+        let segments = segment::kmeans_colors(&image, 4);
+        assert_eq!(segments.len(), 4);
+        let output: ImageBuf<u8, Rgb> = segments.render();
+        assert_eq!(output, image);
+    }
+
+    #[test]
+    fn test_slic() {
+        let image = quadrants();
+        let segments = segment::slic(&image, 16, 10.0);
+        assert!(segments.len() >= 4);
+
+        // Superpixels should not cross the quadrant boundaries
+        let output: ImageBuf<u8, Rgb> = segments.render();
+        assert_eq!(output, image);
+    }
+}
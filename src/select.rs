@@ -0,0 +1,160 @@
+//! Selections
+
+use alloc::vec::Vec;
+
+use crate::analysis::Connectivity;
+use crate::color::{Color, Gray};
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::pixel::PixelVec;
+use crate::rect::Rect;
+use crate::ty::Type;
+
+/// A set of selected pixels
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selection {
+    mask: ImageBuf<u8, Gray>,
+    bbox: Rect,
+    count: usize,
+}
+
+impl Selection {
+    /// Binary mask where selected pixels are 255 and all others are 0
+    pub fn mask(&self) -> &ImageBuf<u8, Gray> {
+        &self.mask
+    }
+
+    /// Consume the selection, returning the mask
+    pub fn into_mask(self) -> ImageBuf<u8, Gray> {
+        self.mask
+    }
+
+    /// Bounding box of the selected pixels
+    pub fn bbox(&self) -> Rect {
+        self.bbox
+    }
+
+    /// Number of selected pixels
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns true if the pixel at (x, y) is selected
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x < self.mask.width() && y < self.mask.height() && self.mask.at(x, y)[0] != 0
+    }
+
+    /// Copy `image`, setting all selected pixels to `color`, which should contain normalized
+    /// values
+    pub fn fill<T: Type, C: Color, I: Image<T, C>>(
+        &self,
+        image: &I,
+        color: &PixelVec<f64>,
+    ) -> ImageBuf<T, C> {
+        let mut dest = Image::clone(image);
+        let color = color.to_vec::<C>();
+        for (px, m) in dest
+            .data_mut()
+            .chunks_mut(C::channels())
+            .zip(self.mask.data())
+        {
+            if *m != 0 {
+                for (x, c) in px.iter_mut().zip(&color) {
+                    *x = T::from_f(*c);
+                }
+            }
+        }
+        dest
+    }
+}
+
+/// Select the region of similar pixels connected to `seed`, like a magic wand tool. A pixel is
+/// similar when the normalized difference between each of its channels and the seed color is
+/// at most `tolerance`.
+pub fn flood_fill<T: Type, C: Color, I: Image<T, C>>(
+    image: &I,
+    seed: (usize, usize),
+    tolerance: f64,
+    connectivity: Connectivity,
+) -> Selection {
+    let (width, height) = (image.width(), image.height());
+    let mut mask: ImageBuf<u8, Gray> = ImageBuf::new(width, height);
+    let mut bbox = Rect::default();
+    let mut count = 0;
+    if seed.0 >= width || seed.1 >= height {
+        return Selection { mask, bbox, count };
+    }
+
+    let target: Vec<f64> = image.at(seed.0, seed.1).iter().map(T::to_f).collect();
+    let similar = |x: usize, y: usize| {
+        image
+            .at(x, y)
+            .iter()
+            .zip(&target)
+            .all(|(v, t)| (T::to_f(v) - t).abs() <= tolerance)
+    };
+
+    let offsets: &[(isize, isize)] = match connectivity {
+        Connectivity::Four => &[(1, 0), (-1, 0), (0, 1), (0, -1)],
+        Connectivity::Eight => &[
+            (1, 0),
+            (-1, 0),
+            (0, 1),
+            (0, -1),
+            (1, 1),
+            (-1, 1),
+            (1, -1),
+            (-1, -1),
+        ],
+    };
+
+    let data = mask.data_mut();
+    let mut stack = vec![seed];
+    data[seed.1 * width + seed.0] = 255;
+    while let Some((x, y)) = stack.pop() {
+        count += 1;
+        bbox = bbox.union(&Rect::point(x, y));
+        for (dx, dy) in offsets {
+            let (nx, ny) = (x as isize + dx, y as isize + dy);
+            if nx < 0 || ny < 0 || nx >= width as isize || ny >= height as isize {
+                continue;
+            }
+
+            let (nx, ny) = (nx as usize, ny as usize);
+            if data[ny * width + nx] == 0 && similar(nx, ny) {
+                data[ny * width + nx] = 255;
+                stack.push((nx, ny));
+            }
+        }
+    }
+
+    Selection { mask, bbox, count }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::analysis::Connectivity;
+    use crate::{select, Gray, Image, ImageBuf, PixelVec, Rect};
+
+    #[test]
+    fn test_flood_fill() {
+        #[rustfmt::skip]
+        let image: ImageBuf<u8, Gray> = ImageBuf::new_from(4, 3, vec![
+            10, 12, 200, 0,
+            11, 200, 10, 0,
+            200, 10, 10, 0,
+        ]);
+
+        let s = select::flood_fill(&image, (0, 0), 0.02, Connectivity::Four);
+        assert_eq!(s.count(), 3);
+        assert_eq!(s.bbox(), Rect::new(0, 0, 2, 2));
+
+        let s = select::flood_fill(&image, (0, 0), 0.02, Connectivity::Eight);
+        assert_eq!(s.count(), 6);
+        assert!(s.contains(2, 2));
+
+        let filled = s.fill(&image, &PixelVec::new_gray(1.0));
+        assert_eq!(filled.at(1, 2)[0], 255);
+        assert_eq!(filled.at(3, 0)[0], 0);
+    }
+}
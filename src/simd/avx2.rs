@@ -0,0 +1,147 @@
+//! AVX2 implementations
+
+use core::arch::x86_64::*;
+
+use super::sse41;
+
+#[target_feature(enable = "avx2")]
+pub unsafe fn u8_to_f32(src: &[u8], dest: &mut [f32]) -> usize {
+    let n = src.len().min(dest.len()) / 8 * 8;
+    let max = _mm256_set1_ps(255.0);
+    for i in (0..n).step_by(8) {
+        let v = _mm256_cvtepu8_epi32(_mm_loadl_epi64(src.as_ptr().add(i) as *const __m128i));
+        let f = _mm256_div_ps(_mm256_cvtepi32_ps(v), max);
+        _mm256_storeu_ps(dest.as_mut_ptr().add(i), f);
+    }
+    n
+}
+
+/// Scale four values to integers, out of range values and `NaN` become 0
+#[target_feature(enable = "avx2")]
+unsafe fn scale_u8(v: __m128) -> __m128i {
+    let x = _mm256_mul_pd(_mm256_cvtps_pd(v), _mm256_set1_pd(255.0));
+    // Ordered comparisons are false for NaN
+    let valid = _mm256_and_pd(
+        _mm256_cmp_pd(x, _mm256_set1_pd(-1.0), _CMP_GT_OQ),
+        _mm256_cmp_pd(x, _mm256_set1_pd(256.0), _CMP_LT_OQ),
+    );
+    _mm256_cvttpd_epi32(_mm256_and_pd(x, valid))
+}
+
+#[target_feature(enable = "avx2")]
+pub unsafe fn f32_to_u8(src: &[f32], dest: &mut [u8]) -> usize {
+    let n = src.len().min(dest.len()) / 8 * 8;
+    for i in (0..n).step_by(8) {
+        let lo = scale_u8(_mm_loadu_ps(src.as_ptr().add(i)));
+        let hi = scale_u8(_mm_loadu_ps(src.as_ptr().add(i + 4)));
+        let words = _mm_packs_epi32(lo, hi);
+        _mm_storel_epi64(
+            dest.as_mut_ptr().add(i) as *mut __m128i,
+            _mm_packus_epi16(words, words),
+        );
+    }
+    n
+}
+
+#[target_feature(enable = "avx2")]
+pub unsafe fn swap_rb(src: &[u8], dest: &mut [u8]) -> usize {
+    let n = src.len().min(dest.len()) / 32 * 32;
+    let mask = _mm256_setr_epi8(
+        2, 1, 0, 3, 6, 5, 4, 7, 10, 9, 8, 11, 14, 13, 12, 15, 2, 1, 0, 3, 6, 5, 4, 7, 10, 9, 8, 11,
+        14, 13, 12, 15,
+    );
+    for i in (0..n).step_by(32) {
+        let v = _mm256_loadu_si256(src.as_ptr().add(i) as *const __m256i);
+        _mm256_storeu_si256(
+            dest.as_mut_ptr().add(i) as *mut __m256i,
+            _mm256_shuffle_epi8(v, mask),
+        );
+    }
+    n
+}
+
+/// Blend four pixels, see `sse2::blend_rgba`
+#[target_feature(enable = "avx2")]
+unsafe fn blend(s: __m128i, d: __m128i) -> __m128i {
+    let s = _mm256_cvtepu8_epi16(s);
+    let d = _mm256_cvtepu8_epi16(d);
+    let a = _mm256_shufflehi_epi16(_mm256_shufflelo_epi16(s, 0xff), 0xff);
+    let s = _mm256_or_si256(
+        s,
+        _mm256_set_epi16(255, 0, 0, 0, 255, 0, 0, 0, 255, 0, 0, 0, 255, 0, 0, 0),
+    );
+    let x = _mm256_add_epi16(
+        _mm256_add_epi16(
+            _mm256_mullo_epi16(s, a),
+            _mm256_mullo_epi16(d, _mm256_sub_epi16(_mm256_set1_epi16(255), a)),
+        ),
+        _mm256_set1_epi16(127),
+    );
+    let x = _mm256_srli_epi16(
+        _mm256_add_epi16(
+            _mm256_add_epi16(x, _mm256_set1_epi16(1)),
+            _mm256_srli_epi16(x, 8),
+        ),
+        8,
+    );
+    _mm_packus_epi16(_mm256_castsi256_si128(x), _mm256_extracti128_si256(x, 1))
+}
+
+#[target_feature(enable = "avx2")]
+pub unsafe fn blend_rgba(src: &[u8], dest: &mut [u8]) -> usize {
+    let n = src.len().min(dest.len()) / 16 * 16;
+    for i in (0..n).step_by(16) {
+        let s = _mm_loadu_si128(src.as_ptr().add(i) as *const __m128i);
+        let d = _mm_loadu_si128(dest.as_ptr().add(i) as *const __m128i);
+        _mm_storeu_si128(dest.as_mut_ptr().add(i) as *mut __m128i, blend(s, d));
+    }
+    n
+}
+
+/// See `sse2::madd`
+#[target_feature(enable = "avx2")]
+unsafe fn madd(a: __m256i, b: __m256i, ca: i16, cb: i16) -> (__m256i, __m256i) {
+    let c = _mm256_set1_epi32(i32::from(cb) << 16 | i32::from(ca) & 0xffff);
+    (
+        _mm256_madd_epi16(_mm256_unpacklo_epi16(a, b), c),
+        _mm256_madd_epi16(_mm256_unpackhi_epi16(a, b), c),
+    )
+}
+
+/// Round, shift and saturate sixteen 32-bit values to bytes, stored in the low half
+#[target_feature(enable = "avx2")]
+unsafe fn finish((lo, hi): (__m256i, __m256i)) -> __m128i {
+    let round = _mm256_set1_epi32(128);
+    let lo = _mm256_srai_epi32(_mm256_add_epi32(lo, round), 8);
+    let hi = _mm256_srai_epi32(_mm256_add_epi32(hi, round), 8);
+    let words = _mm256_packs_epi32(lo, hi);
+    // Each 128-bit lane holds eight results in its low half
+    let bytes = _mm256_packus_epi16(words, words);
+    _mm256_castsi256_si128(_mm256_permute4x64_epi64(bytes, 0b1000))
+}
+
+#[target_feature(enable = "avx2")]
+pub unsafe fn yuyv_to_rgb(src: &[u8], dest: &mut [u8]) -> usize {
+    let n = (src.len() / 2).min(dest.len() / 3) / 16 * 16;
+    let mask = _mm256_set1_epi16(0xff);
+    for i in (0..n).step_by(16) {
+        let v = _mm256_loadu_si256(src.as_ptr().add(i * 2) as *const __m256i);
+        let y = _mm256_sub_epi16(_mm256_and_si256(v, mask), _mm256_set1_epi16(16));
+        let uv = _mm256_sub_epi16(_mm256_srli_epi16(v, 8), _mm256_set1_epi16(128));
+        let d = _mm256_shufflehi_epi16(_mm256_shufflelo_epi16(uv, 0xa0), 0xa0);
+        let e = _mm256_shufflehi_epi16(_mm256_shufflelo_epi16(uv, 0xf5), 0xf5);
+
+        let green = {
+            let (lo, hi) = madd(y, d, 298, -100);
+            let (elo, ehi) = madd(e, _mm256_setzero_si256(), -208, 0);
+            (_mm256_add_epi32(lo, elo), _mm256_add_epi32(hi, ehi))
+        };
+        let planes = [
+            finish(madd(y, e, 298, 409)),
+            finish(green),
+            finish(madd(y, d, 298, 516)),
+        ];
+        sse41::store_rgb(planes, dest.as_mut_ptr().add(i * 3));
+    }
+    n
+}
@@ -0,0 +1,333 @@
+//! SIMD kernels for conversions that are performance critical when processing video
+//!
+//! The fastest implementation supported by the CPU is selected at runtime, so binaries built
+//! without `-C target-cpu=native` still use AVX2 or SSE4.1 when they are available. Without the
+//! `std` feature only the instruction sets enabled at compile time are used:
+//!
+//! - x86_64: AVX2, SSE4.1 or SSE2, which every x86_64 CPU supports
+//! - aarch64: NEON
+//! - everything else uses the scalar implementations in `scalar`
+//!
+//! The remainder that doesn't fill a whole vector is always converted using the scalar version,
+//! every implementation returns exactly the same results. `set_level` can be used to select a
+//! slower implementation, for example to compare performance.
+//!
+//! `Image::convert_type` uses these kernels to convert between `u8` and `f32` images, and
+//! `io::v4l` uses them to convert YUYV frames.
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::ty::Type;
+
+#[cfg(target_arch = "x86_64")]
+mod avx2;
+#[cfg(target_arch = "aarch64")]
+mod neon;
+#[cfg(target_arch = "x86_64")]
+mod sse2;
+#[cfg(target_arch = "x86_64")]
+mod sse41;
+
+/// Instruction sets the kernels are implemented for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Scalar,
+    Sse2,
+    Sse41,
+    Avx2,
+    Neon,
+}
+
+impl Level {
+    /// Every level, in order of preference for each architecture
+    pub const ALL: [Level; 5] = [
+        Level::Scalar,
+        Level::Sse2,
+        Level::Sse41,
+        Level::Avx2,
+        Level::Neon,
+    ];
+
+    /// Returns true when the current CPU supports the instruction set
+    pub fn is_supported(self) -> bool {
+        match self {
+            Level::Scalar => true,
+            #[cfg(target_arch = "x86_64")]
+            Level::Sse2 => true,
+            #[cfg(all(target_arch = "x86_64", feature = "std"))]
+            Level::Sse41 => is_x86_feature_detected!("sse4.1"),
+            #[cfg(all(target_arch = "x86_64", feature = "std"))]
+            Level::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(all(target_arch = "aarch64", feature = "std"))]
+            Level::Neon => std::arch::is_aarch64_feature_detected!("neon"),
+            // Runtime detection needs std, otherwise only the features enabled at compile time
+            // are used
+            #[cfg(all(target_arch = "x86_64", not(feature = "std")))]
+            Level::Sse41 => cfg!(target_feature = "sse4.1"),
+            #[cfg(all(target_arch = "x86_64", not(feature = "std")))]
+            Level::Avx2 => cfg!(target_feature = "avx2"),
+            #[cfg(all(target_arch = "aarch64", not(feature = "std")))]
+            Level::Neon => cfg!(target_feature = "neon"),
+            _ => false,
+        }
+    }
+
+    /// The fastest level supported by the current CPU
+    pub fn detect() -> Level {
+        Level::ALL
+            .iter()
+            .rev()
+            .copied()
+            .find(|level| level.is_supported())
+            .unwrap_or(Level::Scalar)
+    }
+}
+
+/// Selected level, `UNKNOWN` until it is detected
+static LEVEL: AtomicU8 = AtomicU8::new(UNKNOWN);
+const UNKNOWN: u8 = u8::MAX;
+
+/// Returns the level used by the kernels
+pub fn level() -> Level {
+    match LEVEL.load(Ordering::Relaxed) {
+        UNKNOWN => {
+            let level = Level::detect();
+            LEVEL.store(level as u8, Ordering::Relaxed);
+            level
+        }
+        n => Level::ALL[n as usize],
+    }
+}
+
+/// Select the level used by the kernels, returns false without changing anything if the CPU
+/// doesn't support it
+pub fn set_level(level: Level) -> bool {
+    if !level.is_supported() {
+        return false;
+    }
+
+    LEVEL.store(level as u8, Ordering::Relaxed);
+    true
+}
+
+/// Portable implementations of the kernels, these are also used to benchmark the SIMD versions
+pub mod scalar {
+    /// Convert `u8` values to `f32` values from 0 to 1
+    pub fn u8_to_f32(src: &[u8], dest: &mut [f32]) {
+        for (d, s) in dest.iter_mut().zip(src) {
+            *d = (f64::from(*s) / 255.0) as f32;
+        }
+    }
+
+    /// Convert `f32` values from 0 to 1 to `u8` values, like `Type::convert` values that are out of
+    /// range and `NaN` are converted to 0
+    pub fn f32_to_u8(src: &[f32], dest: &mut [u8]) {
+        for (d, s) in dest.iter_mut().zip(src) {
+            let x = f64::from(*s) * 255.0;
+            *d = if x > -1.0 && x < 256.0 { x as u8 } else { 0 };
+        }
+    }
+
+    /// Swap the first and third channel of 8-bit 4 channel pixels, converting RGBA to BGRA and
+    /// back
+    pub fn swap_rb(src: &[u8], dest: &mut [u8]) {
+        for (d, s) in dest.chunks_exact_mut(4).zip(src.chunks_exact(4)) {
+            d.copy_from_slice(&[s[2], s[1], s[0], s[3]]);
+        }
+    }
+
+    /// Blend 8-bit RGBA pixels into `dest` using the alpha of `src`, which is the same as
+    /// compositing `src` over `dest` when `dest` is opaque
+    pub fn blend_rgba(src: &[u8], dest: &mut [u8]) {
+        for (d, s) in dest.chunks_exact_mut(4).zip(src.chunks_exact(4)) {
+            let a = u32::from(s[3]);
+            // Alpha is composited as if the source color was opaque
+            let s = [s[0], s[1], s[2], 255];
+            for (d, s) in d.iter_mut().zip(&s) {
+                *d = ((u32::from(*s) * a + u32::from(*d) * (255 - a) + 127) / 255) as u8;
+            }
+        }
+    }
+
+    /// Convert a pixel from BT.601 limited range YUV to RGB
+    pub fn yuv_to_rgb(y: u8, u: u8, v: u8, rgb: &mut [u8]) {
+        let c = 298 * (i32::from(y) - 16);
+        let d = i32::from(u) - 128;
+        let e = i32::from(v) - 128;
+        rgb[0] = ((c + 409 * e + 128) >> 8).clamp(0, 255) as u8;
+        rgb[1] = ((c - 100 * d - 208 * e + 128) >> 8).clamp(0, 255) as u8;
+        rgb[2] = ((c + 516 * d + 128) >> 8).clamp(0, 255) as u8;
+    }
+
+    /// Convert packed 4:2:2 YUV, where two pixels are stored as Y0 U Y1 V, to RGB
+    pub fn yuyv_to_rgb(src: &[u8], dest: &mut [u8]) {
+        for (yuyv, rgb) in src.chunks_exact(4).zip(dest.chunks_exact_mut(6)) {
+            yuv_to_rgb(yuyv[0], yuyv[1], yuyv[3], &mut rgb[..3]);
+            yuv_to_rgb(yuyv[2], yuyv[1], yuyv[3], &mut rgb[3..]);
+        }
+    }
+}
+
+/// Run the SIMD version of a kernel for the selected level, followed by the scalar version for
+/// the values that are left
+macro_rules! dispatch {
+    ($name:ident, $src:expr, $dest:expr, $src_step:expr, $dest_step:expr) => {{
+        let (src, dest) = ($src, $dest);
+        // The unsafe implementations are only called when `set_level` or `detect` checked that
+        // the CPU supports them
+        let n = match level() {
+            #[cfg(target_arch = "x86_64")]
+            Level::Avx2 => unsafe { avx2::$name(src, dest) },
+            #[cfg(target_arch = "x86_64")]
+            Level::Sse41 => unsafe { sse41::$name(src, dest) },
+            #[cfg(target_arch = "x86_64")]
+            Level::Sse2 => sse2::$name(src, dest),
+            #[cfg(target_arch = "aarch64")]
+            Level::Neon => unsafe { neon::$name(src, dest) },
+            _ => 0,
+        };
+        scalar::$name(&src[n * $src_step..], &mut dest[n * $dest_step..]);
+    }};
+}
+
+/// Convert `u8` values to `f32` values from 0 to 1
+pub fn u8_to_f32(src: &[u8], dest: &mut [f32]) {
+    dispatch!(u8_to_f32, src, dest, 1, 1)
+}
+
+/// Convert `f32` values from 0 to 1 to `u8` values, like `Type::convert` values that are out of
+/// range and `NaN` are converted to 0
+pub fn f32_to_u8(src: &[f32], dest: &mut [u8]) {
+    dispatch!(f32_to_u8, src, dest, 1, 1)
+}
+
+/// Swap the first and third channel of 8-bit 4 channel pixels, converting RGBA to BGRA and back
+pub fn swap_rb(src: &[u8], dest: &mut [u8]) {
+    dispatch!(swap_rb, src, dest, 1, 1)
+}
+
+/// Blend 8-bit RGBA pixels into `dest` using the alpha of `src`, which is the same as
+/// compositing `src` over `dest` when `dest` is opaque
+pub fn blend_rgba(src: &[u8], dest: &mut [u8]) {
+    dispatch!(blend_rgba, src, dest, 1, 1)
+}
+
+/// Convert packed 4:2:2 YUV, where two pixels are stored as Y0 U Y1 V, to RGB
+pub fn yuyv_to_rgb(src: &[u8], dest: &mut [u8]) {
+    dispatch!(yuyv_to_rgb, src, dest, 2, 3)
+}
+
+/// Convert between `u8` and `f32` using the SIMD kernels, returns false for other types
+pub(crate) fn convert<T: Type, U: Type>(src: &[T], dest: &mut [U]) -> bool {
+    use core::any::TypeId;
+
+    if src.len() != dest.len() {
+        return false;
+    }
+
+    // Only the built-in types are reinterpreted, other types with the same size may use a
+    // different representation
+    let len = src.len();
+    let (u8_id, f32_id) = (TypeId::of::<u8>(), TypeId::of::<f32>());
+    match (TypeId::of::<T>(), TypeId::of::<U>()) {
+        (t, u) if t == u8_id && u == f32_id => {
+            let (src, dest) = unsafe {
+                (
+                    core::slice::from_raw_parts(src.as_ptr() as *const u8, len),
+                    core::slice::from_raw_parts_mut(dest.as_mut_ptr() as *mut f32, len),
+                )
+            };
+            u8_to_f32(src, dest);
+            true
+        }
+        (t, u) if t == f32_id && u == u8_id => {
+            let (src, dest) = unsafe {
+                (
+                    core::slice::from_raw_parts(src.as_ptr() as *const f32, len),
+                    core::slice::from_raw_parts_mut(dest.as_mut_ptr() as *mut u8, len),
+                )
+            };
+            f32_to_u8(src, dest);
+            true
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    /// Held by tests that change the level, which is shared by every test thread
+    pub(crate) static LEVEL_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    fn check_kernels() {
+        // Every u8 value converts the same way as `Type::convert`
+        let bytes: Vec<u8> = (0..=255).collect();
+        let mut floats = vec![0.0; 256];
+        u8_to_f32(&bytes, &mut floats);
+        for (b, f) in bytes.iter().zip(&floats) {
+            assert_eq!(*f, b.convert::<f32>());
+        }
+
+        let mut values: Vec<f32> = (0..1000).map(|i| i as f32 / 997.0 - 0.1).collect();
+        values.extend([f32::NAN, f32::INFINITY, -f32::INFINITY, 2.0 / 255.0, 0.5]);
+        let (mut a, mut b) = (vec![0; values.len()], vec![0; values.len()]);
+        f32_to_u8(&values, &mut a);
+        scalar::f32_to_u8(&values, &mut b);
+        assert_eq!(a, b);
+        for (v, x) in values.iter().zip(&a) {
+            assert_eq!(*x, v.convert::<u8>());
+        }
+
+        let pixels: Vec<u8> = (0..4099u32).map(|i| (i * 7919 % 251) as u8).collect();
+        let (mut a, mut b) = (vec![0; 4096], vec![0; 4096]);
+        swap_rb(&pixels[..4096], &mut a);
+        scalar::swap_rb(&pixels[..4096], &mut b);
+        assert_eq!(a, b);
+        assert_eq!(&a[..4], &[pixels[2], pixels[1], pixels[0], pixels[3]]);
+
+        let (mut a, mut b) = (pixels[3..].to_vec(), pixels[3..].to_vec());
+        blend_rgba(&pixels[..4096], &mut a);
+        scalar::blend_rgba(&pixels[..4096], &mut b);
+        assert_eq!(a, b);
+
+        let mut dest = [0, 0, 255, 255];
+        blend_rgba(&[255, 0, 0, 128], &mut dest);
+        assert_eq!(dest, [128, 0, 127, 255]);
+
+        // Other types are never reinterpreted, even when they have the same size
+        use crate::image_buf::test::Shifted;
+        let mut dest = vec![0.0f32; 4];
+        assert!(!convert(&[Shifted::new(255); 4], &mut dest));
+        assert!(!convert(&[0u16; 4], &mut [0u8; 4]));
+        assert!(convert(&[255u8; 4], &mut dest));
+        assert_eq!(dest, [1.0; 4]);
+
+        // Odd sizes exercise the scalar remainder
+        let (mut a, mut b) = (vec![0; 6141], vec![0; 6141]);
+        yuyv_to_rgb(&pixels[..4094], &mut a);
+        scalar::yuyv_to_rgb(&pixels[..4094], &mut b);
+        assert_eq!(a, b);
+
+        let mut rgb = [0; 6];
+        yuyv_to_rgb(&[235, 128, 16, 128], &mut rgb);
+        assert_eq!(rgb, [255, 255, 255, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_simd_kernels() {
+        let _lock = LEVEL_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        for selected in Level::ALL
+            .iter()
+            .copied()
+            .filter(|level| level.is_supported())
+        {
+            assert!(set_level(selected));
+            assert_eq!(level(), selected);
+            check_kernels();
+        }
+        set_level(Level::detect());
+        assert!(Level::Scalar.is_supported());
+    }
+}
@@ -0,0 +1,83 @@
+//! NEON implementations, kernels without a NEON version return 0 and use the scalar version,
+//! which the compiler already vectorizes using NEON
+
+use core::arch::aarch64::*;
+
+#[target_feature(enable = "neon")]
+pub unsafe fn u8_to_f32(src: &[u8], dest: &mut [f32]) -> usize {
+    let n = src.len().min(dest.len()) / 16 * 16;
+    let max = vdupq_n_f32(255.0);
+    for i in (0..n).step_by(16) {
+        let v = vld1q_u8(src.as_ptr().add(i));
+        let lo = vmovl_u8(vget_low_u8(v));
+        let hi = vmovl_u8(vget_high_u8(v));
+        let parts = [
+            vmovl_u16(vget_low_u16(lo)),
+            vmovl_u16(vget_high_u16(lo)),
+            vmovl_u16(vget_low_u16(hi)),
+            vmovl_u16(vget_high_u16(hi)),
+        ];
+        for (j, part) in parts.iter().enumerate() {
+            let f = vdivq_f32(vcvtq_f32_u32(*part), max);
+            vst1q_f32(dest.as_mut_ptr().add(i + j * 4), f);
+        }
+    }
+    n
+}
+
+#[target_feature(enable = "neon")]
+pub unsafe fn f32_to_u8(_src: &[f32], _dest: &mut [u8]) -> usize {
+    0
+}
+
+#[target_feature(enable = "neon")]
+pub unsafe fn swap_rb(src: &[u8], dest: &mut [u8]) -> usize {
+    let n = src.len().min(dest.len()) / 64 * 64;
+    for i in (0..n).step_by(64) {
+        let v = vld4q_u8(src.as_ptr().add(i));
+        vst4q_u8(dest.as_mut_ptr().add(i), uint8x16x4_t(v.2, v.1, v.0, v.3));
+    }
+    n
+}
+
+/// Blend eight values of one channel, see `scalar::blend_rgba`
+#[target_feature(enable = "neon")]
+unsafe fn blend(s: uint8x8_t, d: uint8x8_t, a: uint8x8_t) -> uint8x8_t {
+    let x = vaddq_u16(
+        vaddq_u16(vmull_u8(s, a), vmull_u8(d, vsub_u8(vdup_n_u8(255), a))),
+        vdupq_n_u16(127),
+    );
+    // x / 255 == (x + 1 + (x >> 8)) >> 8 for every value of x that can occur here
+    vshrn_n_u16::<8>(vaddq_u16(vaddq_u16(x, vdupq_n_u16(1)), vshrq_n_u16::<8>(x)))
+}
+
+#[target_feature(enable = "neon")]
+unsafe fn blend_channel(s: uint8x16_t, d: uint8x16_t, a: uint8x16_t) -> uint8x16_t {
+    vcombine_u8(
+        blend(vget_low_u8(s), vget_low_u8(d), vget_low_u8(a)),
+        blend(vget_high_u8(s), vget_high_u8(d), vget_high_u8(a)),
+    )
+}
+
+#[target_feature(enable = "neon")]
+pub unsafe fn blend_rgba(src: &[u8], dest: &mut [u8]) -> usize {
+    let n = src.len().min(dest.len()) / 64 * 64;
+    let opaque = vdupq_n_u8(255);
+    for i in (0..n).step_by(64) {
+        let s = vld4q_u8(src.as_ptr().add(i));
+        let d = vld4q_u8(dest.as_ptr().add(i));
+        let out = uint8x16x4_t(
+            blend_channel(s.0, d.0, s.3),
+            blend_channel(s.1, d.1, s.3),
+            blend_channel(s.2, d.2, s.3),
+            blend_channel(opaque, d.3, s.3),
+        );
+        vst4q_u8(dest.as_mut_ptr().add(i), out);
+    }
+    n
+}
+
+#[target_feature(enable = "neon")]
+pub unsafe fn yuyv_to_rgb(_src: &[u8], _dest: &mut [u8]) -> usize {
+    0
+}
@@ -0,0 +1,167 @@
+//! SSE2 implementations, each function returns the number of pixels or values it converted
+
+use core::arch::x86_64::*;
+
+pub fn u8_to_f32(src: &[u8], dest: &mut [f32]) -> usize {
+    let n = src.len().min(dest.len()) / 16 * 16;
+    unsafe {
+        let zero = _mm_setzero_si128();
+        let max = _mm_set1_ps(255.0);
+        for i in (0..n).step_by(16) {
+            let v = _mm_loadu_si128(src.as_ptr().add(i) as *const __m128i);
+            let lo = _mm_unpacklo_epi8(v, zero);
+            let hi = _mm_unpackhi_epi8(v, zero);
+            let parts = [
+                _mm_unpacklo_epi16(lo, zero),
+                _mm_unpackhi_epi16(lo, zero),
+                _mm_unpacklo_epi16(hi, zero),
+                _mm_unpackhi_epi16(hi, zero),
+            ];
+            for (j, part) in parts.iter().enumerate() {
+                let f = _mm_div_ps(_mm_cvtepi32_ps(*part), max);
+                _mm_storeu_ps(dest.as_mut_ptr().add(i + j * 4), f);
+            }
+        }
+    }
+    n
+}
+
+pub fn f32_to_u8(src: &[f32], dest: &mut [u8]) -> usize {
+    let n = src.len().min(dest.len()) / 4 * 4;
+    unsafe {
+        // Scaling is done using doubles to round the same way as the scalar version
+        let scale = _mm_set1_pd(255.0);
+        let min = _mm_set1_pd(-1.0);
+        let max = _mm_set1_pd(256.0);
+        for i in (0..n).step_by(4) {
+            let v = _mm_loadu_ps(src.as_ptr().add(i));
+            let halves = [_mm_cvtps_pd(v), _mm_cvtps_pd(_mm_movehl_ps(v, v))];
+            // Comparisons are false for NaN, so it is masked out along with values out of range
+            let [lo, hi] = halves.map(|x| {
+                let x = _mm_mul_pd(x, scale);
+                let valid = _mm_and_pd(_mm_cmpgt_pd(x, min), _mm_cmplt_pd(x, max));
+                _mm_cvttpd_epi32(_mm_and_pd(x, valid))
+            });
+            let ints = _mm_unpacklo_epi64(lo, hi);
+            let bytes = _mm_packus_epi16(_mm_packs_epi32(ints, ints), ints);
+            let word = _mm_cvtsi128_si32(bytes) as u32;
+            dest[i..i + 4].copy_from_slice(&word.to_le_bytes());
+        }
+    }
+    n
+}
+
+pub fn swap_rb(src: &[u8], dest: &mut [u8]) -> usize {
+    let n = src.len().min(dest.len()) / 16 * 16;
+    unsafe {
+        let ga = _mm_set1_epi32(0xff00_ff00u32 as i32);
+        let low = _mm_set1_epi32(0xff);
+        for i in (0..n).step_by(16) {
+            let v = _mm_loadu_si128(src.as_ptr().add(i) as *const __m128i);
+            let r = _mm_slli_epi32(_mm_and_si128(v, low), 16);
+            let b = _mm_and_si128(_mm_srli_epi32(v, 16), low);
+            let out = _mm_or_si128(_mm_and_si128(v, ga), _mm_or_si128(r, b));
+            _mm_storeu_si128(dest.as_mut_ptr().add(i) as *mut __m128i, out);
+        }
+    }
+    n
+}
+
+pub fn blend_rgba(src: &[u8], dest: &mut [u8]) -> usize {
+    let n = src.len().min(dest.len()) / 16 * 16;
+    unsafe {
+        let zero = _mm_setzero_si128();
+        let max = _mm_set1_epi16(255);
+        let half = _mm_set1_epi16(127);
+        let one = _mm_set1_epi16(1);
+        let opaque = _mm_set_epi16(255, 0, 0, 0, 255, 0, 0, 0);
+        // Computed with unsigned 16-bit lanes, the largest intermediate value is 65152
+        let blend = |s: __m128i, d: __m128i| {
+            let a = _mm_shufflehi_epi16(_mm_shufflelo_epi16(s, 0xff), 0xff);
+            let s = _mm_or_si128(s, opaque);
+            let x = _mm_add_epi16(
+                _mm_add_epi16(
+                    _mm_mullo_epi16(s, a),
+                    _mm_mullo_epi16(d, _mm_sub_epi16(max, a)),
+                ),
+                half,
+            );
+            // x / 255 == (x + 1 + (x >> 8)) >> 8 for every value of x that can occur here
+            _mm_srli_epi16(
+                _mm_add_epi16(_mm_add_epi16(x, one), _mm_srli_epi16(x, 8)),
+                8,
+            )
+        };
+
+        for i in (0..n).step_by(16) {
+            let s = _mm_loadu_si128(src.as_ptr().add(i) as *const __m128i);
+            let d = _mm_loadu_si128(dest.as_ptr().add(i) as *const __m128i);
+            let lo = blend(_mm_unpacklo_epi8(s, zero), _mm_unpacklo_epi8(d, zero));
+            let hi = blend(_mm_unpackhi_epi8(s, zero), _mm_unpackhi_epi8(d, zero));
+            _mm_storeu_si128(
+                dest.as_mut_ptr().add(i) as *mut __m128i,
+                _mm_packus_epi16(lo, hi),
+            );
+        }
+    }
+    n
+}
+
+/// Multiply pairs of 16-bit values by `ca` and `cb` and add them, giving 32-bit results for
+/// the low and high four lanes
+unsafe fn madd(a: __m128i, b: __m128i, ca: i16, cb: i16) -> (__m128i, __m128i) {
+    let c = _mm_set1_epi32(i32::from(cb) << 16 | i32::from(ca) & 0xffff);
+    (
+        _mm_madd_epi16(_mm_unpacklo_epi16(a, b), c),
+        _mm_madd_epi16(_mm_unpackhi_epi16(a, b), c),
+    )
+}
+
+/// Round, shift and saturate eight 32-bit values to bytes, stored in the low half
+unsafe fn finish((lo, hi): (__m128i, __m128i)) -> __m128i {
+    let round = _mm_set1_epi32(128);
+    let lo = _mm_srai_epi32(_mm_add_epi32(lo, round), 8);
+    let hi = _mm_srai_epi32(_mm_add_epi32(hi, round), 8);
+    let words = _mm_packs_epi32(lo, hi);
+    _mm_packus_epi16(words, words)
+}
+
+/// Convert eight YUYV pixels to RGB, returning the red, green and blue values in the low half of
+/// each vector
+pub(super) unsafe fn yuyv_planes(v: __m128i) -> [__m128i; 3] {
+    let mask = _mm_set1_epi16(0xff);
+    let y = _mm_sub_epi16(_mm_and_si128(v, mask), _mm_set1_epi16(16));
+    let uv = _mm_sub_epi16(_mm_srli_epi16(v, 8), _mm_set1_epi16(128));
+    // Each U and V value is shared by two pixels
+    let d = _mm_shufflehi_epi16(_mm_shufflelo_epi16(uv, 0xa0), 0xa0);
+    let e = _mm_shufflehi_epi16(_mm_shufflelo_epi16(uv, 0xf5), 0xf5);
+
+    let green = {
+        let (lo, hi) = madd(y, d, 298, -100);
+        let (elo, ehi) = madd(e, _mm_setzero_si128(), -208, 0);
+        (_mm_add_epi32(lo, elo), _mm_add_epi32(hi, ehi))
+    };
+    [
+        finish(madd(y, e, 298, 409)),
+        finish(green),
+        finish(madd(y, d, 298, 516)),
+    ]
+}
+
+pub fn yuyv_to_rgb(src: &[u8], dest: &mut [u8]) -> usize {
+    let n = (src.len() / 2).min(dest.len() / 3) / 8 * 8;
+    let mut planes = [[0u8; 16]; 3];
+    for i in (0..n).step_by(8) {
+        unsafe {
+            let v = _mm_loadu_si128(src.as_ptr().add(i * 2) as *const __m128i);
+            for (plane, x) in planes.iter_mut().zip(yuyv_planes(v)) {
+                _mm_storeu_si128(plane.as_mut_ptr() as *mut __m128i, x);
+            }
+        }
+
+        for (k, rgb) in dest[i * 3..(i + 8) * 3].chunks_exact_mut(3).enumerate() {
+            rgb.copy_from_slice(&[planes[0][k], planes[1][k], planes[2][k]]);
+        }
+    }
+    n
+}
@@ -0,0 +1,94 @@
+//! SSE4.1 implementations, kernels that don't benefit from SSE4.1 use the SSE2 versions
+
+use core::arch::x86_64::*;
+
+use super::sse2;
+
+/// `pshufb` mask that moves the values of channel `c` to their place in part `part` of 16 packed
+/// 3 channel pixels
+const fn interleave_mask(part: usize, c: usize) -> [i8; 16] {
+    let mut mask = [-1; 16];
+    let mut i = 0;
+    while i < 16 {
+        let index = part * 16 + i;
+        if index % 3 == c {
+            mask[i] = (index / 3) as i8;
+        }
+        i += 1;
+    }
+    mask
+}
+
+const INTERLEAVE: [[[i8; 16]; 3]; 3] = {
+    let mut masks = [[[0; 16]; 3]; 3];
+    let mut part = 0;
+    while part < 3 {
+        let mut c = 0;
+        while c < 3 {
+            masks[part][c] = interleave_mask(part, c);
+            c += 1;
+        }
+        part += 1;
+    }
+    masks
+};
+
+#[target_feature(enable = "sse4.1")]
+pub unsafe fn u8_to_f32(src: &[u8], dest: &mut [f32]) -> usize {
+    sse2::u8_to_f32(src, dest)
+}
+
+#[target_feature(enable = "sse4.1")]
+pub unsafe fn f32_to_u8(src: &[f32], dest: &mut [u8]) -> usize {
+    sse2::f32_to_u8(src, dest)
+}
+
+#[target_feature(enable = "sse4.1")]
+pub unsafe fn swap_rb(src: &[u8], dest: &mut [u8]) -> usize {
+    let n = src.len().min(dest.len()) / 16 * 16;
+    let mask = _mm_setr_epi8(2, 1, 0, 3, 6, 5, 4, 7, 10, 9, 8, 11, 14, 13, 12, 15);
+    for i in (0..n).step_by(16) {
+        let v = _mm_loadu_si128(src.as_ptr().add(i) as *const __m128i);
+        _mm_storeu_si128(
+            dest.as_mut_ptr().add(i) as *mut __m128i,
+            _mm_shuffle_epi8(v, mask),
+        );
+    }
+    n
+}
+
+#[target_feature(enable = "sse4.1")]
+pub unsafe fn blend_rgba(src: &[u8], dest: &mut [u8]) -> usize {
+    sse2::blend_rgba(src, dest)
+}
+
+/// Interleave 16 red, green and blue values to 48 bytes of RGB
+#[target_feature(enable = "sse4.1")]
+pub(super) unsafe fn store_rgb(planes: [__m128i; 3], dest: *mut u8) {
+    for (part, masks) in INTERLEAVE.iter().enumerate() {
+        let mut out = _mm_setzero_si128();
+        for (plane, mask) in planes.iter().zip(masks) {
+            let mask = _mm_loadu_si128(mask.as_ptr() as *const __m128i);
+            out = _mm_or_si128(out, _mm_shuffle_epi8(*plane, mask));
+        }
+        _mm_storeu_si128(dest.add(part * 16) as *mut __m128i, out);
+    }
+}
+
+#[target_feature(enable = "sse4.1")]
+pub unsafe fn yuyv_to_rgb(src: &[u8], dest: &mut [u8]) -> usize {
+    let n = (src.len() / 2).min(dest.len() / 3) / 16 * 16;
+    for i in (0..n).step_by(16) {
+        let a = sse2::yuyv_planes(_mm_loadu_si128(src.as_ptr().add(i * 2) as *const __m128i));
+        let b = sse2::yuyv_planes(_mm_loadu_si128(
+            src.as_ptr().add(i * 2 + 16) as *const __m128i
+        ));
+        let planes = [
+            _mm_unpacklo_epi64(a[0], b[0]),
+            _mm_unpacklo_epi64(a[1], b[1]),
+            _mm_unpacklo_epi64(a[2], b[2]),
+        ];
+        store_rgb(planes, dest.as_mut_ptr().add(i * 3));
+    }
+    n
+}
@@ -0,0 +1,421 @@
+//! Image statistics
+
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::color::Color;
+use crate::image::Image;
+use crate::ty::Type;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Per-channel histogram
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    min: f64,
+    max: f64,
+    data: Vec<Vec<usize>>,
+}
+
+impl Histogram {
+    /// Create an empty histogram with the given number of channels and bins covering values
+    /// from `min` to `max`
+    pub fn new(channels: usize, bins: usize, min: f64, max: f64) -> Histogram {
+        Histogram {
+            min,
+            max,
+            data: vec![vec![0; bins.max(1)]; channels],
+        }
+    }
+
+    /// Number of channels
+    pub fn channels(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Number of bins per channel
+    pub fn bins(&self) -> usize {
+        self.data.first().map(|x| x.len()).unwrap_or(0)
+    }
+
+    /// The range of values covered by the histogram
+    pub fn range(&self) -> (f64, f64) {
+        (self.min, self.max)
+    }
+
+    /// Counts for a single channel
+    pub fn channel(&self, c: usize) -> &[usize] {
+        &self.data[c]
+    }
+
+    /// Total number of values counted in channel `c`
+    pub fn total(&self, c: usize) -> usize {
+        self.data[c].iter().sum()
+    }
+
+    /// Get the bin index for a value, returns `None` if the value is out of range
+    pub fn bin_index(&self, value: f64) -> Option<usize> {
+        if value < self.min || value > self.max || value.is_nan() {
+            return None;
+        }
+
+        let bins = self.bins();
+        let f = (value - self.min) / (self.max - self.min) * bins as f64;
+        Some((f as usize).min(bins - 1))
+    }
+
+    /// The value at the center of bin `index`
+    pub fn bin_value(&self, index: usize) -> f64 {
+        let width = (self.max - self.min) / self.bins() as f64;
+        self.min + width * (index as f64 + 0.5)
+    }
+
+    /// Add a value to channel `c`, values outside of the histogram range are ignored
+    pub fn add(&mut self, c: usize, value: f64) {
+        if let Some(index) = self.bin_index(value) {
+            self.data[c][index] += 1;
+        }
+    }
+
+    /// Cumulative counts for a single channel
+    pub fn cumulative(&self, c: usize) -> Vec<usize> {
+        let mut total = 0;
+        self.data[c]
+            .iter()
+            .map(|x| {
+                total += x;
+                total
+            })
+            .collect()
+    }
+
+    /// Cumulative counts normalized to the range 0 to 1
+    pub fn cumulative_f(&self, c: usize) -> Vec<f64> {
+        let total = self.total(c).max(1) as f64;
+        self.cumulative(c)
+            .into_iter()
+            .map(|x| x as f64 / total)
+            .collect()
+    }
+
+    #[cfg(feature = "parallel")]
+    fn merge(mut self, other: Histogram) -> Histogram {
+        for (a, b) in self.data.iter_mut().zip(other.data) {
+            for (x, y) in a.iter_mut().zip(b) {
+                *x += y;
+            }
+        }
+        self
+    }
+}
+
+/// Compute a histogram for each channel, the range covers every possible value of `T` for
+/// integer types and 0 to 1 for floating point types
+pub fn histogram<T: Type, C: Color, I: Image<T, C>>(image: &I, bins: usize) -> Histogram {
+    histogram_range(image, bins, T::min_f(), T::max_f())
+}
+
+/// Compute a histogram for each channel covering the values from `min` to `max`, this is useful
+/// for floating point images that contain values outside of the normalized range. Values outside
+/// of the range are not counted.
+#[cfg(feature = "parallel")]
+pub fn histogram_range<T: Type, C: Color, I: Image<T, C>>(
+    image: &I,
+    bins: usize,
+    min: f64,
+    max: f64,
+) -> Histogram {
+    let channels = C::channels();
+    crate::parallel::install(|| {
+        image
+            .data()
+            .par_chunks(channels * image.width().max(1))
+            .fold(
+                || Histogram::new(channels, bins, min, max),
+                |mut hist, row| {
+                    for px in row.chunks_exact(channels) {
+                        for (c, x) in px.iter().enumerate() {
+                            hist.add(c, T::to_float(x));
+                        }
+                    }
+                    hist
+                },
+            )
+            .reduce(
+                || Histogram::new(channels, bins, min, max),
+                Histogram::merge,
+            )
+    })
+}
+
+/// Compute a histogram for each channel covering the values from `min` to `max`, this is useful
+/// for floating point images that contain values outside of the normalized range. Values outside
+/// of the range are not counted.
+#[cfg(not(feature = "parallel"))]
+pub fn histogram_range<T: Type, C: Color, I: Image<T, C>>(
+    image: &I,
+    bins: usize,
+    min: f64,
+    max: f64,
+) -> Histogram {
+    let channels = C::channels();
+    let mut hist = Histogram::new(channels, bins, min, max);
+    for px in image.data().chunks_exact(channels) {
+        for (c, x) in px.iter().enumerate() {
+            hist.add(c, T::to_float(x));
+        }
+    }
+    hist
+}
+
+/// Per-channel summary statistics, computed using `stats::summary`
+#[cfg_attr(feature = "ser", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    count: usize,
+    min: Vec<f64>,
+    max: Vec<f64>,
+    min_loc: Vec<(usize, usize)>,
+    max_loc: Vec<(usize, usize)>,
+    sum: Vec<f64>,
+    sum_sq: Vec<f64>,
+}
+
+impl Summary {
+    fn new(channels: usize) -> Summary {
+        Summary {
+            count: 0,
+            min: vec![f64::INFINITY; channels],
+            max: vec![f64::NEG_INFINITY; channels],
+            min_loc: vec![(0, 0); channels],
+            max_loc: vec![(0, 0); channels],
+            sum: vec![0.0; channels],
+            sum_sq: vec![0.0; channels],
+        }
+    }
+
+    fn add_row<T: Type>(mut self, y: usize, row: &[T], channels: usize) -> Summary {
+        for (x, px) in row.chunks_exact(channels).enumerate() {
+            for (c, v) in px.iter().enumerate() {
+                let v = T::to_float(v);
+                if v < self.min[c] {
+                    self.min[c] = v;
+                    self.min_loc[c] = (x, y);
+                }
+                if v > self.max[c] {
+                    self.max[c] = v;
+                    self.max_loc[c] = (x, y);
+                }
+                self.sum[c] += v;
+                self.sum_sq[c] += v * v;
+            }
+            self.count += 1;
+        }
+        self
+    }
+
+    #[cfg(feature = "parallel")]
+    fn merge(mut self, other: Summary) -> Summary {
+        let first = |a: (usize, usize), b: (usize, usize)| (a.1, a.0) <= (b.1, b.0);
+        for c in 0..self.min.len() {
+            if other.min[c] < self.min[c]
+                || (other.min[c] == self.min[c] && !first(self.min_loc[c], other.min_loc[c]))
+            {
+                self.min[c] = other.min[c];
+                self.min_loc[c] = other.min_loc[c];
+            }
+            if other.max[c] > self.max[c]
+                || (other.max[c] == self.max[c] && !first(self.max_loc[c], other.max_loc[c]))
+            {
+                self.max[c] = other.max[c];
+                self.max_loc[c] = other.max_loc[c];
+            }
+            self.sum[c] += other.sum[c];
+            self.sum_sq[c] += other.sum_sq[c];
+        }
+        self.count += other.count;
+        self
+    }
+
+    /// Number of pixels
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Minimum value of each channel
+    pub fn min(&self) -> &[f64] {
+        &self.min
+    }
+
+    /// Maximum value of each channel
+    pub fn max(&self) -> &[f64] {
+        &self.max
+    }
+
+    /// Location of the first minimum value of each channel
+    pub fn min_loc(&self) -> &[(usize, usize)] {
+        &self.min_loc
+    }
+
+    /// Location of the first maximum value of each channel
+    pub fn max_loc(&self) -> &[(usize, usize)] {
+        &self.max_loc
+    }
+
+    /// Mean value of each channel
+    pub fn mean(&self) -> Vec<f64> {
+        let n = self.count.max(1) as f64;
+        self.sum.iter().map(|x| x / n).collect()
+    }
+
+    /// Population standard deviation of each channel
+    pub fn stddev(&self) -> Vec<f64> {
+        let n = self.count.max(1) as f64;
+        self.sum
+            .iter()
+            .zip(&self.sum_sq)
+            .map(|(s, sq)| {
+                let mean = s / n;
+                (sq / n - mean * mean).max(0.0).sqrt()
+            })
+            .collect()
+    }
+}
+
+/// Location and value of the minimum and maximum of a single channel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MinMaxLoc {
+    pub min: f64,
+    pub max: f64,
+    pub min_loc: (usize, usize),
+    pub max_loc: (usize, usize),
+}
+
+/// Compute the minimum, maximum, mean and standard deviation of each channel in a single pass.
+/// All values are returned using the same scale as the image data, not normalized.
+#[cfg(feature = "parallel")]
+pub fn summary<T: Type, C: Color, I: Image<T, C>>(image: &I) -> Summary {
+    let channels = C::channels();
+    crate::parallel::install(|| {
+        image
+            .data()
+            .par_chunks(channels * image.width().max(1))
+            .enumerate()
+            .fold(
+                || Summary::new(channels),
+                |acc, (y, row)| acc.add_row(y, row, channels),
+            )
+            .reduce(|| Summary::new(channels), Summary::merge)
+    })
+}
+
+/// Compute the minimum, maximum, mean and standard deviation of each channel in a single pass.
+/// All values are returned using the same scale as the image data, not normalized.
+#[cfg(not(feature = "parallel"))]
+pub fn summary<T: Type, C: Color, I: Image<T, C>>(image: &I) -> Summary {
+    let channels = C::channels();
+    image
+        .data()
+        .chunks(channels * image.width().max(1))
+        .enumerate()
+        .fold(Summary::new(channels), |acc, (y, row)| {
+            acc.add_row(y, row, channels)
+        })
+}
+
+/// Minimum value of each channel
+pub fn min<T: Type, C: Color, I: Image<T, C>>(image: &I) -> Vec<f64> {
+    summary(image).min
+}
+
+/// Maximum value of each channel
+pub fn max<T: Type, C: Color, I: Image<T, C>>(image: &I) -> Vec<f64> {
+    summary(image).max
+}
+
+/// Mean value of each channel
+pub fn mean<T: Type, C: Color, I: Image<T, C>>(image: &I) -> Vec<f64> {
+    summary(image).mean()
+}
+
+/// Standard deviation of each channel
+pub fn stddev<T: Type, C: Color, I: Image<T, C>>(image: &I) -> Vec<f64> {
+    summary(image).stddev()
+}
+
+/// Minimum and maximum values of each channel along with their locations
+pub fn min_max_loc<T: Type, C: Color, I: Image<T, C>>(image: &I) -> Vec<MinMaxLoc> {
+    let s = summary(image);
+    (0..C::channels())
+        .map(|c| MinMaxLoc {
+            min: s.min[c],
+            max: s.max[c],
+            min_loc: s.min_loc[c],
+            max_loc: s.max_loc[c],
+        })
+        .collect()
+}
+
+/// Get the value below which `p` percent of the values of each channel fall, using the nearest
+/// rank method
+pub fn percentile<T: Type, C: Color, I: Image<T, C>>(image: &I, p: f64) -> Vec<f64> {
+    let channels = C::channels();
+    let data = image.data();
+    (0..channels)
+        .map(|c| {
+            let mut values: Vec<f64> = data
+                .iter()
+                .skip(c)
+                .step_by(channels)
+                .map(T::to_float)
+                .collect();
+            if values.is_empty() {
+                return 0.0;
+            }
+
+            let rank = (p.clamp(0.0, 100.0) / 100.0 * values.len() as f64).ceil() as usize;
+            let index = rank.max(1) - 1;
+            let (_, x, _) = values.select_nth_unstable_by(index, |a, b| {
+                a.partial_cmp(b).unwrap_or(core::cmp::Ordering::Equal)
+            });
+            *x
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{stats, Gray, ImageBuf, Rgb};
+
+    #[test]
+    fn test_histogram() {
+        let image: ImageBuf<u8, Gray> = ImageBuf::new_from(2, 2, vec![0, 10, 128, 255]);
+        let hist = stats::histogram(&image, 4);
+        assert_eq!(hist.channel(0), &[2, 0, 1, 1]);
+        assert_eq!(hist.cumulative(0), vec![2, 2, 3, 4]);
+
+        let image: ImageBuf<f32, Rgb> =
+            ImageBuf::new_from(1, 2, vec![0.5, 2.0, 4.0, 1.5, 3.0, 9.0]);
+        let hist = stats::histogram_range(&image, 4, 0.0, 4.0);
+        assert_eq!(hist.channel(0), &[1, 1, 0, 0]);
+        assert_eq!(hist.channel(1), &[0, 0, 1, 1]);
+        assert_eq!(hist.channel(2), &[0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_summary() {
+        let image: ImageBuf<u8, Gray> = ImageBuf::new_from(3, 2, vec![4, 2, 9, 2, 7, 6]);
+        let s = stats::summary(&image);
+        assert_eq!(s.min(), &[2.0]);
+        assert_eq!(s.max(), &[9.0]);
+        assert_eq!(s.min_loc(), &[(1, 0)]);
+        assert_eq!(s.max_loc(), &[(2, 0)]);
+        assert_eq!(s.mean(), vec![5.0]);
+        assert!((s.stddev()[0] - (40.0f64 / 6.0).sqrt()).abs() < 1e-9);
+        assert_eq!(stats::percentile(&image, 50.0), vec![4.0]);
+        assert_eq!(stats::percentile(&image, 100.0), vec![9.0]);
+    }
+}
@@ -0,0 +1,791 @@
+//! Panorama stitching
+//!
+//! Neighboring images are aligned by matching features and estimating a homography for each
+//! pair using RANSAC. The images are then warped into the coordinate system of the first image
+//! and combined using multi-band blending.
+//!
+//! ```rust,no_run
+//! use image2::{stitch, ImageBuf, Rgb};
+//!
+//! let images: Vec<ImageBuf<u8, Rgb>> = Vec::new();
+//! let panorama = stitch::stitch(&images);
+//! ```
+
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::color::{Color, Gray};
+use crate::error::Error;
+use crate::feature::{self, Keypoint};
+use crate::hdr::solve;
+use crate::image::Image;
+use crate::image_buf::ImageBuf;
+use crate::pyramid;
+use crate::ty::Type;
+
+/// Half the size of the square patch used to build descriptors
+const PATCH_RADIUS: usize = 8;
+
+/// Number of values in a descriptor, the patch is sampled on an 8x8 grid
+const DESCRIPTOR_SIZE: usize = 64;
+
+/// Minimum number of RANSAC inliers required to accept a homography
+const MIN_INLIERS: usize = 8;
+
+/// The output is rejected when it would be this many times larger than the inputs combined,
+/// which only happens when a homography is degenerate
+const MAX_CANVAS_SCALE: f64 = 16.0;
+
+/// A pair of points, one in each image, that correspond to the same point of the scene
+pub type Correspondence = ((f64, f64), (f64, f64));
+
+/// A 3x3 projective transform stored in row-major order
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Homography(pub [f64; 9]);
+
+impl Homography {
+    /// The identity transform
+    pub fn identity() -> Homography {
+        Homography([1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0])
+    }
+
+    /// A translation by (x, y)
+    pub fn translation(x: f64, y: f64) -> Homography {
+        Homography([1.0, 0.0, x, 0.0, 1.0, y, 0.0, 0.0, 1.0])
+    }
+
+    /// Transform a point
+    pub fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        let h = &self.0;
+        let w = h[6] * x + h[7] * y + h[8];
+        (
+            (h[0] * x + h[1] * y + h[2]) / w,
+            (h[3] * x + h[4] * y + h[5]) / w,
+        )
+    }
+
+    /// The transform that applies `other` followed by `self`
+    pub fn multiply(&self, other: &Homography) -> Homography {
+        let (a, b) = (&self.0, &other.0);
+        let mut dest = [0.0; 9];
+        for row in 0..3 {
+            for col in 0..3 {
+                dest[row * 3 + col] = (0..3).map(|k| a[row * 3 + k] * b[k * 3 + col]).sum();
+            }
+        }
+        Homography(dest)
+    }
+
+    /// The inverse transform, or `None` if the matrix is singular
+    pub fn inverse(&self) -> Option<Homography> {
+        let h = &self.0;
+        let cofactor = [
+            h[4] * h[8] - h[5] * h[7],
+            h[2] * h[7] - h[1] * h[8],
+            h[1] * h[5] - h[2] * h[4],
+            h[5] * h[6] - h[3] * h[8],
+            h[0] * h[8] - h[2] * h[6],
+            h[2] * h[3] - h[0] * h[5],
+            h[3] * h[7] - h[4] * h[6],
+            h[1] * h[6] - h[0] * h[7],
+            h[0] * h[4] - h[1] * h[3],
+        ];
+        let det = h[0] * cofactor[0] + h[1] * cofactor[3] + h[2] * cofactor[6];
+        if det.abs() < 1e-12 {
+            return None;
+        }
+
+        let mut dest = [0.0; 9];
+        for (d, c) in dest.iter_mut().zip(&cofactor) {
+            *d = c / det;
+        }
+        Some(Homography(dest))
+    }
+
+    /// Estimate the homography mapping the first point of each pair onto the second using the
+    /// normalized direct linear transform. At least four pairs are required.
+    pub fn estimate(pairs: &[Correspondence]) -> Option<Homography> {
+        if pairs.len() < 4 {
+            return None;
+        }
+
+        let src = normalization(pairs.iter().map(|p| p.0));
+        let dst = normalization(pairs.iter().map(|p| p.1));
+
+        // Least squares solution with h[8] fixed to 1, using the normal equations
+        let mut ata = [0.0; 64];
+        let mut atb = [0.0; 8];
+        for (a, b) in pairs {
+            let (x, y) = src.apply(a.0, a.1);
+            let (u, v) = dst.apply(b.0, b.1);
+            let rows = [
+                ([x, y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y], u),
+                ([0.0, 0.0, 0.0, x, y, 1.0, -v * x, -v * y], v),
+            ];
+            for (row, rhs) in &rows {
+                for i in 0..8 {
+                    for j in 0..8 {
+                        ata[i * 8 + j] += row[i] * row[j];
+                    }
+                    atb[i] += row[i] * rhs;
+                }
+            }
+        }
+
+        let h = solve(&mut ata, &mut atb, 8)?;
+        let normalized = Homography([h[0], h[1], h[2], h[3], h[4], h[5], h[6], h[7], 1.0]);
+        let dest = dst.inverse()?.multiply(&normalized).multiply(&src);
+        if dest.0.iter().any(|x| !x.is_finite()) || dest.0[8].abs() < 1e-12 {
+            return None;
+        }
+
+        let scale = dest.0[8];
+        let mut h = dest.0;
+        h.iter_mut().for_each(|x| *x /= scale);
+        Some(Homography(h))
+    }
+}
+
+/// Similarity transform that moves the centroid of `points` to the origin and scales their mean
+/// distance from it to sqrt(2)
+fn normalization<P: Iterator<Item = (f64, f64)> + Clone>(points: P) -> Homography {
+    let n = points.clone().count().max(1) as f64;
+    let (sx, sy) = points
+        .clone()
+        .fold((0.0, 0.0), |(sx, sy), (x, y)| (sx + x, sy + y));
+    let (cx, cy) = (sx / n, sy / n);
+    let distance = points
+        .map(|(x, y)| ((x - cx) * (x - cx) + (y - cy) * (y - cy)).sqrt())
+        .sum::<f64>()
+        / n;
+    let s = if distance > 0.0 {
+        core::f64::consts::SQRT_2 / distance
+    } else {
+        1.0
+    };
+    Homography([s, 0.0, -s * cx, 0.0, s, -s * cy, 0.0, 0.0, 1.0])
+}
+
+/// Keypoints and their descriptors
+#[derive(Debug, Clone, Default)]
+pub struct Features {
+    pub keypoints: Vec<Keypoint>,
+    descriptors: Vec<[f64; DESCRIPTOR_SIZE]>,
+}
+
+impl Features {
+    /// Number of features
+    pub fn len(&self) -> usize {
+        self.keypoints.len()
+    }
+
+    /// Returns true when no features were found
+    pub fn is_empty(&self) -> bool {
+        self.keypoints.is_empty()
+    }
+}
+
+/// A correspondence between feature `a` of one image and feature `b` of another
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Match {
+    pub a: usize,
+    pub b: usize,
+    /// Euclidean distance between the descriptors
+    pub distance: f64,
+}
+
+/// Detect up to `max_features` Harris corners and describe each one using a normalized,
+/// downsampled patch of the surrounding pixels
+pub fn detect<T: Type, C: Color, I: Image<T, C>>(image: &I, max_features: usize) -> Features {
+    detect_masked(image, None, max_features)
+}
+
+/// Like `detect`, ignoring features whose patch overlaps pixels that are not set in `mask`
+fn detect_masked<T: Type, C: Color, I: Image<T, C>>(
+    image: &I,
+    mask: Option<&[bool]>,
+    max_features: usize,
+) -> Features {
+    let (width, height) = (image.width(), image.height());
+    let gray = feature::luminance(image);
+    let corners = feature::non_max_suppression(feature::harris(image, 0.04, 0.01), 3.0);
+
+    let mut features = Features::default();
+    for keypoint in corners {
+        if features.len() >= max_features {
+            break;
+        }
+
+        let (x, y) = (keypoint.x, keypoint.y);
+        if x < PATCH_RADIUS
+            || y < PATCH_RADIUS
+            || x + PATCH_RADIUS > width
+            || y + PATCH_RADIUS > height
+        {
+            continue;
+        }
+
+        let (x0, y0) = (x - PATCH_RADIUS, y - PATCH_RADIUS);
+        if let Some(mask) = mask {
+            let covered =
+                (y0..y + PATCH_RADIUS).all(|j| (x0..x + PATCH_RADIUS).all(|i| mask[j * width + i]));
+            if !covered {
+                continue;
+            }
+        }
+
+        // Average 2x2 blocks of the patch
+        let mut descriptor = [0.0; DESCRIPTOR_SIZE];
+        for (k, d) in descriptor.iter_mut().enumerate() {
+            let (i, j) = (x0 + (k % 8) * 2, y0 + (k / 8) * 2);
+            *d = (gray[j * width + i]
+                + gray[j * width + i + 1]
+                + gray[(j + 1) * width + i]
+                + gray[(j + 1) * width + i + 1])
+                / 4.0;
+        }
+
+        let mean = descriptor.iter().sum::<f64>() / DESCRIPTOR_SIZE as f64;
+        let variance = descriptor
+            .iter()
+            .map(|d| (d - mean) * (d - mean))
+            .sum::<f64>()
+            / DESCRIPTOR_SIZE as f64;
+        if variance < 1e-12 {
+            continue;
+        }
+
+        let stddev = variance.sqrt();
+        descriptor
+            .iter_mut()
+            .for_each(|d| *d = (*d - mean) / stddev);
+        features.keypoints.push(keypoint);
+        features.descriptors.push(descriptor);
+    }
+    features
+}
+
+fn distance(a: &[f64; DESCRIPTOR_SIZE], b: &[f64; DESCRIPTOR_SIZE]) -> f64 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(x, y)| (x - y) * (x - y))
+        .sum::<f64>()
+        .sqrt()
+}
+
+/// The index and distance of the two nearest descriptors in `candidates`
+fn nearest(
+    descriptor: &[f64; DESCRIPTOR_SIZE],
+    candidates: &[[f64; DESCRIPTOR_SIZE]],
+) -> Option<(usize, f64, f64)> {
+    let mut best: Option<(usize, f64, f64)> = None;
+    for (i, c) in candidates.iter().enumerate() {
+        let d = distance(descriptor, c);
+        best = match best {
+            None => Some((i, d, f64::INFINITY)),
+            Some((j, first, second)) => {
+                if d < first {
+                    Some((i, d, first))
+                } else {
+                    Some((j, first, second.min(d)))
+                }
+            }
+        };
+    }
+    best
+}
+
+/// Match features using nearest neighbor search. A match is kept when its distance is less
+/// than `ratio` times the distance to the second nearest neighbor and the two features are
+/// each other's nearest neighbor.
+pub fn match_features(a: &Features, b: &Features, ratio: f64) -> Vec<Match> {
+    let mut matches = Vec::new();
+    for (i, descriptor) in a.descriptors.iter().enumerate() {
+        let (j, first, second) = match nearest(descriptor, &b.descriptors) {
+            Some(n) => n,
+            None => break,
+        };
+        if first >= ratio * second {
+            continue;
+        }
+
+        match nearest(&b.descriptors[j], &a.descriptors) {
+            Some((k, _, _)) if k == i => matches.push(Match {
+                a: i,
+                b: j,
+                distance: first,
+            }),
+            _ => (),
+        }
+    }
+    matches
+}
+
+/// Small deterministic generator used to pick RANSAC samples
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// Robustly estimate the homography mapping the first point of each pair onto the second.
+/// Pairs that are mapped within `threshold` pixels are considered inliers; the best of
+/// `iterations` random samples is refined using all of its inliers. Returns the homography and
+/// the indices of the inliers.
+pub fn ransac_homography(
+    pairs: &[Correspondence],
+    threshold: f64,
+    iterations: usize,
+) -> Option<(Homography, Vec<usize>)> {
+    let inliers = |h: &Homography| -> Vec<usize> {
+        pairs
+            .iter()
+            .enumerate()
+            .filter(|(_, (a, b))| {
+                let (x, y) = h.apply(a.0, a.1);
+                let (dx, dy) = (x - b.0, y - b.1);
+                dx * dx + dy * dy <= threshold * threshold
+            })
+            .map(|(i, _)| i)
+            .collect()
+    };
+
+    if pairs.len() < 4 {
+        return None;
+    }
+
+    let mut rng = XorShift(0x9e37_79b9_7f4a_7c15);
+    let mut best: Option<Vec<usize>> = None;
+    for _ in 0..iterations {
+        let mut sample = [0usize; 4];
+        let mut n = 0;
+        while n < 4 {
+            let i = (rng.next() % pairs.len() as u64) as usize;
+            if !sample[..n].contains(&i) {
+                sample[n] = i;
+                n += 1;
+            }
+        }
+
+        let points: Vec<_> = sample.iter().map(|i| pairs[*i]).collect();
+        let h = match Homography::estimate(&points) {
+            Some(h) => h,
+            None => continue,
+        };
+
+        let found = inliers(&h);
+        if best.as_ref().map(|b| found.len() > b.len()).unwrap_or(true) {
+            best = Some(found);
+        }
+    }
+
+    let best = best?;
+    if best.len() < 4 {
+        return None;
+    }
+
+    let points: Vec<_> = best.iter().map(|i| pairs[*i]).collect();
+    let h = Homography::estimate(&points)?;
+    let found = inliers(&h);
+    Some((h, found))
+}
+
+/// How images are projected before they are aligned
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Projection {
+    /// Images are aligned directly, suitable for flat scenes and small rotations
+    #[default]
+    Planar,
+    /// Images are projected onto a cylinder with the given focal length in pixels, suitable for
+    /// panoramas captured by rotating the camera
+    Cylindrical(f64),
+}
+
+/// Bilinear sample of channel `c`, clamping coordinates to the image
+#[inline]
+fn sample<C: Color>(image: &ImageBuf<f64, C>, x: f64, y: f64, c: usize) -> f64 {
+    let max_x = image.width() - 1;
+    let max_y = image.height() - 1;
+    let x = x.clamp(0.0, max_x as f64);
+    let y = y.clamp(0.0, max_y as f64);
+    let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+    let (x1, y1) = ((x0 + 1).min(max_x), (y0 + 1).min(max_y));
+    let (ax, ay) = (x - x0 as f64, y - y0 as f64);
+    let top = image.at(x0, y0)[c] * (1.0 - ax) + image.at(x1, y0)[c] * ax;
+    let bottom = image.at(x0, y1)[c] * (1.0 - ax) + image.at(x1, y1)[c] * ax;
+    top * (1.0 - ay) + bottom * ay
+}
+
+/// An input image converted to normalized values, along with the pixels that contain data
+struct Source<C: Color> {
+    image: ImageBuf<f64, C>,
+    mask: Vec<bool>,
+}
+
+fn project<T: Type, C: Color, I: Image<T, C>>(image: &I, projection: Projection) -> Source<C> {
+    let (width, height) = (image.width(), image.height());
+    let mut input = ImageBuf::new(width, height);
+    image.convert_type(&mut input);
+
+    let focal = match projection {
+        Projection::Planar => {
+            return Source {
+                image: input,
+                mask: vec![true; width * height],
+            }
+        }
+        Projection::Cylindrical(focal) => focal,
+    };
+
+    let (cx, cy) = (width as f64 / 2.0, height as f64 / 2.0);
+    let mut dest: ImageBuf<f64, C> = ImageBuf::new(width, height);
+    let mut mask = vec![false; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            let theta = (x as f64 - cx) / focal;
+            let sx = focal * theta.tan() + cx;
+            let sy = (y as f64 - cy) / theta.cos() + cy;
+            if sx < 0.0 || sy < 0.0 || sx > (width - 1) as f64 || sy > (height - 1) as f64 {
+                continue;
+            }
+
+            mask[y * width + x] = true;
+            let px = dest.at_mut(x, y);
+            for (c, item) in px.iter_mut().enumerate() {
+                *item = sample(&input, sx, sy, c);
+            }
+        }
+    }
+
+    Source { image: dest, mask }
+}
+
+/// Project an image onto a cylinder with the given focal length in pixels, pixels that fall
+/// outside of the source image are set to zero
+pub fn warp_cylindrical<T: Type, C: Color, I: Image<T, C>>(
+    image: &I,
+    focal: f64,
+) -> ImageBuf<T, C> {
+    let source = project(image, Projection::Cylindrical(focal));
+    let mut dest = ImageBuf::new(image.width(), image.height());
+    dest.copy_metadata(image);
+    source.image.convert_type(&mut dest);
+    dest
+}
+
+/// Panorama stitching options
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stitcher {
+    pub projection: Projection,
+    /// Maximum number of features detected in each image
+    pub max_features: usize,
+    /// Nearest neighbor distance ratio used when matching features
+    pub ratio: f64,
+    /// Maximum reprojection error of a RANSAC inlier in pixels
+    pub ransac_threshold: f64,
+    pub ransac_iterations: usize,
+    /// Number of frequency bands used for blending
+    pub bands: usize,
+}
+
+impl Default for Stitcher {
+    fn default() -> Stitcher {
+        Stitcher {
+            projection: Projection::Planar,
+            max_features: 500,
+            ratio: 0.8,
+            ransac_threshold: 3.0,
+            ransac_iterations: 1000,
+            bands: 5,
+        }
+    }
+}
+
+impl Stitcher {
+    fn align<C: Color>(&self, sources: &[Source<C>]) -> Result<Vec<Homography>, Error> {
+        let features: Vec<Features> = sources
+            .iter()
+            .map(|s| detect_masked(&s.image, Some(&s.mask), self.max_features))
+            .collect();
+
+        let mut homographies = vec![Homography::identity()];
+        for i in 1..sources.len() {
+            let (current, previous) = (&features[i], &features[i - 1]);
+            let pairs: Vec<_> = match_features(current, previous, self.ratio)
+                .iter()
+                .map(|m| {
+                    let (a, b) = (&current.keypoints[m.a], &previous.keypoints[m.b]);
+                    ((a.x as f64, a.y as f64), (b.x as f64, b.y as f64))
+                })
+                .collect();
+
+            let h = match ransac_homography(&pairs, self.ransac_threshold, self.ransac_iterations) {
+                Some((h, inliers)) if inliers.len() >= MIN_INLIERS => h,
+                _ => {
+                    return Err(Error::Message(format!(
+                        "Unable to align image {} with image {}",
+                        i,
+                        i - 1
+                    )))
+                }
+            };
+            homographies.push(homographies[i - 1].multiply(&h));
+        }
+        Ok(homographies)
+    }
+
+    /// Estimate the transform from each image to the first image. When a cylindrical
+    /// projection is used the transforms apply to the projected images.
+    pub fn homographies<T: Type, C: Color, I: Image<T, C>>(
+        &self,
+        images: &[I],
+    ) -> Result<Vec<Homography>, Error> {
+        let sources: Vec<_> = images.iter().map(|i| project(i, self.projection)).collect();
+        self.align(&sources)
+    }
+
+    /// Stitch a sequence of overlapping images, each image must overlap the one before it.
+    /// Pixels of the output that are not covered by any image are set to zero.
+    pub fn stitch<T: Type, C: Color, I: Image<T, C>>(
+        &self,
+        images: &[I],
+    ) -> Result<ImageBuf<T, C>, Error> {
+        if images.is_empty() {
+            return Err(Error::Message(String::from("No images to stitch")));
+        }
+
+        let sources: Vec<_> = images.iter().map(|i| project(i, self.projection)).collect();
+        let homographies = self.align(&sources)?;
+
+        // Bounds of the output in the coordinate system of the first image
+        let (mut min_x, mut min_y) = (f64::INFINITY, f64::INFINITY);
+        let (mut max_x, mut max_y) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+        let mut area = 0.0;
+        for (source, h) in sources.iter().zip(&homographies) {
+            let (w, h_) = (source.image.width() as f64, source.image.height() as f64);
+            area += w * h_;
+            for (x, y) in &[
+                (0.0, 0.0),
+                (w - 1.0, 0.0),
+                (0.0, h_ - 1.0),
+                (w - 1.0, h_ - 1.0),
+            ] {
+                let (x, y) = h.apply(*x, *y);
+                min_x = min_x.min(x);
+                min_y = min_y.min(y);
+                max_x = max_x.max(x);
+                max_y = max_y.max(y);
+            }
+        }
+
+        // Allow for rounding errors so that exact alignments do not add a row or column
+        let (ox, oy) = ((min_x + 1e-6).floor(), (min_y + 1e-6).floor());
+        let width = ((max_x - 1e-6).ceil() - ox) as usize + 1;
+        let height = ((max_y - 1e-6).ceil() - oy) as usize + 1;
+        if !(min_x.is_finite() && min_y.is_finite() && max_x.is_finite() && max_y.is_finite())
+            || (width * height) as f64 > MAX_CANVAS_SCALE * area
+        {
+            return Err(Error::Message(String::from(
+                "Image alignment produced a degenerate transform",
+            )));
+        }
+
+        // Warp each image into the output, weighting pixels by their distance from the edge
+        // of the source image
+        let mut warped = Vec::with_capacity(sources.len());
+        let mut weights = Vec::with_capacity(sources.len());
+        for (source, h) in sources.iter().zip(&homographies) {
+            let inverse = h.inverse().ok_or_else(|| {
+                Error::Message(String::from(
+                    "Image alignment produced a singular transform",
+                ))
+            })?;
+            let (w, h) = (source.image.width(), source.image.height());
+            let mut image: ImageBuf<f64, C> = ImageBuf::new(width, height);
+            let mut weight = vec![0.0; width * height];
+            for y in 0..height {
+                for x in 0..width {
+                    let (sx, sy) = inverse.apply(x as f64 + ox, y as f64 + oy);
+                    if !sx.is_finite() || !sy.is_finite() {
+                        continue;
+                    }
+
+                    // Pixels outside of the source are filled by clamping, so that blending
+                    // does not pull in black from beyond the edges
+                    for (c, item) in image.at_mut(x, y).iter_mut().enumerate() {
+                        *item = sample(&source.image, sx, sy, c);
+                    }
+
+                    if sx < 0.0 || sy < 0.0 || sx > (w - 1) as f64 || sy > (h - 1) as f64 {
+                        continue;
+                    }
+
+                    let (i, j) = (sx.round() as usize, sy.round() as usize);
+                    if source.mask[j * w + i] {
+                        weight[y * width + x] = (sx + 1.0)
+                            .min(w as f64 - sx)
+                            .min(sy + 1.0)
+                            .min(h as f64 - sy);
+                    }
+                }
+            }
+            warped.push(image);
+            weights.push(weight);
+        }
+
+        // Each output pixel is assigned to the image with the highest weight, the masks are
+        // then smoothed at each band of the pyramid
+        let mut covered = vec![false; width * height];
+        let mut masks: Vec<Vec<f64>> = vec![vec![0.0; width * height]; sources.len()];
+        for (p, covered) in covered.iter_mut().enumerate() {
+            let best = (0..sources.len())
+                .filter(|i| weights[*i][p] > 0.0)
+                .max_by(|a, b| {
+                    weights[*a][p]
+                        .partial_cmp(&weights[*b][p])
+                        .unwrap_or(core::cmp::Ordering::Equal)
+                });
+            if let Some(i) = best {
+                masks[i][p] = 1.0;
+                *covered = true;
+            }
+        }
+
+        let channels = C::channels();
+        let mut blended: Vec<ImageBuf<f64, C>> = Vec::new();
+        let mut totals: Vec<Vec<f64>> = Vec::new();
+        for (image, mask) in warped.iter().zip(masks) {
+            let mask: ImageBuf<f64, Gray> = ImageBuf::new_from(width, height, mask);
+            let mask = pyramid::gaussian(&mask, self.bands.max(1));
+            let laplacian = pyramid::laplacian(image, self.bands.max(1));
+
+            if blended.is_empty() {
+                blended = laplacian
+                    .iter()
+                    .map(|l| ImageBuf::new(l.width(), l.height()))
+                    .collect();
+                totals = mask.iter().map(|m| vec![0.0; m.data().len()]).collect();
+            }
+
+            for (((dest, total), l), m) in blended
+                .iter_mut()
+                .zip(&mut totals)
+                .zip(&laplacian)
+                .zip(&mask)
+            {
+                for (((d, t), px), w) in dest
+                    .data_mut()
+                    .chunks_mut(channels)
+                    .zip(total.iter_mut())
+                    .zip(l.data().chunks(channels))
+                    .zip(m.data())
+                {
+                    *t += w;
+                    for (d, v) in d.iter_mut().zip(px) {
+                        *d += w * v;
+                    }
+                }
+            }
+        }
+
+        for (level, total) in blended.iter_mut().zip(&totals) {
+            for (px, t) in level.data_mut().chunks_mut(channels).zip(total) {
+                if *t > 1e-12 {
+                    px.iter_mut().for_each(|v| *v /= t);
+                }
+            }
+        }
+
+        let output = pyramid::collapse(&blended);
+        let mut dest = ImageBuf::new(width, height);
+        dest.copy_metadata(&images[0]);
+        for ((d, px), covered) in dest
+            .data_mut()
+            .chunks_mut(channels)
+            .zip(output.data().chunks(channels))
+            .zip(covered)
+        {
+            if covered {
+                for (d, v) in d.iter_mut().zip(px) {
+                    *d = T::from_f(*v);
+                }
+            }
+        }
+        Ok(dest)
+    }
+}
+
+/// Stitch a sequence of overlapping images using the default options
+pub fn stitch<T: Type, C: Color, I: Image<T, C>>(images: &[I]) -> Result<ImageBuf<T, C>, Error> {
+    Stitcher::default().stitch(images)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::stitch::{self, Homography};
+    use crate::{Image, ImageBuf, Rgb};
+
+    fn scene() -> ImageBuf<u8, Rgb> {
+        let hash = |x: usize, y: usize| {
+            let mut h = (x as u64) << 32 | y as u64;
+            h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            ((h ^ (h >> 31)) % 256) as u8
+        };
+        let mut image: ImageBuf<u8, Rgb> = ImageBuf::new(200, 100);
+        image.for_each(|(x, y), px| {
+            let v = hash(x / 4, y / 4);
+            px.copy_from_slice(&[v, 255 - v, (x + y) as u8]);
+        });
+        image.lazy().blur(1.0).eval()
+    }
+
+    #[test]
+    fn test_homography() {
+        let h = Homography([1.1, 0.1, 5.0, -0.05, 0.9, 2.0, 0.001, 0.0005, 1.0]);
+        let pairs: Vec<_> = (0..6)
+            .map(|i| {
+                let p = ((i * 17 % 50) as f64, (i * 31 % 40) as f64);
+                (p, h.apply(p.0, p.1))
+            })
+            .collect();
+        let estimate = Homography::estimate(&pairs).unwrap();
+        for (a, b) in estimate.0.iter().zip(&h.0) {
+            assert!((a - b).abs() < 1e-6);
+        }
+
+        let (x, y) = h.inverse().unwrap().multiply(&h).apply(3.0, 4.0);
+        assert!((x - 3.0).abs() < 1e-9 && (y - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_stitch() {
+        let scene = scene();
+        let images = [scene.crop(0, 0, 120, 100), scene.crop(70, 0, 130, 100)];
+
+        let homographies = stitch::Stitcher::default().homographies(&images).unwrap();
+        let (x, y) = homographies[1].apply(0.0, 0.0);
+        assert!((x - 70.0).abs() < 0.5 && y.abs() < 0.5);
+
+        let panorama = stitch::stitch(&images).unwrap();
+        assert!((199..=201).contains(&panorama.width()));
+        assert!((99..=101).contains(&panorama.height()));
+        for (x, y) in &[(20, 50), (95, 30), (180, 70)] {
+            for c in 0..3 {
+                let a = panorama.at(*x, *y)[c] as i32;
+                let b = scene.at(*x, *y)[c] as i32;
+                assert!((a - b).abs() <= 8, "{} {} {} {}", x, y, a, b);
+            }
+        }
+
+        let empty: [ImageBuf<u8, Rgb>; 0] = [];
+        assert!(stitch::stitch(&empty).is_err());
+    }
+}
@@ -1,12 +1,15 @@
 #![cfg(test)]
 #![cfg(feature = "io")]
 
-use crate::color::{Gray, Rgb};
+use crate::color::{Gray, Rgb, Rgba};
 use crate::filter::{Filter, Invert, ToGrayscale};
-use crate::io::{magick, read, write};
-use crate::kernel::{gaussian_5x5, sobel, Kernel};
-use crate::{Image, ImageBuf, Pixel};
+use crate::io::{decode, encode_png_u8, magick, read, read_into, set_auto_orient, write};
+use crate::kernel::{gaussian_3x3, gaussian_5x5, sobel, Kernel};
+use crate::metadata::exif::Exif;
+use crate::simd;
+use crate::{Error, Image, Image2, ImageBuf, Pixel, Resolution};
 
+use std::path::Path;
 use std::time::Instant;
 
 fn timer<F: FnMut()>(name: &str, mut f: F) {
@@ -37,6 +40,121 @@ fn test_read_write() {
 
     let b: ImageBuf<u8, Rgb> = read("test/test-read-write1.png").unwrap();
     write("test/test-read-write2.png", &b).unwrap();
+
+    let mut c: ImageBuf<u8, Rgb> = ImageBuf::new(1, 1);
+    read_into("test/test-read-write1.png", &mut c).unwrap();
+    assert_eq!(c, b);
+}
+
+#[test]
+fn test_write_hdr() {
+    // Every row is different, so rows written from the wrong offset are detected
+    let image: ImageBuf<f32, Rgb> =
+        ImageBuf::from_fn(16, 4, |x, y| [x as f32 / 16.0, y as f32 / 4.0, 0.5]);
+    crate::io::write_hdr_f32("test/test-write.hdr", &image).unwrap();
+    let hdr: crate::ImagePtr<f32, Rgb> = crate::io::read_f32("test/test-write.hdr").unwrap();
+    assert_eq!(hdr.shape(), image.shape());
+    for (a, b) in hdr.data().iter().zip(image.data()) {
+        assert!((a - b).abs() < 0.01);
+    }
+}
+
+#[test]
+fn test_auto_orient() {
+    let image: ImageBuf<u8, Gray> = ImageBuf::new_from(3, 2, vec![1, 2, 3, 4, 5, 6]);
+    let mut exif = Exif::new();
+    exif.set_orientation(6);
+    let data = exif.encode(&encode_png_u8(&image).unwrap()).unwrap();
+    std::fs::write("test/test-orientation.png", &data).unwrap();
+
+    set_auto_orient(true);
+    let a: ImageBuf<u8, Gray> = read("test/test-orientation.png").unwrap();
+    let b: ImageBuf<u8, Gray> = decode(&data).unwrap();
+    set_auto_orient(false);
+    assert_eq!(a.data(), &[4, 1, 5, 2, 6, 3]);
+    assert_eq!(a, b);
+
+    let c: ImageBuf<u8, Gray> = read("test/test-orientation.png").unwrap();
+    assert_eq!(c, image);
+}
+
+#[test]
+fn test_error() {
+    let err = read::<_, u8, Rgb>("test/missing.jpg").unwrap_err();
+    assert_eq!(err.path(), Some(Path::new("test/missing.jpg")));
+    assert!(matches!(err.inner(), Error::IO(_)));
+    assert!(std::error::Error::source(&err).is_some());
+    assert!(err.to_string().starts_with("test/missing.jpg: "));
+
+    let a: ImageBuf<u8, Rgb> = ImageBuf::new(4, 4);
+    let err = write("test/test-error", &a).unwrap_err();
+    assert_eq!(err.path(), Some(Path::new("test/test-error")));
+}
+
+#[test]
+fn test_resolution() {
+    let mut image: ImageBuf<u8, Rgb> = ImageBuf::new(600, 300);
+    image.set_resolution(Some(Resolution::dpi(300.0)));
+    write("test/test-resolution.png", &image).unwrap();
+    write("test/test-resolution.jpg", &image).unwrap();
+
+    let png: ImageBuf<u8, Rgb> = read("test/test-resolution.png").unwrap();
+    let (width, height) = png.size_in_inches().unwrap();
+    assert!((width - 2.0).abs() < 0.01 && (height - 1.0).abs() < 0.01);
+    let jpg: ImageBuf<u8, Rgb> = read("test/test-resolution.jpg").unwrap();
+    assert_eq!(jpg.resolution(), Some(Resolution::dpi(300.0)));
+    assert_eq!(jpg.size_in_mm(), Some((50.8, 25.4)));
+
+    // Filters keep the resolution of their input
+    let gray: ImageBuf<u8, Gray> = jpg.new_like_with_color();
+    assert_eq!(gray.resolution(), jpg.resolution());
+    let image: ImageBuf<u8, Rgb> = ImageBuf::new(600, 300);
+    assert_eq!(image.size_in_inches(), None);
+}
+
+#[test]
+fn test_profile() {
+    // Only the color space in the header is checked
+    let mut profile = vec![0u8; 512];
+    profile[16..20].copy_from_slice(b"RGB ");
+
+    let mut image: ImageBuf<u8, Rgba> = ImageBuf::new(32, 32);
+    image.set_profile(Some(profile.clone()));
+    write("test/test-profile.jpg", &image).unwrap();
+
+    // The profile is kept through crops, resizes and filters
+    let image: ImageBuf<u8, Rgba> = read("test/test-profile.jpg").unwrap();
+    assert_eq!(image.profile(), Some(profile.as_slice()));
+    let cropped = image.crop(0, 0, 16, 16);
+    let resized = Image2::from(cropped).resize(8, 8).blur().into_image_buf();
+    let inverted = resized.map_pixels(|x| 1.0 - x);
+    write("test/test-profile.png", &inverted).unwrap();
+    let png: ImageBuf<u8, Rgba> = read("test/test-profile.png").unwrap();
+    assert_eq!(png.profile(), Some(profile.as_slice()));
+
+    // Effects and other operations that keep the color do too
+    assert_eq!(
+        crate::effect::sepia(&inverted).profile(),
+        Some(profile.as_slice())
+    );
+    assert_eq!(
+        crate::effect::pixelate(&inverted, 2).profile(),
+        Some(profile.as_slice())
+    );
+    assert_eq!(
+        crate::pyramid::down(&inverted).profile(),
+        Some(profile.as_slice())
+    );
+
+    // Gray images don't keep RGB profiles
+    let gray: ImageBuf<u8, Gray> = read("test/test-profile.png").unwrap();
+    assert_eq!(gray.profile(), None);
+
+    let mut stripped = png;
+    stripped.strip_profile();
+    write("test/test-profile.png", &stripped).unwrap();
+    let png: ImageBuf<u8, Rgba> = read("test/test-profile.png").unwrap();
+    assert_eq!(png.profile(), None);
 }
 
 #[test]
@@ -117,6 +235,90 @@ fn test_diff() {
     write("test/test-diff.png", &image2).unwrap()
 }
 
+#[test]
+fn test_map_pixels() {
+    let a: ImageBuf<f32, Rgb> = read("test/test.jpg").unwrap();
+    let b = a.map_pixels(|x| 1.0 - x);
+    assert_eq!(b.get(10, 10, 1), 1.0 - a.get(10, 10, 1));
+    assert_eq!(a.par_map_pixels(|x| 1.0 - x), b);
+
+    // `b` is stored as f32, so compare with the same expression written in terms of `a` only
+    let expected = a.map_pixels(|x| ((x - (1.0 - x)).abs() * 2.0).min(1.0));
+    let diff = a.zip_map(&b, |a, b| ((a - b).abs() * 2.0).min(1.0));
+    assert!(diff
+        .data()
+        .iter()
+        .zip(expected.data())
+        .all(|(a, b)| (a - b).abs() < 1e-6));
+
+    // Images with a different type
+    let gray: ImageBuf<u8, Gray> = ImageBuf::new(4, 4);
+    let ones = ImageBuf::<f32, Gray>::new(4, 4).map_pixels(|_| 1.0);
+    assert_eq!(gray.zip_map(&ones, |a, b| a + b).get(3, 3, 0), 255);
+    assert_eq!(
+        gray.par_zip_map(&ones, |a, b| a + b),
+        gray.zip_map(&ones, |a, b| a + b)
+    );
+
+    timer("zip_map", || {
+        a.zip_map(&b, |a, b| (a - b).abs() * 2.0);
+    });
+    timer("par_zip_map", || {
+        a.par_zip_map(&b, |a, b| (a - b).abs() * 2.0);
+    });
+}
+
+#[test]
+#[should_panic(expected = "zip_map requires images with the same width and height")]
+fn test_zip_map_size() {
+    // The images have the same number of pixels but different shapes
+    let a: ImageBuf<u8, Gray> = ImageBuf::new(4, 4);
+    let b: ImageBuf<u8, Gray> = ImageBuf::new(2, 8);
+    a.zip_map(&b, |a, b| a + b);
+}
+
+#[test]
+fn test_pixel_map_zip() {
+    let a: ImageBuf<u8, Rgb> = read("test/test.jpg").unwrap();
+
+    let mut sum = 0u64;
+    let mut count = 0;
+    a.for_each_pixel(|(x, y), px| {
+        assert_eq!(px, a.at(x, y));
+        sum += px[1] as u64;
+        count += 1;
+    });
+    assert_eq!(count, a.width() * a.height());
+    assert_eq!(
+        sum,
+        a.data()
+            .iter()
+            .skip(1)
+            .step_by(3)
+            .map(|&x| x as u64)
+            .sum::<u64>()
+    );
+
+    // Keep the brightest channel of each pixel as a grayscale image of a different type
+    let mut max: ImageBuf<f32, Gray> = ImageBuf::new(a.width(), a.height());
+    a.map_to(&mut max, |src, dest| {
+        dest[0] = *src.iter().max().unwrap() as f32 / 255.0
+    });
+    let px = a.at(10, 10);
+    assert_eq!(max.get(10, 10, 0), *px.iter().max().unwrap() as f32 / 255.0);
+
+    // Works with any `Image` implementation, including borrowed images
+    let mut b = Clone::clone(&a);
+    b.for_each(|_, px| px.iter_mut().for_each(|x| *x = 255 - *x));
+    let sum = a.zip_with(&b.as_image_ref(), |a, b, dest| {
+        for c in 0..3 {
+            dest[c] = a[c] + b[c];
+        }
+    });
+    assert!(sum.data().iter().all(|&x| x == 255));
+    assert_eq!(sum.shape(), a.shape());
+}
+
 #[test]
 fn test_colorspace() {
     let image: ImageBuf<u8, Rgb> = read("test/test.jpg").unwrap();
@@ -125,3 +327,71 @@ fn test_colorspace() {
     let rgb = Pixel::<u8, Rgb>::to_rgb(&px);
     println!("{:?}", rgb);
 }
+
+#[test]
+fn test_image2_facade() {
+    let image = crate::Image2::open("test/test.jpg").unwrap();
+    let (width, height) = (image.width(), image.height());
+    let small = image.resize(width / 2, 0).grayscale();
+    assert_eq!(small.width(), width / 2);
+    assert_eq!(small.height(), height / 2);
+    let px = small.as_image_buf().at(10, 10);
+    assert!(px[0] == px[1] && px[1] == px[2]);
+    small.save("test/test-image2.png").unwrap();
+
+    // Degenerate sizes never divide by zero or produce an empty image
+    let wide = Image2::from(ImageBuf::new(100, 1)).resize(10, 0);
+    assert_eq!((wide.width(), wide.height()), (10, 1));
+    let empty = Image2::from(ImageBuf::new(0, 0)).resize(0, 5);
+    assert_eq!((empty.width(), empty.height()), (1, 5));
+}
+
+#[test]
+fn test_simd_benchmark() {
+    let n = 1920 * 1080 * 4;
+    let bytes: Vec<u8> = (0..n).map(|i| (i * 31 % 256) as u8).collect();
+    let mut floats = vec![0.0f32; n];
+    let mut out = vec![0u8; n];
+    let mut rgb = vec![0u8; n / 2 * 3];
+
+    let _lock = simd::test::LEVEL_LOCK
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+    for level in simd::Level::ALL.iter().copied() {
+        if !simd::set_level(level) {
+            continue;
+        }
+
+        let name = |kernel| format!("{} ({:?})", kernel, level);
+        timer(&name("u8 to f32"), || simd::u8_to_f32(&bytes, &mut floats));
+        timer(&name("f32 to u8"), || simd::f32_to_u8(&floats, &mut out));
+        assert_eq!(bytes, out);
+        timer(&name("swap rb"), || simd::swap_rb(&bytes, &mut out));
+        timer(&name("blend rgba"), || simd::blend_rgba(&bytes, &mut out));
+        timer(&name("yuyv to rgb"), || simd::yuyv_to_rgb(&bytes, &mut rgb));
+    }
+    simd::set_level(simd::Level::detect());
+}
+
+#[test]
+fn test_pipeline_tiled_benchmark() {
+    let data = (0..1280 * 720 * 3).map(|i| (i % 251) as u8).collect();
+    let image: ImageBuf<u8, Rgb> = ImageBuf::new_from(1280, 720, data);
+    let pipeline = || {
+        image
+            .lazy()
+            .blur(1.0)
+            .gamma(2.2)
+            .convolve(gaussian_5x5())
+            .contrast(1.2)
+            .convolve(gaussian_3x3())
+    };
+
+    let mut whole = image.new_like();
+    let mut tiled = image.new_like();
+    timer("Pipeline", || pipeline().eval_into(&mut whole));
+    timer("Pipeline (tiled)", || {
+        pipeline().tiled().eval_into(&mut tiled)
+    });
+    assert!(whole == tiled);
+}
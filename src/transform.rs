@@ -1,4 +1,7 @@
-use crate::{Color, Filter, Image, Type};
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::{Color, Filter, Image, ImageBuf, Type};
 use euclid;
 
 pub type Point<T> = euclid::Point2D<T, T>;
@@ -67,6 +70,25 @@ pub fn resize<T: Type, C: Color, I: Image<T, C>, J: Image<T, C>>(
     filter.eval(dest, &[src])
 }
 
+/// Create a resized copy of `src`, when `width` or `height` is 0 it is calculated from the other
+/// to preserve the aspect ratio and when both are 0 the size is kept
+pub fn resized<T: Type, C: Color, I: Image<T, C>>(
+    src: &I,
+    width: usize,
+    height: usize,
+) -> ImageBuf<T, C> {
+    let (width, height) = match (width, height) {
+        (0, 0) => (src.width(), src.height()),
+        (0, h) => ((h * src.width() / src.height().max(1)).max(1), h),
+        (w, 0) => (w, (w * src.height() / src.width().max(1)).max(1)),
+        (w, h) => (w, h),
+    };
+    let mut dest = ImageBuf::new(width, height);
+    dest.copy_metadata(src);
+    resize(&mut dest, src, width, height);
+    dest
+}
+
 pub fn rotate90<T: Type, C: Color, I: Image<T, C>, J: Image<T, C>>(dest: &mut I, src: &J) {
     let dwidth = dest.width() as f64;
     let height = src.height() as f64;
@@ -90,10 +112,23 @@ pub fn rotate270<T: Type, C: Color, I: Image<T, C>, J: Image<T, C>>(dest: &mut I
 mod test {
     use crate::{
         io::magick,
-        transform::{resize, rotate180, rotate90, scale},
+        transform::{resize, resized, rotate180, rotate90, scale},
         Image, ImageBuf, Rgb,
     };
 
+    #[test]
+    fn test_resized() {
+        let a: ImageBuf<u8, Rgb> = ImageBuf::new(40, 20);
+        assert_eq!(resized(&a, 10, 0).shape(), (10, 5, 3));
+        assert_eq!(resized(&a, 0, 10).shape(), (20, 10, 3));
+        assert_eq!(resized(&a, 0, 0).shape(), (40, 20, 3));
+        assert_eq!(resized(&a, 1, 0).shape(), (1, 1, 3));
+        assert_eq!(
+            resized(&ImageBuf::<u8, Rgb>::new(0, 0), 0, 5).shape(),
+            (1, 5, 3)
+        );
+    }
+
     #[test]
     fn test_rotate90() {
         let a: ImageBuf<u8, Rgb> = magick::read("test/test.jpg").unwrap();
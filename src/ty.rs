@@ -1,9 +1,10 @@
+use core::ops::*;
 use num::{FromPrimitive, ToPrimitive, Zero};
-use std::ops::*;
 
 /// Implementing `Type` allows for a type to be used as values contained in an Image
 pub trait Type:
-    ToPrimitive
+    'static
+    + ToPrimitive
     + FromPrimitive
     + Zero
     + Clone
@@ -17,7 +18,7 @@ pub trait Type:
     + Mul<Output = Self>
     + Div<Output = Self>
     + Rem<Output = Self>
-    + std::iter::Sum<Self>
+    + core::iter::Sum<Self>
 {
     /// Minimum value
     fn min_f() -> f64;
@@ -129,6 +130,19 @@ make_type!(i64);
 make_type!(u64);
 make_type!(f64, 0, 1);
 
+/// The numpy name of a type, for example: "uint8"
+#[cfg(any(feature = "dataset", feature = "ipc"))]
+pub(crate) fn dtype_name<T: Type>() -> String {
+    let kind = if T::is_float() {
+        "float"
+    } else if T::is_signed() {
+        "int"
+    } else {
+        "uint"
+    };
+    format!("{}{}", kind, core::mem::size_of::<T>() * 8)
+}
+
 #[cfg(test)]
 mod test {
     use crate::*;